@@ -0,0 +1,165 @@
+use std::collections::BTreeMap; // 使用有序的 BTreeMap，保证账户列表输出顺序稳定
+use std::fs; // 文件读写
+use std::io; // IO 错误类型
+use std::path::{Path, PathBuf}; // 路径处理
+use std::time::{SystemTime, UNIX_EPOCH}; // 记录会话创建时间
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer}; // 账户状态以 JSON 格式持久化
+use zkp_core::ZKP; // 生成设备标识
+
+use crate::kdf::Kdf; // 注册时使用的盐和 KDF 参数
+use crate::zkp_auth::KdfParams; // 保存的 KDF 参数
+
+// 状态文件名，保存在客户端状态目录下
+const ACCOUNTS_FILE: &str = "accounts.json";
+
+// 生成的设备标识的长度
+const DEVICE_ID_LEN: usize = 24;
+
+// 单个账户对应的会话信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub session_id: String, // 服务器在认证成功后返回的会话 ID
+    pub created_at: u64,    // 会话建立时间（Unix 时间戳，秒）
+    #[serde(default)]
+    pub expires_at: Option<u64>, // 服务器返回的会话过期时间（Unix 时间戳，秒），旧服务器不返回时为 None
+    #[serde(default)]
+    pub scopes: Vec<String>, // 会话被授予的权限范围
+}
+
+impl Session {
+    // 以当前时间创建一个新的会话记录，expires_at 为 0 表示服务器没有返回过期时间
+    pub fn new(session_id: String, expires_at: u64, scopes: Vec<String>) -> Self {
+        let expires_at = (expires_at != 0).then_some(expires_at);
+        Session { session_id, created_at: unix_now(), expires_at, scopes }
+    }
+
+    // 会话是否已经过了服务器返回的过期时间
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= unix_now())
+    }
+}
+
+// 当前时间（Unix 时间戳，秒）
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+// 在本机注册（或修改、重置密码）时使用的盐和 KDF 参数，登录时服务器返回的必须与之相同
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedKdf {
+    #[serde(serialize_with = "hex_serialize", deserialize_with = "hex_deserialize")]
+    pub salt: Vec<u8>,     // 盐，在状态文件中为十六进制字符串
+    pub algorithm: String, // KDF 算法名称
+    pub iterations: u32,   // 迭代次数
+}
+
+impl SavedKdf {
+    // 保存的盐和 KDF 参数
+    pub fn kdf(&self) -> Kdf {
+        Kdf::new(self.salt.clone(), Some(KdfParams { algorithm: self.algorithm.clone(), iterations: self.iterations }))
+    }
+}
+
+impl From<&Kdf> for SavedKdf {
+    fn from(kdf: &Kdf) -> Self {
+        let params = kdf.params.clone().unwrap_or_default();
+        SavedKdf { salt: kdf.salt.clone(), algorithm: params.algorithm, iterations: params.iterations }
+    }
+}
+
+fn hex_serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hex::encode(bytes))
+}
+
+fn hex_deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    hex::decode(String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+// 客户端保存的一个身份（账户）。注意：这里从不保存密码，只保存服务器地址、会话和派生私钥时使用的盐和 KDF 参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    pub server: String,           // 该账户注册所在的服务器地址
+    pub session: Option<Session>, // 最近一次登录得到的会话，未登录时为 None
+    #[serde(default)]
+    pub kdf: Option<SavedKdf>, // 在本机注册或修改密码时保存的盐和 KDF 参数，在其他设备上注册的账户和旧的状态文件中为 None
+}
+
+// 所有账户的集合，以及当前激活的账户
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AccountStore {
+    pub active: Option<String>,               // 当前激活账户的用户名
+    pub accounts: BTreeMap<String, Account>,  // 用户名 -> 账户信息
+    #[serde(default)]
+    pub device_id: String, // 本机的设备标识，第一次加载时生成，登录建立的会话绑定到该设备
+    #[serde(skip)]
+    path: PathBuf, // 状态文件路径，不写入文件本身
+}
+
+impl AccountStore {
+    /// 从状态目录加载账户信息，文件不存在时返回空的账户集合
+    ///
+    /// 参数:
+    /// - `dir`: 客户端状态目录
+    ///
+    /// 返回:
+    /// - `io::Result<AccountStore>`: 加载得到的账户集合
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(ACCOUNTS_FILE);
+        let mut store = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice::<AccountStore>(&bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => AccountStore::default(),
+            Err(e) => return Err(e),
+        };
+        if store.device_id.is_empty() {
+            store.device_id = ZKP::generate_random_string(DEVICE_ID_LEN); // 随下一次保存写入状态文件
+        }
+        store.path = path;
+        Ok(store)
+    }
+
+    /// 将账户信息写回状态文件，必要时创建状态目录
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let bytes = serde_json::to_vec_pretty(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(&self.path, bytes)
+    }
+
+    /// 添加账户（已存在时更新服务器地址），返回该账户的可变引用
+    pub fn upsert(&mut self, user: &str, server: &str) -> &mut Account {
+        let account = self.accounts.entry(user.to_string()).or_insert_with(|| Account {
+            server: server.to_string(),
+            session: None,
+            kdf: None,
+        });
+        account.server = server.to_string();
+        account
+    }
+
+    /// 账户保存的盐和 KDF 参数，用于固定登录时服务器返回的参数；没有保存时返回 None
+    pub fn saved_kdf(&self, user: &str) -> Option<Kdf> {
+        self.accounts.get(user).and_then(|account| account.kdf.as_ref()).map(SavedKdf::kdf)
+    }
+
+    /// 切换当前激活账户，账户不存在时返回 false
+    pub fn set_active(&mut self, user: &str) -> bool {
+        if self.accounts.contains_key(user) {
+            self.active = Some(user.to_string());
+            true
+        } else {
+            false
+        }
+    }
+
+    /// 删除本地账户及其会话，删除的是激活账户时同时清除激活状态
+    pub fn remove(&mut self, user: &str) -> bool {
+        let removed = self.accounts.remove(user).is_some();
+        if self.active.as_deref() == Some(user) {
+            self.active = None;
+        }
+        removed
+    }
+}
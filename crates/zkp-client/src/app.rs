@@ -0,0 +1,729 @@
+use std::collections::HashMap; // 请求附带的自定义元数据
+use std::fs; // 读写证明文件
+use std::time::{Duration, Instant}; // 记录注册耗时、流式认证超时
+
+use num_bigint::BigUint; // 离线证明的私钥
+use serde_json::{json, Value}; // JSON 输出
+use tonic::{Code, Status}; // gRPC 错误类型
+
+use zkp_core::{HashAlgorithm, NonInteractiveProof, RandomSource, ZKP}; // Chaum-Pedersen 协议实现
+
+use crate::accounts::{AccountStore, SavedKdf, Session}; // 账户集合、会话记录和保存的 KDF 参数
+use crate::bench; // 压力测试
+use crate::flow::{
+    approve_guardian_recovery, approve_pending_login, change_password, complete_guardian_recovery, create_pending_login, delete_user_data, export_user_data, fetch_parameters,
+    introspect_session, login, login_or_register, logout, recover_account, register, start_guardian_recovery, update_profile, validate_session, wait_pending_login,
+    watch_revocations, Connection, RecoveryOptions,
+}; // 注册、登录和会话管理流程
+use crate::kdf::Kdf; // 新注册和设置新密码时生成盐和 KDF 参数
+use crate::output::{Context, Failure, OutputFormat, Report}; // 命令结果输出
+use crate::qr::{self, LoginTicket}; // 跨设备登录和 TOTP 密钥的二维码
+use crate::totp::TotpSecret; // 注册时启用的 TOTP 第二因素
+use crate::zkp_auth::auth_client::AuthClient; // gRPC 客户端
+use crate::zkp_auth::{Profile, UserDataExport}; // 账户资料、服务器导出的用户数据
+use crate::{prompt, read_password, AccountsCommand, Command, GuardianCommand, QrCommand, DEFAULT_SERVER}; // 命令定义和终端输入
+
+/// 客户端运行时的状态：协议参数、本地账户和已建立的连接
+///
+/// 同一个 `App` 可以连续执行多个命令（交互模式），连接会在命令之间复用
+pub struct App {
+    pub zkp: ZKP,                 // 协议参数
+    pub store: AccountStore,      // 本地账户
+    pub output: OutputFormat,     // 输出格式
+    pub proof_hash: HashAlgorithm, // 注册和离线证明使用的哈希函数
+    pub service: Option<String>,  // 派生服务身份使用的标签
+    pub totp_code: Option<String>, // 登录时提交的 TOTP 验证码
+    pub fetch_params: bool,       // 连接时从服务器取得群参数，替换 zkp
+    pub params_pin: Option<Vec<u8>>, // 从服务器取得的群参数必须具有的指纹
+    pub rng: RandomSource,        // 连接和离线证明使用的随机数来源，默认为系统随机数生成器
+    #[cfg(feature = "tls")]
+    pub tls: Option<tonic::transport::ClientTlsConfig>, // 服务器 CA 和客户端证书，未设置时使用明文连接
+    server: Option<String>,       // 命令行指定的服务器地址
+    prefer_stream: bool,          // 是否优先使用流式认证
+    timeout: Duration,            // 流式认证中等待服务器每条消息的超时时间
+    metadata: HashMap<String, String>, // 请求附带的自定义元数据
+    connection: Option<(String, Connection)>, // 已建立的连接及其服务器地址
+}
+
+impl App {
+    pub fn new(
+        zkp: ZKP,
+        store: AccountStore,
+        output: OutputFormat,
+        server: Option<String>,
+        prefer_stream: bool,
+        timeout: Duration,
+        metadata: HashMap<String, String>,
+    ) -> Self {
+        App {
+            zkp,
+            store,
+            output,
+            proof_hash: HashAlgorithm::Sha256,
+            service: None,
+            totp_code: None,
+            fetch_params: false,
+            params_pin: None,
+            rng: RandomSource::default(),
+            #[cfg(feature = "tls")]
+            tls: None,
+            server,
+            prefer_stream,
+            timeout,
+            metadata,
+            connection: None,
+        }
+    }
+
+    /// 决定命令连接的服务器：命令行参数优先，其次是账户注册时的服务器，最后是默认地址
+    pub fn server_for(&self, user: Option<&str>) -> String {
+        self.server
+            .clone()
+            .or_else(|| user.and_then(|u| self.store.accounts.get(u)).map(|a| a.server.clone()))
+            .unwrap_or_else(|| DEFAULT_SERVER.to_string())
+    }
+
+    // 建立到服务器的 gRPC 通道，配置了 TLS 时出示客户端证书
+    async fn connect(&self, server: &str) -> Result<AuthClient<tonic::transport::Channel>, tonic::transport::Error> {
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.tls {
+            let channel = tonic::transport::Endpoint::from_shared(server.to_string())?.tls_config(tls.clone())?.connect().await?;
+            return Ok(AuthClient::new(channel));
+        }
+        AuthClient::connect(server.to_string()).await
+    }
+
+    /// 获取到指定服务器的连接，已经连接到同一服务器时复用该连接
+    pub async fn client(&mut self, server: &str) -> Result<Connection, Failure> {
+        if let Some((connected, conn)) = &self.connection {
+            if connected == server {
+                return Ok(conn.clone());
+            }
+        }
+        // 创建 gRPC 客户端并连接到服务器
+        let client = self
+            .connect(server)
+            .await
+            .map_err(|e| Failure::from_error("could not connect to server", Status::unavailable(e.to_string()).into()))?;
+        self.output.info("Connected to the server"); // 打印连接成功消息
+        let mut conn = Connection::new(client, self.prefer_stream, self.timeout, self.metadata.clone()).with_proof_hash(self.proof_hash).with_device_id(self.store.device_id.clone()).with_service(self.service.clone()).with_totp_code(self.totp_code.clone()).with_rng(self.rng.clone());
+        if self.fetch_params {
+            // 每个服务器的默认群可能不同，连接到新的服务器时重新取得
+            let (zkp, _) = fetch_parameters(&mut conn, None, self.params_pin.as_deref()).await.context("could not fetch the group parameters")?;
+            self.zkp = zkp;
+        }
+        self.connection = Some((server.to_string(), conn.clone()));
+        Ok(conn)
+    }
+
+    // 取得账户保存的会话，未指定用户时使用当前激活的账户
+    fn stored_session(&self, user: Option<String>) -> Result<(String, String), Failure> {
+        let username = user.or_else(|| self.store.active.clone()).ok_or_else(|| {
+            Failure::new("no active account", Status::failed_precondition("use `accounts use <user>` or pass --user"))
+        })?;
+        let session_id = self
+            .store
+            .accounts
+            .get(&username)
+            .and_then(|account| account.session.as_ref())
+            .map(|session| session.session_id.clone())
+            .ok_or_else(|| Failure::new("no stored session", Status::failed_precondition(format!("{} is not logged in", username))))?;
+        Ok((username, session_id))
+    }
+
+    // 保存本地账户信息
+    fn save(&self) -> Result<(), Failure> {
+        self.store
+            .save()
+            .map_err(|e| Failure::new("could not save the client accounts", Status::internal(e.to_string())))
+    }
+
+    /// 执行一个命令，`None` 表示默认的登录流程（用户不存在时自动注册）
+    pub async fn execute(&mut self, command: Option<Command>) -> Result<Report, Failure> {
+        match command {
+            None => {
+                // 读取用户名和密码登录，用户未注册时先注册（已注册的用户不会被重新注册）
+                let username = prompt("Please provide username: ");
+                let password = read_password("Please provide password: ");
+                let server = self.server_for(Some(&username));
+
+                let mut client = self.client(&server).await?.with_kdf_pin(self.store.saved_kdf(&username));
+                let kdf = Kdf::generate(&self.rng);
+                let outcome = login_or_register(&mut client, &self.zkp, &username, &password, &kdf).await.context("could not log in")?;
+
+                // 保存账户和会话，并设为当前账户；刚注册时同时保存盐和 KDF 参数
+                let account = self.store.upsert(&username, &server);
+                account.session = Some(Session::new(outcome.session_id.clone(), outcome.expires_at, outcome.scopes.clone()));
+                if outcome.register_time.is_some() {
+                    account.kdf = Some(SavedKdf::from(&kdf));
+                }
+                self.store.set_active(&username);
+                self.save()?;
+
+                // 打印成功登录的消息，并显示 session_id
+                Ok(Report::new(format!("You logged in !!! session_id: {}", outcome.session_id), outcome.to_json(&username)))
+            }
+            Some(Command::Register { user, recovery_codes, guardians, guardian_threshold, totp }) => {
+                let username = user.unwrap_or_else(|| prompt("Please provide username: "));
+                let password = read_password("Please provide password: ");
+                let server = self.server_for(None);
+
+                let mut client = self.client(&server).await?;
+                let started = Instant::now();
+                let guardian_threshold = guardian_threshold.unwrap_or(guardians.len() as u32); // 默认需要全部监护人批准
+                let totp = totp.then(|| TotpSecret::generate(&self.rng)); // 启用第二因素时的共享密钥
+                let recovery = RecoveryOptions { codes: recovery_codes, guardians, guardian_threshold, totp_secret: totp.as_ref().map(|secret| secret.0.clone()).unwrap_or_default() };
+                let kdf = Kdf::generate(&self.rng);
+                let response = register(&mut client, &self.zkp, &username, &password, &kdf, &recovery).await.context("could not register")?;
+                let register_time = started.elapsed();
+                let codes = response.into_inner().recovery_codes;
+
+                // 注册成功后保存账户和盐、KDF 参数，并切换为当前账户
+                self.store.upsert(&username, &server).kdf = Some(SavedKdf::from(&kdf));
+                self.store.set_active(&username);
+                self.save()?;
+                // 恢复码只在注册时返回这一次，提示用户离线保存
+                let mut text = format!("Registered {} on {}", username, server);
+                if !codes.is_empty() {
+                    text.push_str("\nRecovery codes (each works once, store them offline):");
+                    for code in &codes {
+                        text.push_str(&format!("\n  {}", code));
+                    }
+                }
+                // TOTP 密钥只在这里显示这一次，用认证器应用扫描二维码或手动输入密钥
+                let totp_uri = totp.map(|secret| secret.uri(&username));
+                if let Some(uri) = &totp_uri {
+                    text.push_str(&format!("\nTOTP enabled, scan this with an authenticator app and pass --totp-code when logging in:\n{}\n{}", qr::render(uri), uri));
+                }
+                Ok(Report::new(
+                    text,
+                    json!({ "user": username, "server": server, "recovery_codes": codes, "totp_uri": totp_uri, "timings_ms": { "register": register_time.as_secs_f64() * 1000.0 } }),
+                ))
+            }
+            Some(Command::Login { user, register_if_missing }) => {
+                // 未指定用户时使用当前激活的账户
+                let username = user.or_else(|| self.store.active.clone()).ok_or_else(|| {
+                    Failure::new("no active account", Status::failed_precondition("use `accounts use <user>` or pass --user"))
+                })?;
+                let server = self.server_for(Some(&username));
+                let password = read_password("Please provide password: ");
+
+                let mut client = self.client(&server).await?.with_kdf_pin(self.store.saved_kdf(&username));
+                let kdf = Kdf::generate(&self.rng);
+                let outcome = if register_if_missing {
+                    login_or_register(&mut client, &self.zkp, &username, &password, &kdf).await
+                } else {
+                    login(&mut client, &self.zkp, &username, &password).await
+                }
+                .context("could not log in")?;
+
+                // 每个账户保存各自的会话，刚注册时同时保存盐和 KDF 参数
+                let account = self.store.upsert(&username, &server);
+                account.session = Some(Session::new(outcome.session_id.clone(), outcome.expires_at, outcome.scopes.clone()));
+                if outcome.register_time.is_some() {
+                    account.kdf = Some(SavedKdf::from(&kdf));
+                }
+                self.store.set_active(&username);
+                self.save()?;
+                Ok(Report::new(
+                    format!("You logged in as {} !!! session_id: {}", username, outcome.session_id),
+                    outcome.to_json(&username),
+                ))
+            }
+            Some(Command::ValidateSession { user }) => {
+                let (username, session_id) = self.stored_session(user)?;
+                let server = self.server_for(Some(&username));
+                let mut conn = self.client(&server).await?;
+                let response = validate_session(&mut conn, &session_id).await.context("could not validate the session")?;
+                if !response.valid {
+                    return Err(Failure::new(
+                        "session is not valid",
+                        Status::unauthenticated(format!("session of {} is no longer valid, log in again", username)),
+                    ));
+                }
+                // 记录服务器返回的最新过期时间
+                if let Some(session) = self.store.accounts.get_mut(&username).and_then(|account| account.session.as_mut()) {
+                    session.expires_at = (response.expires_at != 0).then_some(response.expires_at);
+                }
+                self.save()?;
+                let text = match response.expires_at {
+                    0 => format!("Session of {} is valid", response.user),
+                    expires_at => format!("Session of {} is valid until {}", response.user, expires_at),
+                };
+                Ok(Report::new(
+                    text,
+                    json!({
+                        "user": response.user,
+                        "session_id": session_id,
+                        "valid": true,
+                        "expires_at": (response.expires_at != 0).then_some(response.expires_at),
+                    }),
+                ))
+            }
+            Some(Command::Introspect { user, session_id }) => {
+                let (server, session_id) = match session_id {
+                    Some(session_id) => (self.server_for(None), session_id),
+                    None => {
+                        let (username, session_id) = self.stored_session(user)?;
+                        (self.server_for(Some(&username)), session_id)
+                    }
+                };
+                let mut conn = self.client(&server).await?;
+                let response = introspect_session(&mut conn, &session_id).await.context("could not introspect the session")?;
+                if !response.active {
+                    return Ok(Report::new(format!("Session {} is not active", session_id), json!({ "session_id": session_id, "active": false })));
+                }
+                let profile = response.profile.clone().unwrap_or_default(); // 旧服务器不返回账户资料
+                Ok(Report::new(
+                    format!(
+                        "Session {} is active\nsubject: {}\nissued at: {}\nexpires at: {}\nauth method: {}\nscopes: {}\nmetadata: {:?}\ndisplay name: {}\ncontact: {}",
+                        session_id,
+                        response.subject,
+                        response.issued_at,
+                        response.expires_at,
+                        response.auth_method,
+                        response.scopes.join(" "),
+                        response.metadata,
+                        profile.display_name,
+                        profile.contact
+                    ),
+                    json!({
+                        "session_id": session_id,
+                        "active": true,
+                        "subject": response.subject,
+                        "issued_at": response.issued_at,
+                        "expires_at": response.expires_at,
+                        "auth_method": response.auth_method,
+                        "scopes": response.scopes,
+                        "metadata": response.metadata,
+                        "profile": profile_json(&profile),
+                    }),
+                ))
+            }
+            Some(Command::Params { user }) => {
+                let server = self.server_for(user.as_deref());
+                let mut conn = self.client(&server).await?;
+                let (zkp, response) = fetch_parameters(&mut conn, user.as_deref(), self.params_pin.as_deref()).await.context("could not fetch the group parameters")?;
+                let group = if response.group.is_empty() { "(custom)".to_string() } else { response.group.clone() };
+                let accepted: Vec<String> = response.accepted_params_hashes.iter().map(hex::encode).collect();
+                Ok(Report::new(
+                    format!(
+                        "group: {}\nfingerprint: {}\np: {} bits\nq: {} bits\naccepted groups: {}",
+                        group,
+                        hex::encode(&response.params_hash),
+                        zkp.p.bits(),
+                        zkp.q.bits(),
+                        accepted.join(" ")
+                    ),
+                    json!({
+                        "user": user,
+                        "group": response.group,
+                        "params_hash": hex::encode(&response.params_hash),
+                        "p_bits": zkp.p.bits(),
+                        "q_bits": zkp.q.bits(),
+                        "p": zkp.p.to_str_radix(16),
+                        "q": zkp.q.to_str_radix(16),
+                        "alpha": zkp.alpha.to_str_radix(16),
+                        "beta": zkp.beta.to_str_radix(16),
+                        "accepted_params_hashes": accepted,
+                    }),
+                ))
+            }
+            Some(Command::WatchRevocations) => {
+                let server = self.server_for(None);
+                let mut conn = self.client(&server).await?;
+                let mut revocations = watch_revocations(&mut conn).await.context("could not subscribe to revocations")?;
+                self.output.info("Watching session revocations...");
+
+                // 每个通知单独输出，流结束后再输出最终结果
+                let mut count = 0;
+                while let Some(revoked) = revocations.message().await.context("revocation stream failed")? {
+                    count += 1;
+                    let report = Report::new(
+                        format!("revoked {} of {} at {} ({})", revoked.session_id, revoked.subject, revoked.revoked_at, revoked.reason),
+                        json!({
+                            "session_id": revoked.session_id,
+                            "subject": revoked.subject,
+                            "revoked_at": revoked.revoked_at,
+                            "reason": revoked.reason,
+                        }),
+                    );
+                    self.output.print("revoked", &Ok(report));
+                }
+                Ok(Report::new(format!("Revocation stream closed after {} revocations", count), json!({ "revocations": count })))
+            }
+            Some(Command::Logout { user }) => {
+                let (username, session_id) = self.stored_session(user)?;
+                let server = self.server_for(Some(&username));
+                let mut conn = self.client(&server).await?;
+                // 服务器上已经不存在的会话同样视为注销成功
+                match logout(&mut conn, &session_id).await {
+                    Ok(()) => {}
+                    Err(error) if error.code() == Code::NotFound => {}
+                    Err(error) => return Err(Failure::from_error("could not log out", error)),
+                }
+
+                // 清除本地保存的会话
+                if let Some(account) = self.store.accounts.get_mut(&username) {
+                    account.session = None;
+                }
+                self.save()?;
+                Ok(Report::new(format!("Logged out {}", username), json!({ "user": username, "session_id": session_id })))
+            }
+            Some(Command::ChangePassword { user }) => {
+                let username = user.or_else(|| self.store.active.clone()).ok_or_else(|| {
+                    Failure::new("no active account", Status::failed_precondition("use `accounts use <user>` or pass --user"))
+                })?;
+                let server = self.server_for(Some(&username));
+                let old_password = read_password("Please provide the current password: ");
+                let new_password = read_password("Please provide the new password: ");
+                if read_password("Please repeat the new password: ") != new_password {
+                    return Err(Failure::new("passwords do not match", Status::invalid_argument("the new password was not repeated correctly")));
+                }
+
+                let mut conn = self.client(&server).await?.with_kdf_pin(self.store.saved_kdf(&username));
+                let kdf = Kdf::generate(&self.rng);
+                change_password(&mut conn, &self.zkp, &username, &old_password, &new_password, &kdf)
+                    .await
+                    .context("could not change the password")?;
+
+                // 服务器已注销该用户的所有会话，同时清除本地保存的会话；保存新密码的盐和 KDF 参数
+                let account = self.store.upsert(&username, &server);
+                account.session = None;
+                account.kdf = Some(SavedKdf::from(&kdf));
+                self.save()?;
+                Ok(Report::new(format!("Password of {} changed, please log in again", username), json!({ "user": username })))
+            }
+            Some(Command::UpdateProfile { user, display_name, contact }) => {
+                let username = user.or_else(|| self.store.active.clone()).ok_or_else(|| {
+                    Failure::new("no active account", Status::failed_precondition("use `accounts use <user>` or pass --user"))
+                })?;
+                if display_name.is_none() && contact.is_none() {
+                    return Err(Failure::new("nothing to update", Status::invalid_argument("pass --display-name or --contact")));
+                }
+                let server = self.server_for(Some(&username));
+                let password = read_password("Please provide the password: ");
+
+                let mut conn = self.client(&server).await?.with_kdf_pin(self.store.saved_kdf(&username));
+                let profile = update_profile(&mut conn, &self.zkp, &username, &password, display_name, contact)
+                    .await
+                    .context("could not update the profile")?;
+                Ok(Report::new(
+                    format!("Profile of {} updated\ndisplay name: {}\ncontact: {}", username, profile.display_name, profile.contact),
+                    json!({ "user": username, "profile": profile_json(&profile) }),
+                ))
+            }
+            Some(Command::ExportData { user, out }) => {
+                let username = user.or_else(|| self.store.active.clone()).ok_or_else(|| {
+                    Failure::new("no active account", Status::failed_precondition("use `accounts use <user>` or pass --user"))
+                })?;
+                let server = self.server_for(Some(&username));
+                let password = read_password("Please provide the password: ");
+
+                let mut conn = self.client(&server).await?.with_kdf_pin(self.store.saved_kdf(&username));
+                let data = export_user_data(&mut conn, &self.zkp, &username, &password).await.context("could not export the user data")?;
+                let exported = export_json(&data);
+                match out {
+                    Some(path) => {
+                        let text = serde_json::to_string_pretty(&exported).expect("JSON values always serialize");
+                        fs::write(&path, text).map_err(|e| Failure::new("could not write the export", Status::internal(format!("{}: {}", path.display(), e))))?;
+                        Ok(Report::new(
+                            format!("Exported data of {} to {}", username, path.display()),
+                            json!({ "user": username, "file": path.display().to_string() }),
+                        ))
+                    }
+                    None => Ok(Report::new(serde_json::to_string_pretty(&exported).expect("JSON values always serialize"), exported)),
+                }
+            }
+            Some(Command::DeleteAccount { user, yes }) => {
+                let username = user.or_else(|| self.store.active.clone()).ok_or_else(|| {
+                    Failure::new("no active account", Status::failed_precondition("use `accounts use <user>` or pass --user"))
+                })?;
+                if !yes && prompt(&format!("This permanently deletes {} on the server. Type the username to confirm:", username)) != username {
+                    return Err(Failure::new("deletion not confirmed", Status::cancelled("the username was not repeated")));
+                }
+                let server = self.server_for(Some(&username));
+                let password = read_password("Please provide the password: ");
+
+                let mut conn = self.client(&server).await?.with_kdf_pin(self.store.saved_kdf(&username));
+                let revoked = delete_user_data(&mut conn, &self.zkp, &username, &password).await.context("could not delete the user")?;
+
+                // 服务器上的账户已不存在，同时删除本地账户和会话
+                self.store.remove(&username);
+                self.save()?;
+                Ok(Report::new(
+                    format!("Deleted {} ({} sessions revoked)", username, revoked),
+                    json!({ "user": username, "revoked_sessions": revoked }),
+                ))
+            }
+            Some(Command::Recover { user }) => {
+                let username = user.or_else(|| self.store.active.clone()).ok_or_else(|| {
+                    Failure::new("no active account", Status::failed_precondition("use `accounts use <user>` or pass --user"))
+                })?;
+                let server = self.server_for(Some(&username));
+                let code = read_password("Please provide a recovery code: ");
+                let new_password = read_password("Please provide the new password: ");
+                if read_password("Please repeat the new password: ") != new_password {
+                    return Err(Failure::new("passwords do not match", Status::invalid_argument("the new password was not repeated correctly")));
+                }
+
+                let mut conn = self.client(&server).await?;
+                let kdf = Kdf::generate(&self.rng);
+                recover_account(&mut conn, &self.zkp, &username, String::from_utf8_lossy(&code).trim(), &new_password, &kdf)
+                    .await
+                    .context("could not recover the account")?;
+
+                // 服务器已注销该用户的所有会话，同时清除本地保存的会话；保存新密码的盐和 KDF 参数
+                let account = self.store.upsert(&username, &server);
+                account.session = None;
+                account.kdf = Some(SavedKdf::from(&kdf));
+                self.save()?;
+                Ok(Report::new(format!("Password of {} reset, please log in again", username), json!({ "user": username })))
+            }
+            Some(Command::Accounts(AccountsCommand::List)) => {
+                let mut lines = Vec::new();
+                let mut accounts = Vec::new();
+                for (name, account) in &self.store.accounts {
+                    let marker = if self.store.active.as_deref() == Some(name) { "*" } else { " " };
+                    match &account.session {
+                        Some(session) => {
+                            let expiry = match session.expires_at {
+                                Some(_) if session.is_expired() => ", expired".to_string(),
+                                Some(expires_at) => format!(", expires at {}", expires_at),
+                                None => String::new(),
+                            };
+                            lines.push(format!("{} {}\t{}\tsession: {} (since {}{})", marker, name, account.server, session.session_id, session.created_at, expiry))
+                        }
+                        None => lines.push(format!("{} {}\t{}\tno session", marker, name, account.server)),
+                    }
+                    accounts.push(json!({
+                        "user": name,
+                        "server": account.server,
+                        "session_id": account.session.as_ref().map(|s| &s.session_id),
+                        "session_created_at": account.session.as_ref().map(|s| s.created_at),
+                        "session_expires_at": account.session.as_ref().and_then(|s| s.expires_at),
+                        "session_scopes": account.session.as_ref().map(|s| &s.scopes),
+                    }));
+                }
+                if lines.is_empty() {
+                    lines.push("No accounts".to_string());
+                }
+                Ok(Report::new(lines.join("\n"), json!({ "active": self.store.active, "accounts": accounts })))
+            }
+            Some(Command::Accounts(AccountsCommand::Use { user })) => {
+                if !self.store.set_active(&user) {
+                    return Err(Failure::new("unknown account", Status::not_found(format!("Account {} not found", user))));
+                }
+                self.save()?;
+                Ok(Report::new(format!("Switched to {}", user), json!({ "active": user })))
+            }
+            Some(Command::Accounts(AccountsCommand::Remove { user })) => {
+                if !self.store.remove(&user) {
+                    return Err(Failure::new("unknown account", Status::not_found(format!("Account {} not found", user))));
+                }
+                self.save()?;
+                Ok(Report::new(format!("Removed {}", user), json!({ "user": user })))
+            }
+            Some(Command::Guardian(GuardianCommand::Start { user })) => {
+                let username = user.or_else(|| self.store.active.clone()).ok_or_else(|| {
+                    Failure::new("no active account", Status::failed_precondition("use `accounts use <user>` or pass --user"))
+                })?;
+                let server = self.server_for(Some(&username));
+
+                let mut conn = self.client(&server).await?;
+                let recovery = start_guardian_recovery(&mut conn, &username).await.context("could not start the recovery")?;
+                Ok(Report::new(
+                    format!(
+                        "Recovery {} started for {}: ask {} of {} to approve it before {}",
+                        recovery.recovery_id,
+                        username,
+                        recovery.threshold,
+                        recovery.guardians.join(", "),
+                        recovery.expires_at
+                    ),
+                    json!({
+                        "user": username,
+                        "recovery_id": recovery.recovery_id,
+                        "guardians": recovery.guardians,
+                        "threshold": recovery.threshold,
+                        "expires_at": recovery.expires_at,
+                    }),
+                ))
+            }
+            Some(Command::Guardian(GuardianCommand::Approve { recovery_id, user })) => {
+                let guardian = user.or_else(|| self.store.active.clone()).ok_or_else(|| {
+                    Failure::new("no active account", Status::failed_precondition("use `accounts use <user>` or pass --user"))
+                })?;
+                let server = self.server_for(Some(&guardian));
+                let password = read_password(&format!("Please provide the password of {}: ", guardian));
+
+                let mut conn = self.client(&server).await?.with_kdf_pin(self.store.saved_kdf(&guardian));
+                let approved = approve_guardian_recovery(&mut conn, &self.zkp, &guardian, &password, &recovery_id)
+                    .await
+                    .context("could not approve the recovery")?;
+                Ok(Report::new(
+                    format!("Approved recovery {} ({} of {} approvals)", recovery_id, approved.approvals, approved.threshold),
+                    json!({ "guardian": guardian, "recovery_id": recovery_id, "approvals": approved.approvals, "threshold": approved.threshold }),
+                ))
+            }
+            Some(Command::Guardian(GuardianCommand::Complete { recovery_id, user })) => {
+                let username = user.or_else(|| self.store.active.clone()).ok_or_else(|| {
+                    Failure::new("no active account", Status::failed_precondition("use `accounts use <user>` or pass --user"))
+                })?;
+                let server = self.server_for(Some(&username));
+                let new_password = read_password("Please provide the new password: ");
+                if read_password("Please repeat the new password: ") != new_password {
+                    return Err(Failure::new("passwords do not match", Status::invalid_argument("the new password was not repeated correctly")));
+                }
+
+                let mut conn = self.client(&server).await?;
+                let kdf = Kdf::generate(&self.rng);
+                complete_guardian_recovery(&mut conn, &self.zkp, &username, &recovery_id, &new_password, &kdf)
+                    .await
+                    .context("could not complete the recovery")?;
+
+                // 服务器已注销该用户的所有会话，同时清除本地保存的会话；保存新密码的盐和 KDF 参数
+                let account = self.store.upsert(&username, &server);
+                account.session = None;
+                account.kdf = Some(SavedKdf::from(&kdf));
+                self.save()?;
+                Ok(Report::new(format!("Password of {} reset, please log in again", username), json!({ "user": username, "recovery_id": recovery_id })))
+            }
+            Some(Command::Qr(QrCommand::Show { user, wait })) => {
+                let username = user.or_else(|| self.store.active.clone()).ok_or_else(|| {
+                    Failure::new("no active account", Status::failed_precondition("use `accounts use <user>` or pass --user"))
+                })?;
+                let server = self.server_for(Some(&username));
+
+                let mut conn = self.client(&server).await?;
+                let pending = create_pending_login(&mut conn, &username).await.context("could not create the pending login")?;
+                let ticket = LoginTicket { server: server.clone(), user: username.clone(), pending_id: pending.pending_id, nonce: pending.nonce };
+
+                // 二维码输出到 stderr，保证 JSON 模式下 stdout 上只有命令结果
+                eprintln!("{}", ticket.render());
+                eprintln!("Scan this code on a device that knows the password of {} and run `client qr approve`,", username);
+                eprintln!("or paste this text there: {}", ticket.encode());
+                eprintln!("Waiting up to {}s for approval...", wait);
+
+                let approved = wait_pending_login(&mut conn, &ticket.pending_id, &ticket.nonce, Duration::from_secs(wait))
+                    .await
+                    .context("could not log in")?;
+
+                // 保存账户和会话，并设为当前账户
+                let (session_id, expires_at) = (approved.session_id, approved.expires_at);
+                self.store.upsert(&username, &server).session = Some(Session::new(session_id.clone(), expires_at, approved.scopes.clone()));
+                self.store.set_active(&username);
+                self.save()?;
+                Ok(Report::new(
+                    format!("You logged in as {} !!! session_id: {}", username, session_id),
+                    json!({
+                        "user": username,
+                        "session_id": session_id,
+                        "expires_at": (expires_at != 0).then_some(expires_at),
+                        "scopes": approved.scopes,
+                        "transport": "qr",
+                    }),
+                ))
+            }
+            Some(Command::Qr(QrCommand::Approve { payload })) => {
+                let payload = payload.unwrap_or_else(|| prompt("Please paste the text of the login QR code: "));
+                let ticket = LoginTicket::decode(&payload)?;
+                // 二维码中已经包含服务器地址，--server 只用于覆盖
+                let server = self.server.clone().unwrap_or_else(|| ticket.server.clone());
+                let password = read_password(&format!("Please provide the password of {}: ", ticket.user));
+
+                let mut conn = self.client(&server).await?.with_kdf_pin(self.store.saved_kdf(&ticket.user));
+                approve_pending_login(&mut conn, &self.zkp, &ticket.user, &password, &ticket.pending_id, &ticket.nonce)
+                    .await
+                    .context("could not approve the login")?;
+                Ok(Report::new(
+                    format!("Approved the login of {} on the other device", ticket.user),
+                    json!({ "user": ticket.user, "pending_id": ticket.pending_id }),
+                ))
+            }
+            Some(Command::Bench { users, concurrency, iterations }) => {
+                let server = self.server_for(None);
+                let client = self.client(&server).await?;
+                let report = bench::run(client, &self.zkp, users, concurrency, iterations).await;
+                let fields = serde_json::to_value(&report).expect("bench report is serializable");
+                Ok(Report::new(report.to_string(), fields))
+            }
+            Some(Command::Prove { context, out }) => {
+                let password = read_password("Please provide password: ");
+                let proof = self.zkp.prove_non_interactive_with_rng(self.proof_hash, &BigUint::from_bytes_be(&password), context.as_bytes(), &self.rng);
+                let bytes = proof.to_bytes();
+                fs::write(&out, &bytes)
+                    .map_err(|e| Failure::new("could not write the proof file", Status::internal(e.to_string())))?;
+                Ok(Report::new(
+                    format!("Proof for context {:?} written to {}", context, out.display()),
+                    json!({ "file": out, "context": context, "bytes": bytes.len(), "hash": proof.hash.name(), "y1": proof.y1.to_str_radix(16) }),
+                ))
+            }
+            Some(Command::Verify { file, context }) => {
+                let bytes = fs::read(&file)
+                    .map_err(|e| Failure::new("could not read the proof file", Status::not_found(e.to_string())))?;
+                let proof = NonInteractiveProof::from_bytes(&bytes)
+                    .ok_or_else(|| Failure::new("malformed proof file", Status::invalid_argument(file.display().to_string())))?;
+                let proof_context = String::from_utf8_lossy(&proof.context).into_owned();
+                if let Some(expected) = context {
+                    if expected.as_bytes() != proof.context.as_slice() {
+                        return Err(Failure::new(
+                            "context mismatch",
+                            Status::invalid_argument(format!("proof is bound to {:?}, expected {:?}", proof_context, expected)),
+                        ));
+                    }
+                }
+                if !self.zkp.verify_non_interactive(&proof) {
+                    return Err(Failure::new("proof rejected", Status::permission_denied(format!("{} does not verify", file.display()))));
+                }
+                Ok(Report::new(
+                    format!("Proof in {} is valid for context {:?}", file.display(), proof_context),
+                    json!({ "file": file, "context": proof_context, "valid": true, "y1": proof.y1.to_str_radix(16) }),
+                ))
+            }
+            Some(Command::Shell) => Err(Failure::new(
+                "already in the interactive shell",
+                Status::failed_precondition("nested shells are not supported"),
+            )),
+        }
+    }
+}
+
+// 导出数据的 JSON 形式，字节字段为十六进制
+fn export_json(data: &UserDataExport) -> Value {
+    let sessions: Vec<Value> = data
+        .sessions
+        .iter()
+        .map(|session| {
+            json!({
+                "issued_at": session.issued_at,
+                "expires_at": session.expires_at,
+                "auth_method": session.auth_method,
+                "scopes": session.scopes,
+                "metadata": session.metadata,
+                "device_id": session.device_id,
+            })
+        })
+        .collect();
+    json!({
+        "user": data.user,
+        "y1": hex::encode(&data.y1),
+        "y2": hex::encode(&data.y2),
+        "salt": hex::encode(&data.salt),
+        "kdf": data.kdf.as_ref().map(|kdf| json!({ "algorithm": kdf.algorithm, "iterations": kdf.iterations })),
+        "scopes": data.scopes,
+        "metadata": data.metadata,
+        "sessions": sessions,
+        "pending_challenges": data.pending_challenges,
+        "pending_logins": data.pending_logins,
+        "exported_at": data.exported_at,
+        "profile": data.profile.as_ref().map(profile_json),
+        "recovery_codes_remaining": data.recovery_codes_remaining,
+        "credential_reset_required": data.credential_reset_required,
+    })
+}
+
+// 账户资料的 JSON 形式
+fn profile_json(profile: &Profile) -> Value {
+    json!({ "display_name": profile.display_name, "contact": profile.contact, "created_at": profile.created_at })
+}
@@ -0,0 +1,178 @@
+use std::fmt; // 文本输出
+use std::sync::atomic::{AtomicUsize, Ordering}; // 工作任务之间分配登录任务的计数器
+use std::sync::Arc; // 在多个任务之间共享数据
+use std::time::{Duration, Instant}; // 计时
+
+use serde::Serialize; // JSON 输出
+
+use zkp_core::ZKP; // Chaum-Pedersen 协议实现
+
+use crate::error::ClientError; // 登录和注册的错误
+use crate::flow::{login, register, Connection, RecoveryOptions}; // 复用交互模式下的注册和登录流程
+use crate::kdf::Kdf; // 临时用户的私钥已经是随机数，不需要 KDF
+
+// 单个工作任务的统计结果
+#[derive(Default)]
+struct WorkerStats {
+    latencies: Vec<Duration>, // 每次成功登录的耗时
+    errors: usize,            // 失败的登录次数
+}
+
+/// 压力测试的统计结果
+#[derive(Serialize)]
+pub struct BenchReport {
+    pub users_registered: usize, // 注册成功的临时用户数
+    pub register_errors: usize,  // 注册失败次数
+    pub register_ms: f64,        // 注册阶段总耗时
+    pub logins_total: usize,     // 计划执行的登录次数
+    pub logins_ok: usize,        // 成功的登录次数
+    pub login_errors: usize,     // 失败的登录次数
+    pub elapsed_ms: f64,         // 登录阶段总耗时
+    pub throughput: f64,         // 每秒成功登录次数
+    pub latency_ms: LatencyReport, // 单次登录延迟分布
+}
+
+/// 单次登录的延迟分布（毫秒）
+#[derive(Serialize)]
+pub struct LatencyReport {
+    pub min: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Registered {} users in {:.2}ms ({} errors)", self.users_registered, self.register_ms, self.register_errors)?;
+        writeln!(f, "Logins: {} total, {} ok, {} errors in {:.2}ms", self.logins_total, self.logins_ok, self.login_errors, self.elapsed_ms)?;
+        writeln!(f, "Throughput: {:.2} logins/s", self.throughput)?;
+        write!(
+            f,
+            "Latency: min {:.2}ms, mean {:.2}ms, p50 {:.2}ms, p99 {:.2}ms, max {:.2}ms",
+            self.latency_ms.min, self.latency_ms.mean, self.latency_ms.p50, self.latency_ms.p99, self.latency_ms.max,
+        )
+    }
+}
+
+/// 压力测试：注册 `users` 个临时用户，再以 `concurrency` 个并发任务
+/// 为每个用户执行 `iterations` 次完整的登录流程，返回吞吐量、延迟和错误数
+///
+/// 参数:
+/// - `conn`: 已建立的连接，各任务克隆后共享同一个 HTTP/2 通道
+/// - `zkp`: 协议参数
+/// - `users`: 临时用户数量
+/// - `concurrency`: 并发任务数
+/// - `iterations`: 每个用户的登录次数
+pub async fn run(conn: Connection, zkp: &ZKP, users: usize, concurrency: usize, iterations: usize) -> BenchReport {
+    let zkp = Arc::new(zkp.clone());
+    let conn = conn.with_kdf_pin(Some(Kdf::none())); // 登录时也不使用 KDF，服务器返回的空盐不会被拒绝
+    let prefix = format!("bench-{}", ZKP::generate_random_string(8)); // 随机前缀，避免与已有用户冲突
+
+    // 第一阶段：注册临时用户，每个用户使用随机私钥
+    let started = Instant::now();
+    let mut identities: Vec<(String, Vec<u8>)> = Vec::with_capacity(users);
+    let mut register_errors = 0;
+    for i in 0..users {
+        let user = format!("{}-{}", prefix, i);
+        let x = ZKP::generate_random_number_below(&zkp.q).to_bytes_be();
+        match register(&mut conn.clone(), &zkp, &user, &x, &Kdf::none(), &RecoveryOptions::default()).await {
+            Ok(_) => identities.push((user, x)),
+            Err(error) => {
+                register_errors += 1;
+                eprintln!("register {} failed: {}", user, error);
+                backoff(&error).await;
+            }
+        }
+    }
+    let register_time = started.elapsed();
+
+    // 第二阶段：并发登录，每个任务从共享计数器领取下一个登录任务
+    let identities = Arc::new(identities);
+    let total = identities.len() * iterations;
+    let next = Arc::new(AtomicUsize::new(0));
+    let started = Instant::now();
+
+    let mut workers = Vec::with_capacity(concurrency);
+    for _ in 0..concurrency.max(1) {
+        let mut conn = conn.clone();
+        let zkp = zkp.clone();
+        let identities = identities.clone();
+        let next = next.clone();
+        workers.push(tokio::spawn(async move {
+            let mut stats = WorkerStats::default();
+            loop {
+                let job = next.fetch_add(1, Ordering::Relaxed);
+                if job >= total {
+                    break;
+                }
+                let (user, x) = &identities[job % identities.len()];
+                let begin = Instant::now();
+                match login(&mut conn, &zkp, user, x).await {
+                    Ok(_) => stats.latencies.push(begin.elapsed()),
+                    Err(error) => {
+                        stats.errors += 1;
+                        eprintln!("login {} failed: {}", user, error);
+                        backoff(&error).await;
+                    }
+                }
+            }
+            stats
+        }));
+    }
+
+    // 汇总各任务的统计结果
+    let mut latencies = Vec::with_capacity(total);
+    let mut errors = 0;
+    for worker in workers {
+        let stats = worker.await.expect("bench worker panicked");
+        latencies.extend(stats.latencies);
+        errors += stats.errors;
+    }
+    let elapsed = started.elapsed();
+    latencies.sort();
+
+    let mean = if latencies.is_empty() {
+        Duration::ZERO
+    } else {
+        latencies.iter().sum::<Duration>() / latencies.len() as u32
+    };
+    BenchReport {
+        users_registered: identities.len(),
+        register_errors,
+        register_ms: millis(register_time),
+        logins_total: total,
+        logins_ok: latencies.len(),
+        login_errors: errors,
+        elapsed_ms: millis(elapsed),
+        throughput: latencies.len() as f64 / elapsed.as_secs_f64(),
+        latency_ms: LatencyReport {
+            min: millis(latencies.first().copied().unwrap_or_default()),
+            mean: millis(mean),
+            p50: millis(percentile(&latencies, 50)),
+            p99: millis(percentile(&latencies, 99)),
+            max: millis(latencies.last().copied().unwrap_or_default()),
+        },
+    }
+}
+
+// 服务器限流或锁定账户时，按建议的时间等待后再发送下一个请求，而不是继续压测被拒绝的端点
+async fn backoff(error: &ClientError) {
+    if let Some(after) = error.retry_after() {
+        tokio::time::sleep(after).await;
+    }
+}
+
+// 从已排序的延迟列表中取百分位数，列表为空时返回 0
+fn percentile(sorted: &[Duration], pct: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let index = (sorted.len() * pct / 100).min(sorted.len() - 1);
+    sorted[index]
+}
+
+// 转为毫秒（保留小数）
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,113 @@
+use std::fmt; // 错误信息的输出格式
+use std::time::Duration; // 服务器建议的重试等待时间
+
+use tonic::{Code, Status}; // gRPC 错误类型
+
+use zkp_proto::detail; // 错误的结构化详情
+use zkp_proto::retry; // 限流和账户锁定时的重试提示
+use zkp_proto::zkp_auth::ErrorCode; // 详情中的错误分类
+
+// 进程退出码：1 为本地错误（参数、账户、文件），clap 参数错误为 2，以下为协议流程的错误
+pub const EXIT_FAILURE: i32 = 1; // 本地错误
+pub const EXIT_TRANSPORT: i32 = 3; // 无法连接服务器或 RPC 调用失败
+pub const EXIT_REGISTRATION_FAILED: i32 = 4; // 服务器拒绝注册
+pub const EXIT_CHALLENGE_EXPIRED: i32 = 5; // 挑战在应答前过期
+pub const EXIT_PROOF_REJECTED: i32 = 6; // 服务器拒绝了证明
+pub const EXIT_INVALID_SERVER_DATA: i32 = 7; // 服务器返回的数据无法使用
+
+/// 注册、登录和会话管理流程的错误
+///
+/// gRPC 错误装箱保存，使 `Result<T, ClientError>` 保持较小的体积
+#[derive(Debug)]
+pub enum ClientError {
+    /// 无法连接服务器，或 RPC 调用返回了其他分类之外的错误（例如用户不存在）
+    Transport(Box<Status>),
+    /// 服务器拒绝了注册请求
+    RegistrationFailed(Box<Status>),
+    /// 挑战在应答送达之前过期，由客户端发现或由服务器返回
+    ChallengeExpired(String),
+    /// 服务器拒绝了对挑战的应答（私钥错误或挑战不匹配）
+    ProofRejected(Box<Status>),
+    /// 服务器返回的数据无法使用：挑战为空、KDF 参数不受支持、流上的消息顺序错误
+    InvalidServerData(String),
+}
+
+impl ClientError {
+    // 注册请求的错误：连接层面的错误仍归为 Transport，其余为服务器拒绝注册
+    pub(crate) fn registration(status: Status) -> Self {
+        match status.code() {
+            Code::Unavailable | Code::Cancelled | Code::Unknown | Code::DeadlineExceeded => ClientError::Transport(Box::new(status)),
+            _ => ClientError::RegistrationFailed(Box::new(status)),
+        }
+    }
+
+    // 提交应答的错误：按错误详情的分类区分证明被拒绝和挑战已过期；没有详情的旧服务器按错误码区分，
+    // 此时 DeadlineExceeded 也可能是客户端自己的截止时间
+    pub(crate) fn answer(status: Status) -> Self {
+        match (detail::error_code(&status), status.code()) {
+            (ErrorCode::BadProof, _) | (ErrorCode::Unspecified, Code::PermissionDenied) => ClientError::ProofRejected(Box::new(status)),
+            (ErrorCode::ChallengeExpired, _) | (ErrorCode::Unspecified, Code::DeadlineExceeded) => ClientError::ChallengeExpired(status.message().to_string()),
+            _ => ClientError::Transport(Box::new(status)),
+        }
+    }
+
+    /// 对应的 gRPC 错误码，JSON 输出和调用方按错误码区分处理时使用
+    pub fn code(&self) -> Code {
+        match self {
+            ClientError::Transport(status) | ClientError::RegistrationFailed(status) | ClientError::ProofRejected(status) => status.code(),
+            ClientError::ChallengeExpired(_) => Code::DeadlineExceeded,
+            ClientError::InvalidServerData(_) => Code::Internal,
+        }
+    }
+
+    /// 错误信息
+    pub fn message(&self) -> &str {
+        match self {
+            ClientError::Transport(status) | ClientError::RegistrationFailed(status) | ClientError::ProofRejected(status) => status.message(),
+            ClientError::ChallengeExpired(message) | ClientError::InvalidServerData(message) => message,
+        }
+    }
+
+    /// 服务器在限流或锁定账户时建议的等待时间，没有时为 None
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ClientError::Transport(status) | ClientError::RegistrationFailed(status) | ClientError::ProofRejected(status) => retry::retry_after(status),
+            ClientError::ChallengeExpired(_) | ClientError::InvalidServerData(_) => None,
+        }
+    }
+
+    /// 命令因该错误失败时的进程退出码
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ClientError::Transport(_) => EXIT_TRANSPORT,
+            ClientError::RegistrationFailed(_) => EXIT_REGISTRATION_FAILED,
+            ClientError::ChallengeExpired(_) => EXIT_CHALLENGE_EXPIRED,
+            ClientError::ProofRejected(_) => EXIT_PROOF_REJECTED,
+            ClientError::InvalidServerData(_) => EXIT_INVALID_SERVER_DATA,
+        }
+    }
+
+    /// 转为 gRPC 错误，保留错误码和错误信息；客户端发现的挑战过期与服务器返回的一样带有 `ErrorCode::ChallengeExpired`
+    pub fn into_status(self) -> Status {
+        match self {
+            ClientError::Transport(status) | ClientError::RegistrationFailed(status) | ClientError::ProofRejected(status) => *status,
+            ClientError::ChallengeExpired(message) => detail::error(Code::DeadlineExceeded, ErrorCode::ChallengeExpired, message),
+            error => Status::new(error.code(), error.message()),
+        }
+    }
+}
+
+// 其他 RPC 调用的错误直接归为 Transport
+impl From<Status> for ClientError {
+    fn from(status: Status) -> Self {
+        ClientError::Transport(Box::new(status))
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for ClientError {}
@@ -0,0 +1,958 @@
+use std::collections::HashMap; // 请求附带的自定义元数据
+use std::fmt; // 协议值的输出格式
+use std::sync::atomic::{AtomicBool, Ordering}; // 是否输出协议值的全局开关、服务器是否支持流式认证
+use std::sync::Arc; // 连接的多个克隆共享流式认证的支持状态
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH}; // 记录每个协议步骤的耗时、检查挑战是否过期
+
+use num_bigint::BigUint; // 引入 num_bigint 库中的 BigUint 类型，用于处理大整数
+use serde_json::{json, Value}; // JSON 输出
+use tokio::sync::mpsc; // 流式认证的请求通道
+use tokio_stream::wrappers::ReceiverStream; // 将请求通道包装为请求流
+use tonic::{transport::Channel, Code, Request, Response, Status, Streaming}; // gRPC 客户端使用的传输通道、响应与错误类型
+use tracing::{debug, info, info_span, trace, Instrument, Span}; // 协议步骤的跟踪输出，每个操作一个带有关联 ID 的 span
+
+use crate::error::ClientError; // 协议流程的错误
+use crate::kdf::Kdf; // 由密码派生私钥
+
+// 引入 gRPC 客户端和认证/注册请求消息类型
+use crate::zkp_auth::{
+    auth_client::AuthClient, authenticate_request, ApproveGuardianRecoveryRequest, ApproveGuardianRecoveryResponse, CompleteGuardianRecoveryRequest, authenticate_response, AuthenticateRequest, AuthenticateResponse,
+    ApprovePendingLoginRequest, AuthenticationAnswerRequest, AuthenticationChallengeRequest, AuthenticationChallengeResponse, ChangePasswordRequest,
+    CreatePendingLoginRequest, CreatePendingLoginResponse, DeleteUserDataRequest, ExportUserDataRequest, GetAuthParametersRequest, GetAuthParametersResponse, IntrospectSessionRequest, IntrospectSessionResponse, LogoutRequest, PollPendingLoginRequest, PollPendingLoginResponse, RecoverAccountRequest, RegisterRequest, RegisterResponse, ResetCredentialsRequest, RevokedSession, StartGuardianRecoveryRequest, StartGuardianRecoveryResponse,
+    Profile, UpdateProfileRequest, UserDataExport, ValidateSessionRequest, ValidateSessionResponse, WatchRevocationsRequest,
+};
+use zkp_core::{registration_context, HashAlgorithm, NonInteractiveProof, RandomSource, Scalar, ZKP}; // 引入实现 Chaum-Pedersen 零知识证明协议的库 ZKP、指数类型，以及注册持有证明的上下文、证明和哈希函数
+
+use zkp_proto::CORRELATION_ID_HEADER; // 请求关联 ID 所在的元数据键
+
+// 客户端生成的关联 ID 的长度
+const CORRELATION_ID_LEN: usize = 16;
+
+// 是否在跟踪输出中打印协议值（y1、y2、r1、r2、c、s），默认只打印位数
+static DUMP_VALUES: AtomicBool = AtomicBool::new(false);
+
+// 打开或关闭协议值的输出（--unsafe-dump-values）
+pub fn set_dump_values(dump: bool) {
+    DUMP_VALUES.store(dump, Ordering::Relaxed);
+}
+
+// 跟踪输出中的协议值：默认脱敏为 <redacted, N bits>，打开开关后输出十六进制
+struct Shown<'a>(&'a BigUint);
+
+impl fmt::Display for Shown<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if DUMP_VALUES.load(Ordering::Relaxed) {
+            write!(f, "{}", self.0.to_str_radix(16))
+        } else {
+            write!(f, "<redacted, {} bits>", self.0.bits())
+        }
+    }
+}
+
+/// 到服务器的连接：gRPC 客户端，以及服务器是否支持双向流认证
+///
+/// 克隆的连接共享同一个 HTTP/2 通道和流式认证的支持状态
+#[derive(Clone)]
+pub struct Connection {
+    pub client: AuthClient<Channel>, // gRPC 客户端
+    streaming: Arc<AtomicBool>,      // 是否尝试流式认证，服务器返回 Unimplemented 后关闭
+    timeout: Duration,               // 流式认证中等待服务器每条消息的超时时间
+    metadata: Arc<HashMap<String, String>>, // 注册、挑战和应答请求附带的自定义元数据
+    proof_hash: HashAlgorithm,       // 注册时持有证明使用的哈希函数
+    device_id: String,               // 本机的设备标识，登录建立的会话绑定到该设备
+    service: Option<String>,         // 派生服务身份使用的标签，为空时直接使用密码派生的私钥
+    totp_code: String,               // 随应答和批准请求发送的 TOTP 验证码，账户没有启用第二因素时为空
+    kdf_pin: Option<Kdf>,            // 本地保存的盐和 KDF 参数，设置时服务器返回的必须与之相同
+    correlation_id: String,          // 当前操作的关联 ID，随该操作的每个 RPC 发送
+    rng: RandomSource,               // 生成 k、盐和持有证明等随机值的来源
+    #[cfg(feature = "otel")]
+    operation: Span,                 // 当前操作的 span，它的 trace 上下文随该操作的每个 RPC 发送
+}
+
+impl Connection {
+    /// 参数:
+    /// - `client`: 已连接的 gRPC 客户端
+    /// - `prefer_stream`: 是否优先使用流式认证
+    /// - `timeout`: 流式认证中等待服务器每条消息的超时时间
+    /// - `metadata`: 注册、挑战和应答请求附带的自定义元数据
+    pub fn new(client: AuthClient<Channel>, prefer_stream: bool, timeout: Duration, metadata: HashMap<String, String>) -> Self {
+        Connection {
+            client,
+            streaming: Arc::new(AtomicBool::new(prefer_stream)),
+            timeout,
+            metadata: Arc::new(metadata),
+            proof_hash: HashAlgorithm::Sha256,
+            device_id: String::new(),
+            service: None,
+            totp_code: String::new(),
+            kdf_pin: None,
+            correlation_id: String::new(),
+            rng: RandomSource::default(),
+            #[cfg(feature = "otel")]
+            operation: Span::none(),
+        }
+    }
+
+    /// 设置注册时持有证明使用的哈希函数，默认为 SHA-256
+    pub fn with_proof_hash(mut self, proof_hash: HashAlgorithm) -> Self {
+        self.proof_hash = proof_hash;
+        self
+    }
+
+    /// 设置本机的设备标识，随挑战、应答和会话查询请求发送；默认为空，会话不绑定设备
+    pub fn with_device_id(mut self, device_id: String) -> Self {
+        self.device_id = device_id;
+        self
+    }
+
+    /// 设置派生服务身份使用的标签：私钥为 `derive_child_secret(x, label)`，同一账户的注册和登录必须一致
+    pub fn with_service(mut self, service: Option<String>) -> Self {
+        self.service = service;
+        self
+    }
+
+    /// 设置登录时提交的 TOTP 验证码，账户启用了第二因素时服务器要求同时提交；验证码只能使用一次
+    pub fn with_totp_code(mut self, totp_code: Option<String>) -> Self {
+        self.totp_code = totp_code.unwrap_or_default();
+        self
+    }
+
+    /// 设置注册（或修改、重置密码）时保存的盐和 KDF 参数：挑战中服务器返回的不同时拒绝应答，私钥用保存的参数派生；
+    /// 默认不设置，只检查服务器返回的参数本身（有盐、迭代次数不少于 `MIN_ITERATIONS`）
+    pub fn with_kdf_pin(mut self, kdf: Option<Kdf>) -> Self {
+        self.kdf_pin = kdf;
+        self
+    }
+
+    /// 设置生成 k、盐和持有证明等随机值的来源，默认为系统随机数生成器；seeded-rng feature 下固定种子可以复现协议记录
+    pub fn with_rng(mut self, rng: RandomSource) -> Self {
+        self.rng = rng;
+        self
+    }
+
+    // 开始一个操作（注册、登录、注销等）：生成新的关联 ID，返回带有该 ID 的跟踪 span
+    // 操作中的每个 RPC 都携带这个 ID，服务器日志和错误中的 ID 与客户端的跟踪输出一致
+    // otel feature 下这个 span 以操作名导出，操作中的 RPC 携带它的 trace 上下文，服务器的 span 与之属于同一个 trace
+    fn begin(&mut self, operation: &'static str) -> Span {
+        self.correlation_id = ZKP::generate_random_string(CORRELATION_ID_LEN);
+        let span = info_span!("operation", name = operation, correlation_id = %self.correlation_id, otel.name = tracing::field::Empty);
+        #[cfg(feature = "otel")]
+        {
+            span.record("otel.name", operation);
+            self.operation = span.clone();
+        }
+        span
+    }
+
+    // 构建携带当前关联 ID（和 otel feature 下当前操作的 trace 上下文）的请求
+    fn request<T>(&self, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        if let Ok(value) = self.correlation_id.parse() {
+            request.metadata_mut().insert(CORRELATION_ID_HEADER, value);
+        }
+        #[cfg(feature = "otel")]
+        zkp_proto::otel::inject(&tracing_opentelemetry::OpenTelemetrySpanExt::context(&self.operation), request.metadata_mut());
+        request
+    }
+}
+
+// 由密码字节和 KDF 参数派生私钥 x，模 q 约简（alpha、beta 的阶为 q，公开值不变）
+// num-bigint 无法清零 BigUint 的内部缓冲区，因此私钥只在计算 y1、y2 或 s 的函数内部短暂存在，
+// 在请求发出之前就被释放；长期持有的只有 Zeroizing 包装的密码字节
+// 登录时 KDF 参数来自服务器，无法使用的参数视为服务器数据错误（注册时本地生成的参数总是有效）
+// 连接设置了服务标签时，使用由该私钥派生的服务身份
+fn secret(zkp: &ZKP, kdf: &Kdf, password: &[u8], conn: &Connection) -> Result<Scalar, ClientError> {
+    let bytes = kdf.derive(password).map_err(|status| ClientError::InvalidServerData(status.message().to_string()))?;
+    let master = Scalar::reduce(&BigUint::from_bytes_be(&bytes), zkp);
+    Ok(match &conn.service {
+        Some(label) => zkp.derive_child_secret(&master, label),
+        None => master,
+    })
+}
+
+// 挑战中服务器返回的盐和 KDF 参数；连接设置了保存的参数时必须与之相同，否则服务器可能在让客户端用更弱的参数派生私钥
+fn pinned_kdf<'a>(kdf: &'a Kdf, conn: &'a Connection) -> Result<&'a Kdf, ClientError> {
+    match &conn.kdf_pin {
+        Some(pin) if !pin.same_as(kdf) => Err(ClientError::InvalidServerData(
+            "the server returned a different salt or KDF parameters than the ones saved for this account \
+             (if the password was changed on another device, run `accounts remove` and log in again)"
+                .to_string(),
+        )),
+        Some(pin) => Ok(pin),
+        None => Ok(kdf),
+    }
+}
+
+// 注册时一起设置的账户恢复方式和第二因素，默认都不设置
+#[derive(Debug, Clone, Default)]
+pub struct RecoveryOptions {
+    pub codes: u32,              // 签发的一次性恢复码数量
+    pub guardians: Vec<String>,  // 多方恢复的监护人
+    pub guardian_threshold: u32, // 完成多方恢复需要的监护人批准数
+    pub totp_secret: Vec<u8>,    // TOTP 第二因素的共享密钥，为空时不启用
+}
+
+// 注册流程：由密码派生私钥 x，计算 y1 = alpha^x mod p, y2 = beta^x mod p 并发送给服务器
+// 盐和 KDF 参数一起发送，服务器在登录时返回给客户端；同时附上绑定用户名的持有证明
+pub async fn register(conn: &mut Connection, zkp: &ZKP, username: &str, password: &[u8], kdf: &Kdf, recovery: &RecoveryOptions) -> Result<Response<RegisterResponse>, ClientError> {
+    let span = conn.begin("register");
+    register_request(conn, zkp, username, password, kdf, recovery).instrument(span).await
+}
+
+// 由密码派生私钥并计算绑定用户名的持有证明，证明中包含 y1 和 y2，分别为 alpha 和 beta 的私钥次方模 p 的结果，私钥在计算后立即释放
+// 注册、修改密码和重置密码共用
+fn possession(zkp: &ZKP, username: &str, password: &[u8], kdf: &Kdf, conn: &Connection) -> Result<NonInteractiveProof, ClientError> {
+    Ok(zkp.prove_non_interactive_with_rng(conn.proof_hash, secret(zkp, kdf, password, conn)?.value(), &registration_context(username), &conn.rng))
+}
+
+async fn register_request(conn: &mut Connection, zkp: &ZKP, username: &str, password: &[u8], kdf: &Kdf, recovery: &RecoveryOptions) -> Result<Response<RegisterResponse>, ClientError> {
+    let proof = possession(zkp, username, password, kdf, conn)?;
+    let (y1, y2) = (&proof.y1, &proof.y2);
+
+    // 构建一个注册请求 RegisterRequest，包含用户名和计算得到的 y1 和 y2
+    let request = RegisterRequest {
+        user: username.to_string(), // 用户名
+        y1: y1.to_bytes_be(), // 将 y1 转换为字节数组
+        y2: y2.to_bytes_be(), // 将 y2 转换为字节数组
+        salt: kdf.salt.clone(), // 派生私钥时使用的盐
+        kdf: kdf.params.clone(), // 派生私钥时使用的 KDF 参数
+        params_hash: zkp.params_hash(), // 计算时使用的参数集标识，服务器据此拒绝不匹配的参数
+        proof_c: proof.c.to_bytes_be(), // 持有证明的挑战值 c
+        proof_s: proof.s.to_bytes_be(), // 持有证明的响应 s
+        metadata: (*conn.metadata).clone(), // 自定义元数据
+        proof_hash: proof.hash.to_string(), // 持有证明使用的哈希函数
+        display_name: String::new(), // 账户资料在注册后通过 update-profile 设置
+        contact: String::new(),
+        recovery_codes: recovery.codes, // 同时签发的一次性恢复码数量
+        guardians: recovery.guardians.clone(), // 多方恢复的监护人和门限
+        guardian_threshold: recovery.guardian_threshold,
+        auth_id: String::new(), // 只注册新用户，不提供旧密码的证明
+        s: Vec::new(),
+        totp_secret: recovery.totp_secret.clone(), // 启用 TOTP 时的共享密钥
+        totp_code: String::new(),
+    };
+
+    // 向 gRPC 服务器发送注册请求，等待服务器响应
+    info!(user = username, "registering");
+    debug!(y1 = %Shown(y1), y2 = %Shown(y2), "registration values");
+    let started = Instant::now();
+    let response = conn.client.register(conn.request(request)).await.map_err(ClientError::registration)?;
+    trace!(elapsed = ?started.elapsed(), metadata = ?response.metadata(), "register response");
+    info!(user = username, "registered");
+    Ok(response)
+}
+
+// 一次成功登录的结果
+pub struct LoginOutcome {
+    pub auth_id: String,                 // 本次认证的 auth_id
+    pub session_id: String,              // 服务器返回的会话 ID
+    pub expires_at: u64,                 // 会话的过期时间（Unix 时间戳，秒），服务器未返回时为 0
+    pub scopes: Vec<String>,             // 会话被授予的权限范围
+    pub token: String,                   // 服务器签发的 JWT，服务器未配置签名密钥时为空
+    pub streamed: bool,                  // 是否通过双向流完成认证
+    pub register_time: Option<Duration>, // 登录前自动注册的耗时，未注册时为 None
+    pub challenge_time: Duration,        // 请求挑战的耗时
+    pub verify_time: Duration,           // 提交响应并验证的耗时
+}
+
+impl LoginOutcome {
+    // 转为 JSON 输出字段
+    pub fn to_json(&self, username: &str) -> Value {
+        let mut timings = json!({
+            "challenge": self.challenge_time.as_secs_f64() * 1000.0,
+            "verify": self.verify_time.as_secs_f64() * 1000.0,
+        });
+        if let Some(register_time) = self.register_time {
+            timings["register"] = json!(register_time.as_secs_f64() * 1000.0);
+        }
+        json!({
+            "user": username,
+            "auth_id": self.auth_id,
+            "session_id": self.session_id,
+            "expires_at": (self.expires_at != 0).then_some(self.expires_at),
+            "scopes": self.scopes,
+            "token": (!self.token.is_empty()).then_some(&self.token),
+            "transport": if self.streamed { "stream" } else { "unary" },
+            "registered": self.register_time.is_some(),
+            "timings_ms": timings,
+        })
+    }
+}
+
+// 登录失败发生的阶段：请求挑战时（例如用户不存在）或提交响应时
+enum Phase {
+    Challenge,
+    Answer,
+}
+
+// 服务器返回的挑战，以及生成承诺时使用的随机数 k
+struct Challenge {
+    k: Scalar,                // 临时私钥 k，计算响应 s 时使用
+    auth_id: String,          // 本次认证的 auth_id
+    c: Scalar,                // 挑战值 c
+    kdf: Kdf,                 // 服务器返回的盐和 KDF 参数
+    expires_at: u64,          // 挑战的过期时间（Unix 时间戳，秒），服务器未返回时为 0
+    challenge_time: Duration, // 请求挑战的耗时
+}
+
+// 生成承诺：随机数 k 以及 r1 = alpha^k mod p, r2 = beta^k mod p，请求附带连接的元数据和设备标识
+fn commitment(zkp: &ZKP, username: &str, conn: &Connection) -> (Scalar, AuthenticationChallengeRequest) {
+    // 创建用于认证的随机数 k，并计算 r1 和 r2
+    let k = Scalar::random_with(zkp, &conn.rng); // 生成随机数 k
+    let (r1, r2) = zkp.commit(&k); // 计算 r1 = alpha^k mod p, r2 = beta^k mod p
+
+    info!(user = username, "requesting challenge");
+    debug!(r1 = %Shown(r1.value()), r2 = %Shown(r2.value()), "commitment");
+
+    // 构建认证挑战请求 AuthenticationChallengeRequest
+    let request = AuthenticationChallengeRequest {
+        user: username.to_string(), // 用户名
+        r1: r1.to_bytes_be(), // 将 r1 转换为字节数组
+        r2: r2.to_bytes_be(), // 将 r2 转换为字节数组
+        params_hash: zkp.params_hash(), // 计算时使用的参数集标识
+        metadata: conn.metadata.as_ref().clone(), // 自定义元数据
+        device_id: conn.device_id.clone(), // 设备标识
+    };
+    (k, request)
+}
+
+// 计算响应 s = k - c * x mod q，并构建认证应答请求
+fn answer(zkp: &ZKP, challenge: &Challenge, password: &[u8], conn: &Connection) -> Result<AuthenticationAnswerRequest, ClientError> {
+    // 计算响应值 s，使用 k、c 和由密码派生的私钥，私钥在本函数返回时释放
+    let s = zkp.respond(&challenge.k, &challenge.c, &secret(zkp, pinned_kdf(&challenge.kdf, conn)?, password, conn)?);
+
+    // 派生私钥可能耗时较长，挑战已经过期时不再发送注定失败的应答
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+    if challenge.expires_at != 0 && challenge.expires_at <= now {
+        return Err(ClientError::ChallengeExpired(format!("AuthId: {} challenge expired before the answer was ready", challenge.auth_id)));
+    }
+    info!(auth_id = %challenge.auth_id, "sending answer");
+    debug!(s = %Shown(s.value()), "answer");
+
+    // 构建认证应答请求 AuthenticationAnswerRequest
+    Ok(AuthenticationAnswerRequest {
+        auth_id: challenge.auth_id.clone(), // 传递 auth_id
+        s: s.to_bytes_be(), // 将 s 转换为字节数组
+        params_hash: zkp.params_hash(), // 计算时使用的参数集标识
+        metadata: conn.metadata.as_ref().clone(), // 自定义元数据
+        device_id: conn.device_id.clone(), // 设备标识，与挑战请求中的一致
+        totp_code: conn.totp_code.clone(), // 账户启用了 TOTP 时的验证码
+    })
+}
+
+// 记录收到的挑战，缺少 auth_id 或挑战值、挑战值不小于 q、用户注册在其他群中或者挑战绑定到 TLS 通道的响应无法应答
+fn challenge_received(zkp: &ZKP, k: Scalar, response: AuthenticationChallengeResponse, challenge_time: Duration) -> Result<Challenge, ClientError> {
+    if response.auth_id.is_empty() || response.c.is_empty() {
+        return Err(ClientError::InvalidServerData("challenge response without auth_id or challenge".to_string()));
+    }
+    // 客户端没有 TLS 导出器，无法计算绑定到通道的挑战值，发送注定失败的应答没有意义
+    if response.channel_bound {
+        return Err(ClientError::InvalidServerData("server binds the challenge to the TLS channel, which this client cannot export".to_string()));
+    }
+    // 用户注册在其他群中时 s 无法通过验证，提示用户用 --group 指定注册时的群；旧服务器不返回群
+    if !response.params_hash.is_empty() && response.params_hash != zkp.params_hash() {
+        return Err(ClientError::InvalidServerData(format!("the account is registered in parameter set {}, pass its parameters with --group", hex::encode(&response.params_hash))));
+    }
+    // 将挑战值 c 从字节数组转换为指数
+    let c = Scalar::from_bytes_be(&response.c, zkp).map_err(|e| ClientError::InvalidServerData(format!("challenge c: {}", e)))?;
+    let auth_id = response.auth_id;
+    let kdf = Kdf::new(response.salt, response.kdf);
+    let expires_at = response.expires_at;
+    info!(auth_id = %auth_id, "challenge received");
+    debug!(c = %Shown(c.value()), salted = !kdf.salt.is_empty(), expires_at, "challenge");
+    trace!(elapsed = ?challenge_time, "challenge response");
+    Ok(Challenge { k, auth_id, c, kdf, expires_at, challenge_time })
+}
+
+// 一元调用的登录：CreateAuthenticationChallenge 和 VerifyAuthentication 两次调用
+async fn login_unary(conn: &mut Connection, zkp: &ZKP, username: &str, password: &[u8]) -> Result<LoginOutcome, (Phase, ClientError)> {
+    // 向 gRPC 服务器发送认证挑战请求，等待服务器响应
+    let (k, request) = commitment(zkp, username, conn);
+    let started = Instant::now();
+    let response = conn.client.create_authentication_challenge(conn.request(request)).await.map_err(|s| (Phase::Challenge, s.into()))?.into_inner();
+    let challenge = challenge_received(zkp, k, response, started.elapsed()).map_err(|e| (Phase::Challenge, e))?;
+
+    // 向 gRPC 服务器发送认证应答请求，等待服务器响应
+    let request = answer(zkp, &challenge, password, conn).map_err(|e| (Phase::Answer, e))?;
+    let started = Instant::now();
+    let response = conn.client.verify_authentication(conn.request(request)).await.map_err(|s| (Phase::Answer, ClientError::answer(s)))?.into_inner();
+    trace!(elapsed = ?started.elapsed(), "verify response");
+    info!(auth_id = %challenge.auth_id, "authenticated");
+    Ok(LoginOutcome {
+        auth_id: challenge.auth_id,
+        session_id: response.session_id,
+        expires_at: response.expires_at,
+        scopes: response.scopes,
+        token: response.token,
+        streamed: false,
+        register_time: None,
+        challenge_time: challenge.challenge_time,
+        verify_time: started.elapsed(),
+    })
+}
+
+// 流式登录：在一个双向流上发送承诺、接收挑战、发送响应并接收会话
+// 任何一步超时或出错时直接返回，请求通道和响应流随之被丢弃，tonic 会取消该 RPC
+async fn login_stream(conn: &mut Connection, zkp: &ZKP, username: &str, password: &[u8]) -> Result<LoginOutcome, (Phase, ClientError)> {
+    let (tx, rx) = mpsc::channel(2);
+    let (k, commitment) = commitment(zkp, username, conn);
+    tx.send(AuthenticateRequest { step: Some(authenticate_request::Step::Commitment(commitment)) })
+        .await
+        .map_err(|_| (Phase::Challenge, Status::cancelled("request stream closed").into()))?;
+
+    // 整个流的截止时间为两步超时之和，同时通过 grpc-timeout 告知服务器
+    let mut request = conn.request(ReceiverStream::new(rx));
+    request.set_timeout(conn.timeout * 2);
+
+    let started = Instant::now();
+    let mut responses = tokio::time::timeout(conn.timeout, conn.client.authenticate(request))
+        .await
+        .map_err(|_| (Phase::Challenge, Status::deadline_exceeded("timed out waiting for the server").into()))?
+        .map_err(|s| (Phase::Challenge, s.into()))?
+        .into_inner();
+    let challenge = match next_step(&mut responses, conn.timeout).await.map_err(|e| (Phase::Challenge, e))? {
+        authenticate_response::Step::Challenge(response) => challenge_received(zkp, k, response, started.elapsed()).map_err(|e| (Phase::Challenge, e))?,
+        authenticate_response::Step::Session(_) => {
+            return Err((Phase::Challenge, ClientError::InvalidServerData("server sent a session before the challenge".to_string())))
+        }
+    };
+
+    let answer = answer(zkp, &challenge, password, conn).map_err(|e| (Phase::Answer, e))?;
+    let started = Instant::now();
+    tx.send(AuthenticateRequest { step: Some(authenticate_request::Step::Answer(answer)) })
+        .await
+        .map_err(|_| (Phase::Answer, Status::cancelled("request stream closed").into()))?;
+    let session = match next_step(&mut responses, conn.timeout).await.map_err(|e| (Phase::Answer, e))? {
+        authenticate_response::Step::Session(session) => session,
+        authenticate_response::Step::Challenge(_) => {
+            return Err((Phase::Answer, ClientError::InvalidServerData("server sent a second challenge".to_string())))
+        }
+    };
+    drop(tx); // 结束请求流
+    trace!(elapsed = ?started.elapsed(), "verify response");
+    info!(auth_id = %challenge.auth_id, "authenticated");
+    Ok(LoginOutcome {
+        auth_id: challenge.auth_id,
+        session_id: session.session_id,
+        expires_at: session.expires_at,
+        scopes: session.scopes,
+        token: session.token,
+        streamed: true,
+        register_time: None,
+        challenge_time: challenge.challenge_time,
+        verify_time: started.elapsed(),
+    })
+}
+
+// 在超时时间内等待流上的下一条消息，超时或流提前结束都视为错误
+// 流上的错误按应答的错误分类：服务器在流上拒绝证明或报告挑战过期时与一元调用一致
+async fn next_step(responses: &mut Streaming<AuthenticateResponse>, timeout: Duration) -> Result<authenticate_response::Step, ClientError> {
+    match tokio::time::timeout(timeout, responses.message()).await {
+        Err(_) => Err(Status::deadline_exceeded("timed out waiting for the server").into()),
+        Ok(Err(status)) => Err(ClientError::answer(status)),
+        Ok(Ok(None)) => Err(Status::aborted("server closed the stream").into()),
+        Ok(Ok(Some(AuthenticateResponse { step: Some(step) }))) => Ok(step),
+        Ok(Ok(Some(AuthenticateResponse { step: None }))) => Err(ClientError::InvalidServerData("empty message from server".to_string())),
+    }
+}
+
+// 登录：优先使用流式认证，服务器不支持时退回一元调用，并记住该结果
+async fn login_phased(conn: &mut Connection, zkp: &ZKP, username: &str, password: &[u8]) -> Result<LoginOutcome, (Phase, ClientError)> {
+    if conn.streaming.load(Ordering::Relaxed) {
+        match login_stream(conn, zkp, username, password).await {
+            Err((Phase::Challenge, error)) if error.code() == Code::Unimplemented => {
+                info!("server does not support streaming authentication, using unary calls");
+                conn.streaming.store(false, Ordering::Relaxed);
+            }
+            result => return result,
+        }
+    }
+    login_unary(conn, zkp, username, password).await
+}
+
+// 登录流程：提交承诺 (r1, r2)，获得挑战 c，计算并提交响应 s，返回会话 ID
+pub async fn login(conn: &mut Connection, zkp: &ZKP, username: &str, password: &[u8]) -> Result<LoginOutcome, ClientError> {
+    let span = conn.begin("login");
+    login_phased(conn, zkp, username, password).instrument(span).await.map_err(|(_, error)| error)
+}
+
+// 登录流程，用户不存在时先用 kdf 注册再重试，之后的登录使用这次注册的盐和 KDF 参数
+// 只有请求挑战时返回 NotFound（用户未注册）才会注册，其他错误原样返回
+pub async fn login_or_register(conn: &mut Connection, zkp: &ZKP, username: &str, password: &[u8], kdf: &Kdf) -> Result<LoginOutcome, ClientError> {
+    let span = conn.begin("login");
+    match login_phased(conn, zkp, username, password).instrument(span).await {
+        Err((Phase::Challenge, error)) if error.code() == Code::NotFound => {
+            info!(user = username, "user not registered, registering first");
+            let started = Instant::now();
+            register(conn, zkp, username, password, kdf, &RecoveryOptions::default()).await?;
+            let register_time = started.elapsed();
+            conn.kdf_pin = Some(kdf.clone());
+            let mut outcome = login(conn, zkp, username, password).await?;
+            outcome.register_time = Some(register_time);
+            Ok(outcome)
+        }
+        result => result.map_err(|(_, error)| error),
+    }
+}
+
+// 从服务器取得群参数：未指定用户时为服务器的默认群，否则为该用户注册时的群。
+// 参数的指纹由客户端重新计算，与响应中的指纹以及 `pin`（如果指定）不一致，或者参数没有通过检查时返回 InvalidServerData
+pub async fn fetch_parameters(conn: &mut Connection, username: Option<&str>, pin: Option<&[u8]>) -> Result<(ZKP, GetAuthParametersResponse), ClientError> {
+    let span = conn.begin("get-parameters");
+    info!(parent: &span, user = username, "fetching group parameters");
+    let request = conn.request(GetAuthParametersRequest { user: username.unwrap_or_default().to_string() });
+    let response = conn.client.get_auth_parameters(request).instrument(span).await?.into_inner();
+    let zkp = ZKP::builder()
+        .params(BigUint::from_bytes_be(&response.p), BigUint::from_bytes_be(&response.q))
+        .generators(BigUint::from_bytes_be(&response.alpha), BigUint::from_bytes_be(&response.beta))
+        .validate()
+        .build()
+        .map_err(|e| ClientError::InvalidServerData(format!("the server sent invalid group parameters: {}", e)))?;
+    let params_hash = zkp.params_hash();
+    if params_hash != response.params_hash {
+        return Err(ClientError::InvalidServerData(format!("the server sent parameters with fingerprint {} but reported {}", hex::encode(&params_hash), hex::encode(&response.params_hash))));
+    }
+    if let Some(pin) = pin.filter(|pin| *pin != params_hash.as_slice()) {
+        return Err(ClientError::InvalidServerData(format!("the server's parameters have fingerprint {}, expected {}", hex::encode(&params_hash), hex::encode(pin))));
+    }
+    Ok((zkp, response))
+}
+
+// 查询会话是否仍然有效
+pub async fn validate_session(conn: &mut Connection, session_id: &str) -> Result<ValidateSessionResponse, ClientError> {
+    let span = conn.begin("validate-session");
+    info!(parent: &span, "validating session");
+    let request = conn.request(ValidateSessionRequest { session_id: session_id.to_string(), device_id: conn.device_id.clone() });
+    Ok(conn.client.validate_session(request).instrument(span).await?.into_inner())
+}
+
+// 会话内省：查询会话的详细信息
+pub async fn introspect_session(conn: &mut Connection, session_id: &str) -> Result<IntrospectSessionResponse, ClientError> {
+    let span = conn.begin("introspect-session");
+    info!(parent: &span, "introspecting session");
+    let request = conn.request(IntrospectSessionRequest { session_id: session_id.to_string() });
+    Ok(conn.client.introspect_session(request).instrument(span).await?.into_inner())
+}
+
+// 订阅会话吊销通知，返回服务器推送的通知流
+pub async fn watch_revocations(conn: &mut Connection) -> Result<Streaming<RevokedSession>, ClientError> {
+    let span = conn.begin("watch-revocations");
+    info!(parent: &span, "subscribing to session revocations");
+    let request = conn.request(WatchRevocationsRequest {});
+    Ok(conn.client.watch_revocations(request).instrument(span).await?.into_inner())
+}
+
+// 注销会话
+pub async fn logout(conn: &mut Connection, session_id: &str) -> Result<(), ClientError> {
+    let span = conn.begin("logout");
+    info!(parent: &span, "logging out");
+    let request = conn.request(LogoutRequest { session_id: session_id.to_string() });
+    conn.client.logout(request).instrument(span).await?;
+    Ok(())
+}
+
+// 修改密码：用旧密码回答一次挑战，同时提交新密码用 kdf（新的盐）派生的 y1、y2
+// 修改密码需要挑战对应的 auth_id，因此总是使用一元调用
+pub async fn change_password(conn: &mut Connection, zkp: &ZKP, username: &str, old_password: &[u8], new_password: &[u8], kdf: &Kdf) -> Result<(), ClientError> {
+    let span = conn.begin("change-password");
+    change_password_request(conn, zkp, username, old_password, new_password, kdf).instrument(span).await
+}
+
+async fn change_password_request(conn: &mut Connection, zkp: &ZKP, username: &str, old_password: &[u8], new_password: &[u8], kdf: &Kdf) -> Result<(), ClientError> {
+    let (auth_id, s) = prove_ownership(conn, zkp, username, old_password).await?;
+
+    // 新密码对应的 y1、y2 和持有证明
+    let proof = possession(zkp, username, new_password, kdf, conn)?;
+    debug!(y1 = %Shown(&proof.y1), y2 = %Shown(&proof.y2), "new registration values");
+
+    let request = ChangePasswordRequest {
+        auth_id,
+        s,
+        y1: proof.y1.to_bytes_be(),
+        y2: proof.y2.to_bytes_be(),
+        salt: kdf.salt.clone(),
+        kdf: kdf.params.clone(),
+        totp_code: conn.totp_code.clone(), // 账户启用了 TOTP 时的验证码
+        user: username.to_string(), // 持有证明绑定的用户名
+        proof_c: proof.c.to_bytes_be(),
+        proof_s: proof.s.to_bytes_be(),
+        proof_hash: proof.hash.to_string(),
+    };
+    conn.client.change_password(conn.request(request)).await.map_err(ClientError::answer)?;
+    info!(user = username, "password changed");
+    Ok(())
+}
+
+// 修改账户资料：用密码回答一次挑战证明是账户本人，None 的字段不修改，返回修改后的资料
+pub async fn update_profile(
+    conn: &mut Connection,
+    zkp: &ZKP,
+    username: &str,
+    password: &[u8],
+    display_name: Option<String>,
+    contact: Option<String>,
+) -> Result<Profile, ClientError> {
+    let span = conn.begin("update-profile");
+    update_profile_request(conn, zkp, username, password, display_name, contact).instrument(span).await
+}
+
+async fn update_profile_request(
+    conn: &mut Connection,
+    zkp: &ZKP,
+    username: &str,
+    password: &[u8],
+    display_name: Option<String>,
+    contact: Option<String>,
+) -> Result<Profile, ClientError> {
+    let (auth_id, s) = prove_ownership(conn, zkp, username, password).await?;
+    let request = UpdateProfileRequest { auth_id, s, display_name, contact, totp_code: conn.totp_code.clone() };
+    let response = conn.client.update_profile(conn.request(request)).await.map_err(ClientError::answer)?.into_inner();
+    info!(user = username, "profile updated");
+    response.profile.ok_or_else(|| ClientError::InvalidServerData("update response without profile".to_string()))
+}
+
+// 导出服务器保存的关于用户的全部数据：用密码回答一次挑战证明是账户本人
+pub async fn export_user_data(conn: &mut Connection, zkp: &ZKP, username: &str, password: &[u8]) -> Result<UserDataExport, ClientError> {
+    let span = conn.begin("export-user-data");
+    export_request(conn, zkp, username, password).instrument(span).await
+}
+
+async fn export_request(conn: &mut Connection, zkp: &ZKP, username: &str, password: &[u8]) -> Result<UserDataExport, ClientError> {
+    let (auth_id, s) = prove_ownership(conn, zkp, username, password).await?;
+    let response = conn.client.export_user_data(conn.request(ExportUserDataRequest { auth_id, s, totp_code: conn.totp_code.clone() })).await.map_err(ClientError::answer)?.into_inner();
+    info!(user = username, "user data exported");
+    response.data.ok_or_else(|| ClientError::InvalidServerData("export response without data".to_string()))
+}
+
+// 不可恢复地删除服务器上的用户，返回被吊销的会话数
+pub async fn delete_user_data(conn: &mut Connection, zkp: &ZKP, username: &str, password: &[u8]) -> Result<u32, ClientError> {
+    let span = conn.begin("delete-user-data");
+    delete_request(conn, zkp, username, password).instrument(span).await
+}
+
+// 丢失密码时用注册时签发的恢复码登录，再用得到的恢复会话设置新密码（用 kdf 派生）；服务器随后注销该用户的所有会话
+pub async fn recover_account(conn: &mut Connection, zkp: &ZKP, username: &str, code: &str, new_password: &[u8], kdf: &Kdf) -> Result<(), ClientError> {
+    let span = conn.begin("recover-account");
+    recover_request(conn, zkp, username, code, new_password, kdf).instrument(span).await
+}
+
+// 多方恢复的第一步：为丢失密码的用户发起恢复，返回交给监护人的恢复 ID
+pub async fn start_guardian_recovery(conn: &mut Connection, username: &str) -> Result<StartGuardianRecoveryResponse, ClientError> {
+    let span = conn.begin("start-guardian-recovery");
+    info!(parent: &span, user = username, "starting guardian recovery");
+    let request = conn.request(StartGuardianRecoveryRequest { user: username.to_string() });
+    Ok(conn.client.start_guardian_recovery(request).instrument(span).await?.into_inner())
+}
+
+// 监护人用自己的密码证明身份，批准指定的多方恢复
+pub async fn approve_guardian_recovery(conn: &mut Connection, zkp: &ZKP, guardian: &str, password: &[u8], recovery_id: &str) -> Result<ApproveGuardianRecoveryResponse, ClientError> {
+    let span = conn.begin("approve-guardian-recovery");
+    approve_recovery_request(conn, zkp, guardian, password, recovery_id).instrument(span).await
+}
+
+// 批准数达到门限后完成多方恢复，再用得到的恢复会话设置新密码（用 kdf 派生）
+pub async fn complete_guardian_recovery(conn: &mut Connection, zkp: &ZKP, username: &str, recovery_id: &str, new_password: &[u8], kdf: &Kdf) -> Result<(), ClientError> {
+    let span = conn.begin("complete-guardian-recovery");
+    complete_recovery_request(conn, zkp, username, recovery_id, new_password, kdf).instrument(span).await
+}
+
+async fn delete_request(conn: &mut Connection, zkp: &ZKP, username: &str, password: &[u8]) -> Result<u32, ClientError> {
+    let (auth_id, s) = prove_ownership(conn, zkp, username, password).await?;
+    let response = conn.client.delete_user_data(conn.request(DeleteUserDataRequest { auth_id, s, totp_code: conn.totp_code.clone() })).await.map_err(ClientError::answer)?.into_inner();
+    info!(user = username, revoked_sessions = response.revoked_sessions, "user data deleted");
+    Ok(response.revoked_sessions)
+}
+
+async fn recover_request(conn: &mut Connection, zkp: &ZKP, username: &str, code: &str, new_password: &[u8], kdf: &Kdf) -> Result<(), ClientError> {
+    // 恢复码错误时服务器返回 PermissionDenied，与被拒绝的证明同样处理
+    let request = RecoverAccountRequest { user: username.to_string(), code: code.to_string() };
+    let session = conn.client.recover_account(conn.request(request)).await.map_err(ClientError::answer)?.into_inner();
+    info!(user = username, scopes = ?session.scopes, "recovery session established");
+    reset_request(conn, zkp, username, session.session_id, new_password, kdf).await
+}
+
+async fn approve_recovery_request(conn: &mut Connection, zkp: &ZKP, guardian: &str, password: &[u8], recovery_id: &str) -> Result<ApproveGuardianRecoveryResponse, ClientError> {
+    let (auth_id, s) = prove_ownership(conn, zkp, guardian, password).await?;
+    let request = ApproveGuardianRecoveryRequest { recovery_id: recovery_id.to_string(), auth_id, s, totp_code: conn.totp_code.clone() };
+    let response = conn.client.approve_guardian_recovery(conn.request(request)).await.map_err(ClientError::answer)?.into_inner();
+    info!(guardian, approvals = response.approvals, threshold = response.threshold, "guardian recovery approved");
+    Ok(response)
+}
+
+async fn complete_recovery_request(conn: &mut Connection, zkp: &ZKP, username: &str, recovery_id: &str, new_password: &[u8], kdf: &Kdf) -> Result<(), ClientError> {
+    let request = CompleteGuardianRecoveryRequest { recovery_id: recovery_id.to_string() };
+    let session = conn.client.complete_guardian_recovery(conn.request(request)).await?.into_inner();
+    info!(user = username, scopes = ?session.scopes, "recovery session established");
+    reset_request(conn, zkp, username, session.session_id, new_password, kdf).await
+}
+
+// 用恢复会话设置新密码
+async fn reset_request(conn: &mut Connection, zkp: &ZKP, username: &str, session_id: String, new_password: &[u8], kdf: &Kdf) -> Result<(), ClientError> {
+    // 新密码对应的 y1、y2 和持有证明
+    let proof = possession(zkp, username, new_password, kdf, conn)?;
+    debug!(y1 = %Shown(&proof.y1), y2 = %Shown(&proof.y2), "new registration values");
+
+    let request = ResetCredentialsRequest {
+        session_id,
+        y1: proof.y1.to_bytes_be(),
+        y2: proof.y2.to_bytes_be(),
+        salt: kdf.salt.clone(),
+        kdf: kdf.params.clone(),
+        user: username.to_string(), // 持有证明绑定的用户名
+        proof_c: proof.c.to_bytes_be(),
+        proof_s: proof.s.to_bytes_be(),
+        proof_hash: proof.hash.to_string(),
+    };
+    conn.client.reset_credentials(conn.request(request)).await.map_err(ClientError::answer)?;
+    info!(user = username, "password reset");
+    Ok(())
+}
+
+// 请求挑战并计算解答，返回 auth_id 和 s，供需要证明身份的一元调用使用
+async fn prove_ownership(conn: &mut Connection, zkp: &ZKP, username: &str, password: &[u8]) -> Result<(String, Vec<u8>), ClientError> {
+    let (k, request) = commitment(zkp, username, conn);
+    let started = Instant::now();
+    let response = conn.client.create_authentication_challenge(conn.request(request)).await?.into_inner();
+    let challenge = challenge_received(zkp, k, response, started.elapsed())?;
+    let answer = answer(zkp, &challenge, password, conn)?;
+    Ok((answer.auth_id, answer.s))
+}
+
+// 跨设备登录：为指定用户创建待完成的登录，返回 pending_id 和 nonce
+pub async fn create_pending_login(conn: &mut Connection, username: &str) -> Result<CreatePendingLoginResponse, ClientError> {
+    let span = conn.begin("create-pending-login");
+    info!(parent: &span, user = username, "creating pending login");
+    let request = conn.request(CreatePendingLoginRequest { user: username.to_string() });
+    let response = conn.client.create_pending_login(request).instrument(span.clone()).await?.into_inner();
+    debug!(parent: &span, pending_id = %response.pending_id, "pending login created");
+    Ok(response)
+}
+
+// 跨设备登录：每秒轮询一次，直到登录被批准（返回服务器的响应，其中包含会话 ID、过期时间和权限范围）或超过等待时间
+pub async fn wait_pending_login(conn: &mut Connection, pending_id: &str, nonce: &str, wait: Duration) -> Result<PollPendingLoginResponse, ClientError> {
+    let span = conn.begin("wait-pending-login");
+    poll_until_approved(conn, pending_id, nonce, wait).instrument(span).await
+}
+
+async fn poll_until_approved(conn: &mut Connection, pending_id: &str, nonce: &str, wait: Duration) -> Result<PollPendingLoginResponse, ClientError> {
+    let started = Instant::now();
+    loop {
+        let request = conn.request(PollPendingLoginRequest { pending_id: pending_id.to_string(), nonce: nonce.to_string() });
+        let response = conn.client.poll_pending_login(request).await?.into_inner();
+        if response.approved {
+            info!(pending_id, "pending login approved");
+            return Ok(response);
+        }
+        if started.elapsed() >= wait {
+            return Err(Status::deadline_exceeded(format!("the login was not approved within {}s", wait.as_secs())).into());
+        }
+        trace!(pending_id, elapsed = ?started.elapsed(), "pending login not approved yet");
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+// 跨设备登录：持有密码的设备回答一次挑战，批准扫描到的待完成登录
+// 批准需要挑战对应的 auth_id，因此总是使用一元调用
+pub async fn approve_pending_login(conn: &mut Connection, zkp: &ZKP, username: &str, password: &[u8], pending_id: &str, nonce: &str) -> Result<(), ClientError> {
+    let span = conn.begin("approve-pending-login");
+    approve_request(conn, zkp, username, password, pending_id, nonce).instrument(span).await
+}
+
+async fn approve_request(conn: &mut Connection, zkp: &ZKP, username: &str, password: &[u8], pending_id: &str, nonce: &str) -> Result<(), ClientError> {
+    let (k, request) = commitment(zkp, username, conn);
+    let started = Instant::now();
+    let response = conn.client.create_authentication_challenge(conn.request(request)).await?.into_inner();
+    let challenge = challenge_received(zkp, k, response, started.elapsed())?;
+    let answer = answer(zkp, &challenge, password, conn)?;
+
+    let request = ApprovePendingLoginRequest { pending_id: pending_id.to_string(), nonce: nonce.to_string(), auth_id: answer.auth_id, s: answer.s, totp_code: answer.totp_code };
+    conn.client.approve_pending_login(conn.request(request)).await.map_err(ClientError::answer)?;
+    info!(user = username, pending_id, "pending login approved");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock::{ChallengeBehavior, MockAuthServer};
+    use zkp_proto::detail::error_code;
+    use zkp_proto::zkp_auth::ErrorCode;
+
+    fn zkp() -> ZKP {
+        ZKP::get_constants()
+    }
+
+    // 启动模拟服务器，注册 alice（不使用 KDF，避免测试耗时），返回固定了这一选择的连接
+    async fn connect(server: &MockAuthServer, prefer_stream: bool, timeout: Duration) -> Connection {
+        let client = AuthClient::connect(server.spawn().await).await.unwrap();
+        let mut conn = Connection::new(client, prefer_stream, timeout, HashMap::new()).with_kdf_pin(Some(Kdf::none()));
+        register(&mut conn, &zkp(), "alice", b"secret", &Kdf::none(), &RecoveryOptions::default()).await.unwrap();
+        conn
+    }
+
+    #[tokio::test]
+    async fn test_login_falls_back_to_unary() {
+        let server = MockAuthServer::new();
+        let mut conn = connect(&server, true, Duration::from_secs(5)).await;
+
+        let outcome = login(&mut conn, &zkp(), "alice", b"secret").await.unwrap();
+        assert!(!outcome.streamed);
+        assert_eq!(outcome.scopes, vec!["user".to_string()]);
+
+        // 记住服务器不支持流式认证，第二次登录不再尝试
+        login(&mut conn, &zkp(), "alice", b"secret").await.unwrap();
+        assert_eq!(server.stream_calls(), 1);
+        assert_eq!(server.verify_calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_password_and_wrong_challenge_are_denied() {
+        let server = MockAuthServer::new();
+        let mut conn = connect(&server, false, Duration::from_secs(5)).await;
+        let error = login(&mut conn, &zkp(), "alice", b"wrong").await.err().unwrap();
+        assert!(matches!(error, ClientError::ProofRejected(_)));
+        assert_eq!(error.code(), Code::PermissionDenied);
+
+        let server = MockAuthServer::with(ChallengeBehavior::WrongChallenge, Duration::ZERO);
+        let mut conn = connect(&server, false, Duration::from_secs(5)).await;
+        let error = login(&mut conn, &zkp(), "alice", b"secret").await.err().unwrap();
+        assert!(matches!(error, ClientError::ProofRejected(_)));
+        assert_eq!(error.code(), Code::PermissionDenied);
+    }
+
+    #[tokio::test]
+    async fn test_expired_challenge_is_not_answered() {
+        let server = MockAuthServer::with(ChallengeBehavior::Expired, Duration::ZERO);
+        let mut conn = connect(&server, false, Duration::from_secs(5)).await;
+
+        // 客户端发现挑战已过期，不再发送应答
+        let error = login(&mut conn, &zkp(), "alice", b"secret").await.err().unwrap();
+        assert!(matches!(error, ClientError::ChallengeExpired(_)));
+        assert_eq!(error.code(), Code::DeadlineExceeded);
+        assert_eq!(error_code(&error.into_status()), ErrorCode::ChallengeExpired);
+        assert_eq!(server.challenge_calls(), 1);
+        assert_eq!(server.verify_calls(), 0);
+    }
+
+    #[test]
+    fn test_challenge_in_another_group_is_not_answered() {
+        // 账户注册在其他群中时，在客户端的群中计算的 s 不可能通过验证
+        let response = AuthenticationChallengeResponse { auth_id: "mock-1".to_string(), c: vec![1], params_hash: vec![0; 32], ..Default::default() };
+        let error = challenge_received(&zkp(), Scalar::random(&zkp()), response, Duration::ZERO).err().unwrap();
+        assert!(matches!(error, ClientError::InvalidServerData(message) if message.contains("--group")));
+    }
+
+    #[tokio::test]
+    async fn test_fetched_parameters_are_pinned() {
+        let server = MockAuthServer::new();
+        let mut conn = connect(&server, false, Duration::from_secs(5)).await;
+
+        let pin = zkp().params_hash();
+        let (fetched, response) = fetch_parameters(&mut conn, None, Some(&pin)).await.unwrap();
+        assert_eq!(fetched, zkp());
+        assert_eq!(response.accepted_params_hashes, vec![pin]);
+        login(&mut conn, &fetched, "alice", b"secret").await.unwrap();
+
+        // 指纹不一致时不使用服务器发来的参数
+        let error = fetch_parameters(&mut conn, Some("alice"), Some(&[0; 32])).await.err().unwrap();
+        assert!(matches!(error, ClientError::InvalidServerData(_)));
+    }
+
+    #[tokio::test]
+    async fn test_throttled_error_carries_retry_after() {
+        let server = MockAuthServer::with(ChallengeBehavior::Throttled, Duration::ZERO);
+        let mut conn = connect(&server, false, Duration::from_secs(5)).await;
+
+        // 重试提示作为 trailer 经过 HTTP/2 传输后仍然可以读到
+        let error = login(&mut conn, &zkp(), "alice", b"secret").await.err().unwrap();
+        assert_eq!(error.code(), Code::ResourceExhausted);
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(3)));
+        assert_eq!(error_code(&error.into_status()), ErrorCode::RateLimited);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_user_is_registered_on_login() {
+        let server = MockAuthServer::new();
+        let mut conn = connect(&server, false, Duration::from_secs(5)).await;
+
+        assert_eq!(login(&mut conn, &zkp(), "bob", b"secret").await.err().unwrap().code(), Code::NotFound);
+        let kdf = Kdf::generate(&conn.rng);
+        let outcome = login_or_register(&mut conn, &zkp(), "bob", b"secret", &kdf).await.unwrap();
+        assert!(outcome.register_time.is_some());
+        assert_eq!(server.challenge_calls(), 3); // login 一次，login_or_register 注册前后各一次
+    }
+
+    #[tokio::test]
+    async fn test_weak_or_changed_kdf_is_refused() {
+        let server = MockAuthServer::new();
+        let conn = connect(&server, false, Duration::from_secs(5)).await;
+
+        // 没有固定参数时，服务器返回的空盐不会让客户端直接使用密码字节
+        let mut unpinned = conn.clone().with_kdf_pin(None);
+        let error = login(&mut unpinned, &zkp(), "alice", b"secret").await.err().unwrap();
+        assert!(matches!(error, ClientError::InvalidServerData(message) if message.contains("no salt")));
+
+        // 迭代次数少于下限时拒绝派生
+        let weak = Kdf::new(vec![7; 16], Some(crate::zkp_auth::KdfParams { algorithm: crate::kdf::PBKDF2_SHA256.to_string(), iterations: 1000 }));
+        assert!(weak.derive(b"secret").is_err());
+
+        // 服务器返回的盐与保存的不同时拒绝应答
+        let kdf = Kdf::generate(&conn.rng);
+        register(&mut conn.clone(), &zkp(), "dave", b"secret", &kdf, &RecoveryOptions::default()).await.unwrap();
+        login(&mut conn.clone().with_kdf_pin(Some(kdf.clone())), &zkp(), "dave", b"secret").await.unwrap();
+        let mut other = conn.clone().with_kdf_pin(Some(Kdf::generate(&conn.rng)));
+        let error = login(&mut other, &zkp(), "dave", b"secret").await.err().unwrap();
+        assert!(matches!(error, ClientError::InvalidServerData(message) if message.contains("saved for this account")));
+        assert_eq!(server.verify_calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_slow_stream_times_out() {
+        let server = MockAuthServer::with(ChallengeBehavior::Correct, Duration::from_millis(500));
+        let mut conn = connect(&server, true, Duration::from_millis(100)).await;
+
+        // 超时不同于 Unimplemented，不会退回到一元调用
+        let error = login(&mut conn, &zkp(), "alice", b"secret").await.err().unwrap();
+        assert!(matches!(error, ClientError::Transport(_)));
+        assert_eq!(error.code(), Code::DeadlineExceeded);
+        assert_eq!(server.challenge_calls(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_registration_is_typed() {
+        let server = MockAuthServer::new();
+        let mut conn = connect(&server, false, Duration::from_secs(5)).await;
+
+        let error = register(&mut conn, &zkp(), "", b"secret", &Kdf::none(), &RecoveryOptions::default()).await.err().unwrap();
+        assert!(matches!(error, ClientError::RegistrationFailed(_)));
+        assert_eq!(error.exit_code(), crate::error::EXIT_REGISTRATION_FAILED);
+    }
+
+    #[tokio::test]
+    async fn test_service_identities_need_the_same_label() {
+        let server = MockAuthServer::new();
+        let mut conn = connect(&server, false, Duration::from_secs(5)).await;
+        let mut mail = conn.clone().with_service(Some("mail.example".to_string()));
+        register(&mut mail, &zkp(), "carol", b"secret", &Kdf::none(), &RecoveryOptions::default()).await.unwrap();
+
+        // 同一个密码，没有标签或标签不同时得到的是另一个身份
+        login(&mut mail, &zkp(), "carol", b"secret").await.unwrap();
+        assert!(matches!(login(&mut conn, &zkp(), "carol", b"secret").await.err().unwrap(), ClientError::ProofRejected(_)));
+        let mut shop = conn.clone().with_service(Some("shop.example".to_string()));
+        assert!(matches!(login(&mut shop, &zkp(), "carol", b"secret").await.err().unwrap(), ClientError::ProofRejected(_)));
+    }
+
+    #[tokio::test]
+    async fn test_each_operation_has_its_own_correlation_id() {
+        let server = MockAuthServer::new();
+        let mut conn = connect(&server, false, Duration::from_secs(5)).await;
+
+        let _span = conn.begin("login");
+        let first = conn.request(()).metadata().get(CORRELATION_ID_HEADER).unwrap().clone();
+        assert_eq!(first.len(), CORRELATION_ID_LEN);
+        // 同一个操作中的请求使用同一个 ID，下一个操作换一个新的 ID
+        assert_eq!(conn.request(()).metadata().get(CORRELATION_ID_HEADER), Some(&first));
+        let _span = conn.begin("logout");
+        assert_ne!(conn.request(()).metadata().get(CORRELATION_ID_HEADER), Some(&first));
+    }
+}
@@ -0,0 +1,95 @@
+use pbkdf2::pbkdf2_hmac; // PBKDF2-HMAC 密钥派生
+use sha2::Sha256; // PBKDF2 使用的哈希函数
+use tonic::Status; // 服务器返回的参数不受支持时的错误
+use zeroize::Zeroizing; // 派生出的私钥在释放时清零
+use zkp_core::RandomSource; // 生成随机盐
+
+use crate::zkp_auth::KdfParams; // 与服务器交换的 KDF 参数
+
+/// 目前支持的 KDF 算法名称
+pub const PBKDF2_SHA256: &str = "pbkdf2-sha256";
+
+/// 新注册时使用的 PBKDF2 迭代次数
+pub const DEFAULT_ITERATIONS: u32 = 100_000;
+
+/// 接受的最小迭代次数，服务器返回更少的迭代次数时拒绝派生，避免私钥容易被离线猜测
+pub const MIN_ITERATIONS: u32 = DEFAULT_ITERATIONS;
+
+// 接受的最大迭代次数，避免服务器返回的参数让客户端长时间计算
+const MAX_ITERATIONS: u32 = 10_000_000;
+
+// 新注册时生成的盐的长度（字节）
+const SALT_LEN: usize = 16;
+
+// 派生出的私钥长度（字节）
+const SECRET_LEN: usize = 32;
+
+/// 由密码派生私钥 x 的方式：盐和 KDF 参数
+///
+/// 只有本地用 `Kdf::none` 明确选择时才不使用 KDF（私钥已经是随机数的压力测试和测试）；
+/// 服务器返回的空盐和过少的迭代次数在派生时被拒绝
+#[derive(Debug, Clone)]
+pub struct Kdf {
+    pub salt: Vec<u8>,              // 盐，注册时随机生成，登录时由服务器返回
+    pub params: Option<KdfParams>,  // KDF 参数
+    unsalted: bool,                 // 由 Kdf::none 创建：私钥直接取密码字节
+}
+
+impl Kdf {
+    /// 为新注册生成随机盐，使用默认的 PBKDF2 参数
+    ///
+    /// 参数:
+    /// - `rng`: 随机数来源
+    pub fn generate(rng: &RandomSource) -> Self {
+        let mut salt = vec![0u8; SALT_LEN];
+        rng.fill_bytes(&mut salt);
+        Kdf { salt, params: Some(KdfParams { algorithm: PBKDF2_SHA256.to_string(), iterations: DEFAULT_ITERATIONS }), unsalted: false }
+    }
+
+    /// 不使用 KDF，私钥直接取密码字节；只用于私钥本身已经是随机数的情况
+    pub fn none() -> Self {
+        Kdf { salt: Vec::new(), params: None, unsalted: true }
+    }
+
+    /// 服务器返回的（或者本地保存的）盐和 KDF 参数
+    ///
+    /// 参数:
+    /// - `salt`: 盐
+    /// - `params`: KDF 参数
+    pub fn new(salt: Vec<u8>, params: Option<KdfParams>) -> Self {
+        Kdf { salt, params, unsalted: false }
+    }
+
+    /// 盐和 KDF 参数是否与另一个相同
+    pub fn same_as(&self, other: &Kdf) -> bool {
+        self.salt == other.salt && self.params == other.params
+    }
+
+    /// 由密码派生私钥
+    ///
+    /// 参数:
+    /// - `password`: 密码字节
+    ///
+    /// 返回:
+    /// - `Zeroizing<Vec<u8>>`: 私钥 x 的大端字节表示，释放时清零
+    /// - 服务器返回的算法不受支持、没有盐或者迭代次数不在 [MIN_ITERATIONS, MAX_ITERATIONS] 中时返回 Unimplemented / InvalidArgument 错误
+    #[allow(clippy::result_large_err)]
+    pub fn derive(&self, password: &[u8]) -> Result<Zeroizing<Vec<u8>>, Status> {
+        if self.unsalted {
+            return Ok(Zeroizing::new(password.to_vec()));
+        }
+        if self.salt.is_empty() {
+            return Err(Status::invalid_argument("no salt, refusing to use the password without a KDF"));
+        }
+        let params = self.params.as_ref().ok_or_else(|| Status::invalid_argument("salt without KDF parameters"))?;
+        if params.algorithm != PBKDF2_SHA256 {
+            return Err(Status::unimplemented(format!("unsupported KDF: {}", params.algorithm)));
+        }
+        if params.iterations < MIN_ITERATIONS || params.iterations > MAX_ITERATIONS {
+            return Err(Status::invalid_argument(format!("unreasonable KDF iteration count: {}", params.iterations)));
+        }
+        let mut secret = Zeroizing::new(vec![0u8; SECRET_LEN]);
+        pbkdf2_hmac::<Sha256>(password, &self.salt, params.iterations, &mut secret);
+        Ok(secret)
+    }
+}
@@ -0,0 +1,552 @@
+use std::collections::HashMap; // 请求附带的自定义元数据
+use std::io::{stdin, IsTerminal}; // 引入标准库中的 stdin 模块，用于从终端读取用户输入
+use std::path::PathBuf; // 客户端状态目录路径
+use std::time::Duration; // 流式认证超时
+use clap::{Parser, Subcommand}; // 命令行参数解析
+use zeroize::Zeroizing; // 密码缓冲区在释放时清零
+use tracing::Level; // 跟踪输出级别
+use tracing_subscriber::{filter::Targets, layer::SubscriberExt, util::SubscriberInitExt, Layer}; // 按模块过滤跟踪输出
+
+mod accounts; // 本地账户与会话存储
+mod app; // 命令执行
+mod bench; // 压力测试模式
+mod error; // 协议流程的错误与进程退出码
+mod flow; // 注册、登录等协议流程
+mod kdf; // 由密码派生私钥
+#[cfg(test)]
+mod mock; // 测试用的模拟服务器
+mod output; // 文本 / JSON 输出
+mod qr; // 跨设备登录的二维码
+mod shell; // 交互模式
+mod totp; // TOTP 第二因素的共享密钥
+
+pub use zkp_proto::zkp_auth; // 由 .proto 文件生成的 gRPC 代码
+
+use accounts::AccountStore; // 本地账户集合
+use app::App; // 客户端状态与命令执行
+use error::EXIT_FAILURE; // 本地错误的退出码
+use output::OutputFormat; // 输出格式
+use zkp_core::{HashAlgorithm, ZKP}; // 引入实现 Chaum-Pedersen 零知识证明协议的库 ZKP，以及持有证明的哈希函数
+
+// 默认连接的服务器地址
+const DEFAULT_SERVER: &str = "http://127.0.0.1:50051";
+
+// 内置群参数的名称，与服务器的 --group 相同
+const BUILTIN_GROUP: &str = "rfc5114-1024";
+
+// --group 取此值时连接后从服务器取得群参数
+const SERVER_GROUP: &str = "server";
+
+/// Chaum-Pedersen 零知识证明认证客户端
+#[derive(Parser)]
+#[command(name = "client")]
+struct Cli {
+    /// 服务器地址，默认为账户注册时使用的地址或 http://127.0.0.1:50051
+    #[arg(long, global = true)]
+    server: Option<String>,
+
+    /// 客户端状态目录（保存账户和会话），默认为 $HOME/.zkp-client
+    #[arg(long, global = true, env = "ZKP_CLIENT_HOME")]
+    state_dir: Option<PathBuf>,
+
+    /// 输出格式：text 或 json（每个命令输出一行 JSON）
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
+    /// 输出协议步骤的跟踪信息到 stderr：-v 输出步骤，-vv 额外输出协议值
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// 输出最详细的跟踪信息（包括耗时和响应元数据）
+    #[arg(long, global = true)]
+    trace: bool,
+
+    /// 在跟踪信息中输出完整的协议值（y1、y2、r1、r2、c、s），默认脱敏
+    #[arg(long, global = true)]
+    unsafe_dump_values: bool,
+
+    /// 不使用双向流认证，总是通过两次一元调用登录
+    #[arg(long, global = true)]
+    no_stream: bool,
+
+    /// 流式认证中等待服务器每条消息的超时时间（秒）
+    #[arg(long, global = true, default_value_t = 10)]
+    timeout: u64,
+
+    /// 注册和登录请求附带的自定义元数据，格式为 KEY=VALUE，可以指定多次
+    #[arg(long = "metadata", global = true, value_name = "KEY=VALUE", value_parser = parse_metadata)]
+    metadata: Vec<(String, String)>,
+
+    /// 注册时持有证明和离线证明（prove）使用的哈希函数：sha256、sha3-256 或 blake3
+    #[arg(long, global = true, default_value_t = HashAlgorithm::Sha256)]
+    proof_hash: HashAlgorithm,
+
+    /// 为该服务派生独立的身份：私钥由密码得到的主私钥和服务标签再派生，不同服务上注册的公开值无法关联；
+    /// 同一账户的注册、登录和其他证明身份的命令必须使用同一个标签
+    #[arg(long, global = true, value_name = "LABEL")]
+    service: Option<String>,
+
+    /// 群参数：rfc5114-1024（默认）、参数文件的路径（PEM、OpenSSL DH 参数或 JSON），或 server（连接时从服务器取得默认的群），
+    /// 必须是服务器接受的群；注册时选择账户的群，之后该账户的登录和其他证明身份的命令必须使用同一个群
+    #[arg(long, global = true, env = "ZKP_GROUP", value_name = "GROUP")]
+    group: Option<String>,
+
+    /// 群参数的指纹（十六进制，见 `params` 命令）：从服务器取得的参数或本地的参数与之不一致时拒绝使用
+    #[arg(long, global = true, env = "ZKP_PARAMS_PIN", value_name = "HEX")]
+    params_pin: Option<String>,
+
+    /// 账户启用了 TOTP 第二因素时，登录、批准跨设备登录、修改密码、导出和删除数据时提交的当前验证码（认证器应用中的 6 位数字）
+    #[arg(long, global = true, env = "ZKP_TOTP_CODE", value_name = "CODE", hide_env_values = true)]
+    totp_code: Option<String>,
+
+    /// 验证服务器证书的 CA（PEM），指定时通过 TLS 连接（https:// 地址）
+    #[cfg(feature = "tls")]
+    #[arg(long, global = true, value_name = "PEM")]
+    tls_ca: Option<PathBuf>,
+
+    /// 服务器要求客户端证书（mTLS）时出示的证书（PEM），需要同时指定 --tls-key
+    #[cfg(feature = "tls")]
+    #[arg(long, global = true, value_name = "PEM", requires_all = ["tls_key", "tls_ca"])]
+    tls_cert: Option<PathBuf>,
+
+    /// 客户端证书的私钥（PEM）
+    #[cfg(feature = "tls")]
+    #[arg(long, global = true, value_name = "PEM", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// OTLP/gRPC collector 的地址，例如 http://127.0.0.1:4317；设置时导出每个操作的 span，请求携带 trace 上下文，服务器的 span 属于同一个 trace
+    #[cfg(feature = "otel")]
+    #[arg(long, global = true, env = "ZKP_OTLP_ENDPOINT")]
+    otlp_endpoint: Option<String>,
+
+    /// 使用固定的随机数种子，使协议记录可以复现（仅用于测试和调试）
+    #[cfg(feature = "seeded-rng")]
+    #[arg(long, global = true)]
+    seed: Option<u64>,
+
+    /// 不指定子命令时，读取用户名和密码登录，用户不存在时先注册
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 注册新账户，并将其设为当前账户
+    Register {
+        /// 用户名，不指定时从终端读取
+        #[arg(long)]
+        user: Option<String>,
+        /// 同时签发的一次性恢复码数量（最多 16 个），丢失密码时用 recover 命令恢复账户
+        #[arg(long, default_value_t = 0)]
+        recovery_codes: u32,
+        /// 多方恢复的监护人（其他用户的用户名），可以指定多次，最多 8 个
+        #[arg(long = "guardian")]
+        guardians: Vec<String>,
+        /// 完成多方恢复需要的监护人批准数，不指定时需要全部监护人批准
+        #[arg(long, requires = "guardians")]
+        guardian_threshold: Option<u32>,
+        /// 启用 TOTP 第二因素：生成共享密钥并显示给认证器应用扫描，之后登录需要同时提供 --totp-code
+        #[arg(long)]
+        totp: bool,
+    },
+    /// 以当前账户（或 --user 指定的账户）登录，并保存会话
+    Login {
+        /// 用户名，不指定时使用当前激活的账户
+        #[arg(long)]
+        user: Option<String>,
+        /// 服务器上不存在该用户时，先用同一密码注册再登录
+        #[arg(long)]
+        register_if_missing: bool,
+    },
+    /// 查询当前账户（或 --user 指定的账户）保存的会话是否仍然有效
+    #[command(alias = "validate")]
+    ValidateSession {
+        /// 用户名，不指定时使用当前激活的账户
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// 查询会话的详细信息（所属用户、建立和过期时间、认证方式）
+    Introspect {
+        /// 用户名，不指定时使用当前激活的账户
+        #[arg(long)]
+        user: Option<String>,
+        /// 直接查询指定的会话 ID，而不是本地保存的会话（供资源服务器使用）
+        #[arg(long, conflicts_with = "user")]
+        session_id: Option<String>,
+    },
+    /// 查询服务器的群参数（名称、指纹、位数）以及服务器接受的所有群
+    Params {
+        /// 查询该用户注册时的群，不指定时为服务器的默认群
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// 订阅会话吊销通知，每收到一个被吊销的会话输出一行，直到服务器关闭流
+    WatchRevocations,
+    /// 注销当前账户（或 --user 指定的账户）保存的会话
+    Logout {
+        /// 用户名，不指定时使用当前激活的账户
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// 修改当前账户（或 --user 指定的账户）的密码，成功后本地会话失效
+    ChangePassword {
+        /// 用户名，不指定时使用当前激活的账户
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// 修改当前账户（或 --user 指定的账户）在服务器上的资料，未指定的字段不变
+    UpdateProfile {
+        /// 用户名，不指定时使用当前激活的账户
+        #[arg(long)]
+        user: Option<String>,
+        /// 新的显示名称，空字符串表示清除
+        #[arg(long)]
+        display_name: Option<String>,
+        /// 新的联系方式（例如邮箱），空字符串表示清除
+        #[arg(long)]
+        contact: Option<String>,
+    },
+    /// 导出服务器保存的关于当前账户（或 --user 指定的账户）的全部数据（JSON）
+    ExportData {
+        /// 用户名，不指定时使用当前激活的账户
+        #[arg(long)]
+        user: Option<String>,
+        /// 写入导出数据的文件，不指定时输出到 stdout
+        #[arg(short = 'o', long = "out")]
+        out: Option<PathBuf>,
+    },
+    /// 不可恢复地删除服务器上的当前账户（或 --user 指定的账户），并删除本地账户
+    DeleteAccount {
+        /// 用户名，不指定时使用当前激活的账户
+        #[arg(long)]
+        user: Option<String>,
+        /// 不要求输入用户名确认
+        #[arg(long)]
+        yes: bool,
+    },
+    /// 丢失密码时用注册时签发的恢复码设置新密码，服务器上该用户的所有会话随之失效
+    Recover {
+        /// 用户名，不指定时使用当前激活的账户
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// 管理本地保存的账户
+    #[command(subcommand)]
+    Accounts(AccountsCommand),
+    /// 通过二维码跨设备登录
+    #[command(subcommand)]
+    Qr(QrCommand),
+    /// 多方恢复：丢失密码时由注册时指定的监护人批准后重置密码
+    #[command(subcommand)]
+    Guardian(GuardianCommand),
+    /// 压力测试：注册一批临时用户并发执行登录流程，报告吞吐量和错误数
+    Bench {
+        /// 注册的临时用户数量
+        #[arg(long, default_value_t = 10)]
+        users: usize,
+        /// 并发执行登录的任务数
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+        /// 每个用户的登录次数
+        #[arg(long, default_value_t = 10)]
+        iterations: usize,
+    },
+    /// 交互模式：保持一个连接，逐行执行上述命令
+    Shell,
+    /// 离线生成非交互式证明并写入文件，无需连接服务器
+    Prove {
+        /// 证明绑定的上下文（例如工单号），验证时必须一致
+        #[arg(long)]
+        context: String,
+        /// 证明文件路径
+        #[arg(short = 'o', long = "out", default_value = "proof.bin")]
+        out: PathBuf,
+    },
+    /// 离线验证 `prove` 生成的证明文件
+    Verify {
+        /// 证明文件路径
+        file: PathBuf,
+        /// 期望的上下文，指定时必须与证明中的上下文一致
+        #[arg(long)]
+        context: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum AccountsCommand {
+    /// 列出所有账户，当前账户以 * 标记
+    List,
+    /// 切换当前激活的账户
+    Use {
+        /// 要激活的用户名
+        user: String,
+    },
+    /// 删除本地账户及其会话（不影响服务器上的注册信息）
+    Remove {
+        /// 要删除的用户名
+        user: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum QrCommand {
+    /// 在本设备上显示登录二维码，等待持有密码的设备批准后保存会话
+    Show {
+        /// 要登录的用户名，不指定时使用当前激活的账户
+        #[arg(long)]
+        user: Option<String>,
+        /// 等待批准的最长时间（秒）
+        #[arg(long, default_value_t = 120)]
+        wait: u64,
+    },
+    /// 在持有密码的设备上批准扫描到的登录二维码
+    Approve {
+        /// 二维码中的文本，不指定时从终端读取
+        payload: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum GuardianCommand {
+    /// 为丢失密码的账户发起多方恢复，输出交给监护人的恢复 ID
+    Start {
+        /// 要恢复的用户名，不指定时使用当前激活的账户
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// 以监护人的身份批准一个多方恢复
+    Approve {
+        /// 恢复 ID
+        recovery_id: String,
+        /// 监护人的用户名，不指定时使用当前激活的账户
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// 批准数达到门限后完成多方恢复并设置新密码
+    Complete {
+        /// 恢复 ID
+        recovery_id: String,
+        /// 要恢复的用户名，不指定时使用当前激活的账户
+        #[arg(long)]
+        user: Option<String>,
+    },
+}
+
+impl Command {
+    // 命令名称，用于 JSON 输出的 `command` 字段
+    fn name(&self) -> &'static str {
+        match self {
+            Command::Register { .. } => "register",
+            Command::Login { .. } => "login",
+            Command::ValidateSession { .. } => "validate-session",
+            Command::Introspect { .. } => "introspect",
+            Command::Params { .. } => "params",
+            Command::WatchRevocations => "watch-revocations",
+            Command::Logout { .. } => "logout",
+            Command::ChangePassword { .. } => "change-password",
+            Command::UpdateProfile { .. } => "update-profile",
+            Command::ExportData { .. } => "export-data",
+            Command::DeleteAccount { .. } => "delete-account",
+            Command::Recover { .. } => "recover",
+            Command::Accounts(AccountsCommand::List) => "accounts list",
+            Command::Accounts(AccountsCommand::Use { .. }) => "accounts use",
+            Command::Accounts(AccountsCommand::Remove { .. }) => "accounts remove",
+            Command::Qr(QrCommand::Show { .. }) => "qr show",
+            Command::Qr(QrCommand::Approve { .. }) => "qr approve",
+            Command::Guardian(GuardianCommand::Start { .. }) => "guardian start",
+            Command::Guardian(GuardianCommand::Approve { .. }) => "guardian approve",
+            Command::Guardian(GuardianCommand::Complete { .. }) => "guardian complete",
+            Command::Bench { .. } => "bench",
+            Command::Shell => "shell",
+            Command::Prove { .. } => "prove",
+            Command::Verify { .. } => "verify",
+        }
+    }
+}
+
+#[tokio::main] // 使用 tokio 宏，用于定义异步主函数
+async fn main() { // 定义异步主函数，程序的入口点
+    let cli = Cli::parse(); // 解析命令行参数
+    init_tracing(&cli); // 根据 -v / --trace 初始化跟踪输出
+
+    // 加载本地账户信息
+    let state_dir = cli.state_dir.clone().unwrap_or_else(default_state_dir);
+    let store = match AccountStore::load(&state_dir) {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("Error: could not load the client accounts: {}", e);
+            std::process::exit(EXIT_FAILURE);
+        }
+    };
+
+    let fetch_params = cli.group.as_deref() == Some(SERVER_GROUP);
+    let (zkp, params_pin) = match load_group(cli.group.as_deref().filter(|_| !fetch_params)).and_then(|zkp| {
+        let pin = cli.params_pin.as_deref().map(parse_pin).transpose()?;
+        // 本地的参数立即检查，从服务器取得的参数在连接时检查
+        match &pin {
+            Some(pin) if !fetch_params && *pin != zkp.params_hash() => Err(format!("the group parameters have fingerprint {}, expected {}", hex::encode(zkp.params_hash()), hex::encode(pin))),
+            _ => Ok((zkp, pin)),
+        }
+    }) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(EXIT_FAILURE);
+        }
+    };
+
+    // 默认附带客户端版本，命令行指定的同名键覆盖默认值
+    let mut metadata = HashMap::from([("client_version".to_string(), env!("CARGO_PKG_VERSION").to_string())]);
+    metadata.extend(cli.metadata);
+    let mut app = App::new(zkp, store, cli.output, cli.server, !cli.no_stream, Duration::from_secs(cli.timeout), metadata);
+    app.proof_hash = cli.proof_hash;
+    app.service = cli.service;
+    app.totp_code = cli.totp_code;
+    app.fetch_params = fetch_params;
+    app.params_pin = params_pin;
+    #[cfg(feature = "seeded-rng")]
+    if let Some(seed) = cli.seed {
+        app.rng = zkp_core::RandomSource::seeded(seed);
+        tracing::warn!(seed, "--seed: random values are deterministic, do not use outside of testing");
+    }
+    #[cfg(feature = "tls")]
+    if let Some(ca) = &cli.tls_ca {
+        app.tls = match tls_config(ca, cli.tls_cert.as_deref().zip(cli.tls_key.as_deref())) {
+            Ok(tls) => Some(tls),
+            Err(e) => {
+                eprintln!("Error: could not read the TLS certificates: {}", e);
+                std::process::exit(EXIT_FAILURE);
+            }
+        };
+    }
+
+    // 交互模式连接当前账户所在的服务器，并在退出前一直保持连接
+    if let Some(Command::Shell) = cli.command {
+        let active = app.store.active.clone();
+        let server = app.server_for(active.as_deref());
+        shell::run(&mut app, &server).await;
+        return;
+    }
+
+    let name = cli.command.as_ref().map_or("login", Command::name);
+    // 失败时按错误类型设置退出码，见 error 模块
+    let result = app.execute(cli.command).await;
+    app.output.print(name, &result);
+    #[cfg(feature = "otel")]
+    opentelemetry::global::shutdown_tracer_provider(); // 退出前导出尚未发送的 span
+    if let Err(failure) = result {
+        std::process::exit(failure.exit_code);
+    }
+}
+
+// 初始化跟踪输出：默认只输出警告，-v 为 info，-vv 为 debug
+// --trace（或 -vvv）输出客户端的 trace 级别信息，并同时打开 tonic/h2 等传输层的 debug 日志
+fn init_tracing(cli: &Cli) {
+    let (level, transport) = match (cli.trace, cli.verbose) {
+        (true, _) | (_, 3..) => (Level::TRACE, Level::DEBUG),
+        (_, 2) => (Level::DEBUG, Level::WARN),
+        (_, 1) => (Level::INFO, Level::WARN),
+        _ => (Level::WARN, Level::WARN),
+    };
+    let filter = Targets::new().with_target(env!("CARGO_CRATE_NAME"), level).with_default(transport);
+    // --otlp-endpoint：客户端操作的 span 不论 -v 级别都导出
+    #[cfg(feature = "otel")]
+    let otel = match cli.otlp_endpoint.as_deref().map(|endpoint| zkp_proto::otel::otlp_tracer(endpoint, "zkp-client")).transpose() {
+        Ok(tracer) => tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer).with_filter(Targets::new().with_target(env!("CARGO_CRATE_NAME"), Level::INFO))),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(EXIT_FAILURE);
+        }
+    };
+    #[cfg(not(feature = "otel"))]
+    let otel = None::<tracing_subscriber::layer::Identity>;
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr).with_filter(filter))
+        .with(otel)
+        .init();
+    flow::set_dump_values(cli.unsafe_dump_values);
+    if cli.unsafe_dump_values {
+        tracing::warn!("--unsafe-dump-values: protocol values will be printed in full");
+    }
+}
+
+// 由 --tls-ca、--tls-cert 和 --tls-key 构建 TLS 配置，服务器证书必须由 --tls-ca 中的 CA 签发
+#[cfg(feature = "tls")]
+fn tls_config(ca: &std::path::Path, identity: Option<(&std::path::Path, &std::path::Path)>) -> std::io::Result<tonic::transport::ClientTlsConfig> {
+    use tonic::transport::{Certificate, ClientTlsConfig, Identity};
+    let mut tls = ClientTlsConfig::new().ca_certificate(Certificate::from_pem(std::fs::read(ca)?));
+    if let Some((cert, key)) = identity {
+        tls = tls.identity(Identity::from_pem(std::fs::read(cert)?, std::fs::read(key)?));
+    }
+    Ok(tls)
+}
+
+// --group 指定的群参数，未指定时为内置的 RFC 5114 群
+fn load_group(group: Option<&str>) -> Result<ZKP, String> {
+    let path = match group {
+        None | Some(BUILTIN_GROUP) => return Ok(ZKP::get_constants()),
+        Some(path) => path,
+    };
+    let text = std::fs::read_to_string(path).map_err(|e| format!("could not read group parameters {}: {}", path, e))?;
+    ZKP::load_params(&text).ok_or_else(|| format!("{} does not contain valid group parameters", path))
+}
+
+// --params-pin 指定的指纹
+fn parse_pin(pin: &str) -> Result<Vec<u8>, String> {
+    hex::decode(pin.trim()).map_err(|e| format!("invalid --params-pin {}: {}", pin, e))
+}
+
+// 默认的客户端状态目录：$HOME/.zkp-client，没有 HOME 时使用当前目录
+fn default_state_dir() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".zkp-client")
+}
+
+// 解析 --metadata 的 KEY=VALUE 参数
+fn parse_metadata(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got {:?}", arg)),
+    }
+}
+
+// 打印提示并从终端读取一行输入，去除首尾空白
+// 提示信息输出到 stderr，保证 stdout 上只有命令结果
+fn prompt(message: &str) -> String {
+    eprintln!("{}", message);
+    let mut buf = String::new(); // 创建一个空的 String，用于存储用户输入
+    read_line(&mut buf); // 从终端读取用户输入
+    buf.trim().to_string()
+}
+
+// 从 stdin 读取一行，无法读取时打印错误并退出
+fn read_line(buf: &mut String) {
+    if let Err(e) = stdin().read_line(buf) {
+        eprintln!("Error: could not read from stdin: {}", e);
+        std::process::exit(EXIT_FAILURE);
+    }
+}
+
+// 读取密码，返回密码的字节表示，用作私钥 x
+// 在终端中输入时不回显，输入来自管道时按普通行读取
+// 读取缓冲区和返回值都在释放时清零，调用方在算出 y1、y2 或 s 后应尽快释放
+fn read_password(message: &str) -> Zeroizing<Vec<u8>> {
+    let password = if stdin().is_terminal() {
+        match rpassword::prompt_password(format!("{}\n", message)) {
+            Ok(password) => Zeroizing::new(password),
+            Err(e) => {
+                eprintln!("Error: could not read the password from the terminal: {}", e);
+                std::process::exit(EXIT_FAILURE);
+            }
+        }
+    } else {
+        eprintln!("{}", message);
+        // 预先分配容量，避免读取过程中重新分配而在堆上留下未清零的副本
+        let mut buf = Zeroizing::new(String::with_capacity(256));
+        read_line(&mut buf);
+        buf
+    };
+    Zeroizing::new(password.trim().as_bytes().to_vec())
+}
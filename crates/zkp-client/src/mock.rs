@@ -0,0 +1,299 @@
+use std::collections::HashMap; // 注册的用户和未完成的挑战
+use std::sync::atomic::{AtomicUsize, Ordering}; // 各个调用的次数
+use std::sync::{Arc, Mutex}; // 测试代码和服务器共享状态
+use std::time::{Duration, SystemTime, UNIX_EPOCH}; // 模拟慢响应、计算挑战的过期时间
+
+use num_bigint::BigUint; // 公开值、承诺和挑战
+use tokio::net::TcpListener; // 监听随机端口
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream}; // 流式响应类型、监听器包装为连接流
+use tonic::{transport::Server, Code, Request, Response, Status, Streaming}; // gRPC 服务端类型
+
+use crate::zkp_auth::auth_server::{Auth, AuthServer};
+use crate::zkp_auth::{
+    ApproveGuardianRecoveryRequest, ApproveGuardianRecoveryResponse, ApprovePendingLoginRequest, ApprovePendingLoginResponse, AuthenticateRequest, AuthenticateResponse,
+    AuthenticationAnswerRequest, AuthenticationAnswerResponse, AuthenticationChallengeRequest, AuthenticationChallengeResponse, ChangePasswordRequest, ChangePasswordResponse,
+    CompleteGuardianRecoveryRequest, CreatePendingLoginRequest, CreatePendingLoginResponse, DeleteUserDataRequest, DeleteUserDataResponse, ExportUserDataRequest,
+    ErrorCode, ExportUserDataResponse, GetAuthParametersRequest, GetAuthParametersResponse, IntrospectSessionRequest, IntrospectSessionResponse, KdfParams, LogoutRequest,
+    LogoutResponse, PollPendingLoginRequest, PollPendingLoginResponse, RecoverAccountRequest, RecoverAccountResponse, RegisterRequest, RegisterResponse, ResetCredentialsRequest, ResetCredentialsResponse, RevokedSession, StartGuardianRecoveryRequest,
+    StartGuardianRecoveryResponse, UpdateProfileRequest,
+    UpdateProfileResponse, ValidateSessionRequest, ValidateSessionResponse, WatchRevocationsRequest,
+};
+use zkp_core::ZKP;
+use zkp_proto::detail; // 错误的结构化详情
+use zkp_proto::retry; // 限流时的重试提示
+
+/// 模拟服务器发出挑战的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChallengeBehavior {
+    /// 正常的挑战
+    Correct,
+    /// 发给客户端的挑战值与服务器验证时使用的不同，应答总是被拒绝
+    WrongChallenge,
+    /// 挑战在发出时就已经过期，提交应答时返回 NotFound
+    Expired,
+    /// 请求挑战时返回 ResourceExhausted，并建议 3 秒后重试
+    Throttled,
+}
+
+// 一个已注册的用户
+struct MockUser {
+    y1: BigUint,
+    y2: BigUint,
+    salt: Vec<u8>,
+    kdf: Option<KdfParams>,
+}
+
+// 一个未完成的挑战
+struct MockChallenge {
+    user: String,
+    r1: BigUint,
+    r2: BigUint,
+    c: BigUint,
+    expires_at: u64,
+}
+
+// 服务器状态，测试代码持有同一份状态，用于读取调用次数
+struct MockState {
+    zkp: ZKP,
+    challenge: ChallengeBehavior,
+    delay: Duration,
+    users: Mutex<HashMap<String, MockUser>>,
+    challenges: Mutex<HashMap<String, MockChallenge>>,
+    next_id: AtomicUsize,
+    challenge_calls: AtomicUsize,
+    verify_calls: AtomicUsize,
+    stream_calls: AtomicUsize,
+}
+
+/// 用于测试客户端的模拟认证服务器
+///
+/// 注册、挑战和应答按协议处理，其他调用返回 Unimplemented；可以设置挑战的行为以及每个响应之前的延迟。
+/// 流式认证总是（在延迟之后）返回 Unimplemented，客户端因此退回到一元调用
+#[derive(Clone)]
+pub struct MockAuthServer {
+    state: Arc<MockState>,
+}
+
+impl MockAuthServer {
+    pub fn new() -> Self {
+        Self::with(ChallengeBehavior::Correct, Duration::ZERO)
+    }
+
+    /// 参数:
+    /// - `challenge`: 发出挑战的方式
+    /// - `delay`: 每个响应之前的延迟，用于模拟慢速服务器
+    pub fn with(challenge: ChallengeBehavior, delay: Duration) -> Self {
+        let state = MockState {
+            zkp: ZKP::get_constants(),
+            challenge,
+            delay,
+            users: Mutex::new(HashMap::new()),
+            challenges: Mutex::new(HashMap::new()),
+            next_id: AtomicUsize::new(0),
+            challenge_calls: AtomicUsize::new(0),
+            verify_calls: AtomicUsize::new(0),
+            stream_calls: AtomicUsize::new(0),
+        };
+        MockAuthServer { state: Arc::new(state) }
+    }
+
+    /// 在 127.0.0.1 的随机端口上启动服务器，服务器在测试运行时结束时退出
+    ///
+    /// 返回:
+    /// - `String`: 服务器地址，例如 http://127.0.0.1:41234
+    pub async fn spawn(&self) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("could not bind mock server");
+        let addr = listener.local_addr().expect("mock server has no address");
+        let service = AuthServer::new(self.clone());
+        tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+        format!("http://{}", addr)
+    }
+
+    /// CreateAuthenticationChallenge 被调用的次数
+    pub fn challenge_calls(&self) -> usize {
+        self.state.challenge_calls.load(Ordering::Relaxed)
+    }
+
+    /// VerifyAuthentication 被调用的次数
+    pub fn verify_calls(&self) -> usize {
+        self.state.verify_calls.load(Ordering::Relaxed)
+    }
+
+    /// Authenticate（流式认证）被调用的次数
+    pub fn stream_calls(&self) -> usize {
+        self.state.stream_calls.load(Ordering::Relaxed)
+    }
+
+    async fn delay(&self) {
+        if !self.state.delay.is_zero() {
+            tokio::time::sleep(self.state.delay).await;
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+#[tonic::async_trait]
+impl Auth for MockAuthServer {
+    async fn get_auth_parameters(&self, _request: Request<GetAuthParametersRequest>) -> Result<Response<GetAuthParametersResponse>, Status> {
+        self.delay().await;
+        let zkp = &self.state.zkp;
+        Ok(Response::new(GetAuthParametersResponse {
+            p: zkp.p.to_bytes_be(),
+            q: zkp.q.to_bytes_be(),
+            alpha: zkp.alpha.to_bytes_be(),
+            beta: zkp.beta.to_bytes_be(),
+            params_hash: zkp.params_hash(),
+            group: "rfc5114-1024".to_string(),
+            accepted_params_hashes: vec![zkp.params_hash()],
+        }))
+    }
+
+    async fn register(&self, request: Request<RegisterRequest>) -> Result<Response<RegisterResponse>, Status> {
+        self.delay().await;
+        let request = request.into_inner();
+        if request.user.is_empty() {
+            return Err(Status::invalid_argument("user name must not be empty"));
+        }
+        let user = MockUser {
+            y1: BigUint::from_bytes_be(&request.y1),
+            y2: BigUint::from_bytes_be(&request.y2),
+            salt: request.salt,
+            kdf: request.kdf,
+        };
+        let mut users = self.state.users.lock().unwrap();
+        if users.contains_key(&request.user) {
+            return Err(Status::already_exists(format!("User: {} already exists", request.user)));
+        }
+        users.insert(request.user, user);
+        Ok(Response::new(RegisterResponse::default()))
+    }
+
+    async fn create_authentication_challenge(&self, request: Request<AuthenticationChallengeRequest>) -> Result<Response<AuthenticationChallengeResponse>, Status> {
+        self.state.challenge_calls.fetch_add(1, Ordering::Relaxed);
+        self.delay().await;
+        let request = request.into_inner();
+        let (salt, kdf) = match self.state.users.lock().unwrap().get(&request.user) {
+            Some(user) => (user.salt.clone(), user.kdf.clone()),
+            None => return Err(Status::not_found(format!("User: {} not found in database", request.user))),
+        };
+
+        if self.state.challenge == ChallengeBehavior::Throttled {
+            return Err(detail::with_error_code(retry::with_retry_after(Code::ResourceExhausted, "too many challenges", Duration::from_secs(3)), ErrorCode::RateLimited));
+        }
+
+        let c = ZKP::generate_random_number_below(&self.state.zkp.q);
+        let (sent_c, expires_at) = match self.state.challenge {
+            ChallengeBehavior::Correct => (c.clone(), unix_now() + 60),
+            ChallengeBehavior::WrongChallenge => (&c + 1u32, unix_now() + 60),
+            ChallengeBehavior::Expired => (c.clone(), unix_now() - 1),
+            ChallengeBehavior::Throttled => unreachable!("throttled before issuing a challenge"),
+        };
+        let auth_id = format!("mock-{}", self.state.next_id.fetch_add(1, Ordering::Relaxed));
+        let challenge = MockChallenge {
+            user: request.user,
+            r1: BigUint::from_bytes_be(&request.r1),
+            r2: BigUint::from_bytes_be(&request.r2),
+            c,
+            expires_at,
+        };
+        self.state.challenges.lock().unwrap().insert(auth_id.clone(), challenge);
+        Ok(Response::new(AuthenticationChallengeResponse { auth_id, c: sent_c.to_bytes_be(), salt, kdf, expires_at, channel_bound: false, params_hash: self.state.zkp.params_hash() }))
+    }
+
+    async fn verify_authentication(&self, request: Request<AuthenticationAnswerRequest>) -> Result<Response<AuthenticationAnswerResponse>, Status> {
+        self.state.verify_calls.fetch_add(1, Ordering::Relaxed);
+        self.delay().await;
+        let request = request.into_inner();
+        let challenge = match self.state.challenges.lock().unwrap().remove(&request.auth_id) {
+            Some(challenge) if challenge.expires_at > unix_now() => challenge,
+            Some(_) => return Err(Status::not_found(format!("AuthId: {} expired", request.auth_id))),
+            None => return Err(Status::not_found(format!("AuthId: {} not found in database", request.auth_id))),
+        };
+        let users = self.state.users.lock().unwrap();
+        let user = users.get(&challenge.user).ok_or_else(|| Status::not_found(format!("User: {} not found in database", challenge.user)))?;
+        let s = BigUint::from_bytes_be(&request.s);
+        if self.state.zkp.verify(&challenge.r1, &challenge.r2, &user.y1, &user.y2, &challenge.c, &s) {
+            let session_id = format!("session-{}", request.auth_id);
+            Ok(Response::new(AuthenticationAnswerResponse { session_id, expires_at: unix_now() + 3600, scopes: vec!["user".to_string()], token: String::new() }))
+        } else {
+            Err(detail::error(Code::PermissionDenied, ErrorCode::BadProof, format!("AuthId: {} bad solution to the challenge", request.auth_id)))
+        }
+    }
+
+    async fn validate_session(&self, _request: Request<ValidateSessionRequest>) -> Result<Response<ValidateSessionResponse>, Status> {
+        Err(Status::unimplemented("not supported by the mock server"))
+    }
+
+    async fn introspect_session(&self, _request: Request<IntrospectSessionRequest>) -> Result<Response<IntrospectSessionResponse>, Status> {
+        Err(Status::unimplemented("not supported by the mock server"))
+    }
+
+    async fn logout(&self, _request: Request<LogoutRequest>) -> Result<Response<LogoutResponse>, Status> {
+        Err(Status::unimplemented("not supported by the mock server"))
+    }
+
+    type WatchRevocationsStream = ReceiverStream<Result<RevokedSession, Status>>;
+
+    async fn watch_revocations(&self, _request: Request<WatchRevocationsRequest>) -> Result<Response<Self::WatchRevocationsStream>, Status> {
+        Err(Status::unimplemented("not supported by the mock server"))
+    }
+
+    async fn change_password(&self, _request: Request<ChangePasswordRequest>) -> Result<Response<ChangePasswordResponse>, Status> {
+        Err(Status::unimplemented("not supported by the mock server"))
+    }
+
+    async fn create_pending_login(&self, _request: Request<CreatePendingLoginRequest>) -> Result<Response<CreatePendingLoginResponse>, Status> {
+        Err(Status::unimplemented("not supported by the mock server"))
+    }
+
+    async fn approve_pending_login(&self, _request: Request<ApprovePendingLoginRequest>) -> Result<Response<ApprovePendingLoginResponse>, Status> {
+        Err(Status::unimplemented("not supported by the mock server"))
+    }
+
+    async fn poll_pending_login(&self, _request: Request<PollPendingLoginRequest>) -> Result<Response<PollPendingLoginResponse>, Status> {
+        Err(Status::unimplemented("not supported by the mock server"))
+    }
+
+    async fn update_profile(&self, _request: Request<UpdateProfileRequest>) -> Result<Response<UpdateProfileResponse>, Status> {
+        Err(Status::unimplemented("not supported by the mock server"))
+    }
+
+    async fn export_user_data(&self, _request: Request<ExportUserDataRequest>) -> Result<Response<ExportUserDataResponse>, Status> {
+        Err(Status::unimplemented("not supported by the mock server"))
+    }
+
+    async fn delete_user_data(&self, _request: Request<DeleteUserDataRequest>) -> Result<Response<DeleteUserDataResponse>, Status> {
+        Err(Status::unimplemented("not supported by the mock server"))
+    }
+
+    async fn recover_account(&self, _request: Request<RecoverAccountRequest>) -> Result<Response<RecoverAccountResponse>, Status> {
+        Err(Status::unimplemented("not supported by the mock server"))
+    }
+
+    async fn reset_credentials(&self, _request: Request<ResetCredentialsRequest>) -> Result<Response<ResetCredentialsResponse>, Status> {
+        Err(Status::unimplemented("not supported by the mock server"))
+    }
+
+    async fn start_guardian_recovery(&self, _request: Request<StartGuardianRecoveryRequest>) -> Result<Response<StartGuardianRecoveryResponse>, Status> {
+        Err(Status::unimplemented("not supported by the mock server"))
+    }
+
+    async fn approve_guardian_recovery(&self, _request: Request<ApproveGuardianRecoveryRequest>) -> Result<Response<ApproveGuardianRecoveryResponse>, Status> {
+        Err(Status::unimplemented("not supported by the mock server"))
+    }
+
+    async fn complete_guardian_recovery(&self, _request: Request<CompleteGuardianRecoveryRequest>) -> Result<Response<RecoverAccountResponse>, Status> {
+        Err(Status::unimplemented("not supported by the mock server"))
+    }
+
+    type AuthenticateStream = ReceiverStream<Result<AuthenticateResponse, Status>>;
+
+    async fn authenticate(&self, _request: Request<Streaming<AuthenticateRequest>>) -> Result<Response<Self::AuthenticateStream>, Status> {
+        self.state.stream_calls.fetch_add(1, Ordering::Relaxed);
+        self.delay().await;
+        Err(Status::unimplemented("streaming authentication is not supported by the mock server"))
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt::Display; // 文本模式下的输出内容
+use std::time::Duration; // 服务器建议的重试等待时间
+
+use clap::ValueEnum; // 作为命令行参数取值
+use serde_json::{json, Value}; // JSON 模式下的结构化输出
+use tonic::Status; // gRPC 错误
+
+use zkp_proto::{detail, retry, CORRELATION_ID_HEADER}; // 服务器在错误中返回的错误分类、重试提示和关联 ID
+
+use crate::error::{ClientError, EXIT_FAILURE}; // 协议流程的错误与进程退出码
+
+/// 客户端的输出格式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// 面向人阅读的文本输出
+    Text,
+    /// 每个命令输出一行 JSON，便于脚本和测试工具解析
+    Json,
+}
+
+/// 命令执行成功时的结果
+pub struct Report {
+    pub text: String, // 文本模式下打印的内容
+    pub fields: Value, // JSON 模式下输出的字段（JSON 对象）
+}
+
+impl Report {
+    pub fn new(text: impl Into<String>, fields: Value) -> Self {
+        Report { text: text.into(), fields }
+    }
+}
+
+/// 命令执行失败时的原因
+pub struct Failure {
+    pub context: String, // 失败发生在哪一步（例如 "could not register"）
+    pub status: Box<Status>, // 服务器返回（或本地构造）的 gRPC 错误，装箱以减小 Result 的体积
+    pub exit_code: i32, // 命令行模式下的进程退出码
+}
+
+impl Failure {
+    // 本地错误（参数、账户、文件），退出码为 1
+    pub fn new(context: &str, status: Status) -> Self {
+        Failure { context: context.to_string(), status: Box::new(status), exit_code: EXIT_FAILURE }
+    }
+
+    // 协议流程的错误，退出码由错误类型决定
+    pub fn from_error(context: &str, error: ClientError) -> Self {
+        let exit_code = error.exit_code();
+        Failure { context: context.to_string(), status: Box::new(error.into_status()), exit_code }
+    }
+
+    // 服务器返回的关联 ID，用于在服务器日志中查找这次失败；本地错误和连接错误没有
+    pub fn correlation_id(&self) -> Option<&str> {
+        self.status.metadata().get(CORRELATION_ID_HEADER).and_then(|value| value.to_str().ok())
+    }
+
+    // 服务器限流或锁定账户时建议的等待时间
+    pub fn retry_after(&self) -> Option<Duration> {
+        retry::retry_after(&self.status)
+    }
+}
+
+/// 为 gRPC 调用结果附加失败上下文
+pub trait Context<T> {
+    fn context(self, context: &str) -> Result<T, Failure>;
+}
+
+impl<T> Context<T> for Result<T, Status> {
+    fn context(self, context: &str) -> Result<T, Failure> {
+        self.map_err(|status| Failure::from_error(context, status.into()))
+    }
+}
+
+impl<T> Context<T> for Result<T, ClientError> {
+    fn context(self, context: &str) -> Result<T, Failure> {
+        self.map_err(|error| Failure::from_error(context, error))
+    }
+}
+
+impl OutputFormat {
+    /// 打印过程中的提示信息，JSON 模式下不输出，避免破坏结构化结果
+    pub fn info(self, text: impl Display) {
+        if self == OutputFormat::Text {
+            println!("{}", text);
+        }
+    }
+
+    /// 打印命令的执行结果，返回命令是否成功
+    ///
+    /// 参数:
+    /// - `command`: 命令名称，写入 JSON 的 `command` 字段
+    /// - `result`: 命令执行结果
+    pub fn print(self, command: &str, result: &Result<Report, Failure>) -> bool {
+        match (self, result) {
+            (OutputFormat::Text, Ok(report)) => println!("{}", report.text),
+            (OutputFormat::Text, Err(failure)) => {
+                // 关联 ID 和重试提示附在错误信息后面
+                let notes: Vec<String> = [
+                    failure.correlation_id().map(|id| format!("correlation id: {}", id)),
+                    failure.retry_after().map(|after| format!("retry after {}s", after.as_secs())),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+                if notes.is_empty() {
+                    eprintln!("Error: {}: {}", failure.context, failure.status.message());
+                } else {
+                    eprintln!("Error: {}: {} ({})", failure.context, failure.status.message(), notes.join(", "));
+                }
+            }
+            (OutputFormat::Json, Ok(report)) => {
+                let mut object = json!({ "command": command, "ok": true });
+                if let (Some(object), Value::Object(fields)) = (object.as_object_mut(), &report.fields) {
+                    object.extend(fields.clone());
+                }
+                println!("{}", object);
+            }
+            (OutputFormat::Json, Err(failure)) => println!(
+                "{}",
+                json!({
+                    "command": command,
+                    "ok": false,
+                    "error": {
+                        "context": failure.context,
+                        "code": format!("{:?}", failure.status.code()),
+                        "error_code": detail::error_code(&failure.status).as_str_name(),
+                        "message": failure.status.message(),
+                        "correlation_id": failure.correlation_id(),
+                        "retry_after_secs": failure.retry_after().map(|after| after.as_secs()),
+                    },
+                })
+            ),
+        }
+        result.is_ok()
+    }
+}
@@ -0,0 +1,46 @@
+use qrcode::render::unicode; // 在终端中用 Unicode 半块字符绘制二维码
+use qrcode::QrCode; // 二维码编码
+use serde::{Deserialize, Serialize}; // 二维码内容的 JSON 编码
+use tonic::Status; // 解码失败时的错误
+
+use crate::output::Failure; // 命令失败原因
+
+/// 跨设备登录的二维码内容：持有密码的设备扫描后即可批准登录
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginTicket {
+    pub server: String,     // 服务器地址
+    pub user: String,       // 要登录的用户名
+    pub pending_id: String, // 待完成登录的标识符
+    pub nonce: String,      // 待完成登录的随机数
+}
+
+impl LoginTicket {
+    /// 编码为二维码中的文本（一行 JSON）
+    pub fn encode(&self) -> String {
+        serde_json::to_string(self).expect("login ticket is serializable")
+    }
+
+    /// 从扫描得到的文本解码
+    ///
+    /// 参数:
+    /// - `payload`: 二维码中的文本，允许带有首尾空白
+    pub fn decode(payload: &str) -> Result<Self, Failure> {
+        serde_json::from_str(payload.trim())
+            .map_err(|e| Failure::new("could not read the QR code", Status::invalid_argument(format!("not a login QR code: {}", e))))
+    }
+
+    /// 将编码后的文本绘制为可以在终端中扫描的二维码
+    pub fn render(&self) -> String {
+        render(&self.encode())
+    }
+}
+
+/// 将文本（登录票据、otpauth:// URI）绘制为可以在终端中扫描的二维码
+pub fn render(payload: &str) -> String {
+    let code = QrCode::new(payload).expect("payload fits in a QR code");
+    // 终端通常是深色背景，反转颜色使二维码在扫描器看来是白底黑块
+    code.render::<unicode::Dense1x2>()
+        .dark_color(unicode::Dense1x2::Light)
+        .light_color(unicode::Dense1x2::Dark)
+        .build()
+}
@@ -0,0 +1,70 @@
+use std::io::{stdin, Write}; // 读取交互输入、刷新提示符
+
+use clap::{Parser, Subcommand}; // 复用命令行的子命令定义解析每一行输入
+
+use crate::app::App; // 命令执行
+use crate::Command; // 客户端子命令
+
+// 交互模式下的一行输入，不包含程序名
+#[derive(Parser)]
+#[command(no_binary_name = true, disable_version_flag = true, override_usage = "<COMMAND> [OPTIONS]")]
+struct ShellLine {
+    #[command(subcommand)]
+    command: ShellCommand,
+}
+
+#[derive(Subcommand)]
+enum ShellCommand {
+    /// 与命令行相同的客户端命令
+    #[command(flatten)]
+    Client(Command),
+    /// 退出交互模式
+    #[command(alias = "quit")]
+    Exit,
+}
+
+/// 交互模式：保持一个到服务器的连接，逐行读取并执行命令，直到 `exit` 或输入结束
+///
+/// 参数:
+/// - `app`: 客户端状态，所有命令共享同一个 `App`，因此连接和账户在命令之间保持
+/// - `server`: 启动时连接的服务器地址
+pub async fn run(app: &mut App, server: &str) {
+    if let Err(failure) = app.client(server).await {
+        eprintln!("Error: {}: {}", failure.context, failure.status.message());
+        return;
+    }
+    eprintln!("Type `help` for the list of commands, `exit` to quit");
+
+    loop {
+        // 提示符输出到 stderr，保证 JSON 模式下 stdout 上只有命令结果
+        eprint!("zkp> ");
+        std::io::stderr().flush().ok();
+
+        let mut line = String::new();
+        match stdin().read_line(&mut line) {
+            Ok(0) => break, // 输入结束（Ctrl-D）
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Error: could not read from stdin: {}", e);
+                break;
+            }
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+
+        // 解析失败（包括 help）时由 clap 打印说明，继续等待下一条命令
+        let command = match ShellLine::try_parse_from(words) {
+            Ok(ShellLine { command: ShellCommand::Exit }) => break,
+            Ok(ShellLine { command: ShellCommand::Client(command) }) => command,
+            Err(e) => {
+                e.print().ok();
+                continue;
+            }
+        };
+        let name = command.name();
+        let result = app.execute(Some(command)).await;
+        app.output.print(name, &result);
+    }
+}
@@ -0,0 +1,72 @@
+use zkp_core::RandomSource; // 生成随机密钥
+
+// 新启用 TOTP 时生成的共享密钥长度（字节），与常见的认证器应用一致
+const SECRET_LEN: usize = 20;
+
+// 认证器应用使用的 Base32 字母表（RFC 4648）
+const BASE32: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+// otpauth:// URI 中的发行者，认证器应用以此区分不同服务的验证码
+const ISSUER: &str = "zkp-auth";
+
+/// 注册时启用 TOTP 第二因素的共享密钥：发送给服务器，同时以 otpauth:// URI 的形式交给用户的认证器应用
+pub struct TotpSecret(pub Vec<u8>);
+
+impl TotpSecret {
+    /// 生成随机的共享密钥
+    ///
+    /// 参数:
+    /// - `rng`: 随机数来源
+    pub fn generate(rng: &RandomSource) -> Self {
+        let mut secret = vec![0u8; SECRET_LEN];
+        rng.fill_bytes(&mut secret);
+        TotpSecret(secret)
+    }
+
+    /// 认证器应用中手动输入时使用的 Base32 编码（无填充）
+    pub fn base32(&self) -> String {
+        let mut text = String::with_capacity(self.0.len().div_ceil(5) * 8);
+        for chunk in self.0.chunks(5) {
+            let mut block = [0u8; 5];
+            block[..chunk.len()].copy_from_slice(chunk);
+            let bits = block.iter().fold(0u64, |bits, &byte| bits << 8 | u64::from(byte));
+            let symbols = (chunk.len() * 8).div_ceil(5);
+            text.extend((0..symbols).map(|i| BASE32[(bits >> (35 - 5 * i) & 0x1f) as usize] as char));
+        }
+        text
+    }
+
+    /// 认证器应用扫描的 otpauth:// URI
+    ///
+    /// 参数:
+    /// - `user`: 用户名，显示在认证器应用中
+    pub fn uri(&self, user: &str) -> String {
+        format!("otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits=6&period=30", ISSUER, escape(user), self.base32(), ISSUER)
+    }
+}
+
+// URI 中的用户名：非保留字符之外的字节都按百分号编码
+fn escape(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_base32_and_uri() {
+        // RFC 4648 第 10 节的测试向量（去掉填充）
+        for (bytes, expected) in [("", ""), ("f", "MY"), ("fo", "MZXQ"), ("foo", "MZXW6"), ("foob", "MZXW6YQ"), ("fooba", "MZXW6YTB"), ("foobar", "MZXW6YTBOI")] {
+            assert_eq!(TotpSecret(bytes.as_bytes().to_vec()).base32(), expected);
+        }
+        let secret = TotpSecret(b"foobar".to_vec());
+        assert_eq!(secret.uri("alice bob"), "otpauth://totp/zkp-auth:alice%20bob?secret=MZXW6YTBOI&issuer=zkp-auth&algorithm=SHA1&digits=6&period=30");
+        assert_eq!(TotpSecret::generate(&RandomSource::default()).0.len(), SECRET_LEN);
+    }
+}
@@ -0,0 +1,65 @@
+// 2048 位群上批量验证的线程数扩展
+// 参数文件由 zkp-paramgen generate --p-bits 2048 --q-bits 224/256 生成
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rayon::ThreadPoolBuilder;
+use zkp_core::{NonInteractiveProof, ZKP};
+
+// 每批验证的证明数
+const BATCH: usize = 64;
+
+fn groups() -> Vec<(&'static str, ZKP)> {
+    [
+        ("2048_224", include_str!("params/2048_224.json")),
+        ("2048_256", include_str!("params/2048_256.json")),
+    ]
+    .into_iter()
+    .map(|(name, text)| (name, ZKP::load_params(text).expect("bench parameters are valid")))
+    .collect()
+}
+
+fn proofs(zkp: &ZKP) -> Vec<NonInteractiveProof> {
+    (0..BATCH as u32)
+        .map(|i| zkp.prove_non_interactive(&ZKP::generate_random_number_below(&zkp.q), &i.to_be_bytes()))
+        .collect()
+}
+
+// 1、2、4 …… 直到 CPU 核数个线程
+fn thread_counts() -> Vec<usize> {
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut counts: Vec<usize> = std::iter::successors(Some(1), |n| Some(n * 2)).take_while(|n| *n < cores).collect();
+    counts.push(cores);
+    counts
+}
+
+fn batch_verify(c: &mut Criterion) {
+    for (name, zkp) in groups() {
+        let proofs = proofs(&zkp);
+        let mut group = c.benchmark_group(format!("verify_batch/{}", name));
+        group.throughput(Throughput::Elements(BATCH as u64)).sample_size(10);
+        for threads in thread_counts() {
+            let pool = ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            group.bench_with_input(BenchmarkId::from_parameter(threads), &proofs, |b, proofs| {
+                b.iter(|| pool.install(|| assert!(zkp.verify_batch(proofs).into_iter().all(|valid| valid))))
+            });
+        }
+        group.finish();
+    }
+}
+
+fn single_verify(c: &mut Criterion) {
+    for (name, zkp) in groups() {
+        let proof = zkp.prove_non_interactive(&ZKP::generate_random_number_below(&zkp.q), b"bench");
+        let mut group = c.benchmark_group(format!("verify_non_interactive/{}", name));
+        for threads in [1, 4] {
+            let pool = ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            group.bench_function(BenchmarkId::from_parameter(threads), |b| {
+                b.iter(|| pool.install(|| assert!(zkp.verify_non_interactive(&proof))))
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, batch_verify, single_verify);
+criterion_main!(benches);
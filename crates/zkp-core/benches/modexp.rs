@@ -0,0 +1,30 @@
+// 2048 位群上各个模幂后端的对比：单次模幂（指数为 q 以内的随机数）和一次完整的非交互式验证
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use zkp_core::arith::{ModExp, NumBigint, OpenSsl};
+use zkp_core::ZKP;
+
+fn backends() -> Vec<Box<dyn ModExp>> {
+    vec![Box::new(NumBigint), Box::new(OpenSsl)]
+}
+
+fn modexp(c: &mut Criterion) {
+    for (name, text) in [("2048_224", include_str!("params/2048_224.json")), ("2048_256", include_str!("params/2048_256.json"))] {
+        let zkp = ZKP::load_params(text).expect("bench parameters are valid");
+        let exponent = ZKP::generate_random_number_below(&zkp.q);
+        let mut group = c.benchmark_group(format!("modpow/{}", name));
+        for backend in backends() {
+            group.bench_function(BenchmarkId::from_parameter(backend.name()), |b| {
+                b.iter(|| backend.modpow(&zkp.alpha, &exponent, &zkp.p))
+            });
+        }
+        group.finish();
+
+        // 完整验证使用编译时选定的后端（开启 openssl feature 时为 OpenSSL）
+        let proof = zkp.prove_non_interactive(&ZKP::generate_random_number_below(&zkp.q), b"bench");
+        c.bench_function(&format!("verify_non_interactive/{}", name), |b| b.iter(|| assert!(zkp.verify_non_interactive(&proof))));
+    }
+}
+
+criterion_group!(benches, modexp);
+criterion_main!(benches);
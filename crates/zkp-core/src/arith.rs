@@ -0,0 +1,92 @@
+use num_bigint::BigUint;
+
+/// 模幂运算的后端，验证的耗时几乎全部在模幂上
+///
+/// 默认使用 num-bigint；开启 `openssl` feature 后 `exponentiate` 和验证改用 OpenSSL 的 BN_mod_exp（Montgomery 乘法，带汇编优化）
+pub trait ModExp {
+    /// 后端名称，用于基准测试的输出
+    fn name(&self) -> &'static str;
+
+    /// 计算 base^exponent mod modulus
+    ///
+    /// 参数:
+    /// - `base`: 基数
+    /// - `exponent`: 指数
+    /// - `modulus`: 模数，必须大于 0
+    ///
+    /// 返回:
+    /// - `BigUint`: 计算结果
+    fn modpow(&self, base: &BigUint, exponent: &BigUint, modulus: &BigUint) -> BigUint;
+}
+
+/// num-bigint 实现的模幂（纯 Rust）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NumBigint;
+
+impl ModExp for NumBigint {
+    fn name(&self) -> &'static str {
+        "num-bigint"
+    }
+
+    fn modpow(&self, base: &BigUint, exponent: &BigUint, modulus: &BigUint) -> BigUint {
+        base.modpow(exponent, modulus)
+    }
+}
+
+/// OpenSSL BIGNUM 实现的模幂，需要系统安装 OpenSSL
+#[cfg(feature = "openssl")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenSsl;
+
+#[cfg(feature = "openssl")]
+impl ModExp for OpenSsl {
+    fn name(&self) -> &'static str {
+        "openssl"
+    }
+
+    fn modpow(&self, base: &BigUint, exponent: &BigUint, modulus: &BigUint) -> BigUint {
+        use openssl::bn::{BigNum, BigNumContext};
+
+        // 只有内存分配失败时才会出错
+        let convert = |value: &BigUint| BigNum::from_slice(&value.to_bytes_be()).expect("BIGNUM allocation failed");
+        let mut ctx = BigNumContext::new().expect("BIGNUM allocation failed");
+        let mut result = BigNum::new().expect("BIGNUM allocation failed");
+        result
+            .mod_exp(&convert(base), &convert(exponent), &convert(modulus), &mut ctx)
+            .expect("BN_mod_exp failed");
+        BigUint::from_bytes_be(&result.to_vec())
+    }
+}
+
+/// 编译时选定的后端：开启 `openssl` feature 时为 `OpenSsl`，否则为 `NumBigint`
+#[cfg(feature = "openssl")]
+pub type Backend = OpenSsl;
+#[cfg(not(feature = "openssl"))]
+pub type Backend = NumBigint;
+
+// 使用编译时选定的后端计算模幂
+pub(crate) fn modpow(base: &BigUint, exponent: &BigUint, modulus: &BigUint) -> BigUint {
+    Backend::default().modpow(base, exponent, modulus)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ZKP;
+
+    #[test]
+    fn test_backend_matches_num_bigint() {
+        let params = ZKP::get_constants();
+        for _ in 0..4 {
+            let exponent = ZKP::generate_random_number_below(&params.q);
+            let expected = NumBigint.modpow(&params.alpha, &exponent, &params.p);
+            assert_eq!(Backend::default().modpow(&params.alpha, &exponent, &params.p), expected);
+        }
+        // 边界情况：指数为 0、基数为 0、模数为 1
+        let zero = BigUint::from(0u32);
+        let one = BigUint::from(1u32);
+        assert_eq!(Backend::default().modpow(&params.alpha, &zero, &params.p), one);
+        assert_eq!(Backend::default().modpow(&zero, &params.q, &params.p), zero);
+        assert_eq!(Backend::default().modpow(&params.alpha, &params.q, &one), zero);
+    }
+}
@@ -0,0 +1,105 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use num_bigint::BigUint;
+
+use crate::{NonInteractiveProof, ZKP};
+
+/// 同时计算两个互不依赖的值
+///
+/// 开启 `parallel` feature 时通过 rayon 在两个线程上计算，否则依次计算
+///
+/// 参数:
+/// - `a`, `b`: 两个计算
+///
+/// 返回:
+/// - `(A, B)`: 两个计算的结果
+pub fn join<A: Send, B: Send>(a: impl FnOnce() -> A + Send, b: impl FnOnce() -> B + Send) -> (A, B) {
+    #[cfg(feature = "parallel")]
+    {
+        rayon::join(a, b)
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        (a(), b())
+    }
+}
+
+/// 批量检查元素的阶，结果与逐个调用 `check_order` 相同
+/// 开启 `parallel` feature 时各个元素的模幂在 rayon 线程池中并行计算
+///
+/// 参数:
+/// - `elems`: 待检查的元素
+/// - `q`: 子群的阶（素数）
+/// - `p`: 模数
+///
+/// 返回:
+/// - `Vec<bool>`: 每个元素是否生成 q 阶子群，顺序与 `elems` 一致
+pub fn check_orders(elems: &[&BigUint], q: &BigUint, p: &BigUint) -> Vec<bool> {
+    #[cfg(feature = "parallel")]
+    {
+        elems.par_iter().map(|elem| crate::params::check_order(elem, q, p)).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        elems.iter().map(|elem| crate::params::check_order(elem, q, p)).collect()
+    }
+}
+
+// 计算 base1^e1 * base2^e2 mod p，两次模幂互不依赖
+pub(crate) fn double_exponentiate(base1: &BigUint, e1: &BigUint, base2: &BigUint, e2: &BigUint, p: &BigUint) -> BigUint {
+    let (a, b) = join(|| crate::arith::modpow(base1, e1, p), || crate::arith::modpow(base2, e2, p));
+    (a * b) % p
+}
+
+impl ZKP {
+/// 批量验证非交互式证明
+/// 开启 `parallel` feature 时各个证明在 rayon 线程池中并行验证，结果与逐个调用 `verify_non_interactive` 相同
+///
+/// 参数:
+/// - `proofs`: 待验证的证明
+///
+/// 返回:
+/// - `Vec<bool>`: 每个证明是否有效，顺序与 `proofs` 一致
+pub fn verify_batch(&self, proofs: &[NonInteractiveProof]) -> Vec<bool> {
+    #[cfg(feature = "parallel")]
+    {
+        proofs.par_iter().map(|proof| self.verify_non_interactive(proof)).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        proofs.iter().map(|proof| self.verify_non_interactive(proof)).collect()
+    }
+}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_verify_batch_matches_single_verification() {
+        let zkp = ZKP::get_constants();
+        let mut proofs: Vec<NonInteractiveProof> = (0..8u32)
+            .map(|i| zkp.prove_non_interactive(&ZKP::generate_random_number_below(&zkp.q), &i.to_be_bytes()))
+            .collect();
+        // 篡改其中一个证明的上下文
+        proofs[3].context = b"forged".to_vec();
+
+        let expected: Vec<bool> = proofs.iter().map(|proof| zkp.verify_non_interactive(proof)).collect();
+        assert_eq!(zkp.verify_batch(&proofs), expected);
+        assert_eq!(expected.iter().filter(|valid| !**valid).count(), 1);
+        assert!(zkp.verify_batch(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_check_orders_matches_single_check() {
+        // p = 23 = 2 * 11 + 1：4 的阶为 11，5 的阶为 22，22 = p - 1 的阶为 2
+        let (p, q) = (BigUint::from(23u32), BigUint::from(11u32));
+        let elems: Vec<BigUint> = [4u32, 5, 22, 1, 0, 23, 9].into_iter().map(BigUint::from).collect();
+        let refs: Vec<&BigUint> = elems.iter().collect();
+        let expected: Vec<bool> = elems.iter().map(|elem| crate::params::check_order(elem, &q, &p)).collect();
+        assert_eq!(check_orders(&refs, &q, &p), expected);
+        assert_eq!(expected, [true, false, false, false, false, false, true]);
+    }
+}
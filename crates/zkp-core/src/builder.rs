@@ -0,0 +1,78 @@
+use num_bigint::BigUint;
+
+use crate::ZKP;
+
+/// 由 p、q、alpha、beta 构造参数集，`validate()` 后 `build` 会运行 `validate_params` 的全部检查
+/// 例如 `ZKP::builder().params(p, q).generators(alpha, beta).validate().build()`
+#[derive(Debug, Clone, Default)]
+pub struct ZkpBuilder {
+    p: Option<BigUint>,
+    q: Option<BigUint>,
+    alpha: Option<BigUint>,
+    beta: Option<BigUint>,
+    validate: bool,
+}
+
+impl ZkpBuilder {
+    /// 设置素数 p 和子群的阶 q
+    pub fn params(mut self, p: BigUint, q: BigUint) -> Self {
+        self.p = Some(p);
+        self.q = Some(q);
+        self
+    }
+
+    /// 设置 q 阶子群中的两个生成元
+    pub fn generators(mut self, alpha: BigUint, beta: BigUint) -> Self {
+        self.alpha = Some(alpha);
+        self.beta = Some(beta);
+        self
+    }
+
+    /// 构造时检查参数（素性、子群、生成元），见 `validate_params`
+    pub fn validate(mut self) -> Self {
+        self.validate = true;
+        self
+    }
+
+    /// 构造参数集
+    ///
+    /// 返回:
+    /// - `Result<ZKP, String>`: 缺少参数，或开启检查时第一个不满足的条件
+    pub fn build(self) -> Result<ZKP, String> {
+        let (p, q) = self.p.zip(self.q).ok_or("p and q are not set")?;
+        let (alpha, beta) = self.alpha.zip(self.beta).ok_or("alpha and beta are not set")?;
+        let zkp = ZKP { p, q, alpha, beta };
+        if self.validate {
+            zkp.validate_params()?;
+        }
+        Ok(zkp)
+    }
+}
+
+impl ZKP {
+/// 参数集的构造器，见 `ZkpBuilder`
+pub fn builder() -> ZkpBuilder {
+    ZkpBuilder::default()
+}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_builder_validates_on_request() {
+        let toy = || ZKP::builder().params(23u32.into(), 11u32.into());
+
+        // 未开启检查时只要求参数齐全
+        assert!(toy().build().is_err());
+        let unchecked = toy().generators(4u32.into(), 4u32.into()).build().unwrap();
+        assert_eq!(unchecked.alpha, unchecked.beta);
+
+        assert_eq!(toy().generators(4u32.into(), 4u32.into()).validate().build(), Err("alpha and beta must differ".to_string()));
+        assert_eq!(toy().generators(4u32.into(), 5u32.into()).validate().build(), Err("beta does not generate the order-q subgroup".to_string()));
+        let rfc = ZKP::get_constants();
+        let built = ZKP::builder().params(rfc.p.clone(), rfc.q.clone()).generators(rfc.alpha.clone(), rfc.beta.clone()).validate().build();
+        assert_eq!(built, Ok(rfc));
+    }
+}
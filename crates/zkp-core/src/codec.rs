@@ -0,0 +1,200 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use num_bigint::BigUint;
+
+use crate::{GroupElement, GroupParams, NonInteractiveProof, Scalar};
+
+/// 文本形式证明的最大字节数（解码后），超过时不解码
+pub const MAX_PROOF_LEN: usize = 64 * 1024;
+
+// 文本编码：小写十六进制或不带填充的 base64url，解码时只接受编码器的输出（规范形式）
+#[derive(Clone, Copy)]
+enum Text {
+    Hex,
+    Base64Url,
+}
+
+impl Text {
+    fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            Text::Hex => hex::encode(bytes),
+            Text::Base64Url => URL_SAFE_NO_PAD.encode(bytes),
+        }
+    }
+
+    // 先按文本长度检查上限，再解码并确认重新编码后与输入相同（拒绝大写十六进制、填充和非零的末尾比特）
+    fn decode(self, text: &str, max_len: usize) -> Option<Vec<u8>> {
+        let max_text_len = match self {
+            Text::Hex => max_len * 2,
+            Text::Base64Url => (max_len * 4).div_ceil(3),
+        };
+        if text.len() > max_text_len {
+            return None;
+        }
+        let bytes = match self {
+            Text::Hex => hex::decode(text).ok()?,
+            Text::Base64Url => URL_SAFE_NO_PAD.decode(text).ok()?,
+        };
+        (self.encode(&bytes) == text).then_some(bytes)
+    }
+}
+
+// 数值的规范字节形式：BigUint::to_bytes_be 的输出，0 为一个 0x00 字节，其余没有前导零字节
+fn canonical_number(bytes: &[u8]) -> Option<BigUint> {
+    match bytes {
+        [] => None,
+        [0, _, ..] => None,
+        _ => Some(BigUint::from_bytes_be(bytes)),
+    }
+}
+
+// 不超过 bound 的数值最多需要的字节数
+fn byte_len(bound: &BigUint) -> usize {
+    (bound.bits() as usize).div_ceil(8).max(1)
+}
+
+impl Scalar {
+    /// 大端字节的小写十六进制
+    pub fn to_hex(&self) -> String {
+        Text::Hex.encode(&self.to_bytes_be())
+    }
+
+    /// 从 `to_hex` 的输出解码
+    ///
+    /// 参数:
+    /// - `text`: 小写十六进制，不带前导零字节
+    /// - `params`: 群参数
+    ///
+    /// 返回:
+    /// - `Option<Scalar>`: 不是规范形式、长度超过 q 或值不小于 q 时返回 None
+    pub fn from_hex(text: &str, params: &GroupParams) -> Option<Scalar> {
+        Scalar::new(canonical_number(&Text::Hex.decode(text, byte_len(&params.q))?)?, params).ok()
+    }
+
+    /// 大端字节的 base64url（不带填充）
+    pub fn to_base64url(&self) -> String {
+        Text::Base64Url.encode(&self.to_bytes_be())
+    }
+
+    /// 从 `to_base64url` 的输出解码，检查与 `from_hex` 相同
+    pub fn from_base64url(text: &str, params: &GroupParams) -> Option<Scalar> {
+        Scalar::new(canonical_number(&Text::Base64Url.decode(text, byte_len(&params.q))?)?, params).ok()
+    }
+}
+
+impl GroupElement {
+    /// 大端字节的小写十六进制
+    pub fn to_hex(&self) -> String {
+        Text::Hex.encode(&self.to_bytes_be())
+    }
+
+    /// 从 `to_hex` 的输出解码
+    ///
+    /// 参数:
+    /// - `text`: 小写十六进制，不带前导零字节
+    /// - `params`: 群参数
+    ///
+    /// 返回:
+    /// - `Option<GroupElement>`: 不是规范形式、长度超过 p 或不在 q 阶子群中时返回 None
+    pub fn from_hex(text: &str, params: &GroupParams) -> Option<GroupElement> {
+        GroupElement::new(canonical_number(&Text::Hex.decode(text, byte_len(&params.p))?)?, params).ok()
+    }
+
+    /// 大端字节的 base64url（不带填充）
+    pub fn to_base64url(&self) -> String {
+        Text::Base64Url.encode(&self.to_bytes_be())
+    }
+
+    /// 从 `to_base64url` 的输出解码，检查与 `from_hex` 相同
+    pub fn from_base64url(text: &str, params: &GroupParams) -> Option<GroupElement> {
+        GroupElement::new(canonical_number(&Text::Base64Url.decode(text, byte_len(&params.p))?)?, params).ok()
+    }
+}
+
+impl NonInteractiveProof {
+/// `to_bytes` 的小写十六进制
+pub fn to_hex(&self) -> String {
+    Text::Hex.encode(&self.to_bytes())
+}
+
+/// 从 `to_hex` 的输出解码
+///
+/// 参数:
+/// - `text`: 小写十六进制
+///
+/// 返回:
+/// - `Option<NonInteractiveProof>`: 不是规范形式、超过 `MAX_PROOF_LEN` 或 `from_bytes` 失败时返回 None
+pub fn from_hex(text: &str) -> Option<NonInteractiveProof> {
+    decode_proof(&Text::Hex.decode(text, MAX_PROOF_LEN)?)
+}
+
+/// `to_bytes` 的 base64url（不带填充）
+pub fn to_base64url(&self) -> String {
+    Text::Base64Url.encode(&self.to_bytes())
+}
+
+/// 从 `to_base64url` 的输出解码，检查与 `from_hex` 相同
+pub fn from_base64url(text: &str) -> Option<NonInteractiveProof> {
+    decode_proof(&Text::Base64Url.decode(text, MAX_PROOF_LEN)?)
+}
+}
+
+// 字段中的数值带有前导零时，同一个证明有多种字节表示，只接受 to_bytes（或旧格式编码器）的输出
+fn decode_proof(bytes: &[u8]) -> Option<NonInteractiveProof> {
+    let proof = NonInteractiveProof::from_bytes(bytes)?;
+    let canonical = if bytes.starts_with(crate::proof::PROOF_MAGIC) { proof.to_bytes() } else { proof.to_legacy_bytes() };
+    (canonical == bytes).then_some(proof)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ZKP;
+
+    #[test]
+    fn test_scalar_and_element_codecs() {
+        let zkp = ZKP::get_constants();
+        let x = Scalar::random(&zkp);
+        let (y1, _) = zkp.public_values(&x);
+        assert_eq!(Scalar::from_hex(&x.to_hex(), &zkp), Some(x.clone()));
+        assert_eq!(Scalar::from_base64url(&x.to_base64url(), &zkp), Some(x));
+        assert_eq!(GroupElement::from_hex(&y1.to_hex(), &zkp), Some(y1.clone()));
+        assert_eq!(GroupElement::from_base64url(&y1.to_base64url(), &zkp), Some(y1.clone()));
+
+        // 非规范形式：大写、前导零字节、奇数位、填充
+        assert!(GroupElement::from_hex(&y1.to_hex().to_uppercase(), &zkp).is_none());
+        assert!(GroupElement::from_hex(&format!("00{}", y1.to_hex()), &zkp).is_none());
+        assert!(Scalar::from_hex("abc", &zkp).is_none());
+        assert!(Scalar::from_hex("", &zkp).is_none());
+        assert!(Scalar::from_base64url("AQ==", &zkp).is_none());
+        // base64url 中 "AR" 的末尾比特不为零，与 "AQ" 解码出同一个字节
+        assert_eq!(Scalar::from_base64url("AQ", &zkp).map(|s| s.to_hex()), Some("01".to_string()));
+        assert!(Scalar::from_base64url("AR", &zkp).is_none());
+        assert_eq!(Scalar::from_hex("00", &zkp).map(|s| s.to_hex()), Some("00".to_string()));
+
+        // 超出范围或长度
+        assert!(Scalar::from_hex(&hex::encode(zkp.q.to_bytes_be()), &zkp).is_none());
+        assert!(GroupElement::from_hex(&"ff".repeat(200), &zkp).is_none());
+        assert!(GroupElement::from_hex(&hex::encode(zkp.p.to_bytes_be()), &zkp).is_none());
+    }
+
+    #[test]
+    fn test_proof_codecs() {
+        let zkp = ZKP::get_constants();
+        let proof = zkp.prove_non_interactive(&ZKP::generate_random_number_below(&zkp.q), b"ticket-42");
+        assert_eq!(NonInteractiveProof::from_hex(&proof.to_hex()), Some(proof.clone()));
+        assert_eq!(NonInteractiveProof::from_base64url(&proof.to_base64url()), Some(proof.clone()));
+
+        // 在 y1 字段前加一个零字节：from_bytes 接受，文本解码拒绝
+        let bytes = proof.to_legacy_bytes();
+        let y1_len = u32::from_be_bytes(bytes[..4].try_into().unwrap());
+        let mut padded = (y1_len + 1).to_be_bytes().to_vec();
+        padded.push(0);
+        padded.extend_from_slice(&bytes[4..]);
+        let legacy = NonInteractiveProof { group: Vec::new(), ..proof };
+        assert_eq!(NonInteractiveProof::from_hex(&hex::encode(legacy.to_legacy_bytes())), Some(legacy.clone()));
+        assert_eq!(NonInteractiveProof::from_bytes(&padded), Some(legacy));
+        assert!(NonInteractiveProof::from_hex(&hex::encode(&padded)).is_none());
+
+        assert!(NonInteractiveProof::from_hex(&"00".repeat(MAX_PROOF_LEN + 1)).is_none());
+    }
+}
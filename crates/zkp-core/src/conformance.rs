@@ -0,0 +1,264 @@
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{HashAlgorithm, NonInteractiveProof, ParamsFile, ZKP};
+
+/// 测试向量文件：一组参数以及在这组参数下的测试用例
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorFile {
+    pub description: String,
+    pub group: ParamsFile,
+    pub cases: Vec<Case>,
+}
+
+/// 一个测试用例：输入和期望的输出，数值均为小写十六进制（无前导零），布尔值为 JSON 布尔值
+///
+/// 用例类型:
+/// - `public`: x -> y1, y2
+/// - `solve`: k, c, x -> s
+/// - `verify`: r1, r2, y1, y2, c, s -> valid
+/// - `fiat_shamir`: y1, y2, r1, r2, context（十六进制字节）, hash（可选，默认 sha256）-> c
+/// - `proof`: proof（`NonInteractiveProof::to_bytes` 的十六进制）-> valid
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Case {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub inputs: BTreeMap<String, Value>,
+    pub outputs: BTreeMap<String, Value>,
+}
+
+/// 一个用例在某个实现上的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Pass,
+    Fail(String),
+    Unsupported,
+}
+
+/// 被测试的实现
+pub trait Implementation {
+    /// 在兼容性矩阵中显示的名称
+    fn name(&self) -> &str;
+
+    /// 计算一个用例的输出
+    ///
+    /// 参数:
+    /// - `group`: 用例所在文件的参数
+    /// - `case`: 用例，只应读取其中的类型和输入
+    ///
+    /// 返回:
+    /// - `Result<Option<BTreeMap<String, Value>>, String>`: 计算出的输出，不支持该用例类型时为 None
+    fn evaluate(&mut self, group: &ParamsFile, case: &Case) -> Result<Option<BTreeMap<String, Value>>, String>;
+}
+
+/// 在一个实现上运行用例，并与期望的输出比较
+///
+/// 参数:
+/// - `implementation`: 被测试的实现
+/// - `group`: 用例所在文件的参数
+/// - `case`: 用例
+///
+/// 返回:
+/// - `Verdict`: 用例的结果
+pub fn run_case(implementation: &mut dyn Implementation, group: &ParamsFile, case: &Case) -> Verdict {
+    match implementation.evaluate(group, case) {
+        Ok(Some(outputs)) if outputs == case.outputs => Verdict::Pass,
+        Ok(Some(outputs)) => Verdict::Fail(format!("expected {}, got {}", json!(case.outputs), json!(outputs))),
+        Ok(None) => Verdict::Unsupported,
+        Err(message) => Verdict::Fail(message),
+    }
+}
+
+/// 本库的证明者和验证者
+pub struct Native;
+
+impl Implementation for Native {
+    fn name(&self) -> &str {
+        "rust"
+    }
+
+    fn evaluate(&mut self, group: &ParamsFile, case: &Case) -> Result<Option<BTreeMap<String, Value>>, String> {
+        let zkp = ZKP::from_params_file(group).ok_or("invalid group parameters")?;
+        let number = |key: &str| -> Result<BigUint, String> {
+            let value = case.inputs.get(key).and_then(Value::as_str).ok_or_else(|| format!("missing input {}", key))?;
+            BigUint::parse_bytes(value.as_bytes(), 16).ok_or_else(|| format!("input {} is not hex", key))
+        };
+        let bytes = |key: &str| -> Result<Vec<u8>, String> {
+            let value = case.inputs.get(key).and_then(Value::as_str).ok_or_else(|| format!("missing input {}", key))?;
+            hex::decode(value).map_err(|_| format!("input {} is not hex", key))
+        };
+        let hex = |value: &BigUint| Value::String(value.to_str_radix(16));
+
+        let outputs: Vec<(&str, Value)> = match case.kind.as_str() {
+            "public" => {
+                let x = number("x")?;
+                vec![("y1", hex(&ZKP::exponentiate(&zkp.alpha, &x, &zkp.p))), ("y2", hex(&ZKP::exponentiate(&zkp.beta, &x, &zkp.p)))]
+            }
+            "solve" => vec![("s", hex(&zkp.solve(&number("k")?, &number("c")?, &number("x")?)))],
+            "verify" => {
+                let valid = zkp.verify(&number("r1")?, &number("r2")?, &number("y1")?, &number("y2")?, &number("c")?, &number("s")?);
+                vec![("valid", Value::Bool(valid))]
+            }
+            "fiat_shamir" => {
+                let hash = match case.inputs.get("hash").and_then(Value::as_str) {
+                    Some(name) => name.parse()?,
+                    None => HashAlgorithm::Sha256,
+                };
+                let c = zkp.fiat_shamir_challenge_with(hash, &number("y1")?, &number("y2")?, &number("r1")?, &number("r2")?, &bytes("context")?);
+                vec![("c", hex(&c))]
+            }
+            "proof" => {
+                let valid = NonInteractiveProof::from_bytes(&bytes("proof")?).is_some_and(|proof| zkp.verify_non_interactive(&proof));
+                vec![("valid", Value::Bool(valid))]
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(outputs.into_iter().map(|(key, value)| (key.to_string(), value)).collect()))
+    }
+}
+
+/// 通过子进程协议测试的外部实现
+///
+/// 协议：每个用例向子进程的 stdin 写入一行 JSON `{"group": {...}, "type": "...", "inputs": {...}}`，
+/// 子进程在 stdout 回复一行 JSON：`{"outputs": {...}}`、`{"unsupported": true}` 或 `{"error": "..."}`
+pub struct External {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl External {
+    /// 通过 `sh -c` 启动外部实现
+    ///
+    /// 参数:
+    /// - `name`: 在兼容性矩阵中显示的名称
+    /// - `command`: 启动命令
+    ///
+    /// 返回:
+    /// - `std::io::Result<External>`: 无法启动子进程时返回错误
+    pub fn spawn(name: &str, command: &str) -> std::io::Result<Self> {
+        let mut child = Command::new("sh").arg("-c").arg(command).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        Ok(External { name: name.to_string(), child, stdin, stdout })
+    }
+}
+
+impl Drop for External {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Implementation for External {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn evaluate(&mut self, group: &ParamsFile, case: &Case) -> Result<Option<BTreeMap<String, Value>>, String> {
+        let request = json!({ "group": group, "type": case.kind, "inputs": case.inputs });
+        writeln!(self.stdin, "{}", request).and_then(|_| self.stdin.flush()).map_err(|e| format!("could not write to {}: {}", self.name, e))?;
+
+        let mut line = String::new();
+        match self.stdout.read_line(&mut line) {
+            Ok(0) => return Err(format!("{} exited", self.name)),
+            Ok(_) => {}
+            Err(e) => return Err(format!("could not read from {}: {}", self.name, e)),
+        }
+        let response: Value = serde_json::from_str(&line).map_err(|e| format!("{} sent invalid JSON: {}", self.name, e))?;
+        if response["unsupported"] == Value::Bool(true) {
+            return Ok(None);
+        }
+        if let Some(error) = response["error"].as_str() {
+            return Err(error.to_string());
+        }
+        serde_json::from_value(response["outputs"].clone()).map(Some).map_err(|_| format!("{} sent no outputs", self.name))
+    }
+}
+
+/// 子进程协议的服务端：从 `input` 逐行读取请求，用本库计算并写入 `output`，用于验证协议本身，也可以作为其他实现的参考
+///
+/// 参数:
+/// - `input`: 请求流
+/// - `output`: 响应流
+///
+/// 返回:
+/// - `std::io::Result<()>`: 读写失败时返回错误
+pub fn serve(input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => {
+                let case = Case {
+                    name: String::new(),
+                    kind: request["type"].as_str().unwrap_or_default().to_string(),
+                    inputs: serde_json::from_value(request["inputs"].clone()).unwrap_or_default(),
+                    outputs: BTreeMap::new(),
+                };
+                match serde_json::from_value::<ParamsFile>(request["group"].clone()) {
+                    Ok(group) => match Native.evaluate(&group, &case) {
+                        Ok(Some(outputs)) => json!({ "outputs": outputs }),
+                        Ok(None) => json!({ "unsupported": true }),
+                        Err(error) => json!({ "error": error }),
+                    },
+                    Err(e) => json!({ "error": format!("invalid group: {}", e) }),
+                }
+            }
+            Err(e) => json!({ "error": format!("invalid request: {}", e) }),
+        };
+        writeln!(output, "{}", response)?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn toy_group() -> ParamsFile {
+        ParamsFile { p: "17".to_string(), q: "b".to_string(), alpha: "4".to_string(), beta: "9".to_string() }
+    }
+
+    #[test]
+    fn test_native_matches_the_toy_example() {
+        // 与 lib.rs 中 test_toy_example 的数值一致
+        let case = Case {
+            name: "toy".to_string(),
+            kind: "solve".to_string(),
+            inputs: [("k", "7"), ("c", "4"), ("x", "6")].into_iter().map(|(k, v)| (k.to_string(), json!(v))).collect(),
+            outputs: [("s".to_string(), json!("5"))].into(),
+        };
+        assert_eq!(run_case(&mut Native, &toy_group(), &case), Verdict::Pass);
+
+        let mut wrong = case.clone();
+        wrong.outputs.insert("s".to_string(), json!("6"));
+        assert!(matches!(run_case(&mut Native, &toy_group(), &wrong), Verdict::Fail(_)));
+
+        let mut unknown = case;
+        unknown.kind = "pairing".to_string();
+        assert_eq!(run_case(&mut Native, &toy_group(), &unknown), Verdict::Unsupported);
+    }
+
+    #[test]
+    fn test_serve_answers_each_line() {
+        let input = format!(
+            "{}\nnot json\n",
+            json!({ "group": toy_group(), "type": "public", "inputs": { "x": "6" } })
+        );
+        let mut output = Vec::new();
+        serve(input.as_bytes(), &mut output).unwrap();
+
+        let lines: Vec<Value> = output.split(|b| *b == b'\n').filter(|l| !l.is_empty()).map(|l| serde_json::from_slice(l).unwrap()).collect();
+        assert_eq!(lines[0], json!({ "outputs": { "y1": "2", "y2": "3" } }));
+        assert!(lines[1]["error"].is_string());
+    }
+}
@@ -0,0 +1,89 @@
+//! 分层派生：由一个主私钥为每个服务（依赖方）派生独立的子私钥，每个服务只看到自己的 (y1, y2)，
+//! 不同服务之间无法把公开值关联到同一个人；只要保管主私钥（或派生它的密码），就能重新得到所有子私钥
+
+use num_bigint::BigUint;
+
+use crate::proof::write_field;
+use crate::{registration_context, HashAlgorithm, NonInteractiveProof, Scalar, ZKP};
+
+// 子私钥派生的域标签
+const CHILD_SECRET_DOMAIN: &[u8] = b"zkp_chaum_pedersen/child-secret/v1";
+
+// 派生时拼接的哈希分组数：2 个 SHA-256 共 512 位，模 q 约简后的偏差可以忽略
+const CHILD_SECRET_BLOCKS: u8 = 2;
+
+impl ZKP {
+/// 由主私钥和服务标签派生子私钥：x' = H(domain, p, q, alpha, beta, x, label, i) 拼接后模 q
+/// 相同的输入总是得到相同的子私钥；不知道主私钥时，不同标签的子私钥和公开值看起来互不相关
+///
+/// 参数:
+/// - `master_x`: 主私钥
+/// - `service_label`: 服务的标签，例如依赖方的域名
+///
+/// 返回:
+/// - `Scalar`: 子私钥
+pub fn derive_child_secret(&self, master_x: &Scalar, service_label: &str) -> Scalar {
+    let mut wide = Vec::new();
+    for block in 0..CHILD_SECRET_BLOCKS {
+        let mut input = Vec::new();
+        write_field(&mut input, CHILD_SECRET_DOMAIN);
+        for value in [&self.p, &self.q, &self.alpha, &self.beta] {
+            write_field(&mut input, &value.to_bytes_be());
+        }
+        write_field(&mut input, &master_x.to_bytes_be());
+        write_field(&mut input, service_label.as_bytes());
+        input.push(block);
+        wide.extend(HashAlgorithm::Sha256.digest(&input));
+    }
+    Scalar::reduce(&BigUint::from_bytes_be(&wide), self)
+}
+
+/// 为服务注册派生出的身份：子私钥的持有证明，绑定到该服务中的用户名，证明中的 y1、y2 即为注册的公开值
+///
+/// 参数:
+/// - `master_x`: 主私钥
+/// - `service_label`: 服务的标签
+/// - `hash`: 持有证明使用的哈希函数
+/// - `user`: 在该服务中注册的用户名
+///
+/// 返回:
+/// - `NonInteractiveProof`: 注册请求中的 y1、y2 和持有证明 (c, s)
+pub fn child_registration(&self, master_x: &Scalar, service_label: &str, hash: HashAlgorithm, user: &str) -> NonInteractiveProof {
+    let child = self.derive_child_secret(master_x, service_label);
+    self.prove_non_interactive_with(hash, child.value(), &registration_context(user))
+}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_child_secrets_are_deterministic_and_unlinkable() {
+        let zkp = ZKP::get_constants();
+        let master = Scalar::random(&zkp);
+        let child = zkp.derive_child_secret(&master, "mail.example");
+        assert_eq!(zkp.derive_child_secret(&master, "mail.example"), child);
+        assert_ne!(zkp.derive_child_secret(&master, "shop.example"), child);
+        assert_ne!(zkp.derive_child_secret(&Scalar::random(&zkp), "mail.example"), child);
+        assert_ne!(child, master);
+
+        // 标签是长度前缀的字段，拼接方式不同的标签得到不同的子私钥
+        assert_ne!(zkp.derive_child_secret(&master, "ab"), zkp.derive_child_secret(&master, "a"));
+
+        let (y1, _) = zkp.public_values(&child);
+        let (other_y1, _) = zkp.public_values(&zkp.derive_child_secret(&master, "shop.example"));
+        assert_ne!(y1, other_y1);
+    }
+
+    #[test]
+    fn test_child_registration() {
+        let zkp = ZKP::get_constants();
+        let master = Scalar::random(&zkp);
+        let proof = zkp.child_registration(&master, "mail.example", HashAlgorithm::Sha3_256, "alice");
+        assert!(zkp.verify_non_interactive(&proof));
+        let (y1, y2) = zkp.public_values(&zkp.derive_child_secret(&master, "mail.example"));
+        assert_eq!((&proof.y1, &proof.y2), (y1.value(), y2.value()));
+        assert_eq!(proof.context, registration_context("alice"));
+    }
+}
@@ -0,0 +1,150 @@
+use num_bigint::BigUint;
+
+use crate::params::{hash_to_generator, pem_decode, pem_encode, read_der, write_der_integer, write_der_length};
+use crate::GroupParams;
+
+// `openssl dhparam` 输出的 PKCS#3 参数：SEQUENCE { p, g, privateValueLength OPTIONAL }
+const PKCS3_LABEL: &str = "DH PARAMETERS";
+
+// `openssl genpkey -algorithm DHX` 输出的 X9.42 参数：SEQUENCE { p, g, q, j OPTIONAL, validationParams OPTIONAL }
+const X942_LABEL: &str = "X9.42 DH PARAMETERS";
+
+// X9.42 参数中 q 的最小位数，用来与 PKCS#3 的 privateValueLength 区分
+const MIN_Q_BITS: u64 = 64;
+
+impl GroupParams {
+/// 从 OpenSSL 的 DH 参数 PEM 文本读取群参数，支持 `DH PARAMETERS`（PKCS#3）和 `X9.42 DH PARAMETERS`
+/// 见 `from_dhparam_der`
+///
+/// 参数:
+/// - `pem`: PEM 文本
+///
+/// 返回:
+/// - `Option<GroupParams>`: 标签不匹配或 DER 内容格式错误时返回 None
+pub fn from_dhparam_pem(pem: &str) -> Option<GroupParams> {
+    let der = pem_decode(PKCS3_LABEL, pem).or_else(|| pem_decode(X942_LABEL, pem))?;
+    GroupParams::from_dhparam_der(&der)
+}
+
+/// 从 DER 编码的 DH 参数读取群参数
+///
+/// - X9.42 参数直接给出子群的阶 q；PKCS#3 参数没有 q，此时 p 必须是安全素数，q = (p - 1) / 2
+/// - alpha 为文件中的生成元 g，g 不在 q 阶子群中时（例如安全素数下 g = 5）取 g^((p-1)/q)
+/// - beta 不保存在 DH 参数中，由 p、q 哈希派生（与 `generate_params` 相同），因此没有人知道 alpha 与 beta 的离散对数关系
+///
+/// 参数:
+/// - `der`: DER 编码的 PKCS#3 或 X9.42 参数
+///
+/// 返回:
+/// - `Option<GroupParams>`: DER 内容格式错误、PKCS#3 参数的 p 不是 2q + 1 的形式，或参数没有通过 `validate_params` 的检查时返回 None
+pub fn from_dhparam_der(der: &[u8]) -> Option<GroupParams> {
+    let mut rest = der;
+    let mut sequence = read_der(&mut rest, 0x30)?;
+    if !rest.is_empty() {
+        return None;
+    }
+    let p = BigUint::from_bytes_be(read_der(&mut sequence, 0x02)?);
+    let g = BigUint::from_bytes_be(read_der(&mut sequence, 0x02)?);
+    let one = BigUint::from(1u32);
+    if p <= one {
+        return None;
+    }
+
+    // 第三个 INTEGER 足够大且整除 p - 1 时为 X9.42 的 q，否则为 PKCS#3 的 privateValueLength（忽略）
+    let third = read_der(&mut sequence, 0x02).map(BigUint::from_bytes_be);
+    let q = match third {
+        Some(q) if q.bits() >= MIN_Q_BITS && (&p - 1u32) % &q == BigUint::from(0u32) => q,
+        _ => {
+            let q = (&p - 1u32) >> 1;
+            if &q * 2u32 + 1u32 != p || !crate::is_probable_prime(&q) {
+                return None;
+            }
+            q
+        }
+    };
+
+    let alpha = if g.modpow(&q, &p) == one { g } else { g.modpow(&((&p - 1u32) / &q), &p) };
+    let beta = hash_to_generator(&p, &q, b"beta");
+    GroupParams::builder().params(p, q).generators(alpha, beta).validate().build().ok()
+}
+
+/// 转为 OpenSSL 可以读取的 DH 参数 PEM：p = 2q + 1 时为 PKCS#3（`openssl dhparam -in ... -text`），否则为 X9.42
+/// beta 不写入文件，重新读取时由 p、q 哈希派生，因此只有 `generate_params` 生成的参数能完整往返
+///
+/// 返回:
+/// - `String`: PEM 文本
+pub fn to_dhparam_pem(&self) -> String {
+    let label = if self.is_safe_prime_group() { PKCS3_LABEL } else { X942_LABEL };
+    pem_encode(label, &self.to_dhparam_der())
+}
+
+/// 转为 DER 编码的 DH 参数：p = 2q + 1 时为 SEQUENCE { p, alpha }，否则为 SEQUENCE { p, alpha, q }
+///
+/// 返回:
+/// - `Vec<u8>`: DER 编码
+pub fn to_dhparam_der(&self) -> Vec<u8> {
+    let mut body = Vec::new();
+    write_der_integer(&mut body, &self.p);
+    write_der_integer(&mut body, &self.alpha);
+    if !self.is_safe_prime_group() {
+        write_der_integer(&mut body, &self.q);
+    }
+    let mut der = vec![0x30];
+    write_der_length(&mut der, body.len());
+    der.extend_from_slice(&body);
+    der
+}
+
+// p = 2q + 1
+fn is_safe_prime_group(&self) -> bool {
+    &self.q * 2u32 + 1u32 == self.p
+}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_openssl_dhparam_files() {
+        // openssl dhparam -out dh512.pem 512
+        let pkcs3 = GroupParams::from_dhparam_pem(include_str!("../tests/data/dh512.pem")).unwrap();
+        assert_eq!(pkcs3.p.bits(), 512);
+        assert_eq!(&pkcs3.q * 2u32 + 1u32, pkcs3.p);
+        assert_eq!(pkcs3.alpha, BigUint::from(2u32));
+        assert_eq!(pkcs3.validate_params(), Ok(()));
+
+        // openssl genpkey -genparam -algorithm DHX -pkeyopt dh_paramgen_prime_len:1024 -pkeyopt dh_paramgen_subprime_len:160
+        let x942 = GroupParams::load_params(include_str!("../tests/data/dhx1024_160.pem")).unwrap();
+        assert_eq!((x942.p.bits(), x942.q.bits()), (1024, 160));
+        assert_eq!(x942.validate_params(), Ok(()));
+    }
+
+    #[test]
+    fn test_dhparam_roundtrip() {
+        // 生成的参数中 beta 由哈希派生，可以完整往返
+        let params = GroupParams::generate_params(256, 64);
+        let pem = params.to_dhparam_pem();
+        assert!(pem.starts_with("-----BEGIN X9.42 DH PARAMETERS-----\n"));
+        assert_eq!(GroupParams::from_dhparam_pem(&pem).unwrap(), params);
+
+        // 安全素数群导出为 PKCS#3，再次导出的内容不变
+        let pkcs3 = GroupParams::from_dhparam_pem(include_str!("../tests/data/dh512.pem")).unwrap();
+        let pem = pkcs3.to_dhparam_pem();
+        assert!(pem.starts_with("-----BEGIN DH PARAMETERS-----\n"));
+        assert_eq!(GroupParams::from_dhparam_pem(&pem).unwrap(), pkcs3);
+        assert_eq!(pem.trim(), include_str!("../tests/data/dh512.pem").trim());
+    }
+
+    #[test]
+    fn test_dhparam_rejects_non_safe_prime_without_q() {
+        // p = 23 = 2 * 11 + 1 是安全素数，p = 29 不是
+        let encode = |p: u32, g: u32| {
+            let params = GroupParams { p: p.into(), q: ((p - 1) / 2).into(), alpha: g.into(), beta: g.into() };
+            params.to_dhparam_der()
+        };
+        assert!(GroupParams::from_dhparam_der(&encode(23, 4)).is_some());
+        assert!(GroupParams::from_dhparam_der(&encode(29, 4)).is_none());
+        assert!(GroupParams::from_dhparam_der(&[0x30, 0x00]).is_none());
+    }
+}
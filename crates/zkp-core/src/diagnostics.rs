@@ -0,0 +1,120 @@
+//! 验证诊断：`verify` 只返回 bool，`verify_detailed` 指出哪一个等式不成立，
+//! 开启 diagnostics feature 时还带有服务器端重新计算的 alpha^s * y1^c 和 beta^s * y2^c，便于排查不同实现之间的互通问题
+
+use std::fmt;
+
+use num_bigint::BigUint;
+
+use crate::{batch, ZKP};
+
+/// 验证的两个等式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    /// r1 = alpha^s * y1^c mod p
+    R1,
+    /// r2 = beta^s * y2^c mod p
+    R2,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Check::R1 => "r1 = alpha^s * y1^c mod p",
+            Check::R2 => "r2 = beta^s * y2^c mod p",
+        })
+    }
+}
+
+/// `verify_detailed` 的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verification {
+    pub r1_ok: bool, // r1 的等式是否成立
+    pub r2_ok: bool, // r2 的等式是否成立
+    /// 重新计算的 alpha^s * y1^c mod p，等式成立时等于 r1
+    #[cfg(feature = "diagnostics")]
+    pub expected_r1: BigUint,
+    /// 重新计算的 beta^s * y2^c mod p，等式成立时等于 r2
+    #[cfg(feature = "diagnostics")]
+    pub expected_r2: BigUint,
+}
+
+impl Verification {
+    /// 两个等式是否都成立，与 `verify` 的返回值相同
+    pub fn is_valid(&self) -> bool {
+        self.r1_ok && self.r2_ok
+    }
+
+    /// 不成立的等式
+    pub fn failed(&self) -> Vec<Check> {
+        [(Check::R1, self.r1_ok), (Check::R2, self.r2_ok)].into_iter().filter(|(_, ok)| !ok).map(|(check, _)| check).collect()
+    }
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self.failed();
+        if failed.is_empty() {
+            return f.write_str("valid");
+        }
+        let failed: Vec<String> = failed.iter().map(Check::to_string).collect();
+        write!(f, "failed: {}", failed.join(", "))?;
+        #[cfg(feature = "diagnostics")]
+        write!(f, " (expected r1 = {}, r2 = {})", self.expected_r1.to_str_radix(16), self.expected_r2.to_str_radix(16))?;
+        Ok(())
+    }
+}
+
+impl ZKP {
+/// 与 `verify` 相同的验证，返回每个等式的结果
+///
+/// 参数:
+/// - `r1`, `r2`: 承诺
+/// - `y1`, `y2`: 公开值
+/// - `c`: 挑战值
+/// - `s`: 响应
+///
+/// 返回:
+/// - `Verification`: 两个等式是否成立，开启 diagnostics feature 时带有重新计算的值
+pub fn verify_detailed(&self, r1: &BigUint, r2: &BigUint, y1: &BigUint, y2: &BigUint, c: &BigUint, s: &BigUint) -> Verification {
+    // 四次模幂互不依赖，开启 parallel feature 时并行计算
+    let (expected_r1, expected_r2) =
+        batch::join(|| batch::double_exponentiate(&self.alpha, s, y1, c, &self.p), || batch::double_exponentiate(&self.beta, s, y2, c, &self.p));
+    Verification {
+        r1_ok: *r1 == expected_r1,
+        r2_ok: *r2 == expected_r2,
+        #[cfg(feature = "diagnostics")]
+        expected_r1,
+        #[cfg(feature = "diagnostics")]
+        expected_r2,
+    }
+}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_verify_detailed_names_the_failed_check() {
+        let zkp = ZKP::get_constants();
+        let (x, k, c) = (ZKP::generate_random_number_below(&zkp.q), ZKP::generate_random_number_below(&zkp.q), ZKP::generate_random_number_below(&zkp.q));
+        let (y1, y2) = (ZKP::exponentiate(&zkp.alpha, &x, &zkp.p), ZKP::exponentiate(&zkp.beta, &x, &zkp.p));
+        let (r1, r2) = (ZKP::exponentiate(&zkp.alpha, &k, &zkp.p), ZKP::exponentiate(&zkp.beta, &k, &zkp.p));
+        let s = zkp.solve(&k, &c, &x);
+
+        let result = zkp.verify_detailed(&r1, &r2, &y1, &y2, &c, &s);
+        assert!(result.is_valid());
+        assert_eq!(result.to_string(), "valid");
+
+        // 交换 y2 只影响第二个等式
+        let result = zkp.verify_detailed(&r1, &r2, &y1, &y1, &c, &s);
+        assert_eq!(result.failed(), vec![Check::R2]);
+        assert!(result.to_string().starts_with("failed: r2 = beta^s * y2^c mod p"));
+        assert_eq!(zkp.verify(&r1, &r2, &y1, &y1, &c, &s), result.is_valid());
+
+        let result = zkp.verify_detailed(&r2, &r1, &y1, &y2, &c, &s);
+        assert_eq!(result.failed(), vec![Check::R1, Check::R2]);
+        #[cfg(feature = "diagnostics")]
+        assert_eq!((result.expected_r1, result.expected_r2), (r1, r2));
+    }
+}
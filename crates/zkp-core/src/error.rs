@@ -0,0 +1,27 @@
+use std::fmt;
+
+/// 构造指数或群元素时不满足的条件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZkpError {
+    /// 指数不小于 q
+    ScalarOutOfRange,
+    /// 群元素为 0 或不小于 p
+    ElementOutOfRange,
+    /// 群元素为 1 或 p - 1，y1 = 1 时任何 s 都满足 y1^c 的部分，验证失去意义
+    DegenerateElement,
+    /// 群元素不在 q 阶子群中
+    NotInSubgroup,
+}
+
+impl fmt::Display for ZkpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ZkpError::ScalarOutOfRange => "scalar is not below the subgroup order q",
+            ZkpError::ElementOutOfRange => "element is not in the range 0 < value < p",
+            ZkpError::DegenerateElement => "element is 1 or p - 1",
+            ZkpError::NotInSubgroup => "element is not in the order-q subgroup",
+        })
+    }
+}
+
+impl std::error::Error for ZkpError {}
@@ -0,0 +1,88 @@
+use std::fmt;
+use std::str::FromStr;
+
+use sha2::{Digest, Sha256};
+
+/// Fiat-Shamir 挑战使用的哈希函数
+///
+/// 选用的哈希记录在证明中（`NonInteractiveProof::hash`）和注册请求中（`proof_hash`），
+/// 并且除 SHA-256 外写入哈希输入，同一组值在不同哈希下得到的挑战互不相关
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum HashAlgorithm {
+    /// SHA-256，默认值，旧版本生成的证明都使用它
+    #[default]
+    Sha256,
+    /// SHA3-256（FIPS 202）
+    Sha3_256,
+    /// BLAKE3（32 字节输出）
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// 支持的全部哈希函数
+    pub const ALL: [HashAlgorithm; 3] = [HashAlgorithm::Sha256, HashAlgorithm::Sha3_256, HashAlgorithm::Blake3];
+
+    /// 哈希函数的名称，写入证明、注册请求和哈希输入
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha3_256 => "sha3-256",
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// 计算输入的 32 字节哈希值
+    ///
+    /// 参数:
+    /// - `input`: 哈希输入
+    ///
+    /// 返回:
+    /// - `Vec<u8>`: 哈希值
+    pub fn digest(self, input: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgorithm::Sha256 => digest::<Sha256>(input),
+            HashAlgorithm::Sha3_256 => digest::<sha3::Sha3_256>(input),
+            HashAlgorithm::Blake3 => blake3::hash(input).as_bytes().to_vec(),
+        }
+    }
+}
+
+// 使用任意实现了 Digest 的哈希函数（blake3 依赖的 digest 版本不同，直接调用 blake3::hash）
+fn digest<D: Digest>(input: &[u8]) -> Vec<u8> {
+    D::digest(input).to_vec()
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        HashAlgorithm::ALL
+            .into_iter()
+            .find(|hash| hash.name() == name)
+            .ok_or_else(|| format!("unknown hash function {:?}, expected one of sha256, sha3-256, blake3", name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_known_digests_and_names() {
+        // 空输入的标准哈希值
+        assert_eq!(hex::encode(HashAlgorithm::Sha256.digest(b"")), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(hex::encode(HashAlgorithm::Sha3_256.digest(b"")), "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a");
+        assert_eq!(hex::encode(HashAlgorithm::Blake3.digest(b"")), "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262");
+
+        for hash in HashAlgorithm::ALL {
+            assert_eq!(hash.name().parse::<HashAlgorithm>(), Ok(hash));
+        }
+        assert!("md5".parse::<HashAlgorithm>().is_err());
+    }
+}
@@ -0,0 +1,430 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::ZKP;
+
+// 由哈希派生生成元时使用的域分隔标签
+const GENERATOR_DOMAIN: &[u8] = b"zkp_chaum_pedersen/generator/v1";
+
+// PEM 文件的标签，内容为 DER 编码的 SEQUENCE { p, q, alpha, beta }
+const PEM_LABEL: &str = "CHAUM-PEDERSEN PARAMETERS";
+
+// Miller-Rabin 测试的轮数，错误概率不超过 4^-40
+const MILLER_RABIN_ROUNDS: usize = 40;
+
+/// 参数文件的内容：p, q, alpha, beta 的十六进制表示（JSON）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParamsFile {
+    pub p: String,
+    pub q: String,
+    pub alpha: String,
+    pub beta: String,
+}
+
+impl ZKP {
+/// 生成一组新的 Schnorr 群参数：q 为 `q_bits` 位素数，p = 2kq + 1 为 `p_bits` 位素数，
+/// alpha 和 beta 由 p、q 哈希派生，任何人都可以重新计算，确认其中没有隐藏的离散对数关系
+///
+/// 参数:
+/// - `p_bits`: p 的位数，例如 2048
+/// - `q_bits`: q 的位数，例如 256，必须小于 `p_bits`
+///
+/// 返回:
+/// - `ZKP`: 新的参数集
+pub fn generate_params(p_bits: u64, q_bits: u64) -> ZKP {
+    assert!(q_bits >= 2 && q_bits < p_bits, "q must be shorter than p");
+    loop {
+        let q = random_prime(q_bits);
+        let two_q = &q * 2u32;
+        // 对每个 q 尝试若干个 p，找不到时换一个 q
+        for _ in 0..4 * p_bits {
+            let x = random_bits(p_bits);
+            let p = &x - (&x % &two_q) + 1u32;
+            if p.bits() == p_bits && is_probable_prime(&p) {
+                let alpha = hash_to_generator(&p, &q, b"alpha");
+                let beta = hash_to_generator(&p, &q, b"beta");
+                return ZKP { p, q, alpha, beta };
+            }
+        }
+    }
+}
+
+/// 检查参数是否可用：p、q 为素数，q 整除 p - 1，alpha 和 beta 是 q 阶子群中不同的非单位元
+///
+/// 返回:
+/// - `Result<(), String>`: 第一个不满足的条件
+pub fn validate_params(&self) -> Result<(), String> {
+    if !is_probable_prime(&self.p) {
+        return Err("p is not prime".to_string());
+    }
+    if !is_probable_prime(&self.q) {
+        return Err("q is not prime".to_string());
+    }
+    if (&self.p - 1u32) % &self.q != BigUint::from(0u32) {
+        return Err("q does not divide p - 1".to_string());
+    }
+    let one = BigUint::from(1u32);
+    for (name, g) in [("alpha", &self.alpha), ("beta", &self.beta)] {
+        if *g <= one || *g >= self.p {
+            return Err(format!("{} is not in the range 1 < {} < p", name, name));
+        }
+    }
+    // 两个生成元的阶批量检查，开启 parallel feature 时并行计算
+    let orders = crate::batch::check_orders(&[&self.alpha, &self.beta], &self.q, &self.p);
+    if let Some((name, _)) = ["alpha", "beta"].into_iter().zip(orders).find(|(_, ok)| !ok) {
+        return Err(format!("{} does not generate the order-q subgroup", name));
+    }
+    if self.alpha == self.beta {
+        return Err("alpha and beta must differ".to_string());
+    }
+    Ok(())
+}
+
+/// 转为参数文件的内容
+///
+/// 返回:
+/// - `ParamsFile`: 十六进制表示的参数
+pub fn to_params_file(&self) -> ParamsFile {
+    ParamsFile {
+        p: self.p.to_str_radix(16),
+        q: self.q.to_str_radix(16),
+        alpha: self.alpha.to_str_radix(16),
+        beta: self.beta.to_str_radix(16),
+    }
+}
+
+/// 从参数文件的内容构建参数集，并运行 `validate_params` 的全部检查
+///
+/// 参数:
+/// - `file`: 十六进制表示的参数
+///
+/// 返回:
+/// - `Option<ZKP>`: 任何一个字段不是合法的十六进制数，或参数没有通过检查时返回 None
+pub fn from_params_file(file: &ParamsFile) -> Option<ZKP> {
+    let parse = |value: &str| BigUint::parse_bytes(value.as_bytes(), 16);
+    ZKP::builder().params(parse(&file.p)?, parse(&file.q)?).generators(parse(&file.alpha)?, parse(&file.beta)?).validate().build().ok()
+}
+
+/// 读取参数文件的文本，按内容自动识别 PEM（`to_pem`）、OpenSSL DH 参数（`to_dhparam_pem`）或 JSON（`to_params_file`）格式，并检查参数
+///
+/// 参数:
+/// - `text`: 参数文件的内容
+///
+/// 返回:
+/// - `Option<ZKP>`: 各种格式都无法解析，或参数没有通过检查时返回 None
+pub fn load_params(text: &str) -> Option<ZKP> {
+    let text = text.trim_start();
+    if text.starts_with("-----BEGIN DH PARAMETERS-----") || text.starts_with("-----BEGIN X9.42 DH PARAMETERS-----") {
+        ZKP::from_dhparam_pem(text)
+    } else if text.starts_with("-----BEGIN") {
+        ZKP::from_pem(text)
+    } else {
+        ZKP::from_params_file(&serde_json::from_str(text).ok()?)
+    }
+}
+
+/// 转为 PEM：DER 编码的 SEQUENCE { p, q, alpha, beta }
+///
+/// 返回:
+/// - `String`: PEM 文本
+pub fn to_pem(&self) -> String {
+    let mut body = Vec::new();
+    for value in [&self.p, &self.q, &self.alpha, &self.beta] {
+        write_der_integer(&mut body, value);
+    }
+    let mut der = vec![0x30];
+    write_der_length(&mut der, body.len());
+    der.extend_from_slice(&body);
+
+    pem_encode(PEM_LABEL, &der)
+}
+
+/// 从 `to_pem` 格式的 PEM 文本读取参数集，并运行 `validate_params` 的全部检查
+///
+/// 参数:
+/// - `pem`: PEM 文本
+///
+/// 返回:
+/// - `Option<ZKP>`: 标签不匹配、DER 内容格式错误或参数没有通过检查时返回 None
+pub fn from_pem(pem: &str) -> Option<ZKP> {
+    let der = pem_decode(PEM_LABEL, pem)?;
+
+    let mut rest = der.as_slice();
+    let mut sequence = read_der(&mut rest, 0x30)?;
+    if !rest.is_empty() {
+        return None;
+    }
+    let mut values = Vec::new();
+    for _ in 0..4 {
+        values.push(BigUint::from_bytes_be(read_der(&mut sequence, 0x02)?));
+    }
+    if !sequence.is_empty() {
+        return None;
+    }
+    let beta = values.pop()?;
+    let alpha = values.pop()?;
+    let q = values.pop()?;
+    let p = values.pop()?;
+    ZKP::builder().params(p, q).generators(alpha, beta).validate().build().ok()
+}
+}
+
+/// Miller-Rabin 素性测试（先用小素数试除）
+///
+/// 参数:
+/// - `n`: 待测试的数
+///
+/// 返回:
+/// - `bool`: n 是否（以极高的概率）为素数
+pub fn is_probable_prime(n: &BigUint) -> bool {
+    let two = BigUint::from(2u32);
+    if *n < two {
+        return false;
+    }
+    for small in SMALL_PRIMES {
+        let small = BigUint::from(*small);
+        if *n == small {
+            return true;
+        }
+        if (n % &small) == BigUint::from(0u32) {
+            return false;
+        }
+    }
+
+    // n - 1 = d * 2^r，d 为奇数
+    let one = BigUint::from(1u32);
+    let n_minus_one = n - 1u32;
+    let r = n_minus_one.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_one >> r;
+    'witness: for _ in 0..MILLER_RABIN_ROUNDS {
+        let a = ZKP::generate_random_number_below(&(n - 3u32)) + 2u32; // 2 <= a <= n - 2
+        let mut x = a.modpow(&d, n);
+        if x == one || x == n_minus_one {
+            continue;
+        }
+        for _ in 1..r {
+            x = x.modpow(&two, n);
+            if x == n_minus_one {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// 检查元素的阶恰好为 q，即 1 < elem < p 且 elem^q = 1 mod p
+/// q 为素数时阶只能是 1 或 q，因此不会把小子群（例如 p - 1 生成的 2 阶子群）中的元素当作生成元
+///
+/// 参数:
+/// - `elem`: 待检查的元素
+/// - `q`: 子群的阶（素数）
+/// - `p`: 模数
+///
+/// 返回:
+/// - `bool`: elem 是否生成 q 阶子群
+pub fn check_order(elem: &BigUint, q: &BigUint, p: &BigUint) -> bool {
+    let one = BigUint::from(1u32);
+    *elem > one && elem < p && crate::arith::modpow(elem, q, p) == one
+}
+
+/// 由种子字符串派生 q 阶子群中的一个生成元，任何人都可以用同一个种子重新计算
+/// 用于创建与 alpha、beta 独立的新生成元（例如每个用户的 beta 或承诺方案的第二个生成元），
+/// 派生过程与 `generate_params` 相同：哈希输出模 p 后乘以余因子次幂，结果为 1 时换下一个计数器重试，
+/// 因此没有人知道它与其他生成元之间的离散对数
+///
+/// 参数:
+/// - `seed`: 种子，不同的种子得到（以压倒性的概率）不同的生成元；"alpha"、"beta" 得到 `generate_params` 生成的生成元
+/// - `params`: 群参数
+///
+/// 返回:
+/// - `BigUint`: 阶为 q 的生成元
+pub fn derive_generator(seed: &str, params: &ZKP) -> BigUint {
+    hash_to_generator(&params.p, &params.q, seed.as_bytes())
+}
+
+// 用于试除的小素数
+const SMALL_PRIMES: &[u32] = &[
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131, 137, 139,
+    149, 151, 157, 163, 167, 173, 179, 181, 191, 193, 197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293,
+    307, 311, 313, 317, 331, 337, 347, 349, 353, 359, 367, 373, 379, 383, 389, 397, 401, 409, 419, 421, 431, 433, 439, 443, 449, 457, 461, 463,
+    467, 479, 487, 491, 499, 503, 509, 521, 523, 541,
+];
+
+// 最高位为 1 的 `bits` 位随机数
+fn random_bits(bits: u64) -> BigUint {
+    let mut x = ZKP::generate_random_number_below(&(BigUint::from(1u32) << (bits - 1)));
+    x.set_bit(bits - 1, true);
+    x
+}
+
+// `bits` 位的随机素数
+fn random_prime(bits: u64) -> BigUint {
+    loop {
+        let mut candidate = random_bits(bits);
+        candidate.set_bit(0, true);
+        if is_probable_prime(&candidate) {
+            return candidate;
+        }
+    }
+}
+
+// 由 p、q 和标签哈希派生 q 阶子群的生成元：h = H(domain, p, q, label, counter) mod p，g = h^((p-1)/q) mod p，
+// g 为 1 时递增 counter 重试
+pub(crate) fn hash_to_generator(p: &BigUint, q: &BigUint, label: &[u8]) -> BigUint {
+    let cofactor = (p - 1u32) / q;
+    let one = BigUint::from(1u32);
+    // 哈希输出比 p 多 64 位，使取模后的分布接近均匀
+    let blocks = (p.bits() as usize + 64).div_ceil(256);
+    for counter in 0u32.. {
+        let mut bytes = Vec::with_capacity(blocks * 32);
+        for block in 0..blocks as u32 {
+            let mut hasher = Sha256::new();
+            for field in [GENERATOR_DOMAIN, &p.to_bytes_be(), &q.to_bytes_be(), label, &counter.to_be_bytes(), &block.to_be_bytes()] {
+                hasher.update((field.len() as u32).to_be_bytes());
+                hasher.update(field);
+            }
+            bytes.extend_from_slice(&hasher.finalize());
+        }
+        let g = (BigUint::from_bytes_be(&bytes) % p).modpow(&cofactor, p);
+        if g > one {
+            return g;
+        }
+    }
+    unreachable!()
+}
+
+// PEM 编码：base64 每 64 个字符换行，加上 BEGIN / END 标签
+pub(crate) fn pem_encode(label: &str, der: &[u8]) -> String {
+    let encoded = STANDARD.encode(der);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).unwrap());
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+// PEM 解码：标签不匹配或 base64 格式错误时返回 None
+pub(crate) fn pem_decode(label: &str, pem: &str) -> Option<Vec<u8>> {
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+    let body = pem.trim().strip_prefix(&begin)?.strip_suffix(&end)?;
+    STANDARD.decode(body.split_whitespace().collect::<String>()).ok()
+}
+
+// 写入 DER 长度
+pub(crate) fn write_der_length(out: &mut Vec<u8>, len: usize) {
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len.to_be_bytes().into_iter().skip_while(|b| *b == 0).collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend_from_slice(&bytes);
+    }
+}
+
+// 写入 DER INTEGER（非负数，最高位为 1 时前补 0）
+pub(crate) fn write_der_integer(out: &mut Vec<u8>, value: &BigUint) {
+    let mut bytes = value.to_bytes_be();
+    if bytes[0] & 0x80 != 0 {
+        bytes.insert(0, 0);
+    }
+    out.push(0x02);
+    write_der_length(out, bytes.len());
+    out.extend_from_slice(&bytes);
+}
+
+// 读取一个指定标签的 DER 元素，返回其内容并前移剩余数据
+pub(crate) fn read_der<'a>(rest: &mut &'a [u8], tag: u8) -> Option<&'a [u8]> {
+    let (&actual, tail) = rest.split_first()?;
+    let (&first, mut tail) = tail.split_first()?;
+    if actual != tag {
+        return None;
+    }
+    let len = if first < 0x80 {
+        first as usize
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || tail.len() < count {
+            return None;
+        }
+        let (len, after) = tail.split_at(count);
+        tail = after;
+        len.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize)
+    };
+    if tail.len() < len {
+        return None;
+    }
+    let (content, after) = tail.split_at(len);
+    *rest = after;
+    Some(content)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn zkp() -> ZKP {
+        ZKP::get_constants()
+    }
+
+    #[test]
+    fn test_default_params_are_valid() {
+        assert_eq!(zkp().validate_params(), Ok(()));
+
+        let mut bad = zkp();
+        bad.beta = &bad.p - 1u32; // 阶为 2，不在 q 阶子群中
+        assert!(bad.validate_params().is_err());
+    }
+
+    #[test]
+    fn test_generate_small_params() {
+        let zkp = ZKP::generate_params(256, 64);
+        assert_eq!(zkp.p.bits(), 256);
+        assert_eq!(zkp.q.bits(), 64);
+        assert_eq!(zkp.validate_params(), Ok(()));
+
+        // 生成元由 p、q 确定，可以重新计算
+        assert_eq!(hash_to_generator(&zkp.p, &zkp.q, b"alpha"), zkp.alpha);
+        assert_eq!(hash_to_generator(&zkp.p, &zkp.q, b"beta"), zkp.beta);
+        assert_eq!(derive_generator("alpha", &zkp), zkp.alpha);
+
+        // 派生的生成元是确定的，不同种子得到不同的 q 阶元素
+        let g = derive_generator("user:alice", &zkp);
+        assert_eq!(g, derive_generator("user:alice", &zkp));
+        assert_ne!(g, derive_generator("user:bob", &zkp));
+        assert!(check_order(&g, &zkp.q, &zkp.p));
+    }
+
+    #[test]
+    fn test_params_file_and_pem_roundtrip() {
+        let zkp = zkp();
+        let from_file = ZKP::from_params_file(&zkp.to_params_file()).unwrap();
+        assert_eq!(from_file.params_hash(), zkp.params_hash());
+
+        let pem = zkp.to_pem();
+        assert!(pem.starts_with("-----BEGIN CHAUM-PEDERSEN PARAMETERS-----\n"));
+        let from_pem = ZKP::from_pem(&pem).unwrap();
+        assert_eq!(from_pem.params_hash(), zkp.params_hash());
+        assert!(ZKP::from_pem(&pem.replace("CHAUM-PEDERSEN", "DH")).is_none());
+
+        // load_params 按内容识别格式
+        let json = serde_json::to_string(&zkp.to_params_file()).unwrap();
+        assert_eq!(ZKP::load_params(&json).unwrap().params_hash(), zkp.params_hash());
+        assert_eq!(ZKP::load_params(&pem).unwrap().params_hash(), zkp.params_hash());
+        assert!(ZKP::load_params("p = 23").is_none());
+    }
+
+    #[test]
+    fn test_is_probable_prime() {
+        assert!(is_probable_prime(&BigUint::from(2u32)));
+        assert!(is_probable_prime(&BigUint::from(7919u32)));
+        assert!(!is_probable_prime(&BigUint::from(1u32)));
+        assert!(!is_probable_prime(&BigUint::from(561u32))); // Carmichael 数
+        assert!(!is_probable_prime(&(BigUint::from(7919u32) * 7927u32)));
+    }
+}
@@ -0,0 +1,453 @@
+use num_bigint::BigUint;
+use sha2::{Digest, Sha256};
+
+use crate::batch::{double_exponentiate, join};
+use crate::hash::HashAlgorithm;
+use crate::{RandomSource, Scalar, ZKP};
+
+// Fiat-Shamir 哈希的域分隔标签，避免与其他协议的哈希输入混淆
+const CHALLENGE_DOMAIN: &[u8] = b"zkp_chaum_pedersen/fiat-shamir/v1";
+
+// 参数集标识哈希的域分隔标签
+const PARAMS_DOMAIN: &[u8] = b"zkp_chaum_pedersen/params/v1";
+
+// 通道绑定的域分隔标签
+const CHANNEL_BINDING_DOMAIN: &[u8] = b"zkp_chaum_pedersen/channel-binding/v1";
+
+/// 通道绑定值的 TLS 导出器标签（RFC 9266 的 tls-exporter），导出长度为 `CHANNEL_BINDING_LEN`
+pub const CHANNEL_BINDING_LABEL: &str = "EXPORTER-Channel-Binding";
+
+/// 通道绑定值的长度（字节）
+pub const CHANNEL_BINDING_LEN: usize = 32;
+
+// 注册时的持有证明上下文前缀
+const REGISTRATION_DOMAIN: &[u8] = b"zkp_chaum_pedersen/register/v1:";
+
+/// 注册时持有证明（proof of possession）绑定的上下文：域标签 + 用户名
+/// 证明绑定到用户名，无法被拿去为其他用户注册
+///
+/// 参数:
+/// - `user`: 注册的用户名
+///
+/// 返回:
+/// - `Vec<u8>`: 证明的上下文
+pub fn registration_context(user: &str) -> Vec<u8> {
+    [REGISTRATION_DOMAIN, user.as_bytes()].concat()
+}
+
+/// 序列化证明的魔数，之后是 1 字节的格式版本
+/// 旧版本的格式以 4 字节的 y1 长度开头，不会以这 4 个字节开头（对应约 1.5 GB 的长度）
+pub const PROOF_MAGIC: &[u8; 4] = b"ZKPF";
+
+/// 当前的证明格式版本
+pub const PROOF_VERSION: u8 = 1;
+
+/// 非交互式 Chaum-Pedersen 证明（Fiat-Shamir 变换）
+///
+/// 包含被证明的陈述 (y1, y2)、证明 (c, s)、证明绑定的上下文、计算挑战使用的哈希函数以及生成证明时的参数集标识，
+/// 可以序列化到文件中离线传输，之后再验证。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonInteractiveProof {
+    pub y1: BigUint,
+    pub y2: BigUint,
+    pub c: BigUint,
+    pub s: BigUint,
+    pub context: Vec<u8>,
+    pub hash: HashAlgorithm,
+    pub group: Vec<u8>, // 参数集标识（`params_hash`），旧格式的证明没有记录时为空
+}
+
+impl NonInteractiveProof {
+/// 序列化为字节数组
+/// 格式：魔数 `ZKPF`、1 字节版本号，然后依次写入 group, y1, y2, c, s, context, 哈希函数名称，
+/// 每个字段为 4 字节大端长度 + 内容
+///
+/// 返回:
+/// - `Vec<u8>`: 序列化结果
+pub fn to_bytes(&self) -> Vec<u8> {
+    let mut out = PROOF_MAGIC.to_vec();
+    out.push(PROOF_VERSION);
+    write_field(&mut out, &self.group);
+    for field in [self.y1.to_bytes_be(), self.y2.to_bytes_be(), self.c.to_bytes_be(), self.s.to_bytes_be()] {
+        write_field(&mut out, &field);
+    }
+    write_field(&mut out, &self.context);
+    write_field(&mut out, self.hash.name().as_bytes());
+    out
+}
+
+// 旧版本（无版本号）的格式：y1, y2, c, s, context，哈希函数不是 SHA-256 时再写入名称
+pub(crate) fn to_legacy_bytes(&self) -> Vec<u8> {
+    let mut out = Vec::new();
+    for field in [self.y1.to_bytes_be(), self.y2.to_bytes_be(), self.c.to_bytes_be(), self.s.to_bytes_be()] {
+        write_field(&mut out, &field);
+    }
+    write_field(&mut out, &self.context);
+    if self.hash != HashAlgorithm::Sha256 {
+        write_field(&mut out, self.hash.name().as_bytes());
+    }
+    out
+}
+
+/// 从字节数组反序列化，支持 `to_bytes` 的格式以及没有魔数的旧格式（group 为空）
+///
+/// 参数:
+/// - `bytes`: 序列化的证明
+///
+/// 返回:
+/// - `Option<NonInteractiveProof>`: 版本号未知、数据被截断、带有多余字节或哈希函数未知时返回 None
+pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+    match bytes.strip_prefix(PROOF_MAGIC) {
+        Some([PROOF_VERSION, rest @ ..]) => NonInteractiveProof::from_v1(rest),
+        Some(_) => None,
+        None => NonInteractiveProof::from_legacy(bytes),
+    }
+}
+
+// 版本 1：group 之后是与旧格式相同的字段，哈希函数名称总是写出
+fn from_v1(mut rest: &[u8]) -> Option<Self> {
+    let group = read_field(&mut rest)?.to_vec();
+    let (y1, y2, c, s, context) = read_statement(&mut rest)?;
+    let hash = std::str::from_utf8(read_field(&mut rest)?).ok()?.parse().ok()?;
+    if !rest.is_empty() {
+        return None;
+    }
+    Some(NonInteractiveProof { y1, y2, c, s, context, hash, group })
+}
+
+fn from_legacy(mut rest: &[u8]) -> Option<Self> {
+    let (y1, y2, c, s, context) = read_statement(&mut rest)?;
+    // 省略哈希字段即为 SHA-256，显式写出 sha256 的编码不是旧版本的输出，同样拒绝
+    let hash = match rest.is_empty() {
+        true => HashAlgorithm::Sha256,
+        false => match std::str::from_utf8(read_field(&mut rest)?).ok()?.parse().ok()? {
+            HashAlgorithm::Sha256 => return None,
+            hash => hash,
+        },
+    };
+    if !rest.is_empty() {
+        return None;
+    }
+    Some(NonInteractiveProof { y1, y2, c, s, context, hash, group: Vec::new() })
+}
+}
+
+// 读取 y1, y2, c, s, context 五个字段
+fn read_statement(rest: &mut &[u8]) -> Option<(BigUint, BigUint, BigUint, BigUint, Vec<u8>)> {
+    let y1 = BigUint::from_bytes_be(read_field(rest)?);
+    let y2 = BigUint::from_bytes_be(read_field(rest)?);
+    let c = BigUint::from_bytes_be(read_field(rest)?);
+    let s = BigUint::from_bytes_be(read_field(rest)?);
+    let context = read_field(rest)?.to_vec();
+    Some((y1, y2, c, s, context))
+}
+
+impl ZKP {
+/// 计算参数集的标识：SHA-256(domain, p, q, alpha, beta)
+/// 客户端和服务器比较该值，确认双方使用同一组参数
+///
+/// 返回:
+/// - `Vec<u8>`: 32 字节的哈希值
+pub fn params_hash(&self) -> Vec<u8> {
+    let mut input = Vec::new();
+    write_field(&mut input, PARAMS_DOMAIN);
+    for value in [&self.p, &self.q, &self.alpha, &self.beta] {
+        write_field(&mut input, &value.to_bytes_be());
+    }
+    Sha256::digest(&input).to_vec()
+}
+
+/// 计算 Fiat-Shamir 挑战值（SHA-256）
+/// c = SHA-256(domain, p, q, alpha, beta, y1, y2, r1, r2, context) mod q
+///
+/// 参数:
+/// - `y1`, `y2`: 公开值 (alpha^x, beta^x)
+/// - `r1`, `r2`: 承诺 (alpha^k, beta^k)
+/// - `context`: 证明绑定的上下文
+///
+/// 返回:
+/// - `BigUint`: 挑战值 c
+pub fn fiat_shamir_challenge(&self, y1: &BigUint, y2: &BigUint, r1: &BigUint, r2: &BigUint, context: &[u8]) -> BigUint {
+    self.fiat_shamir_challenge_with(HashAlgorithm::Sha256, y1, y2, r1, r2, context)
+}
+
+/// 使用指定的哈希函数计算 Fiat-Shamir 挑战值
+/// c = H(domain, [hash name,] p, q, alpha, beta, y1, y2, r1, r2, context) mod q，
+/// 哈希函数名称只在不是 SHA-256 时写入，SHA-256 的输入与旧版本相同
+///
+/// 参数:
+/// - `hash`: 哈希函数
+/// - `y1`, `y2`: 公开值 (alpha^x, beta^x)
+/// - `r1`, `r2`: 承诺 (alpha^k, beta^k)
+/// - `context`: 证明绑定的上下文
+///
+/// 返回:
+/// - `BigUint`: 挑战值 c
+pub fn fiat_shamir_challenge_with(&self, hash: HashAlgorithm, y1: &BigUint, y2: &BigUint, r1: &BigUint, r2: &BigUint, context: &[u8]) -> BigUint {
+    let mut input = Vec::new();
+    write_field(&mut input, CHALLENGE_DOMAIN);
+    if hash != HashAlgorithm::Sha256 {
+        write_field(&mut input, hash.name().as_bytes());
+    }
+    for value in [&self.p, &self.q, &self.alpha, &self.beta, y1, y2, r1, r2] {
+        write_field(&mut input, &value.to_bytes_be());
+    }
+    write_field(&mut input, context);
+    BigUint::from_bytes_be(&hash.digest(&input)) % &self.q
+}
+
+/// 把通道绑定值混入服务器发出的挑战：c' = SHA-256(domain, c, r1, r2, binding) mod q
+/// 客户端和服务器各自用自己一端的 TLS 会话导出绑定值，中间人在两条连接之间转发承诺和挑战时，
+/// 两端算出的 c' 不同，应答无法通过验证
+///
+/// 参数:
+/// - `c`: 服务器发出的挑战值
+/// - `r1`, `r2`: 承诺
+/// - `binding`: 通道绑定值（TLS 导出器输出）
+///
+/// 返回:
+/// - `BigUint`: 绑定后的挑战值 c'，用于计算和验证响应 s
+pub fn bind_challenge(&self, c: &BigUint, r1: &BigUint, r2: &BigUint, binding: &[u8]) -> BigUint {
+    let mut input = Vec::new();
+    write_field(&mut input, CHANNEL_BINDING_DOMAIN);
+    for value in [c, r1, r2] {
+        write_field(&mut input, &value.to_bytes_be());
+    }
+    write_field(&mut input, binding);
+    BigUint::from_bytes_be(&Sha256::digest(&input)) % &self.q
+}
+
+/// 生成非交互式证明（SHA-256）：证明者知道 x，使得 y1 = alpha^x, y2 = beta^x
+///
+/// 参数:
+/// - `x`: 私钥
+/// - `context`: 证明绑定的上下文，验证时必须一致
+///
+/// 返回:
+/// - `NonInteractiveProof`: 包含陈述和证明
+pub fn prove_non_interactive(&self, x: &BigUint, context: &[u8]) -> NonInteractiveProof {
+    self.prove_non_interactive_with(HashAlgorithm::Sha256, x, context)
+}
+
+/// 使用指定的哈希函数生成非交互式证明，哈希函数记录在证明中
+///
+/// 参数:
+/// - `hash`: 计算挑战使用的哈希函数
+/// - `x`: 私钥
+/// - `context`: 证明绑定的上下文，验证时必须一致
+///
+/// 返回:
+/// - `NonInteractiveProof`: 包含陈述和证明
+pub fn prove_non_interactive_with(&self, hash: HashAlgorithm, x: &BigUint, context: &[u8]) -> NonInteractiveProof {
+    self.prove_non_interactive_with_rng(hash, x, context, &RandomSource::default())
+}
+
+/// 与 `prove_non_interactive_with` 相同，临时私钥 k 取自指定的随机数来源
+///
+/// 参数:
+/// - `hash`: 计算挑战使用的哈希函数
+/// - `x`: 私钥
+/// - `context`: 证明绑定的上下文，验证时必须一致
+/// - `rng`: 随机数来源
+///
+/// 返回:
+/// - `NonInteractiveProof`: 包含陈述和证明
+pub fn prove_non_interactive_with_rng(&self, hash: HashAlgorithm, x: &BigUint, context: &[u8], rng: &RandomSource) -> NonInteractiveProof {
+    let x = Scalar::reduce(x, self);
+    let (y1, y2) = self.public_values(&x);
+
+    let k = Scalar::random_with(self, rng);
+    let (r1, r2) = self.commit(&k);
+
+    let c = Scalar::reduce(&self.fiat_shamir_challenge_with(hash, y1.value(), y2.value(), r1.value(), r2.value(), context), self);
+    let s = self.respond(&k, &c, &x);
+    let group = self.params_hash();
+    NonInteractiveProof { y1: y1.into_inner(), y2: y2.into_inner(), c: c.into_inner(), s: s.into_inner(), context: context.to_vec(), hash, group }
+}
+
+/// 验证非交互式证明
+/// 证明记录了参数集标识时必须与本参数集一致；
+/// 然后恢复承诺 r1 = alpha^s * y1^c, r2 = beta^s * y2^c，再检查 c 是否等于证明中记录的哈希函数算出的挑战值
+///
+/// 参数:
+/// - `proof`: 待验证的证明
+///
+/// 返回:
+/// - `bool`: 证明是否有效
+pub fn verify_non_interactive(&self, proof: &NonInteractiveProof) -> bool {
+    if !proof.group.is_empty() && proof.group != self.params_hash() {
+        return false;
+    }
+    let (r1, r2) = join(
+        || double_exponentiate(&self.alpha, &proof.s, &proof.y1, &proof.c, &self.p),
+        || double_exponentiate(&self.beta, &proof.s, &proof.y2, &proof.c, &self.p),
+    );
+    proof.c == self.fiat_shamir_challenge_with(proof.hash, &proof.y1, &proof.y2, &r1, &r2, &proof.context)
+}
+}
+
+// 写入一个字段：4 字节大端长度 + 内容
+pub(crate) fn write_field(out: &mut Vec<u8>, field: &[u8]) {
+    out.extend_from_slice(&(field.len() as u32).to_be_bytes());
+    out.extend_from_slice(field);
+}
+
+// 读取一个字段，并前移剩余数据
+fn read_field<'a>(rest: &mut &'a [u8]) -> Option<&'a [u8]> {
+    if rest.len() < 4 {
+        return None;
+    }
+    let (len, tail) = rest.split_at(4);
+    let len = u32::from_be_bytes(len.try_into().ok()?) as usize;
+    if tail.len() < len {
+        return None;
+    }
+    let (field, tail) = tail.split_at(len);
+    *rest = tail;
+    Some(field)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn zkp() -> ZKP {
+        ZKP::get_constants()
+    }
+
+    #[test]
+    fn test_non_interactive_roundtrip() {
+        let zkp = zkp();
+        let x = ZKP::generate_random_number_below(&zkp.q);
+
+        let proof = zkp.prove_non_interactive(&x, b"ticket-42");
+        assert!(zkp.verify_non_interactive(&proof));
+
+        // 序列化后再反序列化，证明仍然有效
+        let decoded = NonInteractiveProof::from_bytes(&proof.to_bytes()).unwrap();
+        assert_eq!(decoded, proof);
+        assert!(zkp.verify_non_interactive(&decoded));
+    }
+
+    #[test]
+    fn test_non_interactive_rejects_tampering() {
+        let zkp = zkp();
+        let x = ZKP::generate_random_number_below(&zkp.q);
+        let proof = zkp.prove_non_interactive(&x, b"ticket-42");
+
+        // 修改上下文后证明失效
+        let mut other_context = proof.clone();
+        other_context.context = b"ticket-43".to_vec();
+        assert!(!zkp.verify_non_interactive(&other_context));
+
+        // 替换为其他私钥的公开值后证明失效
+        let mut other_statement = proof.clone();
+        other_statement.y1 = ZKP::exponentiate(&zkp.alpha, &(x + 1u32), &zkp.p);
+        assert!(!zkp.verify_non_interactive(&other_statement));
+    }
+
+    #[test]
+    fn test_bound_challenge_depends_on_the_channel() {
+        let zkp = zkp();
+        let (x, k) = (ZKP::generate_random_number_below(&zkp.q), ZKP::generate_random_number_below(&zkp.q));
+        let (y1, y2) = (ZKP::exponentiate(&zkp.alpha, &x, &zkp.p), ZKP::exponentiate(&zkp.beta, &x, &zkp.p));
+        let (r1, r2) = (ZKP::exponentiate(&zkp.alpha, &k, &zkp.p), ZKP::exponentiate(&zkp.beta, &k, &zkp.p));
+        let c = ZKP::generate_random_number_below(&zkp.q);
+
+        // 客户端与服务器看到同一个 TLS 会话时应答通过，中间人转发时两端的绑定值不同
+        let client = zkp.bind_challenge(&c, &r1, &r2, &[1; CHANNEL_BINDING_LEN]);
+        let s = zkp.solve(&k, &client, &x);
+        assert!(zkp.verify(&r1, &r2, &y1, &y2, &zkp.bind_challenge(&c, &r1, &r2, &[1; CHANNEL_BINDING_LEN]), &s));
+        assert!(!zkp.verify(&r1, &r2, &y1, &y2, &zkp.bind_challenge(&c, &r1, &r2, &[2; CHANNEL_BINDING_LEN]), &s));
+        assert!(!zkp.verify(&r1, &r2, &y1, &y2, &c, &s));
+    }
+
+    #[test]
+    fn test_registration_proof_is_bound_to_the_user() {
+        let zkp = zkp();
+        let x = ZKP::generate_random_number_below(&zkp.q);
+        let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+        assert!(zkp.verify_non_interactive(&proof));
+
+        // 同一个证明不能用于注册其他用户
+        let mut replayed = proof.clone();
+        replayed.context = registration_context("mallory");
+        assert!(!zkp.verify_non_interactive(&replayed));
+    }
+
+    #[test]
+    fn test_non_interactive_hash_agility() {
+        let zkp = zkp();
+        let x = ZKP::generate_random_number_below(&zkp.q);
+        for hash in HashAlgorithm::ALL {
+            let proof = zkp.prove_non_interactive_with(hash, &x, b"ticket-42");
+            assert_eq!(proof.hash, hash);
+            assert!(zkp.verify_non_interactive(&proof));
+            assert_eq!(NonInteractiveProof::from_bytes(&proof.to_bytes()).unwrap(), proof);
+
+            // 换成其他哈希函数后证明失效
+            for other in HashAlgorithm::ALL.into_iter().filter(|other| *other != hash) {
+                let relabeled = NonInteractiveProof { hash: other, ..proof.clone() };
+                assert!(!zkp.verify_non_interactive(&relabeled));
+            }
+        }
+
+        // 旧格式中 SHA-256 证明不包含哈希字段；显式写出 sha256 的编码被拒绝
+        let bytes = zkp.prove_non_interactive(&x, b"ctx").to_legacy_bytes();
+        let mut explicit = bytes.clone();
+        write_field(&mut explicit, b"sha256");
+        assert!(NonInteractiveProof::from_bytes(&explicit).is_none());
+        let mut unknown = bytes;
+        write_field(&mut unknown, b"md5");
+        assert!(NonInteractiveProof::from_bytes(&unknown).is_none());
+    }
+
+    #[test]
+    fn test_versioned_format() {
+        let zkp = zkp();
+        let x = ZKP::generate_random_number_below(&zkp.q);
+        let proof = zkp.prove_non_interactive(&x, b"ticket-42");
+        assert_eq!(proof.group, zkp.params_hash());
+        let bytes = proof.to_bytes();
+        assert!(bytes.starts_with(b"ZKPF\x01"));
+
+        // 未知的版本号被拒绝
+        let mut future = bytes.clone();
+        future[4] = 2;
+        assert!(NonInteractiveProof::from_bytes(&future).is_none());
+
+        // 旧格式仍然可以读取，没有记录参数集
+        let legacy = NonInteractiveProof::from_bytes(&proof.to_legacy_bytes()).unwrap();
+        assert!(legacy.group.is_empty());
+        assert!(zkp.verify_non_interactive(&legacy));
+
+        // 为其他参数集生成的证明不能在本参数集上验证
+        let mut other_group = proof.clone();
+        other_group.group = vec![0; 32];
+        assert!(!zkp.verify_non_interactive(&other_group));
+    }
+
+    #[test]
+    fn test_params_hash_identifies_the_group() {
+        let expected = zkp().params_hash();
+        assert_eq!(expected.len(), 32);
+        assert_eq!(zkp().params_hash(), expected);
+
+        // 任何一个参数不同，标识都不同
+        let mut other = zkp();
+        other.beta = ZKP::exponentiate(&other.alpha, &BigUint::from(7u32), &other.p);
+        assert_ne!(other.params_hash(), expected);
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_malformed_input() {
+        let zkp = zkp();
+        let x = ZKP::generate_random_number_below(&zkp.q);
+        let bytes = zkp.prove_non_interactive(&x, b"ctx").to_bytes();
+
+        assert!(NonInteractiveProof::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(NonInteractiveProof::from_bytes(&trailing).is_none());
+    }
+}
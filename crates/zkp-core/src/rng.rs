@@ -0,0 +1,70 @@
+use std::fmt;
+
+use num_bigint::{BigUint, RandBigInt};
+use rand::{Rng, RngCore};
+
+#[cfg(feature = "seeded-rng")]
+use rand::{rngs::StdRng, SeedableRng};
+#[cfg(feature = "seeded-rng")]
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// 随机数来源（k、c、auth_id、盐等），在构建服务器（`ServerConfig::rng`）和客户端连接时传入
+///
+/// 默认使用系统随机数生成器；开启 `seeded-rng` feature 时可以用 `seeded` 创建确定性的来源，使协议记录可以复现。
+/// 克隆共享同一个生成器，互不影响的服务器和客户端各自持有自己的来源
+#[derive(Clone, Default)]
+pub struct RandomSource {
+    #[cfg(feature = "seeded-rng")]
+    seeded: Option<Arc<Mutex<StdRng>>>, // 设置了种子时的确定性生成器，为 None 时使用 thread_rng
+}
+
+impl fmt::Debug for RandomSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.is_seeded() { "RandomSource(seeded)" } else { "RandomSource(system)" })
+    }
+}
+
+impl RandomSource {
+    /// 使用固定种子的确定性来源，同一个种子生成相同的随机数序列
+    ///
+    /// 仅在开启 `seeded-rng` feature 时可用，只用于测试和调试，生产环境绝不能使用
+    ///
+    /// 参数:
+    /// - `seed`: 随机数种子
+    #[cfg(feature = "seeded-rng")]
+    pub fn seeded(seed: u64) -> Self {
+        RandomSource { seeded: Some(Arc::new(Mutex::new(StdRng::seed_from_u64(seed)))) }
+    }
+
+    /// 是否为固定种子的确定性来源
+    pub fn is_seeded(&self) -> bool {
+        #[cfg(feature = "seeded-rng")]
+        return self.seeded.is_some();
+        #[cfg(not(feature = "seeded-rng"))]
+        false
+    }
+
+    /// 在 [0, bound) 中均匀选取随机数
+    pub fn number_below(&self, bound: &BigUint) -> BigUint {
+        self.with(|rng| rng.gen_biguint_below(bound))
+    }
+
+    /// 由字母和数字组成的随机字符串
+    pub fn string(&self, size: usize) -> String {
+        self.with(|rng| rng.sample_iter(rand::distributions::Alphanumeric).take(size).map(char::from).collect())
+    }
+
+    /// 用随机字节填充缓冲区（例如生成盐）
+    pub fn fill_bytes(&self, dest: &mut [u8]) {
+        self.with(|rng| rng.fill_bytes(dest))
+    }
+
+    // 使用当前的随机数生成器：设置了种子时为确定性生成器，否则为 thread_rng
+    fn with<T>(&self, f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+        #[cfg(feature = "seeded-rng")]
+        if let Some(rng) = &self.seeded {
+            return f(&mut *rng.lock().unwrap_or_else(PoisonError::into_inner));
+        }
+        f(&mut rand::thread_rng())
+    }
+}
@@ -0,0 +1,262 @@
+use std::fmt;
+
+use num_bigint::BigUint;
+
+use crate::{GroupParams, RandomSource, ZkpError, ZKP};
+
+/// 指数（私钥 x、临时私钥 k、挑战 c、响应 s），构造时保证 0 <= value < q
+///
+/// 与 `GroupElement` 是不同的类型，把公开值当作指数传入会编译失败；
+/// 私钥和临时私钥也是指数，调试输出为 `Scalar(<redacted>)`
+#[derive(Clone, PartialEq, Eq)]
+pub struct Scalar(BigUint);
+
+/// q 阶子群中的群元素（公开值 y1、y2，承诺 r1、r2），构造时保证 1 < value < p - 1 且 value^q = 1 mod p
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupElement(BigUint);
+
+impl Scalar {
+    /// 检查取值范围后构造指数，收到的挑战和响应都应经过这里
+    ///
+    /// 参数:
+    /// - `value`: 指数的值
+    /// - `params`: 群参数
+    ///
+    /// 返回:
+    /// - `Result<Scalar, ZkpError>`: value >= q 时返回 `ScalarOutOfRange`
+    pub fn new(value: BigUint, params: &GroupParams) -> Result<Scalar, ZkpError> {
+        if value < params.q {
+            Ok(Scalar(value))
+        } else {
+            Err(ZkpError::ScalarOutOfRange)
+        }
+    }
+
+    /// 从大端字节构造指数，检查同 `new`
+    pub fn from_bytes_be(bytes: &[u8], params: &GroupParams) -> Result<Scalar, ZkpError> {
+        Scalar::new(BigUint::from_bytes_be(bytes), params)
+    }
+
+    /// 将任意整数模 q 约简为指数，例如由密码字节得到的私钥
+    /// 子群的阶为 q，约简前后的指数得到相同的群元素
+    ///
+    /// 参数:
+    /// - `value`: 任意整数
+    /// - `params`: 群参数
+    ///
+    /// 返回:
+    /// - `Scalar`: value mod q
+    pub fn reduce(value: &BigUint, params: &GroupParams) -> Scalar {
+        Scalar(value % &params.q)
+    }
+
+    /// 在 [0, q) 中均匀随机选取指数
+    pub fn random(params: &GroupParams) -> Scalar {
+        Scalar::random_with(params, &RandomSource::default())
+    }
+
+    /// 使用指定的随机数来源在 [0, q) 中均匀随机选取指数
+    ///
+    /// 参数:
+    /// - `params`: 群参数
+    /// - `rng`: 随机数来源
+    pub fn random_with(params: &GroupParams, rng: &RandomSource) -> Scalar {
+        Scalar(rng.number_below(&params.q))
+    }
+
+    /// 指数的值
+    pub fn value(&self) -> &BigUint {
+        &self.0
+    }
+
+    /// 取出指数的值
+    pub fn into_inner(self) -> BigUint {
+        self.0
+    }
+
+    /// 大端字节表示
+    pub fn to_bytes_be(&self) -> Vec<u8> {
+        self.0.to_bytes_be()
+    }
+}
+
+impl fmt::Debug for Scalar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Scalar(<redacted>)")
+    }
+}
+
+impl GroupElement {
+    /// 检查取值范围和子群成员关系后构造群元素，需要一次模幂；收到的公开值和承诺都应经过这里
+    /// 1 和 p - 1 被拒绝：y1 = 1 对应私钥 0，p - 1 的阶为 2
+    ///
+    /// 参数:
+    /// - `value`: 群元素的值
+    /// - `params`: 群参数
+    ///
+    /// 返回:
+    /// - `Result<GroupElement, ZkpError>`: 第一个不满足的条件
+    pub fn new(value: BigUint, params: &GroupParams) -> Result<GroupElement, ZkpError> {
+        GroupElement::check_range(&value, params)?;
+        if !crate::check_order(&value, &params.q, &params.p) {
+            return Err(ZkpError::NotInSubgroup);
+        }
+        Ok(GroupElement(value))
+    }
+
+    /// 一次构造多个群元素，检查同 `new`；子群成员关系用 `check_orders` 批量检查，开启 parallel feature 时并行计算
+    ///
+    /// 参数:
+    /// - `values`: 群元素的值
+    /// - `params`: 群参数
+    ///
+    /// 返回:
+    /// - `Result<Vec<GroupElement>, (usize, ZkpError)>`: 第一个不满足条件的元素的下标和原因
+    pub fn new_batch(values: Vec<BigUint>, params: &GroupParams) -> Result<Vec<GroupElement>, (usize, ZkpError)> {
+        for (i, value) in values.iter().enumerate() {
+            GroupElement::check_range(value, params).map_err(|e| (i, e))?;
+        }
+        let refs: Vec<&BigUint> = values.iter().collect();
+        if let Some(i) = crate::check_orders(&refs, &params.q, &params.p).iter().position(|ok| !ok) {
+            return Err((i, ZkpError::NotInSubgroup));
+        }
+        Ok(values.into_iter().map(GroupElement).collect())
+    }
+
+    // 取值范围：0 < value < p，且不是 1 或 p - 1
+    fn check_range(value: &BigUint, params: &GroupParams) -> Result<(), ZkpError> {
+        if *value == BigUint::from(0u32) || *value >= params.p {
+            return Err(ZkpError::ElementOutOfRange);
+        }
+        if *value == BigUint::from(1u32) || *value == &params.p - 1u32 {
+            return Err(ZkpError::DegenerateElement);
+        }
+        Ok(())
+    }
+
+    /// 从大端字节构造群元素，检查同 `new`
+    pub fn from_bytes_be(bytes: &[u8], params: &GroupParams) -> Result<GroupElement, ZkpError> {
+        GroupElement::new(BigUint::from_bytes_be(bytes), params)
+    }
+
+    /// 群元素的值
+    pub fn value(&self) -> &BigUint {
+        &self.0
+    }
+
+    /// 取出群元素的值
+    pub fn into_inner(self) -> BigUint {
+        self.0
+    }
+
+    /// 大端字节表示
+    pub fn to_bytes_be(&self) -> Vec<u8> {
+        self.0.to_bytes_be()
+    }
+}
+
+impl ZKP {
+/// 计算公开值 y1 = alpha^x mod p, y2 = beta^x mod p
+/// alpha、beta 都在 q 阶子群中，结果不需要再检查
+///
+/// 参数:
+/// - `x`: 私钥
+///
+/// 返回:
+/// - `(GroupElement, GroupElement)`: (y1, y2)
+pub fn public_values(&self, x: &Scalar) -> (GroupElement, GroupElement) {
+    let (y1, y2) = crate::batch::join(|| ZKP::exponentiate(&self.alpha, &x.0, &self.p), || ZKP::exponentiate(&self.beta, &x.0, &self.p));
+    (GroupElement(y1), GroupElement(y2))
+}
+
+/// 计算承诺 r1 = alpha^k mod p, r2 = beta^k mod p
+///
+/// 参数:
+/// - `k`: 临时私钥
+///
+/// 返回:
+/// - `(GroupElement, GroupElement)`: (r1, r2)
+pub fn commit(&self, k: &Scalar) -> (GroupElement, GroupElement) {
+    self.public_values(k)
+}
+
+/// 计算响应 s = k - c * x mod q，结果总是小于 q
+///
+/// 参数:
+/// - `k`: 临时私钥
+/// - `c`: 挑战值
+/// - `x`: 私钥
+///
+/// 返回:
+/// - `Scalar`: 响应 s
+pub fn respond(&self, k: &Scalar, c: &Scalar, x: &Scalar) -> Scalar {
+    // solve 在 c * x - k 恰好是 q 的倍数时返回 q，这里约简为 0
+    Scalar::reduce(&self.solve(&k.0, &c.0, &x.0), self)
+}
+
+/// 验证 r1 = alpha^s * y1^c 且 r2 = beta^s * y2^c，参数的类型保证了取值范围和子群成员关系
+///
+/// 参数:
+/// - `r1`, `r2`: 承诺
+/// - `y1`, `y2`: 公开值
+/// - `c`: 挑战值
+/// - `s`: 响应
+///
+/// 返回:
+/// - `bool`: 验证是否通过
+pub fn check(&self, r1: &GroupElement, r2: &GroupElement, y1: &GroupElement, y2: &GroupElement, c: &Scalar, s: &Scalar) -> bool {
+    self.verify(&r1.0, &r2.0, &y1.0, &y2.0, &c.0, &s.0)
+}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn toy() -> ZKP {
+        ZKP { p: 23u32.into(), q: 11u32.into(), alpha: 4u32.into(), beta: 9u32.into() }
+    }
+
+    #[test]
+    fn test_constructors_enforce_invariants() {
+        let zkp = toy();
+        assert!(Scalar::new(10u32.into(), &zkp).is_ok());
+        assert_eq!(Scalar::new(11u32.into(), &zkp), Err(ZkpError::ScalarOutOfRange));
+        assert_eq!(Scalar::reduce(&25u32.into(), &zkp).value(), &BigUint::from(3u32));
+        assert_eq!(format!("{:?}", Scalar::reduce(&7u32.into(), &zkp)), "Scalar(<redacted>)");
+
+        // 子群 {1, 2, 3, 4, 6, 8, 9, 12, 13, 16, 18}：5 是二次非剩余，不在子群中
+        assert!(GroupElement::new(2u32.into(), &zkp).is_ok());
+        assert_eq!(GroupElement::new(5u32.into(), &zkp), Err(ZkpError::NotInSubgroup));
+        assert_eq!(GroupElement::new(0u32.into(), &zkp), Err(ZkpError::ElementOutOfRange));
+        assert_eq!(GroupElement::new(25u32.into(), &zkp), Err(ZkpError::ElementOutOfRange));
+        assert_eq!(GroupElement::new(1u32.into(), &zkp), Err(ZkpError::DegenerateElement));
+        assert_eq!(GroupElement::from_bytes_be(&[22], &zkp), Err(ZkpError::DegenerateElement));
+
+        let batch = |values: &[u32]| GroupElement::new_batch(values.iter().map(|v| BigUint::from(*v)).collect(), &zkp);
+        assert_eq!(batch(&[2, 9]).map(|elems| elems.len()), Ok(2));
+        assert_eq!(batch(&[2, 5]), Err((1, ZkpError::NotInSubgroup)));
+        // 范围检查先于子群检查
+        assert_eq!(batch(&[5, 22]), Err((1, ZkpError::DegenerateElement)));
+    }
+
+    #[test]
+    fn test_typed_protocol_run() {
+        let zkp = ZKP::get_constants();
+        let x = Scalar::random(&zkp);
+        let k = Scalar::random(&zkp);
+        let c = Scalar::random(&zkp);
+        let (y1, y2) = zkp.public_values(&x);
+        let (r1, r2) = zkp.commit(&k);
+        let s = zkp.respond(&k, &c, &x);
+        assert!(s.value() < &zkp.q);
+        assert!(zkp.check(&r1, &r2, &y1, &y2, &c, &s));
+        assert!(!zkp.check(&r2, &r1, &y1, &y2, &c, &s));
+        assert_eq!(GroupElement::new(y1.value().clone(), &zkp), Ok(y1));
+
+        // k = c * x mod q 时响应为 0 而不是 q
+        let zkp = toy();
+        let (k, c, x) = (Scalar::reduce(&12u32.into(), &zkp), Scalar::reduce(&3u32.into(), &zkp), Scalar::reduce(&4u32.into(), &zkp));
+        assert_eq!(zkp.respond(&k, &c, &x).value(), &BigUint::from(0u32));
+    }
+}
@@ -0,0 +1,46 @@
+// 固定种子后协议记录可以复现：cargo test --features seeded-rng
+#![cfg(feature = "seeded-rng")]
+
+use num_bigint::BigUint;
+use zkp_core::{HashAlgorithm, NonInteractiveProof, RandomSource, Scalar, ZKP};
+
+fn zkp() -> ZKP {
+    ZKP::get_constants()
+}
+
+// 一次完整的交互：承诺 k、挑战 c、auth_id、响应 s，以及一个非交互式证明
+fn transcript(zkp: &ZKP, x: &BigUint, rng: &RandomSource) -> (BigUint, BigUint, String, BigUint, NonInteractiveProof) {
+    let k = Scalar::random_with(zkp, rng).into_inner();
+    let c = rng.number_below(&zkp.q);
+    let auth_id = rng.string(12);
+    let s = zkp.solve(&k, &c, x);
+    let proof = zkp.prove_non_interactive_with_rng(HashAlgorithm::Sha256, x, b"golden", rng);
+    (k, c, auth_id, s, proof)
+}
+
+#[test]
+fn test_seeded_transcripts_are_reproducible() {
+    let zkp = zkp();
+    let x = BigUint::from(300u32);
+
+    let first = transcript(&zkp, &x, &RandomSource::seeded(42));
+    let second = transcript(&zkp, &x, &RandomSource::seeded(42));
+    assert_eq!(first, second);
+    assert!(zkp.verify_non_interactive(&first.4));
+
+    // 不同的种子得到不同的记录，系统随机数生成器不受影响
+    assert_ne!(transcript(&zkp, &x, &RandomSource::seeded(43)), first);
+    assert_ne!(transcript(&zkp, &x, &RandomSource::default()), first);
+
+    // 克隆共享同一个生成器，继续产生序列中后面的值
+    let rng = RandomSource::seeded(42);
+    let shared = rng.clone();
+    assert_eq!(transcript(&zkp, &x, &rng), first);
+    assert_ne!(transcript(&zkp, &x, &shared), first);
+
+    let mut salt = [0u8; 16];
+    RandomSource::seeded(42).fill_bytes(&mut salt);
+    let mut again = [0u8; 16];
+    RandomSource::seeded(42).fill_bytes(&mut again);
+    assert_eq!(salt, again);
+}
@@ -0,0 +1,23 @@
+use std::path::PathBuf;
+
+fn main() {
+    // 生成的代码和描述符集写入 OUT_DIR，由 lib.rs 引入，不再提交到源码目录
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+
+    // 配置 tonic_build 来生成 gRPC 客户端和服务器代码
+    tonic_build::configure()
+        // 同时生成服务器端代码，服务器和测试用的模拟服务器都需要
+        .build_server(true)
+        // 同时输出文件描述符集，供 gRPC reflection 等工具使用
+        .file_descriptor_set_path(out_dir.join("zkp_auth_descriptor.bin"))
+        // 调用 compile 函数来编译指定的 .proto 文件，并生成相应的 Rust 代码
+        // 第一个参数是需要编译的 .proto 文件的路径列表
+        .compile(
+            &["proto/zkp_auth.proto", "proto/zkp_auth_v2.proto"], // 需要编译的 .proto 文件：当前接口 (v1) 和第二版接口
+            &["proto/"], // 该 .proto 文件所在的目录
+        )
+        // 使用 unwrap() 确保编译成功，如果编译失败则引发 panic
+        .unwrap();
+    println!("cargo:rerun-if-changed=proto/zkp_auth.proto");
+    println!("cargo:rerun-if-changed=proto/zkp_auth_v2.proto");
+}
@@ -0,0 +1,83 @@
+//! 错误的结构化详情：服务器在 gRPC 状态的 details 中返回编码后的 `ErrorDetail`，客户端按 `ErrorCode` 分支处理，
+//! 不需要解析英文的错误信息。重试提示和校验失败的字段同时保留在元数据中（见 `retry` 和 `invalid`），详情中的值与元数据相同
+
+use prost::bytes::Bytes;
+use prost::Message;
+use tonic::{Code, Status};
+
+use crate::invalid::{INVALID_FIELD_HEADER, INVALID_REASON_HEADER};
+use crate::retry::retry_after;
+use crate::zkp_auth::{ErrorCode, ErrorDetail};
+
+/// 构造带有结构化详情的错误
+///
+/// 参数:
+/// - `code`: gRPC 错误码
+/// - `error`: 错误的分类
+/// - `message`: 错误信息
+///
+/// 返回:
+/// - `Status`: details 中带有 `ErrorDetail` 的错误
+pub fn error(code: Code, error: ErrorCode, message: impl Into<String>) -> Status {
+    with_error_code(Status::new(code, message), error)
+}
+
+/// 为已有的错误附加结构化详情，保留错误码、错误信息和元数据；元数据中的重试提示和校验失败的字段一并写入详情
+///
+/// 参数:
+/// - `status`: 原来的错误，已有的详情被替换
+/// - `error`: 错误的分类
+pub fn with_error_code(status: Status, error: ErrorCode) -> Status {
+    let header = |key: &str| status.metadata().get(key).and_then(|value| value.to_str().ok()).unwrap_or_default().to_string();
+    let detail = ErrorDetail {
+        code: error as i32,
+        message: status.message().to_string(),
+        retry_after_secs: retry_after(&status).map_or(0, |after| after.as_secs()),
+        field: header(INVALID_FIELD_HEADER),
+        reason: header(INVALID_REASON_HEADER),
+    };
+    Status::with_details_and_metadata(status.code(), status.message(), Bytes::from(detail.encode_to_vec()), status.metadata().clone())
+}
+
+/// 读取错误中的结构化详情，没有详情或详情不是 `ErrorDetail` 时返回 None
+pub fn error_detail(status: &Status) -> Option<ErrorDetail> {
+    if status.details().is_empty() {
+        return None;
+    }
+    ErrorDetail::decode(status.details()).ok()
+}
+
+/// 错误的分类，没有详情或分类未知（较新的服务器增加的值）时为 `ErrorCode::Unspecified`
+pub fn error_code(status: &Status) -> ErrorCode {
+    error_detail(status).and_then(|detail| ErrorCode::from_i32(detail.code)).unwrap_or(ErrorCode::Unspecified)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::invalid::{invalid_argument, InvalidReason};
+    use crate::retry::with_retry_after;
+
+    #[test]
+    fn test_error_detail_round_trip() {
+        let status = error(Code::NotFound, ErrorCode::UserNotFound, "User: alice not found in database");
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(error_code(&status), ErrorCode::UserNotFound);
+        assert_eq!(error_detail(&status).unwrap().message, "User: alice not found in database");
+
+        // 元数据中的重试提示和校验失败的字段写入详情，元数据本身保留
+        let status = with_error_code(with_retry_after(Code::ResourceExhausted, "too many attempts", Duration::from_secs(3)), ErrorCode::RateLimited);
+        assert_eq!(error_detail(&status).unwrap().retry_after_secs, 3);
+        assert_eq!(retry_after(&status), Some(Duration::from_secs(3)));
+        let detail = error_detail(&invalid_argument("y1", InvalidReason::NotInSubgroup, "y1: not in the subgroup")).unwrap();
+        assert_eq!((ErrorCode::from_i32(detail.code), detail.field.as_str(), detail.reason.as_str()), (Some(ErrorCode::InvalidArgument), "y1", "not-in-subgroup"));
+
+        // 没有详情、详情不是 ErrorDetail 或分类未知
+        assert_eq!(error_code(&Status::not_found("plain")), ErrorCode::Unspecified);
+        assert_eq!(error_detail(&Status::with_details(Code::Internal, "garbage", Bytes::from_static(&[0xff, 0xff]))), None);
+        let unknown = ErrorDetail { code: 999, ..Default::default() };
+        assert_eq!(error_code(&Status::with_details(Code::Internal, "newer", Bytes::from(unknown.encode_to_vec()))), ErrorCode::Unspecified);
+    }
+}
@@ -0,0 +1,144 @@
+//! 请求校验失败时的机器可读原因：服务器在 InvalidArgument 错误的元数据中给出出错的字段（`invalid-field`）和原因（`invalid-reason`），
+//! 客户端据此判断是哪个值有问题，而不是解析英文的错误信息
+
+use std::fmt;
+
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Status};
+
+use crate::detail::with_error_code;
+use crate::zkp_auth::ErrorCode;
+
+/// 出错字段所在的 gRPC 元数据键，值为 proto 中的字段名，例如 `y1`、`user`、`metadata`
+pub const INVALID_FIELD_HEADER: &str = "invalid-field";
+
+/// 出错原因所在的 gRPC 元数据键，值为 `InvalidReason::name`
+pub const INVALID_REASON_HEADER: &str = "invalid-reason";
+
+/// 字段没有通过校验的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidReason {
+    /// 必填的字段为空
+    Empty,
+    /// 超过长度限制，字节字段不能长于模数
+    TooLong,
+    /// 短于长度下限（用户名）
+    TooShort,
+    /// 条目太多（元数据、监护人、恢复码）
+    TooMany,
+    /// 指数不小于 q，或群元素为 0 或不小于 p
+    OutOfRange,
+    /// 群元素为 1 或 p - 1
+    Degenerate,
+    /// 群元素不在 q 阶子群中
+    NotInSubgroup,
+    /// 包含不允许的字符
+    BadCharacters,
+    /// 保留的用户名，不能注册
+    Reserved,
+    /// 值不在允许的集合中，例如未知的哈希函数、服务器不接受的群、重复的监护人
+    Unsupported,
+    /// 与同一请求或之前的请求中的值不一致，例如参数集标识、流上的认证 ID
+    Mismatch,
+    /// 持有证明缺失或不成立
+    InvalidProof,
+    /// 流上的消息顺序不对
+    UnexpectedMessage,
+}
+
+impl InvalidReason {
+    const ALL: [InvalidReason; 13] = [
+        InvalidReason::Empty,
+        InvalidReason::TooLong,
+        InvalidReason::TooShort,
+        InvalidReason::TooMany,
+        InvalidReason::OutOfRange,
+        InvalidReason::Degenerate,
+        InvalidReason::NotInSubgroup,
+        InvalidReason::BadCharacters,
+        InvalidReason::Reserved,
+        InvalidReason::Unsupported,
+        InvalidReason::Mismatch,
+        InvalidReason::InvalidProof,
+        InvalidReason::UnexpectedMessage,
+    ];
+
+    /// 原因的名称，即元数据中的值：empty、too-long、too-short、too-many、out-of-range、degenerate、not-in-subgroup、bad-characters、
+    /// reserved、unsupported、mismatch、invalid-proof、unexpected-message
+    pub fn name(self) -> &'static str {
+        match self {
+            InvalidReason::Empty => "empty",
+            InvalidReason::TooLong => "too-long",
+            InvalidReason::TooShort => "too-short",
+            InvalidReason::TooMany => "too-many",
+            InvalidReason::OutOfRange => "out-of-range",
+            InvalidReason::Degenerate => "degenerate",
+            InvalidReason::NotInSubgroup => "not-in-subgroup",
+            InvalidReason::BadCharacters => "bad-characters",
+            InvalidReason::Reserved => "reserved",
+            InvalidReason::Unsupported => "unsupported",
+            InvalidReason::Mismatch => "mismatch",
+            InvalidReason::InvalidProof => "invalid-proof",
+            InvalidReason::UnexpectedMessage => "unexpected-message",
+        }
+    }
+
+    /// 由名称解析原因，未知的名称（较新的服务器增加的原因）返回 None
+    pub fn from_name(name: &str) -> Option<InvalidReason> {
+        InvalidReason::ALL.into_iter().find(|reason| reason.name() == name)
+    }
+}
+
+impl fmt::Display for InvalidReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// 构造带有出错字段和原因的 InvalidArgument 错误
+///
+/// 参数:
+/// - `field`: proto 中的字段名，多个字段一起出错时为第一个
+/// - `reason`: 出错的原因
+/// - `message`: 给人看的错误信息
+///
+/// 返回:
+/// - `Status`: 元数据中带有 `invalid-field` 和 `invalid-reason`、详情为 `ErrorCode::InvalidArgument` 的 InvalidArgument 错误
+pub fn invalid_argument(field: &'static str, reason: InvalidReason, message: impl Into<String>) -> Status {
+    let mut status = Status::new(Code::InvalidArgument, message);
+    status.metadata_mut().insert(INVALID_FIELD_HEADER, MetadataValue::from_static(field));
+    status.metadata_mut().insert(INVALID_REASON_HEADER, MetadataValue::from_static(reason.name()));
+    with_error_code(status, ErrorCode::InvalidArgument)
+}
+
+/// 读取错误中的出错字段和原因，不是 InvalidArgument、没有这两项或原因未知时返回 None
+pub fn invalid_field(status: &Status) -> Option<(String, InvalidReason)> {
+    if status.code() != Code::InvalidArgument {
+        return None;
+    }
+    let field = status.metadata().get(INVALID_FIELD_HEADER)?.to_str().ok()?;
+    let reason = InvalidReason::from_name(status.metadata().get(INVALID_REASON_HEADER)?.to_str().ok()?)?;
+    Some((field.to_string(), reason))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_invalid_field_round_trip() {
+        let status = invalid_argument("y1", InvalidReason::NotInSubgroup, "y1: element is not in the order-q subgroup");
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.metadata().get(INVALID_REASON_HEADER).unwrap(), "not-in-subgroup");
+        assert_eq!(invalid_field(&status), Some(("y1".to_string(), InvalidReason::NotInSubgroup)));
+        for reason in InvalidReason::ALL {
+            assert_eq!(InvalidReason::from_name(reason.name()), Some(reason));
+        }
+
+        let mut status = Status::invalid_argument("no details");
+        assert_eq!(invalid_field(&status), None);
+        status.metadata_mut().insert(INVALID_FIELD_HEADER, "user".parse().unwrap());
+        status.metadata_mut().insert(INVALID_REASON_HEADER, "something-new".parse().unwrap());
+        assert_eq!(invalid_field(&status), None);
+    }
+}
@@ -0,0 +1,34 @@
+//! 由 proto/zkp_auth.proto 生成的 gRPC 消息、客户端和服务端代码
+//!
+//! `zkp_auth` 是当前的接口 (v1)：为了不影响现有客户端，包名保持不变，服务器同时以 `zkp_auth.v1.Auth` 提供同一组 RPC；
+//! `zkp_auth::v2` 由 proto/zkp_auth_v2.proto 生成，是第二版接口的草案，不兼容的修改只加到那里
+
+// 引入生成的 gRPC 代码模块
+pub mod zkp_auth {
+    // 包含 gRPC 服务和消息类型的定义，构建时由 build.rs 生成到 OUT_DIR
+    tonic::include_proto!("zkp_auth");
+
+    /// 第二版接口（草案）：参数协商、在承诺之前取得盐、不需要 auth_id 的双向流认证
+    pub mod v2 {
+        tonic::include_proto!("zkp_auth.v2");
+    }
+}
+
+pub mod detail;
+pub mod invalid;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod redact;
+pub mod retry;
+
+/// zkp_auth.proto 和 zkp_auth_v2.proto 编译后的文件描述符集（`FileDescriptorSet` 的 protobuf 编码），可以用于 gRPC reflection
+pub const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/zkp_auth_descriptor.bin"));
+
+/// 当前接口 (v1) 的版本化服务名，与不带版本的 `zkp_auth.Auth` 是同一组 RPC
+pub const V1_SERVICE_NAME: &str = "zkp_auth.v1.Auth";
+
+/// 服务器提供的接口版本
+pub const API_VERSIONS: &[&str] = &["v1", "v2"];
+
+/// 请求关联 ID 所在的 gRPC 元数据键：客户端可以在请求中携带，服务器在响应和错误的元数据中返回本次调用使用的 ID
+pub const CORRELATION_ID_HEADER: &str = "x-correlation-id";
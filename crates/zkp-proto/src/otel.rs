@@ -0,0 +1,93 @@
+//! OpenTelemetry（otel feature）：W3C Trace Context 在 gRPC 元数据中的传递，以及以 OTLP 导出 span 的 tracer
+//!
+//! 客户端把当前 span 的上下文写入请求的 `traceparent` / `tracestate` 元数据（`inject`），服务器和网关从请求头中读出（`extract`），
+//! 作为本次调用的 span 的父 span；一次失败的登录因此在客户端、网关、服务器和存储中属于同一个 trace
+
+use opentelemetry::propagation::{Extractor, Injector, TextMapPropagator}; // 在元数据中读写上下文
+use opentelemetry::sdk::propagation::TraceContextPropagator; // W3C Trace Context
+use opentelemetry::sdk::trace::{config, Tracer}; // 导出 span 的 tracer
+use opentelemetry::sdk::Resource; // service.name
+use opentelemetry::{runtime, Context, KeyValue}; // 批量导出在 tokio 运行时中进行
+use opentelemetry_otlp::WithExportConfig; // 设置 collector 的地址
+use tonic::codegen::http::HeaderMap; // 服务器收到的请求头
+use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue}; // 客户端请求的元数据
+
+// 请求元数据中的 trace 上下文
+struct MetadataInjector<'a>(&'a mut MetadataMap);
+
+impl Injector for MetadataInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(key), Ok(value)) = (MetadataKey::from_bytes(key.as_bytes()), MetadataValue::try_from(value)) {
+            self.0.insert(key, value);
+        }
+    }
+}
+
+// 请求头中的 trace 上下文
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// 把 trace 上下文写入请求的元数据，上下文中没有有效的 span 时不写入
+///
+/// 参数:
+/// - `context`: 要传递的上下文，通常是当前 tracing span 的 OpenTelemetry 上下文
+/// - `metadata`: 请求的元数据
+pub fn inject(context: &Context, metadata: &mut MetadataMap) {
+    TraceContextPropagator::new().inject_context(context, &mut MetadataInjector(metadata));
+}
+
+/// 从请求头中读出 trace 上下文，没有或格式不正确时为空的上下文
+pub fn extract(headers: &HeaderMap) -> Context {
+    TraceContextPropagator::new().extract(&HeaderExtractor(headers))
+}
+
+/// 创建以 OTLP/gRPC 把 span 批量导出到 collector 的 tracer，必须在 tokio 运行时中调用；进程退出前调用
+/// `opentelemetry::global::shutdown_tracer_provider` 导出剩余的 span
+///
+/// 参数:
+/// - `endpoint`: collector 的地址，例如 http://127.0.0.1:4317
+/// - `service_name`: span 的 `service.name` 资源属性
+///
+/// 返回:
+/// - `Result<Tracer, String>`: tracer，地址不正确时返回错误
+pub fn otlp_tracer(endpoint: &str, service_name: &'static str) -> Result<Tracer, String> {
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint))
+        .with_trace_config(config().with_resource(Resource::new([KeyValue::new("service.name", service_name)])))
+        .install_batch(runtime::Tokio)
+        .map_err(|e| format!("could not set up the OTLP exporter for {}: {}", endpoint, e))
+}
+
+#[cfg(test)]
+mod test {
+    use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState};
+
+    use super::*;
+
+    #[test]
+    fn test_trace_context_round_trip() {
+        let span = SpanContext::new(TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap(), SpanId::from_hex("b7ad6b7169203331").unwrap(), TraceFlags::SAMPLED, true, TraceState::default());
+        let mut metadata = MetadataMap::new();
+        inject(&Context::new().with_remote_span_context(span.clone()), &mut metadata);
+        assert_eq!(metadata.get("traceparent").unwrap(), "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01");
+
+        let extracted = extract(&metadata.into_headers());
+        assert_eq!(extracted.span().span_context(), &span);
+
+        // 没有有效的 span 时不写入，没有 traceparent 时为空的上下文
+        let mut metadata = MetadataMap::new();
+        inject(&Context::new(), &mut metadata);
+        assert!(metadata.is_empty());
+        assert!(!extract(&HeaderMap::new()).span().span_context().is_valid());
+    }
+}
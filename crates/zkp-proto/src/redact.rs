@@ -0,0 +1,218 @@
+//! 日志输出用的请求包装：协议值（y1、y2、r1、r2、s 和持有证明）只输出长度，随机数、恢复码和联系方式完全隐藏
+
+use std::fmt;
+
+use crate::zkp_auth::{
+    authenticate_request, ApproveGuardianRecoveryRequest, ApprovePendingLoginRequest, AuthenticateRequest, AuthenticationAnswerRequest, AuthenticationChallengeRequest, ChangePasswordRequest,
+    DeleteUserDataRequest, ExportUserDataRequest, RecoverAccountRequest, RegisterRequest, ResetCredentialsRequest, UpdateProfileRequest,
+};
+
+/// 以脱敏的形式输出请求，例如 `println!("{:?}", Redacted(request.get_ref()))`
+///
+/// 生成的消息类型派生的 `Debug` 会输出完整的字节内容，日志中应使用这个包装
+pub struct Redacted<'a, T>(pub &'a T);
+
+// 字节字段只输出长度
+struct Bytes<'a>(&'a [u8]);
+
+impl fmt::Debug for Bytes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<redacted, {} bytes>", self.0.len())
+    }
+}
+
+// 字符串字段（随机数、恢复码、TOTP 验证码、联系方式）完全隐藏
+struct Hidden;
+
+impl fmt::Debug for Hidden {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+impl fmt::Debug for Redacted<'_, RegisterRequest> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let r = self.0;
+        f.debug_struct("RegisterRequest")
+            .field("user", &r.user)
+            .field("y1", &Bytes(&r.y1))
+            .field("y2", &Bytes(&r.y2))
+            .field("salt", &Bytes(&r.salt))
+            .field("kdf", &r.kdf)
+            .field("params_hash", &Bytes(&r.params_hash))
+            .field("proof_c", &Bytes(&r.proof_c))
+            .field("proof_s", &Bytes(&r.proof_s))
+            .field("auth_id", &r.auth_id)
+            .field("s", &Bytes(&r.s))
+            .field("metadata", &r.metadata)
+            .field("proof_hash", &r.proof_hash)
+            .field("display_name", &r.display_name)
+            .field("contact", &Hidden)
+            .field("recovery_codes", &r.recovery_codes)
+            .field("guardians", &r.guardians)
+            .field("guardian_threshold", &r.guardian_threshold)
+            .field("totp_secret", &Bytes(&r.totp_secret))
+            .field("totp_code", &Hidden)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Redacted<'_, AuthenticationChallengeRequest> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let r = self.0;
+        f.debug_struct("AuthenticationChallengeRequest")
+            .field("user", &r.user)
+            .field("r1", &Bytes(&r.r1))
+            .field("r2", &Bytes(&r.r2))
+            .field("params_hash", &Bytes(&r.params_hash))
+            .field("metadata", &r.metadata)
+            .field("device_id", &r.device_id)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Redacted<'_, AuthenticationAnswerRequest> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let r = self.0;
+        f.debug_struct("AuthenticationAnswerRequest")
+            .field("auth_id", &r.auth_id)
+            .field("s", &Bytes(&r.s))
+            .field("params_hash", &Bytes(&r.params_hash))
+            .field("metadata", &r.metadata)
+            .field("device_id", &r.device_id)
+            .field("totp_code", &Hidden)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Redacted<'_, ChangePasswordRequest> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let r = self.0;
+        f.debug_struct("ChangePasswordRequest")
+            .field("auth_id", &r.auth_id)
+            .field("s", &Bytes(&r.s))
+            .field("y1", &Bytes(&r.y1))
+            .field("y2", &Bytes(&r.y2))
+            .field("salt", &Bytes(&r.salt))
+            .field("kdf", &r.kdf)
+            .field("totp_code", &Hidden)
+            .field("user", &r.user)
+            .field("proof_c", &Bytes(&r.proof_c))
+            .field("proof_s", &Bytes(&r.proof_s))
+            .field("proof_hash", &r.proof_hash)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Redacted<'_, ApprovePendingLoginRequest> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let r = self.0;
+        f.debug_struct("ApprovePendingLoginRequest")
+            .field("pending_id", &r.pending_id)
+            .field("nonce", &Hidden)
+            .field("auth_id", &r.auth_id)
+            .field("s", &Bytes(&r.s))
+            .field("totp_code", &Hidden)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Redacted<'_, UpdateProfileRequest> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let r = self.0;
+        f.debug_struct("UpdateProfileRequest")
+            .field("auth_id", &r.auth_id)
+            .field("s", &Bytes(&r.s))
+            .field("display_name", &r.display_name)
+            .field("contact", &r.contact.as_ref().map(|_| Hidden))
+            .field("totp_code", &Hidden)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Redacted<'_, ExportUserDataRequest> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let r = self.0;
+        f.debug_struct("ExportUserDataRequest").field("auth_id", &r.auth_id).field("s", &Bytes(&r.s)).field("totp_code", &Hidden).finish()
+    }
+}
+
+impl fmt::Debug for Redacted<'_, DeleteUserDataRequest> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let r = self.0;
+        f.debug_struct("DeleteUserDataRequest").field("auth_id", &r.auth_id).field("s", &Bytes(&r.s)).field("totp_code", &Hidden).finish()
+    }
+}
+
+impl fmt::Debug for Redacted<'_, RecoverAccountRequest> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let r = self.0;
+        f.debug_struct("RecoverAccountRequest").field("user", &r.user).field("code", &Hidden).finish()
+    }
+}
+
+impl fmt::Debug for Redacted<'_, ResetCredentialsRequest> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let r = self.0;
+        f.debug_struct("ResetCredentialsRequest")
+            .field("session_id", &r.session_id)
+            .field("y1", &Bytes(&r.y1))
+            .field("y2", &Bytes(&r.y2))
+            .field("salt", &Bytes(&r.salt))
+            .field("kdf", &r.kdf)
+            .field("user", &r.user)
+            .field("proof_c", &Bytes(&r.proof_c))
+            .field("proof_s", &Bytes(&r.proof_s))
+            .field("proof_hash", &r.proof_hash)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Redacted<'_, ApproveGuardianRecoveryRequest> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let r = self.0;
+        f.debug_struct("ApproveGuardianRecoveryRequest")
+            .field("recovery_id", &r.recovery_id)
+            .field("auth_id", &r.auth_id)
+            .field("s", &Bytes(&r.s))
+            .field("totp_code", &Hidden)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Redacted<'_, AuthenticateRequest> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0.step {
+            Some(authenticate_request::Step::Commitment(commitment)) => f.debug_tuple("Commitment").field(&Redacted(commitment)).finish(),
+            Some(authenticate_request::Step::Answer(answer)) => f.debug_tuple("Answer").field(&Redacted(answer)).finish(),
+            None => f.write_str("AuthenticateRequest { step: None }"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_redacted_requests_hide_protocol_values() {
+        let answer = AuthenticationAnswerRequest { auth_id: "abc".to_string(), s: vec![0xde, 0xad, 0xbe, 0xef], ..Default::default() };
+        let printed = format!("{:?}", Redacted(&answer));
+        assert!(printed.contains("auth_id: \"abc\""));
+        assert!(printed.contains("s: <redacted, 4 bytes>"));
+        // 派生的 Debug 输出每个字节的十进制值
+        assert!(!printed.contains("222"));
+
+        let step = AuthenticateRequest { step: Some(authenticate_request::Step::Answer(answer)) };
+        assert!(format!("{:?}", Redacted(&step)).starts_with("Answer(AuthenticationAnswerRequest"));
+
+        let approve = ApprovePendingLoginRequest { nonce: "n0nce".to_string(), ..Default::default() };
+        assert!(!format!("{:?}", Redacted(&approve)).contains("n0nce"));
+        let register = RegisterRequest { totp_secret: vec![0x2a; 20], totp_code: "123456".to_string(), ..Default::default() };
+        let printed = format!("{:?}", Redacted(&register));
+        assert!(printed.contains("totp_secret: <redacted, 20 bytes>"));
+        assert!(!printed.contains("123456"));
+        let recover = RecoverAccountRequest { user: "alice".to_string(), code: "c0de".to_string() };
+        assert!(!format!("{:?}", Redacted(&recover)).contains("c0de"));
+    }
+}
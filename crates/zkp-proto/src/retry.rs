@@ -0,0 +1,50 @@
+//! 限流和账户锁定时的重试提示：服务器在错误的元数据中给出 `retry-after`（整数秒，与 HTTP 的 Retry-After 相同），
+//! 客户端据此退避，而不是立即重试
+
+use std::time::Duration;
+
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Status};
+
+/// 重试提示所在的 gRPC 元数据键，错误没有响应消息时作为 trailer 返回
+pub const RETRY_AFTER_HEADER: &str = "retry-after";
+
+/// 构造带有重试提示的错误，例如限流时的 ResourceExhausted、账户锁定时的 Unavailable
+///
+/// 参数:
+/// - `code`: 错误码
+/// - `message`: 错误信息
+/// - `retry_after`: 建议的等待时间，向上取整到秒，至少为 1 秒
+///
+/// 返回:
+/// - `Status`: 元数据中带有 `retry-after` 的错误
+pub fn with_retry_after(code: Code, message: impl Into<String>, retry_after: Duration) -> Status {
+    let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    let mut status = Status::new(code, message);
+    status.metadata_mut().insert(RETRY_AFTER_HEADER, MetadataValue::from(secs.max(1)));
+    status
+}
+
+/// 读取错误中的重试提示，没有或不是整数秒时返回 None
+pub fn retry_after(status: &Status) -> Option<Duration> {
+    let secs: u64 = status.metadata().get(RETRY_AFTER_HEADER)?.to_str().ok()?.parse().ok()?;
+    Some(Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_retry_after_round_trip() {
+        let status = with_retry_after(Code::ResourceExhausted, "too many attempts", Duration::from_millis(2500));
+        assert_eq!(status.metadata().get(RETRY_AFTER_HEADER).unwrap(), "3");
+        assert_eq!(retry_after(&status), Some(Duration::from_secs(3)));
+        assert_eq!(retry_after(&with_retry_after(Code::Unavailable, "locked", Duration::ZERO)), Some(Duration::from_secs(1)));
+
+        let mut status = Status::resource_exhausted("no hint");
+        assert_eq!(retry_after(&status), None);
+        status.metadata_mut().insert(RETRY_AFTER_HEADER, "soon".parse().unwrap());
+        assert_eq!(retry_after(&status), None);
+    }
+}
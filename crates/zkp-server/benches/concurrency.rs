@@ -0,0 +1,98 @@
+// 内存存储的并发扩展：多个任务同时为不同的用户建立、查询和删除会话，
+// 对比分片的 MemoryStore 与所有请求共用一个 Mutex<HashMap> 的实现（改为分片之前的做法）
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tokio::runtime::{Builder, Runtime};
+use zkp_server::store::SessionInfo;
+use zkp_server::{MemoryStore, SessionStore};
+
+// 每个任务的会话数
+const SESSIONS: usize = 2_000;
+
+fn session(user: &str) -> SessionInfo {
+    SessionInfo {
+        user: user.to_string(),
+        issued_at: 0,
+        expires_at: u64::MAX,
+        auth_method: "chaum-pedersen".to_string(),
+        scopes: vec!["profile".to_string()],
+        metadata: HashMap::new(),
+        device_id: String::new(),
+    }
+}
+
+// 1、2、4 …… 直到 CPU 核数个线程
+fn thread_counts() -> Vec<usize> {
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut counts: Vec<usize> = std::iter::successors(Some(1), |n| Some(n * 2)).take_while(|n| *n < cores).collect();
+    counts.push(cores);
+    counts
+}
+
+fn runtime(threads: usize) -> Runtime {
+    Builder::new_multi_thread().worker_threads(threads).build().unwrap()
+}
+
+// 每个线程一个任务，各自操作自己用户的会话
+fn run<F, Fut>(rt: &Runtime, threads: usize, task: F)
+where
+    F: Fn(String) -> Fut,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+{
+    rt.block_on(async {
+        let tasks: Vec<_> = (0..threads).map(|t| tokio::spawn(task(format!("user-{}", t)))).collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+    });
+}
+
+fn sessions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("put_get_delete_session");
+    group.sample_size(10);
+    for threads in thread_counts() {
+        let rt = runtime(threads);
+        group.throughput(Throughput::Elements((threads * SESSIONS) as u64));
+
+        let store = Arc::new(MemoryStore::default());
+        group.bench_function(BenchmarkId::new("sharded", threads), |b| {
+            b.iter(|| {
+                run(&rt, threads, |user| {
+                    let store = store.clone();
+                    async move {
+                        for i in 0..SESSIONS {
+                            let session_id = format!("{}-{}", user, i);
+                            store.put_session(&session_id, session(&user)).await.unwrap();
+                            assert!(store.get_session(&session_id).await.unwrap().is_some());
+                            store.delete_session(&session_id).await.unwrap();
+                        }
+                    }
+                })
+            })
+        });
+
+        let global: Arc<Mutex<HashMap<String, SessionInfo>>> = Arc::default();
+        group.bench_function(BenchmarkId::new("global_mutex", threads), |b| {
+            b.iter(|| {
+                run(&rt, threads, |user| {
+                    let global = global.clone();
+                    async move {
+                        for i in 0..SESSIONS {
+                            let session_id = format!("{}-{}", user, i);
+                            global.lock().unwrap().insert(session_id.clone(), session(&user));
+                            assert!(global.lock().unwrap().get(&session_id).cloned().is_some());
+                            global.lock().unwrap().remove(&session_id);
+                        }
+                    }
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, sessions);
+criterion_main!(benches);
@@ -0,0 +1,190 @@
+//! 管理接口 `zkp_auth.AuthAdmin` 的实现：与 Auth 服务共享同一个 `AuthImpl`，运维通过它分页列出用户、查看和吊销会话、
+//! 解除账户锁定、删除用户、查询审计日志和切换排空模式，不需要直接操作存储后端。服务必须包在 `AdminAuth` 拦截器中，每个处理函数开始时用 `rbac::require` 检查角色：
+//!
+//! ```ignore
+//! let auth = Arc::new(AuthImpl::new(config, store));
+//! Server::builder()
+//!     .add_service(AuthServer::from_arc(auth.clone()))
+//!     .add_service(AuthAdminServer::with_interceptor(AuthAdminImpl::new(auth), AdminAuth::new(policy)))
+//! ```
+//!
+//! `run_server` 在设置了 `ServerConfig::admin` 时自动加入这个服务。会话 ID 是凭据，管理接口只返回由它派生的 handle
+
+use std::sync::Arc; // 与 Auth 服务共享的 AuthImpl
+
+use tonic::{Code, Request, Response, Status}; // gRPC 请求和响应
+use tracing::info; // 记录管理操作和操作者
+
+use zkp_core::HashAlgorithm; // 由会话 ID 派生 handle
+use zkp_proto::zkp_auth::{
+    auth_admin_server::AuthAdmin, // 管理服务接口
+    AdminSession, ListSessionsRequest, ListSessionsResponse, // 查看会话的请求和响应消息类型
+    AuditEntry, QueryAuditLogRequest, QueryAuditLogResponse, // 查询审计日志的请求和响应消息类型
+    DeleteUserRequest, DeleteUserResponse, // 删除用户的请求和响应消息类型
+    ListUsersRequest, ListUsersResponse, UserSummary, // 列出用户的请求和响应消息类型
+    RevokeSessionsRequest, RevokeSessionsResponse, // 吊销会话的请求和响应消息类型
+    SetDrainModeRequest, SetDrainModeResponse, // 切换排空模式的请求和响应消息类型
+    UnlockUserRequest, UnlockUserResponse, // 解除锁定的请求和响应消息类型
+};
+
+use crate::audit::AuditQuery; // 审计日志的查询条件
+use crate::rbac::{require, AdminRole}; // 每个 RPC 需要的角色
+use crate::{exported_session, unix_now, AuthImpl}; // 共享的处理逻辑和存储
+
+// ListUsers 和 QueryAuditLog 每页的默认条目数和上限
+const DEFAULT_PAGE_SIZE: u32 = 100;
+const MAX_PAGE_SIZE: u32 = 1000;
+
+// 管理员吊销会话时推送的吊销原因
+const REVOKED_BY_ADMIN: &str = "admin-revoked";
+
+/// 管理 gRPC 服务的实现
+#[derive(Debug, Clone)]
+pub struct AuthAdminImpl(Arc<AuthImpl>);
+
+impl AuthAdminImpl {
+    /// 参数:
+    /// - `auth`: 与 Auth 服务共享的 `AuthImpl`
+    pub fn new(auth: Arc<AuthImpl>) -> Self {
+        AuthAdminImpl(auth)
+    }
+
+    // 按服务器的用户名策略规范化请求中的用户名，只规范化不检查，管理员仍然可以处理不符合当前策略的旧记录
+    fn user_name(&self, user_name: &str) -> String {
+        self.0.config.user_names.normalize(user_name)
+    }
+}
+
+/// 会话在管理接口中的 handle：会话 ID 的 SHA-256 的前 8 字节（十六进制），由 handle 无法还原会话 ID
+pub fn session_handle(session_id: &str) -> String {
+    hex::encode(&HashAlgorithm::Sha256.digest(session_id.as_bytes())[..8])
+}
+
+#[tonic::async_trait]
+impl AuthAdmin for AuthAdminImpl {
+    // 按用户名排序分页，page_token 是上一页最后一个用户名；多取一个用户来判断是否还有下一页
+    async fn list_users(&self, request: Request<ListUsersRequest>) -> Result<Response<ListUsersResponse>, Status> {
+        require(&request, AdminRole::Viewer)?;
+        let request = request.into_inner();
+        let page_size = match request.page_size {
+            0 => DEFAULT_PAGE_SIZE,
+            size => size.min(MAX_PAGE_SIZE),
+        };
+        let mut users = self.0.users.list_users(&request.page_token, page_size + 1).await?;
+        let more = users.len() > page_size as usize;
+        users.truncate(page_size as usize);
+        let next_page_token = if more { users.last().map(|(user, _)| user.clone()).unwrap_or_default() } else { String::new() };
+        let now = unix_now();
+        let users = users
+            .into_iter()
+            .map(|(user, info)| UserSummary {
+                locked_until: self.0.lockout.locked_for(&user).map_or(0, |lock| now + lock.as_secs() + u64::from(lock.subsec_nanos() > 0)),
+                profile: Some(info.profile()),
+                scopes: info.scopes,
+                credential_reset_required: info.reset_required,
+                totp_enabled: !info.totp_secret.is_empty(),
+                params_hash: info.group,
+                user,
+            })
+            .collect();
+        Ok(Response::new(ListUsersResponse { users, next_page_token }))
+    }
+
+    // 用户的所有会话，包括已过期但尚未被后台清理删除的会话
+    async fn list_sessions(&self, request: Request<ListSessionsRequest>) -> Result<Response<ListSessionsResponse>, Status> {
+        require(&request, AdminRole::Viewer)?;
+        let user_name = self.user_name(&request.into_inner().user);
+        let mut sessions: Vec<AdminSession> = self
+            .0
+            .sessions
+            .list_sessions(&user_name)
+            .await?
+            .into_iter()
+            .map(|(session_id, session)| AdminSession { handle: session_handle(&session_id), session: Some(exported_session(session)) })
+            .collect();
+        sessions.sort_by_key(|session| session.session.as_ref().map(|session| session.issued_at)); // 存储不保证顺序，按建立时间输出
+        Ok(Response::new(ListSessionsResponse { sessions }))
+    }
+
+    // handle 为空时吊销用户的所有会话，否则只吊销 handle 对应的会话，找不到时返回 NotFound
+    async fn revoke_sessions(&self, request: Request<RevokeSessionsRequest>) -> Result<Response<RevokeSessionsResponse>, Status> {
+        let admin = require(&request, AdminRole::Operator)?;
+        let mut request = request.into_inner();
+        request.user = self.user_name(&request.user);
+        let revoked_sessions = if request.handle.is_empty() {
+            self.0.revoke_user_sessions(&request.user, REVOKED_BY_ADMIN).await?
+        } else {
+            let sessions = self.0.sessions.list_sessions(&request.user).await?;
+            let Some((session_id, _)) = sessions.into_iter().find(|(session_id, _)| session_handle(session_id) == request.handle) else {
+                return Err(Status::new(Code::NotFound, format!("User: {} has no session {}", request.user, request.handle)));
+            };
+            match self.0.sessions.delete_session(&session_id).await? {
+                Some(_) => {
+                    self.0.publish_revocation(session_id, request.user.clone(), REVOKED_BY_ADMIN).await;
+                    1
+                }
+                None => 0, // 已经被注销或清理
+            }
+        };
+        info!(admin = %admin.name, user = %request.user, revoked_sessions, "revoked sessions");
+        Ok(Response::new(RevokeSessionsResponse { revoked_sessions }))
+    }
+
+    async fn unlock_user(&self, request: Request<UnlockUserRequest>) -> Result<Response<UnlockUserResponse>, Status> {
+        let admin = require(&request, AdminRole::Operator)?;
+        let user_name = self.user_name(&request.into_inner().user);
+        let was_locked = self.0.unlock_user(&user_name);
+        info!(admin = %admin.name, user = %user_name, was_locked, "unlock requested");
+        Ok(Response::new(UnlockUserResponse { was_locked }))
+    }
+
+    async fn delete_user(&self, request: Request<DeleteUserRequest>) -> Result<Response<DeleteUserResponse>, Status> {
+        let admin = require(&request, AdminRole::Admin)?;
+        let user_name = self.user_name(&request.into_inner().user);
+        let Some(revoked_sessions) = self.0.delete_user(&user_name).await? else {
+            return Err(AuthImpl::user_not_found(&user_name));
+        };
+        info!(admin = %admin.name, user = %user_name, revoked_sessions, "deleted user");
+        Ok(Response::new(DeleteUserResponse { revoked_sessions }))
+    }
+
+    // 按序号分页，page_token 是上一页最后一个事件的序号；与 ListUsers 一样多取一个事件来判断是否还有下一页
+    async fn query_audit_log(&self, request: Request<QueryAuditLogRequest>) -> Result<Response<QueryAuditLogResponse>, Status> {
+        require(&request, AdminRole::Viewer)?;
+        let Some(audit_log) = &self.0.config.audit_log else {
+            return Err(Status::new(Code::FailedPrecondition, "the server has no audit log configured"));
+        };
+        let request = request.into_inner();
+        let page_size = match request.page_size {
+            0 => DEFAULT_PAGE_SIZE,
+            size => size.min(MAX_PAGE_SIZE),
+        };
+        let query = AuditQuery { user: self.user_name(&request.user), since: request.since, until: request.until, after: request.page_token, limit: page_size + 1 };
+        let mut events = audit_log.query(&query).await?;
+        let more = events.len() > page_size as usize;
+        events.truncate(page_size as usize);
+        let next_page_token = if more { events.last().map_or(0, |event| event.id) } else { 0 };
+        let entries = events
+            .into_iter()
+            .map(|event| AuditEntry {
+                id: event.id,
+                at: event.at,
+                kind: event.kind.name().to_string(),
+                user: event.user,
+                success: event.success,
+                reason: event.reason,
+                remote_addr: event.remote_addr,
+                correlation_id: event.correlation_id,
+                session: event.session,
+            })
+            .collect();
+        Ok(Response::new(QueryAuditLogResponse { entries, next_page_token }))
+    }
+    async fn set_drain_mode(&self, request: Request<SetDrainModeRequest>) -> Result<Response<SetDrainModeResponse>, Status> {
+        let admin = require(&request, AdminRole::Operator)?;
+        let draining = request.into_inner().draining;
+        let was_draining = self.0.set_draining(draining);
+        info!(admin = %admin.name, draining, was_draining, "drain mode requested");
+        Ok(Response::new(SetDrainModeResponse { was_draining }))
+    }
+}
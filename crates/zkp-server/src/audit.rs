@@ -0,0 +1,274 @@
+//! 审计日志：注册、挑战、验证（成功或失败及其原因）和会话吊销等与安全相关的事件，按发生顺序只追加、不修改，
+//! 运维通过管理接口的 `QueryAuditLog` 按用户和时间范围查询。唯一的例外是删除用户：该用户的事件改为假名（`AuditLog::pseudonymize`），
+//! 事件和序号保留，但不再能关联到这个用户
+//!
+//! `ServerConfig::audit_log` 为 None 时不记录。`FileAuditLog` 把每个事件写成 JSON 文件中的一行，`SqliteStore` 和 `PostgresStore`
+//! 把事件写入各自数据库的 `audit_log` 表；嵌入服务器的程序可以实现 `AuditLog` 写入自己的系统。
+//! 写入失败时只输出错误日志，请求照常处理，审计系统的故障不会使认证不可用
+
+use std::fmt; // 调试输出不包含事件内容
+use std::fs::{File, OpenOptions}; // 日志文件
+use std::io::{BufRead, BufReader, Write}; // 逐行读写
+use std::path::{Path, PathBuf}; // 日志文件路径
+use std::sync::Mutex; // 追加和分配序号不能交错
+
+use serde::{Deserialize, Serialize}; // 日志文件中每行一个 JSON 对象
+use tonic::{Request, Status}; // 事件的客户端地址和关联 ID、失败的原因
+
+use crate::correlation::correlation_id; // 事件与服务器日志中的请求对应
+use crate::peer::client_addr_text; // 事件的客户端地址，经过可信代理时取自 x-forwarded-for
+use crate::store::{StoreError, StoreResult}; // 与存储后端相同的错误
+use crate::unix_now; // 事件的发生时间
+
+/// 审计事件的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuditKind {
+    /// 注册（包括用旧密码重新注册）
+    Register,
+    /// 请求认证挑战
+    Challenge,
+    /// 提交对挑战的解答
+    Verify,
+    /// 会话被吊销：注销、修改密码、删除用户或管理员吊销，原因在 `reason` 中
+    SessionRevoked,
+}
+
+impl AuditKind {
+    /// 事件类型的名称：register、challenge、verify、session-revoked
+    pub fn name(self) -> &'static str {
+        match self {
+            AuditKind::Register => "register",
+            AuditKind::Challenge => "challenge",
+            AuditKind::Verify => "verify",
+            AuditKind::SessionRevoked => "session-revoked",
+        }
+    }
+
+    /// 由名称解析事件类型，未知的名称返回 None
+    pub fn from_name(name: &str) -> Option<AuditKind> {
+        [AuditKind::Register, AuditKind::Challenge, AuditKind::Verify, AuditKind::SessionRevoked].into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+impl fmt::Display for AuditKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// 一个审计事件，不包含协议值和会话 ID 等凭据
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub id: u64,                // 日志分配的序号，从 1 开始递增；追加时忽略
+    pub at: u64,                // 发生时间（Unix 时间戳，秒）
+    pub kind: AuditKind,        // 事件类型
+    pub user: String,           // 相关的用户名，验证时找不到挑战的事件为空
+    pub success: bool,          // 操作是否成功
+    pub reason: String,         // 失败时为错误码和错误信息，会话被吊销时为吊销原因
+    pub remote_addr: String,    // 客户端地址，没有时为空；经过可信代理时为 x-forwarded-for 中的地址，不含端口
+    pub correlation_id: String, // 请求的关联 ID，服务没有经过 Correlated 包装时为 "-"
+    pub session: String,        // 被吊销的会话的 handle（见 `admin::session_handle`），其他事件为空
+}
+
+impl AuditEvent {
+    // 请求的审计事件，发生时间为当前时间，结果由 outcome 填写
+    pub(crate) fn of<T>(kind: AuditKind, user: &str, request: &Request<T>) -> Self {
+        AuditEvent {
+            id: 0,
+            at: unix_now(),
+            kind,
+            user: user.to_string(),
+            success: false,
+            reason: String::new(),
+            remote_addr: client_addr_text(request),
+            correlation_id: correlation_id(request),
+            session: String::new(),
+        }
+    }
+
+    // 填写请求的结果：成功，或者失败时的错误码和错误信息
+    pub(crate) fn outcome<T>(mut self, result: &Result<T, Status>) -> Self {
+        match result {
+            Ok(_) => self.success = true,
+            Err(status) => self.reason = format!("{:?}: {}", status.code(), status.message()),
+        }
+        self
+    }
+
+    // 改为假名：用户名替换为 tombstone，清空客户端地址；原因中包含用户名时只保留错误码（第一个冒号之前的部分）
+    pub(crate) fn pseudonymize(&mut self, user: &str, tombstone: &str) {
+        self.user = tombstone.to_string();
+        self.remote_addr.clear();
+        if self.reason.contains(user) {
+            self.reason = self.reason.split_once(':').map(|(code, _)| code.to_string()).unwrap_or_default();
+        }
+    }
+}
+
+/// `AuditLog::query` 的条件，按序号从小到大返回
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditQuery {
+    pub user: String, // 只返回该用户的事件，为空时返回所有用户的事件
+    pub since: u64,   // 只返回 at >= since 的事件
+    pub until: u64,   // 只返回 at < until 的事件，为 0 时不限制
+    pub after: u64,   // 只返回序号大于 after 的事件，用于分页
+    pub limit: u32,   // 最多返回的事件数
+}
+
+impl AuditQuery {
+    /// 事件是否满足条件（不考虑 limit）
+    pub fn matches(&self, event: &AuditEvent) -> bool {
+        event.id > self.after && event.at >= self.since && (self.until == 0 || event.at < self.until) && (self.user.is_empty() || event.user == self.user)
+    }
+}
+
+/// 审计日志：只追加事件，除了删除用户时改为假名，不修改和删除事件
+#[tonic::async_trait]
+pub trait AuditLog: fmt::Debug + Send + Sync {
+    /// 追加一个事件，由日志分配序号
+    async fn append(&self, event: AuditEvent) -> StoreResult<()>;
+
+    /// 查询满足条件的事件，按序号从小到大，最多 `query.limit` 个
+    async fn query(&self, query: &AuditQuery) -> StoreResult<Vec<AuditEvent>>;
+
+    /// 删除用户时把该用户的全部事件改为假名：用户名替换为 `tombstone`，客户端地址清空，包含用户名的失败原因只保留错误码；
+    /// 事件的类型、时间、结果和序号不变
+    ///
+    /// 参数:
+    /// - `user`: 被删除的用户名
+    /// - `tombstone`: 代替用户名的假名
+    ///
+    /// 返回:
+    /// - `StoreResult<u64>`: 改为假名的事件数
+    async fn pseudonymize(&self, user: &str, tombstone: &str) -> StoreResult<u64>;
+}
+
+/// 内存中的审计日志，服务器重启后丢失，用于测试和嵌入服务器的程序
+#[derive(Default)]
+pub struct MemoryAuditLog {
+    events: Mutex<Vec<AuditEvent>>,
+}
+
+// 事件包含用户名和客户端地址，调试输出只包含事件数
+impl fmt::Debug for MemoryAuditLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryAuditLog").field("events", &self.events.lock().unwrap().len()).finish()
+    }
+}
+
+#[tonic::async_trait]
+impl AuditLog for MemoryAuditLog {
+    async fn append(&self, mut event: AuditEvent) -> StoreResult<()> {
+        let mut events = self.events.lock().unwrap();
+        event.id = events.len() as u64 + 1;
+        events.push(event);
+        Ok(())
+    }
+
+    async fn query(&self, query: &AuditQuery) -> StoreResult<Vec<AuditEvent>> {
+        let events = self.events.lock().unwrap();
+        Ok(events.iter().filter(|event| query.matches(event)).take(query.limit as usize).cloned().collect())
+    }
+
+    async fn pseudonymize(&self, user: &str, tombstone: &str) -> StoreResult<u64> {
+        let mut events = self.events.lock().unwrap();
+        let mut count = 0;
+        for event in events.iter_mut().filter(|event| event.user == user) {
+            event.pseudonymize(user, tombstone);
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+/// 保存在 JSON Lines 文件中的审计日志：每个事件一行，只以追加方式打开，日志轮转等由外部工具处理
+///
+/// 删除用户时重写整个文件（先写临时文件再替换），已经轮转出去的文件不在其中，需要由外部工具处理。查询时从头读取整个文件，适合单个实例和事件量不大的部署；需要频繁查询时使用数据库存储的 `audit_log` 表
+pub struct FileAuditLog {
+    path: PathBuf,
+    file: Mutex<(File, u64)>, // 追加用的文件和最后一个事件的序号
+}
+
+impl fmt::Debug for FileAuditLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileAuditLog").field("path", &self.path).finish()
+    }
+}
+
+impl FileAuditLog {
+    /// 打开（不存在时创建）日志文件，已有的事件保留，新的事件接着最后一个序号编号
+    ///
+    /// 参数:
+    /// - `path`: 日志文件路径
+    ///
+    /// 返回:
+    /// - `StoreResult<FileAuditLog>`: 打开的日志，或者无法打开、已有内容不是审计事件的原因
+    pub fn open(path: impl AsRef<Path>) -> StoreResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).open(&path).map_err(|e| file_error(&path, e))?;
+        let last = read_events(&path)?.last().map_or(0, |event| event.id);
+        Ok(FileAuditLog { path, file: Mutex::new((file, last)) })
+    }
+}
+
+#[tonic::async_trait]
+impl AuditLog for FileAuditLog {
+    async fn append(&self, mut event: AuditEvent) -> StoreResult<()> {
+        let mut file = self.file.lock().unwrap();
+        event.id = file.1 + 1;
+        let mut line = serde_json::to_vec(&event).map_err(|e| StoreError(format!("audit log: {}", e)))?;
+        line.push(b'\n');
+        file.0.write_all(&line).map_err(|e| file_error(&self.path, e))?; // 一次写入整行，追加模式下不会与其他写入交错
+        file.1 = event.id;
+        Ok(())
+    }
+
+    async fn query(&self, query: &AuditQuery) -> StoreResult<Vec<AuditEvent>> {
+        let _file = self.file.lock().unwrap(); // 不读取写了一半的行
+        Ok(read_events(&self.path)?.into_iter().filter(|event| query.matches(event)).take(query.limit as usize).collect())
+    }
+
+    async fn pseudonymize(&self, user: &str, tombstone: &str) -> StoreResult<u64> {
+        let mut file = self.file.lock().unwrap();
+        let mut events = read_events(&self.path)?;
+        let mut count = 0;
+        for event in events.iter_mut().filter(|event| event.user == user) {
+            event.pseudonymize(user, tombstone);
+            count += 1;
+        }
+        if count == 0 {
+            return Ok(0);
+        }
+        let mut content = Vec::new();
+        for event in &events {
+            serde_json::to_writer(&mut content, event).map_err(|e| StoreError(format!("audit log: {}", e)))?;
+            content.push(b'\n');
+        }
+        // 写到同一目录的临时文件后替换，中途失败时原文件不变；之后的事件追加到新文件
+        let temp = self.path.with_extension("pseudonymize.tmp");
+        std::fs::write(&temp, content).map_err(|e| file_error(&temp, e))?;
+        std::fs::rename(&temp, &self.path).map_err(|e| file_error(&self.path, e))?;
+        file.0 = OpenOptions::new().append(true).open(&self.path).map_err(|e| file_error(&self.path, e))?;
+        Ok(count)
+    }
+}
+
+// 读取日志文件中的全部事件
+fn read_events(path: &Path) -> StoreResult<Vec<AuditEvent>> {
+    let file = File::open(path).map_err(|e| file_error(path, e))?;
+    let mut events = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| file_error(path, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event = serde_json::from_str(&line).map_err(|e| StoreError(format!("audit log {} line {}: {}", path.display(), i + 1, e)))?;
+        events.push(event);
+    }
+    Ok(events)
+}
+
+fn file_error(path: &Path, error: std::io::Error) -> StoreError {
+    StoreError(format!("audit log {}: {}", path.display(), error))
+}
@@ -0,0 +1,106 @@
+//! 挑战值的来源：`create_authentication_challenge` 通过 `ServerConfig::challenge_source` 取得挑战值 c，
+//! 默认在 [0, q) 中均匀随机选取；也可以由承诺的哈希派生（可以事后复算），或者交给外部的生成器（例如 HSM）
+//!
+//! 有通道绑定时，服务器在取得 c 之后再计算绑定后的 c'，挑战来源不需要处理
+
+use std::fmt; // 外部生成器的调试输出
+use std::sync::Arc; // 外部生成器可以在配置的克隆之间共享
+
+use num_bigint::BigUint; // 挑战值
+
+use zkp_core::{GroupParams, HashAlgorithm, RandomSource}; // 群参数、哈希派生的挑战值和随机数来源
+
+// 哈希派生挑战值的上下文前缀
+const CHALLENGE_CONTEXT_DOMAIN: &[u8] = b"zkp_chaum_pedersen/login-challenge/v1:";
+
+/// 生成一个挑战值时可以使用的输入，公开值和承诺都已检查过
+#[derive(Debug, Clone, Copy)]
+pub struct ChallengeInput<'a> {
+    pub user: &'a str,    // 要登录的用户名
+    pub auth_id: &'a str, // 本次认证的随机 ID，随挑战一起返回给客户端
+    pub y1: &'a BigUint,  // 用户的公开值
+    pub y2: &'a BigUint,
+    pub r1: &'a BigUint,  // 客户端的承诺
+    pub r2: &'a BigUint,
+    pub rng: &'a RandomSource, // 服务器的随机数来源（`ServerConfig::rng`）
+}
+
+/// 挑战值的来源
+///
+/// 返回的值必须小于 q，否则服务器以 Internal 错误拒绝本次挑战请求；返回错误时，服务器以 Unavailable 拒绝
+#[tonic::async_trait]
+pub trait ChallengeSource: fmt::Debug + Send + Sync {
+    /// 参数:
+    /// - `params`: 服务器使用的群参数
+    /// - `input`: 用户名、认证 ID、公开值和承诺
+    ///
+    /// 返回:
+    /// - `Result<BigUint, String>`: 挑战值 c，或者生成失败的原因
+    async fn challenge(&self, params: &GroupParams, input: ChallengeInput<'_>) -> Result<BigUint, String>;
+}
+
+/// 用服务器的随机数来源在 [0, q) 中均匀随机选取挑战值，默认的来源
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomChallenge;
+
+#[tonic::async_trait]
+impl ChallengeSource for RandomChallenge {
+    async fn challenge(&self, params: &GroupParams, input: ChallengeInput<'_>) -> Result<BigUint, String> {
+        Ok(input.rng.number_below(&params.q))
+    }
+}
+
+/// 用 Fiat-Shamir 哈希由公开值、承诺和 `challenge_context` 派生挑战值，知道认证 ID 的审计者可以复算
+///
+/// 认证 ID 是服务器随机生成的，重放同一个承诺得到的挑战值不同
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FiatShamirChallenge {
+    pub hash: HashAlgorithm, // 派生使用的哈希函数
+}
+
+#[tonic::async_trait]
+impl ChallengeSource for FiatShamirChallenge {
+    async fn challenge(&self, params: &GroupParams, input: ChallengeInput<'_>) -> Result<BigUint, String> {
+        Ok(params.fiat_shamir_challenge_with(self.hash, input.y1, input.y2, input.r1, input.r2, &challenge_context(input.user, input.auth_id)))
+    }
+}
+
+/// `FiatShamirChallenge` 绑定的上下文：域标签 + 用户名 + 0 字节 + 认证 ID
+///
+/// 参数:
+/// - `user`: 要登录的用户名
+/// - `auth_id`: 挑战响应中的认证 ID
+///
+/// 返回:
+/// - `Vec<u8>`: 挑战值的上下文
+pub fn challenge_context(user: &str, auth_id: &str) -> Vec<u8> {
+    [CHALLENGE_CONTEXT_DOMAIN, user.as_bytes(), &[0], auth_id.as_bytes()].concat()
+}
+
+// 外部生成器的函数类型
+type ChallengeFn = dyn Fn(&GroupParams, ChallengeInput<'_>) -> Result<BigUint, String> + Send + Sync;
+
+/// 由外部提供的函数生成挑战值，例如调用 HSM 的随机数生成器；需要异步调用的生成器可以直接实现 `ChallengeSource`
+#[derive(Clone)]
+pub struct ExternalChallenge(Arc<ChallengeFn>);
+
+impl ExternalChallenge {
+    /// 参数:
+    /// - `generate`: 生成挑战值的函数，返回的值必须小于 q
+    pub fn new(generate: impl Fn(&GroupParams, ChallengeInput<'_>) -> Result<BigUint, String> + Send + Sync + 'static) -> Self {
+        ExternalChallenge(Arc::new(generate))
+    }
+}
+
+impl fmt::Debug for ExternalChallenge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ExternalChallenge(..)")
+    }
+}
+
+#[tonic::async_trait]
+impl ChallengeSource for ExternalChallenge {
+    async fn challenge(&self, params: &GroupParams, input: ChallengeInput<'_>) -> Result<BigUint, String> {
+        (self.0)(params, input)
+    }
+}
@@ -0,0 +1,28 @@
+//! 挑战的通道绑定：TLS 层把本连接的导出值（RFC 9266 tls-exporter，标签 `EXPORTER-Channel-Binding`，32 字节）
+//! 以 `ChannelBinding` 写入请求的扩展，服务器把它混入发出的挑战，客户端必须用自己一端的导出值计算应答
+//!
+//! tonic 的 TLS 不提供导出器，嵌入服务器的程序在自己的 TLS 接入层中导出后写入，例如通过拦截器：
+//! `AuthServer::with_interceptor(auth, move |mut request: Request<()>| { request.extensions_mut().insert(binding.clone()); Ok(request) })`，
+//! 或者让自定义连接类型的 `Connected::ConnectInfo` 为 `ChannelBinding`
+
+use std::fmt; // 脱敏的调试输出
+
+use tonic::Request; // 处理函数收到的请求
+
+/// 一个 TLS 连接的通道绑定值
+#[derive(Clone, PartialEq, Eq)]
+pub struct ChannelBinding(pub Vec<u8>);
+
+impl ChannelBinding {
+    // 取请求所在连接的通道绑定值，TLS 层没有提供时为 None
+    pub(crate) fn of<T>(request: &Request<T>) -> Option<ChannelBinding> {
+        request.extensions().get::<ChannelBinding>().cloned()
+    }
+}
+
+// 导出值由 TLS 主密钥派生，调试输出只包含长度
+impl fmt::Debug for ChannelBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ChannelBinding(<redacted, {} bytes>)", self.0.len())
+    }
+}
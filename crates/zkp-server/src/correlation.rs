@@ -0,0 +1,114 @@
+//! 请求关联 ID：客户端在 `x-correlation-id` 元数据中携带的 ID 原样沿用，没有或不合法时由服务器生成，
+//! 服务器日志、响应和错误的元数据中使用同一个 ID，便于把一次失败的登录在客户端和服务器的日志中对应起来
+//!
+//! `Correlated` 为每个 RPC 打开一个 `rpc` span（方法和关联 ID），处理函数中的日志事件都带有这两个字段；
+//! RPC 结束时记录一条包含 gRPC 状态码和耗时的事件。otel feature 下 `rpc` span 以请求中的 `traceparent` 为父 span 导出
+
+use std::fmt; // 关联 ID 的输出格式
+use std::task::{Context, Poll}; // 服务包装的就绪状态
+use std::time::Instant; // RPC 的耗时
+
+use tonic::codegen::{http, BoxFuture, Service}; // 服务包装处理的 HTTP 请求和响应
+use tonic::server::NamedService; // 加入 tonic 路由时使用的服务名
+use tonic::{Code, Request}; // 处理函数收到的 gRPC 请求，RPC 结束时的状态码
+use tracing::{field, info, info_span, Instrument}; // 每个 RPC 的 span
+
+use zkp_core::ZKP; // 生成随机的关联 ID
+use zkp_proto::CORRELATION_ID_HEADER; // 关联 ID 所在的元数据键
+
+// 客户端提供的关联 ID 的最大长度
+const MAX_CORRELATION_ID_LEN: usize = 64;
+
+// 服务器生成的关联 ID 的长度
+const GENERATED_CORRELATION_ID_LEN: usize = 16;
+
+/// 一次 RPC 的关联 ID，由 `Correlated` 写入请求的扩展中
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorrelationId(String);
+
+impl CorrelationId {
+    /// 沿用请求元数据中的关联 ID，没有或不合法（为空、超过 64 字节、包含字母数字和 `-_.` 以外的字符）时生成新的 ID
+    ///
+    /// 参数:
+    /// - `headers`: 请求的 HTTP 头（gRPC 元数据）
+    ///
+    /// 返回:
+    /// - `CorrelationId`: 本次调用使用的关联 ID
+    pub fn from_headers(headers: &http::HeaderMap) -> CorrelationId {
+        let provided = headers.get(CORRELATION_ID_HEADER).and_then(|value| value.to_str().ok()).filter(|id| {
+            !id.is_empty() && id.len() <= MAX_CORRELATION_ID_LEN && id.bytes().all(|b| b.is_ascii_alphanumeric() || b"-_.".contains(&b))
+        });
+        match provided {
+            Some(id) => CorrelationId(id.to_string()),
+            None => CorrelationId(ZKP::generate_random_string(GENERATED_CORRELATION_ID_LEN)),
+        }
+    }
+
+    /// 关联 ID 的文本
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CorrelationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// 处理函数中取本次调用的关联 ID，服务没有经过 Correlated 包装时为 "-"
+pub(crate) fn correlation_id<T>(request: &Request<T>) -> String {
+    request.extensions().get::<CorrelationId>().map_or_else(|| "-".to_string(), |id| id.to_string())
+}
+
+/// 为每个 RPC 确定关联 ID 的服务包装，例如 `Correlated(AuthServer::new(auth_impl))`
+///
+/// 关联 ID 写入请求的扩展，并加到响应的元数据中；错误在没有响应消息时以 HTTP 头返回，
+/// 因此客户端在 `Status::metadata()` 中同样可以读到
+#[derive(Debug, Clone)]
+pub struct Correlated<S>(pub S);
+
+impl<S: NamedService> NamedService for Correlated<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S, B, RB> Service<http::Request<B>> for Correlated<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<RB>>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+    RB: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<S::Response, S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        let id = CorrelationId::from_headers(request.headers());
+        // 合法的关联 ID 只包含可见的 ASCII 字符，总能作为 HTTP 头的值
+        let value = http::HeaderValue::from_str(id.as_str()).expect("correlation ids are valid header values");
+        let span = info_span!("rpc", method = request.uri().path(), correlation_id = id.as_str(), otel.name = field::Empty, otel.kind = field::Empty, otel.status_code = field::Empty);
+        #[cfg(feature = "otel")]
+        crate::otel::start_span(&span, request.uri().path().trim_start_matches('/'), request.headers());
+        request.extensions_mut().insert(id);
+        let started = Instant::now();
+        let response = span.in_scope(|| self.0.call(request));
+        Box::pin(
+            async move {
+                let mut response = response.await?;
+                // 错误在没有响应消息时以 HTTP 头返回；成功的响应在 trailer 中才有状态码，此时头中没有 grpc-status
+                let code = response.headers().get("grpc-status").map_or(Code::Ok, |code| Code::from_bytes(code.as_bytes()));
+                info!(code = ?code, elapsed_ms = started.elapsed().as_millis() as u64, "rpc finished");
+                #[cfg(feature = "otel")]
+                crate::otel::finish_span(&tracing::Span::current(), code != Code::Ok);
+                response.headers_mut().insert(CORRELATION_ID_HEADER, value);
+                Ok(response)
+            }
+            .instrument(span),
+        )
+    }
+}
@@ -0,0 +1,51 @@
+//! gRPC 截止时间：客户端在 `grpc-timeout` 元数据中给出剩余时间，处理函数在开始验证和写入存储之前检查，
+//! 已经超时的请求不再做模幂运算，也不留下客户端收不到结果的会话
+
+use std::time::{Duration, Instant}; // 截止时间
+
+use tonic::{Code, Request, Status}; // 处理函数收到的请求、超时错误
+
+// 截止时间所在的元数据键
+const GRPC_TIMEOUT_HEADER: &str = "grpc-timeout";
+
+/// 一次 RPC 的截止时间，客户端没有给出时没有限制
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Deadline(Option<Instant>);
+
+impl Deadline {
+    // 由请求的 grpc-timeout 计算截止时间，应在处理函数开始时调用；格式不正确时视为没有限制
+    pub(crate) fn of<T>(request: &Request<T>) -> Deadline {
+        let timeout = request.metadata().get(GRPC_TIMEOUT_HEADER).and_then(|value| value.to_str().ok()).and_then(parse_timeout);
+        Deadline(timeout.and_then(|timeout| Instant::now().checked_add(timeout)))
+    }
+
+    // 截止时间已过时返回 DeadlineExceeded，step 为将要开始的步骤
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn check(&self, step: &str) -> Result<(), Status> {
+        match self.0 {
+            Some(deadline) if Instant::now() >= deadline => Err(Status::new(Code::DeadlineExceeded, format!("deadline exceeded before {}", step))),
+            _ => Ok(()),
+        }
+    }
+}
+
+// grpc-timeout 的格式：最多 8 位数字加一个单位（H、M、S、m、u、n）
+fn parse_timeout(value: &str) -> Option<Duration> {
+    if value.len() < 2 || value.len() > 9 {
+        return None;
+    }
+    let (digits, unit) = value.split_at(value.len() - 1);
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount: u64 = digits.parse().ok()?;
+    Some(match unit {
+        "H" => Duration::from_secs(amount * 60 * 60),
+        "M" => Duration::from_secs(amount * 60),
+        "S" => Duration::from_secs(amount),
+        "m" => Duration::from_millis(amount),
+        "u" => Duration::from_micros(amount),
+        "n" => Duration::from_nanos(amount),
+        _ => return None,
+    })
+}
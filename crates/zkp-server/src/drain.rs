@@ -0,0 +1,77 @@
+//! 排空模式：滚动发布时先让实例停止接受新的注册和登录，等负载均衡器把它摘除、进行中的登录完成后再停止
+//!
+//! 排空时 `grpc.health.v1.Health` 对整个服务器和各个版本的 Auth 服务报告 NOT_SERVING，注册和挑战请求
+//! （Register、CreateAuthenticationChallenge、Authenticate 流中的承诺，以及 REST 网关的对应接口）返回带有重试提示的 Unavailable；
+//! 已经签发的挑战仍然可以应答，会话查询、注销和吊销通知不受影响。
+//! 用管理接口的 SetDrainMode 或 `AuthImpl::set_draining` 切换，`server` 进程收到 SIGUSR1 时进入、收到 SIGUSR2 时退出；
+//! 状态只在当前实例的内存中，重启后恢复正常服务
+
+use std::time::Duration; // 重试提示
+
+use tokio::sync::watch; // 排空状态，健康检查在状态改变时更新
+use tonic::server::NamedService; // 健康检查中的服务名
+use tonic::{Code, Status}; // 排空时拒绝请求
+use tonic_health::server::HealthReporter; // 更新健康检查的状态
+use tonic_health::ServingStatus; // SERVING 和 NOT_SERVING
+use tracing::debug; // 被拒绝的请求
+
+use zkp_proto::detail::with_error_code; // 错误的结构化详情
+use zkp_proto::retry::with_retry_after; // 告诉客户端何时重试
+use zkp_proto::zkp_auth::ErrorCode; // 详情中的错误分类
+
+use crate::{AuthImpl, AuthServer, AuthV2Server, AuthV2Impl, V1}; // 报告状态的服务
+
+// 排空时建议客户端等待的时间，负载均衡器通常在这段时间内摘除实例，重试交给其他实例
+const DRAIN_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// 健康检查中报告状态的服务：空字符串表示整个服务器，以及各个版本的 Auth 服务
+pub const HEALTH_SERVICES: [&str; 4] = ["", <AuthServer<AuthImpl> as NamedService>::NAME, <V1<AuthServer<AuthImpl>> as NamedService>::NAME, <AuthV2Server<AuthV2Impl> as NamedService>::NAME];
+
+// 是否在排空；watch 通道使健康检查在状态改变时更新，不需要轮询
+#[derive(Debug)]
+pub(crate) struct Drain(watch::Sender<bool>);
+
+impl Default for Drain {
+    fn default() -> Self {
+        Drain(watch::channel(false).0)
+    }
+}
+
+impl Drain {
+    // 设置排空状态，返回此前的状态
+    pub(crate) fn set(&self, draining: bool) -> bool {
+        self.0.send_replace(draining)
+    }
+
+    pub(crate) fn is_draining(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    // 排空时拒绝新的注册和挑战，rpc 只用于日志
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn check(&self, rpc: &'static str) -> Result<(), Status> {
+        if !self.is_draining() {
+            return Ok(());
+        }
+        debug!(rpc, "rejected a request while draining");
+        Err(with_error_code(with_retry_after(Code::Unavailable, "the server is draining, retry later or on another instance", DRAIN_RETRY_AFTER), ErrorCode::Draining))
+    }
+
+    // 订阅排空状态的改变
+    pub(crate) fn subscribe(&self) -> watch::Receiver<bool> {
+        self.0.subscribe()
+    }
+}
+
+// 按排空状态更新健康检查中各个服务的状态，持有状态的 AuthImpl 被释放后返回
+pub(crate) async fn report_health(mut draining: watch::Receiver<bool>, mut reporter: HealthReporter) {
+    loop {
+        let status = if *draining.borrow_and_update() { ServingStatus::NotServing } else { ServingStatus::Serving };
+        for service in HEALTH_SERVICES {
+            reporter.set_service_status(service, status).await;
+        }
+        if draining.changed().await.is_err() {
+            return;
+        }
+    }
+}
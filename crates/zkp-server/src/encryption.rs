@@ -0,0 +1,372 @@
+//! 静态加密：用服务器持有的密钥加密写入存储的用户记录和会话 ID，数据库文件、备份或 Redis 被读取时不泄露其中的凭据
+//!
+//! `EncryptedStore` 包装任何 `UserStore` / `SessionStore`：用户记录中的 y1、y2、TOTP 密钥和恢复码的哈希以 AES-256-GCM 加密，
+//! 附加数据是字段名和用户名，密文不能移到其他用户或其他字段；会话 ID 以确定性的方式加密后作为存储中的键，
+//! 持有数据库内容而没有密钥时既不能使用其中的会话，也不能由会话 ID 找到会话。挑战只存在几十秒，其中的承诺和挑战值是公开的，不加密
+//!
+//! 密钥文件每行一个密钥，格式为 `<密钥编号>:<64 个十六进制字符>`，`#` 开头的行是注释；第一个密钥加密新写入的数据，
+//! 其他密钥只用于读取以前写入的数据。轮换密钥时在第一行加入新的密钥并重启服务器，执行 `server rotate-keys` 用新的密钥重新加密所有用户，
+//! 旧的密钥建立的会话全部过期（session_ttl_secs）之后才能从文件中删除旧的密钥。
+//! 密钥也可以由 KMS 提供：`encryption_key_command` 设置的命令的标准输出与密钥文件的格式相同，例如解密 KMS 加密的密钥文件
+//!
+//! 开启加密之前写入的记录和会话仍然可以读取，`rotate-keys` 同样用来加密已有的用户记录
+
+use std::collections::HashSet; // 检查重复的密钥编号
+use std::fmt; // 调试输出不包含密钥
+use std::path::Path; // 密钥文件的路径
+use std::process::Command; // 从 KMS 取得密钥的命令
+use std::sync::Arc; // 被包装的存储和共享的密钥
+
+use aes_gcm::aead::{Aead, AeadCore, OsRng, Payload}; // 加密和随机 nonce
+use aes_gcm::{Aes256Gcm, Key, KeyInit, Nonce}; // AES-256-GCM
+use hmac::{Hmac, Mac}; // 派生子密钥，会话 ID 的 nonce
+use num_bigint::BigUint; // 用户记录中的公开值
+use sha2::Sha256; // HMAC-SHA256
+
+use crate::store::{PendingChallenge, Purged, SessionInfo, SessionStore, StoreError, StoreResult, UserInfo, UserStore, UserUpdate}; // 被包装的存储
+
+// 加密的字段以此开头，之后是 4 字节的密钥编号、12 字节的 nonce 和密文；首字节不为 0，作为大整数保存时不会丢失
+const MAGIC: &[u8] = b"zke1";
+
+// 加密后的会话 ID 的前缀，之后是密钥编号、nonce 和密文的十六进制；服务器生成的会话 ID 只有字母和数字，不会以此开头
+const SESSION_PREFIX: &str = "zke1.";
+
+// 密钥编号的字节数
+const KEY_ID_LEN: usize = 4;
+
+// AES-GCM 的 nonce 字节数
+const NONCE_LEN: usize = 12;
+
+// 加密的字段，作为附加数据的一部分
+const Y1: &str = "y1";
+const Y2: &str = "y2";
+const TOTP_SECRET: &str = "totp_secret";
+const RECOVERY_CODE: &str = "recovery_code";
+const SESSION_ID: &str = "session_id";
+
+// 一个密钥派生出的加密密钥和会话 ID 的 nonce 密钥
+struct RecordKey {
+    id: u32,             // 密钥编号，写在密文前面，读取时按编号选择密钥
+    cipher: Aes256Gcm,   // 加密字段和会话 ID
+    nonce_key: [u8; 32], // 由会话 ID 派生 nonce，同一个会话 ID 在同一个密钥下的密文相同
+}
+
+impl RecordKey {
+    fn new(id: u32, secret: &[u8]) -> Self {
+        let cipher_key = hmac_sha256(secret, b"zkp-server record encryption");
+        RecordKey { id, cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&cipher_key)), nonce_key: hmac_sha256(secret, b"zkp-server session id nonce") }
+    }
+
+    // 密钥编号、nonce 和密文
+    fn seal(&self, nonce: &Nonce<<Aes256Gcm as AeadCore>::NonceSize>, aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let ciphertext = self.cipher.encrypt(nonce, Payload { msg: plaintext, aad }).expect("AES-GCM encrypts messages of any practical length");
+        let mut sealed = Vec::with_capacity(KEY_ID_LEN + NONCE_LEN + ciphertext.len());
+        sealed.extend_from_slice(&self.id.to_be_bytes());
+        sealed.extend_from_slice(nonce);
+        sealed.extend_from_slice(&ciphertext);
+        sealed
+    }
+
+    // 会话 ID 的密文：nonce 由会话 ID 派生，查找时可以由会话 ID 重新计算出存储中的键
+    fn session_key(&self, session_id: &str) -> String {
+        let nonce = hmac_sha256(&self.nonce_key, session_id.as_bytes());
+        format!("{}{}", SESSION_PREFIX, hex::encode(self.seal(Nonce::from_slice(&nonce[..NONCE_LEN]), SESSION_ID.as_bytes(), session_id.as_bytes())))
+    }
+}
+
+// HMAC-SHA256，用于由同一个密钥派生互不相关的子密钥，以及由会话 ID 派生 nonce
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+/// 静态加密的密钥：第一个密钥加密新写入的数据，其他密钥只用于读取轮换前写入的数据
+pub struct Keyring {
+    keys: Vec<RecordKey>, // 至少一个，编号不重复
+}
+
+// 调试输出只包含密钥编号
+impl fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keyring").field("key_ids", &self.key_ids()).finish()
+    }
+}
+
+impl Keyring {
+    /// 由密钥编号和 32 字节的密钥创建，第一个密钥加密新写入的数据
+    ///
+    /// 参数:
+    /// - `keys`: 密钥编号和密钥，至少一个，编号不重复
+    ///
+    /// 返回:
+    /// - `Result<Keyring, String>`: 没有密钥、密钥长度不是 32 字节或编号重复时返回错误
+    pub fn new(keys: &[(u32, Vec<u8>)]) -> Result<Keyring, String> {
+        if keys.is_empty() {
+            return Err("no encryption keys".to_string());
+        }
+        let mut ids = HashSet::new();
+        for (id, secret) in keys {
+            if secret.len() != 32 {
+                return Err(format!("encryption key {} must be 32 bytes, got {}", id, secret.len()));
+            }
+            if !ids.insert(*id) {
+                return Err(format!("duplicate encryption key id {}", id));
+            }
+        }
+        Ok(Keyring { keys: keys.iter().map(|(id, secret)| RecordKey::new(*id, secret)).collect() })
+    }
+
+    /// 解析密钥文件的内容：每行 `<密钥编号>:<64 个十六进制字符>`，空行和 `#` 开头的行被忽略
+    pub fn parse(text: &str) -> Result<Keyring, String> {
+        let mut keys = Vec::new();
+        for (number, line) in text.lines().enumerate().map(|(i, line)| (i + 1, line.trim())) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (id, secret) = line.split_once(':').ok_or_else(|| format!("line {}: expected <key id>:<hex key>", number))?;
+            let id = id.trim().parse().map_err(|e| format!("line {}: invalid key id: {}", number, e))?;
+            let secret = hex::decode(secret.trim()).map_err(|e| format!("line {}: invalid key: {}", number, e))?;
+            keys.push((id, secret));
+        }
+        Keyring::new(&keys)
+    }
+
+    /// 读取密钥文件
+    pub fn from_file(path: &Path) -> Result<Keyring, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        Keyring::parse(&text).map_err(|e| format!("invalid encryption key file {}: {}", path.display(), e))
+    }
+
+    /// 用 `sh -c` 执行命令，标准输出与密钥文件的格式相同；用于从 KMS 取得密钥，命令失败时返回它的标准错误
+    pub fn from_command(command: &str) -> Result<Keyring, String> {
+        let output = Command::new("sh").arg("-c").arg(command).output().map_err(|e| format!("could not run the encryption key command: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("the encryption key command failed ({}): {}", output.status, String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        let text = String::from_utf8(output.stdout).map_err(|_| "the encryption key command printed invalid UTF-8".to_string())?;
+        Keyring::parse(&text).map_err(|e| format!("invalid output of the encryption key command: {}", e))
+    }
+
+    /// 密钥编号，第一个是加密新数据的密钥
+    pub fn key_ids(&self) -> Vec<u32> {
+        self.keys.iter().map(|key| key.id).collect()
+    }
+
+    fn current(&self) -> &RecordKey {
+        &self.keys[0]
+    }
+
+    fn key(&self, id: u32) -> StoreResult<&RecordKey> {
+        self.keys.iter().find(|key| key.id == id).ok_or_else(|| StoreError(format!("data was encrypted with unknown key {}", id)))
+    }
+
+    // 用当前密钥加密一个字段，nonce 随机
+    fn seal(&self, field: &str, user: &str, plaintext: &[u8]) -> Vec<u8> {
+        let key = self.current();
+        let mut sealed = MAGIC.to_vec();
+        sealed.extend(key.seal(&Aes256Gcm::generate_nonce(&mut OsRng), &aad(field, user), plaintext));
+        sealed
+    }
+
+    // 解密一个字段；不以 MAGIC 开头的是开启加密之前写入的明文，原样返回
+    fn open(&self, field: &str, user: &str, stored: &[u8]) -> StoreResult<Vec<u8>> {
+        match stored.strip_prefix(MAGIC) {
+            Some(sealed) => self.open_sealed(&aad(field, user), sealed).map_err(|e| StoreError(format!("could not decrypt {} of user {:?}: {}", field, user, e.0))),
+            None => Ok(stored.to_vec()),
+        }
+    }
+
+    // 解密密钥编号、nonce 和密文
+    fn open_sealed(&self, aad: &[u8], sealed: &[u8]) -> StoreResult<Vec<u8>> {
+        if sealed.len() < KEY_ID_LEN + NONCE_LEN {
+            return Err(StoreError("truncated ciphertext".to_string()));
+        }
+        let (id, rest) = sealed.split_at(KEY_ID_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let key = self.key(u32::from_be_bytes(id.try_into().expect("split at KEY_ID_LEN")))?;
+        key.cipher.decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad }).map_err(|_| StoreError("ciphertext failed authentication".to_string()))
+    }
+
+    // 存储中的会话 ID 还原为会话 ID；开启加密之前写入的会话 ID 原样返回
+    fn open_session_id(&self, stored: &str) -> StoreResult<String> {
+        let Some(sealed) = stored.strip_prefix(SESSION_PREFIX) else {
+            return Ok(stored.to_string());
+        };
+        let sealed = hex::decode(sealed).map_err(|e| StoreError(format!("invalid encrypted session id: {}", e)))?;
+        let session_id = self.open_sealed(SESSION_ID.as_bytes(), &sealed).map_err(|e| StoreError(format!("could not decrypt a session id: {}", e.0)))?;
+        String::from_utf8(session_id).map_err(|_| StoreError("decrypted session id is not UTF-8".to_string()))
+    }
+
+    // 查找会话时依次尝试的键：当前密钥和旧密钥下的密文，以及开启加密之前写入的会话 ID 本身；
+    // 以前缀开头的是存储中的键，持有数据库内容的人不能把它当作会话 ID 使用
+    fn session_lookups(&self, session_id: &str) -> Vec<String> {
+        let mut lookups: Vec<String> = self.keys.iter().map(|key| key.session_key(session_id)).collect();
+        if !session_id.starts_with(SESSION_PREFIX) {
+            lookups.push(session_id.to_string());
+        }
+        lookups
+    }
+
+    // 加密用户记录中的凭据
+    fn seal_user(&self, user: &str, mut info: UserInfo) -> UserInfo {
+        info.y1 = BigUint::from_bytes_be(&self.seal(Y1, user, &info.y1.to_bytes_be()));
+        info.y2 = BigUint::from_bytes_be(&self.seal(Y2, user, &info.y2.to_bytes_be()));
+        if !info.totp_secret.is_empty() {
+            info.totp_secret = self.seal(TOTP_SECRET, user, &info.totp_secret);
+        }
+        info.recovery_codes = info.recovery_codes.iter().map(|code| self.seal(RECOVERY_CODE, user, code)).collect();
+        info
+    }
+
+    // 解密用户记录中的凭据
+    fn open_user(&self, user: &str, mut info: UserInfo) -> StoreResult<UserInfo> {
+        info.y1 = BigUint::from_bytes_be(&self.open(Y1, user, &info.y1.to_bytes_be())?);
+        info.y2 = BigUint::from_bytes_be(&self.open(Y2, user, &info.y2.to_bytes_be())?);
+        info.totp_secret = self.open(TOTP_SECRET, user, &info.totp_secret)?;
+        info.recovery_codes = info.recovery_codes.iter().map(|code| self.open(RECOVERY_CODE, user, code)).collect::<StoreResult<_>>()?;
+        Ok(info)
+    }
+}
+
+// 附加数据：字段名和用户名，以 0 分隔
+fn aad(field: &str, user: &str) -> Vec<u8> {
+    [field.as_bytes(), &[0], user.as_bytes()].concat()
+}
+
+/// 加密写入被包装的存储的用户记录和会话 ID，读取时解密
+pub struct EncryptedStore<T: ?Sized> {
+    inner: Arc<T>,      // 被包装的存储
+    keys: Arc<Keyring>, // 静态加密的密钥
+}
+
+impl<T: ?Sized> EncryptedStore<T> {
+    /// 用 `keys` 加密写入 `inner` 的数据
+    pub fn new(inner: Arc<T>, keys: Arc<Keyring>) -> Self {
+        EncryptedStore { inner, keys }
+    }
+}
+
+impl<T: fmt::Debug + ?Sized> fmt::Debug for EncryptedStore<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptedStore").field("inner", &self.inner).field("keys", &self.keys).finish()
+    }
+}
+
+#[tonic::async_trait]
+impl UserStore for EncryptedStore<dyn UserStore> {
+    async fn put_user(&self, user: &str, info: UserInfo) -> StoreResult<()> {
+        self.inner.put_user(user, self.keys.seal_user(user, info)).await
+    }
+
+    async fn create_user(&self, user: &str, info: UserInfo) -> StoreResult<bool> {
+        self.inner.create_user(user, self.keys.seal_user(user, info)).await
+    }
+
+    async fn get_user(&self, user: &str) -> StoreResult<Option<UserInfo>> {
+        self.inner.get_user(user).await?.map(|info| self.keys.open_user(user, info)).transpose()
+    }
+
+    // update 修改解密后的记录，写回时用当前密钥重新加密；无法解密时放弃修改并返回错误
+    async fn update_user(&self, user: &str, update: UserUpdate<'_>) -> StoreResult<bool> {
+        let mut failure = None;
+        let keys = &self.keys;
+        let updated = self
+            .inner
+            .update_user(
+                user,
+                Box::new(|stored| {
+                    let mut info = match keys.open_user(user, std::mem::take(stored)) {
+                        Ok(info) => info,
+                        Err(err) => {
+                            failure = Some(err);
+                            return false;
+                        }
+                    };
+                    if !update(&mut info) {
+                        return false;
+                    }
+                    *stored = keys.seal_user(user, info);
+                    true
+                }),
+            )
+            .await?;
+        match failure {
+            Some(err) => Err(err),
+            None => Ok(updated),
+        }
+    }
+
+    async fn delete_user(&self, user: &str) -> StoreResult<bool> {
+        self.inner.delete_user(user).await
+    }
+
+    async fn list_users(&self, after: &str, limit: u32) -> StoreResult<Vec<(String, UserInfo)>> {
+        let users = self.inner.list_users(after, limit).await?;
+        users.into_iter().map(|(name, info)| Ok((name.clone(), self.keys.open_user(&name, info)?))).collect()
+    }
+}
+
+#[tonic::async_trait]
+impl SessionStore for EncryptedStore<dyn SessionStore> {
+    async fn put_challenge(&self, auth_id: &str, challenge: PendingChallenge) -> StoreResult<()> {
+        self.inner.put_challenge(auth_id, challenge).await
+    }
+
+    async fn get_challenge(&self, auth_id: &str) -> StoreResult<Option<PendingChallenge>> {
+        self.inner.get_challenge(auth_id).await
+    }
+
+    async fn take_challenge(&self, auth_id: &str) -> StoreResult<Option<PendingChallenge>> {
+        self.inner.take_challenge(auth_id).await
+    }
+
+    async fn count_challenges(&self, user: &str) -> StoreResult<u32> {
+        self.inner.count_challenges(user).await
+    }
+
+    async fn delete_challenges(&self, user: &str) -> StoreResult<()> {
+        self.inner.delete_challenges(user).await
+    }
+
+    async fn put_session(&self, session_id: &str, session: SessionInfo) -> StoreResult<()> {
+        self.inner.put_session(&self.keys.current().session_key(session_id), session).await
+    }
+
+    // 会话 ID 无效时每个密钥都要查找一次，密钥文件中只保留会话还可能有效的旧密钥
+    async fn get_session(&self, session_id: &str) -> StoreResult<Option<SessionInfo>> {
+        for lookup in self.keys.session_lookups(session_id) {
+            if let Some(session) = self.inner.get_session(&lookup).await? {
+                return Ok(Some(session));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn delete_session(&self, session_id: &str) -> StoreResult<Option<SessionInfo>> {
+        for lookup in self.keys.session_lookups(session_id) {
+            if let Some(session) = self.inner.delete_session(&lookup).await? {
+                return Ok(Some(session));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn list_sessions(&self, user: &str) -> StoreResult<Vec<(String, SessionInfo)>> {
+        let sessions = self.inner.list_sessions(user).await?;
+        sessions.into_iter().map(|(stored, session)| Ok((self.keys.open_session_id(&stored)?, session))).collect()
+    }
+
+    async fn delete_sessions(&self, user: &str) -> StoreResult<Vec<String>> {
+        let stored = self.inner.delete_sessions(user).await?;
+        stored.iter().map(|stored| self.keys.open_session_id(stored)).collect()
+    }
+
+    async fn count_sessions(&self, now: u64) -> StoreResult<u64> {
+        self.inner.count_sessions(now).await
+    }
+
+    async fn purge_expired(&self, now: u64) -> StoreResult<Purged> {
+        self.inner.purge_expired(now).await
+    }
+}
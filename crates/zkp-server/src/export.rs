@@ -0,0 +1,376 @@
+//! 用户记录的导出和导入：备份，或者在存储后端之间迁移（例如从 SQLite 迁移到 PostgreSQL），由 `server export-users`
+//! 和 `server import-users` 使用
+//!
+//! 导出的是完整的用户记录，包括恢复码的哈希，导出文件应当与数据库一样保护。TOTP 共享密钥是明文的第二因素，默认不导出，
+//! 只记录用户启用了 TOTP；明确要求时才写出密钥，这样的用户只能从包含密钥的导出文件导入。字节串以十六进制表示，
+//! `group` 为注册时的群的参数集标识，为空时是服务器的默认群。挑战和会话有效期短，不导出
+//!
+//! 两种格式：`json` 每行一个 JSON 对象（JSON Lines），`csv` 第一行为列名，列表和映射在单元格中以 JSON 表示
+
+use std::collections::{BTreeMap, HashSet}; // 导出的元数据按键排序；导入时检查重复的用户名
+use std::io::Write; // 写到文件或标准输出
+
+use clap::ValueEnum; // 命令行中的格式参数
+use num_bigint::BigUint; // 公开值
+use serde::{Deserialize, Serialize}; // 每个用户一个 JSON 对象
+
+use zkp_core::{GroupElement, GroupParams}; // 导入时检查公开值是群中的元素
+use zkp_proto::zkp_auth::KdfParams; // 用户记录中的 KDF 参数
+
+use crate::store::{UserInfo, UserStore}; // 用户记录的存储
+
+// 导出时每次从存储读取的用户数
+const EXPORT_PAGE_SIZE: u32 = 500;
+
+// CSV 的列，与 ExportedUser 的字段顺序一致
+const CSV_COLUMNS: [&str; 19] = [
+    "user", "group", "y1", "y2", "salt", "kdf_algorithm", "kdf_iterations", "scopes", "metadata", "display_name", "contact", "created_at", "recovery_codes",
+    "reset_required", "guardians", "guardian_threshold", "totp_secret", "guardian_bindings", "totp_enabled",
+];
+
+// 增加 guardian_bindings 之前导出的 CSV 只有前 17 列，增加 totp_enabled 之前只有前 18 列，导入时缺少的列为空
+const LEGACY_CSV_COLUMNS: usize = 17;
+
+/// 导出文件的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// 每行一个 JSON 对象
+    Json,
+    /// 第一行为列名的 CSV
+    Csv,
+}
+
+/// 导出文件中的一个用户
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExportedUser {
+    pub user: String,                       // 用户名
+    pub group: String,                      // 注册时的群的参数集标识（十六进制），为空时为默认的群
+    pub y1: String,                         // 公开值 y1（十六进制，大端）
+    pub y2: String,                         // 公开值 y2
+    pub salt: String,                       // 派生私钥的盐（十六进制）
+    pub kdf_algorithm: String,              // KDF 算法，没有 KDF 参数时为空
+    pub kdf_iterations: u32,                // KDF 迭代次数
+    pub scopes: Vec<String>,                // 权限范围
+    pub metadata: BTreeMap<String, String>, // 注册时的元数据
+    pub display_name: String,               // 账户资料
+    pub contact: String,
+    pub created_at: u64,                    // 注册时间（Unix 时间戳，秒）
+    pub recovery_codes: Vec<String>,        // 尚未使用的恢复码的 SHA-256 哈希（十六进制）
+    pub reset_required: bool,               // 恢复登录后尚未重置密码
+    pub guardians: Vec<String>,             // 多方恢复的监护人
+    pub guardian_threshold: u32,            // 多方恢复的门限
+    pub totp_secret: String,                // TOTP 共享密钥（十六进制），未启用或导出时没有包含密钥时为空
+    pub guardian_bindings: Vec<String>,     // 各监护人被指定时账户的指纹（十六进制），与 guardians 一一对应
+    pub totp_enabled: bool,                 // 启用了 TOTP；旧的导出文件没有这一项，以 totp_secret 是否为空为准
+}
+
+impl ExportedUser {
+    /// 由存储中的用户记录构建
+    ///
+    /// 参数:
+    /// - `user`: 用户名
+    /// - `info`: 用户记录
+    /// - `include_totp_secret`: 为 true 时写出 TOTP 共享密钥，否则只记录用户启用了 TOTP
+    pub fn new(user: &str, info: &UserInfo, include_totp_secret: bool) -> Self {
+        let kdf = info.kdf.clone().unwrap_or_default();
+        ExportedUser {
+            user: user.to_string(),
+            group: hex::encode(&info.group),
+            y1: hex::encode(info.y1.to_bytes_be()),
+            y2: hex::encode(info.y2.to_bytes_be()),
+            salt: hex::encode(&info.salt),
+            kdf_algorithm: kdf.algorithm,
+            kdf_iterations: kdf.iterations,
+            scopes: info.scopes.clone(),
+            metadata: info.metadata.iter().map(|(key, value)| (key.clone(), value.clone())).collect(),
+            display_name: info.display_name.clone(),
+            contact: info.contact.clone(),
+            created_at: info.created_at,
+            recovery_codes: info.recovery_codes.iter().map(hex::encode).collect(),
+            reset_required: info.reset_required,
+            guardians: info.guardians.clone(),
+            guardian_threshold: info.guardian_threshold,
+            totp_secret: if include_totp_secret { hex::encode(&info.totp_secret) } else { String::new() },
+            guardian_bindings: info.guardian_bindings.iter().map(hex::encode).collect(),
+            totp_enabled: !info.totp_secret.is_empty(),
+        }
+    }
+
+    /// 转换为用户记录，检查群是服务器接受的群、公开值是该群的子群中的元素
+    ///
+    /// 参数:
+    /// - `groups`: 服务器接受的群，第一个是默认的群
+    ///
+    /// 返回:
+    /// - `Result<UserInfo, String>`: 用户记录，或者字段无法解析、群不被接受、公开值不在子群中、启用了 TOTP 却没有密钥的原因
+    pub fn to_user_info(&self, groups: &[&GroupParams]) -> Result<UserInfo, String> {
+        let bytes = |field: &str, text: &str| hex::decode(text).map_err(|e| format!("{}: {}", field, e));
+        if self.user.is_empty() {
+            return Err("user is empty".to_string());
+        }
+        // 导入后用户会在不知情时失去第二因素
+        if self.totp_enabled && self.totp_secret.is_empty() {
+            return Err("totp_secret was left out of the export, export again with --include-totp-secrets".to_string());
+        }
+        let group = bytes("group", &self.group)?;
+        let zkp = if group.is_empty() {
+            groups[0]
+        } else {
+            *groups.iter().find(|zkp| zkp.params_hash() == group).ok_or_else(|| format!("group {} is not accepted by this server", self.group))?
+        };
+        let values = vec![BigUint::from_bytes_be(&bytes("y1", &self.y1)?), BigUint::from_bytes_be(&bytes("y2", &self.y2)?)];
+        let mut elems = GroupElement::new_batch(values, zkp).map_err(|(i, e)| format!("{}: {}", ["y1", "y2"][i], e))?.into_iter().map(GroupElement::into_inner);
+        Ok(UserInfo {
+            y1: elems.next().unwrap(),
+            y2: elems.next().unwrap(),
+            salt: bytes("salt", &self.salt)?,
+            kdf: (!self.kdf_algorithm.is_empty()).then(|| KdfParams { algorithm: self.kdf_algorithm.clone(), iterations: self.kdf_iterations }),
+            scopes: self.scopes.clone(),
+            metadata: self.metadata.iter().map(|(key, value)| (key.clone(), value.clone())).collect(),
+            display_name: self.display_name.clone(),
+            contact: self.contact.clone(),
+            created_at: self.created_at,
+            recovery_codes: self.recovery_codes.iter().map(|code| bytes("recovery_codes", code)).collect::<Result<_, _>>()?,
+            reset_required: self.reset_required,
+            guardians: self.guardians.clone(),
+            guardian_bindings: self.guardian_bindings.iter().map(|binding| bytes("guardian_bindings", binding)).collect::<Result<_, _>>()?,
+            guardian_threshold: self.guardian_threshold,
+            totp_secret: bytes("totp_secret", &self.totp_secret)?,
+            group,
+        })
+    }
+
+    // CSV 中的一行，顺序与 CSV_COLUMNS 一致
+    fn to_cells(&self) -> Vec<String> {
+        fn json<T: Serialize>(value: &T) -> String {
+            serde_json::to_string(value).expect("lists and maps of strings always serialize")
+        }
+        vec![
+            self.user.clone(),
+            self.group.clone(),
+            self.y1.clone(),
+            self.y2.clone(),
+            self.salt.clone(),
+            self.kdf_algorithm.clone(),
+            self.kdf_iterations.to_string(),
+            json(&self.scopes),
+            json(&self.metadata),
+            self.display_name.clone(),
+            self.contact.clone(),
+            self.created_at.to_string(),
+            json(&self.recovery_codes),
+            self.reset_required.to_string(),
+            json(&self.guardians),
+            self.guardian_threshold.to_string(),
+            self.totp_secret.clone(),
+            json(&self.guardian_bindings),
+            self.totp_enabled.to_string(),
+        ]
+    }
+
+    // 由 CSV 中的一行解析，单元格数已经检查，旧格式缺少的列为空
+    fn from_cells(cells: Vec<String>) -> Result<Self, String> {
+        let mut cells = CSV_COLUMNS.iter().zip(cells.into_iter().chain(std::iter::repeat(String::new())));
+        let mut next = || cells.next().expect("the number of cells is checked by the caller");
+        let text = |(_, cell): (&&str, String)| cell;
+        fn parse<T: std::str::FromStr>((column, cell): (&&str, String)) -> Result<T, String> {
+            cell.parse().map_err(|_| format!("{}: invalid value {:?}", column, cell))
+        }
+        fn json<T: serde::de::DeserializeOwned + Default>((column, cell): (&&str, String)) -> Result<T, String> {
+            if cell.is_empty() {
+                return Ok(T::default());
+            }
+            serde_json::from_str(&cell).map_err(|e| format!("{}: {}", column, e))
+        }
+        Ok(ExportedUser {
+            user: text(next()),
+            group: text(next()),
+            y1: text(next()),
+            y2: text(next()),
+            salt: text(next()),
+            kdf_algorithm: text(next()),
+            kdf_iterations: parse(next())?,
+            scopes: json(next())?,
+            metadata: json(next())?,
+            display_name: text(next()),
+            contact: text(next()),
+            created_at: parse(next())?,
+            recovery_codes: json(next())?,
+            reset_required: parse(next())?,
+            guardians: json(next())?,
+            guardian_threshold: parse(next())?,
+            totp_secret: text(next()),
+            guardian_bindings: json(next())?,
+            totp_enabled: match next() {
+                (_, cell) if cell.is_empty() => false,
+                cell => parse(cell)?,
+            },
+        })
+    }
+}
+
+/// `import_users` 的结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub created: u64,  // 新写入的用户
+    pub replaced: u64, // 替换了已存在的同名用户
+    pub skipped: u64,  // 已存在、没有替换的用户
+}
+
+/// 按用户名顺序导出存储中的所有用户
+///
+/// 参数:
+/// - `users`: 用户记录的存储
+/// - `format`: 导出格式
+/// - `include_totp_secrets`: 为 true 时写出 TOTP 共享密钥，否则启用了 TOTP 的用户不能从这次导出中导入
+/// - `out`: 输出，例如文件或标准输出
+///
+/// 返回:
+/// - `Result<u64, String>`: 导出的用户数，或者读取存储、写入输出失败的原因
+pub async fn export_users(users: &dyn UserStore, format: ExportFormat, include_totp_secrets: bool, out: &mut impl Write) -> Result<u64, String> {
+    let write_error = |e: std::io::Error| format!("could not write the export: {}", e);
+    if format == ExportFormat::Csv {
+        write_csv_row(out, CSV_COLUMNS.iter().map(|column| column.to_string())).map_err(write_error)?;
+    }
+    let mut count = 0;
+    let mut after = String::new();
+    loop {
+        let page = users.list_users(&after, EXPORT_PAGE_SIZE).await.map_err(|e| e.to_string())?;
+        for (user, info) in &page {
+            let exported = ExportedUser::new(user, info, include_totp_secrets);
+            match format {
+                ExportFormat::Json => writeln!(out, "{}", serde_json::to_string(&exported).expect("an exported user always serializes")),
+                ExportFormat::Csv => write_csv_row(out, exported.to_cells().into_iter()),
+            }
+            .map_err(write_error)?;
+            count += 1;
+        }
+        match page.last() {
+            Some((user, _)) if page.len() == EXPORT_PAGE_SIZE as usize => after.clone_from(user),
+            _ => break,
+        }
+    }
+    out.flush().map_err(write_error)?;
+    Ok(count)
+}
+
+/// 导入 `export_users` 导出的用户：先解析并检查全部记录，任何一条有错误时不写入任何用户
+///
+/// 参数:
+/// - `users`: 用户记录的存储
+/// - `groups`: 服务器接受的群，第一个是默认的群；导入的用户必须属于其中之一
+/// - `format`: 导出格式
+/// - `text`: 导出文件的内容
+/// - `replace`: 为 true 时替换已存在的同名用户，否则跳过
+///
+/// 返回:
+/// - `Result<ImportSummary, String>`: 写入和跳过的用户数，或者第一条有错误的记录及原因
+pub async fn import_users(users: &dyn UserStore, groups: &[&GroupParams], format: ExportFormat, text: &str, replace: bool) -> Result<ImportSummary, String> {
+    let exported = match format {
+        ExportFormat::Json => text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| serde_json::from_str(line).map_err(|e| format!("line {}: {}", i + 1, e)))
+            .collect::<Result<Vec<ExportedUser>, _>>()?,
+        ExportFormat::Csv => {
+            let mut rows = parse_csv(text)?.into_iter();
+            let columns = match rows.next() {
+                Some(header) if header.len() >= LEGACY_CSV_COLUMNS && header == CSV_COLUMNS[..header.len().min(CSV_COLUMNS.len())] => header.len(),
+                _ => return Err(format!("the first row must be the columns {}", CSV_COLUMNS.join(","))),
+            };
+            rows.enumerate()
+                .map(|(i, row)| match row.len() {
+                    len if len == columns => ExportedUser::from_cells(row).map_err(|e| format!("row {}: {}", i + 2, e)),
+                    len => Err(format!("row {}: expected {} columns, found {}", i + 2, columns, len)),
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+
+    let mut seen = HashSet::new();
+    let mut records = Vec::with_capacity(exported.len());
+    for user in &exported {
+        let info = user.to_user_info(groups).map_err(|e| format!("user {:?}: {}", user.user, e))?;
+        if !seen.insert(user.user.as_str()) {
+            return Err(format!("user {:?} appears more than once", user.user));
+        }
+        records.push((user.user.as_str(), info));
+    }
+
+    let mut summary = ImportSummary::default();
+    for (user, info) in records {
+        if users.create_user(user, info.clone()).await.map_err(|e| e.to_string())? {
+            summary.created += 1;
+        } else if replace {
+            users.put_user(user, info).await.map_err(|e| e.to_string())?;
+            summary.replaced += 1;
+        } else {
+            summary.skipped += 1;
+        }
+    }
+    Ok(summary)
+}
+
+// 写一行 CSV：含有逗号、引号或换行的单元格加引号，引号写两次
+fn write_csv_row(out: &mut impl Write, cells: impl Iterator<Item = String>) -> std::io::Result<()> {
+    let row: Vec<String> = cells
+        .map(|cell| if cell.contains([',', '"', '\n', '\r']) { format!("\"{}\"", cell.replace('"', "\"\"")) } else { cell })
+        .collect();
+    writeln!(out, "{}", row.join(","))
+}
+
+// 解析 CSV（RFC 4180）：引号中的单元格可以包含逗号、两个引号和换行，行尾可以是 \n 或 \r\n，忽略空行
+fn parse_csv(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false; // 当前单元格以引号开始
+    let mut in_quotes = false; // 在引号之内
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            '"' if in_quotes => in_quotes = false,
+            '"' if cell.is_empty() && !quoted => {
+                quoted = true;
+                in_quotes = true;
+            }
+            '"' => return Err(format!("line {}: unexpected quote", line)),
+            '\n' if in_quotes => {
+                line += 1;
+                cell.push(ch);
+            }
+            ',' if !in_quotes => {
+                row.push(std::mem::take(&mut cell));
+                quoted = false;
+            }
+            '\r' if !in_quotes && chars.peek() == Some(&'\n') => {}
+            '\n' if !in_quotes => {
+                line += 1;
+                row.push(std::mem::take(&mut cell));
+                quoted = false;
+                if row.len() > 1 || !row[0].is_empty() {
+                    rows.push(std::mem::take(&mut row));
+                } else {
+                    row.clear();
+                }
+            }
+            _ if quoted && !in_quotes => return Err(format!("line {}: text after a closing quote", line)),
+            _ => cell.push(ch),
+        }
+    }
+    if in_quotes {
+        return Err(format!("line {}: unterminated quote", line));
+    }
+    if !cell.is_empty() || quoted || !row.is_empty() {
+        row.push(cell);
+        rows.push(row);
+    }
+    Ok(rows)
+}
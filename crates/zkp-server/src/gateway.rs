@@ -0,0 +1,360 @@
+//! REST/JSON 网关：在 gRPC 之外的另一个端口上以 HTTP/JSON 提供注册、登录和会话查询，没有 gRPC 栈的网页和移动客户端也可以使用服务器。
+//! 每个路由把 JSON 请求体转换为对应的 gRPC 消息，再调用同一个 `AuthImpl` 的处理函数，校验、限流、锁定和存储与 gRPC 接口完全相同：
+//!
+//! | 路由                 | gRPC 方法                      |
+//! |----------------------|--------------------------------|
+//! | `POST /v1/register`  | Register                       |
+//! | `POST /v1/challenge` | CreateAuthenticationChallenge  |
+//! | `POST /v1/verify`    | VerifyAuthentication           |
+//! | `POST /v1/session`   | ValidateSession                |
+//! | `POST /v1/logout`    | Logout                         |
+//!
+//! JSON 的字段名与 gRPC 消息相同，字节字段（y1、y2、r1、r2、c、s、盐、持有证明、params_hash 和 totp_secret）为十六进制字符串，省略的字段取默认值。
+//! 错误以 `{"code": "NotFound", "error_code": "USER_NOT_FOUND", "message": "..."}` 返回，HTTP 状态码由 gRPC 状态码对应，限流和账户锁定时带有 `Retry-After` 头，
+//! 请求校验失败时还带有出错的字段和原因，例如 `{"code": "InvalidArgument", "field": "y1", "reason": "not-in-subgroup", ...}`
+//!
+//! 网关本身不使用 TLS，应部署在终止 TLS 的反向代理之后；经过网关的请求没有 mTLS 客户端身份和通道绑定，
+//! 配置了 `registration_identities` 或 `require_channel_binding` 时对应的请求会被拒绝。
+//! 客户端地址与 gRPC 接口一样按 `ServerConfig::peers` 确定，反向代理在 `trusted_proxies` 中时取自 `x-forwarded-for`，
+//! 不接受的地址返回 403
+
+use std::collections::HashMap; // 请求中的元数据
+use std::net::SocketAddr; // 网关的监听地址和客户端地址
+use std::sync::Arc; // 与 gRPC 服务共享的 AuthImpl
+
+use axum::extract::{ConnectInfo, State}; // 对端地址和共享的 AuthImpl
+use axum::middleware::{self, Next}; // 每个请求的 span
+use axum::http::{header, HeaderValue, StatusCode}; // 错误响应
+use axum::response::{IntoResponse, Response}; // 错误响应
+use axum::routing::post; // 所有路由都是 POST
+use axum::{Extension, Json, Router}; // 客户端地址、JSON 请求体和路由
+use serde::{Deserialize, Serialize}; // JSON 请求体和响应
+use tonic::{Code, Request, Status}; // 调用 gRPC 处理函数
+use tracing::{field, info_span, Instrument}; // 每个请求的 span
+
+use zkp_proto::detail::error_code; // 错误的结构化详情
+use zkp_proto::invalid::invalid_field; // 校验失败时的出错字段和原因
+use zkp_proto::retry; // 限流和锁定时的重试提示
+use zkp_proto::zkp_auth::{
+    auth_server::Auth, // gRPC 处理函数
+    AuthenticationAnswerRequest, AuthenticationChallengeRequest, KdfParams, LogoutRequest, RegisterRequest, ValidateSessionRequest,
+};
+
+use crate::peer::ClientAddr; // 中间件确定的客户端地址
+use crate::AuthImpl; // 共享的处理逻辑和存储
+
+/// 网关的路由，`serve_gateway` 使用；嵌入服务器的程序可以把它合并到自己的 axum 应用中，
+/// 以 `into_make_service_with_connect_info::<SocketAddr>()` 提供时按客户端地址限流、检查允许和拒绝的网段
+///
+/// 参数:
+/// - `auth`: 与 gRPC 服务共享的 `AuthImpl`
+pub fn gateway_router(auth: Arc<AuthImpl>) -> Router {
+    Router::new()
+        .route("/v1/register", post(register))
+        .route("/v1/challenge", post(challenge))
+        .route("/v1/verify", post(verify))
+        .route("/v1/session", post(validate_session))
+        .route("/v1/logout", post(logout))
+        .layer(middleware::from_fn_with_state(auth.clone(), filter_peer))
+        .layer(middleware::from_fn(traced))
+        .with_state(auth)
+}
+
+/// 在 `addr` 上提供 REST/JSON 网关，直到出错；`run_server` 在配置了 `gateway_addr` 时自动启动
+///
+/// 参数:
+/// - `auth`: 与 gRPC 服务共享的 `AuthImpl`
+/// - `addr`: 网关的监听地址
+pub async fn serve_gateway(auth: Arc<AuthImpl>, addr: SocketAddr) -> hyper::Result<()> {
+    axum::Server::try_bind(&addr)?.serve(gateway_router(auth).into_make_service_with_connect_info::<SocketAddr>()).await
+}
+
+// 每个请求一个 http span（方法和路径），处理函数调用的 AuthImpl 和存储的 span 在它下面；
+// otel feature 下以请求头中的 traceparent 为父 span，状态码不是 2xx 时标记为错误
+async fn traced<B>(request: axum::http::Request<B>, next: Next<B>) -> Response {
+    let span = info_span!("http", method = %request.method(), path = request.uri().path(), otel.name = field::Empty, otel.kind = field::Empty, otel.status_code = field::Empty);
+    #[cfg(feature = "otel")]
+    crate::otel::start_span(&span, &format!("{} {}", request.method(), request.uri().path()), request.headers());
+    let response = next.run(request).instrument(span.clone()).await;
+    #[cfg(feature = "otel")]
+    crate::otel::finish_span(&span, !response.status().is_success());
+    response
+}
+
+// 按 ServerConfig::peers 确定客户端地址，写入请求的扩展由处理函数取用；不接受的地址返回 403，不调用处理函数
+// 没有对端地址（路由没有以 connect_info 提供）时不写入，allow 非空时拒绝
+async fn filter_peer<B>(State(auth): State<Arc<AuthImpl>>, peer: Option<ConnectInfo<SocketAddr>>, mut request: axum::http::Request<B>, next: Next<B>) -> Response {
+    let policy = &auth.config.peers;
+    let client = peer.map(|ConnectInfo(peer)| policy.client_addr(peer.ip(), request.headers()));
+    if !client.map_or(policy.allow.is_empty(), |client| policy.permits(client)) {
+        return GatewayError(Status::new(Code::PermissionDenied, "requests from this client address are not allowed")).into_response();
+    }
+    if let Some(client) = client {
+        request.extensions_mut().insert(ClientAddr(client));
+    }
+    next.run(request).await
+}
+
+// 字节字段的十六进制编码，允许 0x 前缀
+mod hex_bytes {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        hex::decode(text.strip_prefix("0x").unwrap_or(&text)).map_err(|e| de::Error::custom(format!("expected a hex string: {}", e)))
+    }
+}
+
+// 派生私钥的 KDF 参数
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Kdf {
+    algorithm: String,
+    iterations: u32,
+}
+
+impl From<Kdf> for KdfParams {
+    fn from(kdf: Kdf) -> Self {
+        KdfParams { algorithm: kdf.algorithm, iterations: kdf.iterations }
+    }
+}
+
+impl From<KdfParams> for Kdf {
+    fn from(kdf: KdfParams) -> Self {
+        Kdf { algorithm: kdf.algorithm, iterations: kdf.iterations }
+    }
+}
+
+// POST /v1/register 的请求体，对应 RegisterRequest
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RegisterBody {
+    user: String,
+    #[serde(with = "hex_bytes")]
+    y1: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    y2: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    salt: Vec<u8>,
+    kdf: Option<Kdf>,
+    #[serde(with = "hex_bytes")]
+    params_hash: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    proof_c: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    proof_s: Vec<u8>,
+    metadata: HashMap<String, String>,
+    proof_hash: String,
+    display_name: String,
+    contact: String,
+    recovery_codes: u32,
+    guardians: Vec<String>,
+    guardian_threshold: u32,
+    auth_id: String,
+    #[serde(with = "hex_bytes")]
+    s: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    totp_secret: Vec<u8>,
+    totp_code: String,
+}
+
+#[derive(Serialize)]
+struct RegisterReply {
+    recovery_codes: Vec<String>,
+}
+
+// POST /v1/challenge 的请求体，对应 AuthenticationChallengeRequest
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ChallengeBody {
+    user: String,
+    #[serde(with = "hex_bytes")]
+    r1: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    r2: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    params_hash: Vec<u8>,
+    metadata: HashMap<String, String>,
+    device_id: String,
+}
+
+#[derive(Serialize)]
+struct ChallengeReply {
+    auth_id: String,
+    #[serde(with = "hex_bytes")]
+    c: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    salt: Vec<u8>,
+    kdf: Option<Kdf>,
+    expires_at: u64,
+    channel_bound: bool,
+    #[serde(with = "hex_bytes")]
+    params_hash: Vec<u8>,
+}
+
+// POST /v1/verify 的请求体，对应 AuthenticationAnswerRequest
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct VerifyBody {
+    auth_id: String,
+    #[serde(with = "hex_bytes")]
+    s: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    params_hash: Vec<u8>,
+    metadata: HashMap<String, String>,
+    device_id: String,
+    totp_code: String,
+}
+
+#[derive(Serialize)]
+struct VerifyReply {
+    session_id: String,
+    expires_at: u64,
+    scopes: Vec<String>,
+    token: String,
+}
+
+// POST /v1/session 的请求体，对应 ValidateSessionRequest
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SessionBody {
+    session_id: String,
+    device_id: String,
+}
+
+#[derive(Serialize)]
+struct SessionReply {
+    valid: bool,
+    user: String,
+    expires_at: u64,
+}
+
+// POST /v1/logout 的请求体，对应 LogoutRequest
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LogoutBody {
+    session_id: String,
+}
+
+#[derive(Serialize)]
+struct LogoutReply {}
+
+// 处理函数返回的 gRPC 错误，以 JSON 返回
+struct GatewayError(Status);
+
+impl From<Status> for GatewayError {
+    fn from(status: Status) -> Self {
+        GatewayError(status)
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorReply<'a> {
+    code: String,
+    message: &'a str,
+    error_code: &'static str, // gRPC 错误详情中的 ErrorCode，例如 USER_NOT_FOUND
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<String>, // InvalidArgument 时出错的字段和原因，与 gRPC 错误元数据中的相同
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'static str>,
+}
+
+impl IntoResponse for GatewayError {
+    fn into_response(self) -> Response {
+        let invalid = invalid_field(&self.0);
+        let body = ErrorReply { code: format!("{:?}", self.0.code()), message: self.0.message(), error_code: error_code(&self.0).as_str_name(), reason: invalid.as_ref().map(|(_, reason)| reason.name()), field: invalid.map(|(field, _)| field) };
+        let mut response = (http_status(self.0.code()), Json(body)).into_response();
+        if let Some(after) = retry::retry_after(&self.0) {
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(after.as_secs()));
+        }
+        response
+    }
+}
+
+// gRPC 状态码对应的 HTTP 状态码，与 grpc-gateway 的对应关系相同
+fn http_status(code: Code) -> StatusCode {
+    match code {
+        Code::Ok => StatusCode::OK,
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => StatusCode::BAD_REQUEST,
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted => StatusCode::CONFLICT,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Cancelled => StatusCode::from_u16(499).expect("valid status code"),
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::Unknown | Code::Internal | Code::DataLoss => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+// 转给处理函数的请求，带有 filter_peer 确定的客户端地址；经过网关的请求没有 gRPC 连接，处理函数据此按客户端地址限流和记录审计日志
+fn request<T>(message: T, client: Option<Extension<ClientAddr>>) -> Request<T> {
+    let mut request = Request::new(message);
+    if let Some(Extension(client)) = client {
+        request.extensions_mut().insert(client);
+    }
+    request
+}
+
+async fn register(State(auth): State<Arc<AuthImpl>>, client: Option<Extension<ClientAddr>>, Json(body): Json<RegisterBody>) -> Result<Json<RegisterReply>, GatewayError> {
+    let message = RegisterRequest {
+        user: body.user,
+        y1: body.y1,
+        y2: body.y2,
+        salt: body.salt,
+        kdf: body.kdf.map(KdfParams::from),
+        params_hash: body.params_hash,
+        proof_c: body.proof_c,
+        proof_s: body.proof_s,
+        metadata: body.metadata,
+        proof_hash: body.proof_hash,
+        display_name: body.display_name,
+        contact: body.contact,
+        recovery_codes: body.recovery_codes,
+        guardians: body.guardians,
+        guardian_threshold: body.guardian_threshold,
+        auth_id: body.auth_id,
+        s: body.s,
+        totp_secret: body.totp_secret,
+        totp_code: body.totp_code,
+    };
+    let response = Auth::register(&*auth, request(message, client)).await?.into_inner();
+    Ok(Json(RegisterReply { recovery_codes: response.recovery_codes }))
+}
+
+async fn challenge(State(auth): State<Arc<AuthImpl>>, client: Option<Extension<ClientAddr>>, Json(body): Json<ChallengeBody>) -> Result<Json<ChallengeReply>, GatewayError> {
+    let message = AuthenticationChallengeRequest { user: body.user, r1: body.r1, r2: body.r2, params_hash: body.params_hash, metadata: body.metadata, device_id: body.device_id };
+    let response = auth.create_authentication_challenge(request(message, client)).await?.into_inner();
+    Ok(Json(ChallengeReply {
+        auth_id: response.auth_id,
+        c: response.c,
+        salt: response.salt,
+        kdf: response.kdf.map(Kdf::from),
+        expires_at: response.expires_at,
+        channel_bound: response.channel_bound,
+        params_hash: response.params_hash,
+    }))
+}
+
+async fn verify(State(auth): State<Arc<AuthImpl>>, client: Option<Extension<ClientAddr>>, Json(body): Json<VerifyBody>) -> Result<Json<VerifyReply>, GatewayError> {
+    let message = AuthenticationAnswerRequest { auth_id: body.auth_id, s: body.s, params_hash: body.params_hash, metadata: body.metadata, device_id: body.device_id, totp_code: body.totp_code };
+    let response = auth.verify_authentication(request(message, client)).await?.into_inner();
+    Ok(Json(VerifyReply { session_id: response.session_id, expires_at: response.expires_at, scopes: response.scopes, token: response.token }))
+}
+
+async fn validate_session(State(auth): State<Arc<AuthImpl>>, client: Option<Extension<ClientAddr>>, Json(body): Json<SessionBody>) -> Result<Json<SessionReply>, GatewayError> {
+    let message = ValidateSessionRequest { session_id: body.session_id, device_id: body.device_id };
+    let response = auth.validate_session(request(message, client)).await?.into_inner();
+    Ok(Json(SessionReply { valid: response.valid, user: response.user, expires_at: response.expires_at }))
+}
+
+async fn logout(State(auth): State<Arc<AuthImpl>>, client: Option<Extension<ClientAddr>>, Json(body): Json<LogoutBody>) -> Result<Json<LogoutReply>, GatewayError> {
+    auth.logout(request(LogoutRequest { session_id: body.session_id }, client)).await?;
+    Ok(Json(LogoutReply {}))
+}
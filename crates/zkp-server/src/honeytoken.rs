@@ -0,0 +1,53 @@
+//! 诱饵账户（honeytoken）：运维在 `ServerConfig::honeytokens` 中列出的账户不属于任何真实用户，
+//! 对这些账户的任何认证尝试都说明凭据列表或用户数据库已经泄露。服务器照常处理请求，不让攻击者察觉，
+//! 同时输出高优先级的告警日志，推送给 `AuthImpl::subscribe_honeytoken_alerts` 的订阅者，并 POST 到配置的 webhook
+
+use std::net::SocketAddr; // 客户端地址
+
+use hyper::{Body, Client, Method, Request}; // webhook 的 HTTP 请求
+use serde_json::json; // webhook 请求体
+use tracing::{info, warn}; // webhook 的结果
+
+/// 一次对诱饵账户的访问
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HoneytokenAlert {
+    pub user: String,                    // 被访问的诱饵账户
+    pub rpc: &'static str,               // 访问的 RPC，例如 "CreateAuthenticationChallenge"
+    pub correlation_id: String,          // 请求的关联 ID，服务没有经过 Correlated 包装时为 "-"
+    pub remote_addr: Option<SocketAddr>, // 客户端地址，传输层没有提供时为 None
+    pub at: u64,                         // 访问时间（Unix 时间戳，秒）
+}
+
+impl HoneytokenAlert {
+    /// webhook 请求体：`{"event": "honeytoken-access", "severity": "critical", ...}`
+    pub fn to_json(&self) -> String {
+        json!({
+            "event": "honeytoken-access",
+            "severity": "critical",
+            "user": self.user,
+            "rpc": self.rpc,
+            "correlation_id": self.correlation_id,
+            "remote_addr": self.remote_addr.map(|addr| addr.to_string()),
+            "at": self.at,
+        })
+        .to_string()
+    }
+}
+
+// 把告警 POST 到 webhook，只支持 http://（需要 https 时经本地代理转发）；失败时只输出日志，不影响请求的处理
+pub(crate) async fn post_webhook(url: &str, alert: &HoneytokenAlert) {
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(url)
+        .header("content-type", "application/json")
+        .body(Body::from(alert.to_json()));
+    let result = match request {
+        Ok(request) => Client::new().request(request).await.map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    match result {
+        Ok(response) if response.status().is_success() => {}
+        Ok(response) => info!(correlation_id = %alert.correlation_id, status = %response.status(), "honeytoken webhook returned"),
+        Err(e) => warn!(correlation_id = %alert.correlation_id, error = %e, "could not deliver the honeytoken alert to the webhook"),
+    }
+}
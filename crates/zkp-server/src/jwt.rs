@@ -0,0 +1,181 @@
+//! 认证成功后签发的 JWT：`ServerConfig::jwt` 设置了签名密钥时，`VerifyAuthentication` 的响应带有一个 JWT，
+//! 下游服务用对应的密钥（HS256 的共享密钥或 EdDSA 的公钥）验证签名和过期时间，不需要调用 `ValidateSession`
+//!
+//! JWT 与会话同时过期；会话被注销或吊销后 JWT 在过期前仍然有效，需要立即失效的服务应订阅 `WatchRevocations` 并按 `sid` 拒绝
+
+use std::fmt; // 调试输出不包含密钥
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD; // JWT 各段的编码
+use base64::Engine; // base64 编码和解码
+use ed25519_dalek::{Signer, SigningKey, VerifyingKey}; // EdDSA (Ed25519) 签名
+use hmac::{Hmac, Mac}; // HS256
+use serde_json::{json, Value}; // 头部和声明
+use sha2::Sha256; // HS256 的哈希函数
+
+/// JWT 的签名密钥
+#[derive(Clone)]
+pub enum JwtKey {
+    Hs256(Vec<u8>),      // HMAC-SHA256 的共享密钥，下游服务使用同一个密钥验证
+    EdDsa(SigningKey),   // Ed25519 私钥，下游服务只需要公钥
+}
+
+impl JwtKey {
+    // 头部中的 alg
+    fn algorithm(&self) -> &'static str {
+        match self {
+            JwtKey::Hs256(_) => "HS256",
+            JwtKey::EdDsa(_) => "EdDSA",
+        }
+    }
+
+    fn sign(&self, message: &[u8]) -> Vec<u8> {
+        match self {
+            JwtKey::Hs256(secret) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+                mac.update(message);
+                mac.finalize().into_bytes().to_vec()
+            }
+            JwtKey::EdDsa(key) => key.sign(message).to_bytes().to_vec(),
+        }
+    }
+
+    /// 下游服务验证签名使用的密钥：HS256 为同一个共享密钥，EdDSA 为公钥
+    pub fn verifying_key(&self) -> JwtVerifyingKey {
+        match self {
+            JwtKey::Hs256(secret) => JwtVerifyingKey::Hs256(secret.clone()),
+            JwtKey::EdDsa(key) => JwtVerifyingKey::EdDsa(key.verifying_key()),
+        }
+    }
+}
+
+// 调试输出只包含算法，不包含密钥
+impl fmt::Debug for JwtKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "JwtKey({})", self.algorithm())
+    }
+}
+
+/// 验证 JWT 签名的密钥
+#[derive(Clone)]
+pub enum JwtVerifyingKey {
+    Hs256(Vec<u8>),        // HMAC-SHA256 的共享密钥
+    EdDsa(VerifyingKey),   // Ed25519 公钥
+}
+
+impl fmt::Debug for JwtVerifyingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JwtVerifyingKey::Hs256(_) => f.write_str("JwtVerifyingKey(HS256)"),
+            JwtVerifyingKey::EdDsa(key) => write!(f, "JwtVerifyingKey(EdDSA {})", hex::encode(key.as_bytes())),
+        }
+    }
+}
+
+/// 签发 JWT 的配置
+#[derive(Debug, Clone)]
+pub struct JwtIssuer {
+    pub key: JwtKey,            // 签名密钥
+    pub issuer: String,         // iss 声明，下游服务据此区分签发方
+    pub key_id: Option<String>, // 头部中的 kid，轮换密钥时下游服务据此选择公钥
+}
+
+impl JwtIssuer {
+    /// 参数:
+    /// - `key`: 签名密钥
+    /// - `issuer`: iss 声明
+    pub fn new(key: JwtKey, issuer: impl Into<String>) -> Self {
+        JwtIssuer { key, issuer: issuer.into(), key_id: None }
+    }
+
+    /// 签发一个 JWT
+    ///
+    /// 参数:
+    /// - `claims`: 声明，服务器签发时 `claims.issuer` 取自 `JwtIssuer::issuer`
+    ///
+    /// 返回:
+    /// - `String`: 紧凑格式的 JWT（header.payload.signature）
+    pub fn issue(&self, claims: &JwtClaims) -> String {
+        let mut header = json!({ "alg": self.key.algorithm(), "typ": "JWT" });
+        if let Some(key_id) = &self.key_id {
+            header["kid"] = json!(key_id);
+        }
+        let payload = json!({
+            "iss": claims.issuer,
+            "sub": claims.subject,
+            "sid": claims.session_id,
+            "iat": claims.issued_at,
+            "exp": claims.expires_at,
+            "auth_method": claims.auth_method,
+            "scope": claims.scopes.join(" "),
+        });
+        let signing_input = format!("{}.{}", URL_SAFE_NO_PAD.encode(header.to_string()), URL_SAFE_NO_PAD.encode(payload.to_string()));
+        let signature = URL_SAFE_NO_PAD.encode(self.key.sign(signing_input.as_bytes()));
+        format!("{}.{}", signing_input, signature)
+    }
+}
+
+/// JWT 中的声明
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JwtClaims {
+    pub issuer: String,      // iss：签发方
+    pub subject: String,     // sub：用户名
+    pub session_id: String,  // sid：对应的会话 ID，可以用 ValidateSession 或吊销通知检查
+    pub issued_at: u64,      // iat：签发时间（Unix 时间戳，秒）
+    pub expires_at: u64,     // exp：过期时间，与会话相同
+    pub auth_method: String, // 认证方式，与会话内省中的 auth_method 相同
+    pub scopes: Vec<String>, // scope：空格分隔的权限范围
+}
+
+/// 验证 JWT 的签名和过期时间，供下游的 Rust 服务使用；其他语言的服务可以使用任何支持 HS256 或 EdDSA 的 JWT 库
+///
+/// 参数:
+/// - `token`: 紧凑格式的 JWT
+/// - `key`: 验证签名的密钥，头部的 alg 必须与密钥的算法一致
+/// - `now`: 当前时间（Unix 时间戳，秒）
+///
+/// 返回:
+/// - `Result<JwtClaims, String>`: 声明，或者拒绝的原因
+pub fn verify_jwt(token: &str, key: &JwtVerifyingKey, now: u64) -> Result<JwtClaims, String> {
+    let mut parts = token.split('.');
+    let (Some(header), Some(payload), Some(signature), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return Err("malformed token".to_string());
+    };
+    let signing_input = &token[..header.len() + 1 + payload.len()]; // 签名覆盖的 header.payload
+    let decode = |part: &str| URL_SAFE_NO_PAD.decode(part).map_err(|_| "malformed token".to_string());
+    let header: Value = serde_json::from_slice(&decode(header)?).map_err(|_| "malformed header".to_string())?;
+    let signature = decode(signature)?;
+
+    // 先按密钥的算法验证签名，头部声称的算法不能替换验证方式
+    let valid = match (key, header["alg"].as_str()) {
+        (JwtVerifyingKey::Hs256(secret), Some("HS256")) => {
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+            mac.update(signing_input.as_bytes());
+            mac.verify_slice(&signature).is_ok()
+        }
+        (JwtVerifyingKey::EdDsa(public), Some("EdDSA")) => match ed25519_dalek::Signature::from_slice(&signature) {
+            Ok(signature) => public.verify_strict(signing_input.as_bytes(), &signature).is_ok(),
+            Err(_) => false,
+        },
+        (_, alg) => return Err(format!("unexpected algorithm {:?}", alg.unwrap_or_default())),
+    };
+    if !valid {
+        return Err("invalid signature".to_string());
+    }
+
+    let claims: Value = serde_json::from_slice(&decode(payload)?).map_err(|_| "malformed claims".to_string())?;
+    let text = |name: &str| claims[name].as_str().map(str::to_string).ok_or_else(|| format!("missing claim {}", name));
+    let number = |name: &str| claims[name].as_u64().ok_or_else(|| format!("missing claim {}", name));
+    let claims = JwtClaims {
+        issuer: text("iss")?,
+        subject: text("sub")?,
+        session_id: text("sid")?,
+        issued_at: number("iat")?,
+        expires_at: number("exp")?,
+        auth_method: text("auth_method")?,
+        scopes: text("scope")?.split_whitespace().map(str::to_string).collect(),
+    };
+    if claims.expires_at <= now {
+        return Err("token expired".to_string());
+    }
+    Ok(claims)
+}
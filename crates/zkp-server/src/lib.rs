@@ -0,0 +1,1911 @@
+//! Chaum-Pedersen 零知识证明认证服务
+//!
+//! `AuthImpl` 实现生成的 `Auth` gRPC 服务，可以用 `AuthImpl::new` 注入配置和存储后加入自己的 tonic 路由，
+//! 或者用 `run_server` 单独运行
+//!
+//! 当前接口同时以不带版本的 `zkp_auth.Auth` 和 `zkp_auth.v1.Auth`（`V1`）提供，第二版接口的草案由 `AuthV2Impl` 实现，
+//! 两者共享同一个 `AuthImpl`
+//!
+//! 多实例部署：挑战和会话只通过 `SessionStore` 读写，`AuthImpl` 不在进程内缓存，几个实例使用同一个 `PostgresStore`、
+//! 同一个 SQLite 文件或同一个 `RedisStore` 时，注册、挑战和应答可以分别由不同的实例处理，一个实例上的注销在其他实例上立即生效。
+//! 仍然只在各个实例内存中的是限流配额、连续失败的锁定计数、已使用的 TOTP 验证码、待完成的跨设备登录和进行中的多方恢复：
+//! 前三者在 N 个实例上最多放宽为 N 倍，后两者需要负载均衡器把同一个 pending_id / recovery_id 的请求交给同一个实例
+
+pub mod admin;
+pub mod audit;
+pub mod challenge;
+pub mod channel_binding;
+pub mod correlation;
+mod deadline;
+pub mod drain;
+pub mod encryption;
+pub mod export;
+pub mod gateway;
+pub mod honeytoken;
+pub mod jwt;
+pub mod limits;
+pub mod lockout;
+pub mod metrics;
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+mod migrate;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod peer;
+pub mod ratelimit;
+pub mod rbac;
+pub mod session_limit;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "redis")]
+pub mod redis;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod settings;
+mod shard;
+pub mod store;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod totp;
+pub mod username;
+pub mod v1;
+pub mod v2;
+
+use std::collections::HashMap; // 会话的附加信息
+use std::fmt; // 脱敏的调试输出
+use std::future::Future; // run_server 返回的服务器 future
+use std::net::SocketAddr; // 服务器监听地址
+use std::sync::atomic::{AtomicU64, Ordering}; // 可以重新加载的有效期
+use std::sync::Arc; // run_server 中各版本的服务共享 AuthImpl
+#[cfg(feature = "tls")]
+use std::sync::OnceLock; // run_server 开始监听时设置的 TLS 证书
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH}; // 计算挑战和会话的过期时间、后台清理的间隔、验证耗时
+use num_bigint::BigUint; // 引入大整数类型 BigUint，处理超大数字
+use tokio::sync::{broadcast, mpsc, Semaphore}; // 吊销通知的广播通道、流式响应的发送通道、CPU 密集计算的并发限制
+use tokio::task::JoinHandle; // 后台清理任务
+use tokio_stream::wrappers::ReceiverStream; // 流式响应类型
+use tonic_health::server::HealthReporter; // 按排空状态更新的健康检查
+use tower::limit::GlobalConcurrencyLimitLayer; // 所有连接上同时处理的 RPC
+use tower::util::option_layer; // 没有设置上限时不加这一层
+use tracing::{debug, error, info, warn}; // 结构化日志，请求只以脱敏形式记录
+use tonic::service::interceptor::InterceptedService; // 管理服务的认证拦截器
+use tonic::{transport::Server, Code, Request, Response, Status, Streaming}; // 引入 Tonic 的 gRPC 相关模块，处理 gRPC 请求和响应
+
+use zkp_core::{registration_context, GroupElement, GroupParams, HashAlgorithm, NonInteractiveProof, RandomSource, Scalar, ZkpError}; // 引入 ZKP 模块，用于实现 Chaum-Pedersen 零知识证明协议，以及注册时的持有证明
+
+use zkp_proto::detail::{error, with_error_code}; // 错误的结构化详情
+use zkp_proto::invalid::{invalid_argument, InvalidReason}; // 校验失败时的出错字段和原因
+use zkp_proto::redact::Redacted; // 打印请求时隐藏协议值
+use zkp_proto::zkp_auth; // 由 .proto 文件生成的 gRPC 代码
+
+/// gRPC 服务包装，`AuthServer::new(auth_impl)` 可以加入任意 tonic 路由
+pub use zkp_auth::auth_server::AuthServer;
+/// 管理服务的 gRPC 服务包装，`AuthAdminServer::with_interceptor(AuthAdminImpl::new(auth_impl), AdminAuth::new(policy))`
+pub use zkp_auth::auth_admin_server::AuthAdminServer;
+/// 第二版接口的 gRPC 服务包装，`AuthV2Server::new(AuthV2Impl::new(auth_impl))`
+pub use zkp_auth::v2::auth_server::AuthServer as AuthV2Server;
+
+pub use admin::AuthAdminImpl; // 管理接口的实现
+pub use audit::{AuditEvent, AuditKind, AuditLog, AuditQuery, FileAuditLog, MemoryAuditLog}; // 安全相关事件的审计日志
+pub use challenge::{ChallengeSource, ExternalChallenge, FiatShamirChallenge, RandomChallenge}; // 挑战值的来源
+pub use channel_binding::ChannelBinding; // 挑战绑定的 TLS 导出值
+pub use correlation::{Correlated, CorrelationId}; // 每个 RPC 的关联 ID
+pub use drain::HEALTH_SERVICES; // 健康检查中报告状态的服务
+pub use encryption::{EncryptedStore, Keyring}; // 用户记录和会话 ID 的静态加密
+pub use gateway::{gateway_router, serve_gateway}; // REST/JSON 网关
+pub use honeytoken::HoneytokenAlert; // 诱饵账户被访问时的告警
+pub use jwt::{verify_jwt, JwtClaims, JwtIssuer, JwtKey, JwtVerifyingKey}; // 认证成功后签发的 JWT
+pub use limits::ConnectionLimits; // 连接数、并发 RPC、超时和消息大小的限制
+pub use lockout::LockoutPolicy; // 连续验证失败后的账户锁定
+pub use metrics::{serve_metrics, Metrics}; // Prometheus 指标
+pub use peer::{ClientAddr, IpNet, PeerFilterLayer, PeerPolicy}; // 允许和拒绝的客户端网段，负载均衡器之后的客户端地址
+pub use ratelimit::{Quota, RateLimits}; // 各 RPC 的限流配额
+pub use rbac::{AdminAuth, AdminPolicy, AdminRole}; // 管理接口的角色和认证
+pub use session_limit::{SessionLimit, SessionLimitPolicy}; // 每个用户同时有效的会话数上限
+pub use store::{MemoryStore, SessionStore, StoreError, UserStore}; // 用户、挑战和会话的存储
+#[cfg(feature = "postgres")]
+pub use postgres::{PostgresOptions, PostgresStore}; // 多个实例共享的 PostgreSQL 存储
+#[cfg(feature = "redis")]
+pub use crate::redis::RedisStore; // 多个副本共享的 Redis 挑战和会话存储
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore; // 保存在 SQLite 中的存储
+#[cfg(feature = "tls")]
+pub use tls::TlsConfig; // 服务器证书和 mTLS
+pub use v1::V1; // 以 zkp_auth.v1.Auth 提供当前接口
+pub use v2::AuthV2Impl; // 第二版接口的实现
+pub use username::{UserNameCharset, UserNameNormalization, UserNamePolicy}; // 用户名策略
+use challenge::ChallengeInput; // 挑战来源的输入
+use correlation::correlation_id; // 处理函数中取关联 ID
+use deadline::Deadline; // 客户端给出的截止时间
+use drain::Drain; // 排空模式
+use lockout::Lockout; // 按用户名统计连续验证失败
+use peer::client_ip; // 限流使用的客户端地址
+use ratelimit::{Limited, RateLimiter}; // 按用户名和客户端地址限流
+use rbac::ClientIdentity; // 限制注册的客户端身份
+use session_limit::REVOKED_BY_SESSION_LIMIT; // 超过会话数上限被吊销的原因
+use shard::ShardedMap; // 待完成的登录和恢复
+use totp::{TotpGuard, TOTP_MAX_SECRET_LEN, TOTP_MIN_SECRET_LEN}; // TOTP 第二因素
+use store::{PendingChallenge, Purged, SessionInfo, UserInfo}; // 存储中的记录
+
+// 使用生成的 gRPC 服务和消息结构体
+use zkp_auth::{
+    auth_server::Auth, // 引入 Auth 服务接口，用于 gRPC 服务器的创建
+    ApprovePendingLoginRequest, ApprovePendingLoginResponse, // 批准跨设备登录的请求和响应消息类型
+    ErrorCode, // 错误详情中的分类
+    AuthenticationAnswerRequest, AuthenticationAnswerResponse, // 验证认证时的请求和响应消息类型
+    AuthenticationChallengeRequest, AuthenticationChallengeResponse, // 创建认证挑战的请求和响应消息类型
+    authenticate_request, authenticate_response, AuthenticateRequest, AuthenticateResponse, // 双向流认证的请求和响应消息类型
+    ChangePasswordRequest, ChangePasswordResponse, // 修改密码的请求和响应消息类型
+    CreatePendingLoginRequest, CreatePendingLoginResponse, // 创建跨设备登录的请求和响应消息类型
+    DeleteUserDataRequest, DeleteUserDataResponse, // 删除用户数据的请求和响应消息类型
+    GetAuthParametersRequest, GetAuthParametersResponse, // 取得群参数的请求和响应消息类型
+    ExportUserDataRequest, ExportUserDataResponse, ExportedSession, UserDataExport, // 导出用户数据的请求和响应消息类型
+    UpdateProfileRequest, UpdateProfileResponse, // 修改账户资料的请求和响应消息类型
+    IntrospectSessionRequest, IntrospectSessionResponse, // 会话内省的请求和响应消息类型
+    LogoutRequest, LogoutResponse, // 注销会话的请求和响应消息类型
+    PollPendingLoginRequest, PollPendingLoginResponse, // 轮询跨设备登录的请求和响应消息类型
+    RecoverAccountRequest, RecoverAccountResponse, // 用恢复码登录的请求和响应消息类型
+    RegisterRequest, RegisterResponse, // 注册功能的请求和响应消息类型
+    ResetCredentialsRequest, ResetCredentialsResponse, // 用恢复会话重置密码的请求和响应消息类型
+    StartGuardianRecoveryRequest, StartGuardianRecoveryResponse, // 发起多方恢复的请求和响应消息类型
+    ApproveGuardianRecoveryRequest, ApproveGuardianRecoveryResponse, CompleteGuardianRecoveryRequest, // 批准和完成多方恢复的请求和响应消息类型
+    RevokedSession, WatchRevocationsRequest, // 订阅会话吊销的请求和推送的消息类型
+    ValidateSessionRequest, ValidateSessionResponse, // 查询会话的请求和响应消息类型
+};
+
+// 默认的服务器监听地址
+const DEFAULT_ADDR: &str = "127.0.0.1:50051";
+
+// 挑战的默认有效期（秒），超过后必须重新请求挑战
+const CHALLENGE_TTL_SECS: u64 = 60;
+
+// 会话的默认有效期（秒），超过后会话失效，需要重新登录
+const SESSION_TTL_SECS: u64 = 60 * 60;
+
+// 跨设备登录的有效期（秒），超过后二维码失效
+const PENDING_LOGIN_TTL_SECS: u64 = 5 * 60;
+
+// 后台清理过期挑战、会话和待完成登录的默认间隔（秒）
+const CLEANUP_INTERVAL_SECS: u64 = 60;
+
+// 会话内省中返回的认证方式：直接登录，以及通过二维码跨设备登录
+const AUTH_METHOD_DIRECT: &str = "chaum-pedersen";
+const AUTH_METHOD_QR: &str = "chaum-pedersen-qr";
+const AUTH_METHOD_RECOVERY: &str = "recovery-code";
+const AUTH_METHOD_GUARDIANS: &str = "guardian-recovery";
+
+// 恢复会话唯一的权限范围，只能用于重置密码
+const RECOVERY_SCOPE: &str = "credential-reset";
+
+// 注册时最多签发的恢复码数量，以及每个恢复码的长度
+const MAX_RECOVERY_CODES: u32 = 16;
+const RECOVERY_CODE_LEN: usize = 16;
+
+// 多方恢复的监护人数上限，以及发起的恢复的有效期（秒），监护人需要时间各自批准
+const MAX_GUARDIANS: usize = 8;
+const GUARDIAN_RECOVERY_TTL_SECS: u64 = 24 * 60 * 60;
+
+// 同一用户同时进行的多方恢复数上限：发起恢复不需要认证，新的恢复不取消已有的恢复，达到上限后拒绝发起
+const MAX_GUARDIAN_RECOVERIES_PER_USER: usize = 4;
+
+// 新注册用户的默认权限范围，可以通过 ServerConfig 或修改用户记录的 scopes 为用户授予不同的权限
+const DEFAULT_SCOPES: &[&str] = &["user"];
+
+// 请求中自定义元数据的大小限制：条目数、键和值的最大字节数
+const MAX_METADATA_ENTRIES: usize = 16;
+const MAX_METADATA_KEY_LEN: usize = 64;
+const MAX_METADATA_VALUE_LEN: usize = 256;
+
+// 账户资料的大小限制：显示名称和联系方式的最大字节数
+const MAX_DISPLAY_NAME_LEN: usize = 64;
+const MAX_CONTACT_LEN: usize = 256;
+
+// 设备标识的最大字节数
+const MAX_DEVICE_ID_LEN: usize = 128;
+
+// 诱饵账户告警广播通道的容量
+const ALERT_BUFFER: usize = 256;
+
+// 吊销通知广播通道的容量，订阅者落后超过该数量的通知时断开
+const REVOCATION_BUFFER: usize = 1024;
+
+// 当前时间（Unix 时间戳，秒）
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+// 会话在导出数据和管理接口中的表示，不包含会话 ID
+fn exported_session(session: SessionInfo) -> ExportedSession {
+    ExportedSession {
+        issued_at: session.issued_at,
+        expires_at: session.expires_at,
+        auth_method: session.auth_method,
+        scopes: session.scopes,
+        metadata: session.metadata,
+        device_id: session.device_id,
+    }
+}
+
+/// 服务器配置
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub addr: SocketAddr,            // 监听地址，只有 run_server 使用
+    pub params: &'static GroupParams, // 默认的群参数，默认为内置的 RFC 5114 1024 位群；不指定参数集的注册和没有记录群的旧用户记录使用这组参数
+    pub extra_params: Vec<&'static GroupParams>, // 注册时还可以选择的群参数，用户记录保存注册时的群；例如迁移期间旧用户留在 1024 位群，新用户注册到 2048 位群
+    pub challenge_ttl_secs: u64,     // 挑战的有效期（秒），可以用 AuthImpl::reload 替换
+    pub session_ttl_secs: u64,       // 会话的有效期（秒），可以用 AuthImpl::reload 替换
+    pub default_scopes: Vec<String>, // 新注册用户的权限范围
+    pub proof_hashes: Vec<HashAlgorithm>, // 注册时接受的持有证明哈希函数，部署有哈希策略时可以只保留允许的哈希
+    pub require_channel_binding: bool, // 为 true 时拒绝 TLS 层没有提供 ChannelBinding 的挑战请求
+    pub honeytokens: Vec<String>,    // 诱饵账户的用户名，对它们的任何认证尝试都会触发告警
+    pub alert_webhook: Option<String>, // 诱饵账户告警 POST 到的 http:// 地址
+    pub challenge_source: Arc<dyn ChallengeSource>, // 挑战值的来源，默认均匀随机
+    pub rng: RandomSource,           // 挑战值、auth_id、会话 ID 和恢复码等随机值的来源，默认为系统随机数生成器；seeded-rng feature 下可以固定种子
+    pub cleanup_interval_secs: u64,  // 后台清理过期挑战、会话和待完成登录的间隔（秒），为 0 时不清理
+    pub jwt: Option<JwtIssuer>,      // 设置时认证成功的响应带有与会话同时过期的 JWT
+    pub rate_limits: RateLimits,     // Register、挑战和验证请求按用户名和客户端地址的配额，默认不限流；可以用 AuthImpl::reload 替换
+    pub lockout: Option<LockoutPolicy>, // 连续验证失败后暂时锁定账户，默认连续失败 5 次后锁定 30 秒，为 None 时不锁定
+    pub session_limit: Option<SessionLimit>, // 每个用户同时有效的会话数上限，达到上限时吊销最早的会话或拒绝登录，默认不限制
+    pub registration_identities: Vec<String>, // 非空时只有这些客户端身份（mTLS 证书主体）可以注册
+    pub metrics_addr: Option<SocketAddr>, // 设置时 run_server 同时在该地址以 HTTP 提供 /metrics
+    pub gateway_addr: Option<SocketAddr>, // 设置时 run_server 同时在该地址提供 REST/JSON 网关（POST /v1/register 等）
+    pub admin: Option<AdminPolicy>,  // 设置时 run_server 同时提供 AuthAdmin 管理服务，调用者按其中的令牌和客户端身份认证
+    pub audit_log: Option<Arc<dyn AuditLog>>, // 设置时注册、挑战、验证和会话吊销记入审计日志，管理接口的 QueryAuditLog 据此查询
+    pub allow_reregistration: bool,  // 为 true 时已存在的用户可以用旧密码对挑战的解答重新注册，否则注册已存在的用户名返回 AlreadyExists
+    pub cpu_workers: usize,          // 同时进行的验证和子群检查数，默认为 CPU 核数；它们在阻塞线程池中执行，不阻塞其他 RPC
+    pub user_names: UserNamePolicy,  // 用户名的规范化、长度、字符和保留名，默认 NFC 规范化、不折叠大小写
+    pub connection_limits: ConnectionLimits, // run_server 的连接数、并发 RPC、超时和消息大小的限制，默认只限制消息大小
+    pub peers: PeerPolicy,           // run_server 接受的客户端网段和可信代理，默认接受所有地址、忽略 x-forwarded-for
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsConfig>,      // 设置时 run_server 使用 TLS 监听，配置了客户端 CA 时要求客户端证书；证书可以用 AuthImpl::reload 替换
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            addr: DEFAULT_ADDR.parse().expect("valid default address"),
+            params: GroupParams::rfc5114_1024(),
+            extra_params: Vec::new(),
+            challenge_ttl_secs: CHALLENGE_TTL_SECS,
+            session_ttl_secs: SESSION_TTL_SECS,
+            default_scopes: DEFAULT_SCOPES.iter().map(|scope| scope.to_string()).collect(),
+            proof_hashes: HashAlgorithm::ALL.to_vec(),
+            require_channel_binding: false,
+            honeytokens: Vec::new(),
+            alert_webhook: None,
+            challenge_source: Arc::new(RandomChallenge),
+            rng: RandomSource::default(),
+            cleanup_interval_secs: CLEANUP_INTERVAL_SECS,
+            jwt: None,
+            rate_limits: RateLimits::default(),
+            lockout: Some(LockoutPolicy::default()),
+            session_limit: None,
+            registration_identities: Vec::new(),
+            metrics_addr: None,
+            gateway_addr: None,
+            admin: None,
+            audit_log: None,
+            allow_reregistration: false,
+            cpu_workers: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            user_names: UserNamePolicy::default(),
+            connection_limits: ConnectionLimits::default(),
+            peers: PeerPolicy::default(),
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+}
+
+/// Auth gRPC 服务的实现
+///
+/// 克隆只复制共享状态的引用，克隆得到的实例与原实例使用同一份存储、限流和锁定状态；双向流认证的后台任务持有一个克隆
+#[derive(Debug, Clone)] // 派生 Debug 宏，生成结构体的调试输出
+pub struct AuthImpl {
+    config: Arc<ServerConfig>, // 有效期、默认权限范围等配置
+    ttls: Arc<Ttls>,           // 挑战和会话的有效期，重新加载时替换，不使用 config 中的值
+    #[cfg(feature = "tls")]
+    tls: Arc<OnceLock<Arc<tls::ReloadableTls>>>, // run_server 开始监听后当前使用的证书
+    params: &'static GroupParams, // 默认的群参数，启动时解码一次，所有请求共享
+    groups: Arc<[(Vec<u8>, &'static GroupParams)]>, // 接受的全部群参数及其参数集标识，第一个是默认的群
+    users: Arc<dyn UserStore>,       // 用户记录
+    sessions: Arc<dyn SessionStore>, // 挑战和会话
+    in_flight: Arc<InFlight>,  // 待完成的跨设备登录和进行中的多方恢复
+    revocations: Revocations, // 会话吊销通知，推送给订阅的资源服务器
+    alerts: Alerts,           // 诱饵账户告警，推送给嵌入服务器的程序
+    limiter: Arc<RateLimiter>, // 按配置的配额限流
+    lockout: Arc<Lockout>,     // 连续验证失败的用户暂时锁定
+    totp: Arc<TotpGuard>,      // 已使用的 TOTP 验证码
+    metrics: Arc<Metrics>,     // Prometheus 指标
+    cpu: Arc<Semaphore>,       // 限制同时在阻塞线程池中进行的模幂计算数
+    drain: Arc<Drain>,         // 排空时拒绝新的注册和挑战，健康检查报告 NOT_SERVING
+}
+
+impl Default for AuthImpl {
+    fn default() -> Self {
+        AuthImpl::new(ServerConfig::default(), MemoryStore::default())
+    }
+}
+
+// 挑战和会话的有效期（秒）
+#[derive(Debug)]
+struct Ttls {
+    challenge: AtomicU64,
+    session: AtomicU64,
+}
+
+// 会话吊销通知的广播通道
+#[derive(Debug, Clone)]
+struct Revocations(broadcast::Sender<RevokedSession>);
+
+impl Default for Revocations {
+    fn default() -> Self {
+        Revocations(broadcast::channel(REVOCATION_BUFFER).0)
+    }
+}
+
+// 诱饵账户告警的广播通道
+#[derive(Debug, Clone)]
+struct Alerts(broadcast::Sender<HoneytokenAlert>);
+
+impl Default for Alerts {
+    fn default() -> Self {
+        Alerts(broadcast::channel(ALERT_BUFFER).0)
+    }
+}
+
+// 待完成的跨设备登录和进行中的多方恢复：有效期短，只保存在内存中，不经过存储后端
+#[derive(Default)]
+struct InFlight {
+    pending_logins: ShardedMap<String, PendingLogin>, // 保存待完成的跨设备登录，键为 pending_id
+    guardian_recoveries: ShardedMap<String, GuardianRecovery>, // 保存进行中的多方恢复，键为 recovery_id
+}
+
+// 映射表的键是 pending_id、recovery_id 等凭据，调试输出只包含条目数
+impl fmt::Debug for InFlight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InFlight")
+            .field("pending_logins", &self.pending_logins.len())
+            .field("guardian_recoveries", &self.guardian_recoveries.len())
+            .finish()
+    }
+}
+
+// 待完成的跨设备登录
+#[derive(Debug)]
+struct PendingLogin {
+    user: String,                   // 要登录的用户名
+    nonce: String,                  // 二维码中的随机数，批准和轮询时必须一致
+    session: Option<(String, u64, Vec<String>)>, // 批准后建立的会话 ID、过期时间和权限范围
+    expires_at: u64,                // 过期时间（Unix 时间戳，秒），过期后不能再批准，由后台清理删除
+}
+
+// 进行中的多方恢复，批准数达到门限后可以换取恢复会话
+#[derive(Debug)]
+struct GuardianRecovery {
+    user: String,           // 要恢复的用户名
+    guardians: Vec<(String, Vec<u8>)>, // 发起时用户记录中的监护人及其被指定时账户的指纹
+    threshold: u32,         // 需要的批准数
+    approvals: Vec<String>, // 已经批准的监护人
+    expires_at: u64,        // 恢复的过期时间（Unix 时间戳，秒）
+}
+
+impl AuthImpl {
+    /// 参数:
+    /// - `config`: 服务器配置
+    /// - `store`: 同时保存用户、挑战和会话的存储，例如 `MemoryStore::default()`
+    pub fn new<S: UserStore + SessionStore + 'static>(config: ServerConfig, store: S) -> Self {
+        let store = Arc::new(store);
+        AuthImpl::with_stores(config, store.clone(), store)
+    }
+
+    /// 用户记录与挑战和会话使用不同的存储，例如用户记录在数据库中、会话在 Redis 中
+    ///
+    /// 参数:
+    /// - `config`: 服务器配置
+    /// - `users`: 用户记录的存储
+    /// - `sessions`: 挑战和会话的存储
+    pub fn with_stores(config: ServerConfig, users: Arc<dyn UserStore>, sessions: Arc<dyn SessionStore>) -> Self {
+        // otel feature 下每次存储调用是处理函数的 span 下的一个 span
+        #[cfg(feature = "otel")]
+        let (users, sessions): (Arc<dyn UserStore>, Arc<dyn SessionStore>) = (Arc::new(otel::TracedStore(users)), Arc::new(otel::TracedStore(sessions)));
+        AuthImpl {
+            limiter: Arc::new(RateLimiter::new(config.rate_limits.clone())),
+            lockout: Arc::new(Lockout::new(config.lockout)),
+            totp: Arc::default(),
+            cpu: Arc::new(Semaphore::new(config.cpu_workers.max(1))),
+            params: config.params,
+            groups: std::iter::once(config.params).chain(config.extra_params.iter().copied()).map(|zkp| (zkp.params_hash(), zkp)).collect(),
+            ttls: Arc::new(Ttls { challenge: AtomicU64::new(config.challenge_ttl_secs), session: AtomicU64::new(config.session_ttl_secs) }),
+            #[cfg(feature = "tls")]
+            tls: Arc::default(),
+            config: Arc::new(config),
+            users,
+            sessions,
+            in_flight: Arc::default(),
+            revocations: Revocations::default(),
+            alerts: Alerts::default(),
+            metrics: Arc::default(),
+            drain: Arc::default(),
+        }
+    }
+
+    /// 在运行中替换可以不重启就生效的设置：挑战和会话的有效期、限流配额，以及 run_server 使用的 TLS 证书（tls feature）；
+    /// 已经建立的连接、进行中的 RPC 和已经签发的挑战与会话不受影响。`config` 中的其他设置被忽略，需要重启才能生效
+    ///
+    /// 参数:
+    /// - `config`: 新的配置，通常由重新读取的 `Settings` 构建
+    ///
+    /// 返回:
+    /// - `Result<(), String>`: 新的证书无效，或者要关闭 run_server 正在使用的 TLS 时返回错误，此时不替换任何设置
+    pub fn reload(&self, config: &ServerConfig) -> Result<(), String> {
+        #[cfg(feature = "tls")]
+        match (self.tls.get(), &config.tls) {
+            (Some(current), Some(tls)) => current.replace(tls).map_err(|e| format!("invalid TLS certificate: {}", e))?,
+            (Some(_), None) => return Err("TLS cannot be turned off without a restart".to_string()),
+            (None, Some(_)) => warn!("not serving TLS, the TLS settings take effect after a restart"),
+            (None, None) => {}
+        }
+        self.ttls.challenge.store(config.challenge_ttl_secs, Ordering::Relaxed);
+        self.ttls.session.store(config.session_ttl_secs, Ordering::Relaxed);
+        self.limiter.set_limits(config.rate_limits.clone());
+        info!(challenge_ttl_secs = config.challenge_ttl_secs, session_ttl_secs = config.session_ttl_secs, rate_limits = ?config.rate_limits, "reloaded the configuration");
+        Ok(())
+    }
+
+    /// 进入或退出排空模式：排空时新的注册和挑战返回带有重试提示的 Unavailable，健康检查报告 NOT_SERVING；
+    /// 已经签发的挑战仍然可以应答，会话不受影响。滚动发布时先排空实例，负载均衡器摘除后再停止
+    ///
+    /// 参数:
+    /// - `draining`: true 进入排空模式，false 恢复正常服务
+    ///
+    /// 返回:
+    /// - `bool`: 此前是否在排空
+    pub fn set_draining(&self, draining: bool) -> bool {
+        let was_draining = self.drain.set(draining);
+        if was_draining != draining {
+            info!(draining, "changed the drain mode");
+        }
+        was_draining
+    }
+
+    /// 是否在排空模式
+    pub fn is_draining(&self) -> bool {
+        self.drain.is_draining()
+    }
+
+    /// 按排空状态更新 `grpc.health.v1.Health` 中 `HEALTH_SERVICES` 的状态；自己构建 tonic 服务器的程序在后台运行它，
+    /// 例如 `tokio::spawn(auth.report_health(reporter))`，`run_server` 已经包含健康检查
+    ///
+    /// 参数:
+    /// - `reporter`: `tonic_health::server::health_reporter` 返回的 `HealthReporter`
+    ///
+    /// 返回:
+    /// - `impl Future`: 持续更新状态，`AuthImpl` 的所有克隆都被释放后结束
+    pub fn report_health(&self, reporter: HealthReporter) -> impl Future<Output = ()> + Send + 'static {
+        drain::report_health(self.drain.subscribe(), reporter)
+    }
+
+    /// 本服务的计数器和直方图，自己提供指标接口的程序用 `Metrics::render` 输出
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// 以 Prometheus 文本格式输出指标，包含从会话存储读取的有效会话数
+    pub async fn render_metrics(&self) -> String {
+        let active_sessions = match self.sessions.count_sessions(unix_now()).await {
+            Ok(count) => Some(count),
+            Err(err) => {
+                warn!(error = %err, "could not count active sessions");
+                None
+            }
+        };
+        self.metrics.render(active_sessions)
+    }
+
+    // 参数集标识对应的群参数，为空时为默认的群；不是服务器接受的群时返回 InvalidArgument
+    #[allow(clippy::result_large_err)]
+    fn group(&self, params_hash: &[u8]) -> Result<&'static GroupParams, Status> {
+        if params_hash.is_empty() {
+            return Ok(self.params);
+        }
+        match self.groups.iter().find(|(hash, _)| hash == params_hash) {
+            Some((_, zkp)) => Ok(zkp),
+            None => {
+                let accepted: Vec<String> = self.groups.iter().map(|(hash, _)| hex::encode(hash)).collect();
+                Err(invalid_argument("params_hash", InvalidReason::Unsupported, format!("parameter set mismatch: server accepts {}", accepted.join(", "))))
+            }
+        }
+    }
+
+    // 用户注册时的群参数；记录的群已不在服务器的配置中时返回 FailedPrecondition，运维恢复该群的配置之前用户无法登录
+    #[allow(clippy::result_large_err)]
+    fn user_group(&self, user_name: &str, user_info: &UserInfo) -> Result<&'static GroupParams, Status> {
+        self.group(&user_info.group).map_err(|_| {
+            Status::new(Code::FailedPrecondition, format!("User: {} is registered in parameter set {} which the server no longer accepts", user_name, hex::encode(&user_info.group)))
+        })
+    }
+
+    // 读取用户记录中的群参数，用户不存在时返回 NotFound；替换公开值的 RPC 使用，新的公开值必须在同一个群中
+    async fn stored_group(&self, user_name: &str) -> Result<&'static GroupParams, Status> {
+        match self.users.get_user(user_name).await? {
+            Some(user_info) => self.user_group(user_name, &user_info),
+            None => Err(AuthImpl::user_not_found(user_name)),
+        }
+    }
+
+    // 检查客户端计算时使用的参数集与用户的群一致，旧客户端不发送标识（为空）时不检查
+    #[allow(clippy::result_large_err)]
+    fn check_params(zkp: &GroupParams, params_hash: &[u8]) -> Result<(), Status> {
+        if params_hash.is_empty() || params_hash == zkp.params_hash() {
+            Ok(())
+        } else {
+            Err(invalid_argument("params_hash", InvalidReason::Mismatch, format!("parameter set mismatch: user is registered in {}", hex::encode(zkp.params_hash()))))
+        }
+    }
+
+    // 解析持有证明使用的哈希函数，为空时为 SHA-256（旧客户端），不支持或配置不允许的哈希函数返回 InvalidArgument
+    #[allow(clippy::result_large_err)]
+    fn check_proof_hash(&self, name: &str) -> Result<HashAlgorithm, Status> {
+        let hash = match name {
+            "" => HashAlgorithm::Sha256,
+            name => name.parse().map_err(|e: String| invalid_argument("proof_hash", InvalidReason::Unsupported, e))?,
+        };
+        if self.config.proof_hashes.contains(&hash) {
+            Ok(hash)
+        } else {
+            Err(invalid_argument("proof_hash", InvalidReason::Unsupported, format!("proof hash {} is not allowed by the server policy", hash)))
+        }
+    }
+
+    // 验证持有证明：提交者必须知道 y1、y2 对应的私钥 x，且证明绑定到用户名 user（客户端发送的、规范化前的写法）；
+    // 注册、修改密码和重置密码共用。proof 为证明的 (c, s, 哈希函数名称)，group 为客户端声明的参数集标识，为空时不检查
+    async fn check_possession(&self, zkp: &'static GroupParams, user: &str, (y1, y2): (&BigUint, &BigUint), (c, s, hash): (&[u8], &[u8], &str), group: &[u8], deadline: Deadline) -> Result<(), Status> {
+        let hash = self.check_proof_hash(hash)?; // 持有证明使用的哈希函数
+        let invalid = || with_error_code(invalid_argument("proof_c", InvalidReason::InvalidProof, format!("User: {} missing or invalid proof of possession", user)), ErrorCode::BadProof);
+        if c.is_empty() {
+            return Err(invalid());
+        }
+        let proof = NonInteractiveProof {
+            y1: y1.clone(),
+            y2: y2.clone(),
+            c: AuthImpl::scalar(zkp, "proof_c", c)?,
+            s: AuthImpl::scalar(zkp, "proof_s", s)?,
+            context: registration_context(user),
+            hash,
+            group: group.to_vec(),
+        };
+        deadline.check("verifying the proof of possession")?;
+        if self.off_thread("verification", move || zkp.verify_non_interactive(&proof)).await? {
+            Ok(())
+        } else {
+            Err(invalid())
+        }
+    }
+
+    // 修改或重置密码时检查新的公开值：与注册一样不能退化，并且附有绑定用户名的持有证明；
+    // user 为客户端发送的用户名，规范化后必须是该账户 user_name，返回解析后的 y1、y2
+    async fn check_new_keys(&self, zkp: &'static GroupParams, user_name: &str, user: &str, (y1, y2): (&[u8], &[u8]), proof: (&[u8], &[u8], &str), deadline: Deadline) -> Result<(BigUint, BigUint), Status> {
+        if self.config.user_names.normalize(user) != user_name {
+            return Err(invalid_argument("user", InvalidReason::Mismatch, format!("User: {} is not the account {}", user, user_name)));
+        }
+        let (y1, y2) = self.element_pair(zkp, ("y1", y1), ("y2", y2)).await?;
+        self.check_possession(zkp, user, (&y1, &y2), proof, &[], deadline).await?; // 新的公开值在用户注册时的群中计算
+        Ok((y1, y2))
+    }
+
+    // 解析请求中的一对群元素（y1、y2 或 r1、r2），拒绝空值、长于 p 的字节串，以及 0、1、p - 1、不小于 p 和不在 q 阶子群中的值
+    // 两个元素的阶用 check_orders 批量检查；服务器自身的 alpha、beta 已由 validate_params 检查
+    #[allow(clippy::result_large_err)]
+    async fn element_pair(&self, zkp: &'static GroupParams, (name1, bytes1): (&'static str, &[u8]), (name2, bytes2): (&'static str, &[u8])) -> Result<(BigUint, BigUint), Status> {
+        AuthImpl::check_field_len(name1, bytes1, &zkp.p)?; // 先检查长度，超长的值不做大整数转换
+        AuthImpl::check_field_len(name2, bytes2, &zkp.p)?;
+        let values = vec![BigUint::from_bytes_be(bytes1), BigUint::from_bytes_be(bytes2)];
+        let elems = self.off_thread("subgroup check", move || GroupElement::new_batch(values, zkp)).await?; // 子群检查是两次模幂
+        let elems = elems.map_err(|(i, e)| AuthImpl::invalid_value([name1, name2][i], e))?;
+        let mut elems = elems.into_iter().map(GroupElement::into_inner);
+        Ok((elems.next().unwrap(), elems.next().unwrap()))
+    }
+
+    // 解析请求中的指数（c、s），拒绝空值、长于 q 的字节串和不小于 q 的值
+    #[allow(clippy::result_large_err)]
+    fn scalar(zkp: &GroupParams, name: &'static str, bytes: &[u8]) -> Result<BigUint, Status> {
+        AuthImpl::check_field_len(name, bytes, &zkp.q)?;
+        Scalar::from_bytes_be(bytes, zkp).map(Scalar::into_inner).map_err(|e| AuthImpl::invalid_value(name, e))
+    }
+
+    // 协议值的字节串必须非空，且不长于模数（允许前导零补齐到模数的长度）
+    #[allow(clippy::result_large_err)]
+    fn check_field_len(name: &'static str, bytes: &[u8], modulus: &BigUint) -> Result<(), Status> {
+        let max_len = modulus.bits().div_ceil(8) as usize;
+        if bytes.is_empty() {
+            return Err(invalid_argument(name, InvalidReason::Empty, format!("{} must not be empty", name)));
+        }
+        if bytes.len() > max_len {
+            return Err(invalid_argument(name, InvalidReason::TooLong, format!("{} exceeds {} bytes", name, max_len)));
+        }
+        Ok(())
+    }
+
+    // 指数或群元素不满足的条件对应的 InvalidArgument
+    fn invalid_value(name: &'static str, error: ZkpError) -> Status {
+        let reason = match error {
+            ZkpError::ScalarOutOfRange | ZkpError::ElementOutOfRange => InvalidReason::OutOfRange,
+            ZkpError::DegenerateElement => InvalidReason::Degenerate,
+            ZkpError::NotInSubgroup => InvalidReason::NotInSubgroup,
+        };
+        invalid_argument(name, reason, format!("{}: {}", name, error))
+    }
+
+    // 检查请求中的自定义元数据不超过大小限制
+    #[allow(clippy::result_large_err)]
+    fn check_metadata(metadata: &HashMap<String, String>) -> Result<(), Status> {
+        if metadata.len() > MAX_METADATA_ENTRIES {
+            return Err(invalid_argument("metadata", InvalidReason::TooMany, format!("too many metadata entries: {} > {}", metadata.len(), MAX_METADATA_ENTRIES)));
+        }
+        for (key, value) in metadata {
+            if key.is_empty() || key.len() > MAX_METADATA_KEY_LEN {
+                let reason = if key.is_empty() { InvalidReason::Empty } else { InvalidReason::TooLong };
+                return Err(invalid_argument("metadata", reason, format!("metadata key must be 1 to {} bytes", MAX_METADATA_KEY_LEN)));
+            }
+            if value.len() > MAX_METADATA_VALUE_LEN {
+                return Err(invalid_argument("metadata", InvalidReason::TooLong, format!("metadata value of {} exceeds {} bytes", key, MAX_METADATA_VALUE_LEN)));
+            }
+        }
+        Ok(())
+    }
+
+    // 检查账户资料不超过大小限制，显示名称和联系方式不能包含控制字符
+    #[allow(clippy::result_large_err)]
+    fn check_profile(display_name: &str, contact: &str) -> Result<(), Status> {
+        for (name, value, max_len) in [("display_name", display_name, MAX_DISPLAY_NAME_LEN), ("contact", contact, MAX_CONTACT_LEN)] {
+            if value.len() > max_len {
+                return Err(invalid_argument(name, InvalidReason::TooLong, format!("{} exceeds {} bytes", name, max_len)));
+            }
+            if value.chars().any(char::is_control) {
+                return Err(invalid_argument(name, InvalidReason::BadCharacters, format!("{} must not contain control characters", name)));
+            }
+        }
+        Ok(())
+    }
+
+    // 检查设备标识不超过大小限制，只能包含可见的 ASCII 字符
+    #[allow(clippy::result_large_err)]
+    fn check_device_id(device_id: &str) -> Result<(), Status> {
+        if device_id.len() > MAX_DEVICE_ID_LEN {
+            return Err(invalid_argument("device_id", InvalidReason::TooLong, format!("device_id exceeds {} bytes", MAX_DEVICE_ID_LEN)));
+        }
+        if !device_id.bytes().all(|b| b.is_ascii_graphic()) {
+            return Err(invalid_argument("device_id", InvalidReason::BadCharacters, "device_id must contain only visible ASCII characters"));
+        }
+        Ok(())
+    }
+
+    // 检查注册时指定的监护人：最多 8 个规范化后不重复的已注册用户，不能包含自己，门限在 1 到监护人数之间；没有监护人时门限必须为 0
+    // 返回规范化后的监护人和他们账户的指纹：批准恢复时指纹必须一致，监护人的用户名被删除后重新注册的账户不能代替原来的监护人批准
+    async fn designate_guardians(&self, user_name: &str, guardians: &[String], threshold: u32) -> Result<(Vec<String>, Vec<Vec<u8>>), Status> {
+        if guardians.len() > MAX_GUARDIANS {
+            return Err(invalid_argument("guardians", InvalidReason::TooMany, format!("at most {} guardians can be designated", MAX_GUARDIANS)));
+        }
+        let mut normalized: Vec<String> = Vec::with_capacity(guardians.len());
+        for guardian in guardians {
+            let guardian = self.config.user_names.check("guardians", guardian)?;
+            if guardian == user_name || normalized.contains(&guardian) {
+                return Err(invalid_argument("guardians", InvalidReason::Unsupported, format!("invalid guardian: {:?}", guardian)));
+            }
+            normalized.push(guardian);
+        }
+        if threshold as usize > guardians.len() || (threshold == 0) != guardians.is_empty() {
+            return Err(invalid_argument("guardian_threshold", InvalidReason::OutOfRange, format!("guardian_threshold must be between 1 and {}", guardians.len())));
+        }
+        let mut bindings = Vec::with_capacity(normalized.len());
+        for guardian in &normalized {
+            match self.users.get_user(guardian).await? {
+                Some(guardian_info) => bindings.push(AuthImpl::guardian_binding(&guardian_info)),
+                None => return Err(invalid_argument("guardians", InvalidReason::Unsupported, format!("guardian {:?} is not a registered user", guardian))),
+            }
+        }
+        Ok((normalized, bindings))
+    }
+
+    // 监护人账户的指纹：注册时间和 y1 的 SHA-256；监护人重新注册或修改密码后指纹改变，需要被重新指定才能批准恢复
+    fn guardian_binding(guardian_info: &UserInfo) -> Vec<u8> {
+        let mut bytes = guardian_info.created_at.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&guardian_info.y1.to_bytes_be());
+        HashAlgorithm::Sha256.digest(&bytes)
+    }
+
+    // 解答或 TOTP 验证码错误时的错误，不区分是哪一个错误，不能借此单独猜测密码
+    fn bad_answer(auth_id: &str, with_totp: bool) -> Status {
+        let what = if with_totp { "bad solution to the challenge or TOTP code" } else { "bad solution to the challenge" };
+        error(Code::PermissionDenied, ErrorCode::BadProof, format!("AuthId: {} {}", auth_id, what))
+    }
+
+    // 注册已存在的用户名时的错误
+    fn already_exists(user_name: &str) -> Status {
+        error(Code::AlreadyExists, ErrorCode::UserAlreadyExists, format!("User: {} already exists", user_name))
+    }
+
+    // 用户不存在时的错误
+    fn user_not_found(user_name: &str) -> Status {
+        error(Code::NotFound, ErrorCode::UserNotFound, format!("User: {} not found in database", user_name))
+    }
+
+    // 认证 ID 不存在或已被使用时的错误
+    fn challenge_not_found(auth_id: &str) -> Status {
+        error(Code::NotFound, ErrorCode::ChallengeNotFound, format!("AuthId: {} not found in database", auth_id))
+    }
+
+    // 挑战在应答前过期时的错误
+    fn challenge_expired(auth_id: &str) -> Status {
+        error(Code::DeadlineExceeded, ErrorCode::ChallengeExpired, format!("AuthId: {} challenge expired", auth_id))
+    }
+
+    // 账户用恢复码或多方恢复登录后尚未重置密码时拒绝普通登录的错误：丢失的密码可能已经泄露，只能用恢复会话重置
+    fn reset_required(user_name: &str) -> Status {
+        error(Code::FailedPrecondition, ErrorCode::CredentialResetRequired, format!("User: {} must reset the credentials with the recovery session first", user_name))
+    }
+
+    // 恢复码保存和比较时使用的哈希
+    fn recovery_code_hash(code: &str) -> Vec<u8> {
+        HashAlgorithm::Sha256.digest(code.as_bytes())
+    }
+
+    // 请求访问的是诱饵账户时发出告警：输出告警日志、通知订阅者，配置了 webhook 时在后台 POST
+    // 请求本身照常处理，攻击者看不出账户是诱饵
+    fn check_honeytoken<T>(&self, request: &Request<T>, user_name: &str, rpc: &'static str) {
+        let normalized = self.config.user_names.normalize(user_name); // 诱饵账户按规范化后的用户名匹配，换一种写法也会触发
+        if !self.config.honeytokens.iter().any(|honeytoken| honeytoken == user_name || self.config.user_names.normalize(honeytoken) == normalized) {
+            return;
+        }
+        let alert = HoneytokenAlert { user: user_name.to_string(), rpc, correlation_id: correlation_id(request), remote_addr: request.remote_addr(), at: unix_now() };
+        let from = alert.remote_addr.map_or_else(|| "unknown address".to_string(), |addr| addr.to_string());
+        error!(user = %alert.user, rpc, from = %from, "honeytoken account accessed");
+        let _ = self.alerts.0.send(alert.clone());
+        if let Some(url) = self.config.alert_webhook.clone() {
+            tokio::spawn(async move { honeytoken::post_webhook(&url, &alert).await });
+        }
+    }
+
+    // 配置了 registration_identities 时，只有出示其中一个客户端身份的连接可以注册
+    #[allow(clippy::result_large_err)]
+    fn check_registration_identity<T>(&self, request: &Request<T>) -> Result<(), Status> {
+        if self.config.registration_identities.is_empty() {
+            return Ok(());
+        }
+        match ClientIdentity::of(request) {
+            Some(ClientIdentity(subject)) if self.config.registration_identities.contains(&subject) => Ok(()),
+            Some(ClientIdentity(subject)) => Err(Status::new(Code::PermissionDenied, format!("client {} is not allowed to register users", subject))),
+            None => Err(Status::new(Code::Unauthenticated, "registration requires a client certificate")),
+        }
+    }
+
+    // 配置了审计日志时追加事件，写入失败只记录错误日志，不影响请求的结果
+    async fn audit(&self, event: AuditEvent) {
+        let Some(audit_log) = &self.config.audit_log else {
+            return;
+        };
+        let kind = event.kind;
+        if let Err(err) = audit_log.append(event).await {
+            error!(error = %err, %kind, "could not write the audit log");
+        }
+    }
+
+    // 通知订阅者会话已被吊销（没有订阅者时忽略），并记入审计日志
+    async fn publish_revocation(&self, session_id: String, subject: String, reason: &str) {
+        let event = AuditEvent {
+            id: 0,
+            at: unix_now(),
+            kind: AuditKind::SessionRevoked,
+            user: subject.clone(),
+            success: true,
+            reason: reason.to_string(),
+            remote_addr: String::new(),
+            correlation_id: String::new(),
+            session: admin::session_handle(&session_id),
+        };
+        let revoked = RevokedSession { session_id, subject, revoked_at: event.at, reason: reason.to_string() };
+        let _ = self.revocations.0.send(revoked);
+        self.audit(event).await;
+    }
+
+    // 删除用户的所有会话并通知订阅者，返回被吊销的会话数
+    async fn revoke_user_sessions(&self, user_name: &str, reason: &str) -> Result<u32, Status> {
+        let revoked = self.sessions.delete_sessions(user_name).await?;
+        let count = revoked.len() as u32;
+        for session_id in revoked {
+            self.publish_revocation(session_id, user_name.to_string(), reason).await;
+        }
+        Ok(count)
+    }
+
+    // 为用户建立一个新的会话，scopes 为用户记录中的权限范围，device_id 为空时会话不绑定设备，返回会话 ID、过期时间和权限范围；
+    // 配置了会话数上限时先吊销超出的最早的会话，或者按策略拒绝，账户恢复总是吊销
+    async fn create_session(
+        &self,
+        user_name: String,
+        auth_method: &'static str,
+        scopes: Vec<String>,
+        metadata: HashMap<String, String>,
+        device_id: String,
+    ) -> Result<(String, u64, Vec<String>), Status> {
+        let session_id = self.config.rng.string(12); // 生成 12 位随机字符串作为会话 ID
+        let issued_at = unix_now();
+        let expires_at = issued_at + self.ttls.session.load(Ordering::Relaxed);
+        if let Some(limit) = &self.config.session_limit {
+            let recovery = auth_method == AUTH_METHOD_RECOVERY || auth_method == AUTH_METHOD_GUARDIANS;
+            let evicted = limit.sessions_to_evict(&user_name, self.sessions.list_sessions(&user_name).await?, issued_at, recovery)?;
+            for session_id in evicted {
+                if self.sessions.delete_session(&session_id).await?.is_some() {
+                    info!(user = %user_name, "revoked the oldest session after reaching the session limit");
+                    self.publish_revocation(session_id, user_name.clone(), REVOKED_BY_SESSION_LIMIT).await;
+                }
+            }
+        }
+        let session = SessionInfo { user: user_name, issued_at, expires_at, auth_method: auth_method.to_string(), scopes: scopes.clone(), metadata, device_id };
+        self.sessions.put_session(&session_id, session).await?;
+        Ok((session_id, expires_at, scopes))
+    }
+
+    // 配置了签名密钥时为刚建立的会话签发 JWT，否则返回空字符串
+    fn issue_token(&self, user_name: &str, session_id: &str, auth_method: &str, expires_at: u64, scopes: &[String]) -> String {
+        let Some(issuer) = &self.config.jwt else {
+            return String::new();
+        };
+        issuer.issue(&JwtClaims {
+            issuer: issuer.issuer.clone(),
+            subject: user_name.to_string(),
+            session_id: session_id.to_string(),
+            issued_at: unix_now(),
+            expires_at,
+            auth_method: auth_method.to_string(),
+            scopes: scopes.to_vec(),
+        })
+    }
+
+    /// 订阅诱饵账户告警，嵌入服务器的程序可以据此接入自己的告警系统
+    ///
+    /// 返回:
+    /// - `broadcast::Receiver<HoneytokenAlert>`: 订阅之后发生的告警，落后超过 256 条时返回 `Lagged`
+    pub fn subscribe_honeytoken_alerts(&self) -> broadcast::Receiver<HoneytokenAlert> {
+        self.alerts.0.subscribe()
+    }
+
+    /// 导出服务器保存的关于用户的全部数据：用户记录、会话（不含会话 ID）以及尚未完成的挑战和跨设备登录数
+    /// ExportUserData RPC 在验证用户本人后调用；嵌入服务器的管理程序可以直接调用
+    ///
+    /// 参数:
+    /// - `user_name`: 用户名
+    ///
+    /// 返回:
+    /// - `Result<Option<UserDataExport>, Status>`: 用户不存在时返回 None，存储出错时返回 Unavailable
+    pub async fn export_user(&self, user_name: &str) -> Result<Option<UserDataExport>, Status> {
+        let mut data = match self.users.get_user(user_name).await? {
+            Some(user_info) => UserDataExport {
+                user: user_name.to_string(),
+                y1: user_info.y1.to_bytes_be(),
+                y2: user_info.y2.to_bytes_be(),
+                salt: user_info.salt.clone(),
+                kdf: user_info.kdf.clone(),
+                scopes: user_info.scopes.clone(),
+                metadata: user_info.metadata.clone(),
+                exported_at: unix_now(),
+                profile: Some(user_info.profile()),
+                recovery_codes_remaining: user_info.recovery_codes.len() as u32,
+                credential_reset_required: user_info.reset_required,
+                guardians: user_info.guardians.clone(),
+                guardian_threshold: user_info.guardian_threshold,
+                totp_enabled: !user_info.totp_secret.is_empty(),
+                params_hash: user_info.group.clone(),
+                ..Default::default()
+            },
+            None => return Ok(None),
+        };
+        let mut sessions: Vec<ExportedSession> = self
+            .sessions
+            .list_sessions(user_name)
+            .await?
+            .into_iter()
+            .map(|(_, session)| exported_session(session))
+            .collect();
+        sessions.sort_by_key(|session| session.issued_at); // 存储不保证顺序，按建立时间输出
+        data.sessions = sessions;
+        data.pending_challenges = self.sessions.count_challenges(user_name).await?;
+        data.pending_logins = self.in_flight.pending_logins.collect(|_, pending| (pending.user == user_name).then_some(())).len() as u32;
+        Ok(Some(data))
+    }
+
+    /// 解除用户因连续验证失败而被暂时锁定的状态，并清零失败计数，锁定在到期后也会自动解除；管理接口的 UnlockUser 调用，嵌入服务器的程序也可以直接调用
+    ///
+    /// 参数:
+    /// - `user_name`: 用户名
+    ///
+    /// 返回:
+    /// - `bool`: 用户此前是否被锁定
+    pub fn unlock_user(&self, user_name: &str) -> bool {
+        let unlocked = self.lockout.unlock(user_name);
+        if unlocked {
+            info!(user = %user_name, "unlocked account");
+        }
+        unlocked
+    }
+
+    /// 不可恢复地删除用户：用户记录、挑战、待完成登录和会话，每个被吊销的会话推送 reason 为 "user-deleted" 的通知；
+    /// 配置了审计日志时该用户的事件（包括这次吊销会话的事件）改为假名 "deleted-<随机字符串>"
+    /// DeleteUserData RPC 在验证用户本人后调用；嵌入服务器的管理程序可以直接调用
+    ///
+    /// 参数:
+    /// - `user_name`: 用户名
+    ///
+    /// 返回:
+    /// - `Result<Option<u32>, Status>`: 被吊销的会话数，用户不存在时返回 None，存储出错时返回 Unavailable
+    pub async fn delete_user(&self, user_name: &str) -> Result<Option<u32>, Status> {
+        // 先删除用户记录，之后的挑战验证和跨设备登录都会因用户不存在而失败
+        let revoked = if self.users.delete_user(user_name).await? {
+            self.sessions.delete_challenges(user_name).await?;
+            self.in_flight.pending_logins.retain(|_, pending| pending.user != user_name);
+            self.in_flight.guardian_recoveries.retain(|_, recovery| recovery.user != user_name);
+            self.lockout.unlock(user_name); // 之后注册的同名用户不继承失败计数
+            Some(self.revoke_user_sessions(user_name, "user-deleted").await?)
+        } else {
+            None
+        };
+
+        // 用户不存在时也改为假名：上一次删除在这一步失败后重试，仍能完成
+        if let Some(audit_log) = &self.config.audit_log {
+            let tombstone = format!("deleted-{}", self.config.rng.string(12));
+            let count = audit_log.pseudonymize(user_name, &tombstone).await?;
+            if count > 0 {
+                info!(%tombstone, events = count, "pseudonymized the audit log of a deleted user");
+            }
+        }
+        Ok(revoked)
+    }
+
+    /// 删除已过期的挑战、会话、待完成登录和多方恢复；`spawn_cleanup` 定期调用，嵌入服务器的程序也可以自己安排
+    ///
+    /// 返回:
+    /// - `Result<Purged, Status>`: 存储中删除的挑战和会话数，存储出错时返回 Unavailable
+    pub async fn purge_expired(&self) -> Result<Purged, Status> {
+        let now = unix_now();
+        self.in_flight.pending_logins.retain(|_, pending| pending.expires_at > now);
+        self.in_flight.guardian_recoveries.retain(|_, recovery| recovery.expires_at > now);
+        self.limiter.purge(); // 已经补满的令牌桶
+        self.lockout.purge(); // 很久没有再失败的计数
+        self.totp.purge(now); // 已经过期的 TOTP 时间步
+        Ok(self.sessions.purge_expired(now).await?)
+    }
+
+    // 在阻塞线程池中执行模幂等 CPU 密集的计算，不占用处理其他 RPC 的异步线程；
+    // 同时进行的计算不超过 cpu_workers 个，其余的在这里排队，而不是占满阻塞线程池
+    // 客户端取消或超过截止时间时处理函数的 future 在这里被丢弃，计算结果随之丢弃，调用方只在计算完成后修改存储
+    async fn off_thread<T: Send + 'static>(&self, what: &str, work: impl FnOnce() -> T + Send + 'static) -> Result<T, Status> {
+        let _permit = self.cpu.acquire().await.expect("the cpu semaphore is never closed");
+        tokio::task::spawn_blocking(work).await.map_err(|e| Status::new(Code::Internal, format!("{} did not complete: {}", what, e)))
+    }
+
+    // 在阻塞线程池中验证解答，totp 为 Some 时同时检查 TOTP 验证码，两者都通过才算成功；
+    // 挑战所属的用户被锁定时不验证，返回 Unavailable，验证结果计入用户的连续失败次数
+    async fn verify_off_thread(&self, zkp: &'static GroupParams, challenge: &PendingChallenge, y1: &BigUint, y2: &BigUint, s: BigUint, totp: Option<(&[u8], &str)>) -> Result<bool, Status> {
+        self.lockout.check(&challenge.user)?;
+        let (r1, r2, y1, y2, c) = (challenge.r1.clone(), challenge.r2.clone(), y1.clone(), y2.clone(), challenge.c.clone());
+        let started = Instant::now();
+        let valid = self.off_thread("verification", move || zkp.verify(&r1, &r2, &y1, &y2, &c, &s)).await?;
+        self.metrics.verified(valid, started.elapsed());
+        // 解答正确时才检查并消耗验证码，猜测密码的请求不会使用户当前的验证码失效
+        let passed = valid && totp.is_none_or(|(secret, code)| self.totp.accept(&challenge.user, secret, code, unix_now()));
+        self.lockout.record(&challenge.user, passed);
+        Ok(passed)
+    }
+
+    // 用户启用了 TOTP 时登录必须同时提交验证码，返回需要检查的密钥和验证码；没有启用时返回 None
+    #[allow(clippy::result_large_err)]
+    fn second_factor<'a>(user_name: &str, secret: &'a [u8], code: &'a str) -> Result<Option<(&'a [u8], &'a str)>, Status> {
+        match (secret.is_empty(), code.is_empty()) {
+            (true, _) => Ok(None),
+            (false, true) => Err(Status::new(Code::Unauthenticated, format!("User: {} requires a TOTP code", user_name))),
+            (false, false) => Ok(Some((secret, code))),
+        }
+    }
+
+    // 验证对挑战的解答，认证 ID 只能使用一次，无论成功与否都从映射表中移除
+    // 返回通过验证的挑战（所属用户名和元数据）以及验证时使用的用户记录；与 gRPC 处理函数一样直接返回 Status，方便用 ? 传递
+    // 用户启用了 TOTP 时必须同时提交有效的验证码 totp_code，只知道密码不能证明是账户本人
+    async fn check_answer_with(&self, auth_id: &str, s: &[u8], totp_code: &str, deadline: Deadline) -> Result<(PendingChallenge, UserInfo), Status> {
+        let challenge = self
+            .sessions
+            .take_challenge(auth_id)
+            .await?
+            .ok_or_else(|| AuthImpl::challenge_not_found(auth_id))?;
+        if challenge.expires_at <= unix_now() {
+            return Err(AuthImpl::challenge_expired(auth_id));
+        }
+
+        let Some(user_info) = self.users.get_user(&challenge.user).await? else {
+            return Err(AuthImpl::user_not_found(&challenge.user));
+        };
+        let zkp = self.user_group(&challenge.user, &user_info)?;
+        let totp = AuthImpl::second_factor(&challenge.user, &user_info.totp_secret, totp_code)?;
+
+        let s = AuthImpl::scalar(zkp, "s", s)?; // 将 s 字节数组转换为 BigUint 类型，拒绝不小于 q 的值
+
+        deadline.check("verifying the answer")?;
+        if self.verify_off_thread(zkp, &challenge, &user_info.y1, &user_info.y2, s, totp).await? {
+            Ok((challenge, user_info))
+        } else {
+            Err(AuthImpl::bad_answer(auth_id, totp.is_some()))
+        }
+    }
+
+    // 注册的处理，由 Register 调用，结果记入审计日志
+    async fn register_user(&self, request: Request<RegisterRequest>) -> Result<Response<RegisterResponse>, Status> {
+        debug!(request = ?Redacted(request.get_ref()), "processing Register");
+        self.drain.check("Register")?; // 排空时不接受新的注册
+        let deadline = Deadline::of(&request); // 客户端给出的截止时间
+        self.check_registration_identity(&request)?; // 只允许配置的机器注册
+        self.limiter.check(Limited::Register, client_ip(&request), Some(&self.config.user_names.normalize(&request.get_ref().user)))?; // 超过配额时返回 ResourceExhausted，按规范化后的用户名计数
+
+        let request = request.into_inner(); // 将 gRPC 请求解包，提取请求消息
+        let user_name = self.config.user_names.check_new("user", &request.user)?; // 规范化用户名，拒绝不符合策略的和保留的用户名
+        let zkp = self.group(&request.params_hash)?; // 注册到客户端选择的群，拒绝服务器不接受的参数集
+        AuthImpl::check_metadata(&request.metadata)?; // 拒绝过大的元数据
+        AuthImpl::check_profile(&request.display_name, &request.contact)?; // 拒绝过大的账户资料
+        if request.recovery_codes > MAX_RECOVERY_CODES {
+            return Err(invalid_argument("recovery_codes", InvalidReason::TooMany, format!("at most {} recovery codes can be issued", MAX_RECOVERY_CODES)));
+        }
+        let (guardians, guardian_bindings) = self.designate_guardians(&user_name, &request.guardians, request.guardian_threshold).await?; // 多方恢复的监护人和门限
+        if !request.totp_secret.is_empty() && !(TOTP_MIN_SECRET_LEN..=TOTP_MAX_SECRET_LEN).contains(&request.totp_secret.len()) {
+            let reason = if request.totp_secret.len() < TOTP_MIN_SECRET_LEN { InvalidReason::OutOfRange } else { InvalidReason::TooLong };
+            return Err(invalid_argument("totp_secret", reason, format!("totp_secret must be between {} and {} bytes", TOTP_MIN_SECRET_LEN, TOTP_MAX_SECRET_LEN)));
+        }
+
+        // 不带旧密码证明的注册只能使用新的用户名，在验证持有证明之前先拒绝已存在的用户名
+        let reregister = !request.auth_id.is_empty();
+        if reregister && !self.config.allow_reregistration {
+            return Err(error(Code::AlreadyExists, ErrorCode::UserAlreadyExists, format!("User: {} already exists and re-registration is disabled", user_name)));
+        }
+        if !reregister && self.users.get_user(&user_name).await?.is_some() {
+            return Err(AuthImpl::already_exists(&user_name));
+        }
+
+        // 验证持有证明：注册者必须知道 y1、y2 对应的私钥 x，且证明绑定到该用户名
+        // 拒绝退化或不在子群中的公开值，例如 y1 = y2 = 1 时任何 s 都能通过验证
+        let (y1, y2) = self.element_pair(zkp, ("y1", &request.y1), ("y2", &request.y2)).await?;
+        let proof = (request.proof_c.as_slice(), request.proof_s.as_slice(), request.proof_hash.as_str());
+        self.check_possession(zkp, &request.user, (&y1, &y2), proof, &request.params_hash, deadline).await?; // params_hash 已确认是服务器接受的群
+        deadline.check("storing the user")?; // 客户端已经收不到结果时不再写入
+
+        let recovery_codes: Vec<String> = (0..request.recovery_codes).map(|_| self.config.rng.string(RECOVERY_CODE_LEN)).collect(); // 丢失密码时使用的一次性恢复码
+        let user_info = UserInfo {
+            y1, // 已检查的公开值
+            y2,
+            salt: request.salt, // 盐和 KDF 参数原样保存，登录时返回给客户端
+            kdf: request.kdf,
+            scopes: self.config.default_scopes.clone(), // 新用户使用默认权限范围
+            metadata: request.metadata, // 注册时的元数据随用户记录保存
+            display_name: request.display_name, // 账户资料
+            contact: request.contact,
+            created_at: unix_now(),
+            recovery_codes: recovery_codes.iter().map(|code| AuthImpl::recovery_code_hash(code)).collect(), // 只保存恢复码的哈希
+            reset_required: false,
+            guardians, // 监护人必须已经注册，批准时还需要证明身份
+            guardian_bindings, // 绑定到指定时的监护人账户
+            guardian_threshold: request.guardian_threshold,
+            totp_secret: request.totp_secret, // 为空时不启用第二因素
+            group: zkp.params_hash(), // 之后该用户的挑战和解答都在注册时的群中计算
+        };
+
+        if reregister {
+            // 用旧密码（和旧记录的 TOTP 验证码）回答该用户的挑战，证明是账户本人后替换用户记录，旧密码建立的会话都被吊销
+            let (challenge, _) = self.check_answer_with(&request.auth_id, &request.s, &request.totp_code, deadline).await?;
+            if challenge.user != user_name {
+                return Err(Status::new(Code::PermissionDenied, format!("AuthId: {} does not belong to user {}", request.auth_id, user_name)));
+            }
+            deadline.check("storing the user")?;
+            self.users.put_user(&user_name, user_info).await?;
+            self.revoke_user_sessions(&user_name, "re-registered").await?;
+        } else if !self.users.create_user(&user_name, user_info).await? {
+            return Err(AuthImpl::already_exists(&user_name)); // 并发的注册先写入了同名用户
+        }
+        self.metrics.registered();
+
+        // 注册成功，恢复码只在这里返回一次
+        Ok(Response::new(RegisterResponse { recovery_codes }))
+    }
+
+    // 创建认证挑战的处理，由 CreateAuthenticationChallenge 调用，结果记入审计日志；挑战保存在存储中，等待 VerifyAuthentication 取出
+    async fn issue_challenge(&self, request: Request<AuthenticationChallengeRequest>) -> Result<Response<AuthenticationChallengeResponse>, Status> {
+        let (metadata, extensions, request) = request.into_parts();
+        let (challenge, response) = self.new_challenge(&Request::from_parts(metadata, extensions, ()), request, "CreateAuthenticationChallenge").await?;
+        self.sessions.put_challenge(&response.auth_id, challenge).await?; // 将认证 ID 映射到对应的挑战
+        Ok(Response::new(response))
+    }
+
+    // 检查承诺并生成挑战，返回待应答的挑战和发给客户端的响应，由调用方保存挑战；
+    // context 为原始请求的元数据和扩展（客户端地址、通道绑定、截止时间），rpc 为诱饵账户告警中的 RPC 名称
+    async fn new_challenge(&self, context: &Request<()>, request: AuthenticationChallengeRequest, rpc: &'static str) -> Result<(PendingChallenge, AuthenticationChallengeResponse), Status> {
+        debug!(request = ?Redacted(&request), "processing Challenge");
+        self.check_honeytoken(context, &request.user, rpc);
+        self.drain.check(rpc)?; // 排空时不签发新的挑战，已经签发的挑战仍然可以应答
+        self.limiter.check(Limited::Challenge, client_ip(context), Some(&self.config.user_names.normalize(&request.user)))?; // 在模幂运算和写入存储之前限流，换一种写法不能绕过按用户名的配额
+        let binding = ChannelBinding::of(context); // TLS 层提供的通道绑定值
+        if binding.is_none() && self.config.require_channel_binding {
+            return Err(Status::new(Code::FailedPrecondition, "channel binding is required but the connection provides none"));
+        }
+
+        let user_name = self.config.user_names.check("user", &request.user)?; // 规范化用户名，不为无效的用户名查询存储
+        AuthImpl::check_metadata(&request.metadata)?; // 拒绝过大的元数据
+        AuthImpl::check_device_id(&request.device_id)?; // 拒绝过长的设备标识
+
+        // 盐、KDF 参数和用户的群随挑战返回，公开值供挑战来源使用
+        let (zkp, salt, kdf, y1, y2) = match self.users.get_user(&user_name).await? {
+            Some(user_info) => (self.user_group(&user_name, &user_info)?, user_info.salt, user_info.kdf, user_info.y1, user_info.y2),
+            None => return Err(AuthImpl::user_not_found(&user_name)), // 如果用户不存在，返回 NotFound 错误
+        };
+        AuthImpl::check_params(zkp, &request.params_hash)?; // 拒绝在其他群中计算的 r1、r2
+        let (r1, r2) = self.element_pair(zkp, ("r1", &request.r1), ("r2", &request.r2)).await?; // 先检查承诺，拒绝时不写入挑战
+
+        let auth_id = self.config.rng.string(12); // 生成 12 位随机字符串作为认证 ID
+        // 由配置的来源取得小于 q 的挑战值
+        let input = ChallengeInput { user: &user_name, auth_id: &auth_id, y1: &y1, y2: &y2, r1: &r1, r2: &r2, rng: &self.config.rng };
+        let c = self.config.challenge_source.challenge(zkp, input).await.map_err(|e| Status::new(Code::Unavailable, format!("challenge source failed: {}", e)))?;
+        if c >= zkp.q {
+            return Err(Status::new(Code::Internal, "challenge source returned a value outside [0, q)"));
+        }
+        // 有通道绑定时保存绑定后的挑战值，验证应答的代码不需要区分
+        let expected_c = match &binding {
+            Some(binding) => zkp.bind_challenge(&c, &r1, &r2, &binding.0),
+            None => c.clone(),
+        };
+
+        // 承诺、挑战值和过期时间作为一个条目，不修改用户记录
+        let expires_at = unix_now() + self.ttls.challenge.load(Ordering::Relaxed); // 挑战的过期时间
+        let challenge = PendingChallenge { user: user_name, r1, r2, c: expected_c, expires_at, metadata: request.metadata, device_id: request.device_id };
+        self.metrics.challenge_issued();
+
+        // 认证挑战响应，包含生成的认证 ID、挑战值 c 及其过期时间
+        // 同时返回注册时的盐、KDF 参数和群，客户端据此派生私钥并计算 s
+        Ok((challenge, AuthenticationChallengeResponse { auth_id, c: c.to_bytes_be(), salt, kdf, expires_at, channel_bound: binding.is_some(), params_hash: zkp.params_hash() }))
+    }
+
+    // 验证解答的处理，由 VerifyAuthentication 调用，结果记入审计日志；找到挑战后把所属的用户名写入 user，审计事件据此记录用户
+    async fn verify_answer(&self, request: Request<AuthenticationAnswerRequest>, user: &mut String) -> Result<Response<AuthenticationAnswerResponse>, Status> {
+        debug!(request = ?Redacted(request.get_ref()), "processing Verification");
+        let deadline = Deadline::of(&request); // 客户端给出的截止时间
+        self.limiter.check(Limited::Verify, client_ip(&request), None)?; // 按客户端地址限流，用户名在找到挑战后检查
+
+        let request = request.into_inner(); // 解包 gRPC 请求，获取请求消息
+        AuthImpl::check_metadata(&request.metadata)?; // 拒绝过大的元数据
+
+        // 挑战只能应答一次：无论验证成功与否都先从存储中取出，失败后客户端需要重新请求挑战，
+        // 同一个 (r1, r2, c) 不能被反复用来试探 s；认证 ID 不存在或已被使用时返回 NotFound 错误
+        let Some(challenge) = self.sessions.take_challenge(&request.auth_id).await? else {
+            return Err(AuthImpl::challenge_not_found(&request.auth_id));
+        };
+        user.clone_from(&challenge.user);
+        self.answer_challenge(challenge, request, deadline).await.map(Response::new)
+    }
+
+    // 验证对已取出的挑战的解答，通过后建立会话；一元调用和双向流认证共用
+    async fn answer_challenge(&self, challenge: PendingChallenge, request: AuthenticationAnswerRequest, deadline: Deadline) -> Result<AuthenticationAnswerResponse, Status> {
+        let auth_id = request.auth_id; // 从请求中获取认证 ID
+        // 挑战已过期时拒绝验证，客户端需要重新请求挑战
+        if challenge.expires_at <= unix_now() {
+            return Err(AuthImpl::challenge_expired(&auth_id));
+        }
+        self.limiter.check(Limited::Verify, None, Some(&challenge.user))?; // 按挑战所属的用户限流
+        // 公开值和用户记录中的会话信息，用户在挑战发出后被删除时返回 NotFound
+        let Some(user_info) = self.users.get_user(&challenge.user).await? else {
+            return Err(AuthImpl::user_not_found(&challenge.user));
+        };
+        if user_info.reset_required {
+            return Err(AuthImpl::reset_required(&challenge.user)); // 重置密码之前不建立普通会话
+        }
+        let zkp = self.user_group(&challenge.user, &user_info)?;
+        AuthImpl::check_params(zkp, &request.params_hash)?; // 拒绝在其他群中计算的 s
+        let UserInfo { y1, y2, scopes, mut metadata, totp_secret, .. } = user_info;
+        let totp = AuthImpl::second_factor(&challenge.user, &totp_secret, &request.totp_code)?; // 启用了 TOTP 的用户必须提交验证码
+
+        // 应答必须来自请求挑战的设备，会话随后绑定到该设备
+        if request.device_id != challenge.device_id {
+            return Err(Status::new(Code::PermissionDenied, format!("AuthId: {} answered from a different device", auth_id)));
+        }
+        let s = AuthImpl::scalar(zkp, "s", &request.s)?; // 将请求中的 s 字节数组转换为 BigUint 类型，拒绝不小于 q 的值
+
+        // 验证用户提交的解答是否有效
+        deadline.check("verifying the answer")?;
+        if self.verify_off_thread(zkp, &challenge, &y1, &y2, s, totp).await? {
+            // 如果验证通过，生成一个新的会话 ID，并记录会话所属的用户和过期时间；客户端已经收不到结果时不再建立会话
+            // 会话元数据：依次合并注册、挑战和应答请求的元数据，后者覆盖前者的同名键
+            deadline.check("creating the session")?;
+            metadata.extend(challenge.metadata);
+            metadata.extend(request.metadata);
+            let (session_id, expires_at, scopes) = self.create_session(challenge.user.clone(), AUTH_METHOD_DIRECT, scopes, metadata, challenge.device_id).await?;
+            let token = self.issue_token(&challenge.user, &session_id, AUTH_METHOD_DIRECT, expires_at, &scopes); // 未配置签名密钥时为空
+            Ok(AuthenticationAnswerResponse { session_id, expires_at, scopes, token })
+        } else {
+            // 验证失败，返回权限拒绝错误
+            Err(AuthImpl::bad_answer(&auth_id, totp.is_some()))
+        }
+    }
+
+    // 双向流认证的两步，由 Authenticate 的后台任务调用，每一步的结果与一元调用一样记入审计日志；
+    // 返回的错误由调用方作为流的最后一条消息发送，客户端断开时直接结束
+    async fn authenticate_steps(&self, context: &Request<()>, steps: &mut Streaming<AuthenticateRequest>, tx: &mpsc::Sender<Result<AuthenticateResponse, Status>>) -> Result<(), Status> {
+        let deadline = Deadline::of(context); // 整个流的截止时间
+        let commitment = match steps.message().await? {
+            Some(AuthenticateRequest { step: Some(authenticate_request::Step::Commitment(commitment)) }) => commitment,
+            Some(_) => return Err(invalid_argument("step", InvalidReason::UnexpectedMessage, "the first message on the stream must be the commitment")),
+            None => return Ok(()), // 客户端没有发送任何消息就结束了请求流
+        };
+
+        // 第一步：挑战只保存在本任务中，不写入存储，流结束时随之丢弃
+        let event = AuditEvent::of(AuditKind::Challenge, &commitment.user, context);
+        let result = self.new_challenge(context, commitment, "Authenticate").await;
+        self.audit(event.outcome(&result)).await;
+        let (challenge, response) = result?;
+        let auth_id = response.auth_id.clone();
+        let expires_at = challenge.expires_at;
+        if tx.send(Ok(AuthenticateResponse { step: Some(authenticate_response::Step::Challenge(response)) })).await.is_err() {
+            return Ok(()); // 客户端已断开
+        }
+
+        // 第二步：挑战过期前必须收到解答
+        let event = AuditEvent::of(AuditKind::Verify, &challenge.user, context);
+        let result = async {
+            let wait = Duration::from_secs(expires_at.saturating_sub(unix_now()));
+            let mut answer = match tokio::time::timeout(wait, steps.message()).await {
+                Err(_) => return Err(AuthImpl::challenge_expired(&auth_id)),
+                Ok(message) => match message? {
+                    Some(AuthenticateRequest { step: Some(authenticate_request::Step::Answer(answer)) }) => answer,
+                    Some(_) => return Err(invalid_argument("step", InvalidReason::UnexpectedMessage, "the second message on the stream must be the answer")),
+                    None => return Err(Status::new(Code::Cancelled, format!("AuthId: {} the client closed the stream without answering", auth_id))),
+                },
+            };
+            if answer.auth_id.is_empty() {
+                answer.auth_id.clone_from(&auth_id); // 流上只有一个挑战，客户端可以不重复认证 ID
+            } else if answer.auth_id != auth_id {
+                return Err(invalid_argument("auth_id", InvalidReason::Mismatch, format!("AuthId: {} does not match the challenge on this stream", answer.auth_id)));
+            }
+            self.limiter.check(Limited::Verify, client_ip(context), None)?;
+            AuthImpl::check_metadata(&answer.metadata)?;
+            self.answer_challenge(challenge, answer, deadline).await
+        }
+        .await;
+        self.audit(event.outcome(&result)).await;
+        let _ = tx.send(Ok(AuthenticateResponse { step: Some(authenticate_response::Step::Session(result?)) })).await;
+        Ok(())
+    }
+}
+
+// 实现 gRPC 服务的接口，这里实现的是 Auth 服务接口
+#[tonic::async_trait] // 使用 async_trait 宏将异步函数声明为 Tonic 异步 gRPC 服务
+impl Auth for AuthImpl {
+    // 返回默认的群或用户注册时的群，用户不存在时与挑战请求一样返回 NotFound
+    async fn get_auth_parameters(&self, request: Request<GetAuthParametersRequest>) -> Result<Response<GetAuthParametersResponse>, Status> {
+        debug!(request = ?request.get_ref(), "processing GetAuthParameters");
+        self.check_honeytoken(&request, &request.get_ref().user, "GetAuthParameters");
+        let user_name = request.into_inner().user;
+        let zkp = if user_name.is_empty() {
+            self.params
+        } else {
+            let user_name = self.config.user_names.check("user", &user_name)?; // 与挑战一样按规范化后的用户名查找
+            match self.users.get_user(&user_name).await? {
+                Some(user_info) => self.user_group(&user_name, &user_info)?,
+                None => return Err(AuthImpl::user_not_found(&user_name)),
+            }
+        };
+        let group = if zkp == GroupParams::rfc5114_1024() { settings::BUILTIN_GROUP.to_string() } else { String::new() }; // 参数文件中的同一组参数也报告内置的名称
+        Ok(Response::new(GetAuthParametersResponse {
+            p: zkp.p.to_bytes_be(),
+            q: zkp.q.to_bytes_be(),
+            alpha: zkp.alpha.to_bytes_be(),
+            beta: zkp.beta.to_bytes_be(),
+            params_hash: zkp.params_hash(),
+            group,
+            accepted_params_hashes: self.groups.iter().map(|(hash, _)| hash.clone()).collect(),
+        }))
+    }
+
+    // 实现注册功能，接收 RegisterRequest 并返回 RegisterResponse
+    async fn register(&self, request: Request<RegisterRequest>) -> Result<Response<RegisterResponse>, Status> {
+        let event = AuditEvent::of(AuditKind::Register, &self.config.user_names.normalize(&request.get_ref().user), &request); // 按规范化后的用户名记录，与查询一致
+        let result = self.register_user(request).await;
+        self.audit(event.outcome(&result)).await;
+        result
+    }
+
+    // 实现创建认证挑战的功能，接收 AuthenticationChallengeRequest 并返回 AuthenticationChallengeResponse
+    async fn create_authentication_challenge(&self, request: Request<AuthenticationChallengeRequest>) -> Result<Response<AuthenticationChallengeResponse>, Status> {
+        let event = AuditEvent::of(AuditKind::Challenge, &self.config.user_names.normalize(&request.get_ref().user), &request); // 按规范化后的用户名记录，与查询一致
+        let result = self.issue_challenge(request).await;
+        self.audit(event.outcome(&result)).await;
+        result
+    }
+
+    // 实现认证验证功能，接收 AuthenticationAnswerRequest 并返回 AuthenticationAnswerResponse
+    async fn verify_authentication(&self, request: Request<AuthenticationAnswerRequest>) -> Result<Response<AuthenticationAnswerResponse>, Status> {
+        let mut event = AuditEvent::of(AuditKind::Verify, "", &request);
+        let result = self.verify_answer(request, &mut event.user).await;
+        self.audit(event.outcome(&result)).await;
+        result
+    }
+
+    // 查询会话是否有效，未知或已过期的会话返回 valid = false，过期的会话同时被删除
+    // 绑定了设备的会话只在请求的设备标识一致时有效，会话本身保留；
+    // 恢复会话只能用于重置密码，不是登录会话，账户需要重置密码期间其他会话也无效
+    async fn validate_session(&self, request: Request<ValidateSessionRequest>) -> Result<Response<ValidateSessionResponse>, Status> {
+        let request = request.into_inner(); // 解包 gRPC 请求，获取请求消息
+        let session_id = request.session_id; // 从请求中获取会话 ID
+
+        let response = match self.sessions.get_session(&session_id).await? {
+            Some(session) if session.expires_at > unix_now() => {
+                let recovery = matches!(session.auth_method.as_str(), AUTH_METHOD_RECOVERY | AUTH_METHOD_GUARDIANS);
+                let device_matches = session.device_id.is_empty() || session.device_id == request.device_id;
+                if recovery || !device_matches || self.users.get_user(&session.user).await?.is_some_and(|user_info| user_info.reset_required) {
+                    ValidateSessionResponse::default()
+                } else {
+                    ValidateSessionResponse { valid: true, user: session.user, expires_at: session.expires_at }
+                }
+            }
+            Some(_) => {
+                self.sessions.delete_session(&session_id).await?;
+                ValidateSessionResponse::default()
+            }
+            None => ValidateSessionResponse::default(),
+        };
+        Ok(Response::new(response))
+    }
+
+    // 会话内省：未知或已过期的会话只返回 active = false，不泄露任何其他信息
+    async fn introspect_session(&self, request: Request<IntrospectSessionRequest>) -> Result<Response<IntrospectSessionResponse>, Status> {
+        let session_id = request.into_inner().session_id; // 从请求中获取会话 ID
+
+        let mut response = match self.sessions.get_session(&session_id).await? {
+            Some(session) if session.expires_at > unix_now() => IntrospectSessionResponse {
+                active: true,
+                subject: session.user,
+                issued_at: session.issued_at,
+                expires_at: session.expires_at,
+                auth_method: session.auth_method,
+                scopes: session.scopes,
+                metadata: session.metadata,
+                profile: None,
+                device_id: session.device_id,
+            },
+            _ => return Ok(Response::new(IntrospectSessionResponse::default())),
+        };
+        response.profile = self.users.get_user(&response.subject).await?.map(|user_info| user_info.profile());
+        Ok(Response::new(response))
+    }
+
+    // 注销会话，会话不存在时返回 NotFound 错误
+    async fn logout(&self, request: Request<LogoutRequest>) -> Result<Response<LogoutResponse>, Status> {
+        let session_id = request.into_inner().session_id; // 从请求中获取会话 ID
+
+        if let Some(session) = self.sessions.delete_session(&session_id).await? {
+            self.publish_revocation(session_id, session.user, "logout").await;
+            Ok(Response::new(LogoutResponse {}))
+        } else {
+            Err(Status::new(Code::NotFound, format!("Session: {} not found", session_id)))
+        }
+    }
+
+    // 修改密码：验证用旧密码计算的解决方案 s，通过后替换 y1、y2，并注销该用户的所有会话；
+    // 验证之后凭据已被并发的修改替换时返回 Aborted，客户端需要用当前的密码重新请求挑战
+    async fn change_password(&self, request: Request<ChangePasswordRequest>) -> Result<Response<ChangePasswordResponse>, Status> {
+        let deadline = Deadline::of(&request); // 客户端给出的截止时间
+        let request = request.into_inner(); // 解包 gRPC 请求，获取请求消息
+
+        // 用旧的 y1、y2 验证解答，证明请求者知道旧密码；启用了 TOTP 时还要提交验证码，只知道密码不能替换凭据
+        let (challenge, verified) = self.check_answer_with(&request.auth_id, &request.s, &request.totp_code, deadline).await?;
+        let user_name = challenge.user;
+        let zkp = self.user_group(&user_name, &verified)?; // 新的公开值在用户注册时的群中计算
+        let proof = (request.proof_c.as_slice(), request.proof_s.as_slice(), request.proof_hash.as_str());
+        let (y1, y2) = self.check_new_keys(zkp, &user_name, &request.user, (&request.y1, &request.y2), proof, deadline).await?; // 请求者必须知道新的私钥
+        deadline.check("replacing the password")?; // 客户端已经收不到结果时不再修改用户记录
+
+        // 在同一次修改中确认验证时的 y1、y2 仍是当前的凭据，再替换为新密码对应的 y1、y2
+        let mut superseded = false;
+        let replace = |user_info: &mut UserInfo| {
+            if user_info.y1 != verified.y1 || user_info.y2 != verified.y2 {
+                superseded = true;
+                return false;
+            }
+            user_info.y1 = y1;
+            user_info.y2 = y2;
+            user_info.salt = request.salt;
+            user_info.kdf = request.kdf;
+            true
+        };
+        if !self.users.update_user(&user_name, Box::new(replace)).await? {
+            return Err(if superseded {
+                Status::new(Code::Aborted, format!("User: {} credentials changed while verifying the old password", user_name))
+            } else {
+                AuthImpl::user_not_found(&user_name)
+            });
+        }
+
+        // 旧密码建立的会话全部失效，并通知订阅者
+        self.revoke_user_sessions(&user_name, "password-changed").await?;
+
+        Ok(Response::new(ChangePasswordResponse {}))
+    }
+
+    // 创建待完成的跨设备登录，用户不存在时返回 NotFound 错误
+    async fn create_pending_login(&self, request: Request<CreatePendingLoginRequest>) -> Result<Response<CreatePendingLoginResponse>, Status> {
+        self.check_honeytoken(&request, &request.get_ref().user, "CreatePendingLogin");
+        self.limiter.check(Limited::Challenge, client_ip(&request), Some(&self.config.user_names.normalize(&request.get_ref().user)))?; // 与请求挑战共用配额，不能用来探测用户名或填满待完成登录表
+        let user_name = self.config.user_names.check("user", &request.into_inner().user)?; // 规范化请求中的用户名
+
+        if self.users.get_user(&user_name).await?.is_none() {
+            return Err(AuthImpl::user_not_found(&user_name));
+        }
+
+        let pending_id = self.config.rng.string(12); // 生成 12 位随机字符串作为待完成登录的标识符
+        let nonce = self.config.rng.string(24); // 生成 24 位随机字符串作为二维码中的随机数
+        let pending = PendingLogin { user: user_name, nonce: nonce.clone(), session: None, expires_at: unix_now() + PENDING_LOGIN_TTL_SECS };
+        self.in_flight.pending_logins.write(&pending_id).insert(pending_id.clone(), pending);
+
+        Ok(Response::new(CreatePendingLoginResponse { pending_id, nonce }))
+    }
+
+    // 批准待完成的跨设备登录：验证解答 s，挑战必须属于待完成登录的用户
+    async fn approve_pending_login(&self, request: Request<ApprovePendingLoginRequest>) -> Result<Response<ApprovePendingLoginResponse>, Status> {
+        let deadline = Deadline::of(&request); // 客户端给出的截止时间
+        let request = request.into_inner(); // 解包 gRPC 请求，获取请求消息
+
+        // 先检查待完成的登录，避免为无效的二维码消耗认证 ID
+        let pending_user = match self.in_flight.pending_logins.read(&request.pending_id).get(&request.pending_id) {
+            Some(pending) if pending.nonce != request.nonce => {
+                return Err(Status::new(Code::PermissionDenied, format!("PendingId: {} nonce mismatch", request.pending_id)))
+            }
+            Some(pending) if pending.session.is_some() => {
+                return Err(Status::new(Code::FailedPrecondition, format!("PendingId: {} already approved", request.pending_id)))
+            }
+            Some(pending) if pending.expires_at <= unix_now() => {
+                return Err(Status::new(Code::DeadlineExceeded, format!("PendingId: {} expired", request.pending_id)))
+            }
+            Some(pending) => pending.user.clone(),
+            None => return Err(Status::new(Code::NotFound, format!("PendingId: {} not found", request.pending_id))),
+        };
+
+        let (challenge, _) = self.check_answer_with(&request.auth_id, &request.s, &request.totp_code, deadline).await?;
+        let user_name = challenge.user;
+        if user_name != pending_user {
+            return Err(Status::new(Code::PermissionDenied, format!("AuthId: {} does not belong to user {}", request.auth_id, pending_user)));
+        }
+        deadline.check("approving the login")?; // 客户端已经收不到结果时不建立会话
+
+        // 建立会话，等待待登录的设备通过轮询取走；重置密码之前不建立普通会话
+        let (scopes, mut metadata) = match self.users.get_user(&user_name).await? {
+            Some(user) if user.reset_required => return Err(AuthImpl::reset_required(&user_name)),
+            Some(user) => (user.scopes, user.metadata),
+            None => Default::default(),
+        };
+        metadata.extend(challenge.metadata);
+        let session = self.create_session(user_name, AUTH_METHOD_QR, scopes, metadata, String::new()).await?;
+        // 建立会话期间待完成的登录被取走时，删除刚建立的会话
+        let unclaimed = match self.in_flight.pending_logins.write(&request.pending_id).get_mut(&request.pending_id) {
+            Some(pending) => {
+                pending.session = Some(session);
+                None
+            }
+            None => Some(session.0),
+        };
+        if let Some(session_id) = unclaimed {
+            self.sessions.delete_session(&session_id).await?;
+            return Err(Status::new(Code::NotFound, format!("PendingId: {} not found", request.pending_id)));
+        }
+
+        Ok(Response::new(ApprovePendingLoginResponse {}))
+    }
+
+    // 轮询待完成的跨设备登录，批准后返回会话 ID 并删除该登录
+    async fn poll_pending_login(&self, request: Request<PollPendingLoginRequest>) -> Result<Response<PollPendingLoginResponse>, Status> {
+        let request = request.into_inner(); // 解包 gRPC 请求，获取请求消息
+
+        let mut pending_logins = self.in_flight.pending_logins.write(&request.pending_id); // 获取该登录所在分片的锁
+        let pending = pending_logins
+            .get(&request.pending_id)
+            .ok_or_else(|| Status::new(Code::NotFound, format!("PendingId: {} not found", request.pending_id)))?;
+        if pending.nonce != request.nonce {
+            return Err(Status::new(Code::PermissionDenied, format!("PendingId: {} nonce mismatch", request.pending_id)));
+        }
+        // 过期的登录（即使已经批准）不再返回会话，直接删除，不等定期清理
+        if pending.expires_at <= unix_now() {
+            pending_logins.remove(&request.pending_id);
+            return Err(Status::new(Code::DeadlineExceeded, format!("PendingId: {} expired", request.pending_id)));
+        }
+
+        let response = match &pending.session {
+            Some((session_id, expires_at, scopes)) => {
+                let response = PollPendingLoginResponse {
+                    approved: true,
+                    session_id: session_id.clone(),
+                    expires_at: *expires_at,
+                    scopes: scopes.clone(),
+                };
+                pending_logins.remove(&request.pending_id);
+                response
+            }
+            None => PollPendingLoginResponse::default(),
+        };
+        Ok(Response::new(response))
+    }
+
+    // 修改账户资料：验证解答 s（和启用时的 TOTP 验证码）证明是账户本人后，修改请求中设置了的字段
+    async fn update_profile(&self, request: Request<UpdateProfileRequest>) -> Result<Response<UpdateProfileResponse>, Status> {
+        debug!(request = ?Redacted(request.get_ref()), "processing UpdateProfile");
+        let deadline = Deadline::of(&request); // 客户端给出的截止时间
+        let request = request.into_inner(); // 解包 gRPC 请求，获取请求消息
+        AuthImpl::check_profile(request.display_name.as_deref().unwrap_or_default(), request.contact.as_deref().unwrap_or_default())?; // 先检查大小，避免为无效的请求消耗认证 ID
+
+        let user_name = self.check_answer_with(&request.auth_id, &request.s, &request.totp_code, deadline).await?.0.user;
+        deadline.check("updating the profile")?; // 客户端已经收不到结果时不修改用户记录
+
+        let mut profile = None; // 修改后的账户资料
+        let update = |user_info: &mut UserInfo| {
+            if let Some(display_name) = request.display_name {
+                user_info.display_name = display_name;
+            }
+            if let Some(contact) = request.contact {
+                user_info.contact = contact;
+            }
+            profile = Some(user_info.profile());
+            true
+        };
+        if !self.users.update_user(&user_name, Box::new(update)).await? {
+            return Err(AuthImpl::user_not_found(&user_name));
+        }
+        Ok(Response::new(UpdateProfileResponse { profile }))
+    }
+
+    // 导出用户数据：验证解答 s（和启用时的 TOTP 验证码）证明是账户本人后，返回服务器保存的全部数据
+    async fn export_user_data(&self, request: Request<ExportUserDataRequest>) -> Result<Response<ExportUserDataResponse>, Status> {
+        debug!(request = ?Redacted(request.get_ref()), "processing ExportUserData");
+        let deadline = Deadline::of(&request); // 客户端给出的截止时间
+        let request = request.into_inner(); // 解包 gRPC 请求，获取请求消息
+
+        let user_name = self.check_answer_with(&request.auth_id, &request.s, &request.totp_code, deadline).await?.0.user;
+        let data = self.export_user(&user_name).await?.ok_or_else(|| AuthImpl::user_not_found(&user_name))?;
+        Ok(Response::new(ExportUserDataResponse { data: Some(data) }))
+    }
+
+    // 删除用户数据：验证解答 s（和启用时的 TOTP 验证码）证明是账户本人后，删除用户记录并吊销该用户的所有会话
+    async fn delete_user_data(&self, request: Request<DeleteUserDataRequest>) -> Result<Response<DeleteUserDataResponse>, Status> {
+        debug!(request = ?Redacted(request.get_ref()), "processing DeleteUserData");
+        let deadline = Deadline::of(&request); // 客户端给出的截止时间
+        let request = request.into_inner(); // 解包 gRPC 请求，获取请求消息
+
+        let user_name = self.check_answer_with(&request.auth_id, &request.s, &request.totp_code, deadline).await?.0.user;
+        deadline.check("deleting the user")?; // 客户端已经收不到结果时不删除，客户端可以安全地重试
+        let revoked_sessions = self.delete_user(&user_name).await?.ok_or_else(|| AuthImpl::user_not_found(&user_name))?;
+        info!(user = %user_name, revoked_sessions, "deleted user");
+        Ok(Response::new(DeleteUserDataResponse { revoked_sessions }))
+    }
+
+    // 用恢复码登录：恢复码正确时删除它并标记账户需要重置密码，返回只能用于重置密码的恢复会话
+    // 用户不存在和恢复码错误返回相同的错误，不泄露用户是否存在
+    async fn recover_account(&self, request: Request<RecoverAccountRequest>) -> Result<Response<RecoverAccountResponse>, Status> {
+        debug!(request = ?Redacted(request.get_ref()), "processing RecoverAccount");
+        self.check_honeytoken(&request, &request.get_ref().user, "RecoverAccount");
+        let request = request.into_inner(); // 解包 gRPC 请求，获取请求消息
+        let user_name = self.config.user_names.check("user", &request.user)?; // 规范化请求中的用户名
+        let hash = AuthImpl::recovery_code_hash(&request.code);
+
+        // 在同一次修改中检查并删除恢复码，并发使用同一个恢复码时只有一个请求成功
+        let redeem = |user_info: &mut UserInfo| {
+            let Some(position) = user_info.recovery_codes.iter().position(|stored| *stored == hash) else {
+                return false;
+            };
+            user_info.recovery_codes.remove(position); // 恢复码只能使用一次
+            user_info.reset_required = true;
+            true
+        };
+        if !self.users.update_user(&user_name, Box::new(redeem)).await? {
+            return Err(Status::new(Code::PermissionDenied, format!("User: {} invalid recovery code", user_name)));
+        }
+
+        let scopes = vec![RECOVERY_SCOPE.to_string()]; // 恢复会话不使用用户记录中的权限范围
+        let (session_id, expires_at, scopes) = self.create_session(user_name, AUTH_METHOD_RECOVERY, scopes, HashMap::new(), String::new()).await?;
+        Ok(Response::new(RecoverAccountResponse { session_id, expires_at, scopes }))
+    }
+
+    // 用恢复会话重置密码：替换 y1、y2，清除重置标记，并注销该用户的所有会话（包括恢复会话）
+    async fn reset_credentials(&self, request: Request<ResetCredentialsRequest>) -> Result<Response<ResetCredentialsResponse>, Status> {
+        debug!(request = ?Redacted(request.get_ref()), "processing ResetCredentials");
+        let deadline = Deadline::of(&request); // 客户端给出的截止时间
+        let request = request.into_inner(); // 解包 gRPC 请求，获取请求消息
+
+        let user_name = match self.sessions.get_session(&request.session_id).await? {
+            Some(session) if session.expires_at > unix_now() && matches!(session.auth_method.as_str(), AUTH_METHOD_RECOVERY | AUTH_METHOD_GUARDIANS) => session.user,
+            _ => return Err(Status::new(Code::PermissionDenied, format!("Session: {} is not an active recovery session", request.session_id))),
+        };
+        let zkp = self.stored_group(&user_name).await?; // 新的公开值在用户注册时的群中计算
+        let proof = (request.proof_c.as_slice(), request.proof_s.as_slice(), request.proof_hash.as_str());
+        let (y1, y2) = self.check_new_keys(zkp, &user_name, &request.user, (&request.y1, &request.y2), proof, deadline).await?; // 请求者必须知道新的私钥
+        deadline.check("resetting the password")?; // 客户端已经收不到结果时不再修改用户记录
+
+        let reset = move |user_info: &mut UserInfo| {
+            user_info.y1 = y1;
+            user_info.y2 = y2;
+            user_info.salt = request.salt;
+            user_info.kdf = request.kdf;
+            user_info.reset_required = false;
+            true
+        };
+        if !self.users.update_user(&user_name, Box::new(reset)).await? {
+            return Err(AuthImpl::user_not_found(&user_name));
+        }
+
+        // 丢失的密码和恢复会话都不能再使用
+        self.revoke_user_sessions(&user_name, "credential-reset").await?;
+        Ok(Response::new(ResetCredentialsResponse {}))
+    }
+
+    // 发起多方恢复：复制用户记录中的监护人和门限；任何人都可以发起，新的恢复不取消同一用户进行中的恢复，
+    // 进行中的恢复达到上限时拒绝发起，直到它们完成或过期
+    async fn start_guardian_recovery(&self, request: Request<StartGuardianRecoveryRequest>) -> Result<Response<StartGuardianRecoveryResponse>, Status> {
+        debug!(request = ?request.get_ref(), "processing StartGuardianRecovery");
+        self.check_honeytoken(&request, &request.get_ref().user, "StartGuardianRecovery");
+        let user_name = self.config.user_names.check("user", &request.into_inner().user)?; // 规范化请求中的用户名
+
+        // 复制用户记录中的监护人和门限，以及监护人被指定时账户的指纹；没有记录指纹的旧记录不能由这些监护人批准
+        let (guardians, threshold) = match self.users.get_user(&user_name).await? {
+            Some(user_info) if user_info.guardian_threshold > 0 => {
+                let mut bindings = user_info.guardian_bindings.into_iter();
+                let bound = user_info.guardians.into_iter().filter_map(|guardian| Some((guardian, bindings.next()?))).collect::<Vec<_>>();
+                if (bound.len() as u32) < user_info.guardian_threshold {
+                    return Err(Status::new(Code::FailedPrecondition, format!("User: {} must designate the guardians again by re-registering", user_name)));
+                }
+                (bound, user_info.guardian_threshold)
+            }
+            Some(_) => return Err(Status::new(Code::FailedPrecondition, format!("User: {} has no guardians", user_name))),
+            None => return Err(AuthImpl::user_not_found(&user_name)),
+        };
+
+        let now = unix_now();
+        let in_progress = self.in_flight.guardian_recoveries.collect(|_, recovery| (recovery.user == user_name && recovery.expires_at > now).then_some(())).len();
+        if in_progress >= MAX_GUARDIAN_RECOVERIES_PER_USER {
+            return Err(Status::new(Code::ResourceExhausted, format!("User: {} already has {} recoveries in progress", user_name, in_progress)));
+        }
+        let recovery_id = self.config.rng.string(16); // 生成 16 位随机字符串作为恢复 ID
+        let expires_at = now + GUARDIAN_RECOVERY_TTL_SECS;
+        let names = guardians.iter().map(|(guardian, _)| guardian.clone()).collect();
+        let recovery = GuardianRecovery { user: user_name, guardians, threshold, approvals: Vec::new(), expires_at };
+        self.in_flight.guardian_recoveries.write(&recovery_id).insert(recovery_id.clone(), recovery);
+        Ok(Response::new(StartGuardianRecoveryResponse { recovery_id, guardians: names, threshold, expires_at }))
+    }
+
+    // 监护人批准多方恢复：先验证监护人对自己挑战的解答，再确认其是该恢复的监护人，且账户仍是被指定时的账户
+    async fn approve_guardian_recovery(&self, request: Request<ApproveGuardianRecoveryRequest>) -> Result<Response<ApproveGuardianRecoveryResponse>, Status> {
+        debug!(request = ?Redacted(request.get_ref()), "processing ApproveGuardianRecovery");
+        let deadline = Deadline::of(&request); // 客户端给出的截止时间
+        let request = request.into_inner(); // 解包 gRPC 请求，获取请求消息
+
+        let guardian = self.check_answer_with(&request.auth_id, &request.s, &request.totp_code, deadline).await?.0.user; // 证明身份的监护人，启用了 TOTP 时还要提交验证码
+        let binding = match self.users.get_user(&guardian).await? {
+            Some(guardian_info) => AuthImpl::guardian_binding(&guardian_info),
+            None => return Err(AuthImpl::user_not_found(&guardian)),
+        };
+        let mut recoveries = self.in_flight.guardian_recoveries.write(&request.recovery_id); // 获取该恢复所在分片的锁
+        let recovery = match recoveries.get_mut(&request.recovery_id) {
+            Some(recovery) if recovery.expires_at > unix_now() => recovery,
+            Some(_) => {
+                recoveries.remove(&request.recovery_id);
+                return Err(Status::new(Code::DeadlineExceeded, format!("Recovery: {} expired", request.recovery_id)));
+            }
+            None => return Err(Status::new(Code::NotFound, format!("Recovery: {} not found", request.recovery_id))),
+        };
+        if !recovery.guardians.contains(&(guardian.clone(), binding)) {
+            return Err(Status::new(Code::PermissionDenied, format!("User: {} is not a guardian of {}", guardian, recovery.user)));
+        }
+        if !recovery.approvals.contains(&guardian) {
+            recovery.approvals.push(guardian);
+        }
+        Ok(Response::new(ApproveGuardianRecoveryResponse { approvals: recovery.approvals.len() as u32, threshold: recovery.threshold }))
+    }
+
+    // 完成多方恢复：批准数达到门限时删除恢复，标记账户需要重置密码，返回只能用于重置密码的恢复会话
+    async fn complete_guardian_recovery(&self, request: Request<CompleteGuardianRecoveryRequest>) -> Result<Response<RecoverAccountResponse>, Status> {
+        debug!(request = ?request.get_ref(), "processing CompleteGuardianRecovery");
+        let recovery_id = request.into_inner().recovery_id; // 从请求中获取恢复 ID
+
+        let user_name = {
+            let mut recoveries = self.in_flight.guardian_recoveries.write(&recovery_id); // 获取该恢复所在分片的锁
+            match recoveries.get(&recovery_id) {
+                Some(recovery) if recovery.expires_at <= unix_now() => {
+                    recoveries.remove(&recovery_id);
+                    return Err(Status::new(Code::DeadlineExceeded, format!("Recovery: {} expired", recovery_id)));
+                }
+                Some(recovery) if (recovery.approvals.len() as u32) < recovery.threshold => {
+                    let message = format!("Recovery: {} has {} of {} approvals", recovery_id, recovery.approvals.len(), recovery.threshold);
+                    return Err(Status::new(Code::FailedPrecondition, message));
+                }
+                Some(_) => recoveries.remove(&recovery_id).map(|recovery| recovery.user).unwrap_or_default(),
+                None => return Err(Status::new(Code::NotFound, format!("Recovery: {} not found", recovery_id))),
+            }
+        };
+
+        // 释放分片的锁后修改用户记录，再建立会话
+        let updated = self
+            .users
+            .update_user(
+                &user_name,
+                Box::new(|user_info| {
+                    user_info.reset_required = true;
+                    true
+                }),
+            )
+            .await?;
+        if !updated {
+            return Err(AuthImpl::user_not_found(&user_name));
+        }
+        let scopes = vec![RECOVERY_SCOPE.to_string()]; // 恢复会话不使用用户记录中的权限范围
+        let (session_id, expires_at, scopes) = self.create_session(user_name, AUTH_METHOD_GUARDIANS, scopes, HashMap::new(), String::new()).await?;
+        Ok(Response::new(RecoverAccountResponse { session_id, expires_at, scopes }))
+    }
+
+    // 会话吊销通知的响应流类型
+    type WatchRevocationsStream = ReceiverStream<Result<RevokedSession, Status>>;
+
+    // 订阅会话吊销：转发广播通道中的通知，直到订阅者断开
+    async fn watch_revocations(&self, _request: Request<WatchRevocationsRequest>) -> Result<Response<Self::WatchRevocationsStream>, Status> {
+        let mut revocations = self.revocations.0.subscribe();
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            loop {
+                let item = match revocations.recv().await {
+                    Ok(revoked) => Ok(revoked),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        // 订阅者错过了通知，无法保证其缓存正确，以错误结束流
+                        let _ = tx.send(Err(Status::data_loss(format!("missed {} revocations, resubscribe", missed)))).await;
+                        break;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if tx.send(item).await.is_err() {
+                    break; // 订阅者已断开
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    // 双向流认证的响应流类型
+    type AuthenticateStream = ReceiverStream<Result<AuthenticateResponse, Status>>;
+
+    // 双向流认证：在一个流上收到承诺后返回挑战，再收到解答后返回会话，挑战不需要按认证 ID 保存到存储中等待第二次调用；
+    // 两步在后台任务中进行，任务持有 AuthImpl 的克隆，客户端断开或任何一步出错时结束
+    async fn authenticate(&self, request: Request<Streaming<AuthenticateRequest>>) -> Result<Response<Self::AuthenticateStream>, Status> {
+        let (metadata, extensions, mut steps) = request.into_parts();
+        let context = Request::from_parts(metadata, extensions, ()); // 客户端地址、通道绑定和截止时间
+        let (tx, rx) = mpsc::channel(2);
+        let auth = self.clone();
+        tokio::spawn(async move {
+            if let Err(status) = auth.authenticate_steps(&context, &mut steps, &tx).await {
+                let _ = tx.send(Err(status)).await;
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+/// 启动后台清理任务，每隔 `config.cleanup_interval_secs` 秒调用一次 `purge_expired`；`run_server` 会自动启动，
+/// 自己构建 tonic 服务器的程序需要时调用
+///
+/// 参数:
+/// - `auth`: 与服务共享的 `AuthImpl`
+///
+/// 返回:
+/// - `Option<JoinHandle<()>>`: 清理任务，间隔为 0 时不启动，返回 None
+pub fn spawn_cleanup(auth: Arc<AuthImpl>) -> Option<JoinHandle<()>> {
+    if auth.config.cleanup_interval_secs == 0 {
+        return None;
+    }
+    let mut interval = tokio::time::interval(Duration::from_secs(auth.config.cleanup_interval_secs));
+    Some(tokio::spawn(async move {
+        loop {
+            interval.tick().await;
+            match auth.purge_expired().await {
+                Ok(Purged { challenges: 0, sessions: 0 }) => {}
+                Ok(purged) => info!(challenges = purged.challenges, sessions = purged.sessions, "purged expired challenges and sessions"),
+                Err(status) => warn!(error = status.message(), "could not purge expired sessions"), // 存储暂时不可用时等下一次
+            }
+        }
+    }))
+}
+
+/// `run_server` 失败的原因：监听地址无法绑定、TLS 证书无效或 tonic 服务器出错
+pub type ServeError = Box<dyn std::error::Error + Send + Sync>;
+
+/// 在 `config.addr` 上单独运行认证服务，直到出错；同时提供 `zkp_auth.Auth`、`zkp_auth.v1.Auth`、`zkp_auth.v2.Auth`
+/// 和按排空状态报告的 `grpc.health.v1.Health`
+///
+/// 参数:
+/// - `config`: 服务器配置
+/// - `store`: 用户和会话的存储
+///
+/// 返回:
+/// - `impl Future`: 服务器运行的 future，需要 await 才会开始监听
+pub fn run_server<S: UserStore + SessionStore + 'static>(config: ServerConfig, store: S) -> impl Future<Output = Result<(), ServeError>> {
+    let store = Arc::new(store);
+    run_server_with_stores(config, store.clone(), store)
+}
+
+/// 与 `run_server` 相同，用户记录与挑战和会话使用不同的存储，例如用户记录在 PostgreSQL 中、会话在 Redis 中
+///
+/// 参数:
+/// - `config`: 服务器配置
+/// - `users`: 用户记录的存储
+/// - `sessions`: 挑战和会话的存储
+///
+/// 返回:
+/// - `impl Future`: 服务器运行的 future，需要 await 才会开始监听
+pub fn run_server_with_stores(config: ServerConfig, users: Arc<dyn UserStore>, sessions: Arc<dyn SessionStore>) -> impl Future<Output = Result<(), ServeError>> {
+    serve_auth(Arc::new(AuthImpl::with_stores(config, users, sessions))) // 各版本的服务共享处理逻辑和存储
+}
+
+/// 与 `run_server` 相同，使用调用者创建的 `AuthImpl`；调用者保留一个引用，运行中用 `AuthImpl::reload` 替换有效期、配额和 TLS 证书
+///
+/// 参数:
+/// - `auth`: 服务的实现，监听地址和其他设置取自创建它的 `ServerConfig`
+///
+/// 返回:
+/// - `Result<(), ServeError>`: 服务器停止的原因
+pub async fn serve_auth(auth: Arc<AuthImpl>) -> Result<(), ServeError> {
+    let addr = auth.config.addr;
+    let cleanup = spawn_cleanup(auth.clone()); // 定期删除过期的挑战和会话，服务器退出时停止
+    let metrics = auth.config.metrics_addr.map(|metrics_addr| {
+        let auth = auth.clone();
+        tokio::spawn(async move {
+            if let Err(err) = serve_metrics(auth, metrics_addr).await {
+                error!(addr = %metrics_addr, error = %err, "metrics endpoint stopped");
+            }
+        })
+    });
+    let gateway = auth.config.gateway_addr.map(|gateway_addr| {
+        let auth = auth.clone();
+        tokio::spawn(async move {
+            if let Err(err) = serve_gateway(auth, gateway_addr).await {
+                error!(addr = %gateway_addr, error = %err, "REST gateway stopped");
+            }
+        })
+    });
+    // 连接和请求的限制：每个连接上的流数由 HTTP/2 通告，超时和所有连接上的并发 RPC 由 tower 层执行，消息大小由各个服务检查
+    let limits = &auth.config.connection_limits;
+    let size = limits.max_message_bytes;
+    let mut server = Server::builder().max_concurrent_streams(limits.max_concurrent_streams);
+    if let Some(timeout) = limits.request_timeout {
+        server = server.timeout(timeout);
+    }
+    let (reporter, health) = tonic_health::server::health_reporter();
+    let health_task = tokio::spawn(auth.report_health(reporter)); // 排空时报告 NOT_SERVING
+    let admin = auth.config.admin.clone().map(|policy| {
+        let admin = AuthAdminServer::new(AuthAdminImpl::new(auth.clone())).max_decoding_message_size(size).max_encoding_message_size(size);
+        InterceptedService::new(admin, AdminAuth::new(policy))
+    });
+    let router = server
+        .layer(PeerFilterLayer::new(auth.config.peers.clone())) // 确定客户端地址，拒绝不接受的地址；在并发限制之前，被拒绝的请求不占用配额
+        .layer(option_layer(limits.max_concurrent_requests.map(GlobalConcurrencyLimitLayer::new)))
+        .add_service(Correlated(AuthServer::from_arc(auth.clone()).max_decoding_message_size(size).max_encoding_message_size(size))) // 将 Auth 服务添加到 gRPC 服务器中，每个 RPC 带有关联 ID
+        .add_service(Correlated(V1(AuthServer::from_arc(auth.clone()).max_decoding_message_size(size).max_encoding_message_size(size)))) // 同一个服务以版本化的名称提供
+        .add_service(Correlated(AuthV2Server::new(AuthV2Impl::new(auth.clone())).max_decoding_message_size(size).max_encoding_message_size(size))) // 第二版接口
+        .add_service(health) // 负载均衡器和编排系统的健康检查
+        .add_optional_service(admin.map(Correlated)); // 配置了管理令牌或客户端身份时提供管理接口
+    let result: Result<(), ServeError> = async {
+        // 自己接受连接以限制连接数；使用 TLS 时再握手，reload 替换证书后新的连接使用新证书，配置了客户端 CA 时要求客户端证书
+        let connections = limits::accept(tokio::net::TcpListener::bind(addr).await?, limits.max_connections);
+        #[cfg(feature = "tls")]
+        if let Some(config) = &auth.config.tls {
+            let tls = Arc::new(tls::ReloadableTls::new(config)?);
+            let _ = auth.tls.set(tls.clone());
+            return Ok(router.serve_with_incoming(tls::incoming(connections, tls)).await?);
+        }
+        Ok(router.serve_with_incoming(tokio_stream::StreamExt::map(connections, Ok::<_, std::io::Error>)).await?) // 开始处理接受的连接
+    }
+    .await;
+    for task in cleanup.into_iter().chain(metrics).chain(gateway).chain(Some(health_task)) {
+        task.abort();
+    }
+    result
+}
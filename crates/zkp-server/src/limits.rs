@@ -0,0 +1,112 @@
+//! 连接和请求的限制：同时打开的连接数、每个连接上同时进行的 RPC、所有连接上同时处理的 RPC、每个 RPC 的超时和消息大小，
+//! 一个行为异常的客户端不能耗尽进程的文件描述符、内存或任务
+//!
+//! 连接数在接受连接时限制，达到上限后暂停接受，新的连接留在内核的 accept 队列中，直到有连接关闭；
+//! 同时处理的 RPC 和超时由 `run_server` 中 tonic 服务器的 tower 层执行，消息大小由各个生成的服务检查
+
+use std::io; // 接受连接出错
+use std::pin::Pin; // 转发连接的读写
+use std::sync::Arc; // 连接共享的信号量
+use std::task::{Context, Poll}; // 转发连接的读写
+use std::time::Duration; // RPC 的超时
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf}; // 连接的读写
+use tokio::net::{TcpListener, TcpStream}; // 接受 TCP 连接
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore}; // 接受的连接交给 tonic，连接数的上限
+use tokio_stream::wrappers::ReceiverStream; // 连接流
+use tonic::transport::server::{Connected, TcpConnectInfo}; // 请求中的客户端地址
+use tracing::warn; // 接受连接出错
+
+// 默认的请求和响应消息的最大字节数，与 tonic 解码的默认值相同
+const DEFAULT_MAX_MESSAGE_BYTES: usize = 4 * 1024 * 1024;
+
+// 已经接受、等待交给 tonic 或 TLS 握手的连接数
+const ACCEPT_QUEUE: usize = 16;
+
+// 接受连接出错（例如文件描述符用尽）后再次尝试前等待的时间
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(100);
+
+/// 连接和请求的限制，`ServerConfig::connection_limits`；默认只限制消息大小
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionLimits {
+    pub max_connections: Option<usize>,         // 同时打开的连接数，达到上限后暂停接受新连接
+    pub max_concurrent_streams: Option<u32>,    // 每个连接上同时进行的 RPC（HTTP/2 SETTINGS_MAX_CONCURRENT_STREAMS）
+    pub max_concurrent_requests: Option<usize>, // 所有连接上同时处理的 RPC，超过时新的 RPC 排队等待
+    pub request_timeout: Option<Duration>,      // 每个 RPC 的超时，超时返回 Cancelled；客户端的 grpc-timeout 更短时以它为准
+    pub max_message_bytes: usize,               // 请求和响应消息的最大字节数，超过时返回 OutOfRange
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        ConnectionLimits { max_connections: None, max_concurrent_streams: None, max_concurrent_requests: None, request_timeout: None, max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES }
+    }
+}
+
+/// 接受的 TCP 连接，关闭时归还连接数的配额
+pub(crate) struct Connection {
+    stream: TcpStream,
+    _permit: Option<OwnedSemaphorePermit>, // 没有连接数上限时为 None
+}
+
+impl AsyncRead for Connection {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Connection {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(mut self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[io::IoSlice<'_>]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+// 与 TcpStream 相同，处理函数照常用 remote_addr 取得客户端地址
+impl Connected for Connection {
+    type ConnectInfo = TcpConnectInfo;
+
+    fn connect_info(&self) -> TcpConnectInfo {
+        self.stream.connect_info()
+    }
+}
+
+// 在 listener 上接受连接，同时打开的连接达到 `max_connections` 时等待有连接关闭再接受；返回的流被丢弃后停止接受连接
+pub(crate) fn accept(listener: TcpListener, max_connections: Option<usize>) -> ReceiverStream<Connection> {
+    let semaphore = max_connections.map(|max| Arc::new(Semaphore::new(max)));
+    let (sender, receiver) = mpsc::channel(ACCEPT_QUEUE);
+    tokio::spawn(async move {
+        while !sender.is_closed() {
+            let permit = match &semaphore {
+                Some(semaphore) => Some(semaphore.clone().acquire_owned().await.expect("the semaphore is never closed")),
+                None => None,
+            };
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    warn!(error = %err, "could not accept a connection");
+                    tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                    continue;
+                }
+            };
+            if sender.send(Connection { stream, _permit: permit }).await.is_err() {
+                break;
+            }
+        }
+    });
+    ReceiverStream::new(receiver)
+}
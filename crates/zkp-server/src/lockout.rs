@@ -0,0 +1,120 @@
+//! 账户锁定：按用户名统计连续验证失败的次数，达到阈值后暂时拒绝该用户的所有验证，锁定结束后每再失败一次锁定时间加倍，直到上限。
+//! 验证成功或运维通过管理接口解锁（`AuthImpl::unlock_user`）时清零
+//!
+//! 限流限制的是请求速率，锁定限制的是对同一个账户猜测密码的次数：攻击者换用多个客户端地址时，低熵密码也需要很长时间才能在线猜中。
+//! 锁定期间返回带 `retry-after` 的 Unavailable；计数与限流的令牌桶一样只保存在本进程的内存中，多个副本各自计数
+
+use std::fmt; // 调试输出不包含用户名
+use std::time::{Duration, Instant}; // 锁定的起止时间
+
+use tonic::{Code, Status}; // 锁定错误
+use tracing::warn; // 锁定时记录日志
+use zkp_proto::detail::with_error_code; // 错误的结构化详情
+use zkp_proto::retry::with_retry_after; // 告诉客户端锁定何时结束
+use zkp_proto::zkp_auth::ErrorCode; // 详情中的错误分类
+
+use crate::shard::ShardedMap; // 失败计数在请求之间共享，键为用户名
+
+/// 锁定策略：连续失败 `threshold` 次后锁定 `base`，之后每次失败锁定时间加倍，不超过 `max`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockoutPolicy {
+    pub threshold: u32, // 开始锁定的连续失败次数
+    pub base: Duration, // 第一次锁定的时间
+    pub max: Duration,  // 锁定时间的上限，也是失败计数的保留时间：这段时间内没有再失败时计数清零
+}
+
+impl Default for LockoutPolicy {
+    fn default() -> Self {
+        LockoutPolicy { threshold: 5, base: Duration::from_secs(30), max: Duration::from_secs(60 * 60) }
+    }
+}
+
+impl LockoutPolicy {
+    // 第 failures 次连续失败之后的锁定时间，未达到阈值时为 None
+    fn lock_for(&self, failures: u32) -> Option<Duration> {
+        let doublings = failures.checked_sub(self.threshold)?;
+        let factor = 1u32.checked_shl(doublings).unwrap_or(u32::MAX);
+        Some(self.base.saturating_mul(factor).min(self.max))
+    }
+}
+
+// 一个用户的连续失败
+struct Failures {
+    count: u32,                   // 连续失败的次数
+    last_failure: Instant,        // 最近一次失败的时间
+    locked_until: Option<Instant>, // 锁定结束的时间
+}
+
+// 所有用户的失败计数
+pub(crate) struct Lockout {
+    policy: Option<LockoutPolicy>,
+    failures: ShardedMap<String, Failures>,
+}
+
+// 映射表的键是用户名，调试输出只包含策略和被计数的用户数
+impl fmt::Debug for Lockout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lockout").field("policy", &self.policy).field("users", &self.failures.len()).finish()
+    }
+}
+
+impl Lockout {
+    pub(crate) fn new(policy: Option<LockoutPolicy>) -> Self {
+        Lockout { policy, failures: ShardedMap::default() }
+    }
+
+    // 用户被锁定时返回带 retry-after 的 Unavailable，在验证解答之前调用
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn check(&self, user: &str) -> Result<(), Status> {
+        let now = Instant::now();
+        match self.failures.read(user).get(user).and_then(|failures| failures.locked_until) {
+            Some(until) if until > now => Err(with_error_code(with_retry_after(Code::Unavailable, "account is temporarily locked after too many failed attempts, retry later", until - now), ErrorCode::AccountLocked)),
+            _ => Ok(()),
+        }
+    }
+
+    // 记录一次验证的结果：成功时清零，失败时计数并在达到阈值后锁定
+    pub(crate) fn record(&self, user: &str, passed: bool) {
+        let Some(policy) = self.policy else {
+            return;
+        };
+        let mut failures = self.failures.write(user);
+        if passed {
+            failures.remove(user);
+            return;
+        }
+        let now = Instant::now();
+        let entry = failures.entry(user.to_string()).or_insert(Failures { count: 0, last_failure: now, locked_until: None });
+        if now.duration_since(entry.last_failure) >= policy.max {
+            entry.count = 0; // 上一次失败太久以前，清理还没有删除这条计数
+        }
+        entry.count = entry.count.saturating_add(1);
+        entry.last_failure = now;
+        if let Some(lock) = policy.lock_for(entry.count) {
+            entry.locked_until = Some(now + lock);
+            warn!(user, failures = entry.count, lock_secs = lock.as_secs(), "locked account after failed verifications");
+        }
+    }
+
+    // 用户被锁定时返回锁定的剩余时间
+    pub(crate) fn locked_for(&self, user: &str) -> Option<Duration> {
+        let now = Instant::now();
+        let until = self.failures.read(user).get(user)?.locked_until?;
+        (until > now).then(|| until - now)
+    }
+
+    // 清零用户的失败计数并解除锁定，返回用户此前是否被锁定
+    pub(crate) fn unlock(&self, user: &str) -> bool {
+        let now = Instant::now();
+        self.failures.write(user).remove(user).and_then(|failures| failures.locked_until).is_some_and(|until| until > now)
+    }
+
+    // 删除 max 时间内没有再失败的计数，锁定此时已经结束；由后台清理调用，限制被随机用户名撑大的映射表
+    pub(crate) fn purge(&self) {
+        let Some(policy) = self.policy else {
+            return;
+        };
+        let now = Instant::now();
+        self.failures.retain(|_, failures| now.duration_since(failures.last_failure) < policy.max);
+    }
+}
@@ -0,0 +1,295 @@
+use std::fs::File; // export-users 的输出文件
+use std::io::{BufWriter, Write}; // 逐行写入输出文件
+use std::path::Path; // gen-params 的输出文件
+use std::sync::Arc; // 共享的存储
+
+use tracing::{error, info, warn}; // 启动信息，重新加载配置的结果
+
+use zkp_core::{GroupParams, ZKP}; // 导入的用户所属的群，生成新的群参数
+use zkp_server::export::{export_users, import_users}; // 导出和导入用户
+use zkp_server::settings::{Command, LogFilterHandle, Settings, UserCommand, AUDIT_LOG_IN_STORE}; // 命令行、环境变量和配置文件中的设置
+use zkp_server::{serve_auth, AuditLog, AuthImpl, EncryptedStore, JwtVerifyingKey, MemoryStore, ServerConfig, SessionStore, UserStore}; // 认证服务及其存储
+
+// user list 每次从存储读取的用户数
+const LIST_PAGE_SIZE: u32 = 100;
+
+// 主函数，运行 gRPC 服务器
+#[tokio::main] // 使用 tokio 运行时来处理异步任务
+async fn main() {
+    // 设置的优先级：命令行参数、ZKP_* 环境变量、--config 指定的 TOML 文件、默认值；见 `server --help`
+    let settings = Settings::load().unwrap_or_else(|err| exit(&err));
+    let logging = settings.init_logging().unwrap_or_else(|err| exit(&err));
+    let mut config = settings.server_config().unwrap_or_else(|err| exit(&err));
+    // gen-params 不需要存储
+    if let Some(Command::GenParams { p_bits, q_bits, output }) = &settings.command {
+        gen_params(*p_bits, *q_bits, output.as_deref()).unwrap_or_else(|err| exit(&err));
+        return;
+    }
+    let serve = matches!(settings.command, None | Some(Command::Serve));
+    if config.params.p != zkp_core::GroupParams::rfc5114_1024().p {
+        info!(params_hash = %hex::encode(config.params.params_hash()), "using custom group parameters");
+    }
+    for params in &config.extra_params {
+        info!(params_hash = %hex::encode(params.params_hash()), "also accepting registrations in this group");
+    }
+    if let Some(jwt) = &config.jwt {
+        if let JwtVerifyingKey::EdDsa(public) = jwt.key.verifying_key() {
+            info!(verifying_key = %hex::encode(public.as_bytes()), "issuing EdDSA JWTs");
+        }
+    }
+    #[cfg(feature = "tls")]
+    if let Some(tls) = &config.tls {
+        info!(client_certificates = tls.client_ca_pem.is_some(), "serving over TLS");
+    }
+    if serve {
+        info!(addr = %config.addr, "running the server"); // 记录服务器运行地址，方便调试
+    }
+
+    // 开启 seeded-rng feature 时，可以通过 ZKP_SEED 环境变量固定服务器随机数来源的种子，使协议记录可以复现
+    #[cfg(feature = "seeded-rng")]
+    if let Some(seed) = std::env::var("ZKP_SEED").ok().and_then(|seed| seed.parse().ok()) {
+        config.rng = zkp_core::RandomSource::seeded(seed);
+        tracing::warn!(seed, "ZKP_SEED: random values are deterministic, do not use outside of testing");
+    }
+
+    // 存储由 store 选择：memory（默认，重启后丢失）、sqlite:<数据库文件>（sqlite feature）
+    // 或 postgres://...（postgres feature，连接池大小由 db_max_connections 设置）
+    // audit_log = store 时审计日志写入同一个数据库的 audit_log 表
+    let audit_in_store = settings.audit_log.as_deref().map(str::trim) == Some(AUDIT_LOG_IN_STORE);
+    let (users, sessions, audit_log) = match settings.store.as_deref().unwrap_or("memory") {
+        "memory" => shared(MemoryStore::default()),
+        #[cfg(feature = "sqlite")]
+        store if store.starts_with("sqlite:") => {
+            let path = &store["sqlite:".len()..];
+            let store = zkp_server::SqliteStore::open(path).unwrap_or_else(|err| exit(&format!("could not open {}: {}", path, err)));
+            info!(path, "storing users and sessions in SQLite");
+            with_audit_log(store)
+        }
+        #[cfg(feature = "postgres")]
+        url if url.starts_with("postgres://") || url.starts_with("postgresql://") => {
+            let mut options = zkp_server::PostgresOptions::default();
+            if let Some(max_connections) = settings.db_max_connections {
+                options.max_connections = max_connections;
+            }
+            let store = zkp_server::PostgresStore::connect(url, &options).await.unwrap_or_else(|err| exit(&format!("could not connect to PostgreSQL: {}", err)));
+            info!(max_connections = options.max_connections, "storing users and sessions in PostgreSQL");
+            with_audit_log(store)
+        }
+        store => exit(&format!("unknown store {:?}, expected memory, sqlite:<path> (sqlite feature) or postgres://... (postgres feature)", store)),
+    };
+    if !serve && settings.store.as_deref().unwrap_or("memory") == "memory" {
+        exit("migrate, user, export-users, import-users and rotate-keys need a sqlite or postgres store");
+    }
+    if audit_in_store {
+        config.audit_log = Some(audit_log.unwrap_or_else(|| exit("audit_log = store requires a sqlite or postgres store")));
+        info!("writing the audit log to the store");
+    } else if let Some(path) = settings.audit_log.as_deref().filter(|_| config.audit_log.is_some()) {
+        info!(path, "writing the audit log to a file");
+    }
+    // 设置 session_store = redis://... 时挑战和会话改为保存在 Redis 中（redis feature），键的前缀由 redis_prefix 设置
+    let sessions: Arc<dyn SessionStore> = match settings.session_store.as_deref() {
+        #[cfg(feature = "redis")]
+        Some(url) => {
+            let prefix = settings.redis_prefix.clone().unwrap_or_else(|| "zkp:".to_string());
+            let store = zkp_server::RedisStore::connect(url, &prefix).await.unwrap_or_else(|err| exit(&format!("could not connect to Redis: {}", err)));
+            info!(prefix = %prefix, "storing challenges and sessions in Redis");
+            Arc::new(store)
+        }
+        #[cfg(not(feature = "redis"))]
+        Some(_) => exit("session_store requires the redis feature"),
+        None => sessions,
+    };
+    // 设置了 encryption_key_file 或 encryption_key_command 时，写入存储的用户凭据和会话 ID 用第一个密钥加密
+    let (users, sessions): (Arc<dyn UserStore>, Arc<dyn SessionStore>) = match settings.keyring().unwrap_or_else(|err| exit(&err)) {
+        Some(keys) => {
+            info!(key_ids = ?keys.key_ids(), "encrypting user records and session ids at rest");
+            let keys = Arc::new(keys);
+            (Arc::new(EncryptedStore::new(users, keys.clone())), Arc::new(EncryptedStore::new(sessions, keys)))
+        }
+        None if matches!(settings.command, Some(Command::RotateKeys)) => exit("rotate-keys needs encryption_key_file or encryption_key_command"),
+        None => (users, sessions),
+    };
+    // 其他子命令使用同样的存储（包括 Redis 中的会话）和审计日志，完成后退出，不启动服务器
+    if let Some(command) = settings.command.as_ref().filter(|_| !serve) {
+        run_command(command, config, users, sessions).await.unwrap_or_else(|err| exit(&err));
+        flush_traces();
+        return;
+    }
+
+    // 构建并启动 gRPC 服务器，收到 SIGHUP 时重新加载配置，收到 SIGUSR1 / SIGUSR2 时进入 / 退出排空模式
+    let auth = Arc::new(AuthImpl::with_stores(config, users, sessions));
+    spawn_reload(auth.clone(), logging);
+    spawn_drain(auth.clone());
+    serve_auth(auth).await.unwrap_or_else(|err| exit(&err.to_string()));
+}
+
+// 收到 SIGHUP 时重新读取设置（命令行参数、环境变量和配置文件），替换有效期、配额、日志过滤和 TLS 证书，不断开已有的连接；
+// 设置无效时记录错误并保留原来的设置，其他设置需要重启才能生效
+#[cfg(unix)]
+fn spawn_reload(auth: Arc<AuthImpl>, logging: LogFilterHandle) {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(err) => {
+            warn!(error = %err, "could not listen for SIGHUP, configuration reload is disabled");
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            if let Err(err) = reload(&auth, &logging) {
+                error!(error = %err, "could not reload the configuration, keeping the previous settings");
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+fn spawn_reload(_auth: Arc<AuthImpl>, _logging: LogFilterHandle) {}
+
+// 收到 SIGUSR1 时进入排空模式，收到 SIGUSR2 时恢复正常服务；与管理接口的 SetDrainMode 相同
+#[cfg(unix)]
+fn spawn_drain(auth: Arc<AuthImpl>) {
+    use tokio::signal::unix::{signal, SignalKind};
+    let (mut enter, mut leave) = match (signal(SignalKind::user_defined1()), signal(SignalKind::user_defined2())) {
+        (Ok(enter), Ok(leave)) => (enter, leave),
+        (Err(err), _) | (_, Err(err)) => {
+            warn!(error = %err, "could not listen for SIGUSR1 and SIGUSR2, drain mode is only available through the admin service");
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        loop {
+            let draining = tokio::select! {
+                Some(()) = enter.recv() => true,
+                Some(()) = leave.recv() => false,
+                else => return,
+            };
+            auth.set_draining(draining);
+        }
+    });
+}
+
+#[cfg(not(unix))]
+fn spawn_drain(_auth: Arc<AuthImpl>) {}
+
+// 重新读取设置并应用到运行中的服务器，先检查全部设置，任何一项无效时不修改
+#[cfg(unix)]
+fn reload(auth: &AuthImpl, logging: &LogFilterHandle) -> Result<(), String> {
+    let settings = Settings::load()?;
+    let filter = settings.log_filter()?;
+    auth.reload(&settings.server_config()?)?;
+    logging.set(filter)
+}
+
+// 执行 serve 和 gen-params 以外的子命令
+async fn run_command(command: &Command, config: ServerConfig, users: Arc<dyn UserStore>, sessions: Arc<dyn SessionStore>) -> Result<(), String> {
+    match command {
+        Command::Serve | Command::GenParams { .. } => unreachable!("handled in main"),
+        Command::Migrate => info!("the database schema is up to date"), // 打开存储时已经执行了迁移
+        Command::User(UserCommand::List { after, limit }) => {
+            let mut out = std::io::stdout().lock();
+            let (mut after, mut listed) = (after.clone(), 0u32);
+            loop {
+                let page = match limit {
+                    0 => LIST_PAGE_SIZE,
+                    limit => LIST_PAGE_SIZE.min(limit - listed),
+                };
+                let batch = users.list_users(&after, page).await.map_err(|e| e.to_string())?;
+                for (name, info) in &batch {
+                    writeln!(out, "{}\t{}\t{}", name, info.created_at, info.display_name).map_err(|e| e.to_string())?;
+                }
+                listed += batch.len() as u32;
+                match batch.last() {
+                    Some((name, _)) if batch.len() as u32 == page && listed != *limit => after = name.clone(),
+                    _ => break,
+                }
+            }
+        }
+        Command::User(UserCommand::Delete { user }) => {
+            let user = config.user_names.normalize(user); // 与 RPC 一样按规范化后的用户名查找
+            let auth = AuthImpl::with_stores(config, users, sessions); // 与 DeleteUserData 相同，同时删除挑战并吊销会话
+            let revoked_sessions = auth.delete_user(&user).await.map_err(|status| status.message().to_string())?.ok_or_else(|| format!("user {:?} not found", user))?;
+            info!(user = %user, revoked_sessions, "deleted user");
+        }
+        Command::ExportUsers { format, include_totp_secrets, output } => {
+            let count = match output {
+                Some(path) => {
+                    let file = File::create(path).map_err(|e| format!("could not create {}: {}", path.display(), e))?;
+                    export_users(users.as_ref(), *format, *include_totp_secrets, &mut BufWriter::new(file)).await?
+                }
+                None => export_users(users.as_ref(), *format, *include_totp_secrets, &mut std::io::stdout().lock()).await?,
+            };
+            info!(count, "exported users");
+        }
+        Command::ImportUsers { input, format, replace } => {
+            let text = std::fs::read_to_string(input).map_err(|e| format!("could not read {}: {}", input.display(), e))?;
+            let groups: Vec<&GroupParams> = std::iter::once(config.params).chain(config.extra_params.iter().copied()).collect(); // 导入的用户必须属于服务器接受的群
+            let summary = import_users(users.as_ref(), &groups, *format, &text, *replace).await?;
+            info!(created = summary.created, replaced = summary.replaced, skipped = summary.skipped, "imported users");
+        }
+        Command::RotateKeys => {
+            // 逐页读取用户，每个用户在 update_user 中解密后用第一个密钥重新加密写回；用户名的顺序不受写回影响
+            let (mut after, mut rotated) = (String::new(), 0u64);
+            loop {
+                let batch = users.list_users(&after, LIST_PAGE_SIZE).await.map_err(|e| e.to_string())?;
+                for (name, _) in &batch {
+                    if users.update_user(name, Box::new(|_| true)).await.map_err(|e| e.to_string())? {
+                        rotated += 1;
+                    }
+                }
+                match batch.last() {
+                    Some((name, _)) if batch.len() as u32 == LIST_PAGE_SIZE => after = name.clone(),
+                    _ => break,
+                }
+            }
+            info!(rotated, "re-encrypted users with the current key");
+        }
+    }
+    Ok(())
+}
+
+// 生成群参数并以 PEM 格式写入文件或标准输出
+fn gen_params(p_bits: u64, q_bits: u64, output: Option<&Path>) -> Result<(), String> {
+    if q_bits < 2 || q_bits >= p_bits {
+        return Err(format!("--q-bits ({}) must be at least 2 and smaller than --p-bits ({})", q_bits, p_bits));
+    }
+    info!(p_bits, q_bits, "generating group parameters, this may take a while");
+    let zkp = ZKP::generate_params(p_bits, q_bits);
+    zkp.validate_params()?;
+    match output {
+        Some(path) => std::fs::write(path, zkp.to_pem()).map_err(|e| format!("could not write {}: {}", path.display(), e))?,
+        None => print!("{}", zkp.to_pem()),
+    }
+    info!(params_hash = %hex::encode(zkp.params_hash()), "generated group parameters");
+    Ok(())
+}
+
+// 用户记录、挑战和会话的存储，以及可选的审计日志
+type Stores = (Arc<dyn UserStore>, Arc<dyn SessionStore>, Option<Arc<dyn AuditLog>>);
+
+// 同一个存储同时保存用户记录、挑战和会话，不能保存审计日志
+fn shared<S: UserStore + SessionStore + 'static>(store: S) -> Stores {
+    let store = Arc::new(store);
+    (store.clone(), store, None)
+}
+
+// 同一个存储同时保存用户记录、挑战、会话和审计日志
+#[cfg(any(feature = "sqlite", feature = "postgres"))]
+fn with_audit_log<S: UserStore + SessionStore + AuditLog + 'static>(store: S) -> Stores {
+    let store = Arc::new(store);
+    (store.clone(), store.clone(), Some(store))
+}
+
+// 设置无效时输出错误并退出，不输出 panic 的调用栈
+fn exit(message: &str) -> ! {
+    eprintln!("Error: {}", message);
+    flush_traces();
+    std::process::exit(2);
+}
+
+// 退出前导出尚未发送的 span（otel feature）
+fn flush_traces() {
+    #[cfg(feature = "otel")]
+    opentelemetry::global::shutdown_tracer_provider();
+}
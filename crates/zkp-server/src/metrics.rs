@@ -0,0 +1,107 @@
+//! Prometheus 指标：注册、发出的挑战、通过和失败的验证、验证耗时和有效会话数，
+//! 由 `serve_metrics` 在与 gRPC 不同的地址上以 HTTP `GET /metrics` 提供（Prometheus 文本格式）
+//!
+//! 计数器只统计本进程，多个副本由 Prometheus 按实例汇总；有效会话数在抓取时从会话存储读取，共享存储的副本报告相同的值
+
+use std::convert::Infallible; // HTTP 服务不会失败
+use std::fmt::Write; // 生成指标文本
+use std::net::SocketAddr; // 指标的监听地址
+use std::sync::atomic::{AtomicU64, Ordering}; // 计数器在请求之间共享
+use std::sync::Arc; // 与 gRPC 服务共享的 AuthImpl
+use std::time::Duration; // 验证耗时
+
+use hyper::service::{make_service_fn, service_fn}; // 指标的 HTTP 服务
+use hyper::{header, Body, Method, Response, StatusCode}; // HTTP 请求和响应
+
+use crate::AuthImpl; // 指标和会话存储
+
+// 验证耗时直方图的桶上限（秒），模幂运算通常在毫秒级
+const LATENCY_BUCKETS: [f64; 10] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+/// 认证服务的计数器和直方图
+#[derive(Debug, Default)]
+pub struct Metrics {
+    registrations: AtomicU64,          // 成功的注册
+    challenges: AtomicU64,             // 发出的挑战
+    verifications_passed: AtomicU64,   // 通过的验证
+    verifications_failed: AtomicU64,   // 解答错误的验证
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS.len()], // 每个桶的观测次数（不累加），超过最大上限的只计入 count
+    latency_count: AtomicU64,          // 验证次数
+    latency_sum_micros: AtomicU64,     // 验证总耗时（微秒）
+}
+
+impl Metrics {
+    pub(crate) fn registered(&self) {
+        self.registrations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn challenge_issued(&self) {
+        self.challenges.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // 一次验证的结果和耗时
+    pub(crate) fn verified(&self, passed: bool, elapsed: Duration) {
+        let counter = if passed { &self.verifications_passed } else { &self.verifications_failed };
+        counter.fetch_add(1, Ordering::Relaxed);
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|&le| elapsed.as_secs_f64() <= le) {
+            self.latency_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.latency_count.fetch_add(1, Ordering::Relaxed);
+        self.latency_sum_micros.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// 以 Prometheus 文本格式输出指标
+    ///
+    /// 参数:
+    /// - `active_sessions`: 未过期的会话数，读取失败时为 None，不输出该指标
+    pub fn render(&self, active_sessions: Option<u64>) -> String {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP zkp_registrations_total Successful registrations.\n# TYPE zkp_registrations_total counter");
+        let _ = writeln!(out, "zkp_registrations_total {}", load(&self.registrations));
+        let _ = writeln!(out, "# HELP zkp_challenges_issued_total Authentication challenges issued.\n# TYPE zkp_challenges_issued_total counter");
+        let _ = writeln!(out, "zkp_challenges_issued_total {}", load(&self.challenges));
+        let _ = writeln!(out, "# HELP zkp_verifications_total Answers to challenges checked, by result.\n# TYPE zkp_verifications_total counter");
+        let _ = writeln!(out, "zkp_verifications_total{{result=\"passed\"}} {}", load(&self.verifications_passed));
+        let _ = writeln!(out, "zkp_verifications_total{{result=\"failed\"}} {}", load(&self.verifications_failed));
+        let _ = writeln!(out, "# HELP zkp_verification_duration_seconds Time spent checking an answer.\n# TYPE zkp_verification_duration_seconds histogram");
+        let mut cumulative = 0;
+        for (le, bucket) in LATENCY_BUCKETS.iter().zip(&self.latency_buckets) {
+            cumulative += load(bucket);
+            let _ = writeln!(out, "zkp_verification_duration_seconds_bucket{{le=\"{}\"}} {}", le, cumulative);
+        }
+        let count = load(&self.latency_count);
+        let _ = writeln!(out, "zkp_verification_duration_seconds_bucket{{le=\"+Inf\"}} {}", count);
+        let _ = writeln!(out, "zkp_verification_duration_seconds_sum {}", load(&self.latency_sum_micros) as f64 / 1e6);
+        let _ = writeln!(out, "zkp_verification_duration_seconds_count {}", count);
+        if let Some(active) = active_sessions {
+            let _ = writeln!(out, "# HELP zkp_active_sessions Sessions that have not expired.\n# TYPE zkp_active_sessions gauge");
+            let _ = writeln!(out, "zkp_active_sessions {}", active);
+        }
+        out
+    }
+}
+
+/// 在 `addr` 上以 HTTP 提供 `GET /metrics`，直到出错；`run_server` 在配置了 `metrics_addr` 时自动启动
+///
+/// 参数:
+/// - `auth`: 与 gRPC 服务共享的 `AuthImpl`
+/// - `addr`: 指标的监听地址，应当只对监控系统开放
+pub async fn serve_metrics(auth: Arc<AuthImpl>, addr: SocketAddr) -> hyper::Result<()> {
+    let make_service = make_service_fn(move |_| {
+        let auth = auth.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: hyper::Request<Body>| {
+                let auth = auth.clone();
+                async move {
+                    if request.method() != Method::GET || request.uri().path() != "/metrics" {
+                        return Ok::<_, Infallible>(Response::builder().status(StatusCode::NOT_FOUND).body(Body::empty()).unwrap());
+                    }
+                    let body = auth.render_metrics().await;
+                    Ok(Response::builder().header(header::CONTENT_TYPE, "text/plain; version=0.0.4").body(Body::from(body)).unwrap())
+                }
+            }))
+        }
+    });
+    hyper::Server::try_bind(&addr)?.serve(make_service).await
+}
@@ -0,0 +1,27 @@
+//! 持久化存储（SQLite、PostgreSQL）共用的版本化迁移
+//!
+//! 每个后端有一个 `MIGRATIONS` 数组，第 n 个迁移把数据库结构从版本 n - 1 升级到 n；数据库中记录已经执行到的版本
+//! （SQLite 的 `PRAGMA user_version`，PostgreSQL 的 `zkp_schema` 表）。打开存储时在一个事务中执行之后的迁移，
+//! 用户记录增加字段（盐、锁定计数、权限范围等）时在数组末尾追加一个迁移，已有的数据随之升级，不修改已有的迁移。
+//! 服务器启动时自动迁移；`server migrate`（或 `--migrate-only`）只执行迁移后退出，部署时可以在启动新版本之前单独执行
+
+use std::ops::Range; // 尚未执行的迁移
+
+use tracing::info; // 执行了的迁移
+
+use crate::store::{StoreError, StoreResult}; // 与存储后端相同的错误
+
+// 数据库当前为 version 时尚未执行的迁移在 migrations 中的下标；比代码更新的数据库返回错误，旧版本的服务器不读写它不认识的结构
+pub(crate) fn pending(backend: &str, version: usize, migrations: &[&str]) -> StoreResult<Range<usize>> {
+    if version > migrations.len() {
+        return Err(StoreError(format!("{} database schema version {} is newer than this server ({})", backend, version, migrations.len())));
+    }
+    Ok(version..migrations.len())
+}
+
+// 事务提交后记录执行了的迁移，没有需要执行的迁移时不输出
+pub(crate) fn applied(backend: &str, migrated: Range<usize>) {
+    if !migrated.is_empty() {
+        info!(backend, from = migrated.start, to = migrated.end, "migrated the database schema");
+    }
+}
@@ -0,0 +1,136 @@
+//! OpenTelemetry（otel feature）：服务器的 span 以 OTLP 导出，一次登录在客户端、网关、服务器和存储中的 span 属于同一个 trace
+//!
+//! `Correlated` 的 `rpc` span 和网关的 `http` span 以请求中的 W3C `traceparent` 为父 span，gRPC 状态码不是 OK
+//! 或 HTTP 状态码不是 2xx 时标记为错误；`AuthImpl` 对存储的每次调用是它们下面的一个 `store` span。
+//! 服务器用 `--otlp-endpoint` 打开导出，见 `Settings::init_logging`
+
+use std::fmt; // 调试输出与被包装的存储相同
+use std::sync::Arc; // 被包装的存储
+
+use opentelemetry::sdk::trace::Tracer; // 导出 span 的 tracer
+use tonic::codegen::http::HeaderMap; // 请求头中的 trace 上下文
+use tracing::{info_span, Instrument, Span}; // 存储调用的 span
+use tracing::subscriber::Subscriber; // 日志输出的订阅者
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt}; // 把 tracing 的 span 转换为 OpenTelemetry 的 span
+use tracing_subscriber::registry::LookupSpan; // 订阅者保存 span 的数据
+
+use zkp_proto::otel::{extract, otlp_tracer}; // trace 上下文的传递和 OTLP 导出
+
+use crate::store::{PendingChallenge, Purged, SessionInfo, SessionStore, StoreResult, UserInfo, UserStore, UserUpdate}; // 被包装的存储
+
+// span 的 service.name 资源属性
+const SERVICE_NAME: &str = "zkp-server";
+
+/// 把 span 以 OTLP/gRPC 导出到 `endpoint` 的日志层，必须在 tokio 运行时中调用
+pub fn layer<S>(endpoint: &str) -> Result<OpenTelemetryLayer<S, Tracer>, String>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    Ok(tracing_opentelemetry::layer().with_tracer(otlp_tracer(endpoint, SERVICE_NAME)?))
+}
+
+// 设置服务端 span 的名称和类型，以请求头中的 trace 上下文为父 span；span 需要声明 otel.name、otel.kind 和 otel.status_code 字段
+pub(crate) fn start_span(span: &Span, name: &str, headers: &HeaderMap) {
+    span.record("otel.name", name);
+    span.record("otel.kind", "server");
+    span.set_parent(extract(headers));
+}
+
+// 请求失败时把 span 标记为错误
+pub(crate) fn finish_span(span: &Span, failed: bool) {
+    if failed {
+        span.record("otel.status_code", "ERROR");
+    }
+}
+
+/// 为每次存储调用打开一个 `store` span 的包装，`AuthImpl::with_stores` 在 otel feature 下使用
+pub(crate) struct TracedStore<T: ?Sized>(pub Arc<T>);
+
+impl<T: fmt::Debug + ?Sized> fmt::Debug for TracedStore<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+// 存储调用的 span，名称为操作
+fn store_span(operation: &'static str) -> Span {
+    info_span!("store", operation, otel.name = operation, otel.kind = "client")
+}
+
+#[tonic::async_trait]
+impl UserStore for TracedStore<dyn UserStore> {
+    async fn put_user(&self, user: &str, info: UserInfo) -> StoreResult<()> {
+        self.0.put_user(user, info).instrument(store_span("put_user")).await
+    }
+
+    async fn create_user(&self, user: &str, info: UserInfo) -> StoreResult<bool> {
+        self.0.create_user(user, info).instrument(store_span("create_user")).await
+    }
+
+    async fn get_user(&self, user: &str) -> StoreResult<Option<UserInfo>> {
+        self.0.get_user(user).instrument(store_span("get_user")).await
+    }
+
+    async fn update_user(&self, user: &str, update: UserUpdate<'_>) -> StoreResult<bool> {
+        self.0.update_user(user, update).instrument(store_span("update_user")).await
+    }
+
+    async fn delete_user(&self, user: &str) -> StoreResult<bool> {
+        self.0.delete_user(user).instrument(store_span("delete_user")).await
+    }
+
+    async fn list_users(&self, after: &str, limit: u32) -> StoreResult<Vec<(String, UserInfo)>> {
+        self.0.list_users(after, limit).instrument(store_span("list_users")).await
+    }
+}
+
+#[tonic::async_trait]
+impl SessionStore for TracedStore<dyn SessionStore> {
+    async fn put_challenge(&self, auth_id: &str, challenge: PendingChallenge) -> StoreResult<()> {
+        self.0.put_challenge(auth_id, challenge).instrument(store_span("put_challenge")).await
+    }
+
+    async fn get_challenge(&self, auth_id: &str) -> StoreResult<Option<PendingChallenge>> {
+        self.0.get_challenge(auth_id).instrument(store_span("get_challenge")).await
+    }
+
+    async fn take_challenge(&self, auth_id: &str) -> StoreResult<Option<PendingChallenge>> {
+        self.0.take_challenge(auth_id).instrument(store_span("take_challenge")).await
+    }
+
+    async fn count_challenges(&self, user: &str) -> StoreResult<u32> {
+        self.0.count_challenges(user).instrument(store_span("count_challenges")).await
+    }
+
+    async fn delete_challenges(&self, user: &str) -> StoreResult<()> {
+        self.0.delete_challenges(user).instrument(store_span("delete_challenges")).await
+    }
+
+    async fn put_session(&self, session_id: &str, session: SessionInfo) -> StoreResult<()> {
+        self.0.put_session(session_id, session).instrument(store_span("put_session")).await
+    }
+
+    async fn get_session(&self, session_id: &str) -> StoreResult<Option<SessionInfo>> {
+        self.0.get_session(session_id).instrument(store_span("get_session")).await
+    }
+
+    async fn delete_session(&self, session_id: &str) -> StoreResult<Option<SessionInfo>> {
+        self.0.delete_session(session_id).instrument(store_span("delete_session")).await
+    }
+
+    async fn list_sessions(&self, user: &str) -> StoreResult<Vec<(String, SessionInfo)>> {
+        self.0.list_sessions(user).instrument(store_span("list_sessions")).await
+    }
+
+    async fn delete_sessions(&self, user: &str) -> StoreResult<Vec<String>> {
+        self.0.delete_sessions(user).instrument(store_span("delete_sessions")).await
+    }
+
+    async fn count_sessions(&self, now: u64) -> StoreResult<u64> {
+        self.0.count_sessions(now).instrument(store_span("count_sessions")).await
+    }
+
+    async fn purge_expired(&self, now: u64) -> StoreResult<Purged> {
+        self.0.purge_expired(now).instrument(store_span("purge_expired")).await
+    }
+}
@@ -0,0 +1,212 @@
+//! 客户端地址：允许和拒绝的网段，以及负载均衡器之后的真实客户端地址
+//!
+//! 服务器在负载均衡器或反向代理之后时，连接的对端是代理。对端在 `trusted_proxies` 中时，从 `x-forwarded-for`
+//! 的最右侧向左取第一个不是可信代理的地址作为客户端地址；其他对端发送的 `x-forwarded-for` 被忽略，客户端不能伪造自己的地址。
+//! 限流、审计日志和允许/拒绝列表都使用这样确定的客户端地址
+//!
+//! `PeerFilterLayer` 在 `run_server` 中为每个 RPC 确定客户端地址，写入请求的扩展（`ClientAddr`），
+//! 地址被拒绝时直接返回 PermissionDenied，不调用处理函数。自己构建 tonic 服务器的程序用 `Server::builder().layer(...)` 加入
+
+use std::fmt; // 网段的输出格式
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr}; // 客户端地址和网段
+use std::str::FromStr; // 从 "10.0.0.0/8" 形式的字符串解析网段
+use std::sync::Arc; // 服务包装共享的策略
+use std::task::{Context, Poll}; // 服务包装的就绪状态
+
+use tonic::body::BoxBody; // 拒绝时的响应
+use tonic::codegen::{http, BoxFuture, Service}; // 服务包装处理的 HTTP 请求和响应
+#[cfg(feature = "tls")]
+use tonic::transport::server::TlsConnectInfo; // TLS 连接的客户端地址
+use tonic::transport::server::TcpConnectInfo; // 连接的对端地址
+use tonic::{Code, Request, Status}; // 处理函数收到的请求，拒绝时的错误
+use tower::Layer; // 加入 tonic 服务器的 tower 层
+use tracing::debug; // 被拒绝的地址
+
+/// 可信代理转发请求时携带原始客户端地址的请求头
+pub const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+
+/// 一个网段，例如 `10.0.0.0/8` 或 `2001:db8::/32`；不带前缀长度时为单个地址
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNet {
+    addr: IpAddr, // 网络地址，主机位已清零
+    prefix: u8,   // 前缀长度，IPv4 不超过 32，IPv6 不超过 128
+}
+
+impl IpNet {
+    /// 地址是否在这个网段中；IPv4 映射的 IPv6 地址按 IPv4 地址比较
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => mask_v4(ip, self.prefix) == net,
+            (IpAddr::V6(net), IpAddr::V6(ip)) => mask_v6(ip, self.prefix) == net,
+            _ => false,
+        }
+    }
+}
+
+// 清零主机位
+fn mask_v4(ip: Ipv4Addr, prefix: u8) -> Ipv4Addr {
+    Ipv4Addr::from(u32::from(ip) & u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0))
+}
+
+fn mask_v6(ip: Ipv6Addr, prefix: u8) -> Ipv6Addr {
+    Ipv6Addr::from(u128::from(ip) & u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0))
+}
+
+// 格式为 <地址>/<前缀长度> 或 <地址>，地址的主机位不必为零
+impl FromStr for IpNet {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid network {:?}, expected <address>/<prefix length>, e.g. 10.0.0.0/8", value);
+        let (addr, prefix) = match value.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (value.trim(), None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>().ok().filter(|prefix| *prefix <= max).ok_or_else(invalid)?,
+            None => max,
+        };
+        let addr = match addr {
+            IpAddr::V4(addr) => IpAddr::V4(mask_v4(addr, prefix)),
+            IpAddr::V6(addr) => IpAddr::V6(mask_v6(addr, prefix)),
+        };
+        Ok(IpNet { addr, prefix })
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// 按客户端地址接受或拒绝请求，以及信任哪些对端转发的 `x-forwarded-for`，`ServerConfig::peers`；默认接受所有地址、不信任任何代理
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeerPolicy {
+    pub allow: Vec<IpNet>,           // 非空时只接受这些网段中的客户端
+    pub deny: Vec<IpNet>,            // 拒绝这些网段中的客户端，优先于 allow
+    pub trusted_proxies: Vec<IpNet>, // 这些网段中的对端是负载均衡器或反向代理，按它们转发的 x-forwarded-for 确定客户端地址
+}
+
+impl PeerPolicy {
+    /// 请求的客户端地址：对端是可信代理时，从 `x-forwarded-for` 的最右侧向左取第一个不是可信代理的地址，
+    /// 其中的地址都是可信代理时取最左侧的地址；请求头中的地址格式不正确时停在那里，使用它右侧的可信代理
+    ///
+    /// 参数:
+    /// - `peer`: 连接的对端地址
+    /// - `headers`: 请求的 HTTP 头（gRPC 元数据）
+    ///
+    /// 返回:
+    /// - `IpAddr`: 限流、审计日志和允许/拒绝列表使用的客户端地址
+    pub fn client_addr(&self, peer: IpAddr, headers: &http::HeaderMap) -> IpAddr {
+        let mut client = peer.to_canonical();
+        // 多个 x-forwarded-for 头按出现的顺序连接，每个代理都在末尾追加它看到的对端
+        let forwarded = headers.get_all(FORWARDED_FOR_HEADER).iter().filter_map(|value| value.to_str().ok()).flat_map(|value| value.split(',')).collect::<Vec<_>>();
+        for hop in forwarded.iter().rev() {
+            if !self.is_trusted_proxy(client) {
+                break;
+            }
+            match hop.trim().parse::<IpAddr>() {
+                Ok(hop) => client = hop.to_canonical(),
+                Err(_) => break,
+            }
+        }
+        client
+    }
+
+    /// 是否接受这个客户端地址：在 `deny` 中时拒绝，`allow` 非空时必须在其中
+    pub fn permits(&self, client: IpAddr) -> bool {
+        !self.deny.iter().any(|net| net.contains(client)) && (self.allow.is_empty() || self.allow.iter().any(|net| net.contains(client)))
+    }
+
+    fn is_trusted_proxy(&self, peer: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|net| net.contains(peer))
+    }
+}
+
+/// 一次 RPC 的客户端地址，由 `PeerFilterLayer` 写入请求的扩展
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientAddr(pub IpAddr);
+
+// 处理函数中取客户端地址：服务经过 PeerFilterLayer 时为它确定的地址，否则为连接的对端地址；传输层没有提供地址时为 None
+pub(crate) fn client_ip<T>(request: &Request<T>) -> Option<IpAddr> {
+    match request.extensions().get::<ClientAddr>() {
+        Some(ClientAddr(ip)) => Some(*ip),
+        None => request.remote_addr().map(|addr| addr.ip()),
+    }
+}
+
+// 审计日志中的客户端地址：直接连接时为对端的地址和端口，经过可信代理时为 x-forwarded-for 中的地址；没有时为空
+pub(crate) fn client_addr_text<T>(request: &Request<T>) -> String {
+    let remote = request.remote_addr();
+    match request.extensions().get::<ClientAddr>() {
+        Some(ClientAddr(ip)) if remote.map(|addr| addr.ip().to_canonical()) != Some(*ip) => ip.to_string(),
+        _ => remote.map(|addr| addr.to_string()).unwrap_or_default(),
+    }
+}
+
+// 连接的对端地址，tonic 在调用服务前写入请求的扩展；TLS 连接的地址在 TlsConnectInfo 中
+fn peer_ip(extensions: &http::Extensions) -> Option<IpAddr> {
+    let tcp = extensions.get::<TcpConnectInfo>();
+    #[cfg(feature = "tls")]
+    let tcp = tcp.or_else(|| extensions.get::<TlsConnectInfo<TcpConnectInfo>>().map(TlsConnectInfo::get_ref));
+    tcp.and_then(TcpConnectInfo::remote_addr).map(|addr| addr.ip())
+}
+
+/// 按 `PeerPolicy` 确定客户端地址并拒绝不接受的地址的 tower 层，例如 `Server::builder().layer(PeerFilterLayer::new(policy))`
+///
+/// 传输层没有提供对端地址时不写入 `ClientAddr`，`allow` 非空时拒绝这样的请求
+#[derive(Debug, Clone)]
+pub struct PeerFilterLayer(Arc<PeerPolicy>);
+
+impl PeerFilterLayer {
+    pub fn new(policy: PeerPolicy) -> Self {
+        PeerFilterLayer(Arc::new(policy))
+    }
+}
+
+impl<S> Layer<S> for PeerFilterLayer {
+    type Service = PeerFilter<S>;
+
+    fn layer(&self, inner: S) -> PeerFilter<S> {
+        PeerFilter { inner, policy: self.0.clone() }
+    }
+}
+
+/// `PeerFilterLayer` 包装的服务
+#[derive(Debug, Clone)]
+pub struct PeerFilter<S> {
+    inner: S,
+    policy: Arc<PeerPolicy>,
+}
+
+impl<S, B> Service<http::Request<B>> for PeerFilter<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>>,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<S::Response, S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        let client = peer_ip(request.extensions()).map(|peer| self.policy.client_addr(peer, request.headers()));
+        let permitted = client.map_or(self.policy.allow.is_empty(), |client| self.policy.permits(client));
+        if !permitted {
+            debug!(client = ?client, method = request.uri().path(), "rejected a request from a denied client address");
+            let status = Status::new(Code::PermissionDenied, "requests from this client address are not allowed");
+            return Box::pin(async move { Ok(status.to_http()) });
+        }
+        if let Some(client) = client {
+            request.extensions_mut().insert(ClientAddr(client));
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
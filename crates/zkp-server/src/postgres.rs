@@ -0,0 +1,483 @@
+//! PostgreSQL 存储（postgres feature）：多个服务器实例连接同一个数据库，任何实例都可以处理协议的任何一步
+//!
+//! 连接时在事务中执行尚未执行的迁移（见 `migrate` 模块），用 advisory lock 保证同时启动的实例只有一个执行；新增表或列时在 `MIGRATIONS` 末尾追加
+//!
+//! 修改用户记录时用 `SELECT ... FOR UPDATE` 锁住该行，取走挑战和删除会话用 `DELETE ... RETURNING`，不同实例之间不会重复使用同一个挑战
+
+use std::collections::HashMap; // 元数据
+use std::fmt; // 调试输出不包含连接串
+use std::time::Duration; // 等待连接的超时
+
+use num_bigint::BigUint; // 公开值、承诺和挑战值
+use serde::{de::DeserializeOwned, Serialize}; // JSON 列的编码和解码
+use sqlx::postgres::{PgPool, PgPoolOptions, PgRow}; // 连接池和查询结果
+use sqlx::{Executor, Row}; // 执行迁移、读取列
+
+use zkp_proto::zkp_auth::KdfParams; // 用户记录中的 KDF 参数
+
+use crate::audit::{AuditEvent, AuditKind, AuditLog, AuditQuery}; // 审计日志接口和事件
+use crate::migrate; // 版本化迁移
+use crate::store::{PendingChallenge, Purged, SessionInfo, SessionStore, StoreError, StoreResult, UserInfo, UserStore, UserUpdate}; // 存储接口和记录
+
+// 数据库结构的迁移，第 i 个迁移执行后 zkp_schema.version 为 i + 1
+const MIGRATIONS: &[&str] = &[
+    // 1: 用户、挑战和会话；列表和映射以 JSON 文本保存
+    "CREATE TABLE users (
+        name TEXT PRIMARY KEY,
+        y1 BYTEA NOT NULL,
+        y2 BYTEA NOT NULL,
+        salt BYTEA NOT NULL,
+        kdf_algorithm TEXT,
+        kdf_iterations BIGINT,
+        scopes TEXT NOT NULL,
+        metadata TEXT NOT NULL,
+        display_name TEXT NOT NULL,
+        contact TEXT NOT NULL,
+        created_at BIGINT NOT NULL,
+        recovery_codes TEXT NOT NULL,
+        reset_required BOOLEAN NOT NULL,
+        guardians TEXT NOT NULL,
+        guardian_threshold BIGINT NOT NULL
+    );
+    CREATE TABLE challenges (
+        auth_id TEXT PRIMARY KEY,
+        user_name TEXT NOT NULL,
+        r1 BYTEA NOT NULL,
+        r2 BYTEA NOT NULL,
+        c BYTEA NOT NULL,
+        expires_at BIGINT NOT NULL,
+        metadata TEXT NOT NULL,
+        device_id TEXT NOT NULL
+    );
+    CREATE INDEX challenges_user_name ON challenges (user_name);
+    CREATE TABLE sessions (
+        session_id TEXT PRIMARY KEY,
+        user_name TEXT NOT NULL,
+        issued_at BIGINT NOT NULL,
+        expires_at BIGINT NOT NULL,
+        auth_method TEXT NOT NULL,
+        scopes TEXT NOT NULL,
+        metadata TEXT NOT NULL,
+        device_id TEXT NOT NULL
+    );
+    CREATE INDEX sessions_user_name ON sessions (user_name);",
+    // 2: TOTP 第二因素的共享密钥，已有用户为空（未启用）
+    "ALTER TABLE users ADD COLUMN totp_secret BYTEA NOT NULL DEFAULT ''::bytea;",
+    // 3: 注册时的群的参数集标识，已有用户为空（服务器的默认群）
+    "ALTER TABLE users ADD COLUMN params_hash BYTEA NOT NULL DEFAULT ''::bytea;",
+    // 4: 审计日志，只插入不修改；多个实例同时写入时序号由 BIGSERIAL 分配
+    "CREATE TABLE audit_log (
+        id BIGSERIAL PRIMARY KEY,
+        at BIGINT NOT NULL,
+        kind TEXT NOT NULL,
+        user_name TEXT NOT NULL,
+        success BOOLEAN NOT NULL,
+        reason TEXT NOT NULL,
+        remote_addr TEXT NOT NULL,
+        correlation_id TEXT NOT NULL,
+        session TEXT NOT NULL
+    );
+    CREATE INDEX audit_log_user_name ON audit_log (user_name, id);",
+    // 5: 监护人被指定时账户的指纹；已有的记录为空，其中的监护人需要重新指定才能批准恢复
+    "ALTER TABLE users ADD COLUMN guardian_bindings TEXT NOT NULL DEFAULT '[]';",
+];
+
+// 迁移使用的 advisory lock 键
+const MIGRATION_LOCK: i64 = 0x7a6b_7061_7574_6801;
+
+// 查询用户记录时读取的列
+const USER_COLUMNS: &str = "y1, y2, salt, kdf_algorithm, kdf_iterations, scopes, metadata, display_name, contact, created_at, recovery_codes, reset_required, guardians, guardian_threshold, totp_secret, params_hash, guardian_bindings";
+
+// 查询挑战时读取的列
+const CHALLENGE_COLUMNS: &str = "user_name, r1, r2, c, expires_at, metadata, device_id";
+
+// 查询会话时读取的列
+const SESSION_COLUMNS: &str = "session_id, user_name, issued_at, expires_at, auth_method, scopes, metadata, device_id";
+
+// 查询审计事件时读取的列
+const AUDIT_COLUMNS: &str = "id, at, kind, user_name, success, reason, remote_addr, correlation_id, session";
+
+impl From<sqlx::Error> for StoreError {
+    fn from(err: sqlx::Error) -> Self {
+        StoreError(format!("postgres: {}", err))
+    }
+}
+
+/// 连接池的配置
+#[derive(Debug, Clone)]
+pub struct PostgresOptions {
+    pub max_connections: u32,      // 每个服务器实例的最大连接数
+    pub acquire_timeout: Duration, // 连接全部被占用时等待的时间，超时后请求返回 Unavailable
+}
+
+impl Default for PostgresOptions {
+    fn default() -> Self {
+        PostgresOptions { max_connections: 10, acquire_timeout: Duration::from_secs(5) }
+    }
+}
+
+/// PostgreSQL 中的用户、挑战和会话
+#[derive(Clone)]
+pub struct PostgresStore {
+    pool: PgPool, // 连接池
+}
+
+impl PostgresStore {
+    /// 建立连接池，并执行尚未执行的迁移
+    ///
+    /// 参数:
+    /// - `url`: 连接串，例如 `postgres://zkp@localhost/zkp`
+    /// - `options`: 连接池的配置
+    ///
+    /// 返回:
+    /// - `StoreResult<PostgresStore>`: 连接好的存储，或者连接和迁移失败的原因
+    pub async fn connect(url: &str, options: &PostgresOptions) -> StoreResult<Self> {
+        let pool = PgPoolOptions::new().max_connections(options.max_connections).acquire_timeout(options.acquire_timeout).connect(url).await?;
+        PostgresStore::with_pool(pool).await
+    }
+
+    /// 使用调用者建立的连接池，并执行尚未执行的迁移
+    pub async fn with_pool(pool: PgPool) -> StoreResult<Self> {
+        migrate(&pool).await?;
+        Ok(PostgresStore { pool })
+    }
+}
+
+// 连接串可能包含密码，调试输出只包含连接数
+impl fmt::Debug for PostgresStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PostgresStore").field("connections", &self.pool.size()).field("idle", &self.pool.num_idle()).finish()
+    }
+}
+
+// 在一个事务中执行尚未执行的迁移，比代码更新的数据库拒绝连接
+async fn migrate(pool: &PgPool) -> StoreResult<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)").bind(MIGRATION_LOCK).execute(&mut *tx).await?;
+    tx.execute("CREATE TABLE IF NOT EXISTS zkp_schema (version BIGINT NOT NULL)").await?;
+    let version: Option<i64> = sqlx::query("SELECT MAX(version) FROM zkp_schema").fetch_one(&mut *tx).await?.try_get(0)?;
+    let pending = migrate::pending("postgres", version.unwrap_or(0) as usize, MIGRATIONS)?;
+    for index in pending.clone() {
+        tx.execute(MIGRATIONS[index]).await?;
+        sqlx::query("INSERT INTO zkp_schema (version) VALUES ($1)").bind(index as i64 + 1).execute(&mut *tx).await?;
+    }
+    tx.commit().await?;
+    migrate::applied("postgres", pending);
+    Ok(())
+}
+
+// 列表和映射编码为 JSON
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("lists and maps of strings and bytes always serialize")
+}
+
+// 读取 JSON 列，内容无法解析时作为列解码错误返回
+fn json_column<T: DeserializeOwned>(row: &PgRow, column: &str) -> sqlx::Result<T> {
+    let text: String = row.try_get(column)?;
+    serde_json::from_str(&text).map_err(|err| sqlx::Error::ColumnDecode { index: column.to_string(), source: Box::new(err) })
+}
+
+// 大整数列，大端字节
+fn biguint_column(row: &PgRow, column: &str) -> sqlx::Result<BigUint> {
+    Ok(BigUint::from_bytes_be(&row.try_get::<Vec<u8>, _>(column)?))
+}
+
+// PostgreSQL 没有无符号整数，时间戳和计数以 BIGINT 保存
+fn u64_column(row: &PgRow, column: &str) -> sqlx::Result<u64> {
+    Ok(row.try_get::<i64, _>(column)? as u64)
+}
+
+fn user_from_row(row: &PgRow) -> sqlx::Result<UserInfo> {
+    let kdf = match (row.try_get::<Option<String>, _>("kdf_algorithm")?, row.try_get::<Option<i64>, _>("kdf_iterations")?) {
+        (Some(algorithm), Some(iterations)) => Some(KdfParams { algorithm, iterations: iterations as u32 }),
+        _ => None,
+    };
+    Ok(UserInfo {
+        y1: biguint_column(row, "y1")?,
+        y2: biguint_column(row, "y2")?,
+        salt: row.try_get("salt")?,
+        kdf,
+        scopes: json_column(row, "scopes")?,
+        metadata: json_column(row, "metadata")?,
+        display_name: row.try_get("display_name")?,
+        contact: row.try_get("contact")?,
+        created_at: u64_column(row, "created_at")?,
+        recovery_codes: json_column(row, "recovery_codes")?,
+        reset_required: row.try_get("reset_required")?,
+        guardians: json_column(row, "guardians")?,
+        guardian_bindings: json_column(row, "guardian_bindings")?,
+        guardian_threshold: u64_column(row, "guardian_threshold")? as u32,
+        totp_secret: row.try_get("totp_secret")?,
+        group: row.try_get("params_hash")?,
+    })
+}
+
+fn challenge_from_row(row: &PgRow) -> sqlx::Result<PendingChallenge> {
+    Ok(PendingChallenge {
+        user: row.try_get("user_name")?,
+        r1: biguint_column(row, "r1")?,
+        r2: biguint_column(row, "r2")?,
+        c: biguint_column(row, "c")?,
+        expires_at: u64_column(row, "expires_at")?,
+        metadata: json_column(row, "metadata")?,
+        device_id: row.try_get("device_id")?,
+    })
+}
+
+fn session_from_row(row: &PgRow) -> sqlx::Result<SessionInfo> {
+    Ok(SessionInfo {
+        user: row.try_get("user_name")?,
+        issued_at: u64_column(row, "issued_at")?,
+        expires_at: u64_column(row, "expires_at")?,
+        auth_method: row.try_get("auth_method")?,
+        scopes: json_column(row, "scopes")?,
+        metadata: json_column::<HashMap<String, String>>(row, "metadata")?,
+        device_id: row.try_get("device_id")?,
+    })
+}
+
+fn audit_event_from_row(row: &PgRow) -> sqlx::Result<AuditEvent> {
+    let kind: String = row.try_get("kind")?;
+    let kind = AuditKind::from_name(&kind).ok_or_else(|| sqlx::Error::ColumnDecode { index: "kind".to_string(), source: format!("unknown audit event kind {:?}", kind).into() })?;
+    Ok(AuditEvent {
+        id: u64_column(row, "id")?,
+        at: u64_column(row, "at")?,
+        kind,
+        user: row.try_get("user_name")?,
+        success: row.try_get("success")?,
+        reason: row.try_get("reason")?,
+        remote_addr: row.try_get("remote_addr")?,
+        correlation_id: row.try_get("correlation_id")?,
+        session: row.try_get("session")?,
+    })
+}
+
+// 插入或替换用户记录
+async fn write_user<'c, E: sqlx::PgExecutor<'c>>(executor: E, user: &str, info: &UserInfo) -> sqlx::Result<()> {
+    let on_conflict = "ON CONFLICT (name) DO UPDATE SET (
+            y1, y2, salt, kdf_algorithm, kdf_iterations, scopes, metadata, display_name, contact, created_at, recovery_codes, reset_required, guardians, guardian_threshold, totp_secret, params_hash,
+            guardian_bindings
+         ) = (
+            EXCLUDED.y1, EXCLUDED.y2, EXCLUDED.salt, EXCLUDED.kdf_algorithm, EXCLUDED.kdf_iterations, EXCLUDED.scopes, EXCLUDED.metadata, EXCLUDED.display_name,
+            EXCLUDED.contact, EXCLUDED.created_at, EXCLUDED.recovery_codes, EXCLUDED.reset_required, EXCLUDED.guardians, EXCLUDED.guardian_threshold,
+            EXCLUDED.totp_secret, EXCLUDED.params_hash, EXCLUDED.guardian_bindings
+         )";
+    insert_user(executor, user, info, on_conflict).await?;
+    Ok(())
+}
+
+// 插入用户记录，同名用户已存在时按 on_conflict 处理，返回插入或修改的行数
+async fn insert_user<'c, E: sqlx::PgExecutor<'c>>(executor: E, user: &str, info: &UserInfo, on_conflict: &str) -> sqlx::Result<u64> {
+    let result = sqlx::query(&format!(
+        "INSERT INTO users (name, {}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18) {}",
+        USER_COLUMNS, on_conflict
+    ))
+    .bind(user)
+    .bind(info.y1.to_bytes_be())
+    .bind(info.y2.to_bytes_be())
+    .bind(&info.salt)
+    .bind(info.kdf.as_ref().map(|kdf| kdf.algorithm.clone()))
+    .bind(info.kdf.as_ref().map(|kdf| kdf.iterations as i64))
+    .bind(to_json(&info.scopes))
+    .bind(to_json(&info.metadata))
+    .bind(&info.display_name)
+    .bind(&info.contact)
+    .bind(info.created_at as i64)
+    .bind(to_json(&info.recovery_codes))
+    .bind(info.reset_required)
+    .bind(to_json(&info.guardians))
+    .bind(info.guardian_threshold as i64)
+    .bind(&info.totp_secret)
+    .bind(&info.group)
+    .bind(to_json(&info.guardian_bindings))
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+#[tonic::async_trait]
+impl UserStore for PostgresStore {
+    async fn put_user(&self, user: &str, info: UserInfo) -> StoreResult<()> {
+        write_user(&self.pool, user, &info).await?;
+        Ok(())
+    }
+
+    // 由主键的唯一约束保证并发注册同一个用户名时只有一个实例插入成功
+    async fn create_user(&self, user: &str, info: UserInfo) -> StoreResult<bool> {
+        Ok(insert_user(&self.pool, user, &info, "ON CONFLICT (name) DO NOTHING").await? == 1)
+    }
+
+    async fn get_user(&self, user: &str) -> StoreResult<Option<UserInfo>> {
+        let row = sqlx::query(&format!("SELECT {} FROM users WHERE name = $1", USER_COLUMNS)).bind(user).fetch_optional(&self.pool).await?;
+        Ok(row.as_ref().map(user_from_row).transpose()?)
+    }
+
+    // 锁住用户记录的行后读取、修改和写回，其他实例对同一用户的修改等待本事务结束；update 放弃时回滚
+    async fn update_user(&self, user: &str, update: UserUpdate<'_>) -> StoreResult<bool> {
+        let mut tx = self.pool.begin().await?;
+        let row = sqlx::query(&format!("SELECT {} FROM users WHERE name = $1 FOR UPDATE", USER_COLUMNS)).bind(user).fetch_optional(&mut *tx).await?;
+        let Some(row) = row else {
+            return Ok(false);
+        };
+        let mut info = user_from_row(&row)?;
+        if !update(&mut info) {
+            return Ok(false);
+        }
+        write_user(&mut *tx, user, &info).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn delete_user(&self, user: &str) -> StoreResult<bool> {
+        let result = sqlx::query("DELETE FROM users WHERE name = $1").bind(user).execute(&self.pool).await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 按字节比较（COLLATE "C"），顺序与 MemoryStore 一致，不受数据库的区域设置影响
+    async fn list_users(&self, after: &str, limit: u32) -> StoreResult<Vec<(String, UserInfo)>> {
+        let rows = sqlx::query(&format!("SELECT name, {} FROM users WHERE name COLLATE \"C\" > $1 ORDER BY name COLLATE \"C\" LIMIT $2", USER_COLUMNS))
+            .bind(after)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+        let users = rows.iter().map(|row| Ok((row.try_get("name")?, user_from_row(row)?))).collect::<sqlx::Result<_>>()?;
+        Ok(users)
+    }
+}
+
+#[tonic::async_trait]
+impl SessionStore for PostgresStore {
+    async fn put_challenge(&self, auth_id: &str, challenge: PendingChallenge) -> StoreResult<()> {
+        sqlx::query(&format!("INSERT INTO challenges (auth_id, {}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)", CHALLENGE_COLUMNS))
+            .bind(auth_id)
+            .bind(&challenge.user)
+            .bind(challenge.r1.to_bytes_be())
+            .bind(challenge.r2.to_bytes_be())
+            .bind(challenge.c.to_bytes_be())
+            .bind(challenge.expires_at as i64)
+            .bind(to_json(&challenge.metadata))
+            .bind(&challenge.device_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_challenge(&self, auth_id: &str) -> StoreResult<Option<PendingChallenge>> {
+        let row = sqlx::query(&format!("SELECT {} FROM challenges WHERE auth_id = $1", CHALLENGE_COLUMNS)).bind(auth_id).fetch_optional(&self.pool).await?;
+        Ok(row.as_ref().map(challenge_from_row).transpose()?)
+    }
+
+    // 删除和读取是同一条语句，两个实例同时取同一个认证 ID 时只有一个得到挑战
+    async fn take_challenge(&self, auth_id: &str) -> StoreResult<Option<PendingChallenge>> {
+        let row = sqlx::query(&format!("DELETE FROM challenges WHERE auth_id = $1 RETURNING {}", CHALLENGE_COLUMNS)).bind(auth_id).fetch_optional(&self.pool).await?;
+        Ok(row.as_ref().map(challenge_from_row).transpose()?)
+    }
+
+    async fn count_challenges(&self, user: &str) -> StoreResult<u32> {
+        let count: i64 = sqlx::query("SELECT COUNT(*) FROM challenges WHERE user_name = $1").bind(user).fetch_one(&self.pool).await?.try_get(0)?;
+        Ok(count as u32)
+    }
+
+    async fn delete_challenges(&self, user: &str) -> StoreResult<()> {
+        sqlx::query("DELETE FROM challenges WHERE user_name = $1").bind(user).execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn put_session(&self, session_id: &str, session: SessionInfo) -> StoreResult<()> {
+        sqlx::query(&format!("INSERT INTO sessions ({}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)", SESSION_COLUMNS))
+            .bind(session_id)
+            .bind(&session.user)
+            .bind(session.issued_at as i64)
+            .bind(session.expires_at as i64)
+            .bind(&session.auth_method)
+            .bind(to_json(&session.scopes))
+            .bind(to_json(&session.metadata))
+            .bind(&session.device_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_session(&self, session_id: &str) -> StoreResult<Option<SessionInfo>> {
+        let row = sqlx::query(&format!("SELECT {} FROM sessions WHERE session_id = $1", SESSION_COLUMNS)).bind(session_id).fetch_optional(&self.pool).await?;
+        Ok(row.as_ref().map(session_from_row).transpose()?)
+    }
+
+    async fn delete_session(&self, session_id: &str) -> StoreResult<Option<SessionInfo>> {
+        let row = sqlx::query(&format!("DELETE FROM sessions WHERE session_id = $1 RETURNING {}", SESSION_COLUMNS)).bind(session_id).fetch_optional(&self.pool).await?;
+        Ok(row.as_ref().map(session_from_row).transpose()?)
+    }
+
+    async fn list_sessions(&self, user: &str) -> StoreResult<Vec<(String, SessionInfo)>> {
+        let rows = sqlx::query(&format!("SELECT {} FROM sessions WHERE user_name = $1", SESSION_COLUMNS)).bind(user).fetch_all(&self.pool).await?;
+        let sessions = rows.iter().map(|row| Ok((row.try_get("session_id")?, session_from_row(row)?))).collect::<sqlx::Result<_>>()?;
+        Ok(sessions)
+    }
+
+    async fn delete_sessions(&self, user: &str) -> StoreResult<Vec<String>> {
+        let rows = sqlx::query("DELETE FROM sessions WHERE user_name = $1 RETURNING session_id").bind(user).fetch_all(&self.pool).await?;
+        let session_ids = rows.iter().map(|row| row.try_get("session_id")).collect::<sqlx::Result<_>>()?;
+        Ok(session_ids)
+    }
+
+    async fn count_sessions(&self, now: u64) -> StoreResult<u64> {
+        let count: i64 = sqlx::query("SELECT COUNT(*) FROM sessions WHERE expires_at > $1").bind(now as i64).fetch_one(&self.pool).await?.try_get(0)?;
+        Ok(count as u64)
+    }
+
+    // 每个实例都运行清理任务，同时删除同一批行时各自只计入自己删除的行
+    async fn purge_expired(&self, now: u64) -> StoreResult<Purged> {
+        let challenges = sqlx::query("DELETE FROM challenges WHERE expires_at <= $1").bind(now as i64).execute(&self.pool).await?;
+        let sessions = sqlx::query("DELETE FROM sessions WHERE expires_at <= $1").bind(now as i64).execute(&self.pool).await?;
+        Ok(Purged { challenges: challenges.rows_affected() as u32, sessions: sessions.rows_affected() as u32 })
+    }
+}
+
+#[tonic::async_trait]
+impl AuditLog for PostgresStore {
+    async fn append(&self, event: AuditEvent) -> StoreResult<()> {
+        sqlx::query("INSERT INTO audit_log (at, kind, user_name, success, reason, remote_addr, correlation_id, session) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)")
+            .bind(event.at as i64)
+            .bind(event.kind.name())
+            .bind(&event.user)
+            .bind(event.success)
+            .bind(&event.reason)
+            .bind(&event.remote_addr)
+            .bind(&event.correlation_id)
+            .bind(&event.session)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // until 为 0 时不限制，用 i64::MAX 代替
+    async fn query(&self, query: &AuditQuery) -> StoreResult<Vec<AuditEvent>> {
+        let until = if query.until == 0 { i64::MAX } else { query.until as i64 };
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM audit_log WHERE id > $1 AND at >= $2 AND at < $3 AND ($4 = '' OR user_name = $4) ORDER BY id LIMIT $5",
+            AUDIT_COLUMNS
+        ))
+        .bind(query.after as i64)
+        .bind(query.since as i64)
+        .bind(until)
+        .bind(&query.user)
+        .bind(query.limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().map(audit_event_from_row).collect::<sqlx::Result<_>>()?)
+    }
+
+    // 没有冒号时 greatest(..., 0) 使原因为空字符串，与 AuditEvent::pseudonymize 一致
+    async fn pseudonymize(&self, user: &str, tombstone: &str) -> StoreResult<u64> {
+        let result = sqlx::query(
+            "UPDATE audit_log SET user_name = $2, remote_addr = '',
+                reason = CASE WHEN strpos(reason, $1) > 0 THEN substr(reason, 1, greatest(strpos(reason, ':') - 1, 0)) ELSE reason END
+            WHERE user_name = $1",
+        )
+        .bind(user)
+        .bind(tombstone)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,158 @@
+//! 按用户名和客户端地址限流：Register、CreateAuthenticationChallenge 和 VerifyAuthentication 各有独立的配额，
+//! 每个配额同时按客户端 IP 和用户名各计一个令牌桶，任一个耗尽时返回带 `retry-after` 的 ResourceExhausted。
+//! 挑战请求需要一次模幂运算并在存储中保存挑战，不限流时任何人都可以用它消耗服务器的 CPU 和内存
+//!
+//! 用户名在请求消息中，因此在处理函数开始时检查，而不是在 tower 层中解码请求
+
+use std::fmt; // 调试输出不包含用户名和地址
+use std::net::IpAddr; // 客户端地址
+use std::str::FromStr; // 从 "10/m" 形式的字符串解析配额
+use std::sync::RwLock; // 重新加载配置时替换配额
+use std::time::{Duration, Instant}; // 补充令牌的时间
+
+use tonic::{Code, Status}; // 限流错误
+use zkp_proto::detail::with_error_code; // 错误的结构化详情
+use zkp_proto::retry::with_retry_after; // 告诉客户端何时可以重试
+use zkp_proto::zkp_auth::ErrorCode; // 详情中的错误分类
+
+use crate::shard::ShardedMap; // 令牌桶在请求之间共享，键为 RPC、维度和取值
+
+/// 一个 RPC 的配额：每个客户端 IP 和每个用户名在 `per` 时间内最多 `requests` 次请求，令牌均匀补充，允许一次用完
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub requests: u32, // 令牌桶的容量
+    pub per: Duration, // 补满令牌桶的时间
+}
+
+impl Quota {
+    /// 每分钟最多 `requests` 次请求
+    pub fn per_minute(requests: u32) -> Self {
+        Quota { requests, per: Duration::from_secs(60) }
+    }
+
+    // 每补充一个令牌需要的时间
+    fn interval(&self) -> Duration {
+        self.per / self.requests.max(1)
+    }
+}
+
+// 格式为 <次数>/<s|m|h>，例如 "30/m"
+impl FromStr for Quota {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid quota {:?}, expected <requests>/<s|m|h>, e.g. 30/m", value);
+        let (requests, unit) = value.split_once('/').ok_or_else(invalid)?;
+        let requests: u32 = requests.trim().parse().map_err(|_| invalid())?;
+        let per = match unit.trim() {
+            "s" => Duration::from_secs(1),
+            "m" => Duration::from_secs(60),
+            "h" => Duration::from_secs(60 * 60),
+            _ => return Err(invalid()),
+        };
+        if requests == 0 {
+            return Err(invalid());
+        }
+        Ok(Quota { requests, per })
+    }
+}
+
+/// 各 RPC 的配额，为 None 时该 RPC 不限流
+#[derive(Debug, Clone, Default)]
+pub struct RateLimits {
+    pub register: Option<Quota>,  // Register
+    pub challenge: Option<Quota>, // CreateAuthenticationChallenge
+    pub verify: Option<Quota>,    // VerifyAuthentication
+}
+
+/// 受限流的 RPC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Limited {
+    Register,
+    Challenge,
+    Verify,
+}
+
+// 令牌桶的键：客户端 IP 或用户名
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Peer(IpAddr),
+    User(String),
+}
+
+// 令牌桶：tokens 为 updated 时刻剩余的令牌数
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+// 所有 RPC 的令牌桶
+pub(crate) struct RateLimiter {
+    limits: RwLock<RateLimits>,
+    buckets: ShardedMap<(Limited, Key), Bucket>,
+}
+
+// 令牌桶的键是用户名和客户端地址，调试输出只包含配额和令牌桶数
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimiter").field("limits", &*self.limits.read().unwrap()).field("buckets", &self.buckets.len()).finish()
+    }
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limits: RateLimits) -> Self {
+        RateLimiter { limits: RwLock::new(limits), buckets: ShardedMap::default() }
+    }
+
+    // 替换配额；已有的令牌桶保留剩余的令牌，不超过新的容量，不再限流的 RPC 的令牌桶由下一次清理删除
+    pub(crate) fn set_limits(&self, limits: RateLimits) {
+        *self.limits.write().unwrap() = limits;
+    }
+
+    // RPC 的配额
+    fn quota(&self, rpc: Limited) -> Option<Quota> {
+        let limits = self.limits.read().unwrap();
+        match rpc {
+            Limited::Register => limits.register,
+            Limited::Challenge => limits.challenge,
+            Limited::Verify => limits.verify,
+        }
+    }
+
+    // 先按客户端 IP、再按用户名各取一个令牌；没有配额、地址未知或用户名为空时跳过对应的令牌桶
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn check(&self, rpc: Limited, peer: Option<IpAddr>, user: Option<&str>) -> Result<(), Status> {
+        let Some(quota) = self.quota(rpc) else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let keys = peer.map(Key::Peer).into_iter().chain(user.filter(|user| !user.is_empty()).map(|user| Key::User(user.to_string())));
+        for key in keys {
+            let key = (rpc, key);
+            let mut buckets = self.buckets.write(&key);
+            let bucket = buckets.entry(key.clone()).or_insert(Bucket { tokens: quota.requests as f64, updated: now });
+            // 按经过的时间补充令牌，不超过容量
+            let refilled = now.duration_since(bucket.updated).as_secs_f64() / quota.interval().as_secs_f64();
+            bucket.tokens = (bucket.tokens + refilled).min(quota.requests as f64);
+            bucket.updated = now;
+            if bucket.tokens < 1.0 {
+                let retry_after = quota.interval().mul_f64(1.0 - bucket.tokens);
+                let by = match key.1 {
+                    Key::Peer(_) => "client address",
+                    Key::User(_) => "user",
+                };
+                return Err(with_error_code(with_retry_after(Code::ResourceExhausted, format!("too many {:?} requests for this {}, retry later", rpc, by), retry_after), ErrorCode::RateLimited));
+            }
+            bucket.tokens -= 1.0;
+        }
+        Ok(())
+    }
+
+    // 删除已经补满的令牌桶，它们与新建的令牌桶没有区别；由后台清理调用，限制被随机用户名撑大的映射表
+    pub(crate) fn purge(&self) {
+        let now = Instant::now();
+        self.buckets.retain(|(rpc, _), bucket| {
+            self.quota(*rpc).is_some_and(|quota| now.duration_since(bucket.updated) < quota.interval().mul_f64(quota.requests as f64 - bucket.tokens))
+        });
+    }
+}
@@ -0,0 +1,187 @@
+//! 管理接口的访问控制：调用者通过管理令牌（`authorization: Bearer <token>`）或 mTLS 客户端身份认证，
+//! 每个身份对应一个角色，`AdminAuth` 拦截器认证后把 `AdminIdentity` 写入请求的扩展，管理接口的处理函数再用 `require` 检查角色
+//!
+//! 角色从低到高：`Viewer` 只能查询（列出用户、查看会话、导出数据、查询审计日志），`Operator` 还可以吊销会话和解锁账户，
+//! `Admin` 还可以删除用户；只读的监控面板应使用 `Viewer` 令牌
+//!
+//! 开启 tls feature 并配置客户端 CA 时，服务器从握手时验证过的客户端证书中取得主体；使用自己的 TLS 接入层的程序
+//! 在验证客户端证书后以 `ClientIdentity` 写入请求的扩展，与 `ChannelBinding` 相同
+
+use std::collections::HashMap; // 令牌哈希和客户端身份到角色的映射
+use std::fmt; // 脱敏的调试输出
+use std::sync::Arc; // 拦截器的克隆共享同一个策略
+
+use tonic::service::Interceptor; // 管理服务的拦截器
+use tonic::{Code, Request, Status}; // 认证失败和权限不足的错误
+
+use zkp_core::HashAlgorithm; // 令牌只保存哈希
+
+// 令牌所在的元数据键和前缀
+const AUTHORIZATION_HEADER: &str = "authorization";
+const BEARER_PREFIX: &str = "Bearer ";
+
+/// 管理接口调用者的角色，按权限从低到高排列，高的角色拥有低的角色的全部权限
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AdminRole {
+    /// 只读：列出用户、查看会话、导出数据、查询审计日志
+    Viewer,
+    /// 运维：另外可以吊销会话、解锁账户
+    Operator,
+    /// 管理员：另外可以删除用户
+    Admin,
+}
+
+impl AdminRole {
+    /// 角色名称：viewer、operator、admin
+    pub fn name(self) -> &'static str {
+        match self {
+            AdminRole::Viewer => "viewer",
+            AdminRole::Operator => "operator",
+            AdminRole::Admin => "admin",
+        }
+    }
+
+    /// 由名称解析角色，不区分大小写，未知的名称返回 None
+    pub fn from_name(name: &str) -> Option<AdminRole> {
+        [AdminRole::Viewer, AdminRole::Operator, AdminRole::Admin].into_iter().find(|role| role.name().eq_ignore_ascii_case(name))
+    }
+}
+
+impl fmt::Display for AdminRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// TLS 接入层验证过的客户端证书主体（例如 CN 或 SAN），由嵌入服务器的程序写入请求的扩展，或者由服务器的 mTLS 取得
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientIdentity(pub String);
+
+impl ClientIdentity {
+    /// 取请求的客户端身份：先看请求扩展中的 `ClientIdentity`，再看服务器 mTLS 验证过的客户端证书（tls feature）
+    ///
+    /// 返回:
+    /// - `Option<ClientIdentity>`: 没有客户端证书时为 None
+    pub fn of<T>(request: &Request<T>) -> Option<ClientIdentity> {
+        if let Some(identity) = request.extensions().get::<ClientIdentity>() {
+            return Some(identity.clone());
+        }
+        #[cfg(feature = "tls")]
+        return crate::tls::peer_identity(request);
+        #[cfg(not(feature = "tls"))]
+        None
+    }
+}
+
+/// 认证通过的管理接口调用者，由 `AdminAuth` 写入请求的扩展
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdminIdentity {
+    pub name: String,    // 调用者的名称，用于日志和审计
+    pub role: AdminRole, // 调用者的角色
+}
+
+/// 管理令牌和 mTLS 客户端身份到角色的映射
+#[derive(Default, Clone)]
+pub struct AdminPolicy {
+    tokens: HashMap<Vec<u8>, AdminIdentity>,    // 令牌的 SHA-256 哈希 -> 调用者
+    identities: HashMap<String, AdminRole>,     // 客户端证书主体 -> 角色
+}
+
+impl AdminPolicy {
+    /// 添加一个管理令牌，只保存令牌的哈希
+    ///
+    /// 参数:
+    /// - `token`: 调用者在 `authorization: Bearer <token>` 中发送的令牌
+    /// - `name`: 调用者的名称
+    /// - `role`: 令牌对应的角色
+    pub fn with_token(mut self, token: &str, name: &str, role: AdminRole) -> Self {
+        self.tokens.insert(token_hash(token), AdminIdentity { name: name.to_string(), role });
+        self
+    }
+
+    /// 为一个 mTLS 客户端身份指定角色
+    ///
+    /// 参数:
+    /// - `subject`: TLS 接入层写入的 `ClientIdentity`
+    /// - `role`: 该身份对应的角色
+    pub fn with_client_identity(mut self, subject: &str, role: AdminRole) -> Self {
+        self.identities.insert(subject.to_string(), role);
+        self
+    }
+
+    /// 认证请求的调用者：先看 mTLS 客户端身份，再看管理令牌
+    ///
+    /// 参数:
+    /// - `request`: 管理接口收到的请求
+    ///
+    /// 返回:
+    /// - `Result<AdminIdentity, Status>`: 没有凭据或凭据未知时返回 Unauthenticated
+    #[allow(clippy::result_large_err)]
+    pub fn authenticate<T>(&self, request: &Request<T>) -> Result<AdminIdentity, Status> {
+        if let Some(ClientIdentity(subject)) = ClientIdentity::of(request) {
+            if let Some(role) = self.identities.get(&subject) {
+                return Ok(AdminIdentity { name: subject, role: *role });
+            }
+        }
+        let token = request
+            .metadata()
+            .get(AUTHORIZATION_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix(BEARER_PREFIX));
+        match token.and_then(|token| self.tokens.get(&token_hash(token))) {
+            Some(identity) => Ok(identity.clone()),
+            None => Err(Status::new(Code::Unauthenticated, "missing or unknown admin credentials")),
+        }
+    }
+}
+
+// 令牌和客户端身份是凭据，调试输出只包含条目数
+impl fmt::Debug for AdminPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminPolicy").field("tokens", &self.tokens.len()).field("identities", &self.identities.len()).finish()
+    }
+}
+
+// 令牌保存和比较时使用的哈希，查找不依赖令牌本身的逐字节比较
+fn token_hash(token: &str) -> Vec<u8> {
+    HashAlgorithm::Sha256.digest(token.as_bytes())
+}
+
+/// 管理服务的拦截器，例如 `AuthAdminServer::with_interceptor(admin_impl, AdminAuth::new(policy))`
+///
+/// 认证失败的请求在到达处理函数之前被拒绝，通过的请求带有 `AdminIdentity`
+#[derive(Debug, Clone)]
+pub struct AdminAuth(Arc<AdminPolicy>);
+
+impl AdminAuth {
+    /// 参数:
+    /// - `policy`: 令牌和客户端身份到角色的映射
+    pub fn new(policy: AdminPolicy) -> Self {
+        AdminAuth(Arc::new(policy))
+    }
+}
+
+impl Interceptor for AdminAuth {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let identity = self.0.authenticate(&request)?;
+        request.extensions_mut().insert(identity);
+        Ok(request)
+    }
+}
+
+/// 管理接口的处理函数开始时检查调用者的角色
+///
+/// 参数:
+/// - `request`: 经过 `AdminAuth` 的请求
+/// - `role`: 操作需要的最低角色
+///
+/// 返回:
+/// - `Result<AdminIdentity, Status>`: 请求没有经过 `AdminAuth` 时返回 Unauthenticated，角色不够时返回 PermissionDenied
+#[allow(clippy::result_large_err)]
+pub fn require<T>(request: &Request<T>, role: AdminRole) -> Result<AdminIdentity, Status> {
+    match request.extensions().get::<AdminIdentity>() {
+        Some(identity) if identity.role >= role => Ok(identity.clone()),
+        Some(identity) => Err(Status::new(Code::PermissionDenied, format!("{} ({}) needs the {} role", identity.name, identity.role, role))),
+        None => Err(Status::new(Code::Unauthenticated, "admin request was not authenticated")),
+    }
+}
@@ -0,0 +1,302 @@
+//! Redis 中的挑战和会话（redis feature）：服务器的多个副本连接同一个 Redis，负载均衡器可以把协议的任何一步交给任何副本
+//!
+//! 挑战和会话以 JSON 保存在 `<prefix>challenge:<auth_id>`、`<prefix>session:<session_id>` 中，TTL 为距离过期时间的秒数，
+//! 过期的记录由 Redis 删除；`<prefix>user-challenges:<user>`、`<prefix>user-sessions:<user>` 集合记录用户的认证 ID 和会话 ID，
+//! 集合中已过期的成员在读取时跳过；需要 Redis 7.0 或更新的版本（GETDEL、EXPIRE GT/NX）
+//!
+//! 只实现 `SessionStore`，用户记录需要持久保存，与 `AuthImpl::with_stores` 一起使用，例如：
+//!
+//! ```ignore
+//! let auth = AuthImpl::with_stores(config, Arc::new(PostgresStore::connect(url, &options).await?), Arc::new(RedisStore::connect(redis_url, "zkp:").await?));
+//! ```
+
+use std::collections::HashMap; // 元数据
+use std::fmt; // 调试输出
+use std::time::{SystemTime, UNIX_EPOCH}; // 计算 TTL
+
+use ::redis::aio::ConnectionManager; // 断线自动重连的连接
+use ::redis::{cmd, pipe, AsyncCommands}; // Redis 命令
+use num_bigint::BigUint; // 承诺和挑战值
+use serde::{Deserialize, Serialize}; // 记录的 JSON 编码
+
+use crate::store::{PendingChallenge, Purged, SessionInfo, SessionStore, StoreError, StoreResult}; // 存储接口和记录
+
+impl From<::redis::RedisError> for StoreError {
+    fn from(err: ::redis::RedisError) -> Self {
+        StoreError(format!("redis: {}", err))
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(err: serde_json::Error) -> Self {
+        StoreError(format!("invalid record: {}", err))
+    }
+}
+
+// 挑战在 Redis 中的编码，大整数为大端字节
+#[derive(Serialize, Deserialize)]
+struct ChallengeRecord {
+    user: String,
+    r1: Vec<u8>,
+    r2: Vec<u8>,
+    c: Vec<u8>,
+    expires_at: u64,
+    metadata: HashMap<String, String>,
+    device_id: String,
+}
+
+impl From<&PendingChallenge> for ChallengeRecord {
+    fn from(challenge: &PendingChallenge) -> Self {
+        ChallengeRecord {
+            user: challenge.user.clone(),
+            r1: challenge.r1.to_bytes_be(),
+            r2: challenge.r2.to_bytes_be(),
+            c: challenge.c.to_bytes_be(),
+            expires_at: challenge.expires_at,
+            metadata: challenge.metadata.clone(),
+            device_id: challenge.device_id.clone(),
+        }
+    }
+}
+
+impl From<ChallengeRecord> for PendingChallenge {
+    fn from(record: ChallengeRecord) -> Self {
+        PendingChallenge {
+            user: record.user,
+            r1: BigUint::from_bytes_be(&record.r1),
+            r2: BigUint::from_bytes_be(&record.r2),
+            c: BigUint::from_bytes_be(&record.c),
+            expires_at: record.expires_at,
+            metadata: record.metadata,
+            device_id: record.device_id,
+        }
+    }
+}
+
+// 会话在 Redis 中的编码
+#[derive(Serialize, Deserialize)]
+struct SessionRecord {
+    user: String,
+    issued_at: u64,
+    expires_at: u64,
+    auth_method: String,
+    scopes: Vec<String>,
+    metadata: HashMap<String, String>,
+    device_id: String,
+}
+
+impl From<&SessionInfo> for SessionRecord {
+    fn from(session: &SessionInfo) -> Self {
+        SessionRecord {
+            user: session.user.clone(),
+            issued_at: session.issued_at,
+            expires_at: session.expires_at,
+            auth_method: session.auth_method.clone(),
+            scopes: session.scopes.clone(),
+            metadata: session.metadata.clone(),
+            device_id: session.device_id.clone(),
+        }
+    }
+}
+
+impl From<SessionRecord> for SessionInfo {
+    fn from(record: SessionRecord) -> Self {
+        SessionInfo {
+            user: record.user,
+            issued_at: record.issued_at,
+            expires_at: record.expires_at,
+            auth_method: record.auth_method,
+            scopes: record.scopes,
+            metadata: record.metadata,
+            device_id: record.device_id,
+        }
+    }
+}
+
+/// Redis 中的挑战和会话
+#[derive(Clone)]
+pub struct RedisStore {
+    conn: ConnectionManager, // 连接，克隆后共享同一个底层连接
+    prefix: String,          // 键的前缀，多个部署共用一个 Redis 时区分
+}
+
+impl RedisStore {
+    /// 参数:
+    /// - `url`: Redis 地址，例如 `redis://127.0.0.1/`
+    /// - `prefix`: 所有键的前缀，例如 `zkp:`
+    ///
+    /// 返回:
+    /// - `StoreResult<RedisStore>`: 连接好的存储，或者连接失败的原因
+    pub async fn connect(url: &str, prefix: &str) -> StoreResult<Self> {
+        let conn = ::redis::Client::open(url)?.get_connection_manager().await?;
+        Ok(RedisStore { conn, prefix: prefix.to_string() })
+    }
+
+    fn challenge_key(&self, auth_id: &str) -> String {
+        format!("{}challenge:{}", self.prefix, auth_id)
+    }
+
+    fn session_key(&self, session_id: &str) -> String {
+        format!("{}session:{}", self.prefix, session_id)
+    }
+
+    fn user_challenges_key(&self, user: &str) -> String {
+        format!("{}user-challenges:{}", self.prefix, user)
+    }
+
+    fn user_sessions_key(&self, user: &str) -> String {
+        format!("{}user-sessions:{}", self.prefix, user)
+    }
+
+    // 保存记录并加入用户的集合；集合的 TTL 只延长不缩短，不早于其中最晚过期的记录
+    async fn put(&self, key: String, user_key: String, id: &str, value: String, expires_at: u64) -> StoreResult<()> {
+        let ttl = ttl_secs(expires_at);
+        let mut conn = self.conn.clone();
+        pipe()
+            .atomic()
+            .cmd("SET").arg(&key).arg(value).arg("EX").arg(ttl).ignore()
+            .cmd("SADD").arg(&user_key).arg(id).ignore()
+            .cmd("EXPIRE").arg(&user_key).arg(ttl).arg("GT").ignore()
+            .cmd("EXPIRE").arg(&user_key).arg(ttl).arg("NX").ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    // 取出用户集合中仍然存在的记录，顺便从集合中删除已过期的成员
+    async fn members(&self, user_key: &str, key: impl Fn(&str) -> String) -> StoreResult<Vec<(String, String)>> {
+        let mut conn = self.conn.clone();
+        let ids: Vec<String> = conn.smembers(user_key).await?;
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let keys: Vec<String> = ids.iter().map(|id| key(id)).collect();
+        let values: Vec<Option<String>> = cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+        let mut live = Vec::new();
+        let mut expired = Vec::new();
+        for (id, value) in ids.into_iter().zip(values) {
+            match value {
+                Some(value) => live.push((id, value)),
+                None => expired.push(id),
+            }
+        }
+        if !expired.is_empty() {
+            conn.srem::<_, _, ()>(user_key, expired).await?;
+        }
+        Ok(live)
+    }
+}
+
+// 调试输出只包含键的前缀
+impl fmt::Debug for RedisStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisStore").field("prefix", &self.prefix).finish()
+    }
+}
+
+// 距离过期时间的秒数，已过期的记录也保留 1 秒，由读取方按 expires_at 判断
+fn ttl_secs(expires_at: u64) -> u64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_secs();
+    expires_at.saturating_sub(now).max(1)
+}
+
+fn decode_challenge(value: &str) -> StoreResult<PendingChallenge> {
+    Ok(serde_json::from_str::<ChallengeRecord>(value)?.into())
+}
+
+fn decode_session(value: &str) -> StoreResult<SessionInfo> {
+    Ok(serde_json::from_str::<SessionRecord>(value)?.into())
+}
+
+#[tonic::async_trait]
+impl SessionStore for RedisStore {
+    async fn put_challenge(&self, auth_id: &str, challenge: PendingChallenge) -> StoreResult<()> {
+        let value = serde_json::to_string(&ChallengeRecord::from(&challenge))?;
+        self.put(self.challenge_key(auth_id), self.user_challenges_key(&challenge.user), auth_id, value, challenge.expires_at).await
+    }
+
+    async fn get_challenge(&self, auth_id: &str) -> StoreResult<Option<PendingChallenge>> {
+        let value: Option<String> = self.conn.clone().get(self.challenge_key(auth_id)).await?;
+        value.as_deref().map(decode_challenge).transpose()
+    }
+
+    // GETDEL 是一条命令，两个副本同时取同一个认证 ID 时只有一个得到挑战
+    async fn take_challenge(&self, auth_id: &str) -> StoreResult<Option<PendingChallenge>> {
+        let value: Option<String> = cmd("GETDEL").arg(self.challenge_key(auth_id)).query_async(&mut self.conn.clone()).await?;
+        let Some(challenge) = value.as_deref().map(decode_challenge).transpose()? else {
+            return Ok(None);
+        };
+        self.conn.clone().srem::<_, _, ()>(self.user_challenges_key(&challenge.user), auth_id).await?;
+        Ok(Some(challenge))
+    }
+
+    async fn count_challenges(&self, user: &str) -> StoreResult<u32> {
+        Ok(self.members(&self.user_challenges_key(user), |auth_id| self.challenge_key(auth_id)).await?.len() as u32)
+    }
+
+    async fn delete_challenges(&self, user: &str) -> StoreResult<()> {
+        let user_key = self.user_challenges_key(user);
+        let mut conn = self.conn.clone();
+        let auth_ids: Vec<String> = conn.smembers(&user_key).await?;
+        let mut keys: Vec<String> = auth_ids.iter().map(|auth_id| self.challenge_key(auth_id)).collect();
+        keys.push(user_key);
+        conn.del::<_, ()>(keys).await?;
+        Ok(())
+    }
+
+    async fn put_session(&self, session_id: &str, session: SessionInfo) -> StoreResult<()> {
+        let value = serde_json::to_string(&SessionRecord::from(&session))?;
+        self.put(self.session_key(session_id), self.user_sessions_key(&session.user), session_id, value, session.expires_at).await
+    }
+
+    async fn get_session(&self, session_id: &str) -> StoreResult<Option<SessionInfo>> {
+        let value: Option<String> = self.conn.clone().get(self.session_key(session_id)).await?;
+        value.as_deref().map(decode_session).transpose()
+    }
+
+    async fn delete_session(&self, session_id: &str) -> StoreResult<Option<SessionInfo>> {
+        let value: Option<String> = cmd("GETDEL").arg(self.session_key(session_id)).query_async(&mut self.conn.clone()).await?;
+        let Some(session) = value.as_deref().map(decode_session).transpose()? else {
+            return Ok(None);
+        };
+        self.conn.clone().srem::<_, _, ()>(self.user_sessions_key(&session.user), session_id).await?;
+        Ok(Some(session))
+    }
+
+    async fn list_sessions(&self, user: &str) -> StoreResult<Vec<(String, SessionInfo)>> {
+        let members = self.members(&self.user_sessions_key(user), |session_id| self.session_key(session_id)).await?;
+        members.into_iter().map(|(session_id, value)| Ok((session_id, decode_session(&value)?))).collect()
+    }
+
+    // 逐个 GETDEL，与其他副本同时删除同一个会话时只有一方把它计入被吊销的会话
+    async fn delete_sessions(&self, user: &str) -> StoreResult<Vec<String>> {
+        let user_key = self.user_sessions_key(user);
+        let mut conn = self.conn.clone();
+        let session_ids: Vec<String> = conn.smembers(&user_key).await?;
+        let mut deleted = Vec::new();
+        for session_id in session_ids {
+            let value: Option<String> = cmd("GETDEL").arg(self.session_key(&session_id)).query_async(&mut conn).await?;
+            conn.srem::<_, _, ()>(&user_key, &session_id).await?;
+            if value.is_some() {
+                deleted.push(session_id);
+            }
+        }
+        Ok(deleted)
+    }
+
+    // 过期的挑战和会话由 Redis 按 TTL 删除，用户集合中的残留成员在读取时清除
+    // 过期的会话已经由 Redis 删除，逐批 SCAN 会话键计数，不阻塞 Redis
+    async fn count_sessions(&self, _now: u64) -> StoreResult<u64> {
+        let mut conn = self.conn.clone();
+        let mut keys = conn.scan_match::<_, String>(format!("{}session:*", self.prefix)).await?;
+        let mut count = 0;
+        while keys.next_item().await.is_some() {
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    async fn purge_expired(&self, _now: u64) -> StoreResult<Purged> {
+        Ok(Purged::default())
+    }
+}
@@ -0,0 +1,53 @@
+//! 每个用户同时有效的会话数上限：达到上限后，新的登录吊销该用户最早建立的会话，或者被拒绝
+//!
+//! 被吊销的会话与注销一样通知吊销的订阅者，并以 `session-limit` 为原因记入审计日志。
+//! 用恢复码或监护人恢复账户时总是吊销最早的会话：账户被盗用时，攻击者持有的会话不能阻止用户恢复账户。
+//! 有效的会话数在建立会话前从 `SessionStore` 读取，同一个用户同时完成的几次登录可能短暂超过上限
+
+use clap::ValueEnum; // 命令行和配置文件中的取值
+use serde::Deserialize; // 配置文件
+use tonic::{Code, Status}; // 拒绝新的登录
+use zkp_proto::detail::error; // 错误的结构化详情
+use zkp_proto::zkp_auth::ErrorCode; // 详情中的错误分类
+
+use crate::store::SessionInfo; // 用户已有的会话
+
+/// 审计日志和吊销通知中因超过会话数上限被吊销的原因
+pub const REVOKED_BY_SESSION_LIMIT: &str = "session-limit";
+
+/// 达到会话数上限时如何处理新的登录
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SessionLimitPolicy {
+    /// 吊销最早建立的会话（默认）
+    #[default]
+    EvictOldest,
+    /// 拒绝新的登录，用户需要先注销其他会话
+    Reject,
+}
+
+/// 会话数上限，`ServerConfig::session_limit`；默认不限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionLimit {
+    pub max_sessions: u32,          // 每个用户同时有效的会话数，至少为 1
+    pub policy: SessionLimitPolicy, // 达到上限时的处理
+}
+
+impl SessionLimit {
+    // 建立新会话前需要吊销的会话 ID，按建立时间从早到晚，使新会话建立后有效的会话数不超过上限；
+    // 策略为 Reject 且 evict 为 false 时，达到上限返回 FailedPrecondition
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn sessions_to_evict(&self, user_name: &str, mut sessions: Vec<(String, SessionInfo)>, now: u64, evict: bool) -> Result<Vec<String>, Status> {
+        sessions.retain(|(_, session)| session.expires_at > now);
+        let excess = (sessions.len() + 1).saturating_sub(self.max_sessions.max(1) as usize);
+        if excess == 0 {
+            return Ok(Vec::new());
+        }
+        if self.policy == SessionLimitPolicy::Reject && !evict {
+            let message = format!("User: {} already has {} active sessions, log out of one of them first", user_name, sessions.len());
+            return Err(error(Code::FailedPrecondition, ErrorCode::TooManySessions, message));
+        }
+        sessions.sort_by(|(a_id, a), (b_id, b)| (a.issued_at, a_id).cmp(&(b.issued_at, b_id)));
+        Ok(sessions.into_iter().take(excess).map(|(session_id, _)| session_id).collect())
+    }
+}
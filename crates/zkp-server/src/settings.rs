@@ -0,0 +1,670 @@
+//! 独立运行的服务器的配置：命令行参数、环境变量和 TOML 配置文件，优先级从高到低为命令行、环境变量、配置文件、默认值
+//!
+//! 每个设置项的命令行参数、环境变量和配置文件中的键一一对应，例如 `--session-ttl-secs`、`ZKP_SESSION_TTL_SECS` 和
+//! `session_ttl_secs = 3600`；配置文件由 `--config` 或 `ZKP_CONFIG` 指定，未知的键视为错误。
+//! 嵌入服务器的程序直接构建 `ServerConfig`，不需要这个模块
+//!
+//! 在 Unix 上，服务器收到 SIGHUP 时重新读取配置，替换挑战和会话的有效期、配额、日志过滤和 TLS 证书，已经建立的连接不断开；
+//! 其他设置修改后需要重启
+
+use std::net::SocketAddr; // 监听地址
+use std::path::{Path, PathBuf}; // 配置文件、证书和群参数文件的路径
+use std::sync::Arc; // 配置中的挑战来源
+use std::time::Duration; // 锁定时间、RPC 的超时
+
+use clap::builder::BoolishValueParser; // 环境变量中的 1/0、true/false
+use clap::{Parser, Subcommand, ValueEnum}; // 命令行参数解析
+use serde::Deserialize; // 配置文件
+use tracing_subscriber::filter::Targets; // 按模块过滤日志
+use tracing_subscriber::{layer::SubscriberExt, reload, util::SubscriberInitExt, Layer, Registry}; // 日志输出，重新加载配置时替换过滤
+
+use zkp_core::{GroupParams, ZKP}; // 群参数
+
+use crate::export::ExportFormat; // 导出和导入用户的文件格式
+use crate::username::{UserNameCharset, UserNameNormalization, UserNamePolicy}; // 用户名策略
+use crate::{AdminPolicy, AdminRole, ConnectionLimits, FiatShamirChallenge, FileAuditLog, IpNet, JwtIssuer, JwtKey, Keyring, LockoutPolicy, PeerPolicy, RandomChallenge, RateLimits, ServerConfig, SessionLimit, SessionLimitPolicy}; // 由设置构建的服务器配置
+
+/// 内置群参数的名称，`group` 为其他值时视为参数文件的路径
+pub const BUILTIN_GROUP: &str = "rfc5114-1024";
+
+/// `audit_log` 取此值时审计日志写入存储后端，由调用者在创建存储后设置 `ServerConfig::audit_log`；其他值视为日志文件的路径
+pub const AUDIT_LOG_IN_STORE: &str = "store";
+
+/// 服务器的全部设置，未设置的项使用 `ServerConfig::default()` 中的默认值
+#[derive(Clone, Default, Parser, Deserialize)]
+#[command(name = "server", version, about = "Chaum-Pedersen zero-knowledge authentication server")]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// TOML 配置文件，其中的设置优先级低于命令行和环境变量
+    #[arg(long, env = "ZKP_CONFIG", value_name = "PATH")]
+    #[serde(skip)]
+    pub config: Option<PathBuf>,
+
+    /// 要执行的操作，不设置时与 serve 相同；除 serve 以外的子命令执行一次后退出，不启动服务器
+    #[command(subcommand)]
+    #[serde(skip)]
+    pub command: Option<Command>,
+
+    /// 只执行存储尚未执行的数据库迁移，然后退出，与 migrate 子命令相同；可以用环境变量在同一个部署中先单独迁移；
+    /// 后面可能跟着子命令，显式的值必须写成 `--migrate-only=false`
+    #[arg(long, env = "ZKP_MIGRATE_ONLY", num_args = 0..=1, require_equals = true, default_missing_value = "true", value_parser = BoolishValueParser::new())]
+    #[serde(skip)]
+    pub migrate_only: Option<bool>,
+
+    /// 监听地址，默认为 127.0.0.1:50051
+    #[arg(long, env = "ZKP_SERVER_ADDR")]
+    pub addr: Option<SocketAddr>,
+
+    /// Prometheus 指标的 HTTP 监听地址（GET /metrics），不设置时不提供指标
+    #[arg(long, env = "ZKP_METRICS_ADDR")]
+    pub metrics_addr: Option<SocketAddr>,
+
+    /// REST/JSON 网关的 HTTP 监听地址（POST /v1/register、/v1/challenge、/v1/verify 等），不设置时不提供网关
+    #[arg(long, env = "ZKP_GATEWAY_ADDR")]
+    pub gateway_addr: Option<SocketAddr>,
+
+    /// 群参数：rfc5114-1024（默认）或参数文件的路径（PEM、OpenSSL DH 参数或 JSON），客户端必须使用同一组参数
+    #[arg(long, env = "ZKP_GROUP")]
+    pub group: Option<String>,
+
+    /// 注册时还可以选择的群（内置群的名称或参数文件的路径），逗号分隔；用户之后一直使用注册时的群，迁移到新的群时旧用户不受影响
+    #[arg(long, env = "ZKP_EXTRA_GROUPS", value_delimiter = ',')]
+    pub extra_groups: Vec<String>,
+
+    /// 用户和会话的存储：memory（默认）、sqlite:<数据库文件>（sqlite feature）或 postgres://...（postgres feature）
+    #[arg(long, env = "ZKP_STORE")]
+    pub store: Option<String>,
+
+    /// PostgreSQL 连接池的大小
+    #[arg(long, env = "ZKP_DB_MAX_CONNECTIONS")]
+    pub db_max_connections: Option<u32>,
+
+    /// 把挑战和会话改为保存在 Redis 中（redis feature），例如 redis://127.0.0.1/
+    #[arg(long, env = "ZKP_SESSION_STORE")]
+    pub session_store: Option<String>,
+
+    /// Redis 中键的前缀，默认为 "zkp:"
+    #[arg(long, env = "ZKP_REDIS_PREFIX")]
+    pub redis_prefix: Option<String>,
+
+    /// 静态加密的密钥文件，每行 <密钥编号>:<64 个十六进制字符>，第一个密钥加密新数据；设置时存储中的用户凭据和会话 ID 被加密
+    #[arg(long, env = "ZKP_ENCRYPTION_KEY_FILE", value_name = "PATH")]
+    pub encryption_key_file: Option<PathBuf>,
+
+    /// 输出静态加密密钥的命令（sh -c），输出与密钥文件的格式相同，例如从 KMS 解密密钥；不能与 encryption_key_file 同时设置
+    #[arg(long, env = "ZKP_ENCRYPTION_KEY_COMMAND")]
+    pub encryption_key_command: Option<String>,
+
+    /// 审计日志：JSON Lines 文件的路径，或 store（写入 sqlite / postgres 存储的 audit_log 表）；不设置时不记录
+    #[arg(long, env = "ZKP_AUDIT_LOG")]
+    pub audit_log: Option<String>,
+
+    /// 挑战的有效期（秒）
+    #[arg(long, env = "ZKP_CHALLENGE_TTL_SECS")]
+    pub challenge_ttl_secs: Option<u64>,
+
+    /// 会话的有效期（秒）
+    #[arg(long, env = "ZKP_SESSION_TTL_SECS")]
+    pub session_ttl_secs: Option<u64>,
+
+    /// 后台清理过期条目的间隔（秒），为 0 时不清理
+    #[arg(long, env = "ZKP_CLEANUP_INTERVAL_SECS")]
+    pub cleanup_interval_secs: Option<u64>,
+
+    /// 同时进行的验证和子群检查数，默认为 CPU 核数
+    #[arg(long, env = "ZKP_CPU_WORKERS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub cpu_workers: Option<u64>,
+
+    /// 同时打开的连接数上限，达到上限后暂停接受新连接；不设置时不限制
+    #[arg(long, env = "ZKP_MAX_CONNECTIONS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_connections: Option<u64>,
+
+    /// 每个连接上同时进行的 RPC 数上限（HTTP/2 流）
+    #[arg(long, env = "ZKP_MAX_CONCURRENT_STREAMS", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_concurrent_streams: Option<u32>,
+
+    /// 所有连接上同时处理的 RPC 数上限，超过时排队等待
+    #[arg(long, env = "ZKP_MAX_CONCURRENT_REQUESTS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_concurrent_requests: Option<u64>,
+
+    /// 每个 RPC 的超时（秒），超时返回 Cancelled；不设置时不限制
+    #[arg(long, env = "ZKP_REQUEST_TIMEOUT_SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub request_timeout_secs: Option<u64>,
+
+    /// 请求和响应消息的最大字节数，默认为 4194304（4 MiB）
+    #[arg(long, env = "ZKP_MAX_MESSAGE_BYTES", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_message_bytes: Option<u64>,
+
+    /// 只接受这些网段中的客户端，逗号分隔，例如 10.0.0.0/8,2001:db8::/32；不设置时接受所有地址
+    #[arg(long, env = "ZKP_ALLOW_IPS", value_delimiter = ',')]
+    pub allow_ips: Vec<String>,
+
+    /// 拒绝这些网段中的客户端，逗号分隔，优先于 allow_ips
+    #[arg(long, env = "ZKP_DENY_IPS", value_delimiter = ',')]
+    pub deny_ips: Vec<String>,
+
+    /// 负载均衡器或反向代理的网段，逗号分隔；它们转发的请求按 x-forwarded-for 确定客户端地址，其他连接的 x-forwarded-for 被忽略
+    #[arg(long, env = "ZKP_TRUSTED_PROXIES", value_delimiter = ',')]
+    pub trusted_proxies: Vec<String>,
+
+    /// 挑战值的来源：random（默认）、fiat-shamir 或 fiat-shamir:<哈希函数>
+    #[arg(long, env = "ZKP_CHALLENGE_SOURCE")]
+    pub challenge_source: Option<String>,
+
+    /// 诱饵账户的用户名，逗号分隔
+    #[arg(long, env = "ZKP_HONEYTOKENS", value_delimiter = ',')]
+    pub honeytokens: Vec<String>,
+
+    /// 诱饵账户告警 POST 到的 http:// 地址
+    #[arg(long, env = "ZKP_ALERT_WEBHOOK")]
+    pub alert_webhook: Option<String>,
+
+    /// 只允许这些客户端身份（mTLS 证书主体）注册，逗号分隔
+    #[arg(long, env = "ZKP_REGISTRATION_IDENTITIES", value_delimiter = ',')]
+    pub registration_identities: Vec<String>,
+
+    /// 允许已存在的用户用旧密码对挑战的解答重新注册，默认拒绝注册已存在的用户名
+    #[arg(long, env = "ZKP_ALLOW_REREGISTRATION", num_args = 0..=1, default_missing_value = "true", value_parser = BoolishValueParser::new())]
+    pub allow_reregistration: Option<bool>,
+
+    /// 用户名的规范化：nfc（默认）、nfc-lowercase（同时不区分大小写）或 none；修改后需要用 export-users / import-users 迁移已有用户
+    #[arg(long, env = "ZKP_USER_NAME_NORMALIZATION", value_enum)]
+    pub user_name_normalization: Option<UserNameNormalization>,
+
+    /// 用户名允许的字符：unicode（默认，不包含控制字符，首尾没有空白）或 ascii（字母、数字和 . _ - @ +）
+    #[arg(long, env = "ZKP_USER_NAME_CHARSET", value_enum)]
+    pub user_name_charset: Option<UserNameCharset>,
+
+    /// 规范化后的用户名的最少字节数，默认为 1
+    #[arg(long, env = "ZKP_USER_NAME_MIN_LEN")]
+    pub user_name_min_len: Option<usize>,
+
+    /// 规范化后的用户名的最多字节数，默认为 128
+    #[arg(long, env = "ZKP_USER_NAME_MAX_LEN")]
+    pub user_name_max_len: Option<usize>,
+
+    /// 不能注册的用户名，逗号分隔，按同样的方式规范化后比较
+    #[arg(long, env = "ZKP_RESERVED_USER_NAMES", value_delimiter = ',')]
+    pub reserved_user_names: Vec<String>,
+
+    /// Register 的配额，格式为 <次数>/<s|m|h>，例如 10/m
+    #[arg(long, env = "ZKP_RATE_LIMIT_REGISTER")]
+    pub rate_limit_register: Option<String>,
+
+    /// 挑战请求的配额
+    #[arg(long, env = "ZKP_RATE_LIMIT_CHALLENGE")]
+    pub rate_limit_challenge: Option<String>,
+
+    /// 验证请求的配额
+    #[arg(long, env = "ZKP_RATE_LIMIT_VERIFY")]
+    pub rate_limit_verify: Option<String>,
+
+    /// 锁定账户的连续验证失败次数，默认为 5，为 0 时不锁定
+    #[arg(long, env = "ZKP_LOCKOUT_THRESHOLD")]
+    pub lockout_threshold: Option<u32>,
+
+    /// 第一次锁定的时间（秒），之后每次失败加倍，默认为 30
+    #[arg(long, env = "ZKP_LOCKOUT_SECS")]
+    pub lockout_secs: Option<u64>,
+
+    /// 锁定时间的上限（秒），这段时间内没有再失败时计数清零，默认为 3600
+    #[arg(long, env = "ZKP_LOCKOUT_MAX_SECS")]
+    pub lockout_max_secs: Option<u64>,
+
+    /// 每个用户同时有效的会话数上限，不设置时不限制
+    #[arg(long, env = "ZKP_MAX_SESSIONS_PER_USER", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_sessions_per_user: Option<u32>,
+
+    /// 达到会话数上限时：evict-oldest（默认，吊销最早的会话）或 reject（拒绝新的登录）；账户恢复总是吊销最早的会话
+    #[arg(long, env = "ZKP_SESSION_LIMIT_POLICY", value_enum)]
+    pub session_limit_policy: Option<SessionLimitPolicy>,
+
+    /// 管理接口的令牌，逗号分隔，每项格式为 <名称>:<角色>:<令牌>，角色为 viewer、operator 或 admin；设置时提供 AuthAdmin 服务
+    #[arg(long, env = "ZKP_ADMIN_TOKENS", value_delimiter = ',', hide_env_values = true)]
+    pub admin_tokens: Vec<String>,
+
+    /// 可以调用管理接口的 mTLS 客户端身份，逗号分隔，每项格式为 <证书主体>:<角色>
+    #[arg(long, env = "ZKP_ADMIN_IDENTITIES", value_delimiter = ',')]
+    pub admin_identities: Vec<String>,
+
+    /// HS256 JWT 的共享密钥
+    #[arg(long, env = "ZKP_JWT_SECRET", hide_env_values = true)]
+    pub jwt_secret: Option<String>,
+
+    /// EdDSA JWT 的 Ed25519 私钥种子（64 个十六进制字符）
+    #[arg(long, env = "ZKP_JWT_ED25519_SEED", hide_env_values = true)]
+    pub jwt_ed25519_seed: Option<String>,
+
+    /// JWT 的 iss 声明，默认为 zkp-server
+    #[arg(long, env = "ZKP_JWT_ISSUER")]
+    pub jwt_issuer: Option<String>,
+
+    /// 服务器证书链（PEM，tls feature）
+    #[arg(long, env = "ZKP_TLS_CERT", value_name = "PATH")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// 服务器私钥（PEM）
+    #[arg(long, env = "ZKP_TLS_KEY", value_name = "PATH")]
+    pub tls_key: Option<PathBuf>,
+
+    /// 签发客户端证书的 CA（PEM），设置时要求客户端证书
+    #[arg(long, env = "ZKP_TLS_CLIENT_CA", value_name = "PATH")]
+    pub tls_client_ca: Option<PathBuf>,
+
+    /// 也接受没有客户端证书的连接
+    #[arg(long, env = "ZKP_TLS_CLIENT_AUTH_OPTIONAL", num_args = 0..=1, default_missing_value = "true", value_parser = BoolishValueParser::new())]
+    pub tls_client_auth_optional: Option<bool>,
+
+    /// 日志过滤，格式为 <级别> 或 <模块>=<级别>，逗号分隔，默认为 info；debug 级别输出脱敏后的请求
+    #[arg(long, env = "ZKP_LOG")]
+    pub log: Option<String>,
+
+    /// 日志格式：text（默认）或 json（每行一个 JSON 对象）
+    #[arg(long, env = "ZKP_LOG_FORMAT", value_enum)]
+    pub log_format: Option<LogFormat>,
+
+    /// OTLP/gRPC collector 的地址（otel feature），例如 http://127.0.0.1:4317；设置时 RPC、网关请求和存储调用的 span 导出到这里
+    #[arg(long, env = "ZKP_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+}
+
+/// `server` 的子命令，使用与服务器相同的设置（`store`、`group` 等）打开存储
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// 启动服务器（默认）
+    Serve,
+    /// 执行存储尚未执行的数据库迁移后退出（与 --migrate-only 相同）；服务器启动时也会迁移，部署时可以先单独执行
+    Migrate,
+    /// 查看和删除用户
+    #[command(subcommand)]
+    User(UserCommand),
+    /// 生成新的群参数（PEM），可以作为 group 或 extra_groups 的参数文件；不需要存储
+    GenParams {
+        /// p 的位数
+        #[arg(long, default_value_t = 2048)]
+        p_bits: u64,
+
+        /// q 的位数，必须小于 p 的位数
+        #[arg(long, default_value_t = 256)]
+        q_bits: u64,
+
+        /// 输出文件，不设置时写到标准输出
+        #[arg(long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// 按用户名顺序导出所有用户，用于备份或迁移到其他存储；输出应当与数据库一样保护
+    ExportUsers {
+        /// 输出格式：json（每行一个用户）或 csv
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+
+        /// 同时导出明文的 TOTP 共享密钥；默认不导出，启用了 TOTP 的用户不能从这样的导出文件导入
+        #[arg(long)]
+        include_totp_secrets: bool,
+
+        /// 输出文件，不设置时写到标准输出
+        #[arg(long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// 导入 export-users 导出的用户；任何一条记录有错误时不导入任何用户
+    ImportUsers {
+        /// export-users 的输出文件
+        #[arg(value_name = "PATH")]
+        input: PathBuf,
+
+        /// 文件格式：json 或 csv
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+
+        /// 替换已存在的同名用户，默认跳过
+        #[arg(long)]
+        replace: bool,
+    },
+    /// 用第一个静态加密密钥重新加密所有用户记录，之后可以删除轮换前的旧密钥（它们加密的会话过期后）；也用于加密开启加密之前写入的用户
+    RotateKeys,
+}
+
+/// `server user` 的子命令
+#[derive(Debug, Clone, Subcommand)]
+pub enum UserCommand {
+    /// 按用户名顺序列出用户，每行一个：用户名、注册时间（Unix 时间戳）和显示名称，以制表符分隔
+    List {
+        /// 从这个用户名之后开始列出
+        #[arg(long, default_value = "")]
+        after: String,
+
+        /// 最多列出的用户数，为 0 时列出全部
+        #[arg(long, default_value_t = 0)]
+        limit: u32,
+    },
+    /// 删除用户及其挑战和会话，用户名按 user_name_normalization 规范化；会话的吊销记入审计日志
+    Delete {
+        /// 用户名
+        user: String,
+    },
+}
+
+/// `Settings::init_logging` 返回的句柄，运行中替换日志过滤；日志格式需要重启才能修改
+pub struct LogFilterHandle(reload::Handle<Targets, Registry>);
+
+impl LogFilterHandle {
+    /// 替换日志过滤，之后的日志按新的过滤输出
+    pub fn set(&self, filter: Targets) -> Result<(), String> {
+        self.0.reload(filter).map_err(|e| format!("could not replace the log filter: {}", e))
+    }
+}
+
+/// 日志的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl Settings {
+    /// 读取命令行参数和环境变量，再用配置文件补充未设置的项
+    ///
+    /// 返回:
+    /// - `Result<Settings, String>`: 配置文件无法读取或格式不正确时返回错误
+    pub fn load() -> Result<Settings, String> {
+        let settings = Settings::parse();
+        let mut settings = match settings.config.clone() {
+            Some(path) => settings.or(Settings::from_file(&path)?),
+            None => settings,
+        };
+        settings.command = settings.effective_command()?;
+        Ok(settings)
+    }
+
+    /// 要执行的子命令：设置了 `--migrate-only` 时为 migrate
+    ///
+    /// 返回:
+    /// - `Result<Option<Command>, String>`: 子命令，`--migrate-only` 与 serve、migrate 以外的子命令同时使用时返回错误
+    pub fn effective_command(&self) -> Result<Option<Command>, String> {
+        match (&self.command, self.migrate_only.unwrap_or(false)) {
+            (command, false) => Ok(command.clone()),
+            (None | Some(Command::Serve | Command::Migrate), true) => Ok(Some(Command::Migrate)),
+            (Some(_), true) => Err("--migrate-only cannot be combined with another command".to_string()),
+        }
+    }
+
+    /// 读取 TOML 配置文件
+    ///
+    /// 参数:
+    /// - `path`: 配置文件路径
+    pub fn from_file(path: &Path) -> Result<Settings, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("invalid configuration file {}: {}", path.display(), e))
+    }
+
+    /// 用 `fallback` 补充本设置中未设置的项
+    pub fn or(self, fallback: Settings) -> Settings {
+        let list = |values: Vec<String>, fallback: Vec<String>| if values.is_empty() { fallback } else { values };
+        Settings {
+            config: self.config.or(fallback.config),
+            command: self.command.or(fallback.command),
+            migrate_only: self.migrate_only.or(fallback.migrate_only),
+            addr: self.addr.or(fallback.addr),
+            metrics_addr: self.metrics_addr.or(fallback.metrics_addr),
+            gateway_addr: self.gateway_addr.or(fallback.gateway_addr),
+            group: self.group.or(fallback.group),
+            extra_groups: list(self.extra_groups, fallback.extra_groups),
+            store: self.store.or(fallback.store),
+            db_max_connections: self.db_max_connections.or(fallback.db_max_connections),
+            session_store: self.session_store.or(fallback.session_store),
+            redis_prefix: self.redis_prefix.or(fallback.redis_prefix),
+            encryption_key_file: self.encryption_key_file.or(fallback.encryption_key_file),
+            encryption_key_command: self.encryption_key_command.or(fallback.encryption_key_command),
+            audit_log: self.audit_log.or(fallback.audit_log),
+            challenge_ttl_secs: self.challenge_ttl_secs.or(fallback.challenge_ttl_secs),
+            session_ttl_secs: self.session_ttl_secs.or(fallback.session_ttl_secs),
+            cleanup_interval_secs: self.cleanup_interval_secs.or(fallback.cleanup_interval_secs),
+            cpu_workers: self.cpu_workers.or(fallback.cpu_workers),
+            max_connections: self.max_connections.or(fallback.max_connections),
+            max_concurrent_streams: self.max_concurrent_streams.or(fallback.max_concurrent_streams),
+            max_concurrent_requests: self.max_concurrent_requests.or(fallback.max_concurrent_requests),
+            request_timeout_secs: self.request_timeout_secs.or(fallback.request_timeout_secs),
+            max_message_bytes: self.max_message_bytes.or(fallback.max_message_bytes),
+            allow_ips: list(self.allow_ips, fallback.allow_ips),
+            deny_ips: list(self.deny_ips, fallback.deny_ips),
+            trusted_proxies: list(self.trusted_proxies, fallback.trusted_proxies),
+            challenge_source: self.challenge_source.or(fallback.challenge_source),
+            honeytokens: list(self.honeytokens, fallback.honeytokens),
+            alert_webhook: self.alert_webhook.or(fallback.alert_webhook),
+            registration_identities: list(self.registration_identities, fallback.registration_identities),
+            allow_reregistration: self.allow_reregistration.or(fallback.allow_reregistration),
+            user_name_normalization: self.user_name_normalization.or(fallback.user_name_normalization),
+            user_name_charset: self.user_name_charset.or(fallback.user_name_charset),
+            user_name_min_len: self.user_name_min_len.or(fallback.user_name_min_len),
+            user_name_max_len: self.user_name_max_len.or(fallback.user_name_max_len),
+            reserved_user_names: list(self.reserved_user_names, fallback.reserved_user_names),
+            rate_limit_register: self.rate_limit_register.or(fallback.rate_limit_register),
+            rate_limit_challenge: self.rate_limit_challenge.or(fallback.rate_limit_challenge),
+            rate_limit_verify: self.rate_limit_verify.or(fallback.rate_limit_verify),
+            lockout_threshold: self.lockout_threshold.or(fallback.lockout_threshold),
+            lockout_secs: self.lockout_secs.or(fallback.lockout_secs),
+            lockout_max_secs: self.lockout_max_secs.or(fallback.lockout_max_secs),
+            max_sessions_per_user: self.max_sessions_per_user.or(fallback.max_sessions_per_user),
+            session_limit_policy: self.session_limit_policy.or(fallback.session_limit_policy),
+            admin_tokens: list(self.admin_tokens, fallback.admin_tokens),
+            admin_identities: list(self.admin_identities, fallback.admin_identities),
+            jwt_secret: self.jwt_secret.or(fallback.jwt_secret),
+            jwt_ed25519_seed: self.jwt_ed25519_seed.or(fallback.jwt_ed25519_seed),
+            jwt_issuer: self.jwt_issuer.or(fallback.jwt_issuer),
+            tls_cert: self.tls_cert.or(fallback.tls_cert),
+            tls_key: self.tls_key.or(fallback.tls_key),
+            tls_client_ca: self.tls_client_ca.or(fallback.tls_client_ca),
+            tls_client_auth_optional: self.tls_client_auth_optional.or(fallback.tls_client_auth_optional),
+            log: self.log.or(fallback.log),
+            log_format: self.log_format.or(fallback.log_format),
+            otlp_endpoint: self.otlp_endpoint.or(fallback.otlp_endpoint),
+        }
+    }
+
+    /// 初始化全局的日志输出，写到标准错误；设置了 `otlp_endpoint` 时同时以 OTLP 导出 span（日志过滤同样适用）。
+    /// 必须在 tokio 运行时中调用；嵌入服务器的程序自己初始化 tracing 的订阅者
+    ///
+    /// 返回:
+    /// - `Result<LogFilterHandle, String>`: 重新加载配置时替换日志过滤的句柄，日志过滤的格式不正确、
+    ///   或者设置了 `otlp_endpoint` 但没有 otel feature 时返回错误
+    pub fn init_logging(&self) -> Result<LogFilterHandle, String> {
+        let (filter, handle) = reload::Layer::new(self.log_filter()?);
+        let layer = tracing_subscriber::fmt::layer().with_writer(std::io::stderr);
+        let layer = match self.log_format.unwrap_or(LogFormat::Text) {
+            LogFormat::Text => layer.boxed(),
+            LogFormat::Json => layer.json().with_current_span(true).boxed(),
+        };
+        let endpoint = self.otlp_endpoint.as_deref().map(str::trim).filter(|endpoint| !endpoint.is_empty());
+        #[cfg(feature = "otel")]
+        let otel = endpoint.map(crate::otel::layer).transpose()?;
+        #[cfg(not(feature = "otel"))]
+        let otel = match endpoint {
+            Some(_) => return Err("OTLP export requires the otel feature".to_string()),
+            None => None::<tracing_subscriber::layer::Identity>,
+        };
+        tracing_subscriber::registry().with(filter).with(layer).with(otel).init();
+        Ok(LogFilterHandle(handle))
+    }
+
+    /// 由 `log` 解析日志过滤，未设置时为 info
+    pub fn log_filter(&self) -> Result<Targets, String> {
+        self.log.as_deref().unwrap_or("info").parse().map_err(|e| format!("invalid log filter {:?}: {}", self.log.as_deref().unwrap_or_default(), e))
+    }
+
+    /// 读取静态加密的密钥：`encryption_key_file` 或 `encryption_key_command` 的输出，都没有设置时不加密
+    ///
+    /// 返回:
+    /// - `Result<Option<Keyring>, String>`: 两者同时设置、文件无法读取、命令失败或密钥格式不正确时返回错误
+    pub fn keyring(&self) -> Result<Option<Keyring>, String> {
+        match (&self.encryption_key_file, self.encryption_key_command.as_deref().map(str::trim).filter(|command| !command.is_empty())) {
+            (Some(_), Some(_)) => Err("encryption_key_file and encryption_key_command cannot both be set".to_string()),
+            (Some(path), None) => Keyring::from_file(path).map(Some),
+            (None, Some(command)) => Keyring::from_command(command).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// 由设置构建服务器配置，存储由调用者按 `store` 等设置另行创建
+    ///
+    /// 返回:
+    /// - `Result<ServerConfig, String>`: 设置的值无效或文件无法读取时返回错误
+    pub fn server_config(&self) -> Result<ServerConfig, String> {
+        let mut config = ServerConfig::default();
+        if let Some(addr) = self.addr {
+            config.addr = addr;
+        }
+        config.metrics_addr = self.metrics_addr;
+        config.gateway_addr = self.gateway_addr;
+        if let Some(group) = &self.group {
+            config.params = load_group(group)?;
+        }
+        config.extra_params = trimmed(&self.extra_groups).iter().map(|group| load_group(group)).collect::<Result<_, _>>()?;
+        if let Some(ttl) = self.challenge_ttl_secs {
+            config.challenge_ttl_secs = ttl;
+        }
+        if let Some(ttl) = self.session_ttl_secs {
+            config.session_ttl_secs = ttl;
+        }
+        if let Some(interval) = self.cleanup_interval_secs {
+            config.cleanup_interval_secs = interval;
+        }
+        if let Some(workers) = self.cpu_workers {
+            config.cpu_workers = workers as usize;
+        }
+        config.connection_limits = ConnectionLimits {
+            max_connections: self.max_connections.map(|max| max as usize),
+            max_concurrent_streams: self.max_concurrent_streams,
+            max_concurrent_requests: self.max_concurrent_requests.map(|max| max as usize),
+            request_timeout: self.request_timeout_secs.map(Duration::from_secs),
+            max_message_bytes: self.max_message_bytes.map_or(config.connection_limits.max_message_bytes, |max| max as usize),
+        };
+        let networks = |values: &[String]| trimmed(values).iter().map(|net| net.parse::<IpNet>()).collect::<Result<Vec<_>, _>>();
+        config.peers = PeerPolicy { allow: networks(&self.allow_ips)?, deny: networks(&self.deny_ips)?, trusted_proxies: networks(&self.trusted_proxies)? };
+        if let Some(source) = &self.challenge_source {
+            config.challenge_source = match (source.as_str(), source.strip_prefix("fiat-shamir:")) {
+                ("random", _) => Arc::new(RandomChallenge),
+                ("fiat-shamir", _) => Arc::new(FiatShamirChallenge::default()),
+                (_, Some(hash)) => Arc::new(FiatShamirChallenge { hash: hash.parse()? }),
+                _ => return Err(format!("unknown challenge source {:?}, expected random, fiat-shamir or fiat-shamir:<hash>", source)),
+            };
+        }
+        config.honeytokens = trimmed(&self.honeytokens);
+        config.alert_webhook = self.alert_webhook.clone().filter(|url| !url.is_empty());
+        config.registration_identities = trimmed(&self.registration_identities);
+        config.allow_reregistration = self.allow_reregistration.unwrap_or(false);
+        config.user_names = self.user_name_policy()?;
+        config.audit_log = match self.audit_log.as_deref().map(str::trim) {
+            None | Some("") | Some(AUDIT_LOG_IN_STORE) => None,
+            Some(path) => Some(Arc::new(FileAuditLog::open(path).map_err(|e| e.to_string())?)),
+        };
+
+        let quota = |value: &Option<String>| value.as_deref().map(str::parse).transpose();
+        config.rate_limits = RateLimits { register: quota(&self.rate_limit_register)?, challenge: quota(&self.rate_limit_challenge)?, verify: quota(&self.rate_limit_verify)? };
+        config.lockout = match self.lockout_threshold {
+            Some(0) => None,
+            threshold => {
+                let default = LockoutPolicy::default();
+                let policy = LockoutPolicy {
+                    threshold: threshold.unwrap_or(default.threshold),
+                    base: self.lockout_secs.map_or(default.base, Duration::from_secs),
+                    max: self.lockout_max_secs.map_or(default.max, Duration::from_secs),
+                };
+                if policy.base > policy.max {
+                    return Err("lockout_secs must not be greater than lockout_max_secs".to_string());
+                }
+                Some(policy)
+            }
+        };
+
+        config.session_limit = match (self.max_sessions_per_user, self.session_limit_policy) {
+            (Some(max_sessions), policy) => Some(SessionLimit { max_sessions, policy: policy.unwrap_or_default() }),
+            (None, Some(_)) => return Err("session_limit_policy requires max_sessions_per_user".to_string()),
+            (None, None) => None,
+        };
+
+        config.admin = self.admin_policy()?;
+
+        let jwt_key = match (&self.jwt_secret, &self.jwt_ed25519_seed) {
+            (Some(_), Some(_)) => return Err("set only one of jwt_secret and jwt_ed25519_seed".to_string()),
+            (Some(secret), None) => Some(JwtKey::Hs256(secret.clone().into_bytes())),
+            (None, Some(seed)) => {
+                let seed: [u8; 32] = hex::decode(seed.trim()).ok().and_then(|seed| seed.try_into().ok()).ok_or("jwt_ed25519_seed must be 32 hex-encoded bytes")?;
+                Some(JwtKey::EdDsa(ed25519_dalek::SigningKey::from_bytes(&seed)))
+            }
+            (None, None) => None,
+        };
+        config.jwt = jwt_key.map(|key| JwtIssuer::new(key, self.jwt_issuer.clone().unwrap_or_else(|| "zkp-server".to_string())));
+
+        match (&self.tls_cert, &self.tls_key) {
+            #[cfg(feature = "tls")]
+            (Some(cert), Some(key)) => {
+                let mut tls = crate::TlsConfig::from_files(cert, key).map_err(|e| format!("could not read the TLS certificate: {}", e))?;
+                if let Some(ca) = &self.tls_client_ca {
+                    tls = tls.with_client_ca(std::fs::read(ca).map_err(|e| format!("could not read {}: {}", ca.display(), e))?);
+                    tls.client_auth_optional = self.tls_client_auth_optional.unwrap_or(false);
+                }
+                config.tls = Some(tls);
+            }
+            #[cfg(not(feature = "tls"))]
+            (Some(_), Some(_)) => return Err("TLS requires the tls feature".to_string()),
+            (None, None) if self.tls_client_ca.is_none() => {}
+            _ => return Err("tls_cert and tls_key must be set together, and tls_client_ca requires both".to_string()),
+        }
+        Ok(config)
+    }
+
+    // 用户名策略，未设置的项使用默认值；长度范围不能为空
+    fn user_name_policy(&self) -> Result<UserNamePolicy, String> {
+        let default = UserNamePolicy::default();
+        let policy = UserNamePolicy {
+            normalization: self.user_name_normalization.unwrap_or(default.normalization),
+            charset: self.user_name_charset.unwrap_or(default.charset),
+            min_len: self.user_name_min_len.unwrap_or(default.min_len).max(1),
+            max_len: self.user_name_max_len.unwrap_or(default.max_len),
+            reserved: trimmed(&self.reserved_user_names),
+        };
+        if policy.min_len > policy.max_len {
+            return Err("user_name_min_len must not be greater than user_name_max_len".to_string());
+        }
+        Ok(policy)
+    }
+
+    // 管理令牌和客户端身份，都没有设置时不提供管理接口；错误信息不包含令牌
+    fn admin_policy(&self) -> Result<Option<AdminPolicy>, String> {
+        let role = |name: &str| AdminRole::from_name(name.trim()).ok_or_else(|| format!("unknown admin role {:?}, expected viewer, operator or admin", name));
+        let (tokens, identities) = (trimmed(&self.admin_tokens), trimmed(&self.admin_identities));
+        if tokens.is_empty() && identities.is_empty() {
+            return Ok(None);
+        }
+        let mut policy = AdminPolicy::default();
+        for entry in &tokens {
+            match entry.splitn(3, ':').collect::<Vec<_>>()[..] {
+                [name, role_name, token] if !name.is_empty() && !token.is_empty() => policy = policy.with_token(token, name, role(role_name)?),
+                _ => return Err("admin_tokens entries must look like <name>:<role>:<token>".to_string()),
+            }
+        }
+        for entry in &identities {
+            match entry.rsplit_once(':') {
+                Some((subject, role_name)) if !subject.is_empty() => policy = policy.with_client_identity(subject, role(role_name)?),
+                _ => return Err(format!("invalid admin identity {:?}, expected <subject>:<role>", entry)),
+            }
+        }
+        Ok(Some(policy))
+    }
+}
+
+// 去掉列表项两端的空白和空项
+fn trimmed(values: &[String]) -> Vec<String> {
+    values.iter().map(|value| value.trim()).filter(|value| !value.is_empty()).map(str::to_string).collect()
+}
+
+// 内置群参数或参数文件；文件中的参数通过检查后在进程的整个生命周期中使用
+fn load_group(group: &str) -> Result<&'static GroupParams, String> {
+    if group == BUILTIN_GROUP {
+        return Ok(GroupParams::rfc5114_1024());
+    }
+    let text = std::fs::read_to_string(group).map_err(|e| format!("could not read group parameters {}: {}", group, e))?;
+    let params = ZKP::load_params(&text).ok_or_else(|| format!("{} does not contain valid group parameters", group))?;
+    Ok(Box::leak(Box::new(params)))
+}
@@ -0,0 +1,78 @@
+//! 分片的并发映射表：键按哈希值分到固定数量的分片，每个分片由各自的 `RwLock` 保护，
+//! 不同用户的挑战、会话和待完成登录通常落在不同的分片上，互不等待；读取只取读锁
+//!
+//! 锁中毒（持锁的线程 panic）时继续使用分片中的数据，而不是让之后访问该分片的每个请求都 panic：
+//! 所有修改都是单个 `HashMap` 操作或在副本上完成后替换，panic 不会留下改了一半的条目
+
+use std::borrow::Borrow; // 用 &str 查找 String 键
+use std::collections::hash_map::RandomState; // 选择分片的哈希函数
+use std::collections::HashMap; // 每个分片中的条目
+use std::hash::{BuildHasher, Hash}; // 计算键的哈希值
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard}; // 分片的锁
+
+// 分片数，远大于常见的 CPU 核数，使并发的请求很少落在同一个分片上
+const SHARDS: usize = 64;
+
+pub(crate) struct ShardedMap<K, V> {
+    shards: Box<[RwLock<HashMap<K, V>>]>, // 各分片的条目
+    hasher: RandomState,                  // 每个映射表使用不同的随机种子，外部无法构造落在同一分片的键
+}
+
+impl<K, V> Default for ShardedMap<K, V> {
+    fn default() -> Self {
+        ShardedMap { shards: (0..SHARDS).map(|_| RwLock::new(HashMap::new())).collect(), hasher: RandomState::new() }
+    }
+}
+
+impl<K: Hash + Eq, V> ShardedMap<K, V> {
+    // 键所在的分片
+    fn shard<Q: Hash + ?Sized>(&self, key: &Q) -> &RwLock<HashMap<K, V>> {
+        &self.shards[self.hasher.hash_one(key) as usize % SHARDS]
+    }
+
+    /// 键所在分片的读锁，其他分片不受影响
+    pub(crate) fn read<Q>(&self, key: &Q) -> RwLockReadGuard<'_, HashMap<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + ?Sized,
+    {
+        self.shard(key).read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 键所在分片的写锁，同一个键的读取、修改和删除在持有期间是原子的
+    pub(crate) fn write<Q>(&self, key: &Q) -> RwLockWriteGuard<'_, HashMap<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + ?Sized,
+    {
+        self.shard(key).write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 逐个分片删除 `keep` 返回 false 的条目，返回删除的条目数；不同分片之间不是原子的
+    pub(crate) fn retain(&self, mut keep: impl FnMut(&K, &mut V) -> bool) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| {
+                let mut shard = shard.write().unwrap_or_else(PoisonError::into_inner);
+                let before = shard.len();
+                shard.retain(|key, value| keep(key, value));
+                before - shard.len()
+            })
+            .sum()
+    }
+
+    /// 逐个分片取读锁，收集 `select` 返回的值
+    pub(crate) fn collect<T>(&self, mut select: impl FnMut(&K, &V) -> Option<T>) -> Vec<T> {
+        let mut selected = Vec::new();
+        for shard in self.shards.iter() {
+            let shard = shard.read().unwrap_or_else(PoisonError::into_inner);
+            selected.extend(shard.iter().filter_map(|(key, value)| select(key, value)));
+        }
+        selected
+    }
+
+    /// 条目总数
+    pub(crate) fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.read().unwrap_or_else(PoisonError::into_inner).len()).sum()
+    }
+}
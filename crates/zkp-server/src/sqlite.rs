@@ -0,0 +1,541 @@
+//! SQLite 存储（sqlite feature）：用户、挑战、会话和审计日志保存在一个数据库文件中，服务器重启后仍然有效
+//!
+//! 打开数据库时按 `PRAGMA user_version` 依次执行尚未执行的迁移（见 `migrate` 模块），新增表或列时在 `MIGRATIONS` 末尾追加，不修改已有的迁移
+//!
+//! 所有操作共享一个连接，在 tokio 的阻塞线程池中执行：等待连接、其他进程的写锁和磁盘 I/O 时不占用处理请求的工作线程
+//!
+//! 同一台机器上的几个服务器进程可以打开同一个数据库文件：挑战和会话只保存在数据库中，任何进程都可以处理协议的任何一步；
+//! 另一个进程正在写入时等待 `BUSY_TIMEOUT`，而不是立即失败
+
+use std::collections::HashMap; // 元数据
+use std::fmt; // 调试输出不包含数据库内容
+use std::path::Path; // 数据库文件路径
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError}; // 所有操作共享一个连接，阻塞线程中的操作持有它
+use std::time::Duration; // 等待其他进程的写锁
+
+use num_bigint::BigUint; // 公开值、承诺和挑战值
+use rusqlite::{params, Connection, OptionalExtension, Row, TransactionBehavior}; // SQLite 连接和查询
+use serde::{de::DeserializeOwned, Serialize}; // JSON 列的编码和解码
+use tokio::sync::oneshot; // update_user 在阻塞线程和当前任务之间传递用户记录
+use tokio::task::JoinHandle; // 阻塞线程池中的操作
+
+use zkp_proto::zkp_auth::KdfParams; // 用户记录中的 KDF 参数
+
+use crate::audit::{AuditEvent, AuditKind, AuditLog, AuditQuery}; // 审计日志接口和事件
+use crate::migrate; // 版本化迁移
+use crate::store::{PendingChallenge, Purged, SessionInfo, SessionStore, StoreError, StoreResult, UserInfo, UserStore, UserUpdate}; // 存储接口和记录
+
+// 其他进程持有写锁时，一次操作最多等待的时间
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 数据库结构的迁移，第 i 个迁移执行后 user_version 为 i + 1
+const MIGRATIONS: &[&str] = &[
+    // 1: 用户、挑战和会话；列表和映射以 JSON 保存
+    "CREATE TABLE users (
+        name TEXT PRIMARY KEY,
+        y1 BLOB NOT NULL,
+        y2 BLOB NOT NULL,
+        salt BLOB NOT NULL,
+        kdf_algorithm TEXT,
+        kdf_iterations INTEGER,
+        scopes TEXT NOT NULL,
+        metadata TEXT NOT NULL,
+        display_name TEXT NOT NULL,
+        contact TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        recovery_codes TEXT NOT NULL,
+        reset_required INTEGER NOT NULL,
+        guardians TEXT NOT NULL,
+        guardian_threshold INTEGER NOT NULL
+    );
+    CREATE TABLE challenges (
+        auth_id TEXT PRIMARY KEY,
+        user TEXT NOT NULL,
+        r1 BLOB NOT NULL,
+        r2 BLOB NOT NULL,
+        c BLOB NOT NULL,
+        expires_at INTEGER NOT NULL,
+        metadata TEXT NOT NULL,
+        device_id TEXT NOT NULL
+    );
+    CREATE INDEX challenges_user ON challenges (user);
+    CREATE TABLE sessions (
+        session_id TEXT PRIMARY KEY,
+        user TEXT NOT NULL,
+        issued_at INTEGER NOT NULL,
+        expires_at INTEGER NOT NULL,
+        auth_method TEXT NOT NULL,
+        scopes TEXT NOT NULL,
+        metadata TEXT NOT NULL,
+        device_id TEXT NOT NULL
+    );
+    CREATE INDEX sessions_user ON sessions (user);",
+    // 2: TOTP 第二因素的共享密钥，已有用户为空（未启用）
+    "ALTER TABLE users ADD COLUMN totp_secret BLOB NOT NULL DEFAULT X'';",
+    // 3: 注册时的群的参数集标识，已有用户为空（服务器的默认群）
+    "ALTER TABLE users ADD COLUMN params_hash BLOB NOT NULL DEFAULT X'';",
+    // 4: 审计日志，只插入不修改；AUTOINCREMENT 保证序号不会重复使用
+    "CREATE TABLE audit_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        at INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        user TEXT NOT NULL,
+        success INTEGER NOT NULL,
+        reason TEXT NOT NULL,
+        remote_addr TEXT NOT NULL,
+        correlation_id TEXT NOT NULL,
+        session TEXT NOT NULL
+    );
+    CREATE INDEX audit_log_user ON audit_log (user, id);",
+    // 5: 监护人被指定时账户的指纹；已有的记录为空，其中的监护人需要重新指定才能批准恢复
+    "ALTER TABLE users ADD COLUMN guardian_bindings TEXT NOT NULL DEFAULT '[]';",
+];
+
+// 查询用户记录时读取的列，顺序与 user_from_row 一致
+const USER_COLUMNS: &str = "y1, y2, salt, kdf_algorithm, kdf_iterations, scopes, metadata, display_name, contact, created_at, recovery_codes, reset_required, guardians, guardian_threshold, totp_secret, params_hash, guardian_bindings";
+
+// 查询挑战时读取的列，顺序与 challenge_from_row 一致
+const CHALLENGE_COLUMNS: &str = "user, r1, r2, c, expires_at, metadata, device_id";
+
+// 查询会话时读取的列，顺序与 session_from_row 一致
+const SESSION_COLUMNS: &str = "user, issued_at, expires_at, auth_method, scopes, metadata, device_id";
+
+// 查询审计事件时读取的列，顺序与 audit_event_from_row 一致
+const AUDIT_COLUMNS: &str = "id, at, kind, user, success, reason, remote_addr, correlation_id, session";
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(err: rusqlite::Error) -> Self {
+        StoreError(format!("sqlite: {}", err))
+    }
+}
+
+/// SQLite 中的用户、挑战和会话
+pub struct SqliteStore {
+    conn: Arc<Mutex<Connection>>, // 数据库连接
+}
+
+impl SqliteStore {
+    /// 打开（不存在时创建）数据库文件，并执行尚未执行的迁移
+    ///
+    /// 参数:
+    /// - `path`: 数据库文件路径
+    ///
+    /// 返回:
+    /// - `StoreResult<SqliteStore>`: 打开的存储，或者打开和迁移失败的原因
+    pub fn open(path: impl AsRef<Path>) -> StoreResult<Self> {
+        SqliteStore::with_connection(Connection::open(path)?)
+    }
+
+    /// 打开只存在于内存中的数据库，用于测试
+    pub fn open_in_memory() -> StoreResult<Self> {
+        SqliteStore::with_connection(Connection::open_in_memory()?)
+    }
+
+    // 执行迁移后包装连接
+    fn with_connection(mut conn: Connection) -> StoreResult<Self> {
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        migrate(&mut conn)?;
+        Ok(SqliteStore { conn: Arc::new(Mutex::new(conn)) })
+    }
+
+    // 在阻塞线程池中持有连接执行 f
+    fn spawn<T, F>(&self, f: F) -> JoinHandle<rusqlite::Result<T>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let conn = Arc::clone(&self.conn);
+        tokio::task::spawn_blocking(move || f(&mut lock(&conn)))
+    }
+
+    // 在阻塞线程池中执行 f 并等待结果
+    async fn run<T, F>(&self, f: F) -> StoreResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        joined(self.spawn(f)).await
+    }
+}
+
+// 取得连接的锁；持有锁的操作 panic 后锁被毒化，但未提交的事务在 panic 时已经回滚，连接仍然可以使用
+fn lock(conn: &Mutex<Connection>) -> MutexGuard<'_, Connection> {
+    conn.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// 等待阻塞线程中的操作，操作 panic 时作为存储错误返回
+async fn joined<T>(task: JoinHandle<rusqlite::Result<T>>) -> StoreResult<T> {
+    Ok(task.await.map_err(|err| StoreError(format!("sqlite: operation did not complete: {}", err)))??)
+}
+
+// 数据库内容是用户记录和会话 ID，调试输出不包含
+impl fmt::Debug for SqliteStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let conn = lock(&self.conn);
+        f.debug_struct("SqliteStore").field("path", &conn.path()).finish()
+    }
+}
+
+// 在一个事务中执行 user_version 之后的迁移，比代码更新的数据库拒绝打开
+// 事务开始时即取得写锁，几个进程同时打开新数据库时只有一个执行迁移，其他进程等待后读到新的 user_version
+fn migrate(conn: &mut Connection) -> StoreResult<()> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let version: usize = tx.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    let pending = migrate::pending("sqlite", version, MIGRATIONS)?;
+    for migration in &MIGRATIONS[pending.clone()] {
+        tx.execute_batch(migration)?;
+    }
+    tx.pragma_update(None, "user_version", pending.end)?;
+    tx.commit()?;
+    migrate::applied("sqlite", pending);
+    Ok(())
+}
+
+// 列表和映射编码为 JSON
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("lists and maps of strings and bytes always serialize")
+}
+
+// 读取 JSON 列，内容无法解析时作为列类型错误返回
+fn json_column<T: DeserializeOwned>(row: &Row, index: usize) -> rusqlite::Result<T> {
+    let text: String = row.get(index)?;
+    serde_json::from_str(&text).map_err(|err| rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(err)))
+}
+
+// 大整数列，大端字节
+fn biguint_column(row: &Row, index: usize) -> rusqlite::Result<BigUint> {
+    Ok(BigUint::from_bytes_be(&row.get::<_, Vec<u8>>(index)?))
+}
+
+fn user_from_row(row: &Row) -> rusqlite::Result<UserInfo> {
+    let kdf = match (row.get::<_, Option<String>>(3)?, row.get::<_, Option<u32>>(4)?) {
+        (Some(algorithm), Some(iterations)) => Some(KdfParams { algorithm, iterations }),
+        _ => None,
+    };
+    Ok(UserInfo {
+        y1: biguint_column(row, 0)?,
+        y2: biguint_column(row, 1)?,
+        salt: row.get(2)?,
+        kdf,
+        scopes: json_column(row, 5)?,
+        metadata: json_column(row, 6)?,
+        display_name: row.get(7)?,
+        contact: row.get(8)?,
+        created_at: row.get(9)?,
+        recovery_codes: json_column(row, 10)?,
+        reset_required: row.get(11)?,
+        guardians: json_column(row, 12)?,
+        guardian_bindings: json_column(row, 16)?,
+        guardian_threshold: row.get(13)?,
+        totp_secret: row.get(14)?,
+        group: row.get(15)?,
+    })
+}
+
+fn challenge_from_row(row: &Row) -> rusqlite::Result<PendingChallenge> {
+    Ok(PendingChallenge {
+        user: row.get(0)?,
+        r1: biguint_column(row, 1)?,
+        r2: biguint_column(row, 2)?,
+        c: biguint_column(row, 3)?,
+        expires_at: row.get(4)?,
+        metadata: json_column(row, 5)?,
+        device_id: row.get(6)?,
+    })
+}
+
+fn session_from_row(row: &Row) -> rusqlite::Result<SessionInfo> {
+    Ok(SessionInfo {
+        user: row.get(0)?,
+        issued_at: row.get(1)?,
+        expires_at: row.get(2)?,
+        auth_method: row.get(3)?,
+        scopes: json_column(row, 4)?,
+        metadata: json_column::<HashMap<String, String>>(row, 5)?,
+        device_id: row.get(6)?,
+    })
+}
+
+fn audit_event_from_row(row: &Row) -> rusqlite::Result<AuditEvent> {
+    let kind: String = row.get(2)?;
+    let kind = AuditKind::from_name(&kind).ok_or_else(|| rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, format!("unknown audit event kind {:?}", kind).into()))?;
+    Ok(AuditEvent {
+        id: row.get(0)?,
+        at: row.get(1)?,
+        kind,
+        user: row.get(3)?,
+        success: row.get(4)?,
+        reason: row.get(5)?,
+        remote_addr: row.get(6)?,
+        correlation_id: row.get(7)?,
+        session: row.get(8)?,
+    })
+}
+
+// 插入用户记录，返回写入的行数；conflict 是用户名已存在时的处理，替换（OR REPLACE）或者跳过（ON CONFLICT(name) DO NOTHING）
+fn write_user(conn: &Connection, user: &str, info: &UserInfo, conflict: Conflict) -> rusqlite::Result<usize> {
+    let (or_replace, on_conflict) = match conflict {
+        Conflict::Replace => (" OR REPLACE", ""),
+        Conflict::Skip => ("", " ON CONFLICT(name) DO NOTHING"),
+    };
+    conn.execute(
+        &format!(
+            "INSERT{} INTO users (name, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18){}",
+            or_replace, USER_COLUMNS, on_conflict
+        ),
+        params![
+            user,
+            info.y1.to_bytes_be(),
+            info.y2.to_bytes_be(),
+            info.salt,
+            info.kdf.as_ref().map(|kdf| &kdf.algorithm),
+            info.kdf.as_ref().map(|kdf| kdf.iterations),
+            to_json(&info.scopes),
+            to_json(&info.metadata),
+            info.display_name,
+            info.contact,
+            info.created_at,
+            to_json(&info.recovery_codes),
+            info.reset_required,
+            to_json(&info.guardians),
+            info.guardian_threshold,
+            info.totp_secret,
+            info.group,
+            to_json(&info.guardian_bindings),
+        ],
+    )
+}
+
+// 写入用户记录时用户名已存在的处理
+#[derive(Clone, Copy)]
+enum Conflict {
+    Replace, // 替换已有的记录
+    Skip,    // 保留已有的记录，不写入
+}
+
+fn read_user(conn: &Connection, user: &str) -> rusqlite::Result<Option<UserInfo>> {
+    conn.query_row(&format!("SELECT {} FROM users WHERE name = ?1", USER_COLUMNS), [user], user_from_row).optional()
+}
+
+#[tonic::async_trait]
+impl UserStore for SqliteStore {
+    async fn put_user(&self, user: &str, info: UserInfo) -> StoreResult<()> {
+        let user = user.to_string();
+        self.run(move |conn| write_user(conn, &user, &info, Conflict::Replace)).await?;
+        Ok(())
+    }
+
+    // 检查和写入是同一条语句，打开同一个数据库的其他进程也不能在两者之间插入同名用户
+    async fn create_user(&self, user: &str, info: UserInfo) -> StoreResult<bool> {
+        let user = user.to_string();
+        Ok(self.run(move |conn| write_user(conn, &user, &info, Conflict::Skip)).await? == 1)
+    }
+
+    async fn get_user(&self, user: &str) -> StoreResult<Option<UserInfo>> {
+        let user = user.to_string();
+        self.run(move |conn| read_user(conn, &user)).await
+    }
+
+    // 读取、修改和写回在同一个事务中完成，update 放弃时回滚
+    // 事务开始时即取得写锁，其他进程不能在读取和写回之间修改这条记录
+    // 事务在阻塞线程中进行，update 不一定能移到其他线程，读出的记录交给当前任务修改后再送回；请求被取消时回滚
+    async fn update_user(&self, user: &str, update: UserUpdate<'_>) -> StoreResult<bool> {
+        let user = user.to_string();
+        let (read_sender, read) = oneshot::channel();
+        let (write, write_receiver) = oneshot::channel::<Option<UserInfo>>();
+        let task = self.spawn(move |conn| {
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let Some(info) = read_user(&tx, &user)? else {
+                let _ = read_sender.send(None);
+                return Ok(false);
+            };
+            let _ = read_sender.send(Some(info));
+            // 等待修改后的记录，update 放弃或请求被取消时不写回
+            let Ok(Some(info)) = write_receiver.blocking_recv() else {
+                return Ok(false);
+            };
+            write_user(&tx, &user, &info, Conflict::Replace)?;
+            tx.commit()?;
+            Ok(true)
+        });
+        if let Ok(Some(mut info)) = read.await {
+            let _ = write.send(update(&mut info).then_some(info));
+        }
+        joined(task).await
+    }
+
+    async fn delete_user(&self, user: &str) -> StoreResult<bool> {
+        let user = user.to_string();
+        Ok(self.run(move |conn| conn.execute("DELETE FROM users WHERE name = ?1", [user])).await? > 0)
+    }
+
+    // TEXT 列默认的 BINARY 排序规则按字节比较，与 MemoryStore 的顺序一致
+    async fn list_users(&self, after: &str, limit: u32) -> StoreResult<Vec<(String, UserInfo)>> {
+        let after = after.to_string();
+        self.run(move |conn| {
+            // 用户名放在用户的列之后，user_from_row 的列号不变
+            let mut statement = conn.prepare(&format!("SELECT {}, name FROM users WHERE name > ?1 ORDER BY name LIMIT ?2", USER_COLUMNS))?;
+            let rows = statement.query_map(params![after, limit], |row| Ok((row.get(17)?, user_from_row(row)?)))?;
+            rows.collect()
+        })
+        .await
+    }
+}
+
+#[tonic::async_trait]
+impl SessionStore for SqliteStore {
+    async fn put_challenge(&self, auth_id: &str, challenge: PendingChallenge) -> StoreResult<()> {
+        let auth_id = auth_id.to_string();
+        self.run(move |conn| {
+            conn.execute(
+                &format!("INSERT OR REPLACE INTO challenges (auth_id, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)", CHALLENGE_COLUMNS),
+                params![
+                    auth_id,
+                    challenge.user,
+                    challenge.r1.to_bytes_be(),
+                    challenge.r2.to_bytes_be(),
+                    challenge.c.to_bytes_be(),
+                    challenge.expires_at,
+                    to_json(&challenge.metadata),
+                    challenge.device_id,
+                ],
+            )
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn get_challenge(&self, auth_id: &str) -> StoreResult<Option<PendingChallenge>> {
+        let auth_id = auth_id.to_string();
+        self.run(move |conn| conn.query_row(&format!("SELECT {} FROM challenges WHERE auth_id = ?1", CHALLENGE_COLUMNS), [auth_id], challenge_from_row).optional()).await
+    }
+
+    // 删除并返回被删除的行是同一条语句，打开同一个数据库的几个进程中同一个认证 ID 也只能被取走一次
+    async fn take_challenge(&self, auth_id: &str) -> StoreResult<Option<PendingChallenge>> {
+        let auth_id = auth_id.to_string();
+        self.run(move |conn| conn.query_row(&format!("DELETE FROM challenges WHERE auth_id = ?1 RETURNING {}", CHALLENGE_COLUMNS), [auth_id], challenge_from_row).optional()).await
+    }
+
+    async fn count_challenges(&self, user: &str) -> StoreResult<u32> {
+        let user = user.to_string();
+        self.run(move |conn| conn.query_row("SELECT COUNT(*) FROM challenges WHERE user = ?1", [user], |row| row.get(0))).await
+    }
+
+    async fn delete_challenges(&self, user: &str) -> StoreResult<()> {
+        let user = user.to_string();
+        self.run(move |conn| conn.execute("DELETE FROM challenges WHERE user = ?1", [user])).await?;
+        Ok(())
+    }
+
+    async fn put_session(&self, session_id: &str, session: SessionInfo) -> StoreResult<()> {
+        let session_id = session_id.to_string();
+        self.run(move |conn| {
+            conn.execute(
+                &format!("INSERT OR REPLACE INTO sessions (session_id, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)", SESSION_COLUMNS),
+                params![
+                    session_id,
+                    session.user,
+                    session.issued_at,
+                    session.expires_at,
+                    session.auth_method,
+                    to_json(&session.scopes),
+                    to_json(&session.metadata),
+                    session.device_id,
+                ],
+            )
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn get_session(&self, session_id: &str) -> StoreResult<Option<SessionInfo>> {
+        let session_id = session_id.to_string();
+        self.run(move |conn| conn.query_row(&format!("SELECT {} FROM sessions WHERE session_id = ?1", SESSION_COLUMNS), [session_id], session_from_row).optional()).await
+    }
+
+    // 与 take_challenge 相同，只有一个进程能得到被删除的会话
+    async fn delete_session(&self, session_id: &str) -> StoreResult<Option<SessionInfo>> {
+        let session_id = session_id.to_string();
+        self.run(move |conn| conn.query_row(&format!("DELETE FROM sessions WHERE session_id = ?1 RETURNING {}", SESSION_COLUMNS), [session_id], session_from_row).optional()).await
+    }
+
+    async fn list_sessions(&self, user: &str) -> StoreResult<Vec<(String, SessionInfo)>> {
+        let user = user.to_string();
+        self.run(move |conn| {
+            // 会话 ID 放在会话的列之后，session_from_row 的列号不变
+            let mut statement = conn.prepare(&format!("SELECT {}, session_id FROM sessions WHERE user = ?1", SESSION_COLUMNS))?;
+            let rows = statement.query_map([user], |row| Ok((row.get(7)?, session_from_row(row)?)))?;
+            rows.collect()
+        })
+        .await
+    }
+
+    // 返回的正是被删除的会话，其他进程同时插入的会话不会被删除而不返回
+    async fn delete_sessions(&self, user: &str) -> StoreResult<Vec<String>> {
+        let user = user.to_string();
+        self.run(move |conn| {
+            let mut statement = conn.prepare("DELETE FROM sessions WHERE user = ?1 RETURNING session_id")?;
+            let rows = statement.query_map([user], |row| row.get(0))?;
+            rows.collect()
+        })
+        .await
+    }
+
+    async fn count_sessions(&self, now: u64) -> StoreResult<u64> {
+        self.run(move |conn| conn.query_row("SELECT COUNT(*) FROM sessions WHERE expires_at > ?1", [now], |row| row.get(0))).await
+    }
+
+    async fn purge_expired(&self, now: u64) -> StoreResult<Purged> {
+        self.run(move |conn| {
+            let challenges = conn.execute("DELETE FROM challenges WHERE expires_at <= ?1", [now])?;
+            let sessions = conn.execute("DELETE FROM sessions WHERE expires_at <= ?1", [now])?;
+            Ok(Purged { challenges: challenges as u32, sessions: sessions as u32 })
+        })
+        .await
+    }
+}
+
+#[tonic::async_trait]
+impl AuditLog for SqliteStore {
+    async fn append(&self, event: AuditEvent) -> StoreResult<()> {
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT INTO audit_log (at, kind, user, success, reason, remote_addr, correlation_id, session) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![event.at, event.kind.name(), event.user, event.success, event.reason, event.remote_addr, event.correlation_id, event.session],
+            )
+        })
+        .await?;
+        Ok(())
+    }
+
+    // until 为 0 时不限制，用 i64::MAX 代替；SQLite 的整数是有符号的
+    async fn query(&self, query: &AuditQuery) -> StoreResult<Vec<AuditEvent>> {
+        let until = if query.until == 0 { i64::MAX as u64 } else { query.until };
+        let (after, since, user, limit) = (query.after, query.since, query.user.clone(), query.limit);
+        self.run(move |conn| {
+            let mut statement = conn.prepare(&format!(
+                "SELECT {} FROM audit_log WHERE id > ?1 AND at >= ?2 AND at < ?3 AND (?4 = '' OR user = ?4) ORDER BY id LIMIT ?5",
+                AUDIT_COLUMNS
+            ))?;
+            let rows = statement.query_map(params![after, since, until, user, limit], audit_event_from_row)?;
+            rows.collect()
+        })
+        .await
+    }
+
+    // 没有冒号时 substr(reason, 1, -1) 为空字符串，与 AuditEvent::pseudonymize 一致
+    async fn pseudonymize(&self, user: &str, tombstone: &str) -> StoreResult<u64> {
+        let (user, tombstone) = (user.to_string(), tombstone.to_string());
+        let count = self
+            .run(move |conn| {
+                conn.execute(
+                    "UPDATE audit_log SET user = ?2, remote_addr = '',
+                        reason = CASE WHEN instr(reason, ?1) > 0 THEN substr(reason, 1, instr(reason, ':') - 1) ELSE reason END
+                    WHERE user = ?1",
+                    params![user, tombstone],
+                )
+            })
+            .await?;
+        Ok(count as u64)
+    }
+}
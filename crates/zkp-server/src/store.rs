@@ -0,0 +1,321 @@
+//! 服务器的存储：`UserStore` 保存用户记录，`SessionStore` 保存挑战和会话，`AuthImpl` 只通过这两个 trait 访问存储，
+//! 其他后端（SQLite、Postgres、Redis 等）实现它们后用 `AuthImpl::with_stores` 注入；默认的 `MemoryStore` 同时实现两者，服务器重启后数据丢失
+//!
+//! 存储中的数值与 gRPC 接口中的含义相同：y1、y2 已经过子群检查，时间都是 Unix 时间戳（秒）
+
+use std::collections::hash_map::Entry; // 不存在时才插入新用户
+use std::collections::HashMap; // 记录中的元数据
+use std::fmt; // 脱敏的调试输出
+
+use num_bigint::BigUint; // 用户的公开值、挑战中的承诺
+use tonic::{Code, Status}; // 存储错误转换为 gRPC 错误
+
+use zkp_proto::zkp_auth::{KdfParams, Profile}; // 派生私钥的 KDF 参数、账户资料
+
+use crate::shard::ShardedMap; // 内存存储的分片映射表，在多线程之间共享
+
+/// 存储后端的错误，例如数据库连接断开；处理函数以 Unavailable 返回给客户端
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoreError(pub String);
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "storage backend failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<StoreError> for Status {
+    fn from(error: StoreError) -> Status {
+        Status::new(Code::Unavailable, error.to_string())
+    }
+}
+
+/// 存储操作的结果
+pub type StoreResult<T> = Result<T, StoreError>;
+
+/// 用户记录
+#[derive(Clone, Default)] // Debug 手动实现
+pub struct UserInfo {
+    pub y1: BigUint, // 大整数 y1，用户注册时传递的验证数据
+    pub y2: BigUint, // 大整数 y2，用户注册时传递的验证数据
+    pub salt: Vec<u8>, // 客户端派生私钥时使用的盐，旧客户端注册的用户为空
+    pub kdf: Option<KdfParams>, // 客户端派生私钥时使用的 KDF 参数
+    pub scopes: Vec<String>, // 用户被授予的权限范围，登录时写入会话
+    pub metadata: HashMap<String, String>, // 注册时客户端附带的元数据
+    pub display_name: String, // 账户资料：显示名称
+    pub contact: String, // 账户资料：联系方式
+    pub created_at: u64, // 注册时间（Unix 时间戳，秒）
+    pub recovery_codes: Vec<Vec<u8>>, // 尚未使用的恢复码的 SHA-256 哈希，服务器不保存恢复码本身
+    pub reset_required: bool, // 用恢复码或多方恢复登录后尚未重置密码
+    pub guardians: Vec<String>, // 多方恢复的监护人
+    pub guardian_bindings: Vec<Vec<u8>>, // 各监护人被指定时账户的指纹（注册时间和 y1 的 SHA-256），与 guardians 一一对应；批准时必须与监护人当前的指纹一致
+    pub guardian_threshold: u32, // 完成多方恢复需要的批准数，没有监护人时为 0
+    pub totp_secret: Vec<u8>, // TOTP 第二因素的共享密钥，为空时未启用
+    pub group: Vec<u8>, // 注册时的群的参数集标识（params_hash），记录群之前注册的用户为空，使用服务器的默认群
+}
+
+impl UserInfo {
+    /// 账户资料
+    pub fn profile(&self) -> Profile {
+        Profile { display_name: self.display_name.clone(), contact: self.contact.clone(), created_at: self.created_at }
+    }
+}
+
+// 协议值只输出位数，盐、联系方式和 TOTP 密钥只输出长度
+impl fmt::Debug for UserInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bits = |value: &BigUint| format!("<redacted, {} bits>", value.bits());
+        f.debug_struct("UserInfo")
+            .field("y1", &format_args!("{}", bits(&self.y1)))
+            .field("y2", &format_args!("{}", bits(&self.y2)))
+            .field("salt", &format_args!("<redacted, {} bytes>", self.salt.len()))
+            .field("kdf", &self.kdf)
+            .field("scopes", &self.scopes)
+            .field("metadata", &self.metadata)
+            .field("display_name", &self.display_name)
+            .field("contact", &format_args!("<redacted, {} bytes>", self.contact.len()))
+            .field("created_at", &self.created_at)
+            .field("recovery_codes", &format_args!("<redacted, {} codes>", self.recovery_codes.len()))
+            .field("reset_required", &self.reset_required)
+            .field("guardians", &self.guardians)
+            .field("guardian_bindings", &format_args!("<{} bindings>", self.guardian_bindings.len()))
+            .field("guardian_threshold", &self.guardian_threshold)
+            .field("totp_secret", &format_args!("<redacted, {} bytes>", self.totp_secret.len()))
+            .field("group", &format_args!("{}", hex::encode(&self.group)))
+            .finish()
+    }
+}
+
+/// 已发出、尚未验证的挑战
+///
+/// 承诺和挑战值随认证 ID 保存，创建挑战只需一次写入，请求在任何时刻被取消都不会留下写了一半的认证状态
+#[derive(Debug, Clone)]
+pub struct PendingChallenge {
+    pub user: String,                      // 挑战所属的用户名
+    pub r1: BigUint,                       // 客户端的承诺 r1
+    pub r2: BigUint,                       // 客户端的承诺 r2
+    pub c: BigUint,                        // 验证时使用的挑战值：发出的 c，有通道绑定时为绑定后的 c'
+    pub expires_at: u64,                   // 挑战的过期时间（Unix 时间戳，秒）
+    pub metadata: HashMap<String, String>, // 挑战请求附带的元数据
+    pub device_id: String,                 // 挑战请求中的设备标识，应答必须一致
+}
+
+/// 认证成功后建立的会话
+#[derive(Debug, Clone)]
+pub struct SessionInfo {
+    pub user: String,              // 会话所属的用户名
+    pub issued_at: u64,            // 会话的建立时间（Unix 时间戳，秒）
+    pub expires_at: u64,           // 会话的过期时间（Unix 时间戳，秒）
+    pub auth_method: String,       // 建立会话的认证方式
+    pub scopes: Vec<String>,       // 会话被授予的权限范围，建立会话时从用户记录复制
+    pub metadata: HashMap<String, String>, // 建立会话时客户端附带的元数据
+    pub device_id: String,         // 会话绑定的设备标识，为空时不绑定
+}
+
+/// `UserStore::update_user` 对用户记录的修改，返回 false 时放弃修改
+pub type UserUpdate<'a> = Box<dyn FnOnce(&mut UserInfo) -> bool + Send + 'a>;
+
+/// `SessionStore::purge_expired` 删除的条目数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Purged {
+    pub challenges: u32, // 过期的挑战
+    pub sessions: u32,   // 过期的会话
+}
+
+/// 用户记录的存储
+#[tonic::async_trait]
+pub trait UserStore: fmt::Debug + Send + Sync {
+    /// 保存用户记录，同名的用户已经存在时替换
+    async fn put_user(&self, user: &str, info: UserInfo) -> StoreResult<()>;
+
+    /// 保存新用户的记录，同名的用户已经存在时不修改，返回 false；检查和写入是原子的，并发注册同一个用户名时只有一个成功
+    async fn create_user(&self, user: &str, info: UserInfo) -> StoreResult<bool>;
+
+    /// 读取用户记录，用户不存在时返回 None
+    async fn get_user(&self, user: &str) -> StoreResult<Option<UserInfo>>;
+
+    /// 原子地修改用户记录：读取、调用 update、写回之间不能插入其他对该用户的修改
+    ///
+    /// 返回:
+    /// - `StoreResult<bool>`: 是否写入了修改，用户不存在或 update 返回 false 时为 false
+    async fn update_user(&self, user: &str, update: UserUpdate<'_>) -> StoreResult<bool>;
+
+    /// 删除用户记录，返回用户是否存在
+    async fn delete_user(&self, user: &str) -> StoreResult<bool>;
+
+    /// 按用户名的字节顺序列出用户名大于 `after` 的最多 `limit` 个用户，用于管理接口的分页
+    async fn list_users(&self, after: &str, limit: u32) -> StoreResult<Vec<(String, UserInfo)>>;
+}
+
+/// 挑战和会话的存储，键为认证 ID 和会话 ID
+#[tonic::async_trait]
+pub trait SessionStore: fmt::Debug + Send + Sync {
+    /// 保存新发出的挑战
+    async fn put_challenge(&self, auth_id: &str, challenge: PendingChallenge) -> StoreResult<()>;
+
+    /// 读取挑战，不删除
+    async fn get_challenge(&self, auth_id: &str) -> StoreResult<Option<PendingChallenge>>;
+
+    /// 删除并返回挑战，同一个认证 ID 并发调用时只有一个调用者能取到
+    async fn take_challenge(&self, auth_id: &str) -> StoreResult<Option<PendingChallenge>>;
+
+    /// 用户尚未完成的挑战数
+    async fn count_challenges(&self, user: &str) -> StoreResult<u32>;
+
+    /// 删除用户的所有挑战
+    async fn delete_challenges(&self, user: &str) -> StoreResult<()>;
+
+    /// 保存新建立的会话
+    async fn put_session(&self, session_id: &str, session: SessionInfo) -> StoreResult<()>;
+
+    /// 读取会话，不检查是否过期
+    async fn get_session(&self, session_id: &str) -> StoreResult<Option<SessionInfo>>;
+
+    /// 删除并返回会话
+    async fn delete_session(&self, session_id: &str) -> StoreResult<Option<SessionInfo>>;
+
+    /// 用户的所有会话及其会话 ID
+    async fn list_sessions(&self, user: &str) -> StoreResult<Vec<(String, SessionInfo)>>;
+
+    /// 删除用户的所有会话，返回被删除的会话 ID
+    async fn delete_sessions(&self, user: &str) -> StoreResult<Vec<String>>;
+
+    /// 所有用户 `expires_at > now` 的会话数，用于指标
+    async fn count_sessions(&self, now: u64) -> StoreResult<u64>;
+
+    /// 删除 `expires_at <= now` 的挑战和会话，由后台清理任务定期调用
+    async fn purge_expired(&self, now: u64) -> StoreResult<Purged>;
+}
+
+/// 内存中的用户、挑战和会话，服务器重启后丢失
+#[derive(Default)]
+pub struct MemoryStore {
+    user_info: ShardedMap<String, UserInfo>, // 按用户名分片保存用户信息，不同用户的请求互不等待
+    auth_id_to_user: ShardedMap<String, PendingChallenge>, // 保存认证 ID 到挑战的映射，方便后续认证流程
+    sessions: ShardedMap<String, SessionInfo>, // 保存会话 ID 到会话信息的映射，用于查询和注销会话
+}
+
+// 映射表的键是认证 ID、会话 ID 等凭据，调试输出只包含条目数
+impl fmt::Debug for MemoryStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryStore")
+            .field("users", &self.user_info.len())
+            .field("challenges", &self.auth_id_to_user.len())
+            .field("sessions", &self.sessions.len())
+            .finish()
+    }
+}
+
+#[tonic::async_trait]
+impl UserStore for MemoryStore {
+    async fn put_user(&self, user: &str, info: UserInfo) -> StoreResult<()> {
+        self.user_info.write(user).insert(user.to_string(), info);
+        Ok(())
+    }
+
+    async fn create_user(&self, user: &str, info: UserInfo) -> StoreResult<bool> {
+        match self.user_info.write(user).entry(user.to_string()) {
+            Entry::Occupied(_) => Ok(false),
+            Entry::Vacant(entry) => {
+                entry.insert(info);
+                Ok(true)
+            }
+        }
+    }
+
+    async fn get_user(&self, user: &str) -> StoreResult<Option<UserInfo>> {
+        Ok(self.user_info.read(user).get(user).cloned())
+    }
+
+    // 在用户所在分片的写锁内修改副本，update 同意后才替换，放弃的修改不会留下一半
+    async fn update_user(&self, user: &str, update: UserUpdate<'_>) -> StoreResult<bool> {
+        let mut user_info = self.user_info.write(user);
+        let Some(stored) = user_info.get_mut(user) else {
+            return Ok(false);
+        };
+        let mut updated = stored.clone();
+        if !update(&mut updated) {
+            return Ok(false);
+        }
+        *stored = updated;
+        Ok(true)
+    }
+
+    async fn delete_user(&self, user: &str) -> StoreResult<bool> {
+        Ok(self.user_info.write(user).remove(user).is_some())
+    }
+
+    // 用户分布在各个分片中，收集后排序
+    async fn list_users(&self, after: &str, limit: u32) -> StoreResult<Vec<(String, UserInfo)>> {
+        let mut users = self.user_info.collect(|user, info| (user.as_str() > after).then(|| (user.clone(), info.clone())));
+        users.sort_by(|(a, _), (b, _)| a.cmp(b));
+        users.truncate(limit as usize);
+        Ok(users)
+    }
+}
+
+#[tonic::async_trait]
+impl SessionStore for MemoryStore {
+    async fn put_challenge(&self, auth_id: &str, challenge: PendingChallenge) -> StoreResult<()> {
+        self.auth_id_to_user.write(auth_id).insert(auth_id.to_string(), challenge);
+        Ok(())
+    }
+
+    async fn get_challenge(&self, auth_id: &str) -> StoreResult<Option<PendingChallenge>> {
+        Ok(self.auth_id_to_user.read(auth_id).get(auth_id).cloned())
+    }
+
+    async fn take_challenge(&self, auth_id: &str) -> StoreResult<Option<PendingChallenge>> {
+        Ok(self.auth_id_to_user.write(auth_id).remove(auth_id))
+    }
+
+    async fn count_challenges(&self, user: &str) -> StoreResult<u32> {
+        Ok(self.auth_id_to_user.collect(|_, challenge| (challenge.user == user).then_some(())).len() as u32)
+    }
+
+    async fn delete_challenges(&self, user: &str) -> StoreResult<()> {
+        self.auth_id_to_user.retain(|_, challenge| challenge.user != user);
+        Ok(())
+    }
+
+    async fn put_session(&self, session_id: &str, session: SessionInfo) -> StoreResult<()> {
+        self.sessions.write(session_id).insert(session_id.to_string(), session);
+        Ok(())
+    }
+
+    async fn get_session(&self, session_id: &str) -> StoreResult<Option<SessionInfo>> {
+        Ok(self.sessions.read(session_id).get(session_id).cloned())
+    }
+
+    async fn delete_session(&self, session_id: &str) -> StoreResult<Option<SessionInfo>> {
+        Ok(self.sessions.write(session_id).remove(session_id))
+    }
+
+    async fn list_sessions(&self, user: &str) -> StoreResult<Vec<(String, SessionInfo)>> {
+        Ok(self.sessions.collect(|id, session| (session.user == user).then(|| (id.clone(), session.clone()))))
+    }
+
+    async fn delete_sessions(&self, user: &str) -> StoreResult<Vec<String>> {
+        let mut removed = Vec::new();
+        self.sessions.retain(|session_id, session| {
+            let keep = session.user != user;
+            if !keep {
+                removed.push(session_id.clone());
+            }
+            keep
+        });
+        Ok(removed)
+    }
+
+    async fn count_sessions(&self, now: u64) -> StoreResult<u64> {
+        Ok(self.sessions.collect(|_, session| (session.expires_at > now).then_some(())).len() as u64)
+    }
+
+    async fn purge_expired(&self, now: u64) -> StoreResult<Purged> {
+        let challenges = self.auth_id_to_user.retain(|_, challenge| challenge.expires_at > now);
+        let sessions = self.sessions.retain(|_, session| session.expires_at > now);
+        Ok(Purged { challenges: challenges as u32, sessions: sessions as u32 })
+    }
+}
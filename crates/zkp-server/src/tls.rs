@@ -0,0 +1,184 @@
+//! TLS 和 mTLS（tls feature）：服务器用 `TlsConfig` 中的证书监听；配置了客户端 CA 时要求客户端出示由该 CA 签发的证书，
+//! 握手失败的连接不会到达任何处理函数
+//!
+//! 客户端证书的主体作为 `ClientIdentity` 提供给处理函数（`ClientIdentity::of`）：取证书的 CN，没有 CN 时取第一个 DNS 或 URI SAN。
+//! `ServerConfig::registration_identities` 据此限制哪些机器可以注册，管理接口的 `AdminPolicy` 据此分配角色
+//!
+//! `run_server` 自己接受 TLS 连接而不使用 tonic 的 TLS 配置，`AuthImpl::reload` 替换证书后新的连接使用新证书，
+//! 已经建立的连接和进行中的 RPC 不受影响
+
+use std::fmt; // 调试输出不包含私钥
+use std::io; // 读取证书文件
+use std::path::Path; // 证书文件路径
+use std::sync::{Arc, RwLock}; // 可以替换的 rustls 配置
+use std::time::Duration; // 握手的超时
+
+use tokio::sync::mpsc; // 握手完成的连接交给 tonic
+use tokio_rustls::rustls::server::{AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient}; // 验证客户端证书
+use tokio_rustls::rustls::{self, RootCertStore}; // TLS 实现
+use tokio_rustls::server::TlsStream; // 握手完成的连接
+use tokio_rustls::TlsAcceptor; // 在 TCP 连接上握手
+use tokio_stream::wrappers::ReceiverStream; // 连接流
+use tokio_stream::StreamExt; // 取下一个接受的连接
+use tonic::transport::server::Connected; // 握手失败时记录客户端地址
+use tonic::transport::{Certificate, Identity, ServerTlsConfig}; // tonic 的 TLS 配置
+use tonic::Request; // 读取请求所在连接的客户端证书
+use tracing::debug; // 握手失败只在调试日志中记录
+use x509_parser::extensions::GeneralName; // 证书的 SAN
+use x509_parser::prelude::{FromDer, X509Certificate}; // 解析客户端证书
+
+use crate::limits::Connection; // 接受的连接，计入连接数的上限
+use crate::rbac::ClientIdentity; // 客户端证书的主体
+
+// 客户端在这段时间内没有完成握手时断开，不占用连接
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// 等待交给 tonic 的已握手连接数
+const ACCEPT_BACKLOG: usize = 128;
+
+/// 服务器的 TLS 配置，PEM 格式
+#[derive(Clone)]
+pub struct TlsConfig {
+    pub cert_pem: Vec<u8>,              // 服务器证书链
+    pub key_pem: Vec<u8>,               // 服务器私钥
+    pub client_ca_pem: Option<Vec<u8>>, // 签发客户端证书的 CA，设置时启用 mTLS
+    pub client_auth_optional: bool,     // 为 true 时也接受没有客户端证书的连接，这些连接没有 ClientIdentity
+}
+
+impl TlsConfig {
+    /// 从 PEM 文件读取证书和私钥，不启用 mTLS
+    ///
+    /// 参数:
+    /// - `cert`: 服务器证书链文件
+    /// - `key`: 服务器私钥文件
+    pub fn from_files(cert: impl AsRef<Path>, key: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(TlsConfig { cert_pem: std::fs::read(cert)?, key_pem: std::fs::read(key)?, client_ca_pem: None, client_auth_optional: false })
+    }
+
+    /// 要求客户端出示由 `ca_pem` 中的 CA 签发的证书
+    pub fn with_client_ca(mut self, ca_pem: Vec<u8>) -> Self {
+        self.client_ca_pem = Some(ca_pem);
+        self
+    }
+
+    /// tonic 服务器的 TLS 配置，自己构建 tonic 服务器的程序传给 `Server::tls_config`；`run_server` 使用同样的设置，但自己握手以便替换证书
+    pub fn server_tls_config(&self) -> ServerTlsConfig {
+        let mut tls = ServerTlsConfig::new().identity(Identity::from_pem(&self.cert_pem, &self.key_pem));
+        if let Some(ca) = &self.client_ca_pem {
+            tls = tls.client_ca_root(Certificate::from_pem(ca)).client_auth_optional(self.client_auth_optional);
+        }
+        tls
+    }
+
+    // 由 PEM 构建 rustls 的服务器配置，与 tonic 的 TLS 配置相同：可选的客户端证书验证，ALPN 只提供 h2
+    fn rustls_config(&self) -> io::Result<rustls::ServerConfig> {
+        let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+        let certs: Vec<rustls::Certificate> = rustls_pemfile::certs(&mut &self.cert_pem[..])?.into_iter().map(rustls::Certificate).collect();
+        if certs.is_empty() {
+            return Err(invalid("no certificate found in the certificate PEM"));
+        }
+        let key = rustls_pemfile::read_all(&mut &self.key_pem[..])?
+            .into_iter()
+            .find_map(|item| match item {
+                rustls_pemfile::Item::RSAKey(key) | rustls_pemfile::Item::PKCS8Key(key) | rustls_pemfile::Item::ECKey(key) => Some(rustls::PrivateKey(key)),
+                _ => None,
+            })
+            .ok_or_else(|| invalid("no RSA, PKCS#8 or EC private key found in the key PEM"))?;
+
+        let builder = rustls::ServerConfig::builder().with_safe_defaults();
+        let builder = match &self.client_ca_pem {
+            None => builder.with_no_client_auth(),
+            Some(ca) => {
+                let mut roots = RootCertStore::empty();
+                let (added, ignored) = roots.add_parsable_certificates(&rustls_pemfile::certs(&mut &ca[..])?);
+                if added == 0 || ignored > 0 {
+                    return Err(invalid("the client CA PEM contains no usable certificate"));
+                }
+                match self.client_auth_optional {
+                    true => builder.with_client_cert_verifier(AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed()),
+                    false => builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots).boxed()),
+                }
+            }
+        };
+        let mut config = builder.with_single_cert(certs, key).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        config.alpn_protocols = vec![b"h2".to_vec()];
+        Ok(config)
+    }
+}
+
+// 当前使用的 rustls 配置，每个新连接握手时读取一次
+pub(crate) struct ReloadableTls(RwLock<Arc<rustls::ServerConfig>>);
+
+impl ReloadableTls {
+    pub(crate) fn new(tls: &TlsConfig) -> io::Result<Self> {
+        Ok(ReloadableTls(RwLock::new(Arc::new(tls.rustls_config()?))))
+    }
+
+    // 替换证书，证书无效时保留原来的配置并返回错误
+    pub(crate) fn replace(&self, tls: &TlsConfig) -> io::Result<()> {
+        let config = Arc::new(tls.rustls_config()?);
+        *self.0.write().unwrap() = config;
+        Ok(())
+    }
+
+    fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(self.0.read().unwrap().clone())
+    }
+}
+
+impl fmt::Debug for ReloadableTls {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReloadableTls").finish_non_exhaustive()
+    }
+}
+
+// 用当前的证书在 `limits::accept` 接受的连接上握手，返回握手完成的连接，交给 `Server::serve_with_incoming`
+// 每个握手在自己的任务中进行，慢的客户端不阻塞其他连接；握手失败的连接不会到达 tonic。返回的流被丢弃后停止接受连接
+pub(crate) fn incoming(mut connections: ReceiverStream<Connection>, tls: Arc<ReloadableTls>) -> ReceiverStream<io::Result<TlsStream<Connection>>> {
+    let (sender, receiver) = mpsc::channel(ACCEPT_BACKLOG);
+    tokio::spawn(async move {
+        while let Some(stream) = connections.next().await {
+            if sender.is_closed() {
+                break;
+            }
+            let addr = stream.connect_info().remote_addr();
+            let acceptor = tls.acceptor();
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                    Ok(Ok(stream)) => {
+                        let _ = sender.send(Ok(stream)).await;
+                    }
+                    Ok(Err(err)) => debug!(addr = ?addr, error = %err, "TLS handshake failed"),
+                    Err(_) => debug!(addr = ?addr, "TLS handshake timed out"),
+                }
+            });
+        }
+    });
+    ReceiverStream::new(receiver)
+}
+
+// 调试输出不包含私钥
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsConfig")
+            .field("mtls", &self.client_ca_pem.is_some())
+            .field("client_auth_optional", &self.client_auth_optional)
+            .finish()
+    }
+}
+
+// 请求所在连接的客户端证书的主体，连接没有使用 TLS、客户端没有出示证书或证书无法解析时为 None
+// 证书链已经在握手时由 rustls 按客户端 CA 验证，这里只读取叶子证书
+pub(crate) fn peer_identity<T>(request: &Request<T>) -> Option<ClientIdentity> {
+    let certs = request.peer_certs()?;
+    let (_, cert) = X509Certificate::from_der(certs.first()?.get_ref()).ok()?;
+    if let Some(cn) = cert.subject().iter_common_name().next().and_then(|cn| cn.as_str().ok()) {
+        return Some(ClientIdentity(cn.to_string()));
+    }
+    let san = cert.subject_alternative_name().ok()??;
+    san.value.general_names.iter().find_map(|name| match name {
+        GeneralName::DNSName(name) | GeneralName::URI(name) => Some(ClientIdentity(name.to_string())),
+        _ => None,
+    })
+}
@@ -0,0 +1,90 @@
+//! TOTP 第二因素（RFC 6238）：注册时客户端提交共享密钥，服务器与用户记录一起保存，之后的登录除了对挑战的解答之外还需要当前的验证码，
+//! 密码泄露时账户仍然受到保护，适合管理员账户
+//!
+//! 服务器不知道密码，但必须保存 TOTP 密钥的原文才能计算验证码：泄露的数据库会泄露第二因素，不会泄露密码。
+//! 验证码为 HMAC-SHA1、30 秒一步、6 位，与常见的认证器应用兼容，接受前后各一步的时钟偏差。
+//! 每个用户最近接受的时间步记录在本进程的内存中，同一个验证码不能使用两次；与限流和锁定一样，多个副本各自记录
+
+use std::fmt; // 调试输出不包含用户名
+
+use hmac::{Hmac, Mac}; // HOTP 的 HMAC
+use sha1::Sha1; // 认证器应用默认使用 SHA-1
+
+use crate::shard::ShardedMap; // 已使用的时间步在请求之间共享，键为用户名
+
+/// 每个验证码的有效时间（秒）
+pub const TOTP_STEP_SECS: u64 = 30;
+
+/// 验证码的位数
+pub const TOTP_DIGITS: usize = 6;
+
+/// 共享密钥的最小和最大长度（字节），RFC 4226 要求至少 128 位
+pub const TOTP_MIN_SECRET_LEN: usize = 16;
+pub const TOTP_MAX_SECRET_LEN: usize = 64;
+
+// 接受的时钟偏差（时间步）
+const SKEW_STEPS: u64 = 1;
+
+/// 共享密钥在 Unix 时间 `unix_time`（秒）的验证码，客户端和测试据此计算期望的验证码
+///
+/// 参数:
+/// - `secret`: 注册时提交的共享密钥
+/// - `unix_time`: Unix 时间戳（秒）
+pub fn totp_code(secret: &[u8], unix_time: u64) -> String {
+    hotp(secret, unix_time / TOTP_STEP_SECS)
+}
+
+// RFC 4226 的 HOTP：HMAC-SHA1 的动态截断，取十进制的低 6 位
+fn hotp(secret: &[u8], counter: u64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = usize::from(digest[digest.len() - 1] & 0x0f);
+    let value = u32::from_be_bytes([digest[offset], digest[offset + 1], digest[offset + 2], digest[offset + 3]]) & 0x7fff_ffff;
+    format!("{:0width$}", value % 10u32.pow(TOTP_DIGITS as u32), width = TOTP_DIGITS)
+}
+
+// 比较验证码，耗时与第一个不同的字符的位置无关
+fn same_code(expected: &str, code: &str) -> bool {
+    expected.len() == code.len() && expected.bytes().zip(code.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+// 所有用户最近接受的时间步
+#[derive(Default)]
+pub(crate) struct TotpGuard {
+    used: ShardedMap<String, u64>,
+}
+
+// 映射表的键是用户名，调试输出只包含被记录的用户数
+impl fmt::Debug for TotpGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TotpGuard").field("users", &self.used.len()).finish()
+    }
+}
+
+impl TotpGuard {
+    // 检查用户提交的验证码：密钥为空（未启用）时总是通过；否则验证码必须属于 now 前后一步之内、且晚于该用户上次使用的时间步，
+    // 通过时记录该时间步，同一个验证码不能再次使用
+    pub(crate) fn accept(&self, user: &str, secret: &[u8], code: &str, now: u64) -> bool {
+        if secret.is_empty() {
+            return true;
+        }
+        let current = now / TOTP_STEP_SECS;
+        let mut used = self.used.write(user);
+        let first = match used.get(user) {
+            Some(&last) => current.saturating_sub(SKEW_STEPS).max(last + 1),
+            None => current.saturating_sub(SKEW_STEPS),
+        };
+        let Some(step) = (first..=current + SKEW_STEPS).find(|&step| same_code(&hotp(secret, step), code)) else {
+            return false;
+        };
+        used.insert(user.to_string(), step);
+        true
+    }
+
+    // 删除已经落在接受窗口之外的时间步，它们不会再挡住任何验证码；由后台清理调用
+    pub(crate) fn purge(&self, now: u64) {
+        let oldest = (now / TOTP_STEP_SECS).saturating_sub(SKEW_STEPS);
+        self.used.retain(|_, step| *step >= oldest);
+    }
+}
@@ -0,0 +1,114 @@
+//! 用户名的规范化和策略：注册、挑战、恢复和管理接口等所有接收用户名的地方先规范化再使用，
+//! 看起来相同的用户名（组合方式不同的 "é"，开启大小写折叠时的 "Alice" 和 "alice"）对应同一个账户，不会成为两个容易混淆的账户
+//!
+//! 先做 NFC 规范化，再按配置折叠为小写，然后检查长度、字符和保留名。修改规范化方式后，
+//! 存储中按旧方式保存的用户名不再能被找到，需要先用 `export-users` / `import-users` 迁移
+
+use clap::ValueEnum; // 作为命令行参数取值
+use serde::Deserialize; // 配置文件
+use tonic::Status; // 不符合策略时的错误
+use unicode_normalization::UnicodeNormalization; // NFC 规范化
+
+use zkp_proto::invalid::{invalid_argument, InvalidReason}; // 出错字段和原因
+
+// 默认的用户名长度范围（字节）
+const DEFAULT_MIN_LEN: usize = 1;
+const DEFAULT_MAX_LEN: usize = 128;
+
+// ascii 字符集中除字母和数字以外允许的字符
+const ASCII_PUNCTUATION: &str = "._-@+";
+
+/// 用户名的规范化方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UserNameNormalization {
+    /// 不修改，逐字节比较
+    None,
+    /// Unicode NFC 规范化（默认），组合方式不同的同一字符视为相同
+    #[default]
+    Nfc,
+    /// NFC 之后折叠为小写，"Alice" 和 "alice" 是同一个用户
+    NfcLowercase,
+}
+
+/// 用户名允许的字符
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UserNameCharset {
+    /// 控制字符以外的任何 Unicode 字符（默认），首尾不能有空白
+    #[default]
+    Unicode,
+    /// 只允许 ASCII 字母、数字和 . _ - @ +
+    Ascii,
+}
+
+/// 用户名策略，`ServerConfig::user_names`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserNamePolicy {
+    pub normalization: UserNameNormalization, // 比较和保存之前的规范化
+    pub charset: UserNameCharset,             // 允许的字符
+    pub min_len: usize,                       // 规范化后的最少字节数
+    pub max_len: usize,                       // 规范化后的最多字节数
+    pub reserved: Vec<String>,                // 不能注册的用户名，按同样的方式规范化后比较
+}
+
+impl Default for UserNamePolicy {
+    fn default() -> Self {
+        UserNamePolicy { normalization: UserNameNormalization::Nfc, charset: UserNameCharset::Unicode, min_len: DEFAULT_MIN_LEN, max_len: DEFAULT_MAX_LEN, reserved: Vec::new() }
+    }
+}
+
+impl UserNamePolicy {
+    /// 按配置规范化用户名，不做检查
+    pub fn normalize(&self, name: &str) -> String {
+        match self.normalization {
+            UserNameNormalization::None => name.to_string(),
+            UserNameNormalization::Nfc => name.nfc().collect(),
+            UserNameNormalization::NfcLowercase => name.nfc().collect::<String>().to_lowercase().nfc().collect(), // 小写映射可能产生非 NFC 的序列
+        }
+    }
+
+    /// 规范化并检查用户名的长度和字符，找到已有账户的 RPC 使用
+    ///
+    /// 参数:
+    /// - `field`: 请求中的字段名，写入错误的 `invalid-field`
+    /// - `name`: 请求中的用户名
+    ///
+    /// 返回:
+    /// - `Result<String, Status>`: 规范化后的用户名，不符合策略时返回 InvalidArgument
+    #[allow(clippy::result_large_err)]
+    pub fn check(&self, field: &'static str, name: &str) -> Result<String, Status> {
+        let name = self.normalize(name);
+        if name.is_empty() {
+            return Err(invalid_argument(field, InvalidReason::Empty, format!("{} must not be empty", field)));
+        }
+        if name.len() < self.min_len {
+            return Err(invalid_argument(field, InvalidReason::TooShort, format!("{} must be at least {} bytes", field, self.min_len)));
+        }
+        if name.len() > self.max_len {
+            return Err(invalid_argument(field, InvalidReason::TooLong, format!("{} exceeds {} bytes", field, self.max_len)));
+        }
+        let allowed = match self.charset {
+            UserNameCharset::Unicode => !name.chars().any(char::is_control) && name.trim() == name,
+            UserNameCharset::Ascii => name.chars().all(|c| c.is_ascii_alphanumeric() || ASCII_PUNCTUATION.contains(c)),
+        };
+        if !allowed {
+            let rule = match self.charset {
+                UserNameCharset::Unicode => "must not contain control characters or surrounding whitespace",
+                UserNameCharset::Ascii => "may only contain ASCII letters, digits and . _ - @ +",
+            };
+            return Err(invalid_argument(field, InvalidReason::BadCharacters, format!("{} {}", field, rule)));
+        }
+        Ok(name)
+    }
+
+    /// 与 `check` 相同，另外拒绝保留的用户名；注册新账户时使用
+    #[allow(clippy::result_large_err)]
+    pub fn check_new(&self, field: &'static str, name: &str) -> Result<String, Status> {
+        let name = self.check(field, name)?;
+        if self.reserved.iter().any(|reserved| self.normalize(reserved) == name) {
+            return Err(invalid_argument(field, InvalidReason::Reserved, format!("User: {} is a reserved name", name)));
+        }
+        Ok(name)
+    }
+}
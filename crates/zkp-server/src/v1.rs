@@ -0,0 +1,43 @@
+//! 当前接口的版本化服务名：`zkp_auth.proto` 的包名保持为 `zkp_auth`，现有客户端调用 `/zkp_auth.Auth/<Method>` 不受影响；
+//! `V1` 把 `/zkp_auth.v1.Auth/<Method>` 改写为内部服务的路径后转发，两个名称共享同一个处理函数
+
+use std::task::{Context, Poll}; // 服务包装的就绪状态
+
+use tonic::codegen::{http, Service}; // 服务包装处理的 HTTP 请求
+use tonic::server::NamedService; // 加入 tonic 路由时使用的服务名
+
+use zkp_proto::V1_SERVICE_NAME; // 版本化的服务名
+
+/// 以 `zkp_auth.v1.Auth` 提供内部的服务，例如 `V1(AuthServer::from_arc(auth_impl.clone()))`，
+/// 可以与不带版本的 `AuthServer` 同时加入路由
+#[derive(Debug, Clone)]
+pub struct V1<S>(pub S);
+
+impl<S> NamedService for V1<S> {
+    const NAME: &'static str = V1_SERVICE_NAME;
+}
+
+impl<S, B> Service<http::Request<B>> for V1<S>
+where
+    S: Service<http::Request<B>> + NamedService,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        // tonic 只把 /zkp_auth.v1.Auth/ 下的请求交给这里，方法名原样保留
+        if let Some(method) = request.uri().path().strip_prefix(&format!("/{}/", V1_SERVICE_NAME)) {
+            let mut parts = request.uri().clone().into_parts();
+            parts.path_and_query = format!("/{}/{}", S::NAME, method).parse().ok();
+            if let Ok(uri) = http::Uri::from_parts(parts) {
+                *request.uri_mut() = uri;
+            }
+        }
+        self.0.call(request)
+    }
+}
@@ -0,0 +1,93 @@
+//! 第二版接口（`zkp_auth.v2`，草案）的实现：与当前接口共享同一个 `AuthImpl`，读写同一份配置、群参数和存储，
+//! 两个版本的服务可以同时加入路由，例如：
+//!
+//! ```ignore
+//! let auth = Arc::new(AuthImpl::new(config, store));
+//! Server::builder()
+//!     .add_service(AuthServer::from_arc(auth.clone()))     // zkp_auth.Auth，现有客户端
+//!     .add_service(V1(AuthServer::from_arc(auth.clone()))) // zkp_auth.v1.Auth
+//!     .add_service(AuthV2Server::new(AuthV2Impl::new(auth))) // zkp_auth.v2.Auth
+//! ```
+
+use std::sync::Arc; // 与 v1 服务共享的 AuthImpl
+
+use tokio_stream::wrappers::ReceiverStream; // 流式响应类型
+use tonic::{Code, Request, Response, Status, Streaming}; // gRPC 请求和响应
+use tracing::debug; // 结构化日志
+
+use zkp_proto::zkp_auth::v2::{
+    auth_server::Auth, // 第二版 Auth 服务接口
+    AuthenticateRequest, AuthenticateResponse, // 双向流认证的请求和响应消息类型
+    GetParametersRequest, GetParametersResponse, // 参数协商的请求和响应消息类型
+    GetSaltRequest, GetSaltResponse, // 取得盐的请求和响应消息类型
+    KdfParams, // 派生私钥的 KDF 参数
+};
+use zkp_proto::API_VERSIONS; // 服务器提供的接口版本
+
+use crate::AuthImpl; // 共享的处理逻辑和存储
+
+/// 第二版 Auth gRPC 服务的实现
+#[derive(Debug, Clone)]
+pub struct AuthV2Impl(Arc<AuthImpl>);
+
+impl AuthV2Impl {
+    /// 参数:
+    /// - `auth`: 与当前接口共享的 `AuthImpl`
+    pub fn new(auth: Arc<AuthImpl>) -> Self {
+        AuthV2Impl(auth)
+    }
+}
+
+#[tonic::async_trait]
+impl Auth for AuthV2Impl {
+    // 参数协商：返回客户端列出的参数集中第一个服务器接受的群（没有列出时为默认的群）和接受的持有证明哈希，
+    // 客户端列出的参数集都不被服务器接受时返回 FailedPrecondition
+    async fn get_parameters(&self, request: Request<GetParametersRequest>) -> Result<Response<GetParametersResponse>, Status> {
+        debug!(request = ?request.get_ref(), "processing v2 GetParameters");
+        let request = request.into_inner();
+        let chosen = if request.params_hashes.is_empty() {
+            Some(self.0.params)
+        } else {
+            request.params_hashes.iter().find_map(|params_hash| self.0.group(params_hash).ok())
+        };
+        let Some(zkp) = chosen else {
+            let accepted: Vec<String> = self.0.groups.iter().map(|(hash, _)| hex::encode(hash)).collect();
+            return Err(Status::new(Code::FailedPrecondition, format!("none of the client's parameter sets is supported: server accepts {}", accepted.join(", "))));
+        };
+        let params_hash = zkp.params_hash();
+        Ok(Response::new(GetParametersResponse {
+            p: zkp.p.to_bytes_be(),
+            q: zkp.q.to_bytes_be(),
+            alpha: zkp.alpha.to_bytes_be(),
+            beta: zkp.beta.to_bytes_be(),
+            params_hash,
+            proof_hashes: self.0.config.proof_hashes.iter().map(|hash| hash.to_string()).collect(),
+            api_versions: API_VERSIONS.iter().map(|version| version.to_string()).collect(),
+        }))
+    }
+
+    // 取得用户注册时的盐、KDF 参数和群，用户不存在时与 v1 的挑战请求一样返回 NotFound，
+    // 账户需要重置密码时与 v1 的应答一样返回 FailedPrecondition
+    async fn get_salt(&self, request: Request<GetSaltRequest>) -> Result<Response<GetSaltResponse>, Status> {
+        debug!(request = ?request.get_ref(), "processing v2 GetSalt");
+        self.0.check_honeytoken(&request, &request.get_ref().user, "v2.GetSalt");
+        let user_name = self.0.config.user_names.check("user", &request.into_inner().user)?; // 与 v1 的挑战一样按规范化后的用户名查找
+        match self.0.users.get_user(&user_name).await? {
+            Some(user_info) if user_info.reset_required => Err(AuthImpl::reset_required(&user_name)), // 重置密码之前不能用密码登录
+            Some(user_info) => {
+                let params_hash = self.0.user_group(&user_name, &user_info)?.params_hash();
+                let kdf = user_info.kdf.map(|kdf| KdfParams { algorithm: kdf.algorithm, iterations: kdf.iterations });
+                Ok(Response::new(GetSaltResponse { salt: user_info.salt, kdf, params_hash }))
+            }
+            None => Err(AuthImpl::user_not_found(&user_name)),
+        }
+    }
+
+    // 双向流认证的响应流类型
+    type AuthenticateStream = ReceiverStream<Result<AuthenticateResponse, Status>>;
+
+    // 双向流认证：消息已经定义，服务器端尚未实现
+    async fn authenticate(&self, _request: Request<Streaming<AuthenticateRequest>>) -> Result<Response<Self::AuthenticateStream>, Status> {
+        Err(Status::unimplemented("v2 streaming authentication is not supported by this server yet"))
+    }
+}
@@ -0,0 +1,269 @@
+// 攻击模拟：启动 server 二进制，用各种伪造或重放的请求尝试认证，确认服务器全部拒绝
+//
+// 标记为 ignore 的测试对应服务器尚未实现的校验，实现后去掉 ignore
+
+use std::net::TcpListener;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+use num_bigint::BigUint;
+use tonic::transport::Channel;
+use tonic::Code;
+use zkp_core::{registration_context, HashAlgorithm, NonInteractiveProof, ZKP};
+
+use zkp_proto::zkp_auth::auth_client::AuthClient;
+use zkp_proto::zkp_auth::{AuthenticationAnswerRequest, AuthenticationChallengeRequest, AuthenticationChallengeResponse, RegisterRequest};
+
+// 运行中的服务器进程，测试结束时结束进程
+struct TestServer {
+    child: Child,
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// 在随机端口上启动服务器并连接
+async fn start() -> (TestServer, AuthClient<Channel>) {
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let child = Command::new(env!("CARGO_BIN_EXE_server"))
+        .env("ZKP_SERVER_ADDR", format!("127.0.0.1:{}", port))
+        .env("ZKP_LOG", "warn") // 日志写到标准错误，只保留警告和错误
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("could not start the server");
+    let server = TestServer { child };
+
+    let url = format!("http://127.0.0.1:{}", port);
+    for _ in 0..200 {
+        if let Ok(client) = AuthClient::connect(url.clone()).await {
+            return (server, client);
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("the server did not start");
+}
+
+fn zkp() -> ZKP {
+    ZKP::get_constants()
+}
+
+// 发送注册请求，公开值和持有证明由调用者给出
+async fn register_values(client: &mut AuthClient<Channel>, user: &str, y1: &BigUint, y2: &BigUint, c: &BigUint, s: &BigUint) -> Result<(), tonic::Status> {
+    let request = RegisterRequest {
+        user: user.to_string(),
+        y1: y1.to_bytes_be(),
+        y2: y2.to_bytes_be(),
+        proof_c: c.to_bytes_be(),
+        proof_s: s.to_bytes_be(),
+        ..Default::default()
+    };
+    client.register(request).await.map(|_| ())
+}
+
+// 正常注册，返回私钥 x
+async fn register(client: &mut AuthClient<Channel>, zkp: &ZKP, user: &str) -> BigUint {
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context(user));
+    register_values(client, user, &proof.y1, &proof.y2, &proof.c, &proof.s).await.unwrap();
+    x
+}
+
+// 发送承诺 (r1, r2)，返回挑战
+async fn challenge(client: &mut AuthClient<Channel>, user: &str, r1: &BigUint, r2: &BigUint) -> AuthenticationChallengeResponse {
+    let request = AuthenticationChallengeRequest { user: user.to_string(), r1: r1.to_bytes_be(), r2: r2.to_bytes_be(), ..Default::default() };
+    client.create_authentication_challenge(request).await.unwrap().into_inner()
+}
+
+fn answer_request(auth_id: &str, s: &BigUint) -> AuthenticationAnswerRequest {
+    AuthenticationAnswerRequest { auth_id: auth_id.to_string(), s: s.to_bytes_be(), ..Default::default() }
+}
+
+// 用私钥 x 完成一次正常登录，返回使用过的应答请求
+async fn login(client: &mut AuthClient<Channel>, zkp: &ZKP, user: &str, x: &BigUint) -> AuthenticationAnswerRequest {
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p);
+    let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p);
+    let response = challenge(client, user, &r1, &r2).await;
+    let s = zkp.solve(&k, &BigUint::from_bytes_be(&response.c), x);
+    let request = answer_request(&response.auth_id, &s);
+    client.verify_authentication(request.clone()).await.unwrap();
+    request
+}
+
+#[tokio::test]
+async fn test_wrong_secret_is_rejected() {
+    let (_server, mut client) = start().await;
+    let zkp = zkp();
+    let x = register(&mut client, &zkp, "alice").await;
+
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p);
+    let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p);
+    let response = challenge(&mut client, "alice", &r1, &r2).await;
+    let s = zkp.solve(&k, &BigUint::from_bytes_be(&response.c), &(x + 1u32));
+    let status = client.verify_authentication(answer_request(&response.auth_id, &s)).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn test_swapped_commitment_is_rejected() {
+    let (_server, mut client) = start().await;
+    let zkp = zkp();
+    let x = register(&mut client, &zkp, "alice").await;
+
+    // 承诺中 r1 和 r2 互换，应答按正常方式计算
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p);
+    let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p);
+    let response = challenge(&mut client, "alice", &r2, &r1).await;
+    let s = zkp.solve(&k, &BigUint::from_bytes_be(&response.c), &x);
+    let status = client.verify_authentication(answer_request(&response.auth_id, &s)).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn test_out_of_subgroup_commitment_is_rejected() {
+    let (_server, mut client) = start().await;
+    let zkp = zkp();
+    let x = register(&mut client, &zkp, "alice").await;
+
+    // r1 = -alpha^k 不在 q 阶子群中；无论服务器在哪一步拒绝，都不能建立会话
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let r1 = &zkp.p - ZKP::exponentiate(&zkp.alpha, &k, &zkp.p);
+    let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p);
+    let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1: r1.to_bytes_be(), r2: r2.to_bytes_be(), ..Default::default() };
+    if let Ok(response) = client.create_authentication_challenge(request).await {
+        let response = response.into_inner();
+        let s = zkp.solve(&k, &BigUint::from_bytes_be(&response.c), &x);
+        let status = client.verify_authentication(answer_request(&response.auth_id, &s)).await.unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+    }
+}
+
+#[tokio::test]
+async fn test_proof_for_another_user_is_rejected() {
+    let (_server, mut client) = start().await;
+    let zkp = zkp();
+
+    // 截获 alice 的注册请求，用于注册 mallory
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+    let status = register_values(&mut client, "mallory", &proof.y1, &proof.y2, &proof.c, &proof.s).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_unknown_auth_id_is_rejected() {
+    let (_server, mut client) = start().await;
+    let status = client.verify_authentication(answer_request("forged", &BigUint::from(1u32))).await.unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn test_replayed_answer_is_rejected() {
+    let (_server, mut client) = start().await;
+    let zkp = zkp();
+    let x = register(&mut client, &zkp, "alice").await;
+
+    // 重放一个已经成功的应答
+    let used = login(&mut client, &zkp, "alice", &x).await;
+    assert!(client.verify_authentication(used).await.is_err());
+}
+
+#[tokio::test]
+async fn test_challenge_reuse_is_rejected() {
+    let (_server, mut client) = start().await;
+    let zkp = zkp();
+    let x = register(&mut client, &zkp, "alice").await;
+
+    // 对已经使用过的挑战重新计算应答（同一个 auth_id，不同的 s）
+    let used = login(&mut client, &zkp, "alice", &x).await;
+    let s = (BigUint::from_bytes_be(&used.s) + 1u32) % &zkp.q;
+    let status = client.verify_authentication(answer_request(&used.auth_id, &s)).await.unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn test_failed_answer_consumes_challenge() {
+    let (_server, mut client) = start().await;
+    let zkp = zkp();
+    let x = register(&mut client, &zkp, "alice").await;
+
+    // 猜错一次之后，同一个挑战的正确应答也不再被接受
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p);
+    let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p);
+    let response = challenge(&mut client, "alice", &r1, &r2).await;
+    let c = BigUint::from_bytes_be(&response.c);
+    let wrong = zkp.solve(&k, &c, &(&x + 1u32));
+    let status = client.verify_authentication(answer_request(&response.auth_id, &wrong)).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    let status = client.verify_authentication(answer_request(&response.auth_id, &zkp.solve(&k, &c, &x))).await.unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn test_takeover_by_reregistration_is_rejected() {
+    let (_server, mut client) = start().await;
+    let zkp = zkp();
+    let x = register(&mut client, &zkp, "alice").await;
+
+    // 攻击者用自己的私钥注册同一个用户名，持有证明本身是有效的
+    let mallory = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&mallory, &registration_context("alice"));
+    let status = register_values(&mut client, "alice", &proof.y1, &proof.y2, &proof.c, &proof.s).await.unwrap_err();
+    assert_eq!(status.code(), Code::AlreadyExists);
+
+    // 账户仍然属于原来的用户
+    login(&mut client, &zkp, "alice", &x).await;
+}
+
+#[tokio::test]
+async fn test_out_of_subgroup_registration_is_rejected() {
+    let (_server, mut client) = start().await;
+    let zkp = zkp();
+
+    // y1 = -alpha^x 的阶为 2q；挑战值 c 为偶数时 y1^c = alpha^(xc)，持有证明仍然成立
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let context = registration_context("mallory");
+    let y1 = &zkp.p - ZKP::exponentiate(&zkp.alpha, &x, &zkp.p);
+    let y2 = ZKP::exponentiate(&zkp.beta, &x, &zkp.p);
+    let proof = loop {
+        let k = ZKP::generate_random_number_below(&zkp.q);
+        let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p);
+        let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p);
+        let c = zkp.fiat_shamir_challenge(&y1, &y2, &r1, &r2, &context);
+        let s = zkp.solve(&k, &c, &x);
+        let proof = NonInteractiveProof { y1: y1.clone(), y2: y2.clone(), c, s, context: context.clone(), hash: HashAlgorithm::Sha256, group: Vec::new() };
+        if zkp.verify_non_interactive(&proof) {
+            break proof;
+        }
+    };
+    let status = register_values(&mut client, "mallory", &proof.y1, &proof.y2, &proof.c, &proof.s).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_out_of_range_registration_is_rejected() {
+    let (_server, mut client) = start().await;
+    let zkp = zkp();
+
+    // y1 + p 与 y1 模 p 同余，持有证明仍然成立
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let context = registration_context("mallory");
+    let mut proof = zkp.prove_non_interactive(&x, &context);
+    proof.y1 += &zkp.p;
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p);
+    let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p);
+    proof.c = zkp.fiat_shamir_challenge(&proof.y1, &proof.y2, &r1, &r2, &context);
+    proof.s = zkp.solve(&k, &proof.c, &x);
+    assert!(zkp.verify_non_interactive(&proof));
+
+    let status = register_values(&mut client, "mallory", &proof.y1, &proof.y2, &proof.c, &proof.s).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
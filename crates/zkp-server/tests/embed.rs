@@ -0,0 +1,3011 @@
+// 作为库使用：用自定义配置构建 AuthImpl，加入调用者自己的 tonic 服务器
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::Body;
+use hyper::http;
+use num_bigint::BigUint;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::transport::Server;
+use tonic::codec::ProstCodec;
+use tonic::{Code, Request, Status, Streaming};
+use zkp_core::{registration_context, HashAlgorithm, CHANNEL_BINDING_LEN, ZKP};
+use zkp_proto::zkp_auth::auth_admin_client::AuthAdminClient;
+use zkp_proto::zkp_auth::auth_client::AuthClient;
+use zkp_proto::zkp_auth::{
+    authenticate_request, authenticate_response, ApproveGuardianRecoveryRequest, AuthenticateRequest, ChangePasswordRequest, CreatePendingLoginRequest, AuthenticateResponse, AuthenticationAnswerRequest, DeleteUserRequest, ListSessionsRequest, ListUsersRequest, RevokeSessionsRequest, UnlockUserRequest, AuthenticationChallengeRequest, CompleteGuardianRecoveryRequest, DeleteUserDataRequest,
+    ErrorCode, ExportUserDataRequest, GetAuthParametersRequest, IntrospectSessionRequest, RecoverAccountRequest, RegisterRequest, ResetCredentialsRequest, StartGuardianRecoveryRequest,
+    KdfParams, LogoutRequest, QueryAuditLogRequest, UpdateProfileRequest, ValidateSessionRequest, ValidateSessionResponse, WatchRevocationsRequest,
+};
+use zkp_proto::zkp_auth::v2::auth_client::AuthClient as AuthV2Client;
+use zkp_proto::zkp_auth::v2::{GetParametersRequest, GetSaltRequest};
+use zkp_proto::detail::{error_code, error_detail};
+use zkp_proto::invalid::{invalid_field, InvalidReason};
+use zkp_proto::{retry, CORRELATION_ID_HEADER};
+use tonic::service::Interceptor;
+use zkp_server::challenge::challenge_context;
+use zkp_server::rbac::{self, ClientIdentity};
+use zkp_server::totp::totp_code;
+use zkp_server::store::{PendingChallenge, Purged, SessionInfo, StoreResult, UserInfo};
+use zkp_server::{
+    AdminAuth, AdminPolicy, AdminRole, AuditEvent, AuditKind, AuditLog, AuditQuery, AuthAdminImpl, AuthAdminServer, AuthImpl, AuthServer, AuthV2Impl, AuthV2Server, ChallengeSource, ChannelBinding, ConnectionLimits, Correlated, ExternalChallenge, HEALTH_SERVICES, FiatShamirChallenge, JwtIssuer, JwtKey, JwtVerifyingKey,
+    FileAuditLog, LockoutPolicy, MemoryAuditLog, MemoryStore, Quota, RateLimits, ServerConfig, SessionStore, StoreError, UserNameCharset, UserNameNormalization, UserNamePolicy, UserStore, V1, serve_gateway, serve_metrics, spawn_cleanup, verify_jwt,
+};
+
+#[tokio::test]
+async fn test_embedded_service_uses_injected_config() {
+    let config = ServerConfig { session_ttl_secs: 30, default_scopes: vec!["admin".to_string()], ..Default::default() };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let service = AuthServer::new(AuthImpl::new(config, MemoryStore::default()));
+    tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+    let request = RegisterRequest {
+        user: "alice".to_string(),
+        y1: proof.y1.to_bytes_be(),
+        y2: proof.y2.to_bytes_be(),
+        proof_c: proof.c.to_bytes_be(),
+        proof_s: proof.s.to_bytes_be(),
+        ..Default::default()
+    };
+    client.register(request).await.unwrap();
+
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+    let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+    let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() };
+    let challenge = client.create_authentication_challenge(request).await.unwrap().into_inner();
+    let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be();
+    let request = AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() };
+    let session = client.verify_authentication(request).await.unwrap().into_inner();
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    assert_eq!(session.scopes, vec!["admin".to_string()]);
+    assert!(session.expires_at > now && session.expires_at <= now + 30);
+}
+
+#[tokio::test]
+async fn test_proof_hash_policy() {
+    // 只接受 SHA3-256 的持有证明
+    let config = ServerConfig { proof_hashes: vec![HashAlgorithm::Sha3_256], ..Default::default() };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let service = AuthServer::new(AuthImpl::new(config, MemoryStore::default()));
+    tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let request = |hash: HashAlgorithm, name: &str| {
+        let proof = zkp.prove_non_interactive_with(hash, &x, &registration_context("alice"));
+        RegisterRequest {
+            user: "alice".to_string(),
+            y1: proof.y1.to_bytes_be(),
+            y2: proof.y2.to_bytes_be(),
+            proof_c: proof.c.to_bytes_be(),
+            proof_s: proof.s.to_bytes_be(),
+            proof_hash: name.to_string(),
+            ..Default::default()
+        }
+    };
+
+    // 未指定哈希（SHA-256）、不允许的哈希、未知的哈希和标注错误的证明都被拒绝
+    for (hash, name) in [(HashAlgorithm::Sha256, ""), (HashAlgorithm::Blake3, "blake3"), (HashAlgorithm::Sha3_256, "md5"), (HashAlgorithm::Blake3, "sha3-256")] {
+        let status = client.register(request(hash, name)).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument, "{}", name);
+    }
+    client.register(request(HashAlgorithm::Sha3_256, "sha3-256")).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_request_validation() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let service = AuthServer::new(AuthImpl::new(ServerConfig::default(), MemoryStore::default()));
+    tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+    let invalid = |status: Status| invalid_field(&status).unwrap_or_else(|| panic!("no invalid-field in {:?}", status));
+
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let register = |user: &str, y1: Vec<u8>| {
+        let proof = zkp.prove_non_interactive(&x, &registration_context(user));
+        RegisterRequest { user: user.to_string(), y1, y2: proof.y2.to_bytes_be(), proof_c: proof.c.to_bytes_be(), proof_s: proof.s.to_bytes_be(), ..Default::default() }
+    };
+    let y1 = ZKP::exponentiate(&zkp.alpha, &x, &zkp.p).to_bytes_be();
+    let p_len = zkp.p.to_bytes_be().len();
+
+    // 用户名：空的、过长的、包含控制字符或首尾空白的
+    for (user, reason) in [("", InvalidReason::Empty), (&"a".repeat(129), InvalidReason::TooLong), ("al\nice", InvalidReason::BadCharacters), (" alice", InvalidReason::BadCharacters)] {
+        let status = client.register(register(user, y1.clone())).await.unwrap_err();
+        assert_eq!(invalid(status), ("user".to_string(), reason), "{:?}", user);
+    }
+
+    // 公开值：空的、长于 p 的（即使只是前导零）、退化的和不在子群中的
+    let padded = |value: &[u8], len: usize| [vec![0; len - value.len()], value.to_vec()].concat();
+    let cases = [
+        (Vec::new(), InvalidReason::Empty),
+        (padded(&y1, p_len + 1), InvalidReason::TooLong),
+        (vec![1], InvalidReason::Degenerate),
+        ((&zkp.p - 1u32).to_bytes_be(), InvalidReason::Degenerate),
+        (zkp.p.to_bytes_be(), InvalidReason::OutOfRange),
+        (vec![2], InvalidReason::NotInSubgroup),
+    ];
+    for (value, reason) in cases {
+        let status = client.register(register("alice", value)).await.unwrap_err();
+        assert_eq!(invalid(status), ("y1".to_string(), reason));
+    }
+    let mut request = register("alice", y1.clone());
+    request.proof_c = Vec::new();
+    assert_eq!(invalid(client.register(request).await.unwrap_err()), ("proof_c".to_string(), InvalidReason::InvalidProof));
+
+    // 补齐到模数长度的前导零是允许的
+    client.register(register("alice", padded(&y1, p_len))).await.unwrap();
+
+    // 应答：空的、长于 q 的和不小于 q 的 s，每次都需要新的挑战
+    let q_len = zkp.q.to_bytes_be().len();
+    for (s, reason) in [(Vec::new(), InvalidReason::Empty), (vec![0; q_len + 1], InvalidReason::TooLong), (zkp.q.to_bytes_be(), InvalidReason::OutOfRange)] {
+        let k = ZKP::generate_random_number_below(&zkp.q);
+        let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+        let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+        let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() };
+        let challenge = client.create_authentication_challenge(request).await.unwrap().into_inner();
+        let status = client.verify_authentication(AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() }).await.unwrap_err();
+        assert_eq!(invalid(status), ("s".to_string(), reason));
+    }
+
+    // 校验失败的详情与元数据一致；用户、认证 ID 不存在和重复注册有各自的分类
+    let status = client.register(register("bob", Vec::new())).await.unwrap_err();
+    let detail = error_detail(&status).unwrap();
+    assert_eq!((detail.code(), detail.field.as_str(), detail.reason.as_str(), detail.message.as_str()), (ErrorCode::InvalidArgument, "y1", "empty", status.message()));
+    assert_eq!(error_code(&client.register(register("alice", y1.clone())).await.unwrap_err()), ErrorCode::UserAlreadyExists);
+    let request = AuthenticationChallengeRequest { user: "nobody".to_string(), r1: y1.clone(), r2: y1.clone(), ..Default::default() };
+    assert_eq!(error_code(&client.create_authentication_challenge(request).await.unwrap_err()), ErrorCode::UserNotFound);
+    let request = AuthenticationAnswerRequest { auth_id: "unknown".to_string(), s: vec![1], ..Default::default() };
+    assert_eq!(error_code(&client.verify_authentication(request).await.unwrap_err()), ErrorCode::ChallengeNotFound);
+
+    // 承诺和其他字段
+    let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1: y1.clone(), r2: Vec::new(), ..Default::default() };
+    assert_eq!(invalid(client.create_authentication_challenge(request).await.unwrap_err()), ("r2".to_string(), InvalidReason::Empty));
+    let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1: y1, device_id: "a b".to_string(), ..Default::default() };
+    assert_eq!(invalid(client.create_authentication_challenge(request).await.unwrap_err()), ("device_id".to_string(), InvalidReason::BadCharacters));
+    let request = AuthenticationChallengeRequest { user: "\u{7f}".to_string(), ..Default::default() };
+    assert_eq!(invalid(client.create_authentication_challenge(request).await.unwrap_err()), ("user".to_string(), InvalidReason::BadCharacters));
+}
+
+#[tokio::test]
+async fn test_user_name_policy() {
+    let policy = UserNamePolicy { normalization: UserNameNormalization::NfcLowercase, min_len: 3, reserved: vec!["Admin".to_string()], ..Default::default() };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let auth = Arc::new(AuthImpl::new(ServerConfig { user_names: policy, ..Default::default() }, MemoryStore::default()));
+    let admin_auth = AdminAuth::new(AdminPolicy::default().with_token("dashboard-token", "dashboard", AdminRole::Viewer));
+    tokio::spawn(Server::builder().add_service(AuthServer::from_arc(auth.clone())).add_service(AuthAdminServer::with_interceptor(AuthAdminImpl::new(auth), admin_auth)).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url.clone()).await.unwrap();
+    let mut admin = AuthAdminClient::connect(url).await.unwrap();
+    let invalid = |status: Status| invalid_field(&status).unwrap_or_else(|| panic!("no invalid-field in {:?}", status));
+
+    // 持有证明绑定到客户端发送的写法，用户记录保存在规范化后的用户名下
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let register = |user: &str| {
+        let proof = zkp.prove_non_interactive(&x, &registration_context(user));
+        RegisterRequest { user: user.to_string(), y1: proof.y1.to_bytes_be(), y2: proof.y2.to_bytes_be(), proof_c: proof.c.to_bytes_be(), proof_s: proof.s.to_bytes_be(), ..Default::default() }
+    };
+    client.register(register("Re\u{301}my")).await.unwrap();
+
+    // 大小写和组合方式不同的写法是同一个用户
+    assert_eq!(error_code(&client.register(register("RÉMY")).await.unwrap_err()), ErrorCode::UserAlreadyExists);
+    for user in ["rémy", "RE\u{301}MY", "Rémy"] {
+        login_in_group(&mut client, &zkp, user, &x).await.unwrap();
+    }
+    let sessions = admin.list_sessions(as_admin("dashboard-token", ListSessionsRequest { user: "RÉMY".to_string() })).await.unwrap().into_inner().sessions;
+    assert_eq!(sessions.len(), 3);
+
+    // 长度下限、保留名（同样规范化后比较）；登录时不检查保留名
+    assert_eq!(invalid(client.register(register("Al")).await.unwrap_err()), ("user".to_string(), InvalidReason::TooShort));
+    assert_eq!(invalid(client.register(register("ADMIN")).await.unwrap_err()), ("user".to_string(), InvalidReason::Reserved));
+    let request = GetAuthParametersRequest { user: "admin".to_string() };
+    assert_eq!(error_code(&client.get_auth_parameters(request).await.unwrap_err()), ErrorCode::UserNotFound);
+
+    // 监护人按同样的方式规范化，规范化后重复的是错误
+    let request = RegisterRequest { guardians: vec!["Rémy".to_string(), "re\u{301}my".to_string()], guardian_threshold: 1, ..register("carol") };
+    assert_eq!(invalid(client.register(request).await.unwrap_err()), ("guardians".to_string(), InvalidReason::Unsupported));
+
+    // ascii 字符集只允许字母、数字和 . _ - @ +
+    let ascii = UserNamePolicy { charset: UserNameCharset::Ascii, ..Default::default() };
+    assert_eq!(ascii.check("user", "bob.smith+zkp@example.com").unwrap(), "bob.smith+zkp@example.com");
+    assert_eq!(invalid(ascii.check("user", "rémy").unwrap_err()), ("user".to_string(), InvalidReason::BadCharacters));
+    assert_eq!(invalid(ascii.check("user", "bob smith").unwrap_err()), ("user".to_string(), InvalidReason::BadCharacters));
+
+    // 不规范化时不同的写法是不同的用户
+    let none = UserNamePolicy { normalization: UserNameNormalization::None, ..Default::default() };
+    assert_ne!(none.check("user", "Re\u{301}my").unwrap(), none.check("user", "Rémy").unwrap());
+    assert_eq!(UserNamePolicy::default().check("user", "Re\u{301}my").unwrap(), "Rémy");
+}
+
+#[tokio::test]
+async fn test_correlation_ids() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let service = Correlated(AuthServer::new(AuthImpl::new(ServerConfig::default(), MemoryStore::default())));
+    tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+
+    // 客户端提供的 ID 在错误的元数据中原样返回
+    let mut request = Request::new(AuthenticationAnswerRequest { auth_id: "unknown".to_string(), ..Default::default() });
+    request.metadata_mut().insert(CORRELATION_ID_HEADER, "login-42".parse().unwrap());
+    let status = client.verify_authentication(request).await.unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+    assert_eq!(status.metadata().get(CORRELATION_ID_HEADER).unwrap(), "login-42");
+
+    // 没有或不合法时由服务器生成
+    let response = client.validate_session(ValidateSessionRequest::default()).await.unwrap();
+    let generated = response.metadata().get(CORRELATION_ID_HEADER).unwrap().to_str().unwrap().to_string();
+    assert_eq!(generated.len(), 16);
+    let mut request = Request::new(ValidateSessionRequest::default());
+    request.metadata_mut().insert(CORRELATION_ID_HEADER, "has spaces".parse().unwrap());
+    let response = client.validate_session(request).await.unwrap();
+    let replaced = response.metadata().get(CORRELATION_ID_HEADER).unwrap();
+    assert_ne!(replaced, "has spaces");
+    assert_ne!(replaced.to_str().unwrap(), generated);
+}
+
+#[tokio::test]
+async fn test_expired_deadline_and_overlapping_challenges() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let service = AuthServer::new(AuthImpl::new(ServerConfig::default(), MemoryStore::default()));
+    tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+    let register = || RegisterRequest {
+        user: "alice".to_string(),
+        y1: proof.y1.to_bytes_be(),
+        y2: proof.y2.to_bytes_be(),
+        proof_c: proof.c.to_bytes_be(),
+        proof_s: proof.s.to_bytes_be(),
+        ..Default::default()
+    };
+
+    // 截止时间在验证持有证明之前已过：处理函数返回 DeadlineExceeded，或者 tonic 在读取请求时已经取消调用，用户都没有被写入
+    let mut request = Request::new(register());
+    request.set_timeout(Duration::from_nanos(1));
+    let code = client.register(request).await.unwrap_err().code();
+    assert!(matches!(code, Code::DeadlineExceeded | Code::Cancelled), "{:?}", code);
+    let commit = |k: &BigUint| AuthenticationChallengeRequest {
+        user: "alice".to_string(),
+        r1: ZKP::exponentiate(&zkp.alpha, k, &zkp.p).to_bytes_be(),
+        r2: ZKP::exponentiate(&zkp.beta, k, &zkp.p).to_bytes_be(),
+        ..Default::default()
+    };
+    let k1 = ZKP::generate_random_number_below(&zkp.q);
+    assert_eq!(client.create_authentication_challenge(commit(&k1)).await.unwrap_err().code(), Code::NotFound);
+    client.register(register()).await.unwrap();
+
+    // 两个重叠的挑战各自保存承诺，后一个挑战不会覆盖前一个
+    let first = client.create_authentication_challenge(commit(&k1)).await.unwrap().into_inner();
+    let k2 = ZKP::generate_random_number_below(&zkp.q);
+    let second = client.create_authentication_challenge(commit(&k2)).await.unwrap().into_inner();
+    for (k, challenge) in [(&k1, first), (&k2, second)] {
+        let s = zkp.solve(k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be();
+        let request = AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() };
+        client.verify_authentication(request).await.unwrap();
+    }
+}
+
+// 打开一个双向流认证，先发送第一条消息
+async fn open_stream(client: &mut AuthClient<tonic::transport::Channel>, first: AuthenticateRequest) -> (mpsc::Sender<AuthenticateRequest>, Streaming<AuthenticateResponse>) {
+    let (tx, rx) = mpsc::channel(2);
+    tx.send(first).await.unwrap();
+    let responses = client.authenticate(ReceiverStream::new(rx)).await.unwrap().into_inner();
+    (tx, responses)
+}
+
+#[tokio::test]
+async fn test_streaming_authentication() {
+    let mut clients = Vec::new();
+    for challenge_ttl_secs in [60, 1] {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let service = AuthServer::new(AuthImpl::new(ServerConfig { challenge_ttl_secs, ..Default::default() }, MemoryStore::default()));
+        tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+        clients.push(AuthClient::connect(url).await.unwrap());
+    }
+
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+    for client in &mut clients {
+        let request = RegisterRequest {
+            user: "alice".to_string(),
+            y1: proof.y1.to_bytes_be(),
+            y2: proof.y2.to_bytes_be(),
+            proof_c: proof.c.to_bytes_be(),
+            proof_s: proof.s.to_bytes_be(),
+            ..Default::default()
+        };
+        client.register(request).await.unwrap();
+    }
+    let commit = |k: &BigUint| AuthenticateRequest {
+        step: Some(authenticate_request::Step::Commitment(AuthenticationChallengeRequest {
+            user: "alice".to_string(),
+            r1: ZKP::exponentiate(&zkp.alpha, k, &zkp.p).to_bytes_be(),
+            r2: ZKP::exponentiate(&zkp.beta, k, &zkp.p).to_bytes_be(),
+            ..Default::default()
+        })),
+    };
+    let answer = |auth_id: &str, s: Vec<u8>| AuthenticateRequest { step: Some(authenticate_request::Step::Answer(AuthenticationAnswerRequest { auth_id: auth_id.to_string(), s, ..Default::default() })) };
+    let challenge_of = |response: Option<AuthenticateResponse>| match response.and_then(|response| response.step) {
+        Some(authenticate_response::Step::Challenge(challenge)) => challenge,
+        other => panic!("expected a challenge, got {:?}", other),
+    };
+    let mut client = clients[0].clone();
+
+    // 挑战只保存在流中：一元调用不能用它的认证 ID 应答；流上的解答可以省略认证 ID，成功后返回会话并结束流
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let (tx, mut responses) = open_stream(&mut client, commit(&k)).await;
+    let challenge = challenge_of(responses.message().await.unwrap());
+    let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be();
+    let request = AuthenticationAnswerRequest { auth_id: challenge.auth_id.clone(), s: s.clone(), ..Default::default() };
+    assert_eq!(client.verify_authentication(request).await.unwrap_err().code(), Code::NotFound);
+    tx.send(answer("", s)).await.unwrap();
+    let session = match responses.message().await.unwrap().and_then(|response| response.step) {
+        Some(authenticate_response::Step::Session(session)) => session,
+        other => panic!("expected a session, got {:?}", other),
+    };
+    assert!(responses.message().await.unwrap().is_none());
+    let response = client.validate_session(ValidateSessionRequest { session_id: session.session_id, ..Default::default() }).await.unwrap().into_inner();
+    assert_eq!((response.valid, response.user.as_str()), (true, "alice"));
+
+    // 错误的解答以 PermissionDenied 结束流
+    let (tx, mut responses) = open_stream(&mut client, commit(&k)).await;
+    let challenge = challenge_of(responses.message().await.unwrap());
+    let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &(&x + 1u32)).to_bytes_be();
+    tx.send(answer(&challenge.auth_id, s)).await.unwrap();
+    assert_eq!(responses.message().await.unwrap_err().code(), Code::PermissionDenied);
+
+    // 消息顺序错误或认证 ID 与流上的挑战不一致时返回 InvalidArgument
+    let (_tx, mut responses) = open_stream(&mut client, answer("", vec![1])).await;
+    assert_eq!(responses.message().await.unwrap_err().code(), Code::InvalidArgument);
+    let (tx, mut responses) = open_stream(&mut client, commit(&k)).await;
+    challenge_of(responses.message().await.unwrap());
+    tx.send(answer("someone-else", vec![1])).await.unwrap();
+    assert_eq!(responses.message().await.unwrap_err().code(), Code::InvalidArgument);
+
+    // 挑战过期前没有收到解答时服务器以 DeadlineExceeded 结束流
+    let (_tx, mut responses) = open_stream(&mut clients[1], commit(&k)).await;
+    challenge_of(responses.message().await.unwrap());
+    let status = tokio::time::timeout(Duration::from_secs(5), responses.message()).await.unwrap().unwrap_err();
+    assert_eq!((status.code(), error_code(&status)), (Code::DeadlineExceeded, ErrorCode::ChallengeExpired));
+}
+
+#[tokio::test]
+async fn test_export_and_delete_user_data() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let service = AuthServer::new(AuthImpl::new(ServerConfig::default(), MemoryStore::default()));
+    tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+    let request = RegisterRequest {
+        user: "alice".to_string(),
+        y1: proof.y1.to_bytes_be(),
+        y2: proof.y2.to_bytes_be(),
+        proof_c: proof.c.to_bytes_be(),
+        proof_s: proof.s.to_bytes_be(),
+        metadata: [("device".to_string(), "laptop".to_string())].into(),
+        ..Default::default()
+    };
+    client.register(request).await.unwrap();
+
+    // 每次证明身份都需要一个新的挑战，返回 (auth_id, s)
+    let challenger = client.clone();
+    let answer = || {
+        let mut client = challenger.clone();
+        let (zkp, x) = (zkp.clone(), x.clone());
+        async move {
+            let k = ZKP::generate_random_number_below(&zkp.q);
+            let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+            let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+            let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() };
+            let challenge = client.create_authentication_challenge(request).await.unwrap().into_inner();
+            (challenge.auth_id, zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be())
+        }
+    };
+    let (auth_id, s) = answer().await;
+    let session = client.verify_authentication(AuthenticationAnswerRequest { auth_id, s, ..Default::default() }).await.unwrap().into_inner();
+
+    // 错误的解答不能导出数据
+    let (auth_id, _) = answer().await;
+    let status = client.export_user_data(ExportUserDataRequest { auth_id, s: vec![1], totp_code: String::new() }).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let (auth_id, s) = answer().await;
+    let data = client.export_user_data(ExportUserDataRequest { auth_id, s, totp_code: String::new() }).await.unwrap().into_inner().data.unwrap();
+    assert_eq!(data.user, "alice");
+    assert_eq!(data.y1, proof.y1.to_bytes_be());
+    assert_eq!(data.metadata.get("device").map(String::as_str), Some("laptop"));
+    assert_eq!(data.sessions.len(), 1);
+    assert_eq!(data.pending_logins, 0);
+
+    // 删除后会话被吊销，用户不再存在
+    let mut revocations = client.watch_revocations(WatchRevocationsRequest {}).await.unwrap().into_inner();
+    let (auth_id, s) = answer().await;
+    let deleted = client.delete_user_data(DeleteUserDataRequest { auth_id, s, totp_code: String::new() }).await.unwrap().into_inner();
+    assert_eq!(deleted.revoked_sessions, 1);
+    let revoked = revocations.message().await.unwrap().unwrap();
+    assert_eq!((revoked.session_id.as_str(), revoked.reason.as_str()), (session.session_id.as_str(), "user-deleted"));
+    assert!(!client.validate_session(ValidateSessionRequest { session_id: session.session_id, ..Default::default() }).await.unwrap().into_inner().valid);
+    let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1: proof.y1.to_bytes_be(), r2: proof.y2.to_bytes_be(), ..Default::default() };
+    assert_eq!(client.create_authentication_challenge(request).await.unwrap_err().code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn test_profile_fields() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let service = AuthServer::new(AuthImpl::new(ServerConfig::default(), MemoryStore::default()));
+    tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+    let register = |display_name: &str| RegisterRequest {
+        user: "alice".to_string(),
+        y1: proof.y1.to_bytes_be(),
+        y2: proof.y2.to_bytes_be(),
+        proof_c: proof.c.to_bytes_be(),
+        proof_s: proof.s.to_bytes_be(),
+        display_name: display_name.to_string(),
+        contact: "alice@example.com".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(client.register(register(&"a".repeat(65))).await.unwrap_err().code(), Code::InvalidArgument);
+    client.register(register("Alice")).await.unwrap();
+
+    let challenger = client.clone();
+    let answer = || {
+        let mut client = challenger.clone();
+        let (zkp, x) = (zkp.clone(), x.clone());
+        async move {
+            let k = ZKP::generate_random_number_below(&zkp.q);
+            let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+            let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+            let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() };
+            let challenge = client.create_authentication_challenge(request).await.unwrap().into_inner();
+            (challenge.auth_id, zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be())
+        }
+    };
+
+    // 只修改设置了的字段，错误的解答不能修改
+    let (auth_id, _) = answer().await;
+    let request = UpdateProfileRequest { auth_id, s: vec![1], display_name: Some("Mallory".to_string()), contact: None, totp_code: String::new() };
+    assert_eq!(client.update_profile(request).await.unwrap_err().code(), Code::PermissionDenied);
+    let (auth_id, s) = answer().await;
+    let request = UpdateProfileRequest { auth_id, s, display_name: Some("Alice L.".to_string()), contact: None, totp_code: String::new() };
+    let profile = client.update_profile(request).await.unwrap().into_inner().profile.unwrap();
+    assert_eq!((profile.display_name.as_str(), profile.contact.as_str()), ("Alice L.", "alice@example.com"));
+    assert!(profile.created_at > 0);
+
+    // 资源服务器通过会话内省读取账户资料
+    let (auth_id, s) = answer().await;
+    let session = client.verify_authentication(AuthenticationAnswerRequest { auth_id, s, ..Default::default() }).await.unwrap().into_inner();
+    let response = client.introspect_session(IntrospectSessionRequest { session_id: session.session_id }).await.unwrap().into_inner();
+    assert_eq!(response.profile, Some(profile));
+}
+
+// 拦截器代替 TLS 层写入本连接的导出值
+#[allow(clippy::result_large_err)]
+fn export_binding(mut request: Request<()>) -> Result<Request<()>, Status> {
+    request.extensions_mut().insert(ChannelBinding(vec![7; CHANNEL_BINDING_LEN]));
+    Ok(request)
+}
+
+#[tokio::test]
+async fn test_channel_bound_challenges() {
+    let config = ServerConfig { require_channel_binding: true, ..Default::default() };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let service = AuthServer::with_interceptor(AuthImpl::new(config.clone(), MemoryStore::default()), export_binding);
+    tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+    let request = RegisterRequest {
+        user: "alice".to_string(),
+        y1: proof.y1.to_bytes_be(),
+        y2: proof.y2.to_bytes_be(),
+        proof_c: proof.c.to_bytes_be(),
+        proof_s: proof.s.to_bytes_be(),
+        ..Default::default()
+    };
+    client.register(request).await.unwrap();
+
+    // 用客户端一端的绑定值计算应答：同一个通道时通过，中间人转发（两端绑定值不同）或忽略绑定时被拒绝
+    for (client_binding, accepted) in [(Some(vec![7; CHANNEL_BINDING_LEN]), true), (Some(vec![8; CHANNEL_BINDING_LEN]), false), (None, false)] {
+        let k = ZKP::generate_random_number_below(&zkp.q);
+        let (r1, r2) = (ZKP::exponentiate(&zkp.alpha, &k, &zkp.p), ZKP::exponentiate(&zkp.beta, &k, &zkp.p));
+        let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1: r1.to_bytes_be(), r2: r2.to_bytes_be(), ..Default::default() };
+        let challenge = client.create_authentication_challenge(request).await.unwrap().into_inner();
+        assert!(challenge.channel_bound);
+        let c = BigUint::from_bytes_be(&challenge.c);
+        let c = match &client_binding {
+            Some(client_binding) => zkp.bind_challenge(&c, &r1, &r2, client_binding),
+            None => c,
+        };
+        let request = AuthenticationAnswerRequest { auth_id: challenge.auth_id, s: zkp.solve(&k, &c, &x).to_bytes_be(), ..Default::default() };
+        assert_eq!(client.verify_authentication(request).await.is_ok(), accepted);
+    }
+
+    // 要求通道绑定时，没有导出值的连接不能请求挑战
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let service = AuthServer::new(AuthImpl::new(config, MemoryStore::default()));
+    tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+    let status = client.create_authentication_challenge(AuthenticationChallengeRequest::default()).await.unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn test_device_bound_sessions() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let service = AuthServer::new(AuthImpl::new(ServerConfig::default(), MemoryStore::default()));
+    tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+    let request = RegisterRequest {
+        user: "alice".to_string(),
+        y1: proof.y1.to_bytes_be(),
+        y2: proof.y2.to_bytes_be(),
+        proof_c: proof.c.to_bytes_be(),
+        proof_s: proof.s.to_bytes_be(),
+        ..Default::default()
+    };
+    client.register(request).await.unwrap();
+
+    let challenger = client.clone();
+    let answer = |device_id: &str| {
+        let mut client = challenger.clone();
+        let (zkp, x, device_id) = (zkp.clone(), x.clone(), device_id.to_string());
+        async move {
+            let k = ZKP::generate_random_number_below(&zkp.q);
+            let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+            let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+            let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, device_id, ..Default::default() };
+            let challenge = client.create_authentication_challenge(request).await?.into_inner();
+            Ok::<_, Status>((challenge.auth_id, zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be()))
+        }
+    };
+    assert_eq!(answer(&"d".repeat(129)).await.unwrap_err().code(), Code::InvalidArgument);
+
+    // 应答必须来自请求挑战的设备
+    let (auth_id, s) = answer("laptop").await.unwrap();
+    let request = AuthenticationAnswerRequest { auth_id, s, device_id: "phone".to_string(), ..Default::default() };
+    assert_eq!(client.verify_authentication(request).await.unwrap_err().code(), Code::PermissionDenied);
+
+    // 绑定到设备的会话在其他设备上无效，会话本身不受影响
+    let (auth_id, s) = answer("laptop").await.unwrap();
+    let request = AuthenticationAnswerRequest { auth_id, s, device_id: "laptop".to_string(), ..Default::default() };
+    let session_id = client.verify_authentication(request).await.unwrap().into_inner().session_id;
+    let validate = |device_id: &str| ValidateSessionRequest { session_id: session_id.clone(), device_id: device_id.to_string() };
+    assert!(!client.validate_session(validate("")).await.unwrap().into_inner().valid);
+    assert!(!client.validate_session(validate("phone")).await.unwrap().into_inner().valid);
+    assert!(client.validate_session(validate("laptop")).await.unwrap().into_inner().valid);
+    let response = client.introspect_session(IntrospectSessionRequest { session_id: session_id.clone() }).await.unwrap().into_inner();
+    assert_eq!(response.device_id, "laptop");
+
+    // 没有设备标识时会话不绑定设备
+    let (auth_id, s) = answer("").await.unwrap();
+    let session_id = client.verify_authentication(AuthenticationAnswerRequest { auth_id, s, ..Default::default() }).await.unwrap().into_inner().session_id;
+    let request = ValidateSessionRequest { session_id, device_id: "anything".to_string() };
+    assert!(client.validate_session(request).await.unwrap().into_inner().valid);
+}
+
+#[tokio::test]
+async fn test_recovery_codes() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let service = AuthServer::new(AuthImpl::new(ServerConfig::default(), MemoryStore::default()));
+    tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+    let register = |recovery_codes: u32| RegisterRequest {
+        user: "alice".to_string(),
+        y1: proof.y1.to_bytes_be(),
+        y2: proof.y2.to_bytes_be(),
+        proof_c: proof.c.to_bytes_be(),
+        proof_s: proof.s.to_bytes_be(),
+        recovery_codes,
+        ..Default::default()
+    };
+    assert_eq!(client.register(register(17)).await.unwrap_err().code(), Code::InvalidArgument);
+    let codes = client.register(register(2)).await.unwrap().into_inner().recovery_codes;
+    assert_eq!(codes.len(), 2);
+    assert_ne!(codes[0], codes[1]);
+
+    // 用私钥 x 回答一个新的挑战，返回 (auth_id, s)
+    let challenger = client.clone();
+    let answer = |x: BigUint| {
+        let mut client = challenger.clone();
+        let zkp = zkp.clone();
+        async move {
+            let k = ZKP::generate_random_number_below(&zkp.q);
+            let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+            let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+            let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() };
+            let challenge = client.create_authentication_challenge(request).await.unwrap().into_inner();
+            (challenge.auth_id, zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be())
+        }
+    };
+
+    // 恢复之前用密码建立的普通会话
+    let (auth_id, s) = answer(x.clone()).await;
+    let session = client.verify_authentication(AuthenticationAnswerRequest { auth_id, s, ..Default::default() }).await.unwrap().into_inner();
+    let validate = |session_id: &str| ValidateSessionRequest { session_id: session_id.to_string(), ..Default::default() };
+
+    // 错误的恢复码和未知用户返回相同的错误；恢复码只能使用一次
+    let recover = |user: &str, code: &str| RecoverAccountRequest { user: user.to_string(), code: code.to_string() };
+    assert_eq!(client.recover_account(recover("alice", "not-a-code")).await.unwrap_err().code(), Code::PermissionDenied);
+    assert_eq!(client.recover_account(recover("bob", &codes[0])).await.unwrap_err().code(), Code::PermissionDenied);
+    let recovery = client.recover_account(recover("alice", &codes[0])).await.unwrap().into_inner();
+    assert_eq!(recovery.scopes, vec!["credential-reset".to_string()]);
+    assert_eq!(client.recover_account(recover("alice", &codes[0])).await.unwrap_err().code(), Code::PermissionDenied);
+
+    let (auth_id, s) = answer(x.clone()).await;
+    let data = client.export_user_data(ExportUserDataRequest { auth_id, s, totp_code: String::new() }).await.unwrap().into_inner().data.unwrap();
+    assert_eq!((data.recovery_codes_remaining, data.credential_reset_required), (1, true));
+
+    // 恢复会话不是登录会话；重置密码之前普通会话无效，也不能再用密码登录
+    assert!(!client.validate_session(validate(&recovery.session_id)).await.unwrap().into_inner().valid);
+    assert!(!client.validate_session(validate(&session.session_id)).await.unwrap().into_inner().valid);
+    let (auth_id, s) = answer(x.clone()).await;
+    let status = client.verify_authentication(AuthenticationAnswerRequest { auth_id, s, ..Default::default() }).await.unwrap_err();
+    assert_eq!((status.code(), error_code(&status)), (Code::FailedPrecondition, ErrorCode::CredentialResetRequired));
+
+    // 普通会话不能重置密码
+    let new_x = ZKP::generate_random_number_below(&zkp.q);
+    let new_proof = zkp.prove_non_interactive(&new_x, &registration_context("alice"));
+    let reset = |session_id: &str| ResetCredentialsRequest {
+        session_id: session_id.to_string(),
+        y1: new_proof.y1.to_bytes_be(),
+        y2: new_proof.y2.to_bytes_be(),
+        user: "alice".to_string(),
+        proof_c: new_proof.c.to_bytes_be(),
+        proof_s: new_proof.s.to_bytes_be(),
+        ..Default::default()
+    };
+    assert_eq!(client.reset_credentials(reset(&session.session_id)).await.unwrap_err().code(), Code::PermissionDenied);
+
+    // 新的公开值必须附有绑定该账户用户名的持有证明，不能把别人的公开值设为自己的凭据
+    let status = client.reset_credentials(ResetCredentialsRequest { proof_c: Vec::new(), ..reset(&recovery.session_id) }).await.unwrap_err();
+    assert_eq!((status.code(), error_code(&status)), (Code::InvalidArgument, ErrorCode::BadProof));
+    let other = zkp.prove_non_interactive(&new_x, &registration_context("mallory"));
+    let request = ResetCredentialsRequest { proof_c: other.c.to_bytes_be(), proof_s: other.s.to_bytes_be(), ..reset(&recovery.session_id) };
+    assert_eq!(error_code(&client.reset_credentials(request).await.unwrap_err()), ErrorCode::BadProof);
+    let request = ResetCredentialsRequest { user: "mallory".to_string(), proof_c: other.c.to_bytes_be(), proof_s: other.s.to_bytes_be(), ..reset(&recovery.session_id) };
+    let status = client.reset_credentials(request).await.unwrap_err();
+    assert_eq!(invalid_field(&status), Some(("user".to_string(), InvalidReason::Mismatch)));
+
+    // 重置后所有会话失效，旧私钥不能再登录，新私钥可以
+    client.reset_credentials(reset(&recovery.session_id)).await.unwrap();
+    for session_id in [&session.session_id, &recovery.session_id] {
+        assert!(!client.validate_session(validate(session_id)).await.unwrap().into_inner().valid);
+    }
+    assert_eq!(client.reset_credentials(reset(&recovery.session_id)).await.unwrap_err().code(), Code::PermissionDenied);
+    let (auth_id, s) = answer(x).await;
+    let request = AuthenticationAnswerRequest { auth_id, s, ..Default::default() };
+    assert_eq!(client.verify_authentication(request).await.unwrap_err().code(), Code::PermissionDenied);
+    let (auth_id, s) = answer(new_x.clone()).await;
+    let data = client.export_user_data(ExportUserDataRequest { auth_id, s, totp_code: String::new() }).await.unwrap().into_inner().data.unwrap();
+    assert_eq!((data.recovery_codes_remaining, data.credential_reset_required), (1, false));
+}
+
+#[tokio::test]
+async fn test_guardian_recovery() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let service = AuthServer::new(AuthImpl::new(ServerConfig::default(), MemoryStore::default()));
+    tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+
+    let zkp = ZKP::get_constants();
+    let register = |user: &str, x: &BigUint, guardians: &[&str], guardian_threshold: u32| {
+        let proof = zkp.prove_non_interactive(x, &registration_context(user));
+        RegisterRequest {
+            user: user.to_string(),
+            y1: proof.y1.to_bytes_be(),
+            y2: proof.y2.to_bytes_be(),
+            proof_c: proof.c.to_bytes_be(),
+            proof_s: proof.s.to_bytes_be(),
+            guardians: guardians.iter().map(|guardian| guardian.to_string()).collect(),
+            guardian_threshold,
+            ..Default::default()
+        }
+    };
+    let keys: Vec<BigUint> = (0..5).map(|_| ZKP::generate_random_number_below(&zkp.q)).collect();
+    let guardians = ["bob", "carol", "dave"];
+    assert_eq!(client.register(register("alice", &keys[0], &["alice"], 1)).await.unwrap_err().code(), Code::InvalidArgument);
+    for (user, x) in [("bob", &keys[1]), ("carol", &keys[2]), ("mallory", &keys[3])] {
+        client.register(register(user, x, &[], 0)).await.unwrap();
+    }
+    // 监护人必须已经注册，不能指定一个以后任何人都可以注册的用户名
+    let status = client.register(register("alice", &keys[0], &guardians, 2)).await.unwrap_err();
+    assert_eq!(invalid_field(&status), Some(("guardians".to_string(), InvalidReason::Unsupported)));
+    client.register(register("dave", &keys[4], &[], 0)).await.unwrap();
+    assert_eq!(client.register(register("alice", &keys[0], &guardians, 4)).await.unwrap_err().code(), Code::InvalidArgument);
+    assert_eq!(client.register(register("alice", &keys[0], &guardians, 0)).await.unwrap_err().code(), Code::InvalidArgument);
+    client.register(register("alice", &keys[0], &guardians, 2)).await.unwrap();
+
+    // 以 user 的身份回答一个新的挑战，返回 (auth_id, s)
+    let challenger = client.clone();
+    let answer = |user: &str, x: &BigUint| {
+        let mut client = challenger.clone();
+        let (zkp, x, user) = (zkp.clone(), x.clone(), user.to_string());
+        async move {
+            let k = ZKP::generate_random_number_below(&zkp.q);
+            let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+            let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+            let request = AuthenticationChallengeRequest { user, r1, r2, ..Default::default() };
+            let challenge = client.create_authentication_challenge(request).await.unwrap().into_inner();
+            (challenge.auth_id, zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be())
+        }
+    };
+
+    // dave 删除账户后 mallory 注册同名用户，不能代替 dave 批准
+    let (auth_id, s) = answer("dave", &keys[4]).await;
+    client.delete_user_data(DeleteUserDataRequest { auth_id, s, totp_code: String::new() }).await.unwrap();
+    client.register(register("dave", &keys[3], &[], 0)).await.unwrap();
+
+    let start = |user: &str| StartGuardianRecoveryRequest { user: user.to_string() };
+    assert_eq!(client.start_guardian_recovery(start("bob")).await.unwrap_err().code(), Code::FailedPrecondition);
+    let recovery = client.start_guardian_recovery(start("alice")).await.unwrap().into_inner();
+    assert_eq!((recovery.guardians.len(), recovery.threshold), (3, 2));
+    let recovery_id = recovery.recovery_id;
+    let (auth_id, s) = answer("dave", &keys[3]).await;
+    let request = ApproveGuardianRecoveryRequest { recovery_id: recovery_id.clone(), auth_id, s, totp_code: String::new() };
+    assert_eq!(client.approve_guardian_recovery(request).await.unwrap_err().code(), Code::PermissionDenied);
+
+    // 其他人发起的恢复不取消进行中的恢复，同时进行的恢复数有上限
+    for _ in 0..3 {
+        client.start_guardian_recovery(start("alice")).await.unwrap();
+    }
+    assert_eq!(client.start_guardian_recovery(start("alice")).await.unwrap_err().code(), Code::ResourceExhausted);
+    let complete = || CompleteGuardianRecoveryRequest { recovery_id: recovery_id.clone() };
+    assert_eq!(client.complete_guardian_recovery(complete()).await.unwrap_err().code(), Code::FailedPrecondition);
+
+    // 只有监护人能批准，同一监护人重复批准只计一次
+    let (auth_id, s) = answer("mallory", &keys[3]).await;
+    let request = ApproveGuardianRecoveryRequest { recovery_id: recovery_id.clone(), auth_id, s, totp_code: String::new() };
+    assert_eq!(client.approve_guardian_recovery(request).await.unwrap_err().code(), Code::PermissionDenied);
+    for _ in 0..2 {
+        let (auth_id, s) = answer("bob", &keys[1]).await;
+        let request = ApproveGuardianRecoveryRequest { recovery_id: recovery_id.clone(), auth_id, s, totp_code: String::new() };
+        assert_eq!(client.approve_guardian_recovery(request).await.unwrap().into_inner().approvals, 1);
+    }
+    assert_eq!(client.complete_guardian_recovery(complete()).await.unwrap_err().code(), Code::FailedPrecondition);
+    let (auth_id, s) = answer("carol", &keys[2]).await;
+    let request = ApproveGuardianRecoveryRequest { recovery_id: recovery_id.clone(), auth_id, s, totp_code: String::new() };
+    assert_eq!(client.approve_guardian_recovery(request).await.unwrap().into_inner().approvals, 2);
+
+    // 达到门限后得到恢复会话，恢复 ID 随即失效；恢复会话可以重置密码
+    let session = client.complete_guardian_recovery(complete()).await.unwrap().into_inner();
+    assert_eq!(session.scopes, vec!["credential-reset".to_string()]);
+    assert_eq!(client.complete_guardian_recovery(complete()).await.unwrap_err().code(), Code::NotFound);
+    let (auth_id, s) = answer("alice", &keys[0]).await;
+    let status = client.verify_authentication(AuthenticationAnswerRequest { auth_id, s, ..Default::default() }).await.unwrap_err();
+    assert_eq!(error_code(&status), ErrorCode::CredentialResetRequired);
+    let new_x = ZKP::generate_random_number_below(&zkp.q);
+    let new_proof = zkp.prove_non_interactive(&new_x, &registration_context("alice"));
+    let request = ResetCredentialsRequest {
+        session_id: session.session_id,
+        y1: new_proof.y1.to_bytes_be(),
+        y2: new_proof.y2.to_bytes_be(),
+        user: "alice".to_string(),
+        proof_c: new_proof.c.to_bytes_be(),
+        proof_s: new_proof.s.to_bytes_be(),
+        ..Default::default()
+    };
+    client.reset_credentials(request).await.unwrap();
+    let (auth_id, s) = answer("alice", &new_x).await;
+    client.verify_authentication(AuthenticationAnswerRequest { auth_id, s, ..Default::default() }).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_honeytoken_alerts() {
+    // webhook 接收端：把收到的请求体转发给测试
+    let (tx, mut webhook) = tokio::sync::mpsc::channel::<String>(4);
+    let make_service = make_service_fn(move |_| {
+        let tx = tx.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: hyper::Request<Body>| {
+                let tx = tx.clone();
+                async move {
+                    let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                    tx.send(String::from_utf8(body.to_vec()).unwrap()).await.unwrap();
+                    Ok::<_, Infallible>(hyper::Response::new(Body::empty()))
+                }
+            }))
+        }
+    });
+    let receiver = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+    let config = ServerConfig {
+        honeytokens: vec!["admin".to_string()],
+        alert_webhook: Some(format!("http://{}/alerts", receiver.local_addr())),
+        ..Default::default()
+    };
+    tokio::spawn(receiver);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let auth = AuthImpl::new(config, MemoryStore::default());
+    let mut alerts = auth.subscribe_honeytoken_alerts();
+    tokio::spawn(Server::builder().add_service(Correlated(AuthServer::new(auth))).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+
+    let zkp = ZKP::get_constants();
+    let challenge = |user: &str| {
+        let k = ZKP::generate_random_number_below(&zkp.q);
+        let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+        let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+        AuthenticationChallengeRequest { user: user.to_string(), r1, r2, ..Default::default() }
+    };
+    for user in ["alice", "admin"] {
+        let x = ZKP::generate_random_number_below(&zkp.q);
+        let proof = zkp.prove_non_interactive(&x, &registration_context(user));
+        let request = RegisterRequest {
+            user: user.to_string(),
+            y1: proof.y1.to_bytes_be(),
+            y2: proof.y2.to_bytes_be(),
+            proof_c: proof.c.to_bytes_be(),
+            proof_s: proof.s.to_bytes_be(),
+            ..Default::default()
+        };
+        client.register(request).await.unwrap();
+    }
+
+    // 普通账户不触发告警；诱饵账户的挑战照常返回，同时触发告警
+    client.create_authentication_challenge(challenge("alice")).await.unwrap();
+    let mut request = Request::new(challenge("admin"));
+    request.metadata_mut().insert(CORRELATION_ID_HEADER, "stuffing-1".parse().unwrap());
+    client.create_authentication_challenge(request).await.unwrap();
+
+    let alert = alerts.recv().await.unwrap();
+    assert_eq!((alert.user.as_str(), alert.rpc, alert.correlation_id.as_str()), ("admin", "CreateAuthenticationChallenge", "stuffing-1"));
+    assert!(alert.remote_addr.is_some());
+    let body = tokio::time::timeout(Duration::from_secs(5), webhook.recv()).await.unwrap().unwrap();
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["event"], "honeytoken-access");
+    assert_eq!(body["user"], "admin");
+    assert_eq!(body["correlation_id"], "stuffing-1");
+
+    let request = RecoverAccountRequest { user: "admin".to_string(), code: "guess".to_string() };
+    assert_eq!(client.recover_account(request).await.unwrap_err().code(), Code::PermissionDenied);
+    assert_eq!(alerts.recv().await.unwrap().rpc, "RecoverAccount");
+}
+
+// 带上管理令牌或 mTLS 客户端身份，经过拦截器
+#[allow(clippy::result_large_err)]
+fn authenticated(interceptor: &mut AdminAuth, token: Option<&str>, subject: Option<&str>) -> Result<Request<()>, Status> {
+    let mut request = Request::new(());
+    if let Some(token) = token {
+        request.metadata_mut().insert("authorization", format!("Bearer {}", token).parse().unwrap());
+    }
+    if let Some(subject) = subject {
+        request.extensions_mut().insert(ClientIdentity(subject.to_string()));
+    }
+    interceptor.call(request)
+}
+
+#[test]
+fn test_admin_roles() {
+    let policy = AdminPolicy::default()
+        .with_token("dashboard-token", "dashboard", AdminRole::Viewer)
+        .with_token("oncall-token", "oncall", AdminRole::Operator)
+        .with_client_identity("ops.example.com", AdminRole::Admin);
+    let mut interceptor = AdminAuth::new(policy);
+
+    // 没有凭据、未知的令牌或未知的客户端身份都不能通过
+    assert_eq!(authenticated(&mut interceptor, None, None).unwrap_err().code(), Code::Unauthenticated);
+    assert_eq!(authenticated(&mut interceptor, Some("guess"), None).unwrap_err().code(), Code::Unauthenticated);
+    assert_eq!(authenticated(&mut interceptor, None, Some("evil.example.com")).unwrap_err().code(), Code::Unauthenticated);
+    assert_eq!(rbac::require(&Request::new(()), AdminRole::Viewer).unwrap_err().code(), Code::Unauthenticated);
+
+    // 只读面板不能吊销会话或删除用户
+    let dashboard = authenticated(&mut interceptor, Some("dashboard-token"), None).unwrap();
+    assert_eq!(rbac::require(&dashboard, AdminRole::Viewer).unwrap().name, "dashboard");
+    assert_eq!(rbac::require(&dashboard, AdminRole::Operator).unwrap_err().code(), Code::PermissionDenied);
+    let oncall = authenticated(&mut interceptor, Some("oncall-token"), None).unwrap();
+    assert!(rbac::require(&oncall, AdminRole::Operator).is_ok());
+    assert_eq!(rbac::require(&oncall, AdminRole::Admin).unwrap_err().code(), Code::PermissionDenied);
+    let ops = authenticated(&mut interceptor, Some("dashboard-token"), Some("ops.example.com")).unwrap();
+    assert_eq!(rbac::require(&ops, AdminRole::Admin).unwrap().role, AdminRole::Admin);
+
+    assert_eq!(AdminRole::from_name("Operator"), Some(AdminRole::Operator));
+    assert!(!format!("{:?}", AdminPolicy::default().with_token("dashboard-token", "dashboard", AdminRole::Viewer)).contains("dashboard"));
+}
+
+// 带有管理令牌的请求
+fn as_admin<T>(token: &str, message: T) -> Request<T> {
+    let mut request = Request::new(message);
+    request.metadata_mut().insert("authorization", format!("Bearer {}", token).parse().unwrap());
+    request
+}
+
+#[tokio::test]
+async fn test_admin_service() {
+    let lockout = LockoutPolicy { threshold: 1, ..Default::default() };
+    let auth = Arc::new(AuthImpl::new(ServerConfig { lockout: Some(lockout), ..Default::default() }, MemoryStore::default()));
+    let policy = AdminPolicy::default()
+        .with_token("dashboard-token", "dashboard", AdminRole::Viewer)
+        .with_token("oncall-token", "oncall", AdminRole::Operator)
+        .with_token("root-token", "root", AdminRole::Admin);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(
+        Server::builder()
+            .add_service(AuthServer::from_arc(auth.clone()))
+            .add_service(AuthAdminServer::with_interceptor(AuthAdminImpl::new(auth.clone()), AdminAuth::new(policy)))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    let mut client = AuthClient::connect(url.clone()).await.unwrap();
+    let mut admin = AuthAdminClient::connect(url).await.unwrap();
+
+    // 三个用户，alice 登录两次，bob 猜错一次后被锁定
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    for user in ["carol", "alice", "bob"] {
+        let proof = zkp.prove_non_interactive(&x, &registration_context(user));
+        let request = RegisterRequest {
+            user: user.to_string(),
+            y1: proof.y1.to_bytes_be(),
+            y2: proof.y2.to_bytes_be(),
+            proof_c: proof.c.to_bytes_be(),
+            proof_s: proof.s.to_bytes_be(),
+            ..Default::default()
+        };
+        client.register(request).await.unwrap();
+    }
+    let login = |user: &'static str, secret: BigUint| {
+        let mut client = client.clone();
+        let zkp = zkp.clone();
+        async move {
+            let k = ZKP::generate_random_number_below(&zkp.q);
+            let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+            let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+            let challenge = client.create_authentication_challenge(AuthenticationChallengeRequest { user: user.to_string(), r1, r2, ..Default::default() }).await.unwrap().into_inner();
+            let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &secret).to_bytes_be();
+            client.verify_authentication(AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() }).await
+        }
+    };
+    let first = login("alice", x.clone()).await.unwrap().into_inner().session_id;
+    login("alice", x.clone()).await.unwrap();
+    login("bob", &x + 1u32).await.unwrap_err();
+
+    // 没有令牌或角色不够的调用在处理函数之前或开始时被拒绝
+    assert_eq!(admin.list_users(ListUsersRequest::default()).await.unwrap_err().code(), Code::Unauthenticated);
+    let request = as_admin("dashboard-token", UnlockUserRequest { user: "bob".to_string() });
+    assert_eq!(admin.unlock_user(request).await.unwrap_err().code(), Code::PermissionDenied);
+    let request = as_admin("oncall-token", DeleteUserRequest { user: "carol".to_string() });
+    assert_eq!(admin.delete_user(request).await.unwrap_err().code(), Code::PermissionDenied);
+
+    // 按用户名分页，锁定的用户带有锁定结束时间
+    let page = admin.list_users(as_admin("dashboard-token", ListUsersRequest { page_size: 2, page_token: String::new() })).await.unwrap().into_inner();
+    assert_eq!(page.users.iter().map(|user| user.user.as_str()).collect::<Vec<_>>(), ["alice", "bob"]);
+    assert_eq!(page.users[0].locked_until, 0);
+    assert!(page.users[1].locked_until > SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs());
+    let page = admin.list_users(as_admin("dashboard-token", ListUsersRequest { page_size: 2, page_token: page.next_page_token })).await.unwrap().into_inner();
+    assert_eq!(page.users.iter().map(|user| user.user.as_str()).collect::<Vec<_>>(), ["carol"]);
+    assert!(page.next_page_token.is_empty());
+
+    // 会话只以 handle 返回，吊销一个会话后另一个仍然有效
+    let sessions = admin.list_sessions(as_admin("dashboard-token", ListSessionsRequest { user: "alice".to_string() })).await.unwrap().into_inner().sessions;
+    assert_eq!(sessions.len(), 2);
+    assert!(sessions.iter().all(|session| session.handle.len() == 16 && session.handle != first));
+    let handle = zkp_server::admin::session_handle(&first);
+    let request = as_admin("oncall-token", RevokeSessionsRequest { user: "alice".to_string(), handle: handle.clone() });
+    assert_eq!(admin.revoke_sessions(request).await.unwrap().into_inner().revoked_sessions, 1);
+    assert!(!client.clone().validate_session(ValidateSessionRequest { session_id: first, ..Default::default() }).await.unwrap().into_inner().valid);
+    let request = as_admin("oncall-token", RevokeSessionsRequest { user: "alice".to_string(), handle });
+    assert_eq!(admin.revoke_sessions(request).await.unwrap_err().code(), Code::NotFound);
+    let request = as_admin("oncall-token", RevokeSessionsRequest { user: "alice".to_string(), handle: String::new() });
+    assert_eq!(admin.revoke_sessions(request).await.unwrap().into_inner().revoked_sessions, 1);
+
+    // 解锁后 bob 可以登录；删除的用户不能再登录
+    assert_eq!(login("bob", x.clone()).await.unwrap_err().code(), Code::Unavailable);
+    assert!(admin.unlock_user(as_admin("oncall-token", UnlockUserRequest { user: "bob".to_string() })).await.unwrap().into_inner().was_locked);
+    login("bob", x.clone()).await.unwrap();
+    assert_eq!(admin.delete_user(as_admin("root-token", DeleteUserRequest { user: "bob".to_string() })).await.unwrap().into_inner().revoked_sessions, 1);
+    assert_eq!(admin.delete_user(as_admin("root-token", DeleteUserRequest { user: "bob".to_string() })).await.unwrap_err().code(), Code::NotFound);
+
+    // 没有配置审计日志时无法查询
+    let request = as_admin("dashboard-token", QueryAuditLogRequest::default());
+    assert_eq!(admin.query_audit_log(request).await.unwrap_err().code(), Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn test_audit_log() {
+    let audit_log = Arc::new(MemoryAuditLog::default());
+    let auth = Arc::new(AuthImpl::new(ServerConfig { audit_log: Some(audit_log.clone()), ..Default::default() }, MemoryStore::default()));
+    let policy = AdminPolicy::default().with_token("dashboard-token", "dashboard", AdminRole::Viewer);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(
+        Server::builder()
+            .add_service(AuthServer::from_arc(auth.clone()))
+            .add_service(AuthAdminServer::with_interceptor(AuthAdminImpl::new(auth.clone()), AdminAuth::new(policy)))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    let mut client = AuthClient::connect(url.clone()).await.unwrap();
+    let mut admin = AuthAdminClient::connect(url).await.unwrap();
+
+    // alice 注册两次（第二次失败），登录一次成功、一次失败，然后注销；bob 注册
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    for user in ["alice", "alice", "bob"] {
+        let proof = zkp.prove_non_interactive(&x, &registration_context(user));
+        let request = RegisterRequest {
+            user: user.to_string(),
+            y1: proof.y1.to_bytes_be(),
+            y2: proof.y2.to_bytes_be(),
+            proof_c: proof.c.to_bytes_be(),
+            proof_s: proof.s.to_bytes_be(),
+            ..Default::default()
+        };
+        let _ = client.register(request).await;
+    }
+    let mut session_id = String::new();
+    for secret in [x.clone(), &x + 1u32] {
+        let k = ZKP::generate_random_number_below(&zkp.q);
+        let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+        let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+        let challenge = client.create_authentication_challenge(AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() }).await.unwrap().into_inner();
+        let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &secret).to_bytes_be();
+        if let Ok(response) = client.verify_authentication(AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() }).await {
+            session_id = response.into_inner().session_id;
+        }
+    }
+    client.logout(LogoutRequest { session_id: session_id.clone() }).await.unwrap();
+
+    // 按用户查询，事件按发生顺序排列，失败带有原因，吊销的会话只以 handle 出现
+    let request = as_admin("dashboard-token", QueryAuditLogRequest { user: "alice".to_string(), ..Default::default() });
+    let entries = admin.query_audit_log(request).await.unwrap().into_inner().entries;
+    let summary = entries.iter().map(|entry| (entry.kind.as_str(), entry.success)).collect::<Vec<_>>();
+    assert_eq!(summary, [("register", true), ("register", false), ("challenge", true), ("verify", true), ("challenge", true), ("verify", false), ("session-revoked", true)]);
+    assert!(entries[1].reason.starts_with("AlreadyExists: "));
+    assert!(entries[5].reason.starts_with("PermissionDenied: ") && entries[5].reason.ends_with("bad solution to the challenge"));
+    assert_eq!((entries[6].reason.as_str(), entries[6].session.clone()), ("logout", zkp_server::admin::session_handle(&session_id)));
+    assert!(entries[..6].iter().all(|entry| !entry.remote_addr.is_empty() && entry.session.is_empty()));
+    assert!(entries.iter().all(|entry| !entry.reason.contains(&session_id)));
+
+    // 分页：所有用户的 8 个事件，每页 3 个
+    let mut ids = Vec::new();
+    let mut page_token = 0;
+    loop {
+        let request = as_admin("dashboard-token", QueryAuditLogRequest { page_size: 3, page_token, ..Default::default() });
+        let page = admin.query_audit_log(request).await.unwrap().into_inner();
+        assert!(page.entries.len() <= 3);
+        ids.extend(page.entries.iter().map(|entry| entry.id));
+        page_token = page.next_page_token;
+        if page_token == 0 {
+            break;
+        }
+    }
+    assert_eq!(ids, (1..=8).collect::<Vec<_>>());
+
+    // 按时间范围查询
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let request = as_admin("dashboard-token", QueryAuditLogRequest { since: now + 60, ..Default::default() });
+    assert!(admin.query_audit_log(request).await.unwrap().into_inner().entries.is_empty());
+    let request = as_admin("dashboard-token", QueryAuditLogRequest { since: now - 60, until: now + 60, ..Default::default() });
+    assert_eq!(admin.query_audit_log(request).await.unwrap().into_inner().entries.len(), 8);
+
+    // 删除 alice 后她的事件改为同一个假名：事件和序号保留，客户端地址清空，包含用户名的原因只保留错误码
+    auth.delete_user("alice").await.unwrap().unwrap();
+    let request = as_admin("dashboard-token", QueryAuditLogRequest { user: "alice".to_string(), ..Default::default() });
+    assert!(admin.query_audit_log(request).await.unwrap().into_inner().entries.is_empty());
+    let all = admin.query_audit_log(as_admin("dashboard-token", QueryAuditLogRequest::default())).await.unwrap().into_inner().entries;
+    assert_eq!(all.len(), 8);
+    let tombstone = all[0].user.clone();
+    assert!(tombstone.starts_with("deleted-"));
+    let pseudonymized = all.iter().filter(|entry| entry.user == tombstone).collect::<Vec<_>>();
+    assert_eq!(pseudonymized.iter().map(|entry| entry.id).collect::<Vec<_>>(), entries.iter().map(|entry| entry.id).collect::<Vec<_>>());
+    assert!(pseudonymized.iter().all(|entry| entry.remote_addr.is_empty() && !entry.reason.contains("alice")));
+    assert_eq!(pseudonymized[1].reason, "AlreadyExists");
+    assert!(all.iter().any(|entry| entry.user == "bob" && !entry.remote_addr.is_empty()));
+
+    // 文件日志重新打开后保留已有事件，新的事件接着编号
+    let path = std::env::temp_dir().join(format!("zkp-audit-test-{}.jsonl", ZKP::generate_random_string(8)));
+    let event = AuditEvent { id: 0, at: now, kind: AuditKind::Register, user: "alice".to_string(), success: true, reason: String::new(), remote_addr: String::new(), correlation_id: "-".to_string(), session: String::new() };
+    FileAuditLog::open(&path).unwrap().append(event.clone()).await.unwrap();
+    let file = FileAuditLog::open(&path).unwrap();
+    file.append(AuditEvent { user: "bob".to_string(), ..event.clone() }).await.unwrap();
+    let events = file.query(&AuditQuery { limit: 10, ..Default::default() }).await.unwrap();
+    assert_eq!(events.iter().map(|event| (event.id, event.user.as_str())).collect::<Vec<_>>(), [(1, "alice"), (2, "bob")]);
+    let events = file.query(&AuditQuery { user: "bob".to_string(), limit: 10, ..Default::default() }).await.unwrap();
+    assert_eq!(events.len(), 1);
+
+    // 文件日志改为假名时重写文件，之后的事件追加到新文件
+    assert_eq!(file.pseudonymize("bob", "deleted-bob").await.unwrap(), 1);
+    file.append(AuditEvent { user: "carol".to_string(), ..event }).await.unwrap();
+    let events = FileAuditLog::open(&path).unwrap().query(&AuditQuery { limit: 10, ..Default::default() }).await.unwrap();
+    assert_eq!(events.iter().map(|event| (event.id, event.user.as_str())).collect::<Vec<_>>(), [(1, "alice"), (2, "deleted-bob"), (3, "carol")]);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_api_versions() {
+    let config = ServerConfig { proof_hashes: vec![HashAlgorithm::Sha256], ..Default::default() };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let auth = Arc::new(AuthImpl::new(config, MemoryStore::default()));
+    tokio::spawn(
+        Server::builder()
+            .add_service(AuthServer::from_arc(auth.clone()))
+            .add_service(V1(AuthServer::from_arc(auth.clone())))
+            .add_service(AuthV2Server::new(AuthV2Impl::new(auth)))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    let mut client = AuthClient::connect(url.clone()).await.unwrap();
+
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+    let request = RegisterRequest {
+        user: "alice".to_string(),
+        y1: proof.y1.to_bytes_be(),
+        y2: proof.y2.to_bytes_be(),
+        proof_c: proof.c.to_bytes_be(),
+        proof_s: proof.s.to_bytes_be(),
+        salt: b"pepper".to_vec(),
+        kdf: Some(KdfParams { algorithm: "pbkdf2-sha256".to_string(), iterations: 1000 }),
+        ..Default::default()
+    };
+    client.register(request).await.unwrap();
+
+    // 同一个注册可以通过 zkp_auth.v1.Auth 访问：不存在的会话无效，说明请求到达了同一个处理函数
+    let channel = tonic::transport::Endpoint::from_shared(url).unwrap().connect().await.unwrap();
+    let mut grpc = tonic::client::Grpc::new(channel.clone());
+    grpc.ready().await.unwrap();
+    let path = http::uri::PathAndQuery::from_static("/zkp_auth.v1.Auth/ValidateSession");
+    let request = ValidateSessionRequest { session_id: "nope".to_string(), ..Default::default() };
+    let response: ValidateSessionResponse = grpc.unary(Request::new(request), path, ProstCodec::default()).await.unwrap().into_inner();
+    assert!(!response.valid);
+
+    // 第二版接口读取同一份存储和参数
+    let mut v2 = AuthV2Client::new(channel);
+    let salt = v2.get_salt(GetSaltRequest { user: "alice".to_string() }).await.unwrap().into_inner();
+    assert_eq!(salt.salt, b"pepper".to_vec());
+    assert_eq!(salt.kdf.map(|kdf| kdf.iterations), Some(1000));
+    assert_eq!(v2.get_salt(GetSaltRequest { user: "bob".to_string() }).await.unwrap_err().code(), Code::NotFound);
+
+    let params = v2.get_parameters(GetParametersRequest { params_hashes: vec![vec![0; 32], zkp.params_hash()] }).await.unwrap().into_inner();
+    assert_eq!(params.params_hash, zkp.params_hash());
+    assert_eq!(params.p, zkp.p.to_bytes_be());
+    assert_eq!(params.proof_hashes, vec!["sha256".to_string()]);
+    assert_eq!(params.api_versions, vec!["v1".to_string(), "v2".to_string()]);
+    let status = v2.get_parameters(GetParametersRequest { params_hashes: vec![vec![0; 32]] }).await.unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn test_challenge_sources() {
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    // 用给定的挑战来源启动服务器，注册 alice 后发送一个承诺，返回挑战响应和 k
+    let challenge = |source: Arc<dyn ChallengeSource>| {
+        let (zkp, x) = (zkp.clone(), x.clone());
+        async move {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let config = ServerConfig { challenge_source: source, ..Default::default() };
+            let service = AuthServer::new(AuthImpl::new(config, MemoryStore::default()));
+            tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+            let mut client = AuthClient::connect(url).await.unwrap();
+            let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+            let request = RegisterRequest {
+                user: "alice".to_string(),
+                y1: proof.y1.to_bytes_be(),
+                y2: proof.y2.to_bytes_be(),
+                proof_c: proof.c.to_bytes_be(),
+                proof_s: proof.s.to_bytes_be(),
+                ..Default::default()
+            };
+            client.register(request).await.unwrap();
+            let k = ZKP::generate_random_number_below(&zkp.q);
+            let (r1, r2) = (ZKP::exponentiate(&zkp.alpha, &k, &zkp.p), ZKP::exponentiate(&zkp.beta, &k, &zkp.p));
+            let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1: r1.to_bytes_be(), r2: r2.to_bytes_be(), ..Default::default() };
+            let result = client.create_authentication_challenge(request).await.map(|response| response.into_inner());
+            (client, result, k, (proof.y1, proof.y2, r1, r2))
+        }
+    };
+
+    // 外部生成器的挑战值原样使用，登录照常完成
+    let (mut client, result, k, _) = challenge(Arc::new(ExternalChallenge::new(|_, input| Ok(BigUint::from(input.user.len() as u32 + 40))))).await;
+    let response = result.unwrap();
+    assert_eq!(BigUint::from_bytes_be(&response.c), BigUint::from(45u32));
+    let s = zkp.solve(&k, &BigUint::from(45u32), &x).to_bytes_be();
+    client.verify_authentication(AuthenticationAnswerRequest { auth_id: response.auth_id, s, ..Default::default() }).await.unwrap();
+
+    // 哈希派生的挑战值可以由认证 ID 复算
+    let (_, result, _, (y1, y2, r1, r2)) = challenge(Arc::new(FiatShamirChallenge { hash: HashAlgorithm::Blake3 })).await;
+    let response = result.unwrap();
+    let expected = zkp.fiat_shamir_challenge_with(HashAlgorithm::Blake3, &y1, &y2, &r1, &r2, &challenge_context("alice", &response.auth_id));
+    assert_eq!(BigUint::from_bytes_be(&response.c), expected);
+
+    // 生成失败和超出范围的挑战值都不会发给客户端
+    let (_, result, _, _) = challenge(Arc::new(ExternalChallenge::new(|_, _| Err("hsm offline".to_string())))).await;
+    assert_eq!(result.unwrap_err().code(), Code::Unavailable);
+    let (_, result, _, _) = challenge(Arc::new(ExternalChallenge::new(|params, _| Ok(params.q.clone())))).await;
+    assert_eq!(result.unwrap_err().code(), Code::Internal);
+}
+
+// 挑战和会话的存储不可用，例如 Redis 断开连接
+#[derive(Debug)]
+struct OfflineSessions;
+
+#[tonic::async_trait]
+impl SessionStore for OfflineSessions {
+    async fn put_challenge(&self, _: &str, _: PendingChallenge) -> StoreResult<()> {
+        Err(StoreError("connection refused".to_string()))
+    }
+    async fn get_challenge(&self, _: &str) -> StoreResult<Option<PendingChallenge>> {
+        Err(StoreError("connection refused".to_string()))
+    }
+    async fn take_challenge(&self, _: &str) -> StoreResult<Option<PendingChallenge>> {
+        Err(StoreError("connection refused".to_string()))
+    }
+    async fn count_challenges(&self, _: &str) -> StoreResult<u32> {
+        Err(StoreError("connection refused".to_string()))
+    }
+    async fn delete_challenges(&self, _: &str) -> StoreResult<()> {
+        Err(StoreError("connection refused".to_string()))
+    }
+    async fn put_session(&self, _: &str, _: SessionInfo) -> StoreResult<()> {
+        Err(StoreError("connection refused".to_string()))
+    }
+    async fn get_session(&self, _: &str) -> StoreResult<Option<SessionInfo>> {
+        Err(StoreError("connection refused".to_string()))
+    }
+    async fn delete_session(&self, _: &str) -> StoreResult<Option<SessionInfo>> {
+        Err(StoreError("connection refused".to_string()))
+    }
+    async fn list_sessions(&self, _: &str) -> StoreResult<Vec<(String, SessionInfo)>> {
+        Err(StoreError("connection refused".to_string()))
+    }
+    async fn delete_sessions(&self, _: &str) -> StoreResult<Vec<String>> {
+        Err(StoreError("connection refused".to_string()))
+    }
+    async fn count_sessions(&self, _: u64) -> StoreResult<u64> {
+        Err(StoreError("connection refused".to_string()))
+    }
+    async fn purge_expired(&self, _: u64) -> StoreResult<Purged> {
+        Err(StoreError("connection refused".to_string()))
+    }
+}
+
+#[tokio::test]
+async fn test_pluggable_stores() {
+    // 用户记录和会话使用不同的存储
+    let users = Arc::new(MemoryStore::default());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let service = AuthServer::new(AuthImpl::with_stores(ServerConfig::default(), users.clone(), Arc::new(OfflineSessions)));
+    tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+
+    // 注册只写用户记录，调用者可以直接读到
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+    let request = RegisterRequest {
+        user: "alice".to_string(),
+        y1: proof.y1.to_bytes_be(),
+        y2: proof.y2.to_bytes_be(),
+        proof_c: proof.c.to_bytes_be(),
+        proof_s: proof.s.to_bytes_be(),
+        ..Default::default()
+    };
+    client.register(request).await.unwrap();
+    let user_info = users.get_user("alice").await.unwrap().unwrap();
+    assert_eq!(user_info.y1, proof.y1);
+
+    // 会话存储出错时返回 Unavailable，客户端可以重试
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+    let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+    let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() };
+    let status = client.create_authentication_challenge(request).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+    assert!(status.message().contains("connection refused"));
+}
+
+#[tokio::test]
+async fn test_export_and_import_users() {
+    use zkp_server::export::{export_users, import_users, ExportFormat, ExportedUser, ImportSummary};
+
+    async fn exported(store: &MemoryStore) -> Vec<ExportedUser> {
+        store.list_users("", 10).await.unwrap().iter().map(|(user, info)| ExportedUser::new(user, info, true)).collect()
+    }
+
+    // 一个带有各种字段的用户和一个只有公开值的用户；元数据中有逗号、引号和换行
+    let zkp = ZKP::get_constants();
+    let params = zkp_core::GroupParams::rfc5114_1024();
+    let source = MemoryStore::default();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let (y1, y2) = (ZKP::exponentiate(&zkp.alpha, &x, &zkp.p), ZKP::exponentiate(&zkp.beta, &x, &zkp.p));
+    let alice = UserInfo {
+        y1: y1.clone(),
+        y2: y2.clone(),
+        salt: vec![7; 16],
+        kdf: Some(KdfParams { algorithm: "pbkdf2-sha256".to_string(), iterations: 1000 }),
+        scopes: vec!["read".to_string(), "write".to_string()],
+        metadata: [("device, model".to_string(), "say \"hi\"\nbye".to_string())].into_iter().collect(),
+        display_name: "Alice".to_string(),
+        created_at: 1_700_000_000,
+        recovery_codes: vec![vec![1; 32]],
+        guardians: vec!["bob".to_string(), "carol".to_string()],
+        guardian_threshold: 2,
+        totp_secret: vec![9; 20],
+        group: params.params_hash(),
+        ..Default::default()
+    };
+    source.put_user("alice", alice).await.unwrap();
+    source.put_user("bob", UserInfo { y1: y1.clone(), y2, ..Default::default() }).await.unwrap();
+
+    // 两种格式都能完整往返
+    for format in [ExportFormat::Json, ExportFormat::Csv] {
+        let mut out = Vec::new();
+        assert_eq!(export_users(&source, format, true, &mut out).await.unwrap(), 2);
+        let text = String::from_utf8(out).unwrap();
+        let target = MemoryStore::default();
+        let summary = import_users(&target, &[params], format, &text, false).await.unwrap();
+        assert_eq!(summary, ImportSummary { created: 2, ..Default::default() });
+        assert_eq!(exported(&target).await, exported(&source).await, "{:?}", format);
+
+        // 已存在的用户默认跳过，replace 时替换
+        assert_eq!(import_users(&target, &[params], format, &text, false).await.unwrap().skipped, 2);
+        assert_eq!(import_users(&target, &[params], format, &text, true).await.unwrap().replaced, 2);
+    }
+
+    // 默认不导出 TOTP 密钥，启用了 TOTP 的用户不能从这样的导出文件导入，不会在导入后失去第二因素
+    for format in [ExportFormat::Json, ExportFormat::Csv] {
+        let mut out = Vec::new();
+        export_users(&source, format, false, &mut out).await.unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(!text.contains(&hex::encode([9u8; 20])));
+        let target = MemoryStore::default();
+        assert!(import_users(&target, &[params], format, &text, false).await.unwrap_err().contains("totp_secret"));
+        assert!(exported(&target).await.is_empty());
+    }
+
+    // 任何一条记录有错误时不导入任何用户：公开值不在子群中、群不被接受、用户名重复、CSV 的列不对
+    let mut out = Vec::new();
+    export_users(&source, ExportFormat::Json, true, &mut out).await.unwrap();
+    let lines: Vec<&str> = std::str::from_utf8(&out).unwrap().lines().collect();
+    let target = MemoryStore::default();
+    let bad_y1 = format!("{}\n{}", lines[0], lines[1].replace(&hex::encode(y1.to_bytes_be()), "01"));
+    assert!(import_users(&target, &[params], ExportFormat::Json, &bad_y1, false).await.unwrap_err().contains("y1"));
+    let other_group = lines[0].replace(&hex::encode(params.params_hash()), &hex::encode([0u8; 32]));
+    assert!(import_users(&target, &[params], ExportFormat::Json, &other_group, false).await.unwrap_err().contains("not accepted"));
+    let duplicate = format!("{}\n{}", lines[0], lines[0]);
+    assert!(import_users(&target, &[params], ExportFormat::Json, &duplicate, false).await.unwrap_err().contains("more than once"));
+    assert!(import_users(&target, &[params], ExportFormat::Csv, "user,y1\nalice,01\n", false).await.unwrap_err().contains("columns"));
+    assert!(exported(&target).await.is_empty());
+}
+
+// 单线程的运行时上，验证和子群检查在阻塞线程池中进行，等待 CPU 的登录不阻塞其他 RPC
+#[tokio::test(flavor = "current_thread")]
+async fn test_concurrent_logins_with_one_cpu_worker() {
+    let config = ServerConfig { cpu_workers: 1, ..Default::default() };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(Server::builder().add_service(AuthServer::new(AuthImpl::new(config, MemoryStore::default()))).serve_with_incoming(TcpListenerStream::new(listener)));
+    let client = AuthClient::connect(url).await.unwrap();
+
+    let zkp = ZKP::get_constants();
+    let logins = (0..8).map(|i| {
+        let mut client = client.clone();
+        let zkp = zkp.clone();
+        async move {
+            let user = format!("user-{}", i);
+            let x = ZKP::generate_random_number_below(&zkp.q);
+            let proof = zkp.prove_non_interactive(&x, &registration_context(&user));
+            let request = RegisterRequest {
+                user: user.clone(),
+                y1: proof.y1.to_bytes_be(),
+                y2: proof.y2.to_bytes_be(),
+                proof_c: proof.c.to_bytes_be(),
+                proof_s: proof.s.to_bytes_be(),
+                ..Default::default()
+            };
+            client.register(request).await.unwrap();
+            let k = ZKP::generate_random_number_below(&zkp.q);
+            let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+            let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+            let challenge = client.create_authentication_challenge(AuthenticationChallengeRequest { user, r1, r2, ..Default::default() }).await.unwrap().into_inner();
+            let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be();
+            client.verify_authentication(AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() }).await.unwrap().into_inner().session_id
+        }
+    });
+    let sessions = futures_join_all(logins).await;
+
+    // 所有登录都建立了各自的会话
+    let mut client = client;
+    for session_id in sessions {
+        let response = client.validate_session(ValidateSessionRequest { session_id, ..Default::default() }).await.unwrap().into_inner();
+        assert!(response.valid);
+    }
+}
+
+// 并发等待一组 future，按输入的顺序返回结果
+async fn futures_join_all<F: std::future::Future + Send + 'static>(futures: impl Iterator<Item = F>) -> Vec<F::Output>
+where
+    F::Output: Send + 'static,
+{
+    let tasks: Vec<_> = futures.map(tokio::spawn).collect();
+    let mut outputs = Vec::new();
+    for task in tasks {
+        outputs.push(task.await.unwrap());
+    }
+    outputs
+}
+
+#[tokio::test]
+async fn test_memory_store_survives_panicking_update() {
+    let store = Arc::new(MemoryStore::default());
+    let user = |y: u32| UserInfo { y1: BigUint::from(y), ..Default::default() };
+    store.put_user("alice", user(1)).await.unwrap();
+
+    // 修改用户记录的回调在分片的写锁内 panic，锁中毒后同一分片的其他请求仍然可以读写
+    let panicking = store.clone();
+    let result = tokio::spawn(async move { panicking.update_user("alice", Box::new(|_| panic!("update failed"))).await }).await;
+    assert!(result.unwrap_err().is_panic());
+    assert_eq!(store.get_user("alice").await.unwrap().unwrap().y1, BigUint::from(1u32));
+    assert!(store.update_user("alice", Box::new(|info| { info.y1 = BigUint::from(2u32); true })).await.unwrap());
+    assert_eq!(store.get_user("alice").await.unwrap().unwrap().y1, BigUint::from(2u32));
+}
+
+#[tokio::test]
+async fn test_purge_expired() {
+    // 会话一建立就过期，挑战保持默认有效期
+    let store = Arc::new(MemoryStore::default());
+    let config = ServerConfig { session_ttl_secs: 0, cleanup_interval_secs: 1, ..Default::default() };
+    let auth = Arc::new(AuthImpl::with_stores(config, store.clone(), store.clone()));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(Server::builder().add_service(AuthServer::from_arc(auth.clone())).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+    let request = RegisterRequest {
+        user: "alice".to_string(),
+        y1: proof.y1.to_bytes_be(),
+        y2: proof.y2.to_bytes_be(),
+        proof_c: proof.c.to_bytes_be(),
+        proof_s: proof.s.to_bytes_be(),
+        ..Default::default()
+    };
+    client.register(request).await.unwrap();
+    let login = |pending: bool| {
+        let mut client = client.clone();
+        let (zkp, x) = (zkp.clone(), x.clone());
+        async move {
+            let k = ZKP::generate_random_number_below(&zkp.q);
+            let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+            let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+            let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() };
+            let challenge = client.create_authentication_challenge(request).await.unwrap().into_inner();
+            if !pending {
+                let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be();
+                client.verify_authentication(AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() }).await.unwrap();
+            }
+        }
+    };
+
+    // 一个过期的会话、一个未应答的挑战和一个早已过期的挑战
+    login(false).await;
+    login(true).await;
+    let stale = PendingChallenge { user: "alice".to_string(), r1: BigUint::from(1u32), r2: BigUint::from(1u32), c: BigUint::from(1u32), expires_at: 1, metadata: Default::default(), device_id: String::new() };
+    store.put_challenge("stale", stale).await.unwrap();
+
+    // 只删除过期的条目
+    assert_eq!(auth.purge_expired().await.unwrap(), Purged { challenges: 1, sessions: 1 });
+    assert!(store.get_challenge("stale").await.unwrap().is_none());
+    assert!(store.count_challenges("alice").await.unwrap() >= 1);
+    assert!(store.list_sessions("alice").await.unwrap().is_empty());
+
+    // 后台任务定期清理
+    login(false).await;
+    let cleanup = spawn_cleanup(auth.clone()).unwrap();
+    for _ in 0..20 {
+        if store.list_sessions("alice").await.unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(store.list_sessions("alice").await.unwrap().is_empty());
+    cleanup.abort();
+}
+
+#[tokio::test]
+async fn test_jwt_issued_after_authentication() {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let eddsa = JwtKey::EdDsa(ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]));
+    for key in [None, Some(JwtKey::Hs256(b"shared secret".to_vec())), Some(eddsa)] {
+        let jwt = key.clone().map(|key| JwtIssuer { key_id: Some("k1".to_string()), ..JwtIssuer::new(key, "zkp-test") });
+        let config = ServerConfig { session_ttl_secs: 60, jwt, ..Default::default() };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(Server::builder().add_service(AuthServer::new(AuthImpl::new(config, MemoryStore::default()))).serve_with_incoming(TcpListenerStream::new(listener)));
+        let mut client = AuthClient::connect(url).await.unwrap();
+
+        let zkp = ZKP::get_constants();
+        let x = ZKP::generate_random_number_below(&zkp.q);
+        let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+        let request = RegisterRequest {
+            user: "alice".to_string(),
+            y1: proof.y1.to_bytes_be(),
+            y2: proof.y2.to_bytes_be(),
+            proof_c: proof.c.to_bytes_be(),
+            proof_s: proof.s.to_bytes_be(),
+            ..Default::default()
+        };
+        client.register(request).await.unwrap();
+        let k = ZKP::generate_random_number_below(&zkp.q);
+        let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+        let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+        let challenge = client.create_authentication_challenge(AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() }).await.unwrap().into_inner();
+        let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be();
+        let session = client.verify_authentication(AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() }).await.unwrap().into_inner();
+
+        // 未配置签名密钥时不签发
+        let Some(key) = key else {
+            assert!(session.token.is_empty());
+            continue;
+        };
+        let claims = verify_jwt(&session.token, &key.verifying_key(), now).unwrap();
+        assert_eq!(claims.issuer, "zkp-test");
+        assert_eq!(claims.subject, "alice");
+        assert_eq!(claims.session_id, session.session_id);
+        assert_eq!(claims.expires_at, session.expires_at);
+        assert_eq!(claims.auth_method, "chaum-pedersen");
+        assert_eq!(claims.scopes, session.scopes);
+        assert!(claims.issued_at <= claims.expires_at);
+
+        // 过期、篡改、错误的密钥和错误的算法都被拒绝
+        assert_eq!(verify_jwt(&session.token, &key.verifying_key(), session.expires_at).unwrap_err(), "token expired");
+        let mut parts: Vec<&str> = session.token.split('.').collect();
+        let forged = URL_SAFE_NO_PAD.encode(r#"{"iss":"zkp-test","sub":"mallory","sid":"x","iat":0,"exp":99999999999,"auth_method":"chaum-pedersen","scope":"admin"}"#);
+        parts[1] = &forged;
+        assert_eq!(verify_jwt(&parts.join("."), &key.verifying_key(), now).unwrap_err(), "invalid signature");
+        let other = match key {
+            JwtKey::Hs256(_) => JwtVerifyingKey::Hs256(b"other secret".to_vec()),
+            JwtKey::EdDsa(_) => JwtVerifyingKey::EdDsa(ed25519_dalek::SigningKey::from_bytes(&[8u8; 32]).verifying_key()),
+        };
+        assert_eq!(verify_jwt(&session.token, &other, now).unwrap_err(), "invalid signature");
+        let confused = match key {
+            JwtKey::Hs256(_) => JwtVerifyingKey::EdDsa(ed25519_dalek::SigningKey::from_bytes(&[7u8; 32]).verifying_key()),
+            JwtKey::EdDsa(public) => JwtVerifyingKey::Hs256(public.verifying_key().as_bytes().to_vec()),
+        };
+        assert!(verify_jwt(&session.token, &confused, now).unwrap_err().starts_with("unexpected algorithm"));
+    }
+}
+
+#[tokio::test]
+async fn test_reregistration_with_old_secret() {
+    let config = ServerConfig { allow_reregistration: true, ..Default::default() };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(Server::builder().add_service(AuthServer::new(AuthImpl::new(config, MemoryStore::default()))).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+    let zkp = ZKP::get_constants();
+
+    let registration = |user: &str, x: &BigUint| {
+        let proof = zkp.prove_non_interactive(x, &registration_context(user));
+        RegisterRequest { user: user.to_string(), y1: proof.y1.to_bytes_be(), y2: proof.y2.to_bytes_be(), proof_c: proof.c.to_bytes_be(), proof_s: proof.s.to_bytes_be(), ..Default::default() }
+    };
+    // 请求挑战并用 x 计算应答，返回认证 ID 和 s
+    let answer = |client: &mut AuthClient<tonic::transport::Channel>, user: &str, x: &BigUint| {
+        let (mut client, user, x, zkp) = (client.clone(), user.to_string(), x.clone(), zkp.clone());
+        async move {
+            let k = ZKP::generate_random_number_below(&zkp.q);
+            let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+            let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+            let challenge = client.create_authentication_challenge(AuthenticationChallengeRequest { user, r1, r2, ..Default::default() }).await.unwrap().into_inner();
+            (challenge.auth_id, zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be())
+        }
+    };
+
+    let old = ZKP::generate_random_number_below(&zkp.q);
+    let new = ZKP::generate_random_number_below(&zkp.q);
+    client.register(registration("alice", &old)).await.unwrap();
+    client.register(registration("bob", &new)).await.unwrap();
+    let (auth_id, s) = answer(&mut client, "alice", &old).await;
+    let session_id = client.verify_authentication(AuthenticationAnswerRequest { auth_id, s, ..Default::default() }).await.unwrap().into_inner().session_id;
+
+    // 没有旧密码的证明、用错误的密码或用其他用户的挑战都不能替换用户记录
+    assert_eq!(client.register(registration("alice", &new)).await.unwrap_err().code(), Code::AlreadyExists);
+    let (auth_id, s) = answer(&mut client, "alice", &new).await;
+    let status = client.register(RegisterRequest { auth_id, s, ..registration("alice", &new) }).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    let (auth_id, s) = answer(&mut client, "bob", &new).await;
+    let status = client.register(RegisterRequest { auth_id, s, ..registration("alice", &new) }).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    // 用旧密码回答挑战后重新注册，旧密码建立的会话被吊销
+    let (auth_id, s) = answer(&mut client, "alice", &old).await;
+    client.register(RegisterRequest { auth_id, s, ..registration("alice", &new) }).await.unwrap();
+    assert!(!client.validate_session(ValidateSessionRequest { session_id, ..Default::default() }).await.unwrap().into_inner().valid);
+    let (auth_id, s) = answer(&mut client, "alice", &new).await;
+    client.verify_authentication(AuthenticationAnswerRequest { auth_id, s, ..Default::default() }).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_totp_second_factor() {
+    // RFC 6238 附录 B 的 SHA-1 测试向量（取低 6 位）
+    let rfc_secret = b"12345678901234567890";
+    for (time, code) in [(59, "287082"), (1111111109, "081804"), (1234567890, "005924"), (2000000000, "279037")] {
+        assert_eq!(totp_code(rfc_secret, time), code);
+    }
+
+    let config = ServerConfig { allow_reregistration: true, ..Default::default() };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(Server::builder().add_service(AuthServer::new(AuthImpl::new(config, MemoryStore::default()))).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+    let zkp = ZKP::get_constants();
+    let now = || SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+    let registration = |user: &str, x: &BigUint| {
+        let proof = zkp.prove_non_interactive(x, &registration_context(user));
+        RegisterRequest { user: user.to_string(), y1: proof.y1.to_bytes_be(), y2: proof.y2.to_bytes_be(), proof_c: proof.c.to_bytes_be(), proof_s: proof.s.to_bytes_be(), ..Default::default() }
+    };
+    let answer = |client: &mut AuthClient<tonic::transport::Channel>, user: &str, x: &BigUint| {
+        let (mut client, user, x, zkp) = (client.clone(), user.to_string(), x.clone(), zkp.clone());
+        async move {
+            let k = ZKP::generate_random_number_below(&zkp.q);
+            let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+            let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+            let challenge = client.create_authentication_challenge(AuthenticationChallengeRequest { user, r1, r2, ..Default::default() }).await.unwrap().into_inner();
+            (challenge.auth_id, zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be())
+        }
+    };
+
+    // 密钥太短时拒绝注册
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let status = client.register(RegisterRequest { totp_secret: vec![7; 8], ..registration("alice", &x) }).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    let secret = vec![0x5a; 20];
+    client.register(RegisterRequest { totp_secret: secret.clone(), ..registration("alice", &x) }).await.unwrap();
+
+    // 只有正确的解答而没有验证码，或者验证码错误时都不能登录；错误的验证码与错误的解答返回同一个错误
+    let (auth_id, s) = answer(&mut client, "alice", &x).await;
+    let status = client.verify_authentication(AuthenticationAnswerRequest { auth_id, s, ..Default::default() }).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+    let wrong = format!("{:06}", (totp_code(&secret, now()).parse::<u32>().unwrap() + 1) % 1_000_000);
+    let (auth_id, s) = answer(&mut client, "alice", &x).await;
+    let status = client.verify_authentication(AuthenticationAnswerRequest { auth_id, s, totp_code: wrong, ..Default::default() }).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    assert!(status.message().contains("or TOTP code"));
+
+    // 正确的验证码只能使用一次
+    let code = totp_code(&secret, now());
+    let (auth_id, s) = answer(&mut client, "alice", &x).await;
+    client.verify_authentication(AuthenticationAnswerRequest { auth_id, s, totp_code: code.clone(), ..Default::default() }).await.unwrap();
+    let (auth_id, s) = answer(&mut client, "alice", &x).await;
+    let status = client.verify_authentication(AuthenticationAnswerRequest { auth_id, s, totp_code: code, ..Default::default() }).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    // 修改密码和账户资料、批准多方恢复、导出和删除用户数据同样需要验证码，只知道密码不能替换凭据、冒用账户或取走、删除账户
+    let bob_x = ZKP::generate_random_number_below(&zkp.q);
+    client.register(RegisterRequest { totp_secret: secret.clone(), ..registration("bob", &bob_x) }).await.unwrap();
+    let new_x = ZKP::generate_random_number_below(&zkp.q);
+    let new_proof = zkp.prove_non_interactive(&new_x, &registration_context("bob"));
+    let change = |auth_id: String, s: Vec<u8>, code: String| ChangePasswordRequest {
+        auth_id,
+        s,
+        y1: new_proof.y1.to_bytes_be(),
+        y2: new_proof.y2.to_bytes_be(),
+        totp_code: code,
+        user: "bob".to_string(),
+        proof_c: new_proof.c.to_bytes_be(),
+        proof_s: new_proof.s.to_bytes_be(),
+        ..Default::default()
+    };
+    let (auth_id, s) = answer(&mut client, "bob", &bob_x).await;
+    assert_eq!(client.change_password(change(auth_id, s, String::new())).await.unwrap_err().code(), Code::Unauthenticated);
+    let (auth_id, s) = answer(&mut client, "bob", &bob_x).await;
+    assert_eq!(client.export_user_data(ExportUserDataRequest { auth_id, s, totp_code: String::new() }).await.unwrap_err().code(), Code::Unauthenticated);
+    let (auth_id, s) = answer(&mut client, "bob", &bob_x).await;
+    assert_eq!(client.delete_user_data(DeleteUserDataRequest { auth_id, s, totp_code: String::new() }).await.unwrap_err().code(), Code::Unauthenticated);
+    let (auth_id, s) = answer(&mut client, "bob", &bob_x).await;
+    let request = UpdateProfileRequest { auth_id, s, display_name: Some("Mallory".to_string()), contact: None, totp_code: String::new() };
+    assert_eq!(client.update_profile(request).await.unwrap_err().code(), Code::Unauthenticated);
+    let carol_x = ZKP::generate_random_number_below(&zkp.q);
+    client.register(RegisterRequest { guardians: vec!["bob".to_string()], guardian_threshold: 1, ..registration("carol", &carol_x) }).await.unwrap();
+    let recovery_id = client.start_guardian_recovery(StartGuardianRecoveryRequest { user: "carol".to_string() }).await.unwrap().into_inner().recovery_id;
+    let (auth_id, s) = answer(&mut client, "bob", &bob_x).await;
+    let request = ApproveGuardianRecoveryRequest { recovery_id, auth_id, s, totp_code: String::new() };
+    assert_eq!(client.approve_guardian_recovery(request).await.unwrap_err().code(), Code::Unauthenticated);
+    let (auth_id, s) = answer(&mut client, "bob", &bob_x).await;
+    client.change_password(change(auth_id, s, totp_code(&secret, now() - 30))).await.unwrap();
+    let (auth_id, s) = answer(&mut client, "bob", &new_x).await;
+    client.export_user_data(ExportUserDataRequest { auth_id, s, totp_code: totp_code(&secret, now()) }).await.unwrap();
+    let (auth_id, s) = answer(&mut client, "bob", &new_x).await;
+    client.delete_user_data(DeleteUserDataRequest { auth_id, s, totp_code: totp_code(&secret, now() + 30) }).await.unwrap();
+
+    // 重新注册不能只凭密码关闭第二因素；下一个时间步的验证码在时钟偏差范围内有效
+    let (auth_id, s) = answer(&mut client, "alice", &x).await;
+    let status = client.register(RegisterRequest { auth_id, s, ..registration("alice", &x) }).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+    let (auth_id, s) = answer(&mut client, "alice", &x).await;
+    client.register(RegisterRequest { auth_id, s, totp_code: totp_code(&secret, now() + 30), ..registration("alice", &x) }).await.unwrap();
+    let (auth_id, s) = answer(&mut client, "alice", &x).await;
+    client.verify_authentication(AuthenticationAnswerRequest { auth_id, s, ..Default::default() }).await.unwrap();
+}
+
+// 在 zkp 中计算承诺和解答登录 user，返回挑战响应中用户的群
+async fn login_in_group(client: &mut AuthClient<tonic::transport::Channel>, zkp: &ZKP, user: &str, x: &BigUint) -> Result<Vec<u8>, Status> {
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let (r1, r2) = (ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be(), ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be());
+    let request = AuthenticationChallengeRequest { user: user.to_string(), r1, r2, params_hash: zkp.params_hash(), ..Default::default() };
+    let challenge = client.create_authentication_challenge(request).await?.into_inner();
+    let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), x).to_bytes_be();
+    client.verify_authentication(AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, params_hash: zkp.params_hash(), ..Default::default() }).await?;
+    Ok(challenge.params_hash)
+}
+
+#[tokio::test]
+async fn test_per_user_groups() {
+    // 迁移期间默认群仍是 1024 位群，新用户可以选择注册到 2048 位群
+    let large: &'static ZKP = Box::leak(Box::new(ZKP::load_params(include_str!("../../zkp-core/benches/params/2048_256.json")).unwrap()));
+    let legacy = ZKP::get_constants();
+    let auth = Arc::new(AuthImpl::new(ServerConfig { extra_params: vec![large], ..Default::default() }, MemoryStore::default()));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(
+        Server::builder()
+            .add_service(AuthServer::from_arc(auth.clone()))
+            .add_service(AuthV2Server::new(AuthV2Impl::new(auth.clone())))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    let channel = tonic::transport::Endpoint::from_shared(url).unwrap().connect().await.unwrap();
+    let mut client = AuthClient::new(channel.clone());
+
+    let registration = |zkp: &ZKP, user: &str, x: &BigUint, params_hash: Vec<u8>| {
+        let proof = zkp.prove_non_interactive(x, &registration_context(user));
+        RegisterRequest { user: user.to_string(), y1: proof.y1.to_bytes_be(), y2: proof.y2.to_bytes_be(), proof_c: proof.c.to_bytes_be(), proof_s: proof.s.to_bytes_be(), params_hash, ..Default::default() }
+    };
+
+    // 不指定群的旧客户端注册到默认群，新客户端注册到自己选择的群；服务器不接受的群被拒绝
+    let old_x = ZKP::generate_random_number_below(&legacy.q);
+    client.register(registration(&legacy, "old", &old_x, Vec::new())).await.unwrap();
+    let new_x = ZKP::generate_random_number_below(&large.q);
+    client.register(registration(large, "new", &new_x, large.params_hash())).await.unwrap();
+    let status = client.register(registration(large, "other", &new_x, vec![0; 32])).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    // 每个用户在注册时的群中登录，挑战响应返回用户的群
+    assert_eq!(login_in_group(&mut client, &legacy, "old", &old_x).await.unwrap(), legacy.params_hash());
+    assert_eq!(login_in_group(&mut client, large, "new", &new_x).await.unwrap(), large.params_hash());
+    assert_eq!(auth.export_user("new").await.unwrap().unwrap().params_hash, large.params_hash());
+
+    // 在其他群中计算的承诺被拒绝，错误信息给出用户的群
+    let status = login_in_group(&mut client, &legacy, "new", &new_x).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert!(status.message().contains(&hex::encode(large.params_hash())));
+
+    // v2 按客户端的优先顺序选择服务器接受的群，GetSalt 返回用户的群
+    let mut v2 = AuthV2Client::new(channel);
+    let params = v2.get_parameters(GetParametersRequest { params_hashes: vec![vec![0; 32], large.params_hash(), legacy.params_hash()] }).await.unwrap().into_inner();
+    assert_eq!(params.params_hash, large.params_hash());
+    assert_eq!(params.p, large.p.to_bytes_be());
+    assert_eq!(v2.get_salt(GetSaltRequest { user: "old".to_string() }).await.unwrap().into_inner().params_hash, legacy.params_hash());
+
+    // GetAuthParameters 返回默认的群或用户注册时的群，以及服务器接受的所有群
+    let params = client.get_auth_parameters(GetAuthParametersRequest::default()).await.unwrap().into_inner();
+    assert_eq!((params.p, params.q, params.alpha, params.beta), (legacy.p.to_bytes_be(), legacy.q.to_bytes_be(), legacy.alpha.to_bytes_be(), legacy.beta.to_bytes_be()));
+    assert_eq!(params.params_hash, legacy.params_hash());
+    assert_eq!(params.group, "rfc5114-1024");
+    assert_eq!(params.accepted_params_hashes, vec![legacy.params_hash(), large.params_hash()]);
+    let params = client.get_auth_parameters(GetAuthParametersRequest { user: "new".to_string() }).await.unwrap().into_inner();
+    assert_eq!((params.p, params.params_hash, params.group), (large.p.to_bytes_be(), large.params_hash(), String::new()));
+    let status = client.get_auth_parameters(GetAuthParametersRequest { user: "nobody".to_string() }).await.unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+
+    // 服务器不再接受用户注册时的群时，该用户无法登录
+    let store = MemoryStore::default();
+    store.put_user("new", UserInfo { group: large.params_hash(), ..Default::default() }).await.unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(Server::builder().add_service(AuthServer::new(AuthImpl::new(ServerConfig::default(), store))).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+    let status = login_in_group(&mut client, large, "new", &new_x).await.unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn test_metrics_endpoint() {
+    let auth = Arc::new(AuthImpl::new(ServerConfig::default(), MemoryStore::default()));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(Server::builder().add_service(AuthServer::from_arc(auth.clone())).serve_with_incoming(TcpListenerStream::new(listener)));
+    let metrics_addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap(); // 取一个空闲端口
+    tokio::spawn(serve_metrics(auth.clone(), metrics_addr));
+    let mut client = AuthClient::connect(url).await.unwrap();
+
+    // 注册，一次错误的解答，一次正确的解答
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+    let request = RegisterRequest {
+        user: "alice".to_string(),
+        y1: proof.y1.to_bytes_be(),
+        y2: proof.y2.to_bytes_be(),
+        proof_c: proof.c.to_bytes_be(),
+        proof_s: proof.s.to_bytes_be(),
+        ..Default::default()
+    };
+    client.register(request).await.unwrap();
+    for secret in [BigUint::from(7u32), x] {
+        let k = ZKP::generate_random_number_below(&zkp.q);
+        let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+        let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+        let challenge = client.create_authentication_challenge(AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() }).await.unwrap().into_inner();
+        let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &secret).to_bytes_be();
+        let _ = client.verify_authentication(AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() }).await;
+    }
+
+    let response = hyper::Client::new().get(format!("http://{}/metrics", metrics_addr).parse().unwrap()).await.unwrap();
+    assert_eq!(response.status(), http::StatusCode::OK);
+    let body = String::from_utf8(hyper::body::to_bytes(response.into_body()).await.unwrap().to_vec()).unwrap();
+    for line in [
+        "zkp_registrations_total 1",
+        "zkp_challenges_issued_total 2",
+        "zkp_verifications_total{result=\"passed\"} 1",
+        "zkp_verifications_total{result=\"failed\"} 1",
+        "zkp_verification_duration_seconds_count 2",
+        "zkp_verification_duration_seconds_bucket{le=\"+Inf\"} 2",
+        "zkp_active_sessions 1",
+    ] {
+        assert!(body.lines().any(|l| l == line), "missing {:?} in\n{}", line, body);
+    }
+    assert_eq!(body, auth.render_metrics().await);
+
+    // 其他路径返回 404
+    let response = hyper::Client::new().get(format!("http://{}/", metrics_addr).parse().unwrap()).await.unwrap();
+    assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+}
+
+// 向网关 POST 一个 JSON 请求体，返回状态码和 JSON 响应
+async fn post_json(addr: std::net::SocketAddr, path: &str, body: serde_json::Value) -> (http::StatusCode, serde_json::Value) {
+    let request = http::Request::post(format!("http://{}{}", addr, path)).header("content-type", "application/json").body(Body::from(body.to_string())).unwrap();
+    let response = hyper::Client::new().request(request).await.unwrap();
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+}
+
+#[tokio::test]
+async fn test_rest_gateway() {
+    let config = ServerConfig { rate_limits: RateLimits { challenge: Some(Quota::per_minute(2)), ..Default::default() }, ..Default::default() };
+    let auth = Arc::new(AuthImpl::new(config, MemoryStore::default()));
+    let gateway_addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap(); // 取一个空闲端口
+    tokio::spawn(serve_gateway(auth.clone(), gateway_addr));
+    tokio::time::sleep(Duration::from_millis(100)).await; // 等待网关开始监听
+
+    // 注册：字节字段为十六进制字符串
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+    let registration = serde_json::json!({
+        "user": "alice",
+        "y1": hex::encode(proof.y1.to_bytes_be()),
+        "y2": hex::encode(proof.y2.to_bytes_be()),
+        "proof_c": hex::encode(proof.c.to_bytes_be()),
+        "proof_s": hex::encode(proof.s.to_bytes_be()),
+    });
+    let (status, body) = post_json(gateway_addr, "/v1/register", registration.clone()).await;
+    assert_eq!(status, http::StatusCode::OK, "{}", body);
+    let (status, body) = post_json(gateway_addr, "/v1/register", registration.clone()).await;
+    assert_eq!(status, http::StatusCode::CONFLICT);
+    assert_eq!((&body["code"], &body["error_code"]), (&serde_json::json!("AlreadyExists"), &serde_json::json!("USER_ALREADY_EXISTS")));
+    let mut invalid = registration.clone();
+    invalid["user"] = "bob".into();
+    invalid["y1"] = "01".into();
+    let (status, body) = post_json(gateway_addr, "/v1/register", invalid).await;
+    assert_eq!(status, http::StatusCode::BAD_REQUEST);
+    assert_eq!((&body["field"], &body["reason"]), (&serde_json::json!("y1"), &serde_json::json!("degenerate")));
+
+    // 挑战和应答，得到的会话与 gRPC 接口建立的会话相同
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let r1 = hex::encode(ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be());
+    let r2 = hex::encode(ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be());
+    let (status, challenge) = post_json(gateway_addr, "/v1/challenge", serde_json::json!({ "user": "alice", "r1": r1, "r2": r2 })).await;
+    assert_eq!(status, http::StatusCode::OK, "{}", challenge);
+    let c = BigUint::from_bytes_be(&hex::decode(challenge["c"].as_str().unwrap()).unwrap());
+    let s = hex::encode(zkp.solve(&k, &c, &x).to_bytes_be());
+    let (status, verified) = post_json(gateway_addr, "/v1/verify", serde_json::json!({ "auth_id": challenge["auth_id"], "s": s })).await;
+    assert_eq!(status, http::StatusCode::OK, "{}", verified);
+    let session_id = verified["session_id"].as_str().unwrap().to_string();
+    let (_, session) = post_json(gateway_addr, "/v1/session", serde_json::json!({ "session_id": session_id })).await;
+    assert_eq!(session["valid"], true);
+    assert_eq!(session["user"], "alice");
+
+    // 挑战已被消耗，再次应答返回 404
+    let (status, body) = post_json(gateway_addr, "/v1/verify", serde_json::json!({ "auth_id": challenge["auth_id"], "s": s })).await;
+    assert_eq!(status, http::StatusCode::NOT_FOUND);
+    assert_eq!((&body["code"], &body["error_code"]), (&serde_json::json!("NotFound"), &serde_json::json!("CHALLENGE_NOT_FOUND")));
+
+    // 注销后会话无效
+    let (status, _) = post_json(gateway_addr, "/v1/logout", serde_json::json!({ "session_id": session_id })).await;
+    assert_eq!(status, http::StatusCode::OK);
+    let (_, session) = post_json(gateway_addr, "/v1/session", serde_json::json!({ "session_id": session_id })).await;
+    assert_eq!(session["valid"], false);
+
+    // 不是十六进制的字节字段和未知的字段在到达处理函数之前被拒绝
+    let (status, _) = post_json(gateway_addr, "/v1/challenge", serde_json::json!({ "user": "alice", "r1": "not hex", "r2": r2 })).await;
+    assert!(status.is_client_error());
+    let (status, _) = post_json(gateway_addr, "/v1/session", serde_json::json!({ "session": session_id })).await;
+    assert!(status.is_client_error());
+
+    // 超过配额返回 429 和 Retry-After
+    post_json(gateway_addr, "/v1/challenge", serde_json::json!({ "user": "alice", "r1": r1, "r2": r2 })).await;
+    let request = http::Request::post(format!("http://{}/v1/challenge", gateway_addr)).header("content-type", "application/json").body(Body::from(serde_json::json!({ "user": "alice", "r1": r1, "r2": r2 }).to_string())).unwrap();
+    let response = hyper::Client::new().request(request).await.unwrap();
+    assert_eq!(response.status(), http::StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().get(http::header::RETRY_AFTER).is_some());
+}
+
+#[tokio::test]
+async fn test_rate_limits() {
+    assert_eq!("30/m".parse::<Quota>().unwrap(), Quota::per_minute(30));
+    assert_eq!("5/s".parse::<Quota>().unwrap(), Quota { requests: 5, per: Duration::from_secs(1) });
+    assert!("0/m".parse::<Quota>().is_err());
+    assert!("5/d".parse::<Quota>().is_err());
+
+    let rate_limits = RateLimits { register: Some(Quota::per_minute(2)), challenge: Some(Quota::per_minute(2)), verify: Some(Quota::per_minute(1)) };
+    let config = ServerConfig { rate_limits, ..Default::default() };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(Server::builder().add_service(AuthServer::new(AuthImpl::new(config, MemoryStore::default()))).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+
+    // 同一个客户端地址的第三次注册超过配额，错误带有重试提示
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    for user in ["alice", "bob", "carol"] {
+        let proof = zkp.prove_non_interactive(&x, &registration_context(user));
+        let request = RegisterRequest {
+            user: user.to_string(),
+            y1: proof.y1.to_bytes_be(),
+            y2: proof.y2.to_bytes_be(),
+            proof_c: proof.c.to_bytes_be(),
+            proof_s: proof.s.to_bytes_be(),
+            ..Default::default()
+        };
+        let result = client.register(request).await;
+        if user == "carol" {
+            let status = result.unwrap_err();
+            assert_eq!(status.code(), Code::ResourceExhausted);
+            let retry_after = retry::retry_after(&status).unwrap();
+            assert!(retry_after > Duration::ZERO && retry_after <= Duration::from_secs(30));
+            let detail = error_detail(&status).unwrap();
+            assert_eq!((detail.code(), detail.retry_after_secs), (ErrorCode::RateLimited, retry_after.as_secs()));
+        } else {
+            result.unwrap();
+        }
+    }
+
+    // 挑战和验证的配额相互独立：两次挑战用完挑战配额，第二次应答超过验证配额
+    let mut answers = Vec::new();
+    for _ in 0..2 {
+        let k = ZKP::generate_random_number_below(&zkp.q);
+        let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+        let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+        let challenge = client.create_authentication_challenge(AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() }).await.unwrap().into_inner();
+        let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be();
+        answers.push(AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() });
+    }
+    let request = AuthenticationChallengeRequest { user: "bob".to_string(), r1: zkp.alpha.to_bytes_be(), r2: zkp.beta.to_bytes_be(), ..Default::default() };
+    assert_eq!(client.create_authentication_challenge(request).await.unwrap_err().code(), Code::ResourceExhausted);
+    // 跨设备登录与挑战共用配额，不能用来绕过限流探测用户名
+    assert_eq!(client.create_pending_login(CreatePendingLoginRequest { user: "bob".to_string() }).await.unwrap_err().code(), Code::ResourceExhausted);
+    client.verify_authentication(answers.remove(0)).await.unwrap();
+    assert_eq!(client.verify_authentication(answers.remove(0)).await.unwrap_err().code(), Code::ResourceExhausted);
+}
+
+#[tokio::test]
+async fn test_reload() {
+    let auth = Arc::new(AuthImpl::new(ServerConfig::default(), MemoryStore::default()));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(Server::builder().add_service(AuthServer::from_arc(auth.clone())).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+    let request = RegisterRequest {
+        user: "alice".to_string(),
+        y1: proof.y1.to_bytes_be(),
+        y2: proof.y2.to_bytes_be(),
+        proof_c: proof.c.to_bytes_be(),
+        proof_s: proof.s.to_bytes_be(),
+        ..Default::default()
+    };
+    client.register(request).await.unwrap();
+    let challenge = |client: &mut AuthClient<tonic::transport::Channel>| {
+        let mut client = client.clone();
+        let zkp = zkp.clone();
+        async move {
+            let k = ZKP::generate_random_number_below(&zkp.q);
+            let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+            let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+            client.create_authentication_challenge(AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() }).await.map(|response| (k, response.into_inner()))
+        }
+    };
+    let now = || SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let (_, first) = challenge(&mut client).await.unwrap();
+    assert!(first.expires_at > now() + 30);
+
+    // 重新加载后，同一个连接上的新挑战和会话使用新的有效期，挑战受新的配额限制
+    let rate_limits = RateLimits { challenge: Some(Quota::per_minute(1)), ..Default::default() };
+    auth.reload(&ServerConfig { challenge_ttl_secs: 5, session_ttl_secs: 30, rate_limits, ..Default::default() }).unwrap();
+    let (k, second) = challenge(&mut client).await.unwrap();
+    assert!(second.expires_at <= now() + 5);
+    let s = zkp.solve(&k, &BigUint::from_bytes_be(&second.c), &x).to_bytes_be();
+    let session = client.verify_authentication(AuthenticationAnswerRequest { auth_id: second.auth_id, s, ..Default::default() }).await.unwrap().into_inner();
+    assert!(session.expires_at > now() && session.expires_at <= now() + 30);
+    assert_eq!(challenge(&mut client).await.unwrap_err().code(), Code::ResourceExhausted);
+
+    // 恢复默认配置后不再限制
+    auth.reload(&ServerConfig::default()).unwrap();
+    challenge(&mut client).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_connection_limits() {
+    use tonic::transport::Endpoint;
+    use zkp_server::serve_auth;
+
+    let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let connection_limits = ConnectionLimits { max_connections: Some(1), max_message_bytes: 1024, ..Default::default() };
+    tokio::spawn(serve_auth(Arc::new(AuthImpl::new(ServerConfig { addr, connection_limits, ..Default::default() }, MemoryStore::default()))));
+    while tokio::net::TcpStream::connect(addr).await.is_err() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let endpoint = Endpoint::from_shared(format!("http://{}", addr)).unwrap();
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let register = |user: &str, metadata: HashMap<String, String>| {
+        let proof = zkp.prove_non_interactive(&x, &registration_context(user));
+        RegisterRequest {
+            user: user.to_string(),
+            y1: proof.y1.to_bytes_be(),
+            y2: proof.y2.to_bytes_be(),
+            proof_c: proof.c.to_bytes_be(),
+            proof_s: proof.s.to_bytes_be(),
+            metadata,
+            ..Default::default()
+        }
+    };
+
+    // 超过大小的消息被拒绝，不会到达处理函数
+    let mut first = AuthClient::new(endpoint.connect().await.unwrap());
+    let large = HashMap::from([("note".to_string(), "x".repeat(2048))]);
+    assert_eq!(first.register(register("alice", large)).await.unwrap_err().code(), Code::OutOfRange);
+    first.register(register("alice", HashMap::new())).await.unwrap();
+
+    // 达到连接数上限时第二个连接等待，第一个连接关闭后才被处理
+    let mut second = AuthClient::new(endpoint.connect_lazy());
+    assert!(tokio::time::timeout(Duration::from_millis(300), second.register(register("bob", HashMap::new()))).await.is_err());
+    drop(first);
+    tokio::time::timeout(Duration::from_secs(5), second.register(register("bob", HashMap::new()))).await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_peer_policy_and_forwarded_addresses() {
+    use tonic::transport::Endpoint;
+    use zkp_server::{serve_auth, IpNet, PeerPolicy};
+
+    let net = |value: &str| value.parse::<IpNet>().unwrap();
+    assert!(net("10.0.0.0/8").contains("10.1.2.3".parse().unwrap()));
+    assert!(!net("10.0.0.0/8").contains("11.0.0.1".parse().unwrap()));
+    assert!(net("10.0.0.0/8").contains("::ffff:10.0.0.1".parse().unwrap()));
+    assert!(net("2001:db8::/32").contains("2001:db8::1".parse().unwrap()));
+    assert_eq!(net("10.1.2.3/8").to_string(), "10.0.0.0/8");
+    assert_eq!(net("192.0.2.1"), net("192.0.2.1/32"));
+    assert!("10.0.0.0/33".parse::<IpNet>().is_err());
+    assert!("example.com/8".parse::<IpNet>().is_err());
+
+    // 只有可信代理转发的 x-forwarded-for 有效，客户端在最左侧伪造的地址被忽略
+    let policy = PeerPolicy { trusted_proxies: vec![net("127.0.0.1"), net("10.0.0.0/8")], ..Default::default() };
+    let mut headers = http::HeaderMap::new();
+    headers.insert("x-forwarded-for", "192.0.2.9, 198.51.100.7, 10.0.0.2".parse().unwrap());
+    assert_eq!(policy.client_addr("127.0.0.1".parse().unwrap(), &headers), "198.51.100.7".parse::<std::net::IpAddr>().unwrap());
+    assert_eq!(policy.client_addr("192.0.2.1".parse().unwrap(), &headers), "192.0.2.1".parse::<std::net::IpAddr>().unwrap());
+    let policy = PeerPolicy { allow: vec![net("198.51.100.0/24")], deny: vec![net("198.51.100.66")], ..Default::default() };
+    assert!(policy.permits("198.51.100.7".parse().unwrap()));
+    assert!(!policy.permits("198.51.100.66".parse().unwrap()));
+    assert!(!policy.permits("192.0.2.1".parse().unwrap()));
+
+    // 服务器在 127.0.0.1 上的代理之后：按转发的客户端地址限流、记录审计日志和拒绝
+    let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let audit_log = Arc::new(MemoryAuditLog::default());
+    let peers = PeerPolicy { deny: vec![net("203.0.113.0/24")], trusted_proxies: vec![net("127.0.0.1")], ..Default::default() };
+    let rate_limits = RateLimits { register: Some(Quota::per_minute(1)), ..Default::default() };
+    let config = ServerConfig { addr, peers, rate_limits, audit_log: Some(audit_log.clone()), ..Default::default() };
+    tokio::spawn(serve_auth(Arc::new(AuthImpl::new(config, MemoryStore::default()))));
+    while tokio::net::TcpStream::connect(addr).await.is_err() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let mut client = AuthClient::new(Endpoint::from_shared(format!("http://{}", addr)).unwrap().connect().await.unwrap());
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let register = |user: &str, forwarded_for: &str| {
+        let proof = zkp.prove_non_interactive(&x, &registration_context(user));
+        let message = RegisterRequest {
+            user: user.to_string(),
+            y1: proof.y1.to_bytes_be(),
+            y2: proof.y2.to_bytes_be(),
+            proof_c: proof.c.to_bytes_be(),
+            proof_s: proof.s.to_bytes_be(),
+            ..Default::default()
+        };
+        let mut request = Request::new(message);
+        request.metadata_mut().insert("x-forwarded-for", forwarded_for.parse().unwrap());
+        request
+    };
+    client.register(register("alice", "198.51.100.7")).await.unwrap();
+    client.register(register("bob", "198.51.100.8")).await.unwrap();
+    assert_eq!(client.register(register("carol", "198.51.100.7")).await.unwrap_err().code(), Code::ResourceExhausted);
+    assert_eq!(client.register(register("dave", "203.0.113.5")).await.unwrap_err().code(), Code::PermissionDenied);
+
+    let events = audit_log.query(&AuditQuery { user: "alice".to_string(), limit: 10, ..Default::default() }).await.unwrap();
+    assert_eq!(events[0].remote_addr, "198.51.100.7");
+    assert!(audit_log.query(&AuditQuery { user: "dave".to_string(), limit: 10, ..Default::default() }).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_account_lockout() {
+    let lockout = LockoutPolicy { threshold: 2, base: Duration::from_secs(60), max: Duration::from_secs(3600) };
+    let auth = Arc::new(AuthImpl::new(ServerConfig { lockout: Some(lockout), ..Default::default() }, MemoryStore::default()));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(Server::builder().add_service(AuthServer::from_arc(auth.clone())).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+    let request = RegisterRequest {
+        user: "alice".to_string(),
+        y1: proof.y1.to_bytes_be(),
+        y2: proof.y2.to_bytes_be(),
+        proof_c: proof.c.to_bytes_be(),
+        proof_s: proof.s.to_bytes_be(),
+        ..Default::default()
+    };
+    client.register(request).await.unwrap();
+    let answer = |secret: BigUint| {
+        let mut client = client.clone();
+        let zkp = zkp.clone();
+        async move {
+            let k = ZKP::generate_random_number_below(&zkp.q);
+            let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+            let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+            let challenge = client.create_authentication_challenge(AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() }).await.unwrap().into_inner();
+            let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &secret).to_bytes_be();
+            client.verify_authentication(AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() }).await
+        }
+    };
+
+    // 两次猜错之后，正确的密码在锁定期间也被拒绝，错误带有锁定结束前的等待时间
+    for _ in 0..2 {
+        assert_eq!(error_code(&answer(&x + 1u32).await.unwrap_err()), ErrorCode::BadProof);
+    }
+    let status = answer(x.clone()).await.unwrap_err();
+    assert_eq!((status.code(), error_code(&status)), (Code::Unavailable, ErrorCode::AccountLocked));
+    let retry_after = retry::retry_after(&status).unwrap();
+    assert!(retry_after > Duration::from_secs(50) && retry_after <= Duration::from_secs(60));
+
+    // 运维解锁后可以登录，登录成功清零计数
+    assert!(auth.unlock_user("alice"));
+    assert!(!auth.unlock_user("alice"));
+    answer(x.clone()).await.unwrap();
+    assert_eq!(answer(&x + 1u32).await.unwrap_err().code(), Code::PermissionDenied);
+    answer(x).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_session_limit() {
+    use zkp_proto::zkp_auth::auth_server::Auth;
+    use zkp_server::{SessionLimit, SessionLimitPolicy};
+
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let start = |policy: SessionLimitPolicy| {
+        let audit_log = Arc::new(MemoryAuditLog::default());
+        let config = ServerConfig { session_limit: Some(SessionLimit { max_sessions: 2, policy }), audit_log: Some(audit_log.clone()), ..Default::default() };
+        let auth = Arc::new(AuthImpl::new(config, MemoryStore::default()));
+        let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+        let request = RegisterRequest {
+            user: "alice".to_string(),
+            y1: proof.y1.to_bytes_be(),
+            y2: proof.y2.to_bytes_be(),
+            proof_c: proof.c.to_bytes_be(),
+            proof_s: proof.s.to_bytes_be(),
+            ..Default::default()
+        };
+        (auth, audit_log, request)
+    };
+    let login = |auth: Arc<AuthImpl>| {
+        let (zkp, x) = (zkp.clone(), x.clone());
+        async move {
+            let k = ZKP::generate_random_number_below(&zkp.q);
+            let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+            let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+            let challenge = auth.create_authentication_challenge(Request::new(AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() })).await?.into_inner();
+            let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be();
+            let response = auth.verify_authentication(Request::new(AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() })).await?;
+            Ok::<_, Status>(response.into_inner().session_id)
+        }
+    };
+    let valid = |auth: Arc<AuthImpl>, session_id: String| async move { auth.validate_session(Request::new(ValidateSessionRequest { session_id, ..Default::default() })).await.unwrap().into_inner().valid };
+
+    // 第三次登录吊销一个最早的会话，吊销记入审计日志
+    let (auth, audit_log, request) = start(SessionLimitPolicy::EvictOldest);
+    auth.register(Request::new(request)).await.unwrap();
+    let mut sessions = Vec::new();
+    for _ in 0..3 {
+        sessions.push(login(auth.clone()).await.unwrap());
+    }
+    let mut revoked = Vec::new();
+    for session_id in &sessions {
+        if !valid(auth.clone(), session_id.clone()).await {
+            revoked.push(session_id.clone());
+        }
+    }
+    assert_eq!(revoked.len(), 1);
+    assert_ne!(revoked[0], sessions[2]);
+    let events = audit_log.query(&AuditQuery { user: "alice".to_string(), limit: 20, ..Default::default() }).await.unwrap();
+    let evictions = events.iter().filter(|event| event.kind == AuditKind::SessionRevoked).collect::<Vec<_>>();
+    assert_eq!(evictions.len(), 1);
+    assert_eq!((evictions[0].reason.as_str(), evictions[0].session.clone()), ("session-limit", zkp_server::admin::session_handle(&revoked[0])));
+
+    // 拒绝策略：达到上限后新的登录失败，注销一个会话后可以再登录
+    let (auth, _, request) = start(SessionLimitPolicy::Reject);
+    auth.register(Request::new(request)).await.unwrap();
+    let first = login(auth.clone()).await.unwrap();
+    login(auth.clone()).await.unwrap();
+    let status = login(auth.clone()).await.unwrap_err();
+    assert_eq!((status.code(), error_code(&status)), (Code::FailedPrecondition, ErrorCode::TooManySessions));
+    assert!(valid(auth.clone(), first.clone()).await);
+    auth.logout(Request::new(LogoutRequest { session_id: first })).await.unwrap();
+    login(auth).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_drain_mode() {
+    use tonic_health::pb::{health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest};
+    use zkp_proto::zkp_auth::SetDrainModeRequest;
+    use zkp_server::serve_auth;
+
+    let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let admin = AdminPolicy::default().with_token("dashboard-token", "dashboard", AdminRole::Viewer).with_token("oncall-token", "oncall", AdminRole::Operator);
+    let auth = Arc::new(AuthImpl::new(ServerConfig { addr, admin: Some(admin), ..Default::default() }, MemoryStore::default()));
+    tokio::spawn(serve_auth(auth.clone()));
+    while tokio::net::TcpStream::connect(addr).await.is_err() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let url = format!("http://{}", addr);
+    let mut client = AuthClient::connect(url.clone()).await.unwrap();
+    let mut admin = AuthAdminClient::connect(url.clone()).await.unwrap();
+    let health = HealthClient::new(tonic::transport::Endpoint::from_shared(url).unwrap().connect().await.unwrap());
+    // 健康检查的状态由后台任务更新，等待服务的状态变为 expected
+    let reports = |service: &'static str, expected: ServingStatus| {
+        let mut health = health.clone();
+        async move {
+            for _ in 0..100 {
+                if health.check(HealthCheckRequest { service: service.to_string() }).await.ok().map(|response| response.into_inner().status()) == Some(expected) {
+                    return true;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            false
+        }
+    };
+    for service in HEALTH_SERVICES {
+        assert!(reports(service, ServingStatus::Serving).await);
+    }
+
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let register = |user: &str| {
+        let proof = zkp.prove_non_interactive(&x, &registration_context(user));
+        RegisterRequest {
+            user: user.to_string(),
+            y1: proof.y1.to_bytes_be(),
+            y2: proof.y2.to_bytes_be(),
+            proof_c: proof.c.to_bytes_be(),
+            proof_s: proof.s.to_bytes_be(),
+            ..Default::default()
+        }
+    };
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let commitment = AuthenticationChallengeRequest {
+        user: "alice".to_string(),
+        r1: ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be(),
+        r2: ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be(),
+        ..Default::default()
+    };
+    client.register(register("alice")).await.unwrap();
+    let challenge = client.create_authentication_challenge(commitment.clone()).await.unwrap().into_inner();
+
+    // 只有 operator 可以切换排空模式
+    let request = as_admin("dashboard-token", SetDrainModeRequest { draining: true });
+    assert_eq!(admin.set_drain_mode(request).await.unwrap_err().code(), Code::PermissionDenied);
+    assert!(!admin.set_drain_mode(as_admin("oncall-token", SetDrainModeRequest { draining: true })).await.unwrap().into_inner().was_draining);
+    assert!(auth.is_draining());
+
+    // 排空时健康检查报告 NOT_SERVING，新的注册和挑战带有重试提示地被拒绝
+    for service in HEALTH_SERVICES {
+        assert!(reports(service, ServingStatus::NotServing).await);
+    }
+    for status in [client.register(register("bob")).await.unwrap_err(), client.create_authentication_challenge(commitment.clone()).await.unwrap_err()] {
+        assert_eq!((status.code(), error_code(&status)), (Code::Unavailable, ErrorCode::Draining));
+        assert!(retry::retry_after(&status).is_some());
+    }
+
+    // 已经签发的挑战仍然可以应答，得到的会话仍然有效
+    let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be();
+    let session_id = client.verify_authentication(AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() }).await.unwrap().into_inner().session_id;
+    assert!(client.validate_session(ValidateSessionRequest { session_id, ..Default::default() }).await.unwrap().into_inner().valid);
+
+    // 退出排空模式后恢复正常服务
+    assert!(auth.set_draining(false));
+    client.register(register("bob")).await.unwrap();
+    client.create_authentication_challenge(commitment).await.unwrap();
+    assert!(reports("zkp_auth.Auth", ServingStatus::Serving).await);
+}
+
+#[tokio::test]
+async fn test_encryption_at_rest() {
+    use zkp_server::{EncryptedStore, Keyring};
+
+    let (old_key, new_key) = (vec![1u8; 32], vec![2u8; 32]);
+    let inner = Arc::new(MemoryStore::default());
+    let encrypted = |keys: &[(u32, Vec<u8>)]| {
+        let keys = Arc::new(Keyring::new(keys).unwrap());
+        (Arc::new(EncryptedStore::new(inner.clone() as Arc<dyn UserStore>, keys.clone())), Arc::new(EncryptedStore::new(inner.clone() as Arc<dyn SessionStore>, keys)))
+    };
+    let (users, sessions) = encrypted(&[(1, old_key.clone())]);
+
+    // 通过加密的存储注册和登录
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let service = AuthServer::new(AuthImpl::with_stores(ServerConfig::default(), users.clone(), sessions.clone()));
+    tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+    let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+    let request = RegisterRequest { user: "alice".to_string(), y1: proof.y1.to_bytes_be(), y2: proof.y2.to_bytes_be(), proof_c: proof.c.to_bytes_be(), proof_s: proof.s.to_bytes_be(), ..Default::default() };
+    client.register(request).await.unwrap();
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let (r1, r2) = (ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be(), ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be());
+    let challenge = client.create_authentication_challenge(AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() }).await.unwrap().into_inner();
+    let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be();
+    let session_id = client.verify_authentication(AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() }).await.unwrap().into_inner().session_id;
+    assert!(client.validate_session(ValidateSessionRequest { session_id: session_id.clone(), ..Default::default() }).await.unwrap().into_inner().valid);
+
+    // 存储中的 y1 和会话 ID 都是密文，通过加密的存储读到的是明文
+    let stored = inner.get_user("alice").await.unwrap().unwrap();
+    assert_ne!(stored.y1, proof.y1);
+    assert_eq!(users.get_user("alice").await.unwrap().unwrap().y1, proof.y1);
+    let stored_sessions = inner.list_sessions("alice").await.unwrap();
+    assert_eq!(stored_sessions.len(), 1);
+    let stored_id = stored_sessions[0].0.clone();
+    assert_ne!(stored_id, session_id);
+    assert!(inner.get_session(&session_id).await.unwrap().is_none());
+    assert_eq!(sessions.list_sessions("alice").await.unwrap()[0].0, session_id);
+    // 持有存储内容的人不能把其中的键当作会话 ID 使用
+    assert!(sessions.get_session(&stored_id).await.unwrap().is_none());
+
+    // 密文与用户名绑定，不能复制到其他用户的记录中
+    inner.put_user("mallory", stored.clone()).await.unwrap();
+    assert!(users.get_user("mallory").await.is_err());
+
+    // 轮换：新的密钥在前，旧的记录和会话仍然可以读取；重新写入后用新的密钥加密
+    let (users, sessions) = encrypted(&[(2, new_key.clone()), (1, old_key)]);
+    assert!(sessions.get_session(&session_id).await.unwrap().is_some());
+    assert!(users.update_user("alice", Box::new(|_| true)).await.unwrap());
+    let rotated = inner.get_user("alice").await.unwrap().unwrap().y1.to_bytes_be();
+    assert_eq!(rotated[4..8], 2u32.to_be_bytes());
+    assert_eq!(users.get_user("alice").await.unwrap().unwrap().y1, proof.y1);
+
+    // 删除旧的密钥后，用户记录已经重新加密，旧的密钥建立的会话不再有效
+    let (users, sessions) = encrypted(&[(2, new_key)]);
+    assert_eq!(users.get_user("alice").await.unwrap().unwrap().y1, proof.y1);
+    assert!(sessions.get_session(&session_id).await.unwrap().is_none());
+    assert!(sessions.list_sessions("alice").await.is_err());
+
+    // 开启加密之前写入的明文记录仍然可以读取
+    inner.put_user("bob", UserInfo { y1: proof.y1.clone(), ..Default::default() }).await.unwrap();
+    assert_eq!(users.get_user("bob").await.unwrap().unwrap().y1, proof.y1);
+
+    // 密钥文件的格式
+    let keys = Keyring::parse(&format!("# current\n3:{}\n\n1:{}\n", "ab".repeat(32), "cd".repeat(32))).unwrap();
+    assert_eq!(keys.key_ids(), [3, 1]);
+    assert!(Keyring::parse("").is_err());
+    assert!(Keyring::parse(&format!("1:{}", "ab".repeat(16))).is_err());
+    assert!(Keyring::parse(&format!("1:{0}\n1:{0}", "ab".repeat(32))).is_err());
+    assert_eq!(Keyring::from_command(&format!("echo 7:{}", "ef".repeat(32))).unwrap().key_ids(), [7]);
+    assert!(Keyring::from_command("exit 1").is_err());
+}
+
+#[test]
+fn test_settings_from_cli_and_file() {
+    use clap::Parser;
+    use zkp_server::export::ExportFormat;
+    use zkp_server::settings::{Command, Settings, UserCommand};
+
+    let path = std::env::temp_dir().join(format!("zkp-settings-{}.toml", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"
+addr = "0.0.0.0:6000"
+session_ttl_secs = 120
+honeytokens = ["admin", " root "]
+rate_limit_challenge = "30/m"
+jwt_secret = "from-file"
+"#,
+    )
+    .unwrap();
+
+    // 命令行的设置优先，配置文件补充未设置的项，其余使用默认值
+    let cli = Settings::try_parse_from(["server", "--addr", "127.0.0.1:7000", "--config", path.to_str().unwrap()]).unwrap();
+    let settings = cli.or(Settings::from_file(&path).unwrap());
+    let config = settings.server_config().unwrap();
+    assert_eq!(config.addr, "127.0.0.1:7000".parse().unwrap());
+    assert_eq!(config.session_ttl_secs, 120);
+    assert_eq!(config.challenge_ttl_secs, ServerConfig::default().challenge_ttl_secs);
+    assert_eq!(config.honeytokens, vec!["admin".to_string(), "root".to_string()]);
+    assert_eq!(config.rate_limits.challenge, Some(Quota::per_minute(30)));
+    assert_eq!(config.rate_limits.register, None);
+    assert!(matches!(config.jwt.unwrap().key, JwtKey::Hs256(secret) if secret == b"from-file"));
+    assert!(std::ptr::eq(config.params, zkp_core::GroupParams::rfc5114_1024()));
+
+    // 示例配置文件可以直接使用
+    let example = Settings::from_file(&std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("server.example.toml")).ok().unwrap();
+    assert_eq!(example.server_config().unwrap().rate_limits.register, Some(Quota::per_minute(10)));
+    assert_eq!(example.log_format, Some(zkp_server::settings::LogFormat::Text));
+
+    // 未知的键和无效的值都是错误
+    std::fs::write(&path, "adress = \"0.0.0.0:6000\"\n").unwrap();
+    assert!(Settings::from_file(&path).err().unwrap().contains("adress"));
+    std::fs::remove_file(&path).unwrap();
+    let invalid = Settings::try_parse_from(["server", "--rate-limit-verify", "often"]).unwrap();
+    assert!(invalid.server_config().unwrap_err().contains("often"));
+    let invalid = Settings::try_parse_from(["server", "--group", "/nonexistent/params.pem"]).unwrap();
+    assert!(invalid.server_config().is_err());
+    let invalid = Settings::try_parse_from(["server", "--extra-groups", "rfc5114-1024,/nonexistent/params.pem"]).unwrap();
+    assert!(invalid.server_config().unwrap_err().contains("/nonexistent/params.pem"));
+    assert!(Settings::try_parse_from(["server", "--tls-key", "key.pem"]).unwrap().server_config().is_err());
+    assert!(Settings::try_parse_from(["server", "--lockout-secs", "7200"]).unwrap().server_config().is_err());
+    assert_eq!(Settings::try_parse_from(["server", "--lockout-threshold", "0"]).unwrap().server_config().unwrap().lockout, None);
+    let admin = Settings::try_parse_from(["server", "--admin-tokens", "oncall:operator:s3cr:t", "--admin-identities", "ops.example.com:admin"]).unwrap();
+    assert!(admin.server_config().unwrap().admin.is_some());
+    assert!(Settings::default().server_config().unwrap().admin.is_none());
+    let invalid = Settings::try_parse_from(["server", "--admin-tokens", "oncall:root:s3cret"]).unwrap();
+    assert!(invalid.server_config().unwrap_err().contains("root"));
+    assert!(Settings::try_parse_from(["server", "--admin-tokens", "s3cret"]).unwrap().server_config().is_err());
+    let names = Settings::try_parse_from(["server", "--user-name-normalization", "nfc-lowercase", "--user-name-min-len", "3", "--reserved-user-names", "admin, root"]).unwrap();
+    let policy = names.server_config().unwrap().user_names;
+    assert_eq!((policy.normalization, policy.min_len, policy.reserved), (UserNameNormalization::NfcLowercase, 3, vec!["admin".to_string(), "root".to_string()]));
+    assert!(Settings::try_parse_from(["server", "--user-name-min-len", "10", "--user-name-max-len", "5"]).unwrap().server_config().is_err());
+    let limits = Settings::try_parse_from(["server", "--max-connections", "100", "--request-timeout-secs", "30"]).unwrap().server_config().unwrap().connection_limits;
+    assert_eq!(limits, ConnectionLimits { max_connections: Some(100), request_timeout: Some(Duration::from_secs(30)), ..Default::default() });
+    assert!(Settings::try_parse_from(["server", "--max-message-bytes", "0"]).is_err());
+    let peers = Settings::try_parse_from(["server", "--deny-ips", "203.0.113.0/24", "--trusted-proxies", "10.0.0.1, 10.0.1.0/24"]).unwrap().server_config().unwrap().peers;
+    assert_eq!((peers.allow.len(), peers.deny.len(), peers.trusted_proxies.len()), (0, 1, 2));
+    assert!(Settings::try_parse_from(["server", "--allow-ips", "10.0.0.0/40"]).unwrap().server_config().unwrap_err().contains("10.0.0.0/40"));
+    let limit = Settings::try_parse_from(["server", "--max-sessions-per-user", "5", "--session-limit-policy", "reject"]).unwrap().server_config().unwrap().session_limit;
+    assert_eq!(limit, Some(zkp_server::SessionLimit { max_sessions: 5, policy: zkp_server::SessionLimitPolicy::Reject }));
+    assert!(Settings::try_parse_from(["server", "--session-limit-policy", "reject"]).unwrap().server_config().is_err());
+    assert!(Settings::try_parse_from(["server", "--max-sessions-per-user", "0"]).is_err());
+
+    // 导出和导入用户的子命令使用同样的设置
+    let export = Settings::try_parse_from(["server", "--store", "sqlite:users.db", "export-users", "--format", "csv"]).unwrap();
+    assert!(matches!(export.command, Some(Command::ExportUsers { format: ExportFormat::Csv, include_totp_secrets: false, output: None })));
+    let import = Settings::try_parse_from(["server", "import-users", "users.jsonl", "--replace"]).unwrap();
+    assert!(matches!(import.command, Some(Command::ImportUsers { format: ExportFormat::Json, replace: true, .. })));
+
+    // 运维的子命令，不指定子命令时启动服务器
+    assert!(matches!(Settings::try_parse_from(["server", "serve"]).unwrap().command, Some(Command::Serve)));
+    assert!(Settings::try_parse_from(["server"]).unwrap().command.is_none());
+    let delete = Settings::try_parse_from(["server", "--store", "sqlite:users.db", "user", "delete", "alice"]).unwrap();
+    assert!(matches!(delete.command, Some(Command::User(UserCommand::Delete { user })) if user == "alice"));
+    assert!(matches!(Settings::try_parse_from(["server", "user", "list", "--limit", "10"]).unwrap().command, Some(Command::User(UserCommand::List { limit: 10, .. }))));
+    assert!(matches!(Settings::try_parse_from(["server", "gen-params", "--q-bits", "160"]).unwrap().command, Some(Command::GenParams { p_bits: 2048, q_bits: 160, output: None })));
+
+    // --migrate-only 与 migrate 子命令相同，不能与其他子命令同时使用
+    assert!(matches!(Settings::try_parse_from(["server", "--migrate-only"]).unwrap().effective_command(), Ok(Some(Command::Migrate))));
+    assert!(matches!(Settings::try_parse_from(["server", "--migrate-only", "serve"]).unwrap().effective_command(), Ok(Some(Command::Migrate))));
+    assert!(Settings::try_parse_from(["server", "--migrate-only", "user", "list"]).unwrap().effective_command().is_err());
+    assert!(matches!(Settings::try_parse_from(["server", "--migrate-only=false", "serve"]).unwrap().effective_command(), Ok(Some(Command::Serve))));
+}
+
+#[cfg(feature = "tls")]
+#[tokio::test]
+async fn test_mtls_registration_identities() {
+    use rcgen::{BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType, IsCa};
+    use tonic::transport::{Certificate as CaCertificate, ClientTlsConfig, Endpoint, Identity};
+    use zkp_server::TlsConfig;
+
+    // 一个 CA，由它签发的服务器证书和两个客户端证书
+    let mut params = CertificateParams::new(Vec::new());
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca = Certificate::from_params(params).unwrap();
+    let server_cert = Certificate::from_params(CertificateParams::new(vec!["localhost".to_string()])).unwrap();
+    let client_cert = |name: &str| {
+        let mut params = CertificateParams::new(Vec::new());
+        params.distinguished_name = DistinguishedName::new();
+        params.distinguished_name.push(DnType::CommonName, name);
+        let cert = Certificate::from_params(params).unwrap();
+        Identity::from_pem(cert.serialize_pem_with_signer(&ca).unwrap(), cert.serialize_private_key_pem())
+    };
+
+    let tls = TlsConfig {
+        cert_pem: server_cert.serialize_pem_with_signer(&ca).unwrap().into_bytes(),
+        key_pem: server_cert.serialize_private_key_pem().into_bytes(),
+        client_ca_pem: None,
+        client_auth_optional: false,
+    }
+    .with_client_ca(ca.serialize_pem().unwrap().into_bytes());
+    let config = ServerConfig { registration_identities: vec!["registrar-1".to_string()], ..Default::default() };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("https://{}", listener.local_addr().unwrap());
+    let mut server = Server::builder().tls_config(tls.server_tls_config()).unwrap();
+    tokio::spawn(server.add_service(AuthServer::new(AuthImpl::new(config, MemoryStore::default()))).serve_with_incoming(TcpListenerStream::new(listener)));
+
+    let ca_pem = ca.serialize_pem().unwrap();
+    let connect = |identity: Option<Identity>| {
+        let mut tls = ClientTlsConfig::new().domain_name("localhost").ca_certificate(CaCertificate::from_pem(&ca_pem));
+        if let Some(identity) = identity {
+            tls = tls.identity(identity);
+        }
+        let endpoint = Endpoint::from_shared(url.clone()).unwrap().tls_config(tls).unwrap();
+        async move { endpoint.connect().await.map(AuthClient::new) }
+    };
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let register = |user: &str| {
+        let proof = zkp.prove_non_interactive(&x, &registration_context(user));
+        RegisterRequest {
+            user: user.to_string(),
+            y1: proof.y1.to_bytes_be(),
+            y2: proof.y2.to_bytes_be(),
+            proof_c: proof.c.to_bytes_be(),
+            proof_s: proof.s.to_bytes_be(),
+            ..Default::default()
+        }
+    };
+
+    // 只有配置的客户端身份可以注册，其他持有有效证书的客户端仍然可以认证
+    let mut registrar = connect(Some(client_cert("registrar-1"))).await.unwrap();
+    registrar.register(register("alice")).await.unwrap();
+    let mut laptop = connect(Some(client_cert("laptop"))).await.unwrap();
+    let status = laptop.register(register("bob")).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    assert!(status.message().contains("laptop"));
+    let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1: zkp.alpha.to_bytes_be(), r2: zkp.beta.to_bytes_be(), ..Default::default() };
+    laptop.create_authentication_challenge(request).await.unwrap();
+
+    // 没有客户端证书的连接在握手时被拒绝，不会到达处理函数
+    let rejected = match connect(None).await {
+        Ok(mut client) => client.register(register("carol")).await.is_err(),
+        Err(_) => true,
+    };
+    assert!(rejected);
+}
+
+#[cfg(feature = "tls")]
+#[tokio::test]
+async fn test_reload_tls_certificate() {
+    use rcgen::{BasicConstraints, Certificate, CertificateParams, IsCa};
+    use tonic::transport::{Certificate as CaCertificate, ClientTlsConfig, Endpoint};
+    use zkp_server::{serve_auth, TlsConfig};
+
+    // 两个 CA，各签发一个服务器证书
+    let issue = || {
+        let mut params = CertificateParams::new(Vec::new());
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = Certificate::from_params(params).unwrap();
+        let server_cert = Certificate::from_params(CertificateParams::new(vec!["localhost".to_string()])).unwrap();
+        let tls = TlsConfig {
+            cert_pem: server_cert.serialize_pem_with_signer(&ca).unwrap().into_bytes(),
+            key_pem: server_cert.serialize_private_key_pem().into_bytes(),
+            client_ca_pem: None,
+            client_auth_optional: false,
+        };
+        (ca.serialize_pem().unwrap(), tls)
+    };
+    let (first_ca, first_tls) = issue();
+    let (second_ca, second_tls) = issue();
+
+    let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let auth = Arc::new(AuthImpl::new(ServerConfig { addr, tls: Some(first_tls), ..Default::default() }, MemoryStore::default()));
+    tokio::spawn(serve_auth(auth.clone()));
+    while tokio::net::TcpStream::connect(addr).await.is_err() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let connect = |ca_pem: &str| {
+        let tls = ClientTlsConfig::new().domain_name("localhost").ca_certificate(CaCertificate::from_pem(ca_pem));
+        let endpoint = Endpoint::from_shared(format!("https://{}", addr)).unwrap().tls_config(tls).unwrap();
+        async move { endpoint.connect().await.map(AuthClient::new) }
+    };
+    let register = |user: &str| {
+        let zkp = ZKP::get_constants();
+        let x = ZKP::generate_random_number_below(&zkp.q);
+        let proof = zkp.prove_non_interactive(&x, &registration_context(user));
+        RegisterRequest {
+            user: user.to_string(),
+            y1: proof.y1.to_bytes_be(),
+            y2: proof.y2.to_bytes_be(),
+            proof_c: proof.c.to_bytes_be(),
+            proof_s: proof.s.to_bytes_be(),
+            ..Default::default()
+        }
+    };
+    let mut before = connect(&first_ca).await.unwrap();
+    before.register(register("alice")).await.unwrap();
+
+    // 无效的证书和关闭 TLS 被拒绝，原来的证书继续使用
+    let invalid = TlsConfig { cert_pem: b"not a certificate".to_vec(), ..second_tls.clone() };
+    assert!(auth.reload(&ServerConfig { addr, tls: Some(invalid), ..Default::default() }).unwrap_err().contains("certificate"));
+    assert!(auth.reload(&ServerConfig { addr, ..Default::default() }).is_err());
+    connect(&first_ca).await.unwrap().register(register("bob")).await.unwrap();
+
+    // 替换证书后新的连接使用新证书，已经建立的连接不受影响
+    auth.reload(&ServerConfig { addr, tls: Some(second_tls), ..Default::default() }).unwrap();
+    connect(&second_ca).await.unwrap().register(register("carol")).await.unwrap();
+    let rejected = match connect(&first_ca).await {
+        Ok(mut client) => client.register(register("dave")).await.is_err(),
+        Err(_) => true,
+    };
+    assert!(rejected);
+    before.register(register("erin")).await.unwrap();
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_sqlite_store_survives_restart() {
+    use zkp_server::SqliteStore;
+
+    let path = std::env::temp_dir().join(format!("zkp-server-test-{}.sqlite", ZKP::generate_random_string(8)));
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+
+    // 第一个服务器：注册并登录
+    let session_id = {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let service = AuthServer::new(AuthImpl::new(ServerConfig::default(), SqliteStore::open(&path).unwrap()));
+        let server = tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+        let mut client = AuthClient::connect(url).await.unwrap();
+        let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+        let request = RegisterRequest {
+            user: "alice".to_string(),
+            y1: proof.y1.to_bytes_be(),
+            y2: proof.y2.to_bytes_be(),
+            proof_c: proof.c.to_bytes_be(),
+            proof_s: proof.s.to_bytes_be(),
+            salt: vec![7; 16],
+            kdf: Some(KdfParams { algorithm: "pbkdf2-sha256".to_string(), iterations: 1000 }),
+            ..Default::default()
+        };
+        client.register(request).await.unwrap();
+        let k = ZKP::generate_random_number_below(&zkp.q);
+        let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+        let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+        let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() };
+        let challenge = client.create_authentication_challenge(request).await.unwrap().into_inner();
+        let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be();
+        let request = AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() };
+        let session = client.verify_authentication(request).await.unwrap().into_inner();
+        server.abort();
+        session.session_id
+    };
+
+    // 重新打开同一个数据库：会话仍然有效，用户可以再次登录，盐和 KDF 参数不变
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let service = AuthServer::new(AuthImpl::new(ServerConfig::default(), SqliteStore::open(&path).unwrap()));
+    tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+    let mut client = AuthClient::connect(url).await.unwrap();
+    let response = client.validate_session(ValidateSessionRequest { session_id, ..Default::default() }).await.unwrap().into_inner();
+    assert!(response.valid);
+    assert_eq!(response.user, "alice");
+
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+    let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+    let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() };
+    let challenge = client.create_authentication_challenge(request).await.unwrap().into_inner();
+    assert_eq!(challenge.salt, vec![7; 16]);
+    assert_eq!(challenge.kdf.unwrap().iterations, 1000);
+    let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be();
+    let request = AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() };
+    client.verify_authentication(request).await.unwrap();
+
+    // 管理接口分页列出用户
+    let store = SqliteStore::open(&path).unwrap();
+    let users = store.list_users("", 10).await.unwrap();
+    assert_eq!(users.iter().map(|(user, _)| user.as_str()).collect::<Vec<_>>(), ["alice"]);
+
+    // TOTP 密钥随用户记录保存
+    store.put_user("bob", UserInfo { totp_secret: vec![9; 20], group: vec![3; 32], ..users[0].1.clone() }).await.unwrap();
+    let bob = store.get_user("bob").await.unwrap().unwrap();
+    assert_eq!((bob.totp_secret, bob.group), (vec![9; 20], vec![3; 32]));
+
+    // 打开同一个文件的另一个连接（另一个进程）：同名用户只能创建一次，同一个挑战和会话只能被取走一次
+    let other = SqliteStore::open(&path).unwrap();
+    assert!(!other.create_user("bob", UserInfo::default()).await.unwrap());
+    assert!(other.create_user("carol", users[0].1.clone()).await.unwrap());
+    assert!(!store.create_user("carol", UserInfo::default()).await.unwrap());
+    let challenge = PendingChallenge { user: "bob".to_string(), r1: BigUint::from(1u32), r2: BigUint::from(2u32), c: BigUint::from(3u32), expires_at: u64::MAX >> 1, metadata: Default::default(), device_id: String::new() };
+    store.put_challenge("auth", challenge).await.unwrap();
+    assert_eq!(other.take_challenge("auth").await.unwrap().map(|challenge| challenge.c), Some(BigUint::from(3u32)));
+    assert!(store.take_challenge("auth").await.unwrap().is_none());
+    let session = SessionInfo { user: "bob".to_string(), issued_at: 1, expires_at: u64::MAX >> 1, auth_method: "password".to_string(), scopes: Vec::new(), metadata: HashMap::new(), device_id: String::new() };
+    store.put_session("session", session).await.unwrap();
+    assert_eq!(other.delete_session("session").await.unwrap().map(|session| session.user), Some("bob".to_string()));
+    assert!(store.delete_session("session").await.unwrap().is_none());
+
+    // 修改用户记录：update 放弃时不写回，用户不存在时不调用 update
+    assert!(store.update_user("bob", Box::new(|bob| { bob.display_name = "Bob".to_string(); true })).await.unwrap());
+    assert!(!store.update_user("bob", Box::new(|bob| { bob.display_name = "Robert".to_string(); false })).await.unwrap());
+    assert_eq!(other.get_user("bob").await.unwrap().unwrap().display_name, "Bob");
+    assert!(!store.update_user("nobody", Box::new(|_| unreachable!())).await.unwrap());
+
+    // 审计事件写入同一个数据库，按用户和序号查询
+    let event = AuditEvent { id: 0, at: 100, kind: AuditKind::Verify, user: "alice".to_string(), success: false, reason: "PermissionDenied: bad".to_string(), remote_addr: "127.0.0.1:1".to_string(), correlation_id: "-".to_string(), session: String::new() };
+    store.append(event.clone()).await.unwrap();
+    store.append(AuditEvent { user: "bob".to_string(), at: 200, ..event.clone() }).await.unwrap();
+    let events = SqliteStore::open(&path).unwrap().query(&AuditQuery { user: "alice".to_string(), limit: 10, ..Default::default() }).await.unwrap();
+    assert_eq!(events, [AuditEvent { id: 1, ..event.clone() }]);
+    let events = store.query(&AuditQuery { since: 150, until: 250, after: 1, limit: 10, ..Default::default() }).await.unwrap();
+    assert_eq!(events.iter().map(|event| (event.id, event.user.as_str())).collect::<Vec<_>>(), [(2, "bob")]);
+
+    // 改为假名：包含用户名的原因只保留错误码，客户端地址清空
+    store.append(AuditEvent { user: "bob".to_string(), reason: "NotFound: User: bob not found".to_string(), ..event.clone() }).await.unwrap();
+    assert_eq!(store.pseudonymize("bob", "deleted-bob").await.unwrap(), 2);
+    let events = store.query(&AuditQuery { user: "deleted-bob".to_string(), limit: 10, ..Default::default() }).await.unwrap();
+    assert_eq!(events.iter().map(|event| (event.id, event.reason.as_str(), event.remote_addr.as_str())).collect::<Vec<_>>(), [(2, "PermissionDenied: bad", ""), (3, "NotFound", "")]);
+    assert!(store.query(&AuditQuery { user: "bob".to_string(), limit: 10, ..Default::default() }).await.unwrap().is_empty());
+
+    // 比服务器更新的数据库结构拒绝打开：把文件头中的 user_version（第 60 个字节起，大端）改为一个很大的版本
+    let mut header = std::fs::read(&path).unwrap();
+    header[60..64].copy_from_slice(&1000u32.to_be_bytes());
+    std::fs::write(&path, header).unwrap();
+    assert!(SqliteStore::open(&path).unwrap_err().0.contains("schema version 1000 is newer than this server"));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[cfg(feature = "postgres")]
+#[tokio::test]
+#[ignore = "needs a PostgreSQL database in ZKP_TEST_DATABASE_URL"]
+async fn test_postgres_store_shared_between_instances() {
+    use zkp_server::{PostgresOptions, PostgresStore};
+
+    let url = std::env::var("ZKP_TEST_DATABASE_URL").expect("ZKP_TEST_DATABASE_URL is not set");
+    let user = format!("alice-{}", ZKP::generate_random_string(8)); // 数据库在多次运行之间保留，每次使用新的用户名
+
+    // 两个服务器实例连接同一个数据库
+    let mut clients = Vec::new();
+    for _ in 0..2 {
+        let store = PostgresStore::connect(&url, &PostgresOptions { max_connections: 2, ..Default::default() }).await.unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let service = AuthServer::new(AuthImpl::new(ServerConfig::default(), store));
+        tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+        clients.push(AuthClient::connect(url).await.unwrap());
+    }
+
+    // 在第一个实例注册和请求挑战，在第二个实例应答
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context(&user));
+    let request = RegisterRequest {
+        user: user.clone(),
+        y1: proof.y1.to_bytes_be(),
+        y2: proof.y2.to_bytes_be(),
+        proof_c: proof.c.to_bytes_be(),
+        proof_s: proof.s.to_bytes_be(),
+        ..Default::default()
+    };
+    clients[0].register(request.clone()).await.unwrap();
+    assert_eq!(clients[1].register(request).await.unwrap_err().code(), Code::AlreadyExists); // 另一个实例也看到用户名已被使用
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+    let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+    let request = AuthenticationChallengeRequest { user: user.clone(), r1, r2, ..Default::default() };
+    let challenge = clients[0].create_authentication_challenge(request).await.unwrap().into_inner();
+    let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be();
+    let request = AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() };
+    let session = clients[1].verify_authentication(request).await.unwrap().into_inner();
+
+    // 会话在两个实例上都有效
+    let response = clients[0].validate_session(ValidateSessionRequest { session_id: session.session_id.clone(), ..Default::default() }).await.unwrap().into_inner();
+    assert!(response.valid);
+    assert_eq!(response.user, user);
+
+    // 在第二个实例请求挑战并修改资料，第一个实例立即可见；同一个挑战不能在另一个实例再用一次
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+    let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+    let request = AuthenticationChallengeRequest { user: user.clone(), r1, r2, ..Default::default() };
+    let challenge = clients[1].create_authentication_challenge(request).await.unwrap().into_inner();
+    let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be();
+    let request = UpdateProfileRequest { auth_id: challenge.auth_id, s, display_name: Some("Alice".to_string()), contact: None, totp_code: String::new() };
+    clients[0].update_profile(request.clone()).await.unwrap();
+    assert_eq!(clients[1].update_profile(request).await.unwrap_err().code(), Code::NotFound);
+    let response = clients[1].introspect_session(IntrospectSessionRequest { session_id: session.session_id }).await.unwrap().into_inner();
+    assert_eq!(response.profile.unwrap().display_name, "Alice");
+
+    // 管理接口按用户名的字节顺序分页
+    let store = PostgresStore::connect(&url, &PostgresOptions { max_connections: 1, ..Default::default() }).await.unwrap();
+    let users = store.list_users(&user[..user.len() - 1], 1).await.unwrap();
+    assert_eq!(users[0].0, user);
+
+    // TOTP 密钥随用户记录保存
+    let totp_user = format!("{}-totp", user);
+    store.put_user(&totp_user, UserInfo { totp_secret: vec![9; 20], group: vec![3; 32], ..users[0].1.clone() }).await.unwrap();
+    let totp_info = store.get_user(&totp_user).await.unwrap().unwrap();
+    assert_eq!((totp_info.totp_secret, totp_info.group), (vec![9; 20], vec![3; 32]));
+
+    // 审计事件写入同一个数据库，按用户和时间范围查询
+    let event = AuditEvent { id: 0, at: 100, kind: AuditKind::SessionRevoked, user: user.clone(), success: true, reason: "logout".to_string(), remote_addr: String::new(), correlation_id: String::new(), session: "0123456789abcdef".to_string() };
+    store.append(event.clone()).await.unwrap();
+    store.append(AuditEvent { at: 200, ..event.clone() }).await.unwrap();
+    let events = store.query(&AuditQuery { user: user.clone(), until: 150, limit: 10, ..Default::default() }).await.unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(AuditEvent { id: 0, ..events[0].clone() }, event);
+    let events = store.query(&AuditQuery { user: user.clone(), after: events[0].id, limit: 10, ..Default::default() }).await.unwrap();
+    assert_eq!(events.iter().map(|event| event.at).collect::<Vec<_>>(), [200]);
+
+    // 改为假名后按原用户名查不到事件
+    let tombstone = format!("deleted-{}", ZKP::generate_random_string(8));
+    assert_eq!(store.pseudonymize(&user, &tombstone).await.unwrap(), 2);
+    assert!(store.query(&AuditQuery { user: user.clone(), limit: 10, ..Default::default() }).await.unwrap().is_empty());
+    assert_eq!(store.query(&AuditQuery { user: tombstone, limit: 10, ..Default::default() }).await.unwrap().len(), 2);
+}
+
+#[cfg(feature = "redis")]
+#[tokio::test]
+#[ignore = "needs a Redis server in ZKP_TEST_REDIS_URL"]
+async fn test_redis_sessions_shared_between_replicas() {
+    use zkp_server::RedisStore;
+
+    let url = std::env::var("ZKP_TEST_REDIS_URL").expect("ZKP_TEST_REDIS_URL is not set");
+    let prefix = format!("zkp-test-{}:", ZKP::generate_random_string(8)); // 每次运行使用新的键前缀
+    let users = Arc::new(MemoryStore::default()); // 用户记录在两个副本之间共享
+
+    // 两个副本的挑战和会话保存在同一个 Redis 中
+    let mut clients = Vec::new();
+    for _ in 0..2 {
+        let sessions = Arc::new(RedisStore::connect(&url, &prefix).await.unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let service = AuthServer::new(AuthImpl::with_stores(ServerConfig::default(), users.clone(), sessions));
+        tokio::spawn(Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)));
+        clients.push(AuthClient::connect(url).await.unwrap());
+    }
+
+    // 在第一个副本请求挑战，在第二个副本应答；同一个认证 ID 不能再应答一次
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context("alice"));
+    let request = RegisterRequest {
+        user: "alice".to_string(),
+        y1: proof.y1.to_bytes_be(),
+        y2: proof.y2.to_bytes_be(),
+        proof_c: proof.c.to_bytes_be(),
+        proof_s: proof.s.to_bytes_be(),
+        ..Default::default()
+    };
+    clients[0].register(request).await.unwrap();
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+    let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+    let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() };
+    let challenge = clients[0].create_authentication_challenge(request).await.unwrap().into_inner();
+    let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be();
+    let request = UpdateProfileRequest { auth_id: challenge.auth_id, s, display_name: Some("Alice".to_string()), contact: None, totp_code: String::new() };
+    clients[1].update_profile(request.clone()).await.unwrap();
+    assert_eq!(clients[0].update_profile(request).await.unwrap_err().code(), Code::NotFound);
+
+    // 在第二个副本登录，会话在第一个副本上有效，TTL 不晚于会话的过期时间
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+    let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+    let request = AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() };
+    let challenge = clients[1].create_authentication_challenge(request).await.unwrap().into_inner();
+    let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be();
+    let request = AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() };
+    let session = clients[1].verify_authentication(request).await.unwrap().into_inner();
+    let response = clients[0].validate_session(ValidateSessionRequest { session_id: session.session_id.clone(), ..Default::default() }).await.unwrap().into_inner();
+    assert!(response.valid);
+    let mut conn = redis::Client::open(url.as_str()).unwrap().get_multiplexed_async_connection().await.unwrap();
+    let ttl: i64 = redis::cmd("TTL").arg(format!("{}session:{}", prefix, session.session_id)).query_async(&mut conn).await.unwrap();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    assert!(ttl > 0 && ttl as u64 <= session.expires_at - now + 1);
+}
+
+#[cfg(feature = "seeded-rng")]
+#[tokio::test]
+async fn test_seeded_server_transcripts_are_reproducible() {
+    use zkp_core::RandomSource;
+
+    // 服务器和客户端各自持有固定种子的随机数来源，互不影响，也不影响同一进程中的其他服务器
+    async fn transcript(seed: u64) -> (Vec<u8>, String, String) {
+        let config = ServerConfig { rng: RandomSource::seeded(seed), ..Default::default() };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(Server::builder().add_service(AuthServer::new(AuthImpl::new(config, MemoryStore::default()))).serve_with_incoming(TcpListenerStream::new(listener)));
+        let mut client = AuthClient::connect(url).await.unwrap();
+
+        let zkp = ZKP::get_constants();
+        let rng = RandomSource::seeded(seed);
+        let x = rng.number_below(&zkp.q);
+        let proof = zkp.prove_non_interactive_with_rng(HashAlgorithm::Sha256, &x, &registration_context("alice"), &rng);
+        let request = RegisterRequest { user: "alice".to_string(), y1: proof.y1.to_bytes_be(), y2: proof.y2.to_bytes_be(), proof_c: proof.c.to_bytes_be(), proof_s: proof.s.to_bytes_be(), ..Default::default() };
+        client.register(request).await.unwrap();
+
+        let k = rng.number_below(&zkp.q);
+        let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+        let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+        let challenge = client.create_authentication_challenge(AuthenticationChallengeRequest { user: "alice".to_string(), r1, r2, ..Default::default() }).await.unwrap().into_inner();
+        let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be();
+        let session = client.verify_authentication(AuthenticationAnswerRequest { auth_id: challenge.auth_id.clone(), s, ..Default::default() }).await.unwrap().into_inner();
+        (challenge.c, challenge.auth_id, session.session_id)
+    }
+
+    let first = transcript(42).await;
+    assert_eq!(transcript(42).await, first);
+    assert_ne!(transcript(43).await, first);
+}
@@ -0,0 +1,160 @@
+// 多实例部署：启动两个 server 进程共享同一个存储，协议的各步交替发给两个进程，确认认证状态不依赖处理请求的进程；
+// 运维的子命令（server user、migrate）也作为另一个进程操作运行中的服务器的存储
+// 需要能被几个进程共享的存储，只在 sqlite 或 postgres feature 下编译
+#![cfg(any(feature = "sqlite", feature = "postgres"))]
+
+use std::net::TcpListener;
+use std::process::{Child, Command, Output, Stdio};
+use std::time::Duration;
+
+use num_bigint::BigUint;
+use tonic::transport::Channel;
+use tonic::Code;
+use zkp_core::{registration_context, ZKP};
+
+use zkp_proto::zkp_auth::auth_client::AuthClient;
+use zkp_proto::zkp_auth::{AuthenticationAnswerRequest, AuthenticationChallengeRequest, GetAuthParametersRequest, LogoutRequest, RegisterRequest, ValidateSessionRequest};
+
+// 运行中的服务器进程，测试结束时结束进程
+struct TestServer {
+    child: Child,
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// 在随机端口上启动使用给定存储的服务器并连接
+async fn start(store: &str) -> (TestServer, AuthClient<Channel>) {
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let child = Command::new(env!("CARGO_BIN_EXE_server"))
+        .env("ZKP_SERVER_ADDR", format!("127.0.0.1:{}", port))
+        .env("ZKP_STORE", store)
+        .env("ZKP_LOG", "warn") // 日志写到标准错误，只保留警告和错误
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("could not start the server");
+    let server = TestServer { child };
+
+    let url = format!("http://127.0.0.1:{}", port);
+    for _ in 0..200 {
+        if let Ok(client) = AuthClient::connect(url.clone()).await {
+            return (server, client);
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("the server did not start");
+}
+
+// 两个进程交替处理注册、挑战、应答、查询和注销
+async fn interleave(store: &str, user: &str) {
+    let (_a, mut a) = start(store).await;
+    let (_b, mut b) = start(store).await;
+    let zkp = ZKP::get_constants();
+
+    // 在 A 注册，B 也看到用户名已被使用
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    let proof = zkp.prove_non_interactive(&x, &registration_context(user));
+    let request = RegisterRequest {
+        user: user.to_string(),
+        y1: proof.y1.to_bytes_be(),
+        y2: proof.y2.to_bytes_be(),
+        proof_c: proof.c.to_bytes_be(),
+        proof_s: proof.s.to_bytes_be(),
+        ..Default::default()
+    };
+    a.register(request.clone()).await.unwrap();
+    assert_eq!(b.register(request).await.unwrap_err().code(), Code::AlreadyExists);
+
+    // 在 B 请求挑战、在 A 应答；同一个挑战不能再在 B 应答一次
+    let k = ZKP::generate_random_number_below(&zkp.q);
+    let r1 = ZKP::exponentiate(&zkp.alpha, &k, &zkp.p).to_bytes_be();
+    let r2 = ZKP::exponentiate(&zkp.beta, &k, &zkp.p).to_bytes_be();
+    let challenge = b.create_authentication_challenge(AuthenticationChallengeRequest { user: user.to_string(), r1, r2, ..Default::default() }).await.unwrap().into_inner();
+    let s = zkp.solve(&k, &BigUint::from_bytes_be(&challenge.c), &x).to_bytes_be();
+    let answer = AuthenticationAnswerRequest { auth_id: challenge.auth_id, s, ..Default::default() };
+    let session_id = a.verify_authentication(answer.clone()).await.unwrap().into_inner().session_id;
+    assert_eq!(b.verify_authentication(answer).await.unwrap_err().code(), Code::NotFound);
+
+    // A 建立的会话在 B 有效，在 B 注销后 A 上也失效
+    let validate = || ValidateSessionRequest { session_id: session_id.clone(), ..Default::default() };
+    let response = b.validate_session(validate()).await.unwrap().into_inner();
+    assert_eq!((response.valid, response.user.as_str()), (true, user));
+    b.logout(LogoutRequest { session_id: session_id.clone() }).await.unwrap();
+    assert!(!a.validate_session(validate()).await.unwrap().into_inner().valid);
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_processes_share_sqlite_file() {
+    let path = std::env::temp_dir().join(format!("zkp-multi-instance-{}.sqlite", ZKP::generate_random_string(8)));
+    interleave(&format!("sqlite:{}", path.display()), "alice").await;
+    let _ = std::fs::remove_file(&path);
+}
+
+// 用给定的存储执行 server 的子命令
+#[cfg(feature = "sqlite")]
+fn run(store: &str, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_server")).env("ZKP_STORE", store).env("ZKP_LOG", "warn").args(args).output().expect("could not run the server")
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_cli_subcommands() {
+    let path = std::env::temp_dir().join(format!("zkp-cli-{}.sqlite", ZKP::generate_random_string(8)));
+    let store = format!("sqlite:{}", path.display());
+    assert!(run(&store, &["migrate"]).status.success());
+    let (_server, mut client) = start(&store).await;
+
+    let zkp = ZKP::get_constants();
+    let x = ZKP::generate_random_number_below(&zkp.q);
+    for user in ["bob", "alice"] {
+        let proof = zkp.prove_non_interactive(&x, &registration_context(user));
+        let request = RegisterRequest {
+            user: user.to_string(),
+            y1: proof.y1.to_bytes_be(),
+            y2: proof.y2.to_bytes_be(),
+            proof_c: proof.c.to_bytes_be(),
+            proof_s: proof.s.to_bytes_be(),
+            display_name: format!("{} (test)", user),
+            ..Default::default()
+        };
+        client.register(request).await.unwrap();
+    }
+
+    // 按用户名顺序列出，每行以制表符分隔
+    let users = |args: &[&str]| String::from_utf8(run(&store, args).stdout).unwrap().lines().map(|line| line.split('\t').next().unwrap().to_string()).collect::<Vec<_>>();
+    assert_eq!(users(&["user", "list"]), ["alice", "bob"]);
+    assert!(String::from_utf8(run(&store, &["user", "list"]).stdout).unwrap().starts_with("alice\t"));
+    assert_eq!(users(&["user", "list", "--after", "alice"]), ["bob"]);
+    assert_eq!(users(&["user", "list", "--limit", "1"]), ["alice"]);
+
+    // 删除后运行中的服务器也找不到该用户；删除不存在的用户是错误
+    assert!(run(&store, &["user", "delete", "alice"]).status.success());
+    let status = client.get_auth_parameters(GetAuthParametersRequest { user: "alice".to_string() }).await.unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+    assert_eq!(users(&["user", "list"]), ["bob"]);
+    let output = run(&store, &["user", "delete", "alice"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("not found"));
+
+    // 生成的参数文件可以作为 group 使用
+    let params = std::env::temp_dir().join(format!("zkp-cli-{}.pem", ZKP::generate_random_string(8)));
+    assert!(run("memory", &["gen-params", "--p-bits", "256", "--q-bits", "64", "--output", params.to_str().unwrap()]).status.success());
+    let generated = ZKP::load_params(&std::fs::read_to_string(&params).unwrap()).unwrap();
+    assert_eq!((generated.p.bits(), generated.q.bits()), (256, 64));
+    assert!(!run("memory", &["user", "list"]).status.success()); // 内存存储没有可以管理的用户
+    let _ = std::fs::remove_file(&params);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[cfg(feature = "postgres")]
+#[tokio::test]
+#[ignore = "needs a PostgreSQL database in ZKP_TEST_DATABASE_URL"]
+async fn test_processes_share_postgres() {
+    let url = std::env::var("ZKP_TEST_DATABASE_URL").expect("ZKP_TEST_DATABASE_URL is not set");
+    interleave(&url, &format!("alice-{}", ZKP::generate_random_string(8))).await; // 数据库在多次运行之间保留，每次使用新的用户名
+}
@@ -0,0 +1,147 @@
+use std::collections::BTreeMap; // 兼容性矩阵的行
+use std::path::{Path, PathBuf}; // 测试向量文件路径
+use std::process::ExitCode; // 有用例失败时以非零状态退出
+
+use clap::Parser; // 命令行参数解析
+use zkp_core::conformance::{self, External, Implementation, Native, VectorFile, Verdict}; // 测试向量和被测试的实现
+
+/// 在本库以及外部实现上运行共享的测试向量，输出兼容性矩阵
+#[derive(Parser)]
+#[command(name = "zkp-conformance")]
+struct Cli {
+    /// 测试向量文件或目录（目录中的所有 .json 文件）
+    #[arg(default_value = "tests/vectors")]
+    vectors: Vec<PathBuf>,
+
+    /// 外部实现，格式为 NAME=COMMAND，命令通过子进程协议通信，可以指定多次
+    #[arg(long = "external", value_name = "NAME=COMMAND", value_parser = parse_external)]
+    externals: Vec<(String, String)>,
+
+    /// 作为子进程协议的服务端运行：从 stdin 读取请求，用本库计算后写入 stdout
+    #[arg(long, conflicts_with = "externals")]
+    serve: bool,
+}
+
+// 解析 NAME=COMMAND
+fn parse_external(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((name, command)) if !name.is_empty() && !command.is_empty() => Ok((name.to_string(), command.to_string())),
+        _ => Err("expected NAME=COMMAND".to_string()),
+    }
+}
+
+// 矩阵中的一格：通过、失败、不支持的用例数
+#[derive(Default, Clone, Copy)]
+struct Cell {
+    passed: usize,
+    failed: usize,
+    unsupported: usize,
+}
+
+impl Cell {
+    fn show(&self) -> String {
+        let total = self.passed + self.failed + self.unsupported;
+        if self.unsupported == total {
+            "n/a".to_string()
+        } else {
+            format!("{}/{}", self.passed, total - self.unsupported)
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if cli.serve {
+        let stdin = std::io::stdin().lock();
+        return match conformance::serve(stdin, std::io::stdout().lock()) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(_) => ExitCode::FAILURE,
+        };
+    }
+
+    let files = match load(&cli.vectors) {
+        Ok(files) => files,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut implementations: Vec<Box<dyn Implementation>> = vec![Box::new(Native)];
+    for (name, command) in &cli.externals {
+        match External::spawn(name, command) {
+            Ok(external) => implementations.push(Box::new(external)),
+            Err(e) => {
+                eprintln!("Error: could not start {}: {}", name, e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    // 行为 "文件 / 用例类型"，列为实现
+    let mut matrix: BTreeMap<String, Vec<Cell>> = BTreeMap::new();
+    let mut failures = Vec::new();
+    for (file_name, file) in &files {
+        for case in &file.cases {
+            let row = matrix.entry(format!("{} / {}", file_name, case.kind)).or_insert_with(|| vec![Cell::default(); implementations.len()]);
+            for (column, implementation) in implementations.iter_mut().enumerate() {
+                match conformance::run_case(implementation.as_mut(), &file.group, case) {
+                    Verdict::Pass => row[column].passed += 1,
+                    Verdict::Unsupported => row[column].unsupported += 1,
+                    Verdict::Fail(reason) => {
+                        row[column].failed += 1;
+                        failures.push(format!("{}: {} / {}: {}", implementation.name(), file_name, case.name, reason));
+                    }
+                }
+            }
+        }
+    }
+
+    let width = matrix.keys().map(String::len).max().unwrap_or(0).max(4);
+    print!("{:width$}", "case", width = width);
+    for implementation in &implementations {
+        print!("  {:>10}", implementation.name());
+    }
+    println!();
+    for (row, cells) in &matrix {
+        print!("{:width$}", row, width = width);
+        for cell in cells {
+            print!("  {:>10}", cell.show());
+        }
+        println!();
+    }
+
+    for failure in &failures {
+        eprintln!("FAIL {}", failure);
+    }
+    if failures.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+// 读取测试向量文件，目录展开为其中的 .json 文件（按文件名排序）
+fn load(paths: &[PathBuf]) -> Result<Vec<(String, VectorFile)>, String> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let entries = std::fs::read_dir(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+            let mut children: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).filter(|p| p.extension().is_some_and(|e| e == "json")).collect();
+            children.sort();
+            files.extend(children);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    files.iter().map(|path| Ok((file_name(path), read(path)?))).collect()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+fn read(path: &Path) -> Result<VectorFile, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| format!("{} is not a test vector file: {}", path.display(), e))
+}
@@ -0,0 +1,110 @@
+use std::path::PathBuf; // 参数文件路径
+use std::process::ExitCode; // 检查失败时以非零状态退出
+
+use clap::{Parser, Subcommand}; // 命令行参数解析
+use zkp_core::ZKP; // 参数集及其文件格式
+
+/// 生成或检查 Chaum-Pedersen 协议使用的群参数
+#[derive(Parser)]
+#[command(name = "zkp-paramgen")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 生成新的 Schnorr 群参数，生成元由 p、q 哈希派生；参数文件（JSON）输出到 stdout
+    Generate {
+        /// p 的位数
+        #[arg(long, default_value_t = 2048)]
+        p_bits: u64,
+        /// q 的位数，必须小于 p 的位数
+        #[arg(long, default_value_t = 256)]
+        q_bits: u64,
+        /// 同时将参数以 PEM 格式写入该文件
+        #[arg(long)]
+        pem: Option<PathBuf>,
+        /// 同时将参数以 OpenSSL DH 参数（X9.42）格式写入该文件，beta 在读取时由 p、q 重新派生
+        #[arg(long)]
+        dhparam: Option<PathBuf>,
+    },
+    /// 检查参数文件（JSON、PEM 或 OpenSSL DH 参数）是否可用
+    Validate {
+        /// 参数文件路径
+        file: PathBuf,
+    },
+    /// 由种子字符串派生 q 阶子群中的生成元，以十六进制输出；同一个种子总是得到同一个生成元
+    Derive {
+        /// 种子，例如 "user:alice"
+        seed: String,
+        /// 参数文件，默认使用内置的 RFC 5114 参数
+        #[arg(long)]
+        params: Option<PathBuf>,
+    },
+    /// 输出内置的 RFC 5114 参数
+    Default {
+        /// 输出 PEM 格式而不是 JSON
+        #[arg(long)]
+        pem: bool,
+    },
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse().command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(command: Command) -> Result<(), String> {
+    match command {
+        Command::Generate { p_bits, q_bits, pem, dhparam } => {
+            if q_bits < 2 || q_bits >= p_bits {
+                return Err(format!("--q-bits ({}) must be at least 2 and smaller than --p-bits ({})", q_bits, p_bits));
+            }
+            eprintln!("Generating a {}-bit group with a {}-bit subgroup, this may take a while", p_bits, q_bits);
+            let zkp = ZKP::generate_params(p_bits, q_bits);
+            zkp.validate_params()?;
+            if let Some(path) = pem {
+                std::fs::write(&path, zkp.to_pem()).map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+            }
+            if let Some(path) = dhparam {
+                std::fs::write(&path, zkp.to_dhparam_pem()).map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+            }
+            print_json(&zkp);
+        }
+        Command::Validate { file } => {
+            let text = std::fs::read_to_string(&file).map_err(|e| format!("could not read {}: {}", file.display(), e))?;
+            let zkp = ZKP::load_params(&text).ok_or_else(|| format!("{} does not contain valid group parameters", file.display()))?;
+            println!("OK: {}-bit p, {}-bit q, params hash {}", zkp.p.bits(), zkp.q.bits(), hex::encode(zkp.params_hash()));
+        }
+        Command::Derive { seed, params } => {
+            let zkp = match params {
+                Some(file) => {
+                    let text = std::fs::read_to_string(&file).map_err(|e| format!("could not read {}: {}", file.display(), e))?;
+                    ZKP::load_params(&text).ok_or_else(|| format!("{} does not contain valid group parameters", file.display()))?
+                }
+                None => ZKP::get_constants(),
+            };
+            println!("{}", zkp_core::derive_generator(&seed, &zkp).to_str_radix(16));
+        }
+        Command::Default { pem } => {
+            let zkp = ZKP::get_constants();
+            if pem {
+                print!("{}", zkp.to_pem());
+            } else {
+                print_json(&zkp);
+            }
+        }
+    }
+    Ok(())
+}
+
+// 以参数文件格式输出
+fn print_json(zkp: &ZKP) {
+    println!("{}", serde_json::to_string_pretty(&zkp.to_params_file()).expect("parameters serialize to JSON"));
+}
@@ -0,0 +1,190 @@
+use std::io::Read; // 从 stdin 读取私钥
+use std::path::{Path, PathBuf}; // 参数文件、私钥文件和证明文件路径
+use std::process::ExitCode; // 失败时以非零状态退出
+
+use clap::{Args, Parser, Subcommand, ValueEnum}; // 命令行参数解析
+use num_bigint::BigUint; // 私钥和公开值
+use zeroize::Zeroizing; // 私钥字节在释放时清零
+use zkp_core::{HashAlgorithm, NonInteractiveProof, ZKP}; // 参数集、非交互式证明和挑战使用的哈希函数
+
+/// 不依赖服务器，离线生成和验证非交互式 Chaum-Pedersen 证明
+#[derive(Parser)]
+#[command(name = "zkp-proof")]
+struct Cli {
+    /// 参数文件（zkp-paramgen 输出的 JSON 或 PEM），默认使用内置的 RFC 5114 参数
+    #[arg(long, global = true)]
+    params: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 为私钥 x 生成证明，写入证明文件
+    Prove {
+        #[command(flatten)]
+        secret: Secret,
+        /// 证明绑定的上下文
+        #[arg(long, default_value = "")]
+        context: String,
+        /// 证明文件路径
+        #[arg(short = 'o', long = "out", default_value = "proof.bin")]
+        out: PathBuf,
+        /// 计算挑战使用的哈希函数：sha256、sha3-256 或 blake3
+        #[arg(long, default_value_t = HashAlgorithm::Sha256)]
+        hash: HashAlgorithm,
+        /// 证明文件的格式：raw（二进制）、hex 或 base64url（文本，可以放进配置文件或 URL）
+        #[arg(long, value_enum, default_value_t = ProofFormat::Raw)]
+        format: ProofFormat,
+    },
+    /// 验证证明文件，失败时以非零状态退出
+    Verify {
+        /// 证明文件路径
+        file: PathBuf,
+        /// 期望的上下文，指定时必须与证明中的上下文一致
+        #[arg(long)]
+        context: Option<String>,
+        /// 期望的公开值 y1（十六进制），指定时必须与证明中的陈述一致
+        #[arg(long)]
+        y1: Option<String>,
+    },
+    /// 输出证明文件中的各个字段（十六进制）
+    Inspect {
+        /// 证明文件路径
+        file: PathBuf,
+    },
+    /// 输出私钥 x 对应的公开值 y1 = alpha^x, y2 = beta^x
+    Public {
+        #[command(flatten)]
+        secret: Secret,
+    },
+}
+
+// 证明文件的格式，读取时自动识别
+#[derive(Clone, Copy, ValueEnum)]
+enum ProofFormat {
+    Raw,
+    Hex,
+    Base64url,
+}
+
+// 私钥的来源：十六进制、文件中的原始字节，都未指定时读取 stdin 的原始字节（去掉末尾换行）
+// 原始字节按大端整数解释，与客户端由密码得到私钥的方式一致
+#[derive(Args)]
+struct Secret {
+    /// 私钥（十六进制）；会出现在进程列表中，只用于调试
+    #[arg(long, conflicts_with = "secret_file")]
+    secret_hex: Option<String>,
+    /// 从文件读取私钥的原始字节
+    #[arg(long)]
+    secret_file: Option<PathBuf>,
+}
+
+impl Secret {
+    fn read(&self) -> Result<BigUint, String> {
+        let bytes = match (&self.secret_hex, &self.secret_file) {
+            (Some(hex), _) => Zeroizing::new(hex::decode(hex).map_err(|e| format!("--secret-hex is not valid hex: {}", e))?),
+            (None, Some(path)) => Zeroizing::new(read_file(path)?),
+            (None, None) => {
+                let mut bytes = Zeroizing::new(Vec::new());
+                std::io::stdin().read_to_end(&mut bytes).map_err(|e| format!("could not read the secret from stdin: {}", e))?;
+                while bytes.last().is_some_and(|b| *b == b'\n' || *b == b'\r') {
+                    bytes.pop();
+                }
+                bytes
+            }
+        };
+        if bytes.is_empty() {
+            return Err("the secret is empty".to_string());
+        }
+        Ok(BigUint::from_bytes_be(&bytes))
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match load_params(cli.params.as_deref()).and_then(|zkp| run(&zkp, cli.command)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(zkp: &ZKP, command: Command) -> Result<(), String> {
+    match command {
+        Command::Prove { secret, context, out, hash, format } => {
+            let proof = zkp.prove_non_interactive_with(hash, &secret.read()?, context.as_bytes());
+            let contents = match format {
+                ProofFormat::Raw => proof.to_bytes(),
+                ProofFormat::Hex => format!("{}\n", proof.to_hex()).into_bytes(),
+                ProofFormat::Base64url => format!("{}\n", proof.to_base64url()).into_bytes(),
+            };
+            std::fs::write(&out, contents).map_err(|e| format!("could not write {}: {}", out.display(), e))?;
+            println!("Proof for context {:?} written to {}", context, out.display());
+        }
+        Command::Verify { file, context, y1 } => {
+            let proof = read_proof(&file)?;
+            if let Some(expected) = context {
+                if expected.as_bytes() != proof.context.as_slice() {
+                    return Err(format!("proof is bound to {:?}, expected {:?}", String::from_utf8_lossy(&proof.context), expected));
+                }
+            }
+            if let Some(expected) = y1 {
+                let expected = BigUint::parse_bytes(expected.as_bytes(), 16).ok_or("--y1 is not valid hex")?;
+                if expected != proof.y1 {
+                    return Err("proof is for a different y1".to_string());
+                }
+            }
+            if !zkp.verify_non_interactive(&proof) {
+                return Err(format!("{} does not verify", file.display()));
+            }
+            println!("OK: proof in {} is valid for context {:?}", file.display(), String::from_utf8_lossy(&proof.context));
+        }
+        Command::Inspect { file } => {
+            let proof = read_proof(&file)?;
+            println!("y1:      {}", proof.y1.to_str_radix(16));
+            println!("y2:      {}", proof.y2.to_str_radix(16));
+            println!("c:       {}", proof.c.to_str_radix(16));
+            println!("s:       {}", proof.s.to_str_radix(16));
+            println!("context: {:?}", String::from_utf8_lossy(&proof.context));
+            println!("hash:    {}", proof.hash);
+            match proof.group.is_empty() {
+                true => println!("group:   (not recorded, legacy format)"),
+                false => println!("group:   {}", hex::encode(&proof.group)),
+            }
+        }
+        Command::Public { secret } => {
+            let x = secret.read()?;
+            println!("y1: {}", ZKP::exponentiate(&zkp.alpha, &x, &zkp.p).to_str_radix(16));
+            println!("y2: {}", ZKP::exponentiate(&zkp.beta, &x, &zkp.p).to_str_radix(16));
+        }
+    }
+    Ok(())
+}
+
+// 读取并检查参数文件，未指定时使用内置参数
+fn load_params(path: Option<&Path>) -> Result<ZKP, String> {
+    let Some(path) = path else {
+        return Ok(ZKP::get_constants());
+    };
+    let text = std::fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+    ZKP::load_params(&text).ok_or_else(|| format!("{} does not contain valid group parameters", path.display()))
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("could not read {}: {}", path.display(), e))
+}
+
+// 依次尝试二进制、十六进制和 base64url 格式
+fn read_proof(path: &Path) -> Result<NonInteractiveProof, String> {
+    let bytes = read_file(path)?;
+    NonInteractiveProof::from_bytes(&bytes)
+        .or_else(|| {
+            let text = std::str::from_utf8(&bytes).ok()?.trim_end();
+            NonInteractiveProof::from_hex(text).or_else(|| NonInteractiveProof::from_base64url(text))
+        })
+        .ok_or_else(|| format!("{} is not a proof file", path.display()))
+}
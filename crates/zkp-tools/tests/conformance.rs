@@ -0,0 +1,35 @@
+// 在本库以及通过子进程协议连接的参考实现（zkp-conformance --serve）上运行仓库根目录 tests/vectors 中的测试向量
+
+use std::path::Path;
+
+use zkp_core::conformance::{run_case, External, Implementation, Native, VectorFile, Verdict};
+
+fn vector_files() -> Vec<VectorFile> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../tests/vectors");
+    let mut paths: Vec<_> = std::fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+    paths.sort();
+    let files: Vec<VectorFile> = paths.iter().map(|path| serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()).collect();
+    assert!(!files.is_empty());
+    files
+}
+
+// 所有用例都必须通过
+fn check(implementation: &mut dyn Implementation) {
+    for file in vector_files() {
+        for case in &file.cases {
+            let verdict = run_case(implementation, &file.group, case);
+            assert_eq!(verdict, Verdict::Pass, "{}: {} / {}", implementation.name(), file.description, case.name);
+        }
+    }
+}
+
+#[test]
+fn test_native_passes_all_vectors() {
+    check(&mut Native);
+}
+
+#[test]
+fn test_subprocess_protocol_passes_all_vectors() {
+    let command = format!("'{}' --serve", env!("CARGO_BIN_EXE_zkp-conformance"));
+    check(&mut External::spawn("reference", &command).unwrap());
+}